@@ -0,0 +1,1477 @@
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::{
+    address_book::{
+        store::{write_atomic, ConflictStore, HostStore},
+        subscription::{destination_hash, is_valid_hostname, parse_entry, HostEntry},
+    },
+    config::AddressBookConfig,
+    error::Error,
+};
+
+use emissary_core::runtime::AddressBook;
+use futures::{channel::oneshot, future::Either};
+use parking_lot::RwLock;
+use reqwest::{
+    header::{
+        HeaderMap, HeaderValue, CONNECTION, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED,
+    },
+    Client, Proxy, StatusCode,
+};
+use schnellru::{ByLength, LruMap};
+use serde::{Deserialize, Serialize};
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    future::Future,
+    path::{Path, PathBuf},
+    pin::Pin,
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+pub use store::Conflict;
+
+mod store;
+mod subscription;
+
+/// Logging target for the file
+const LOG_TARGET: &str = "emissary::address-book";
+
+/// Backoff if downloading the hosts file fails.
+///
+/// The backoff is doubled after each failed download, up to the refresh interval.
+const RETRY_BACKOFF: Duration = Duration::from_secs(30);
+
+/// How often subscriptions are fetched, unless specified in the configuration.
+const DEFAULT_REFRESH_INTERVAL: Duration = Duration::from_secs(12 * 60 * 60);
+
+/// Size for the .i2p -> .b32.i2p hostname cache.
+const HOSTNAME_CACHE_SIZE: u32 = 128u32;
+
+/// Address book.
+///
+/// Hosts are resolved from the private address book first, then from the local address book and
+/// finally from the router address book.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Book {
+    /// Local address book.
+    ///
+    /// Edited by the user and exported as `hosts.txt`.
+    Local,
+
+    /// Private address book.
+    ///
+    /// Edited by the user but never exported.
+    Private,
+
+    /// Router address book.
+    ///
+    /// Populated from subscriptions and not editable by the user.
+    Router,
+}
+
+impl fmt::Display for Book {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Local => write!(f, "local"),
+            Self::Private => write!(f, "private"),
+            Self::Router => write!(f, "router"),
+        }
+    }
+}
+
+impl FromStr for Book {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "local" => Ok(Self::Local),
+            "private" => Ok(Self::Private),
+            "router" => Ok(Self::Router),
+            book => Err(Error::Custom(format!("unknown address book: {book}"))),
+        }
+    }
+}
+
+/// Get current time as seconds since UNIX epoch.
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Fetch state of a subscription.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct SubscriptionState {
+    /// `ETag` of the last response.
+    etag: Option<String>,
+
+    /// When the subscription was last fetched, as seconds since UNIX epoch.
+    last_fetched: Option<u64>,
+
+    /// `Last-Modified` of the last response.
+    last_modified: Option<String>,
+
+    /// Number of consecutive failed downloads.
+    #[serde(skip)]
+    num_failures: u32,
+
+    /// When a failed download is retried, as seconds since UNIX epoch.
+    #[serde(skip)]
+    retry_at: Option<u64>,
+}
+
+impl SubscriptionState {
+    /// Get when the subscription should be fetched next, as seconds since UNIX epoch.
+    fn next_fetch(&self, refresh_interval: Duration) -> u64 {
+        self.retry_at.unwrap_or_else(|| {
+            self.last_fetched
+                .map_or(0, |last_fetched| last_fetched + refresh_interval.as_secs())
+        })
+    }
+
+    /// Mark the subscription as fetched.
+    fn fetched(&mut self, now: u64) {
+        self.last_fetched = Some(now);
+        self.num_failures = 0;
+        self.retry_at = None;
+    }
+
+    /// Mark the download of the subscription as failed.
+    fn failed(&mut self, now: u64, refresh_interval: Duration) {
+        let backoff = RETRY_BACKOFF
+            .saturating_mul(2u32.saturating_pow(self.num_failures))
+            .min(refresh_interval);
+
+        self.num_failures = self.num_failures.saturating_add(1);
+        self.retry_at = Some(now + backoff.as_secs());
+    }
+}
+
+/// Result of a successful download.
+enum Download {
+    /// Subscription has been modified since it was last fetched.
+    Modified {
+        /// Downloaded `hosts.txt`.
+        hosts: String,
+
+        /// `ETag` of the response.
+        etag: Option<String>,
+
+        /// `Last-Modified` of the response.
+        last_modified: Option<String>,
+    },
+
+    /// Subscription has not been modified since it was last fetched.
+    NotModified,
+}
+
+/// Address book.
+#[derive(Clone)]
+pub struct AddressBookManager {
+    /// Cache of recently queried .b32.i2p hostnames, shared with all [`AddressBookHandle`]s.
+    cache: Arc<RwLock<LruMap<String, String>>>,
+
+    /// Conflicting destinations seen in subscriptions.
+    conflicts: ConflictStore,
+
+    /// Path to which the local address book is exported as `hosts.txt`.
+    export_path: &'static Path,
+
+    /// URL from which the primary `hosts.txt` is downloaded from.
+    hosts_url: Option<String>,
+
+    /// Local address book.
+    ///
+    /// Contains hosts added by the user, e.g., via address helpers, and is never overwritten by
+    /// subscriptions.
+    local: HostStore,
+
+    /// Private address book.
+    private: HostStore,
+
+    /// How often subscriptions are fetched.
+    refresh_interval: Duration,
+
+    /// Router address book, populated from subscriptions.
+    router: HostStore,
+
+    /// Path to the file which stores the fetch state of subscriptions.
+    state_path: &'static Path,
+
+    /// Additional subscriptions.
+    subscriptions: Vec<String>,
+}
+
+impl AddressBookManager {
+    /// Create new [`AddressBookManager`].
+    ///
+    /// The address books are loaded from disk into memory.
+    pub fn new(base_path: PathBuf, config: AddressBookConfig) -> Self {
+        let path = |name: &str| -> &'static str {
+            base_path
+                .join("addressbook")
+                .join(name)
+                .to_str()
+                .expect("to succeed")
+                .to_string()
+                .leak()
+        };
+
+        Self {
+            cache: Arc::new(RwLock::new(LruMap::new(ByLength::new(HOSTNAME_CACHE_SIZE)))),
+            conflicts: ConflictStore::new(path("conflicts.toml")),
+            export_path: Path::new(path("hosts.txt")),
+            hosts_url: config.default,
+            local: HostStore::new(path("local")),
+            private: HostStore::new(path("private")),
+            refresh_interval: Self::refresh_interval(config.refresh_interval),
+            router: HostStore::new(path("addresses")),
+            state_path: Path::new(path("subscriptions.toml")),
+            subscriptions: config
+                .subscriptions
+                .map_or_else(Vec::new, |subscriptions| subscriptions),
+        }
+    }
+
+    /// Get refresh interval of subscriptions from the interval given in the configuration.
+    ///
+    /// The configured interval is in hours.
+    fn refresh_interval(hours: Option<u64>) -> Duration {
+        hours.map_or(DEFAULT_REFRESH_INTERVAL, |hours| {
+            Duration::from_secs(hours.max(1) * 60 * 60)
+        })
+    }
+
+    /// Create new [`AddressBookManager`] for the same address book but with a new `config`.
+    pub fn with_config(&self, config: AddressBookConfig) -> Self {
+        Self {
+            cache: Arc::clone(&self.cache),
+            conflicts: self.conflicts.clone(),
+            export_path: self.export_path,
+            hosts_url: config.default,
+            local: self.local.clone(),
+            private: self.private.clone(),
+            refresh_interval: Self::refresh_interval(config.refresh_interval),
+            router: self.router.clone(),
+            state_path: self.state_path,
+            subscriptions: config.subscriptions.unwrap_or_default(),
+        }
+    }
+
+    /// Get handle to address book.
+    ///
+    /// The handle implements [`AddressBook`] and can be used to edit the local and private
+    /// address books.
+    pub fn handle(&self) -> AddressBookHandle {
+        AddressBookHandle {
+            cache: Arc::clone(&self.cache),
+            conflicts: self.conflicts.clone(),
+            export_path: self.export_path,
+            local: self.local.clone(),
+            private: self.private.clone(),
+            router: self.router.clone(),
+        }
+    }
+
+    /// Attempt to download `hosts.txt` from `url`.
+    ///
+    /// If `url` has been fetched before, the request is made conditional on `hosts.txt` having
+    /// been modified since.
+    async fn download(client: &Client, url: &str, state: &SubscriptionState) -> Option<Download> {
+        let mut headers = HeaderMap::from_iter([(CONNECTION, HeaderValue::from_static("close"))]);
+
+        if let Some(etag) = state.etag.as_deref().and_then(|etag| etag.parse().ok()) {
+            headers.insert(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = state
+            .last_modified
+            .as_deref()
+            .and_then(|last_modified| last_modified.parse().ok())
+        {
+            headers.insert(IF_MODIFIED_SINCE, last_modified);
+        }
+
+        let response = match client.get(url.to_string()).headers(headers).send().await {
+            Err(error) => {
+                tracing::debug!(
+                    target: LOG_TARGET,
+                    ?url,
+                    ?error,
+                    "failed to fetch hosts.txt"
+                );
+                return None;
+            }
+            Ok(response) => response,
+        };
+
+        if response.status() == StatusCode::NOT_MODIFIED {
+            return Some(Download::NotModified);
+        }
+
+        if !response.status().is_success() {
+            tracing::debug!(
+                target: LOG_TARGET,
+                ?url,
+                status = ?response.status(),
+                "request to address book server failed",
+            );
+            return None;
+        }
+
+        let header = |name| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value: &HeaderValue| value.to_str().ok())
+                .map(ToString::to_string)
+        };
+        let (etag, last_modified) = (header(ETAG), header(LAST_MODIFIED));
+
+        match response.bytes().await {
+            Ok(response) => match std::str::from_utf8(&response) {
+                Ok(response) => Some(Download::Modified {
+                    hosts: response.to_owned(),
+                    etag,
+                    last_modified,
+                }),
+                Err(error) => {
+                    tracing::debug!(
+                        target: LOG_TARGET,
+                        ?url,
+                        ?error,
+                        "failed to convert `hosts.txt` to utf-8",
+                    );
+                    None
+                }
+            },
+            Err(error) => {
+                tracing::debug!(
+                    target: LOG_TARGET,
+                    ?url,
+                    ?error,
+                    "failed to get response from address book server"
+                );
+                None
+            }
+        }
+    }
+
+    /// Parse `hosts` downloaded from `source` and merge them into the router address book.
+    ///
+    /// Hosts already present in the address book are only changed by entries signed by the
+    /// destination they currently resolve to. Other destinations seen for existing hosts are
+    /// recorded as conflicts.
+    async fn parse_and_merge(&self, source: &str, hosts: String) {
+        let now = now();
+        let conflict = |host: &str, destination: &str, conflicting: &str| Conflict {
+            host: host.to_string(),
+            destination: destination.to_string(),
+            conflicting: conflicting.to_string(),
+            source: source.to_string(),
+            seen: now,
+        };
+
+        // verifying the signatures of a large `hosts.txt` is expensive so the entries are parsed
+        // on a blocking thread and only merged while holding the address book lock
+        let entries = tokio::task::spawn_blocking(move || {
+            hosts
+                .lines()
+                .filter_map(|line| match parse_entry(line) {
+                    Ok(entry) => entry,
+                    Err(error) => {
+                        tracing::debug!(
+                            target: LOG_TARGET,
+                            ?error,
+                            "ignoring invalid subscription entry",
+                        );
+                        None
+                    }
+                })
+                .collect::<Vec<_>>()
+        })
+        .await;
+
+        let entries = match entries {
+            Ok(entries) => entries,
+            Err(error) => {
+                tracing::error!(
+                    target: LOG_TARGET,
+                    %source,
+                    ?error,
+                    "failed to parse hosts",
+                );
+                return;
+            }
+        };
+
+        let (num_hosts, conflicts, changed) = self.router.modify(|addresses| {
+            let mut conflicts = Vec::new();
+            let mut changed = Vec::new();
+
+            for entry in entries {
+                match entry {
+                    // first-come, first-served: existing hosts can only be changed with entries
+                    // signed by the destination the host currently resolves to
+                    HostEntry::AddHost { name, destination } => match addresses.get(&name) {
+                        Some(current) if current != &destination => {
+                            conflicts.push(conflict(&name, current, &destination));
+                        }
+                        Some(_) => {}
+                        None => {
+                            addresses.insert(name, destination);
+                        }
+                    },
+                    HostEntry::AddDest {
+                        name,
+                        destination,
+                        old_destination,
+                    } => match addresses.get(&name) {
+                        Some(current) if current != &destination && current != &old_destination => {
+                            conflicts.push(conflict(&name, current, &destination));
+                        }
+                        Some(_) => {}
+                        None => {
+                            addresses.insert(name, destination);
+                        }
+                    },
+                    HostEntry::ChangeDest {
+                        name,
+                        destination,
+                        old_destination,
+                    } => match addresses.get(&name) {
+                        Some(current) if current != &old_destination =>
+                            if current != &destination {
+                                conflicts.push(conflict(&name, current, &destination));
+                            },
+                        _ => {
+                            addresses.insert(name.clone(), destination);
+                            changed.push(name);
+                        }
+                    },
+                    HostEntry::Remove { name, destination } => match addresses.get(&name) {
+                        Some(current) if current == &destination => {
+                            addresses.remove(&name);
+                            changed.push(name);
+                        }
+                        _ => tracing::debug!(
+                            target: LOG_TARGET,
+                            %name,
+                            "ignoring removal signed by a different destination",
+                        ),
+                    },
+                }
+            }
+
+            (addresses.len(), conflicts, changed)
+        });
+
+        // hosts whose destination changed or which were removed must be resolved again
+        {
+            let mut cache = self.cache.write();
+
+            for host in &changed {
+                cache.remove(host);
+            }
+        }
+
+        tracing::debug!(
+            target: LOG_TARGET,
+            %source,
+            ?num_hosts,
+            num_conflicts = ?conflicts.len(),
+            "hosts merged into address book",
+        );
+
+        if let Err(error) = self.router.persist().await {
+            tracing::error!(
+                target: LOG_TARGET,
+                ?error,
+                "failed to write to address book",
+            );
+        }
+
+        let num_recorded = conflicts
+            .into_iter()
+            .map(|conflict| self.conflicts.record(conflict))
+            .filter(|recorded| *recorded)
+            .count();
+
+        if num_recorded > 0 {
+            if let Err(error) = self.conflicts.persist().await {
+                tracing::warn!(
+                    target: LOG_TARGET,
+                    ?error,
+                    "failed to write address book conflicts",
+                );
+            }
+        }
+    }
+
+    /// Read fetch state of subscriptions from disk.
+    async fn read_state(&self) -> BTreeMap<String, SubscriptionState> {
+        match tokio::fs::read_to_string(self.state_path).await {
+            Ok(contents) => toml::from_str(&contents).unwrap_or_else(|error| {
+                tracing::warn!(
+                    target: LOG_TARGET,
+                    ?error,
+                    "invalid subscription state, fetching all subscriptions",
+                );
+                BTreeMap::new()
+            }),
+            Err(_) => BTreeMap::new(),
+        }
+    }
+
+    /// Write fetch state of subscriptions to disk.
+    async fn write_state(&self, state: &BTreeMap<String, SubscriptionState>) {
+        let contents = toml::to_string(state).expect("to succeed");
+
+        if let Err(error) = write_atomic(self.state_path, contents).await {
+            tracing::warn!(
+                target: LOG_TARGET,
+                ?error,
+                "failed to write subscription state",
+            );
+        }
+    }
+
+    /// Fetch subscriptions which are due and merge them into the address book.
+    ///
+    /// If a signal is received from `shutdown_rx` while a subscription is being downloaded, the
+    /// download is abandoned, the fetch state is written to disk and `None` is returned.
+    ///
+    /// Otherwise returns when the next subscription is due, as seconds since UNIX epoch.
+    async fn refresh(
+        &self,
+        client: &Client,
+        urls: &[String],
+        state: &mut BTreeMap<String, SubscriptionState>,
+        shutdown_rx: &mut oneshot::Receiver<()>,
+    ) -> Option<u64> {
+        for url in urls {
+            let now = now();
+            let subscription = state.entry(url.clone()).or_default();
+
+            if subscription.next_fetch(self.refresh_interval) > now {
+                continue;
+            }
+
+            let download = tokio::select! {
+                download = Self::download(client, url, subscription) => download,
+                _ = &mut *shutdown_rx => {
+                    tracing::debug!(
+                        target: LOG_TARGET,
+                        %url,
+                        "shutdown requested, abandoning download",
+                    );
+
+                    self.write_state(state).await;
+                    return None;
+                }
+            };
+
+            match download {
+                Some(Download::Modified {
+                    hosts,
+                    etag,
+                    last_modified,
+                }) => {
+                    tracing::info!(
+                        target: LOG_TARGET,
+                        %url,
+                        "hosts.txt downloaded",
+                    );
+
+                    self.parse_and_merge(url, hosts).await;
+                    subscription.etag = etag;
+                    subscription.last_modified = last_modified;
+                    subscription.fetched(now);
+                }
+                Some(Download::NotModified) => {
+                    tracing::debug!(
+                        target: LOG_TARGET,
+                        %url,
+                        "hosts.txt not modified",
+                    );
+
+                    subscription.fetched(now);
+                }
+                None => subscription.failed(now, self.refresh_interval),
+            }
+        }
+
+        // forget the state of removed subscriptions
+        state.retain(|url, _| urls.contains(url));
+        self.write_state(state).await;
+
+        Some(
+            state
+                .values()
+                .map(|subscription| subscription.next_fetch(self.refresh_interval))
+                .min()
+                .unwrap_or_else(|| now() + self.refresh_interval.as_secs()),
+        )
+    }
+
+    /// Start event loop for [`AddressBookManager`].
+    ///
+    /// Before the address book subscription download starts, [`AddressBook`] waits on
+    /// `http_proxy_ready_rx` which the HTTP proxy sends a signal to once it's ready.
+    ///
+    /// Subscriptions are fetched periodically, using the state stored from previous fetches to
+    /// skip subscriptions which are not yet due and those which have not been modified.
+    ///
+    /// The event loop exits when a signal is received from `shutdown_rx`. A download in progress
+    /// is abandoned but merging a downloaded subscription and writing the address books to disk
+    /// are completed before exiting.
+    pub async fn run(
+        self,
+        http_port: u16,
+        http_host: String,
+        http_proxy_ready_rx: oneshot::Receiver<()>,
+        mut shutdown_rx: oneshot::Receiver<()>,
+    ) {
+        let Some(hosts_url) = &self.hosts_url else {
+            tracing::debug!(
+                target: LOG_TARGET,
+                "address book download disabled",
+            );
+            return;
+        };
+
+        tokio::select! {
+            result = http_proxy_ready_rx => if let Err(error) = result {
+                tracing::error!(
+                    target: LOG_TARGET,
+                    ?error,
+                    "http proxy failed to start, cannot start address book",
+                );
+            },
+            _ = &mut shutdown_rx => return,
+        }
+
+        tracing::info!(
+            target: LOG_TARGET,
+            ?http_port,
+            ?http_host,
+            ?hosts_url,
+            subscriptions = ?self.subscriptions,
+            refresh_interval = ?self.refresh_interval,
+            "create address book",
+        );
+
+        let client = Client::builder()
+            .proxy(Proxy::http(format!("http://{http_host}:{http_port}")).expect("to succeed"))
+            .http1_title_case_headers()
+            .build()
+            .expect("to succeed");
+
+        let urls = std::iter::once(hosts_url.clone())
+            .chain(self.subscriptions.iter().cloned())
+            .collect::<Vec<_>>();
+        let mut state = self.read_state().await;
+
+        loop {
+            let Some(next_fetch) = self.refresh(&client, &urls, &mut state, &mut shutdown_rx).await
+            else {
+                tracing::debug!(
+                    target: LOG_TARGET,
+                    "shutting down address book",
+                );
+                return;
+            };
+            let now = now();
+
+            tokio::select! {
+                _ = tokio::time::sleep(Duration::from_secs(next_fetch.saturating_sub(now).max(1))) => {}
+                _ = &mut shutdown_rx => {
+                    tracing::debug!(
+                        target: LOG_TARGET,
+                        "shutting down address book",
+                    );
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// Address book handle.
+#[derive(Clone)]
+pub struct AddressBookHandle {
+    /// Cache of recently queried .b32.i2p hostnames.
+    cache: Arc<RwLock<LruMap<String, String>>>,
+
+    /// Conflicting destinations seen in subscriptions.
+    conflicts: ConflictStore,
+
+    /// Path to which the local address book is exported as `hosts.txt`.
+    export_path: &'static Path,
+
+    /// Local address book.
+    local: HostStore,
+
+    /// Private address book.
+    private: HostStore,
+
+    /// Router address book.
+    router: HostStore,
+}
+
+impl AddressBookHandle {
+    /// Attempt to resolve `host` into a base64 destination.
+    ///
+    /// Private address book is searched first, then the local address book and finally the router
+    /// address book, populated from subscriptions.
+    fn resolve(&self, host: &str) -> Option<String> {
+        self.private
+            .get(host)
+            .or_else(|| self.local.get(host))
+            .or_else(|| self.router.get(host))
+    }
+
+    /// Get store of the user-editable `book`.
+    fn editable(&self, book: Book) -> crate::Result<&HostStore> {
+        match book {
+            Book::Local => Ok(&self.local),
+            Book::Private => Ok(&self.private),
+            Book::Router => Err(Error::Custom(
+                "router address book cannot be edited".to_string(),
+            )),
+        }
+    }
+
+    /// Write `book` to disk and, if it's the local address book, export it as `hosts.txt`.
+    async fn persist(&self, book: Book) -> crate::Result<()> {
+        self.editable(book)?.persist().await?;
+
+        if book == Book::Local {
+            write_atomic(self.export_path, self.export()).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Add `host` with base64 `destination` to the local address book.
+    ///
+    /// If `host` already exists in the local address book, its destination is replaced.
+    pub async fn add(&self, host: &str, destination: &str) -> crate::Result<()> {
+        self.add_to(Book::Local, host, destination).await
+    }
+
+    /// Add `host` with base64 `destination` to `book`.
+    ///
+    /// Hostnames are stored in lowercase like hosts of subscriptions. If `host` already exists in
+    /// `book`, its destination is replaced.
+    pub async fn add_to(&self, book: Book, host: &str, destination: &str) -> crate::Result<()> {
+        let store = self.editable(book)?;
+        let hostname = destination_hash(destination).ok_or(Error::InvalidData)?;
+        let host = host.to_ascii_lowercase();
+        let host = host.as_str();
+
+        if !is_valid_hostname(host) {
+            return Err(Error::Custom(format!("invalid hostname: {host}")));
+        }
+
+        store.insert(host.to_string(), destination.to_string());
+        self.persist(book).await?;
+
+        tracing::info!(
+            target: LOG_TARGET,
+            %book,
+            %host,
+            %hostname,
+            "host added to address book",
+        );
+
+        // the host may still resolve to another destination if it's in the private address book
+        match self.resolve(host).is_some_and(|resolved| resolved == destination) {
+            true => self.cache.write().insert(host.to_string(), hostname),
+            false => self.cache.write().remove(host).is_some(),
+        };
+
+        Ok(())
+    }
+
+    /// Remove `host` from `book`.
+    ///
+    /// Returns `false` if `host` doesn't exist in `book`.
+    #[cfg_attr(not(feature = "web-ui"), allow(unused))]
+    pub async fn remove(&self, book: Book, host: &str) -> crate::Result<bool> {
+        let host = host.to_ascii_lowercase();
+        let host = host.as_str();
+
+        if self.editable(book)?.remove(host).is_none() {
+            return Ok(false);
+        }
+        self.persist(book).await?;
+
+        tracing::info!(
+            target: LOG_TARGET,
+            %book,
+            %host,
+            "host removed from address book",
+        );
+
+        self.cache.write().remove(host);
+
+        Ok(true)
+    }
+
+    /// Rename `host` of `book` to `new_host`.
+    ///
+    /// Returns `false` if `host` doesn't exist in `book`.
+    #[cfg_attr(not(feature = "web-ui"), allow(unused))]
+    pub async fn rename(&self, book: Book, host: &str, new_host: &str) -> crate::Result<bool> {
+        let store = self.editable(book)?;
+        let (host, new_host) = (host.to_ascii_lowercase(), new_host.to_ascii_lowercase());
+        let (host, new_host) = (host.as_str(), new_host.as_str());
+
+        if !is_valid_hostname(new_host) {
+            return Err(Error::Custom(format!("invalid hostname: {new_host}")));
+        }
+
+        let renamed = store.modify(|hosts| {
+            if hosts.contains_key(new_host) {
+                return Err(Error::Custom(format!("host already exists: {new_host}")));
+            }
+
+            Ok(hosts
+                .remove(host)
+                .map(|destination| hosts.insert(new_host.to_string(), destination))
+                .is_some())
+        })?;
+
+        if !renamed {
+            return Ok(false);
+        }
+        self.persist(book).await?;
+
+        tracing::info!(
+            target: LOG_TARGET,
+            %book,
+            %host,
+            %new_host,
+            "host renamed",
+        );
+
+        let mut cache = self.cache.write();
+        cache.remove(host);
+        cache.remove(new_host);
+
+        Ok(true)
+    }
+
+    /// Get `(host, destination)` pairs of `book`, sorted by hostname.
+    #[cfg_attr(not(feature = "web-ui"), allow(unused))]
+    pub fn hosts(&self, book: Book) -> Vec<(String, String)> {
+        match book {
+            Book::Local => self.local.hosts(),
+            Book::Private => self.private.hosts(),
+            Book::Router => self.router.hosts(),
+        }
+    }
+
+    /// Get conflicting destinations seen in subscriptions, from oldest to newest.
+    #[cfg_attr(not(feature = "web-ui"), allow(unused))]
+    pub fn conflicts(&self) -> Vec<Conflict> {
+        self.conflicts.conflicts()
+    }
+
+    /// Export the local address book in `hosts.txt` format.
+    ///
+    /// The export is also written to `addressbook/hosts.txt` whenever the local address book is
+    /// modified so it can be served from an eepsite as a subscription.
+    pub fn export(&self) -> String {
+        self.local
+            .hosts()
+            .into_iter()
+            .fold(String::new(), |mut out, (host, destination)| {
+                out.push_str(&host);
+                out.push('=');
+                out.push_str(&destination);
+                out.push('\n');
+                out
+            })
+    }
+}
+
+impl AddressBook for AddressBookHandle {
+    fn resolve_b64(&self, host: String) -> Pin<Box<dyn Future<Output = Option<String>> + Send>> {
+        let destination = self.resolve(&host);
+
+        Box::pin(async move { destination })
+    }
+
+    fn resolve_b32(
+        &self,
+        host: String,
+    ) -> Either<String, Pin<Box<dyn Future<Output = Option<String>> + Send>>> {
+        let mut inner = self.cache.write();
+
+        match inner.get(&host) {
+            Some(host) => Either::Left(host.clone()),
+            None => {
+                let cache = Arc::clone(&self.cache);
+                let destination = self.resolve(&host);
+
+                Either::Right(Box::pin(async move {
+                    destination.as_deref().and_then(destination_hash).inspect(|resolved| {
+                        cache.write().insert(host, resolved.clone());
+                    })
+                }))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use emissary_core::primitives::Destination;
+    use std::collections::HashMap;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn save_only_destination() {
+        let dir = tempdir().unwrap();
+        let address_book = AddressBookManager::new(
+            dir.keep(),
+            AddressBookConfig {
+                default: Some(String::from("url")),
+                subscriptions: None,
+                refresh_interval: None,
+            },
+        );
+
+        let hosts = "tracker2.postman.i2p=lnQ6yoBTxQuQU8EQ1FlF395ITIQF-HGJxUeFvzETLFnoczNjQvKDbtSB7aHhn853zjVXrJBgwlB9sO57KakBDaJ50lUZgVPhjlI19TgJ-CxyHhHSCeKx5JzURdEW-ucdONMynr-b2zwhsx8VQCJwCEkARvt21YkOyQDaB9IdV8aTAmP~PUJQxRwceaTMn96FcVenwdXqleE16fI8CVFOV18jbJKrhTOYpTtcZKV4l1wNYBDwKgwPx5c0kcrRzFyw5~bjuAKO~GJ5dR7BQsL7AwBoQUS4k1lwoYrG1kOIBeDD3XF8BWb6K3GOOoyjc1umYKpur3G~FxBuqtHAsDRICrsRuil8qK~whOvj8uNTv~ohZnTZHxTLgi~sDyo98BwJ-4Y4NMSuF4GLzcgLypcR1D1WY2tDqMKRYFVyLE~MTPVjRRgXfcKolykQ666~Go~A~~CNV4qc~zlO6F4bsUhVZDU7WJ7mxCAwqaMiJsL-NgIkb~SMHNxIzaE~oy0agHJMBQAEAAcAAA==#!oldsig=i02RMv3Hy86NGhVo2O3byIf6xXqWrzrRibSabe5dmNfRRQPZO9L25A==#date=1598641102#action=adddest#sig=cB-mY~sp1uuEmcQJqremV1D6EDWCe3IwPv4lBiGAXgKRYc5MLBBzYvJXtXmOawpfLKeNM~v5fWlXYsDfKf5nDA==#olddest=lnQ6yoBTxQuQU8EQ1FlF395ITIQF-HGJxUeFvzETLFnoczNjQvKDbtSB7aHhn853zjVXrJBgwlB9sO57KakBDaJ50lUZgVPhjlI19TgJ-CxyHhHSCeKx5JzURdEW-ucdONMynr-b2zwhsx8VQCJwCEkARvt21YkOyQDaB9IdV8aTAmP~PUJQxRwceaTMn96FcVenwdXqleE16fI8CVFOV18jbJKrhTOYpTtcZKV4l1wNYBDwKgwPx5c0kcrRzFyw5~bjuAKO~GJ5dR7BQsL7AwBoQUS4k1lwoYrG1kOIBeDD3XF8BWb6K3GOOoyjc1umYKpur3G~FxBuqtHAsDRICkEbKUqJ9mPYQlTSujhNxiRIW-oLwMtvayCFci99oX8MvazPS7~97x0Gsm-onEK1Td9nBdmq30OqDxpRtXBimbzkLbR1IKObbg9HvrKs3L-kSyGwTUmHG9rSQSoZEvFMA-S0EXO~o4g21q1oikmxPMhkeVwQ22VHB0-LZJfmLr4SAAAA\npsi.i2p=a11l91etedRW5Kl2GhdDI9qiRBbDRAQY6TWJb8KlSc0P9WUrEviABAAltqDU1DFJrRhMAZg5i6rWGszkJrF-pWLQK9JOH33l4~mQjB8Hkt83l9qnNJPUlGlh9yIfBY40CQ0Ermy8gzjHLayUpypDJFv2V6rHLwxAQeaXJu8YXbyvCucEu9i6HVO49akXW9YSxcZEqxK04wZnjBqhHGlVbehleMqTx9nkd0pUpBZz~vIaG9matUSHinopEo6Wegml9FEz~FEaQpPknKuMAGGSNFVJb0NtaOQSAocAOg1nLKh80v232Y8sJOHG63asSJoBa6bGwjIHftsqD~lEmVV4NkgNPybmvsD1SCbMQ2ExaCXFPVQV-yJhIAPN9MRVT9cSBT2GCq-vpMwdJ5Nf0iPR3M-Ak961JUwWXPYTL79toXCgxDX2~nZ5QFRV490YNnfB7LQu10G89wG8lzS9GWf2i-nk~~ez0Lq0dH7qQokFXdUkPc7bvSrxqkytrbd-h8O8AAAA\nzerobin.i2p=Jf64hlpW8ILKZGDe61ljHU5wzmUYwN2klOyhM2iR-8VkUEVgDZRuaToRlXIFW4k5J1ccTzGzMxR518BkCAE3jCFIyrbF0MjQDuXO5cwmqfBFWrIv72xgKDizu3HytE4vOF2M730rv8epSNPAJg6OpyXkf5UQW96kgL8SWcxWdTbKU-O8IpE3O01Oc6j0fp1E4wVOci7qIL8UEloNN~mulgka69MkR0uEtXWOXd6wvBjLNrZgdZi7XtT4QlDjx13jr7RGpZBJAUkk~8gLqgJwoUYhbfM7x564PIn3IlMXHK5AKRVxAbCQ5GkS8KdkvNL7FsQ~EiElGzZId4wenraHMHL0destUDmuwGdHKA7YdtovXD~OnaBvIbl36iuIduZnGKPEBD31hVLdJuVId9RND7lQy5BZJHQss5HSxMWTszAnWJDwmxqzMHHCiL6BMpZnkz8znwPDSkUwEs3P6-ba7mDKKt8EPCG0nM6l~BvPl2OKQIBhXIxJLOOavGyqmmYmAAAA\nzzz.i2p=GKapJ8koUcBj~jmQzHsTYxDg2tpfWj0xjQTzd8BhfC9c3OS5fwPBNajgF-eOD6eCjFTqTlorlh7Hnd8kXj1qblUGXT-tDoR9~YV8dmXl51cJn9MVTRrEqRWSJVXbUUz9t5Po6Xa247Vr0sJn27R4KoKP8QVj1GuH6dB3b6wTPbOamC3dkO18vkQkfZWUdRMDXk0d8AdjB0E0864nOT~J9Fpnd2pQE5uoFT6P0DqtQR2jsFvf9ME61aqLvKPPWpkgdn4z6Zkm-NJOcDz2Nv8Si7hli94E9SghMYRsdjU-knObKvxiagn84FIwcOpepxuG~kFXdD5NfsH0v6Uri3usE3XWD7Pw6P8qVYF39jUIq4OiNMwPnNYzy2N4mDMQdsdHO3LUVh~DEppOy9AAmEoHDjjJxt2BFBbGxfdpZCpENkwvmZeYUyNCCzASqTOOlNzdpne8cuesn3NDXIpNnqEE6Oe5Qm5YOJykrX~Vx~cFFT3QzDGkIjjxlFBsjUJyYkFjBQAEAAcAAA==#!action=adddest#date=1490103520#olddest=GKapJ8koUcBj~jmQzHsTYxDg2tpfWj0xjQTzd8BhfC9c3OS5fwPBNajgF-eOD6eCjFTqTlorlh7Hnd8kXj1qblUGXT-tDoR9~YV8dmXl51cJn9MVTRrEqRWSJVXbUUz9t5Po6Xa247Vr0sJn27R4KoKP8QVj1GuH6dB3b6wTPbOamC3dkO18vkQkfZWUdRMDXk0d8AdjB0E0864nOT~J9Fpnd2pQE5uoFT6P0DqtQR2jsFvf9ME61aqLvKPPWpkgdn4z6Zkm-NJOcDz2Nv8Si7hli94E9SghMYRsdjU-knObKvxiagn84FIwcOpepxuG~kFXdD5NfsH0v6Uri3usE3uSzpWS0EHmrlfoLr5uGGd9ZHwwCIcgfOATaPRMUEQxiK9q48PS0V3EXXO4-YLT0vIfk4xO~XqZpn8~PW1kFe2mQMHd7oO89yCk-3yizRG3UyFtI7-mO~eCI6-m1spYoigStgoupnC3G85gJkqEjMm49gUjbhfWKWI-6NwTj0ZnAAAA#oldsig=MbSvc9wsxSm37B65rUC~BCZzFsIJe0-CXCH8n97ZaMMizNUjeytgBQ==#sig=R2wREo~02liJmU4UGfVZr88XFMiHdYDXVfS~HtyxFxwYG~2o1guP~RocqmHBCE6yPg1Cm8m336d~jqijAVJzBA==".to_string();
+
+        address_book.parse_and_merge("url", hosts).await;
+        let addresses = address_book.router.hosts().into_iter().collect::<HashMap<_, _>>();
+
+        assert_eq!(addresses.get(&String::from("tracker2.postman.i2p")), Some(&String::from("lnQ6yoBTxQuQU8EQ1FlF395ITIQF-HGJxUeFvzETLFnoczNjQvKDbtSB7aHhn853zjVXrJBgwlB9sO57KakBDaJ50lUZgVPhjlI19TgJ-CxyHhHSCeKx5JzURdEW-ucdONMynr-b2zwhsx8VQCJwCEkARvt21YkOyQDaB9IdV8aTAmP~PUJQxRwceaTMn96FcVenwdXqleE16fI8CVFOV18jbJKrhTOYpTtcZKV4l1wNYBDwKgwPx5c0kcrRzFyw5~bjuAKO~GJ5dR7BQsL7AwBoQUS4k1lwoYrG1kOIBeDD3XF8BWb6K3GOOoyjc1umYKpur3G~FxBuqtHAsDRICrsRuil8qK~whOvj8uNTv~ohZnTZHxTLgi~sDyo98BwJ-4Y4NMSuF4GLzcgLypcR1D1WY2tDqMKRYFVyLE~MTPVjRRgXfcKolykQ666~Go~A~~CNV4qc~zlO6F4bsUhVZDU7WJ7mxCAwqaMiJsL-NgIkb~SMHNxIzaE~oy0agHJMBQAEAAcAAA==")));
+
+        assert_eq!(addresses.get(&String::from("psi.i2p")), Some(&String::from("a11l91etedRW5Kl2GhdDI9qiRBbDRAQY6TWJb8KlSc0P9WUrEviABAAltqDU1DFJrRhMAZg5i6rWGszkJrF-pWLQK9JOH33l4~mQjB8Hkt83l9qnNJPUlGlh9yIfBY40CQ0Ermy8gzjHLayUpypDJFv2V6rHLwxAQeaXJu8YXbyvCucEu9i6HVO49akXW9YSxcZEqxK04wZnjBqhHGlVbehleMqTx9nkd0pUpBZz~vIaG9matUSHinopEo6Wegml9FEz~FEaQpPknKuMAGGSNFVJb0NtaOQSAocAOg1nLKh80v232Y8sJOHG63asSJoBa6bGwjIHftsqD~lEmVV4NkgNPybmvsD1SCbMQ2ExaCXFPVQV-yJhIAPN9MRVT9cSBT2GCq-vpMwdJ5Nf0iPR3M-Ak961JUwWXPYTL79toXCgxDX2~nZ5QFRV490YNnfB7LQu10G89wG8lzS9GWf2i-nk~~ez0Lq0dH7qQokFXdUkPc7bvSrxqkytrbd-h8O8AAAA")));
+
+        assert_eq!(addresses.get(&String::from("zerobin.i2p")), Some(&String::from("Jf64hlpW8ILKZGDe61ljHU5wzmUYwN2klOyhM2iR-8VkUEVgDZRuaToRlXIFW4k5J1ccTzGzMxR518BkCAE3jCFIyrbF0MjQDuXO5cwmqfBFWrIv72xgKDizu3HytE4vOF2M730rv8epSNPAJg6OpyXkf5UQW96kgL8SWcxWdTbKU-O8IpE3O01Oc6j0fp1E4wVOci7qIL8UEloNN~mulgka69MkR0uEtXWOXd6wvBjLNrZgdZi7XtT4QlDjx13jr7RGpZBJAUkk~8gLqgJwoUYhbfM7x564PIn3IlMXHK5AKRVxAbCQ5GkS8KdkvNL7FsQ~EiElGzZId4wenraHMHL0destUDmuwGdHKA7YdtovXD~OnaBvIbl36iuIduZnGKPEBD31hVLdJuVId9RND7lQy5BZJHQss5HSxMWTszAnWJDwmxqzMHHCiL6BMpZnkz8znwPDSkUwEs3P6-ba7mDKKt8EPCG0nM6l~BvPl2OKQIBhXIxJLOOavGyqmmYmAAAA")));
+
+        assert_eq!(addresses.get(&String::from("zzz.i2p")), Some(&String::from("GKapJ8koUcBj~jmQzHsTYxDg2tpfWj0xjQTzd8BhfC9c3OS5fwPBNajgF-eOD6eCjFTqTlorlh7Hnd8kXj1qblUGXT-tDoR9~YV8dmXl51cJn9MVTRrEqRWSJVXbUUz9t5Po6Xa247Vr0sJn27R4KoKP8QVj1GuH6dB3b6wTPbOamC3dkO18vkQkfZWUdRMDXk0d8AdjB0E0864nOT~J9Fpnd2pQE5uoFT6P0DqtQR2jsFvf9ME61aqLvKPPWpkgdn4z6Zkm-NJOcDz2Nv8Si7hli94E9SghMYRsdjU-knObKvxiagn84FIwcOpepxuG~kFXdD5NfsH0v6Uri3usE3XWD7Pw6P8qVYF39jUIq4OiNMwPnNYzy2N4mDMQdsdHO3LUVh~DEppOy9AAmEoHDjjJxt2BFBbGxfdpZCpENkwvmZeYUyNCCzASqTOOlNzdpne8cuesn3NDXIpNnqEE6Oe5Qm5YOJykrX~Vx~cFFT3QzDGkIjjxlFBsjUJyYkFjBQAEAAcAAA==")));
+    }
+
+    #[tokio::test]
+    async fn b32_cache_hit() {
+        let dir = tempdir().unwrap().keep();
+        tokio::fs::create_dir_all(&dir.join("addressbook")).await.unwrap();
+
+        let address_book = AddressBookManager::new(
+            dir,
+            AddressBookConfig {
+                default: Some(String::from("url")),
+                subscriptions: None,
+                refresh_interval: None,
+            },
+        );
+        let handle = address_book.handle();
+
+        let hosts = "tracker2.postman.i2p=lnQ6yoBTxQuQU8EQ1FlF395ITIQF-HGJxUeFvzETLFnoczNjQvKDbtSB7aHhn853zjVXrJBgwlB9sO57KakBDaJ50lUZgVPhjlI19TgJ-CxyHhHSCeKx5JzURdEW-ucdONMynr-b2zwhsx8VQCJwCEkARvt21YkOyQDaB9IdV8aTAmP~PUJQxRwceaTMn96FcVenwdXqleE16fI8CVFOV18jbJKrhTOYpTtcZKV4l1wNYBDwKgwPx5c0kcrRzFyw5~bjuAKO~GJ5dR7BQsL7AwBoQUS4k1lwoYrG1kOIBeDD3XF8BWb6K3GOOoyjc1umYKpur3G~FxBuqtHAsDRICrsRuil8qK~whOvj8uNTv~ohZnTZHxTLgi~sDyo98BwJ-4Y4NMSuF4GLzcgLypcR1D1WY2tDqMKRYFVyLE~MTPVjRRgXfcKolykQ666~Go~A~~CNV4qc~zlO6F4bsUhVZDU7WJ7mxCAwqaMiJsL-NgIkb~SMHNxIzaE~oy0agHJMBQAEAAcAAA==#!oldsig=i02RMv3Hy86NGhVo2O3byIf6xXqWrzrRibSabe5dmNfRRQPZO9L25A==#date=1598641102#action=adddest#sig=cB-mY~sp1uuEmcQJqremV1D6EDWCe3IwPv4lBiGAXgKRYc5MLBBzYvJXtXmOawpfLKeNM~v5fWlXYsDfKf5nDA==#olddest=lnQ6yoBTxQuQU8EQ1FlF395ITIQF-HGJxUeFvzETLFnoczNjQvKDbtSB7aHhn853zjVXrJBgwlB9sO57KakBDaJ50lUZgVPhjlI19TgJ-CxyHhHSCeKx5JzURdEW-ucdONMynr-b2zwhsx8VQCJwCEkARvt21YkOyQDaB9IdV8aTAmP~PUJQxRwceaTMn96FcVenwdXqleE16fI8CVFOV18jbJKrhTOYpTtcZKV4l1wNYBDwKgwPx5c0kcrRzFyw5~bjuAKO~GJ5dR7BQsL7AwBoQUS4k1lwoYrG1kOIBeDD3XF8BWb6K3GOOoyjc1umYKpur3G~FxBuqtHAsDRICkEbKUqJ9mPYQlTSujhNxiRIW-oLwMtvayCFci99oX8MvazPS7~97x0Gsm-onEK1Td9nBdmq30OqDxpRtXBimbzkLbR1IKObbg9HvrKs3L-kSyGwTUmHG9rSQSoZEvFMA-S0EXO~o4g21q1oikmxPMhkeVwQ22VHB0-LZJfmLr4SAAAA\npsi.i2p=a11l91etedRW5Kl2GhdDI9qiRBbDRAQY6TWJb8KlSc0P9WUrEviABAAltqDU1DFJrRhMAZg5i6rWGszkJrF-pWLQK9JOH33l4~mQjB8Hkt83l9qnNJPUlGlh9yIfBY40CQ0Ermy8gzjHLayUpypDJFv2V6rHLwxAQeaXJu8YXbyvCucEu9i6HVO49akXW9YSxcZEqxK04wZnjBqhHGlVbehleMqTx9nkd0pUpBZz~vIaG9matUSHinopEo6Wegml9FEz~FEaQpPknKuMAGGSNFVJb0NtaOQSAocAOg1nLKh80v232Y8sJOHG63asSJoBa6bGwjIHftsqD~lEmVV4NkgNPybmvsD1SCbMQ2ExaCXFPVQV-yJhIAPN9MRVT9cSBT2GCq-vpMwdJ5Nf0iPR3M-Ak961JUwWXPYTL79toXCgxDX2~nZ5QFRV490YNnfB7LQu10G89wG8lzS9GWf2i-nk~~ez0Lq0dH7qQokFXdUkPc7bvSrxqkytrbd-h8O8AAAA\nzerobin.i2p=Jf64hlpW8ILKZGDe61ljHU5wzmUYwN2klOyhM2iR-8VkUEVgDZRuaToRlXIFW4k5J1ccTzGzMxR518BkCAE3jCFIyrbF0MjQDuXO5cwmqfBFWrIv72xgKDizu3HytE4vOF2M730rv8epSNPAJg6OpyXkf5UQW96kgL8SWcxWdTbKU-O8IpE3O01Oc6j0fp1E4wVOci7qIL8UEloNN~mulgka69MkR0uEtXWOXd6wvBjLNrZgdZi7XtT4QlDjx13jr7RGpZBJAUkk~8gLqgJwoUYhbfM7x564PIn3IlMXHK5AKRVxAbCQ5GkS8KdkvNL7FsQ~EiElGzZId4wenraHMHL0destUDmuwGdHKA7YdtovXD~OnaBvIbl36iuIduZnGKPEBD31hVLdJuVId9RND7lQy5BZJHQss5HSxMWTszAnWJDwmxqzMHHCiL6BMpZnkz8znwPDSkUwEs3P6-ba7mDKKt8EPCG0nM6l~BvPl2OKQIBhXIxJLOOavGyqmmYmAAAA\nzzz.i2p=GKapJ8koUcBj~jmQzHsTYxDg2tpfWj0xjQTzd8BhfC9c3OS5fwPBNajgF-eOD6eCjFTqTlorlh7Hnd8kXj1qblUGXT-tDoR9~YV8dmXl51cJn9MVTRrEqRWSJVXbUUz9t5Po6Xa247Vr0sJn27R4KoKP8QVj1GuH6dB3b6wTPbOamC3dkO18vkQkfZWUdRMDXk0d8AdjB0E0864nOT~J9Fpnd2pQE5uoFT6P0DqtQR2jsFvf9ME61aqLvKPPWpkgdn4z6Zkm-NJOcDz2Nv8Si7hli94E9SghMYRsdjU-knObKvxiagn84FIwcOpepxuG~kFXdD5NfsH0v6Uri3usE3XWD7Pw6P8qVYF39jUIq4OiNMwPnNYzy2N4mDMQdsdHO3LUVh~DEppOy9AAmEoHDjjJxt2BFBbGxfdpZCpENkwvmZeYUyNCCzASqTOOlNzdpne8cuesn3NDXIpNnqEE6Oe5Qm5YOJykrX~Vx~cFFT3QzDGkIjjxlFBsjUJyYkFjBQAEAAcAAA==#!action=adddest#date=1490103520#olddest=GKapJ8koUcBj~jmQzHsTYxDg2tpfWj0xjQTzd8BhfC9c3OS5fwPBNajgF-eOD6eCjFTqTlorlh7Hnd8kXj1qblUGXT-tDoR9~YV8dmXl51cJn9MVTRrEqRWSJVXbUUz9t5Po6Xa247Vr0sJn27R4KoKP8QVj1GuH6dB3b6wTPbOamC3dkO18vkQkfZWUdRMDXk0d8AdjB0E0864nOT~J9Fpnd2pQE5uoFT6P0DqtQR2jsFvf9ME61aqLvKPPWpkgdn4z6Zkm-NJOcDz2Nv8Si7hli94E9SghMYRsdjU-knObKvxiagn84FIwcOpepxuG~kFXdD5NfsH0v6Uri3usE3uSzpWS0EHmrlfoLr5uGGd9ZHwwCIcgfOATaPRMUEQxiK9q48PS0V3EXXO4-YLT0vIfk4xO~XqZpn8~PW1kFe2mQMHd7oO89yCk-3yizRG3UyFtI7-mO~eCI6-m1spYoigStgoupnC3G85gJkqEjMm49gUjbhfWKWI-6NwTj0ZnAAAA#oldsig=MbSvc9wsxSm37B65rUC~BCZzFsIJe0-CXCH8n97ZaMMizNUjeytgBQ==#sig=R2wREo~02liJmU4UGfVZr88XFMiHdYDXVfS~HtyxFxwYG~2o1guP~RocqmHBCE6yPg1Cm8m336d~jqijAVJzBA==".to_string();
+
+        address_book.parse_and_merge("url", hosts).await;
+
+        match handle.resolve_b32("zzz.i2p".to_string()) {
+            Either::Left(_) => panic!("unexpected cache hit"),
+            Either::Right(future) => assert_eq!(
+                future.await.unwrap(),
+                "lhbd7ojcaiofbfku7ixh47qj537g572zmhdc4oilvugzxdpdghua".to_string()
+            ),
+        }
+
+        match handle.resolve_b32("zzz.i2p".to_string()) {
+            Either::Left(value) => assert_eq!(
+                value,
+                "lhbd7ojcaiofbfku7ixh47qj537g572zmhdc4oilvugzxdpdghua".to_string()
+            ),
+            Either::Right(_) => panic!("zzz.i2p should be in cache"),
+        }
+    }
+
+    #[tokio::test]
+    async fn add_host_to_local_address_book() {
+        let destination = "GKapJ8koUcBj~jmQzHsTYxDg2tpfWj0xjQTzd8BhfC9c3OS5fwPBNajgF-eOD6eCjFTqTlorlh7Hnd8kXj1qblUGXT-tDoR9~YV8dmXl51cJn9MVTRrEqRWSJVXbUUz9t5Po6Xa247Vr0sJn27R4KoKP8QVj1GuH6dB3b6wTPbOamC3dkO18vkQkfZWUdRMDXk0d8AdjB0E0864nOT~J9Fpnd2pQE5uoFT6P0DqtQR2jsFvf9ME61aqLvKPPWpkgdn4z6Zkm-NJOcDz2Nv8Si7hli94E9SghMYRsdjU-knObKvxiagn84FIwcOpepxuG~kFXdD5NfsH0v6Uri3usE3XWD7Pw6P8qVYF39jUIq4OiNMwPnNYzy2N4mDMQdsdHO3LUVh~DEppOy9AAmEoHDjjJxt2BFBbGxfdpZCpENkwvmZeYUyNCCzASqTOOlNzdpne8cuesn3NDXIpNnqEE6Oe5Qm5YOJykrX~Vx~cFFT3QzDGkIjjxlFBsjUJyYkFjBQAEAAcAAA==";
+
+        let dir = tempdir().unwrap().keep();
+        tokio::fs::create_dir_all(&dir.join("addressbook")).await.unwrap();
+        tokio::fs::write(dir.join("addressbook/addresses"), "zzz.i2p=invalid\n")
+            .await
+            .unwrap();
+
+        let handle = AddressBookManager::new(
+            dir.clone(),
+            AddressBookConfig {
+                default: None,
+                subscriptions: None,
+                refresh_interval: None,
+            },
+        )
+        .handle();
+
+        assert!(handle.add("zzz.i2p", "invalid").await.is_err());
+
+        // local address book takes precedence over subscriptions
+        handle.add("zzz.i2p", destination).await.unwrap();
+        handle.add("zzz2.i2p", destination).await.unwrap();
+        handle.add("zzz2.i2p", destination).await.unwrap();
+
+        assert_eq!(
+            handle.resolve_b64("zzz.i2p".to_string()).await,
+            Some(destination.to_string())
+        );
+
+        match handle.resolve_b32("zzz.i2p".to_string()) {
+            Either::Left(value) => assert_eq!(
+                value,
+                "lhbd7ojcaiofbfku7ixh47qj537g572zmhdc4oilvugzxdpdghua".to_string()
+            ),
+            Either::Right(_) => panic!("zzz.i2p should be in cache"),
+        }
+
+        let local = tokio::fs::read_to_string(dir.join("addressbook/local")).await.unwrap();
+        assert_eq!(local.lines().count(), 2);
+    }
+
+    #[tokio::test]
+    async fn remove_host_from_local_address_book() {
+        let destination = "GKapJ8koUcBj~jmQzHsTYxDg2tpfWj0xjQTzd8BhfC9c3OS5fwPBNajgF-eOD6eCjFTqTlorlh7Hnd8kXj1qblUGXT-tDoR9~YV8dmXl51cJn9MVTRrEqRWSJVXbUUz9t5Po6Xa247Vr0sJn27R4KoKP8QVj1GuH6dB3b6wTPbOamC3dkO18vkQkfZWUdRMDXk0d8AdjB0E0864nOT~J9Fpnd2pQE5uoFT6P0DqtQR2jsFvf9ME61aqLvKPPWpkgdn4z6Zkm-NJOcDz2Nv8Si7hli94E9SghMYRsdjU-knObKvxiagn84FIwcOpepxuG~kFXdD5NfsH0v6Uri3usE3XWD7Pw6P8qVYF39jUIq4OiNMwPnNYzy2N4mDMQdsdHO3LUVh~DEppOy9AAmEoHDjjJxt2BFBbGxfdpZCpENkwvmZeYUyNCCzASqTOOlNzdpne8cuesn3NDXIpNnqEE6Oe5Qm5YOJykrX~Vx~cFFT3QzDGkIjjxlFBsjUJyYkFjBQAEAAcAAA==";
+
+        let dir = tempdir().unwrap().keep();
+        tokio::fs::create_dir_all(&dir.join("addressbook")).await.unwrap();
+        tokio::fs::write(
+            dir.join("addressbook/addresses"),
+            format!("zzz.i2p={destination}\n"),
+        )
+        .await
+        .unwrap();
+
+        let handle = AddressBookManager::new(
+            dir.clone(),
+            AddressBookConfig {
+                default: None,
+                subscriptions: None,
+                refresh_interval: None,
+            },
+        )
+        .handle();
+
+        // nothing to remove before the local address book exists
+        assert!(!handle.remove(Book::Local, "zzz.i2p").await.unwrap());
+
+        handle.add("host1.i2p", destination).await.unwrap();
+        handle.add("host2.i2p", destination).await.unwrap();
+
+        assert_eq!(handle.hosts(Book::Local).len(), 2);
+        assert_eq!(
+            handle.hosts(Book::Router),
+            vec![("zzz.i2p".to_string(), destination.to_string())]
+        );
+
+        // hosts from subscriptions cannot be removed
+        assert!(!handle.remove(Book::Local, "zzz.i2p").await.unwrap());
+        assert!(handle.remove(Book::Local, "host1.i2p").await.unwrap());
+        assert!(!handle.remove(Book::Local, "host1.i2p").await.unwrap());
+
+        assert!(handle.resolve_b64("host1.i2p".to_string()).await.is_none());
+        assert!(std::matches!(
+            handle.resolve_b32("host1.i2p".to_string()),
+            Either::Right(_)
+        ));
+
+        assert_eq!(
+            handle.hosts(Book::Local),
+            vec![("host2.i2p".to_string(), destination.to_string())]
+        );
+    }
+
+    #[tokio::test]
+    async fn subscription_cannot_hijack_hosts() {
+        use emissary_core::crypto::{base64_encode, SigningPrivateKey};
+        use rand::rngs::OsRng;
+
+        let dir = tempdir().unwrap();
+        let address_book = AddressBookManager::new(
+            dir.keep(),
+            AddressBookConfig {
+                default: Some(String::from("url")),
+                subscriptions: None,
+                refresh_interval: None,
+            },
+        );
+        let keys = (0..3)
+            .map(|_| {
+                let signing_key = SigningPrivateKey::random(OsRng);
+                let destination = Destination::new::<emissary_util::runtime::tokio::Runtime>(
+                    signing_key.public(),
+                );
+
+                (base64_encode(destination.serialized()), signing_key)
+            })
+            .collect::<Vec<_>>();
+        let sign =
+            |index: usize, message: &str| base64_encode(keys[index].1.sign(message.as_bytes()));
+        let change = |name: &str, new: usize, old: usize, signer: usize| {
+            let entry = format!(
+                "{name}={}#!action=changedest#olddest={}",
+                keys[new].0, keys[old].0
+            );
+            let entry = format!("{entry}#oldsig={}", sign(signer, &entry));
+
+            format!("{entry}#sig={}", sign(new, &entry))
+        };
+        let remove = |name: &str, signer: usize| {
+            let command = format!("#!action=remove#dest={}#name={name}", keys[signer].0);
+
+            format!("{command}#sig={}", sign(signer, &command))
+        };
+
+        let hosts = [
+            format!("host1.i2p={}", keys[0].0),
+            format!("host2.i2p={}", keys[0].0),
+            format!("host3.i2p={}", keys[0].0),
+        ]
+        .join("\n");
+        address_book.parse_and_merge("url", hosts).await;
+
+        // resolve the hosts so they're cached by the handle
+        let handle = address_book.handle();
+        for host in ["host2.i2p", "host3.i2p"] {
+            match handle.resolve_b32(host.to_string()) {
+                Either::Right(future) => assert!(future.await.is_some()),
+                Either::Left(_) => panic!("unexpected cache hit"),
+            }
+        }
+        assert!(std::matches!(
+            handle.resolve_b32("host2.i2p".to_string()),
+            Either::Left(_)
+        ));
+
+        let hosts = [
+            // first-come entries cannot be overwritten
+            format!("host1.i2p={}", keys[1].0),
+            // changes must be signed by the current destination
+            change("host1.i2p", 2, 1, 1),
+            change("host2.i2p", 1, 0, 0),
+            // removals must be signed by the current destination
+            remove("host1.i2p", 1),
+            remove("host3.i2p", 0),
+        ]
+        .join("\n");
+
+        address_book.parse_and_merge("url", hosts).await;
+        let addresses = address_book.router.hosts().into_iter().collect::<HashMap<_, _>>();
+
+        assert_eq!(addresses.get("host1.i2p"), Some(&keys[0].0));
+        assert_eq!(addresses.get("host2.i2p"), Some(&keys[1].0));
+        assert_eq!(addresses.get("host3.i2p"), None);
+
+        // changed and removed hosts are evicted from the cache of the handle
+        match handle.resolve_b32("host2.i2p".to_string()) {
+            Either::Right(future) => assert_eq!(future.await, destination_hash(&keys[1].0)),
+            Either::Left(_) => panic!("stale cache entry for host2.i2p"),
+        }
+        match handle.resolve_b32("host3.i2p".to_string()) {
+            Either::Right(future) => assert!(future.await.is_none()),
+            Either::Left(_) => panic!("stale cache entry for host3.i2p"),
+        }
+
+        // rejected destinations are recorded as conflicts
+        let conflicts = handle
+            .conflicts()
+            .into_iter()
+            .map(|conflict| (conflict.host, conflict.destination, conflict.conflicting))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            conflicts,
+            vec![
+                (
+                    "host1.i2p".to_string(),
+                    keys[0].0.clone(),
+                    keys[1].0.clone()
+                ),
+                (
+                    "host1.i2p".to_string(),
+                    keys[0].0.clone(),
+                    keys[2].0.clone()
+                ),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn local_and_private_address_books() {
+        let destination = "GKapJ8koUcBj~jmQzHsTYxDg2tpfWj0xjQTzd8BhfC9c3OS5fwPBNajgF-eOD6eCjFTqTlorlh7Hnd8kXj1qblUGXT-tDoR9~YV8dmXl51cJn9MVTRrEqRWSJVXbUUz9t5Po6Xa247Vr0sJn27R4KoKP8QVj1GuH6dB3b6wTPbOamC3dkO18vkQkfZWUdRMDXk0d8AdjB0E0864nOT~J9Fpnd2pQE5uoFT6P0DqtQR2jsFvf9ME61aqLvKPPWpkgdn4z6Zkm-NJOcDz2Nv8Si7hli94E9SghMYRsdjU-knObKvxiagn84FIwcOpepxuG~kFXdD5NfsH0v6Uri3usE3XWD7Pw6P8qVYF39jUIq4OiNMwPnNYzy2N4mDMQdsdHO3LUVh~DEppOy9AAmEoHDjjJxt2BFBbGxfdpZCpENkwvmZeYUyNCCzASqTOOlNzdpne8cuesn3NDXIpNnqEE6Oe5Qm5YOJykrX~Vx~cFFT3QzDGkIjjxlFBsjUJyYkFjBQAEAAcAAA==";
+        let private_destination = {
+            use emissary_core::crypto::{base64_encode, SigningPrivateKey};
+
+            let signing_key = SigningPrivateKey::random(rand::rngs::OsRng);
+            base64_encode(
+                Destination::new::<emissary_util::runtime::tokio::Runtime>(signing_key.public())
+                    .serialized(),
+            )
+        };
+
+        let dir = tempdir().unwrap().keep();
+        let handle = AddressBookManager::new(
+            dir.clone(),
+            AddressBookConfig {
+                default: None,
+                subscriptions: None,
+                refresh_interval: None,
+            },
+        )
+        .handle();
+
+        // router address book is read-only and hostnames are validated
+        assert!(handle.add_to(Book::Router, "host.i2p", destination).await.is_err());
+        assert!(handle.add_to(Book::Local, "host.com", destination).await.is_err());
+
+        // destinations with signature types the router doesn't support can be added
+        let p384_destination = {
+            let mut destination = vec![0xaa; 384];
+            destination.extend_from_slice(&[5, 0, 4, 0, 2, 0, 0]);
+            emissary_core::crypto::base64_encode(destination)
+        };
+        handle.add_to(Book::Private, "p384.i2p", &p384_destination).await.unwrap();
+        assert!(matches!(
+            handle.resolve_b32("p384.i2p".to_string()),
+            Either::Left(hash) if Some(hash.clone()) == destination_hash(&p384_destination)
+        ));
+
+        handle.add_to(Book::Local, "host.i2p", destination).await.unwrap();
+        handle.add_to(Book::Local, "Other.i2p", destination).await.unwrap();
+        assert_eq!(
+            handle.resolve_b64("other.i2p".to_string()).await,
+            Some(destination.to_string())
+        );
+        handle.add_to(Book::Private, "secret.i2p", &private_destination).await.unwrap();
+
+        // private address book takes precedence over the local address book
+        assert_eq!(
+            handle.resolve_b64("host.i2p".to_string()).await,
+            Some(destination.to_string())
+        );
+        handle.add_to(Book::Private, "host.i2p", &private_destination).await.unwrap();
+        assert_eq!(
+            handle.resolve_b64("host.i2p".to_string()).await,
+            Some(private_destination.clone())
+        );
+
+        // renaming fails if the new name is taken
+        assert!(handle.rename(Book::Local, "host.i2p", "other.i2p").await.is_err());
+        assert!(!handle.rename(Book::Local, "missing.i2p", "new.i2p").await.unwrap());
+        assert!(handle.rename(Book::Local, "Host.i2p", "Renamed.I2P").await.unwrap());
+        assert_eq!(
+            handle.resolve_b64("renamed.i2p".to_string()).await,
+            Some(destination.to_string())
+        );
+
+        // only the local address book is exported
+        let export = format!("other.i2p={destination}\nrenamed.i2p={destination}\n");
+
+        assert_eq!(handle.export(), export);
+        assert_eq!(
+            tokio::fs::read_to_string(dir.join("addressbook/hosts.txt")).await.unwrap(),
+            export
+        );
+        assert_eq!(
+            handle.hosts(Book::Private),
+            vec![
+                ("host.i2p".to_string(), private_destination.clone()),
+                ("p384.i2p".to_string(), p384_destination.clone()),
+                ("secret.i2p".to_string(), private_destination.clone()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn subscription_fetched_conditionally() {
+        use tokio::{
+            io::{AsyncReadExt, AsyncWriteExt},
+            net::TcpListener,
+        };
+
+        let destination = "GKapJ8koUcBj~jmQzHsTYxDg2tpfWj0xjQTzd8BhfC9c3OS5fwPBNajgF-eOD6eCjFTqTlorlh7Hnd8kXj1qblUGXT-tDoR9~YV8dmXl51cJn9MVTRrEqRWSJVXbUUz9t5Po6Xa247Vr0sJn27R4KoKP8QVj1GuH6dB3b6wTPbOamC3dkO18vkQkfZWUdRMDXk0d8AdjB0E0864nOT~J9Fpnd2pQE5uoFT6P0DqtQR2jsFvf9ME61aqLvKPPWpkgdn4z6Zkm-NJOcDz2Nv8Si7hli94E9SghMYRsdjU-knObKvxiagn84FIwcOpepxuG~kFXdD5NfsH0v6Uri3usE3XWD7Pw6P8qVYF39jUIq4OiNMwPnNYzy2N4mDMQdsdHO3LUVh~DEppOy9AAmEoHDjjJxt2BFBbGxfdpZCpENkwvmZeYUyNCCzASqTOOlNzdpne8cuesn3NDXIpNnqEE6Oe5Qm5YOJykrX~Vx~cFFT3QzDGkIjjxlFBsjUJyYkFjBQAEAAcAAA==";
+
+        // serve `hosts.txt` with an `ETag` and reply with 304 if the client already has it
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hosts.txt", listener.local_addr().unwrap());
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut request = vec![0u8; 8192];
+                let nread = stream.read(&mut request).await.unwrap();
+                let request = String::from_utf8_lossy(&request[..nread]).to_lowercase();
+                let conditional = request.contains("if-none-match: \"v1\"");
+
+                let response = match conditional {
+                    true => "HTTP/1.1 304 Not Modified\r\nContent-Length: 0\r\n\r\n".to_string(),
+                    false => {
+                        let body = format!("zzz.i2p={destination}\n");
+
+                        format!(
+                            "HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nContent-Length: {}\r\n\r\n{body}",
+                            body.len()
+                        )
+                    }
+                };
+                stream.write_all(response.as_bytes()).await.unwrap();
+                tx.send(conditional).unwrap();
+            }
+        });
+
+        let dir = tempdir().unwrap().keep();
+        let address_book = AddressBookManager::new(
+            dir.clone(),
+            AddressBookConfig {
+                default: Some(url.clone()),
+                subscriptions: None,
+                refresh_interval: None,
+            },
+        );
+        let handle = address_book.handle();
+        let client = Client::new();
+        let urls = vec![url.clone()];
+        let mut state = address_book.read_state().await;
+        let (_shutdown_tx, mut shutdown_rx) = oneshot::channel();
+
+        // first fetch downloads `hosts.txt`
+        let next_fetch = address_book
+            .refresh(&client, &urls, &mut state, &mut shutdown_rx)
+            .await
+            .unwrap();
+        assert!(!rx.recv().await.unwrap());
+        assert!(next_fetch >= now() + DEFAULT_REFRESH_INTERVAL.as_secs() - 5);
+        assert_eq!(
+            handle.resolve_b64("zzz.i2p".to_string()).await,
+            Some(destination.to_string())
+        );
+
+        // subscription is not due yet
+        address_book
+            .refresh(&client, &urls, &mut state, &mut shutdown_rx)
+            .await
+            .unwrap();
+        assert!(rx.try_recv().is_err());
+
+        // fetch state is persisted and the next fetch is conditional
+        let mut state = address_book.read_state().await;
+        assert_eq!(state[&url].etag.as_deref(), Some("\"v1\""));
+        state.get_mut(&url).unwrap().last_fetched = Some(0);
+
+        address_book
+            .refresh(&client, &urls, &mut state, &mut shutdown_rx)
+            .await
+            .unwrap();
+        assert!(rx.recv().await.unwrap());
+        assert!(state[&url].last_fetched.unwrap() > 0);
+        assert_eq!(
+            handle.resolve_b64("zzz.i2p".to_string()).await,
+            Some(destination.to_string())
+        );
+
+        // address book is reloaded from disk
+        let address_book = AddressBookManager::new(
+            dir,
+            AddressBookConfig {
+                default: None,
+                subscriptions: None,
+                refresh_interval: None,
+            },
+        );
+        assert_eq!(address_book.router.hosts().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn shutdown_interrupts_download() {
+        use tokio::net::TcpListener;
+
+        // accept the connection but never respond
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hosts.txt", listener.local_addr().unwrap());
+
+        tokio::spawn(async move {
+            let mut streams = Vec::new();
+
+            while let Ok((stream, _)) = listener.accept().await {
+                streams.push(stream);
+            }
+        });
+
+        let address_book = AddressBookManager::new(
+            tempdir().unwrap().keep(),
+            AddressBookConfig {
+                default: Some(url.clone()),
+                subscriptions: None,
+                refresh_interval: None,
+            },
+        );
+        let client = Client::new();
+        let urls = vec![url.clone()];
+        let mut state = address_book.read_state().await;
+        let (shutdown_tx, mut shutdown_rx) = oneshot::channel();
+
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            shutdown_tx.send(()).unwrap();
+        });
+
+        let result = tokio::time::timeout(
+            Duration::from_secs(5),
+            address_book.refresh(&client, &urls, &mut state, &mut shutdown_rx),
+        )
+        .await
+        .expect("no timeout");
+        assert!(result.is_none());
+    }
+
+    #[test]
+    fn failed_download_backed_off() {
+        let mut state = SubscriptionState::default();
+        let interval = Duration::from_secs(60 * 60);
+
+        assert_eq!(state.next_fetch(interval), 0);
+
+        state.failed(1000, interval);
+        assert_eq!(state.next_fetch(interval), 1030);
+
+        state.failed(1000, interval);
+        assert_eq!(state.next_fetch(interval), 1060);
+
+        (0..10).for_each(|_| state.failed(1000, interval));
+        assert_eq!(state.next_fetch(interval), 1000 + 3600);
+
+        state.fetched(2000);
+        assert_eq!(state.next_fetch(interval), 2000 + 3600);
+    }
+}
@@ -0,0 +1,334 @@
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! In-memory address book store.
+//!
+//! Hosts of an address book are kept in memory, indexed by hostname, so lookups don't touch the
+//! disk. The address book is written back to disk whenever it's modified by writing it into a
+//! temporary file which is then renamed over the old file, so a crash or a concurrent reader never
+//! sees a partially written address book.
+//!
+//! Conflicts, destinations seen for a host which already resolves to another destination, are
+//! stored the same way.
+
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+
+use std::{
+    collections::{HashMap, VecDeque},
+    path::Path,
+    sync::Arc,
+};
+
+/// Logging target for the file.
+const LOG_TARGET: &str = "emissary::address-book::store";
+
+/// Maximum number of conflicts stored.
+///
+/// Oldest conflicts are forgotten first.
+const MAX_CONFLICTS: usize = 1000usize;
+
+/// Address book store.
+#[derive(Clone)]
+pub struct HostStore {
+    /// Hosts, indexed by hostname.
+    hosts: Arc<RwLock<HashMap<String, String>>>,
+
+    /// Path to the address book file.
+    path: &'static str,
+
+    /// Lock which serializes writes to the address book file.
+    write_lock: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl HostStore {
+    /// Create new [`HostStore`] and load the address book at `path` into it, if it exists.
+    pub fn new(path: &'static str) -> Self {
+        let hosts = match std::fs::read_to_string(path) {
+            Ok(contents) => Self::parse(&contents),
+            Err(error) => {
+                if error.kind() != std::io::ErrorKind::NotFound {
+                    tracing::warn!(
+                        target: LOG_TARGET,
+                        %path,
+                        ?error,
+                        "failed to read address book",
+                    );
+                }
+
+                HashMap::new()
+            }
+        };
+
+        tracing::debug!(
+            target: LOG_TARGET,
+            %path,
+            num_hosts = ?hosts.len(),
+            "address book loaded",
+        );
+
+        Self {
+            hosts: Arc::new(RwLock::new(hosts)),
+            path,
+            write_lock: Default::default(),
+        }
+    }
+
+    /// Parse `host=destination` pairs from `contents`.
+    fn parse(contents: &str) -> HashMap<String, String> {
+        contents
+            .lines()
+            .filter_map(|line| {
+                line.split_once('=').map(|(host, destination)| {
+                    (host.trim().to_string(), destination.trim().to_string())
+                })
+            })
+            .collect()
+    }
+
+    /// Get destination of `host`.
+    pub fn get(&self, host: &str) -> Option<String> {
+        self.hosts.read().get(host).cloned()
+    }
+
+    /// Insert `host` with `destination` into the store, returning the previous destination.
+    pub fn insert(&self, host: String, destination: String) -> Option<String> {
+        self.hosts.write().insert(host, destination)
+    }
+
+    /// Remove `host` from the store, returning its destination.
+    pub fn remove(&self, host: &str) -> Option<String> {
+        self.hosts.write().remove(host)
+    }
+
+    /// Modify hosts of the store with `f`.
+    pub fn modify<T>(&self, f: impl FnOnce(&mut HashMap<String, String>) -> T) -> T {
+        f(&mut self.hosts.write())
+    }
+
+    /// Get `(host, destination)` pairs of the store, sorted by hostname.
+    pub fn hosts(&self) -> Vec<(String, String)> {
+        let mut hosts = self
+            .hosts
+            .read()
+            .iter()
+            .map(|(host, destination)| (host.clone(), destination.clone()))
+            .collect::<Vec<_>>();
+        hosts.sort_unstable();
+
+        hosts
+    }
+
+    /// Write the store to disk atomically.
+    pub async fn persist(&self) -> crate::Result<()> {
+        // the snapshot is taken while holding the lock so the last write reflects the latest
+        // state of the store, regardless of the order in which concurrent writers finish
+        let _guard = self.write_lock.lock().await;
+        let contents = self.hosts().into_iter().fold(String::new(), |mut out, (host, dest)| {
+            out.push_str(&host);
+            out.push('=');
+            out.push_str(&dest);
+            out.push('\n');
+            out
+        });
+
+        write_atomic(Path::new(self.path), contents).await
+    }
+}
+
+/// Destination seen for a host which already resolves to a different destination.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Conflict {
+    /// Hostname.
+    pub host: String,
+
+    /// Destination the host resolved to when the conflict was seen.
+    pub destination: String,
+
+    /// Conflicting destination.
+    pub conflicting: String,
+
+    /// Where the conflicting destination was seen, e.g., URL of a subscription.
+    pub source: String,
+
+    /// When the conflict was seen, as seconds since UNIX epoch.
+    pub seen: u64,
+}
+
+/// Serialized conflicts.
+#[derive(Default, Serialize, Deserialize)]
+struct Conflicts {
+    conflicts: VecDeque<Conflict>,
+}
+
+/// Conflict store.
+#[derive(Clone)]
+pub struct ConflictStore {
+    /// Conflicts, from oldest to newest.
+    conflicts: Arc<RwLock<VecDeque<Conflict>>>,
+
+    /// Path to the conflict file.
+    path: &'static str,
+
+    /// Lock which serializes writes to the conflict file.
+    write_lock: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl ConflictStore {
+    /// Create new [`ConflictStore`] and load the conflicts at `path` into it, if they exist.
+    pub fn new(path: &'static str) -> Self {
+        let conflicts = std::fs::read_to_string(path)
+            .ok()
+            .and_then(|contents| toml::from_str::<Conflicts>(&contents).ok())
+            .unwrap_or_default()
+            .conflicts;
+
+        Self {
+            conflicts: Arc::new(RwLock::new(conflicts)),
+            path,
+            write_lock: Default::default(),
+        }
+    }
+
+    /// Record `conflict`.
+    ///
+    /// Returns `false` if the conflicting destination has already been recorded for the host.
+    pub fn record(&self, conflict: Conflict) -> bool {
+        let mut conflicts = self.conflicts.write();
+
+        if conflicts
+            .iter()
+            .any(|known| known.host == conflict.host && known.conflicting == conflict.conflicting)
+        {
+            return false;
+        }
+
+        tracing::debug!(
+            target: LOG_TARGET,
+            host = %conflict.host,
+            source = %conflict.source,
+            "conflicting destination for host",
+        );
+
+        if conflicts.len() == MAX_CONFLICTS {
+            conflicts.pop_front();
+        }
+        conflicts.push_back(conflict);
+
+        true
+    }
+
+    /// Get recorded conflicts, from oldest to newest.
+    pub fn conflicts(&self) -> Vec<Conflict> {
+        self.conflicts.read().iter().cloned().collect()
+    }
+
+    /// Write the conflicts to disk atomically.
+    pub async fn persist(&self) -> crate::Result<()> {
+        let _guard = self.write_lock.lock().await;
+        let contents = toml::to_string(&Conflicts {
+            conflicts: self.conflicts.read().clone(),
+        })
+        .expect("to succeed");
+
+        write_atomic(Path::new(self.path), contents).await
+    }
+}
+
+/// Write `contents` into `path` atomically.
+///
+/// `contents` is written into a temporary file next to `path` which is then renamed to `path`.
+pub async fn write_atomic(path: &Path, contents: impl AsRef<[u8]>) -> crate::Result<()> {
+    let tmp_path = path.with_extension("tmp");
+
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    tokio::fs::write(&tmp_path, contents).await?;
+    tokio::fs::rename(&tmp_path, path).await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn path(dir: &Path) -> &'static str {
+        dir.join("addressbook/addresses").to_str().unwrap().to_string().leak()
+    }
+
+    #[tokio::test]
+    async fn store_persisted_and_loaded() {
+        let dir = tempdir().unwrap();
+        let store = HostStore::new(path(dir.path()));
+
+        assert!(store.hosts().is_empty());
+
+        store.insert("host2.i2p".to_string(), "dest2".to_string());
+        store.insert("host1.i2p".to_string(), "dest1".to_string());
+        store.insert("host3.i2p".to_string(), "dest3".to_string());
+        assert_eq!(store.remove("host3.i2p"), Some("dest3".to_string()));
+        store.persist().await.unwrap();
+
+        assert_eq!(
+            tokio::fs::read_to_string(dir.path().join("addressbook/addresses"))
+                .await
+                .unwrap(),
+            "host1.i2p=dest1\nhost2.i2p=dest2\n",
+        );
+        assert!(!dir.path().join("addressbook/addresses.tmp").exists());
+
+        let store = HostStore::new(path(dir.path()));
+        assert_eq!(store.hosts().len(), 2);
+        assert_eq!(store.get("host1.i2p"), Some("dest1".to_string()));
+        assert_eq!(store.get("host3.i2p"), None);
+    }
+
+    #[tokio::test]
+    async fn conflicts_recorded_once() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("addressbook/conflicts.toml").to_str().unwrap().to_string();
+        let store = ConflictStore::new(path.clone().leak());
+        let conflict = |conflicting: &str| Conflict {
+            host: "host.i2p".to_string(),
+            destination: "dest1".to_string(),
+            conflicting: conflicting.to_string(),
+            source: "http://hosts.i2p/hosts.txt".to_string(),
+            seen: 1337,
+        };
+
+        assert!(store.record(conflict("dest2")));
+        assert!(!store.record(conflict("dest2")));
+        assert!(store.record(conflict("dest3")));
+        store.persist().await.unwrap();
+
+        let store = ConflictStore::new(path.leak());
+        assert_eq!(
+            store.conflicts(),
+            vec![conflict("dest2"), conflict("dest3")]
+        );
+
+        (0..MAX_CONFLICTS).for_each(|i| {
+            store.record(conflict(&format!("dest{}", i + 4)));
+        });
+        assert_eq!(store.conflicts().len(), MAX_CONFLICTS);
+        assert_eq!(store.conflicts()[0], conflict("dest4"));
+    }
+}
@@ -0,0 +1,513 @@
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Parsing and verification of address book subscription entries.
+//!
+//! Each line of a subscription is either a plain `name=destination` pair or an entry with
+//! extension properties, `name=destination#!key1=value1#key2=value2`. Lines which consist only of
+//! properties, e.g., `#!action=remove#name=...#dest=...#sig=...`, are commands which don't add a
+//! host.
+//!
+//! Signatures are calculated over the entry without the `sig` property, with the properties
+//! sorted by key. Entries which change an existing host, `adddest` and `changedest`, are also
+//! signed with the old destination. That signature, `oldsig`, excludes both `sig` and `oldsig`.
+
+use emissary_core::{
+    crypto::{base32_encode, base64_decode, sha256::Sha256},
+    primitives::Destination,
+};
+
+use std::collections::BTreeMap;
+
+/// Minimum size of a serialized destination: public key, signing key and certificate header.
+const MIN_DESTINATION_LEN: usize = 387usize;
+
+/// Error returned when a subscription entry is rejected.
+#[derive(Debug, PartialEq, Eq)]
+pub enum EntryError {
+    /// Entry is malformed.
+    Malformed,
+
+    /// Hostname is invalid.
+    InvalidHostname,
+
+    /// Destination is invalid.
+    InvalidDestination,
+
+    /// Property required by the action is missing.
+    MissingProperty(&'static str),
+
+    /// Signature is invalid.
+    InvalidSignature,
+
+    /// Action is not supported.
+    UnsupportedAction(String),
+}
+
+/// Verified subscription entry.
+#[derive(Debug, PartialEq, Eq)]
+pub enum HostEntry {
+    /// Add new host.
+    ///
+    /// Ignored if the host already exists.
+    AddHost {
+        /// Hostname.
+        name: String,
+
+        /// Base64-encoded destination.
+        destination: String,
+    },
+
+    /// Add alternative destination for a host.
+    AddDest {
+        /// Hostname.
+        name: String,
+
+        /// Base64-encoded new destination.
+        destination: String,
+
+        /// Base64-encoded destination the host currently uses.
+        old_destination: String,
+    },
+
+    /// Change destination of a host.
+    ChangeDest {
+        /// Hostname.
+        name: String,
+
+        /// Base64-encoded new destination.
+        destination: String,
+
+        /// Base64-encoded destination the host currently uses.
+        old_destination: String,
+    },
+
+    /// Remove host.
+    Remove {
+        /// Hostname.
+        name: String,
+
+        /// Base64-encoded destination the host currently uses.
+        destination: String,
+    },
+}
+
+/// Check if `name` is a valid `.i2p` hostname.
+pub fn is_valid_hostname(name: &str) -> bool {
+    name.len() <= 67
+        && name.ends_with(".i2p")
+        && !name.ends_with(".b32.i2p")
+        && !name.starts_with(['.', '-'])
+        && !name.contains("..")
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-')
+        && name.len() > ".i2p".len()
+}
+
+/// Get the base32-encoded hash of base64-encoded `destination`.
+///
+/// The destination isn't parsed so hosts whose signature type isn't supported by the router can
+/// still be stored in the address book.
+pub fn destination_hash(destination: &str) -> Option<String> {
+    base64_decode(destination)
+        .filter(|destination| destination.len() >= MIN_DESTINATION_LEN)
+        .map(|destination| base32_encode(Sha256::new().update(&destination).finalize()))
+}
+
+/// Parse `key=value` properties separated by `#`.
+fn parse_properties(properties: &str) -> Result<BTreeMap<String, String>, EntryError> {
+    properties
+        .split('#')
+        .map(|property| match property.split_once('=') {
+            Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+            _ => Err(EntryError::Malformed),
+        })
+        .collect()
+}
+
+/// Parse base64-encoded `destination`.
+///
+/// Only needed for verifying signatures and fails for signature types the router doesn't
+/// support.
+fn parse_destination(destination: &str) -> Result<Destination, EntryError> {
+    base64_decode(destination)
+        .and_then(Destination::parse)
+        .ok_or(EntryError::InvalidDestination)
+}
+
+/// Get value of a property required by the action.
+fn property<'a>(
+    properties: &'a BTreeMap<String, String>,
+    key: &'static str,
+) -> Result<&'a str, EntryError> {
+    properties.get(key).map(String::as_str).ok_or(EntryError::MissingProperty(key))
+}
+
+/// Create the message that was signed, excluding the properties in `excluded`.
+fn signed_message(
+    host: Option<(&str, &str)>,
+    properties: &BTreeMap<String, String>,
+    excluded: &[&str],
+) -> String {
+    let mut message = host
+        .map(|(name, destination)| format!("{name}={destination}"))
+        .unwrap_or_default();
+
+    properties
+        .iter()
+        .filter(|(key, _)| !excluded.contains(&key.as_str()))
+        .enumerate()
+        .for_each(|(index, (key, value))| {
+            message.push_str(if index == 0 { "#!" } else { "#" });
+            message.push_str(key);
+            message.push('=');
+            message.push_str(value);
+        });
+
+    message
+}
+
+/// Verify that `signature` of `message` was created with the signing key of `destination`.
+fn verify(destination: &Destination, message: &str, signature: &str) -> Result<(), EntryError> {
+    let signature = base64_decode(signature).ok_or(EntryError::InvalidSignature)?;
+
+    destination
+        .verifying_key()
+        .verify(message.as_bytes(), &signature)
+        .map_err(|_| EntryError::InvalidSignature)
+}
+
+/// Parse a line of a subscription and verify its signatures.
+///
+/// Returns `Ok(None)` for empty lines and comments. Entries without properties are accepted
+/// without a signature but if an entry carries a signature, it must be valid.
+pub fn parse_entry(line: &str) -> Result<Option<HostEntry>, EntryError> {
+    let line = line.trim();
+
+    if line.is_empty() || (line.starts_with('#') && !line.starts_with("#!")) {
+        return Ok(None);
+    }
+
+    let (host, properties) = match line.split_once("#!") {
+        Some((host, properties)) => (host, parse_properties(properties)?),
+        None => (line, BTreeMap::new()),
+    };
+
+    // commands don't have a host
+    if host.is_empty() {
+        return parse_command(properties).map(Some);
+    }
+
+    let (name, destination) = host.split_once('=').ok_or(EntryError::Malformed)?;
+    let (name, destination) = (name.trim(), destination.trim());
+
+    if !is_valid_hostname(&name.to_ascii_lowercase()) {
+        return Err(EntryError::InvalidHostname);
+    }
+    if destination_hash(destination).is_none() {
+        return Err(EntryError::InvalidDestination);
+    }
+    let message = signed_message(Some((name, destination)), &properties, &["sig"]);
+
+    match properties.get("action").map(String::as_str) {
+        None => {
+            if let Some(signature) = properties.get("sig") {
+                verify(&parse_destination(destination)?, &message, signature)?;
+            }
+
+            Ok(Some(HostEntry::AddHost {
+                name: name.to_ascii_lowercase(),
+                destination: destination.to_string(),
+            }))
+        }
+        Some(action @ ("adddest" | "changedest")) => {
+            // the entry must be signed by both the new and the old destination
+            verify(
+                &parse_destination(destination)?,
+                &message,
+                property(&properties, "sig")?,
+            )?;
+
+            let old_destination = property(&properties, "olddest")?;
+            let inner_message =
+                signed_message(Some((name, destination)), &properties, &["oldsig", "sig"]);
+            verify(
+                &parse_destination(old_destination)?,
+                &inner_message,
+                property(&properties, "oldsig")?,
+            )?;
+
+            let (name, destination, old_destination) = (
+                name.to_ascii_lowercase(),
+                destination.to_string(),
+                old_destination.to_string(),
+            );
+
+            Ok(Some(match action {
+                "adddest" => HostEntry::AddDest {
+                    name,
+                    destination,
+                    old_destination,
+                },
+                _ => HostEntry::ChangeDest {
+                    name,
+                    destination,
+                    old_destination,
+                },
+            }))
+        }
+        Some(action) => Err(EntryError::UnsupportedAction(action.to_string())),
+    }
+}
+
+/// Parse a command and verify its signature.
+fn parse_command(properties: BTreeMap<String, String>) -> Result<HostEntry, EntryError> {
+    match property(&properties, "action")? {
+        "remove" => {
+            let name = property(&properties, "name")?;
+            let destination = property(&properties, "dest")?;
+
+            if !is_valid_hostname(&name.to_ascii_lowercase()) {
+                return Err(EntryError::InvalidHostname);
+            }
+
+            verify(
+                &parse_destination(destination)?,
+                &signed_message(None, &properties, &["sig"]),
+                property(&properties, "sig")?,
+            )?;
+
+            Ok(HostEntry::Remove {
+                name: name.to_ascii_lowercase(),
+                destination: destination.to_string(),
+            })
+        }
+        action => Err(EntryError::UnsupportedAction(action.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use emissary_core::crypto::{base64_encode, SigningPrivateKey};
+    use emissary_util::runtime::tokio::Runtime;
+    use rand::rngs::OsRng;
+
+    /// Subscription signer.
+    struct Signer {
+        /// Base64-encoded destination.
+        destination: String,
+
+        /// Signing key.
+        signing_key: SigningPrivateKey,
+    }
+
+    impl Signer {
+        fn new() -> Self {
+            let signing_key = SigningPrivateKey::random(OsRng);
+            let destination = Destination::new::<Runtime>(signing_key.public());
+
+            Self {
+                destination: base64_encode(destination.serialized()),
+                signing_key,
+            }
+        }
+
+        fn sign(&self, message: &str) -> String {
+            base64_encode(self.signing_key.sign(message.as_bytes()))
+        }
+    }
+
+    /// Create signed `action` entry which moves `name` from `old` to `new`.
+    fn change_entry(action: &str, name: &str, new: &Signer, old: &Signer) -> String {
+        let entry = format!(
+            "{name}={}#!action={action}#date=1700000000#olddest={}",
+            new.destination, old.destination
+        );
+        let entry = format!("{entry}#oldsig={}", old.sign(&entry));
+
+        format!("{entry}#sig={}", new.sign(&entry))
+    }
+
+    #[test]
+    fn comments_and_empty_lines_ignored() {
+        assert_eq!(parse_entry(""), Ok(None));
+        assert_eq!(parse_entry("   "), Ok(None));
+        assert_eq!(parse_entry("# hosts.txt"), Ok(None));
+    }
+
+    #[test]
+    fn plain_and_signed_entries() {
+        let signer = Signer::new();
+        let entry = format!("host.i2p={}", signer.destination);
+
+        assert_eq!(
+            parse_entry(&entry),
+            Ok(Some(HostEntry::AddHost {
+                name: "host.i2p".to_string(),
+                destination: signer.destination.clone(),
+            }))
+        );
+
+        let entry = format!("{entry}#!date=1700000000");
+        let signed = format!("{entry}#sig={}", signer.sign(&entry));
+
+        assert_eq!(
+            parse_entry(&signed),
+            Ok(Some(HostEntry::AddHost {
+                name: "host.i2p".to_string(),
+                destination: signer.destination.clone(),
+            }))
+        );
+
+        // signature created by another destination
+        let signed = format!("{entry}#sig={}", Signer::new().sign(&entry));
+        assert_eq!(parse_entry(&signed), Err(EntryError::InvalidSignature));
+    }
+
+    #[test]
+    fn unsupported_signature_type_accepted_without_signature() {
+        // destination with a p384 signing key
+        let mut destination = vec![0xaa; 384];
+        destination.extend_from_slice(&[5, 0, 4, 0, 2, 0, 0]);
+        let destination = emissary_core::crypto::base64_encode(destination);
+
+        assert_eq!(
+            parse_entry(&format!("host.i2p={destination}")),
+            Ok(Some(HostEntry::AddHost {
+                name: "host.i2p".to_string(),
+                destination: destination.clone(),
+            }))
+        );
+        assert_eq!(
+            parse_entry(&format!("host.i2p={destination}#!sig=AAAA")),
+            Err(EntryError::InvalidDestination)
+        );
+    }
+
+    #[test]
+    fn invalid_entries_rejected() {
+        let signer = Signer::new();
+
+        assert_eq!(
+            parse_entry(&format!("host.com={}", signer.destination)),
+            Err(EntryError::InvalidHostname)
+        );
+        assert_eq!(
+            parse_entry(&format!("ho_st.i2p={}", signer.destination)),
+            Err(EntryError::InvalidHostname)
+        );
+        assert_eq!(
+            parse_entry("host.i2p=AAAA"),
+            Err(EntryError::InvalidDestination)
+        );
+        assert_eq!(
+            parse_entry(&format!("host.i2p={}#!date", signer.destination)),
+            Err(EntryError::Malformed)
+        );
+        assert_eq!(
+            parse_entry(&format!(
+                "host.i2p={}#!action=changename",
+                signer.destination
+            )),
+            Err(EntryError::UnsupportedAction("changename".to_string()))
+        );
+    }
+
+    #[test]
+    fn change_destination() {
+        let (old, new) = (Signer::new(), Signer::new());
+
+        assert_eq!(
+            parse_entry(&change_entry("changedest", "host.i2p", &new, &old)),
+            Ok(Some(HostEntry::ChangeDest {
+                name: "host.i2p".to_string(),
+                destination: new.destination.clone(),
+                old_destination: old.destination.clone(),
+            }))
+        );
+        assert_eq!(
+            parse_entry(&change_entry("adddest", "host.i2p", &new, &old)),
+            Ok(Some(HostEntry::AddDest {
+                name: "host.i2p".to_string(),
+                destination: new.destination.clone(),
+                old_destination: old.destination.clone(),
+            }))
+        );
+    }
+
+    #[test]
+    fn change_destination_without_old_signature() {
+        let (old, new) = (Signer::new(), Signer::new());
+
+        // `oldsig` created by the new destination instead of the old one
+        let entry = format!(
+            "host.i2p={}#!action=changedest#olddest={}",
+            new.destination, old.destination
+        );
+        let entry = format!("{entry}#oldsig={}", new.sign(&entry));
+        let entry = format!("{entry}#sig={}", new.sign(&entry));
+
+        assert_eq!(parse_entry(&entry), Err(EntryError::InvalidSignature));
+
+        // `oldsig` missing
+        let entry = format!(
+            "host.i2p={}#!action=changedest#olddest={}",
+            new.destination, old.destination
+        );
+        let entry = format!("{entry}#sig={}", new.sign(&entry));
+
+        assert_eq!(
+            parse_entry(&entry),
+            Err(EntryError::MissingProperty("oldsig"))
+        );
+    }
+
+    #[test]
+    fn remove_host() {
+        let signer = Signer::new();
+        let command = format!("#!action=remove#dest={}#name=host.i2p", signer.destination);
+
+        assert_eq!(
+            parse_entry(&format!("{command}#sig={}", signer.sign(&command))),
+            Ok(Some(HostEntry::Remove {
+                name: "host.i2p".to_string(),
+                destination: signer.destination.clone(),
+            }))
+        );
+        assert_eq!(
+            parse_entry(&format!("{command}#sig={}", Signer::new().sign(&command))),
+            Err(EntryError::InvalidSignature)
+        );
+        assert_eq!(
+            parse_entry(&command),
+            Err(EntryError::MissingProperty("sig"))
+        );
+    }
+
+    #[test]
+    fn properties_sorted_before_verification() {
+        let signer = Signer::new();
+        let entry = format!("host.i2p={}#!a=1#b=2", signer.destination);
+        let signature = signer.sign(&entry);
+
+        // properties are in different order than when they were signed
+        let entry = format!("host.i2p={}#!sig={signature}#b=2#a=1", signer.destination);
+
+        assert!(parse_entry(&entry).unwrap().is_some());
+    }
+}
@@ -16,7 +16,9 @@
 // FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
 // DEALINGS IN THE SOFTWARE.
 
-use clap::{Args, Parser};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+
+use std::{net::SocketAddr, path::PathBuf};
 
 use crate::config::Theme;
 
@@ -67,6 +69,29 @@ pub struct ReseedOptions {
     #[arg(long, value_delimiter = ',', num_args = 1.., value_name = "HOST")]
     pub reseed_hosts: Option<Vec<String>>,
 
+    /// Reseed from a local `.su3` or `.zip` file instead of reseed hosts
+    #[arg(long, value_name = "FILE")]
+    pub reseed_file: Option<PathBuf>,
+
+    /// HTTP or SOCKS5 proxy used for reseeding
+    ///
+    /// Example:
+    ///   --reseed-proxy socks5h://127.0.0.1:9050
+    #[arg(long, value_name = "URL")]
+    pub reseed_proxy: Option<String>,
+
+    /// Comma-separated list of additional trusted reseed signer certificates
+    ///
+    /// Signer ID is read from the common name of the certificate
+    #[arg(long, value_delimiter = ',', num_args = 1.., value_name = "FILE")]
+    pub reseed_certificates: Option<Vec<PathBuf>>,
+
+    /// Directory of additional trusted reseed signer certificates
+    ///
+    /// All `.crt` and `.pem` files of the directory are read
+    #[arg(long, value_name = "PATH")]
+    pub reseed_certificate_dir: Option<PathBuf>,
+
     /// Don't reseed the router even if there aren't enough routers
     #[arg(long, action = clap::ArgAction::SetTrue)]
     pub disable_reseed: Option<bool>,
@@ -95,7 +120,7 @@ pub struct MetricsOptions {
     pub disable_metrics: Option<bool>,
 }
 
-#[derive(Args)]
+#[derive(Args, Clone)]
 pub struct HttpProxyOptions {
     /// HTTP proxy port.
     ///
@@ -114,7 +139,7 @@ pub struct HttpProxyOptions {
     pub http_outproxy: Option<String>,
 }
 
-#[derive(Args)]
+#[derive(Args, Clone)]
 pub struct SocksProxyOptions {
     /// SOCKS proxy port.
     ///
@@ -127,6 +152,10 @@ pub struct SocksProxyOptions {
     /// Defaults to 127.0.0.1
     #[arg(long, value_name = "HOST")]
     pub socks_proxy_host: Option<String>,
+
+    /// SOCKS outproxy.
+    #[arg(long, value_name = "OUTPROXY")]
+    pub socks_outproxy: Option<String>,
 }
 
 #[derive(Args)]
@@ -169,15 +198,280 @@ pub struct RouterUiOptions {
     pub web_ui_port: Option<u16>,
 }
 
+/// Private key format of a destination.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum KeyFormat {
+    /// Base64-encoded private key, used by emissary and SAMv3.
+    Emissary,
+
+    /// Binary private key file, used by Java I2P and i2pd.
+    #[value(alias = "i2pd")]
+    Java,
+}
+
+#[derive(Subcommand)]
+pub enum DestinationCommand {
+    /// Generate new destination
+    Generate {
+        /// Where the private key is written to
+        ///
+        /// If not specified, the private key is printed to stdout
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+
+        /// Format of the private key
+        #[arg(long, value_enum, default_value_t = KeyFormat::Emissary)]
+        format: KeyFormat,
+    },
+
+    /// Print base32 and base64 addresses of a destination
+    Show {
+        /// Path to private key file
+        file: PathBuf,
+
+        /// Format of the private key
+        ///
+        /// If not specified, format is detected from file contents
+        #[arg(long, value_enum)]
+        format: Option<KeyFormat>,
+    },
+
+    /// Convert private key between emissary and Java/i2pd formats
+    Convert {
+        /// Path to input private key file
+        input: PathBuf,
+
+        /// Path to output private key file
+        output: PathBuf,
+
+        /// Format of the output private key
+        ///
+        /// Input format is detected from file contents
+        #[arg(long, value_enum)]
+        to: KeyFormat,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum RouterInfoCommand {
+    /// Print contents of a router info file
+    Dump {
+        /// Path to router info file
+        file: PathBuf,
+    },
+}
+
+#[derive(Args)]
+pub struct BundleOptions {
+    /// Path to the private key of the signer
+    ///
+    /// PEM-encoded ECDSA, Ed25519 or RSA private key
+    #[arg(long, value_name = "FILE")]
+    pub key: PathBuf,
+
+    /// Signer ID, e.g., `you@mail.i2p`
+    ///
+    /// Must match the common name of the signer's certificate
+    #[arg(long, value_name = "ID")]
+    pub signer_id: String,
+
+    /// Path to the `netDb` directory router infos are read from
+    ///
+    /// Defaults to `netDb` in base path
+    #[arg(long, value_name = "PATH")]
+    pub netdb: Option<PathBuf>,
+
+    /// Number of router infos in a bundle
+    #[arg(long, default_value_t = 75)]
+    pub count: usize,
+}
+
+#[derive(Subcommand)]
+pub enum ReseedCommand {
+    /// Create a signed reseed bundle from the router infos of `netDb`
+    Bundle {
+        #[command(flatten)]
+        options: BundleOptions,
+
+        /// Where the reseed bundle is written to
+        #[arg(short, long, value_name = "FILE")]
+        output: PathBuf,
+    },
+
+    /// Serve signed reseed bundles over HTTP or HTTPS
+    Serve {
+        #[command(flatten)]
+        options: BundleOptions,
+
+        /// Address the reseed server listens on
+        #[arg(long, default_value = "0.0.0.0:8443")]
+        address: SocketAddr,
+
+        /// PEM-encoded TLS certificate
+        ///
+        /// If not specified, bundles are served over plain HTTP
+        #[arg(long, value_name = "FILE", requires = "tls_key")]
+        tls_cert: Option<PathBuf>,
+
+        /// PEM-encoded PKCS#8 private key of the TLS certificate
+        #[arg(long, value_name = "FILE", requires = "tls_cert")]
+        tls_key: Option<PathBuf>,
+
+        /// How often, in minutes, a new bundle is created from `netDb`
+        #[arg(long, default_value_t = 60)]
+        refresh_interval: u64,
+    },
+}
+
+#[cfg(unix)]
+#[derive(Subcommand)]
+pub enum ControlCommand {
+    /// List client tunnels, server tunnels and tunnel pools
+    Tunnels,
+
+    /// List connected routers
+    Routers,
+
+    /// List transit tunnels
+    TransitTunnels,
+
+    /// List active destinations
+    Destinations,
+
+    /// Add client tunnel
+    AddClientTunnel {
+        /// Name of the tunnel
+        #[arg(long)]
+        name: String,
+
+        /// Address the client tunnel listens on
+        ///
+        /// Defaults to 127.0.0.1
+        #[arg(long)]
+        address: Option<String>,
+
+        /// Port the client tunnel listens on
+        #[arg(long)]
+        port: u16,
+
+        /// Destination the client tunnel connects to
+        #[arg(long)]
+        destination: String,
+
+        /// Port of the destination
+        #[arg(long)]
+        destination_port: Option<u16>,
+
+        /// Path to a persistent destination private key, relative to base path
+        ///
+        /// Client tunnels with the same key file share the destination. New destination is created
+        /// if the file doesn't exist
+        #[arg(long)]
+        keys: Option<String>,
+
+        /// Share the transient destination with other client tunnels
+        ///
+        /// Defaults to true, ignored if `--keys` is specified
+        #[arg(long)]
+        shared: Option<bool>,
+
+        /// Type of the tunnel
+        ///
+        /// Defaults to `client`
+        #[arg(long = "type", value_enum)]
+        kind: Option<crate::config::ClientTunnelKind>,
+    },
+
+    /// Remove client tunnel
+    RemoveClientTunnel {
+        /// Name of the tunnel
+        name: String,
+    },
+
+    /// Add server tunnel
+    AddServerTunnel {
+        /// Name of the tunnel
+        #[arg(long)]
+        name: String,
+
+        /// Port of the local server
+        #[arg(long)]
+        port: u16,
+
+        /// Path to destination private key, relative to base path
+        ///
+        /// New destination is created if the file doesn't exist
+        #[arg(long)]
+        destination_path: String,
+
+        /// Type of the tunnel
+        ///
+        /// Defaults to `server`
+        #[arg(long = "type", value_enum)]
+        kind: Option<crate::config::ServerTunnelKind>,
+    },
+
+    /// Remove server tunnel
+    RemoveServerTunnel {
+        /// Name of the tunnel
+        name: String,
+    },
+
+    /// Reload router config
+    ///
+    /// Only client tunnels, server tunnels, proxies and address book subscriptions are reloaded
+    Reload,
+
+    /// Reseed the router
+    Reseed,
+
+    /// Gracefully shut down the router
+    Shutdown,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Offline destination management
+    #[command(subcommand)]
+    Dest(DestinationCommand),
+
+    /// Offline router info inspection
+    #[command(subcommand, name = "routerinfo")]
+    RouterInfo(RouterInfoCommand),
+
+    /// Reseed bundle creation and reseed server
+    #[command(subcommand)]
+    Reseed(ReseedCommand),
+
+    /// Control a running router over its control socket
+    #[cfg(unix)]
+    Ctl {
+        /// Path to control socket
+        ///
+        /// Defaults to `control.sock` in base path
+        #[arg(long, value_name = "PATH")]
+        socket: Option<PathBuf>,
+
+        #[command(subcommand)]
+        command: ControlCommand,
+    },
+}
+
 #[derive(Parser)]
 #[command(version, about)]
 pub struct Arguments {
+    /// Subcommand
+    ///
+    /// If no subcommand is given, the router is started
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     /// Base path where all i2p-related files are stored
     ///   
     /// Defaults to $HOME/.emissary/ and if it doesn't exist,
     /// new directory is created
     #[arg(short, long, value_name = "PATH")]
-    pub base_path: Option<std::path::PathBuf>,
+    pub base_path: Option<PathBuf>,
 
     /// Logging targets
     ///
@@ -0,0 +1,107 @@
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::{
+    cli::ControlCommand,
+    config::{ClientTunnelConfig, ServerTunnelConfig},
+    control::{send_request, ControlRequest, CONTROL_SOCKET_NAME},
+    error::Error,
+};
+
+use home::home_dir;
+
+use std::path::PathBuf;
+
+/// Send `command` to the control socket of a running router and print the result.
+///
+/// If `socket` is not specified, `control.sock` is looked up from `base_path` or from the default
+/// base path if no base path was given either.
+pub fn run(
+    command: ControlCommand,
+    socket: Option<PathBuf>,
+    base_path: Option<PathBuf>,
+) -> crate::Result<()> {
+    let socket = match (socket, base_path) {
+        (Some(socket), _) => socket,
+        (None, Some(base_path)) => base_path.join(CONTROL_SOCKET_NAME),
+        (None, None) => home_dir()
+            .filter(|path| !path.as_os_str().is_empty())
+            .map(|path| path.join(".emissary").join(CONTROL_SOCKET_NAME))
+            .ok_or(Error::Custom(String::from("couldn't resolve base path")))?,
+    };
+
+    let request = match command {
+        ControlCommand::Tunnels => ControlRequest::ListTunnels,
+        ControlCommand::Routers => ControlRequest::ListRouters,
+        ControlCommand::TransitTunnels => ControlRequest::ListTransitTunnels,
+        ControlCommand::Destinations => ControlRequest::ListDestinations,
+        ControlCommand::AddClientTunnel {
+            name,
+            address,
+            port,
+            destination,
+            destination_port,
+            keys,
+            shared,
+            kind,
+        } => ControlRequest::AddClientTunnel {
+            tunnel: ClientTunnelConfig {
+                name,
+                address,
+                port,
+                destination,
+                destination_port,
+                keys,
+                shared,
+                kind,
+                ..Default::default()
+            },
+        },
+        ControlCommand::RemoveClientTunnel { name } => ControlRequest::RemoveClientTunnel { name },
+        ControlCommand::AddServerTunnel {
+            name,
+            port,
+            destination_path,
+            kind,
+        } => ControlRequest::AddServerTunnel {
+            tunnel: ServerTunnelConfig {
+                name,
+                port,
+                destination_path,
+                kind,
+                ..Default::default()
+            },
+        },
+        ControlCommand::RemoveServerTunnel { name } => ControlRequest::RemoveServerTunnel { name },
+        ControlCommand::Reload => ControlRequest::Reload,
+        ControlCommand::Reseed => ControlRequest::Reseed,
+        ControlCommand::Shutdown => ControlRequest::Shutdown,
+    };
+
+    let result = tokio::runtime::Runtime::new()?.block_on(send_request(socket, request))?;
+
+    if !result.is_null() {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&result)
+                .map_err(|error| Error::Custom(error.to_string()))?
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,347 @@
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Destination management.
+//!
+//! emissary stores private keys as base64-encoded strings, the same format SAMv3 uses, whereas
+//! Java I2P and i2pd store them as binary files. Both contain the serialized destination, followed
+//! by the encryption private key and the signing private key.
+
+use crate::{
+    cli::{DestinationCommand, KeyFormat},
+    error::Error,
+};
+
+use emissary_core::{
+    crypto::{base32_encode, base64_decode, base64_encode, SigningPrivateKey},
+    primitives::Destination,
+};
+use emissary_util::runtime::tokio::Runtime;
+use rand::rngs::OsRng;
+
+use std::path::Path;
+
+/// Length of the X25519 private key used by emissary.
+const X25519_PRIVATE_KEY_LEN: usize = 32usize;
+
+/// Length of the ElGamal private key.
+const ELGAMAL_PRIVATE_KEY_LEN: usize = 256usize;
+
+/// Length of the Ed25519 signing private key.
+const SIGNING_PRIVATE_KEY_LEN: usize = 32usize;
+
+/// Offset of the key certificate's crypto type in a serialized destination.
+///
+/// Keys (384 bytes) + certificate type (1 byte) + certificate length (2 bytes) + signature type
+/// (2 bytes).
+const CRYPTO_TYPE_OFFSET: usize = 389usize;
+
+/// Crypto type for X25519.
+const CRYPTO_TYPE_X25519: u16 = 4u16;
+
+/// Private keys of a destination.
+pub struct DestinationKeys {
+    /// Destination.
+    destination: Destination,
+
+    /// ElGamal encryption private key of a key file created by Java I2P or i2pd.
+    ///
+    /// emissary doesn't use ElGamal but the key is carried through so it can be written back
+    /// when converting to the Java format.
+    elgamal_key: Option<Vec<u8>>,
+
+    /// X25519 encryption private key.
+    private_key: [u8; X25519_PRIVATE_KEY_LEN],
+
+    /// Ed25519 signing private key.
+    signing_key: [u8; SIGNING_PRIVATE_KEY_LEN],
+}
+
+impl DestinationKeys {
+    /// Generate new [`DestinationKeys`].
+    pub fn generate() -> Self {
+        let signing_key = SigningPrivateKey::random(OsRng);
+        let destination = Destination::new::<Runtime>(signing_key.public());
+        let private_key = x25519_dalek::StaticSecret::random().to_bytes();
+
+        Self {
+            destination,
+            elgamal_key: None,
+            private_key,
+            signing_key: signing_key.as_ref().try_into().expect("to succeed"),
+        }
+    }
+
+    /// Get length of the encryption private key for `destination`, as stored by Java I2P and
+    /// i2pd.
+    ///
+    /// emissary only uses X25519 but destinations it creates advertise ElGamal in the key
+    /// certificate so Java I2P and i2pd expect a 256-byte key.
+    fn encryption_key_len(destination: &Destination) -> usize {
+        match destination.serialized().get(CRYPTO_TYPE_OFFSET..CRYPTO_TYPE_OFFSET + 2) {
+            Some(&[high, low]) if u16::from_be_bytes([high, low]) == CRYPTO_TYPE_X25519 =>
+                X25519_PRIVATE_KEY_LEN,
+            _ => ELGAMAL_PRIVATE_KEY_LEN,
+        }
+    }
+
+    /// Parse [`DestinationKeys`] from `bytes`, which are in `format`.
+    pub fn parse(bytes: impl AsRef<[u8]>, format: KeyFormat) -> crate::Result<Self> {
+        let decoded = match format {
+            KeyFormat::Emissary => std::str::from_utf8(bytes.as_ref())
+                .ok()
+                .and_then(|key| base64_decode(key.trim()))
+                .ok_or(Error::InvalidData)?,
+            KeyFormat::Java => bytes.as_ref().to_vec(),
+        };
+
+        let destination = Destination::parse(&decoded).ok_or(Error::InvalidData)?;
+        let rest = &decoded[destination.serialized_len()..];
+
+        let encryption_key_len = match format {
+            KeyFormat::Emissary => X25519_PRIVATE_KEY_LEN,
+            KeyFormat::Java => Self::encryption_key_len(&destination),
+        };
+
+        if rest.len() < encryption_key_len + SIGNING_PRIVATE_KEY_LEN {
+            return Err(Error::InvalidData);
+        }
+
+        // keys written by emissary store the x25519 key at the start of the encryption key slot
+        // and zero the rest of it so anything else in the slot is a real ElGamal key
+        let encryption_key = &rest[..encryption_key_len];
+        let private_key: [u8; X25519_PRIVATE_KEY_LEN] =
+            encryption_key[..X25519_PRIVATE_KEY_LEN].try_into().expect("to succeed");
+        let elgamal_key = encryption_key[X25519_PRIVATE_KEY_LEN..]
+            .iter()
+            .any(|byte| *byte != 0)
+            .then(|| encryption_key.to_vec());
+        let signing_key: [u8; SIGNING_PRIVATE_KEY_LEN] = rest
+            [encryption_key_len..encryption_key_len + SIGNING_PRIVATE_KEY_LEN]
+            .try_into()
+            .expect("to succeed");
+
+        // verify that the signing key belongs to the destination
+        let public_key =
+            SigningPrivateKey::from_bytes(&signing_key).ok_or(Error::InvalidData)?.public();
+
+        if public_key.as_ref() != destination.verifying_key().as_ref() {
+            return Err(Error::Custom(
+                "signing key doesn't match destination".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            destination,
+            elgamal_key,
+            private_key,
+            signing_key,
+        })
+    }
+
+    /// Parse [`DestinationKeys`] from `bytes`, detecting the format from contents.
+    pub fn parse_any(bytes: impl AsRef<[u8]>) -> crate::Result<Self> {
+        Self::parse(bytes.as_ref(), KeyFormat::Emissary)
+            .or_else(|_| Self::parse(bytes.as_ref(), KeyFormat::Java))
+    }
+
+    /// Serialize [`DestinationKeys`] into `format`.
+    ///
+    /// Fails if the keys contain an ElGamal private key and `format` can't store it.
+    pub fn serialize(&self, format: KeyFormat) -> crate::Result<Vec<u8>> {
+        let mut out = self.destination.serialized().to_vec();
+
+        match format {
+            KeyFormat::Emissary => {
+                if self.elgamal_key.is_some() {
+                    return Err(Error::Custom(
+                        "elgamal private key cannot be stored in emissary format".to_string(),
+                    ));
+                }
+
+                out.extend_from_slice(&self.private_key);
+                out.extend_from_slice(&self.signing_key);
+
+                Ok(base64_encode(out).into_bytes())
+            }
+            KeyFormat::Java => {
+                match &self.elgamal_key {
+                    Some(elgamal_key) => out.extend_from_slice(elgamal_key),
+                    None => {
+                        // the x25519 key is stored at the start of the encryption key and if the
+                        // slot is for a longer key, the rest of it is zeroed
+                        let mut private_key =
+                            vec![0u8; Self::encryption_key_len(&self.destination)];
+                        private_key[..X25519_PRIVATE_KEY_LEN].copy_from_slice(&self.private_key);
+
+                        out.extend_from_slice(&private_key);
+                    }
+                }
+                out.extend_from_slice(&self.signing_key);
+
+                Ok(out)
+            }
+        }
+    }
+
+    /// Get base32 address of the destination.
+    pub fn base32(&self) -> String {
+        format!("{}.b32.i2p", base32_encode(self.destination.id().to_vec()))
+    }
+
+    /// Get base64 of the destination.
+    pub fn base64(&self) -> String {
+        base64_encode(self.destination.serialized())
+    }
+}
+
+/// Read private key from `path`.
+fn read_keys(path: &Path, format: Option<KeyFormat>) -> crate::Result<DestinationKeys> {
+    let contents = std::fs::read(path)?;
+
+    match format {
+        Some(format) => DestinationKeys::parse(contents, format),
+        None => DestinationKeys::parse_any(contents),
+    }
+}
+
+/// Execute destination `command`.
+pub fn run(command: DestinationCommand) -> crate::Result<()> {
+    match command {
+        DestinationCommand::Generate { output, format } => {
+            let keys = DestinationKeys::generate();
+
+            match output {
+                Some(path) => {
+                    std::fs::write(&path, keys.serialize(format)?)?;
+
+                    println!("private key written to {}", path.display());
+                    println!("b32: {}", keys.base32());
+                }
+                None => match format {
+                    KeyFormat::Emissary => {
+                        println!("b32: {}", keys.base32());
+                        println!("b64: {}", keys.base64());
+                        println!(
+                            "private key: {}",
+                            String::from_utf8(keys.serialize(format)?).expect("to succeed")
+                        );
+                    }
+                    KeyFormat::Java =>
+                        return Err(Error::Custom(
+                            "binary private key requires an output file".to_string(),
+                        )),
+                },
+            }
+        }
+        DestinationCommand::Show { file, format } => {
+            let keys = read_keys(&file, format)?;
+
+            println!("b32: {}", keys.base32());
+            println!("b64: {}", keys.base64());
+        }
+        DestinationCommand::Convert { input, output, to } => {
+            let keys = read_keys(&input, None)?;
+            std::fs::write(&output, keys.serialize(to)?)?;
+
+            println!("private key written to {}", output.display());
+            println!("b32: {}", keys.base32());
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::RngCore;
+
+    #[test]
+    fn emissary_format_round_trip() {
+        let keys = DestinationKeys::generate();
+        let parsed = DestinationKeys::parse(
+            keys.serialize(KeyFormat::Emissary).unwrap(),
+            KeyFormat::Emissary,
+        )
+        .unwrap();
+
+        assert_eq!(keys.base32(), parsed.base32());
+        assert_eq!(keys.base64(), parsed.base64());
+        assert_eq!(keys.private_key, parsed.private_key);
+        assert_eq!(keys.signing_key, parsed.signing_key);
+    }
+
+    #[test]
+    fn convert_between_formats() {
+        let keys = DestinationKeys::generate();
+
+        let java = keys.serialize(KeyFormat::Java).unwrap();
+        assert_eq!(
+            java.len(),
+            keys.destination.serialized_len() + ELGAMAL_PRIVATE_KEY_LEN + SIGNING_PRIVATE_KEY_LEN
+        );
+
+        let parsed = DestinationKeys::parse_any(&java).unwrap();
+        assert_eq!(keys.base32(), parsed.base32());
+        assert_eq!(keys.private_key, parsed.private_key);
+        assert_eq!(keys.signing_key, parsed.signing_key);
+
+        let emissary = parsed.serialize(KeyFormat::Emissary).unwrap();
+        assert_eq!(emissary, keys.serialize(KeyFormat::Emissary).unwrap());
+    }
+
+    #[test]
+    fn elgamal_key_carried_through() {
+        let keys = DestinationKeys::generate();
+
+        // java key file with a real elgamal key in the encryption key slot
+        let mut elgamal_key = [0u8; ELGAMAL_PRIVATE_KEY_LEN];
+        OsRng.fill_bytes(&mut elgamal_key);
+
+        let mut java = keys.destination.serialized().to_vec();
+        java.extend_from_slice(&elgamal_key);
+        java.extend_from_slice(&keys.signing_key);
+
+        let parsed = DestinationKeys::parse(&java, KeyFormat::Java).unwrap();
+        assert_eq!(parsed.elgamal_key.as_deref(), Some(&elgamal_key[..]));
+        assert_eq!(keys.base32(), parsed.base32());
+
+        // the key survives a round trip but can't be converted into emissary format
+        assert_eq!(parsed.serialize(KeyFormat::Java).unwrap(), java);
+        assert!(parsed.serialize(KeyFormat::Emissary).is_err());
+    }
+
+    #[test]
+    fn mismatching_signing_key() {
+        let keys = DestinationKeys::generate();
+        let other = DestinationKeys::generate();
+
+        let mut serialized = keys.destination.serialized().to_vec();
+        serialized.extend_from_slice(&keys.private_key);
+        serialized.extend_from_slice(&other.signing_key);
+
+        assert!(DestinationKeys::parse(base64_encode(serialized), KeyFormat::Emissary).is_err());
+    }
+
+    #[test]
+    fn invalid_private_key() {
+        assert!(DestinationKeys::parse_any("hello, world").is_err());
+        assert!(DestinationKeys::parse_any([0u8; 128]).is_err());
+    }
+}
@@ -0,0 +1,42 @@
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Subcommands which don't start the router.
+
+use crate::cli::Command;
+
+use std::path::PathBuf;
+
+#[cfg(unix)]
+mod control;
+mod destination;
+mod reseed;
+mod router_info;
+
+/// Execute `command`.
+///
+/// `base_path` is used to locate the control socket and `netDb` of a running router.
+pub fn run(command: Command, base_path: Option<PathBuf>) -> crate::Result<()> {
+    match command {
+        Command::Dest(command) => destination::run(command),
+        Command::RouterInfo(command) => router_info::run(command),
+        Command::Reseed(command) => reseed::run(command, base_path),
+        #[cfg(unix)]
+        Command::Ctl { socket, command } => control::run(command, socket, base_path),
+    }
+}
@@ -0,0 +1,460 @@
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Reseed bundle creation and reseed server.
+
+use crate::{
+    cli::{BundleOptions, ReseedCommand},
+    error::Error,
+};
+
+use emissary_core::primitives::RouterInfo;
+use emissary_util::su3::{ReseedRouterInfo, Su3, Su3Signer};
+use home::home_dir;
+use parking_lot::RwLock;
+use rand::seq::SliceRandom;
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::TcpListener,
+};
+use tokio_native_tls::{native_tls, TlsAcceptor};
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+/// Logging target for the file.
+const LOG_TARGET: &str = "emissary::reseed";
+
+/// Path of the reseed bundle.
+const BUNDLE_PATH: &str = "/i2pseeds.su3";
+
+/// Maximum size of a request.
+const MAX_REQUEST_SIZE: usize = 8192usize;
+
+/// Timeout for reading a request and writing the response.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long to wait before accepting new connections after accepting a connection failed.
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Signer of reseed bundles.
+struct BundleSigner {
+    /// Number of router infos in a bundle.
+    count: usize,
+
+    /// Path to `netDb`.
+    netdb: PathBuf,
+
+    /// Signer ID.
+    signer_id: String,
+
+    /// Signing key.
+    signer: Su3Signer,
+}
+
+impl BundleSigner {
+    /// Create new [`BundleSigner`] from `options`.
+    ///
+    /// If `netDb` was not specified, it's resolved from `base_path` or from the default base path.
+    fn new(options: BundleOptions, base_path: Option<PathBuf>) -> crate::Result<Self> {
+        let BundleOptions {
+            key,
+            signer_id,
+            netdb,
+            count,
+        } = options;
+
+        let netdb = match (netdb, base_path) {
+            (Some(netdb), _) => netdb,
+            (None, Some(base_path)) => base_path.join("netDb"),
+            (None, None) => home_dir()
+                .filter(|path| !path.as_os_str().is_empty())
+                .map(|path| path.join(".emissary").join("netDb"))
+                .ok_or(Error::Custom(String::from("couldn't resolve base path")))?,
+        };
+        let signer = Su3Signer::from_pem(&fs::read_to_string(&key)?)
+            .map_err(|error| Error::Custom(error.to_string()))?;
+
+        Ok(Self {
+            count,
+            netdb,
+            signer_id,
+            signer,
+        })
+    }
+
+    /// Create new signed reseed bundle from a random subset of reachable routers of `netDb`.
+    fn create_bundle(&self) -> crate::Result<Vec<u8>> {
+        let mut router_infos = load_router_infos(&self.netdb);
+
+        if router_infos.is_empty() {
+            return Err(Error::Custom(format!(
+                "no reachable routers found from {}",
+                self.netdb.display()
+            )));
+        }
+
+        router_infos.shuffle(&mut rand::thread_rng());
+        router_infos.truncate(self.count);
+
+        tracing::info!(
+            target: LOG_TARGET,
+            signer_id = %self.signer_id,
+            num_routers = ?router_infos.len(),
+            "create reseed bundle",
+        );
+
+        Su3::create_reseed(&self.signer_id, &self.signer, &router_infos)
+            .map_err(|error| Error::Custom(error.to_string()))
+    }
+}
+
+/// Load router infos of reachable routers from `netdb`.
+fn load_router_infos(netdb: &Path) -> Vec<ReseedRouterInfo> {
+    let Ok(router_dir) = fs::read_dir(netdb) else {
+        return Vec::new();
+    };
+
+    router_dir
+        .into_iter()
+        .filter_map(|entry| {
+            let dir = entry.ok()?.path();
+
+            if !dir.is_dir() {
+                return None;
+            }
+
+            Some(
+                fs::read_dir(dir)
+                    .ok()?
+                    .filter_map(|entry| {
+                        let file_path = entry.ok()?.path();
+                        let name = file_path.file_name()?.to_str()?.to_string();
+
+                        if !file_path.is_file() || !name.starts_with("routerInfo-") {
+                            return None;
+                        }
+
+                        let router_info = fs::read(&file_path).ok()?;
+
+                        RouterInfo::parse(&router_info)?
+                            .is_reachable()
+                            .then_some(ReseedRouterInfo { name, router_info })
+                    })
+                    .collect::<Vec<_>>(),
+            )
+        })
+        .flatten()
+        .collect()
+}
+
+/// Read HTTP request from `stream` and respond with `bundle` if it was requested.
+async fn handle_request<S: AsyncRead + AsyncWrite + Unpin>(
+    mut stream: S,
+    bundle: Arc<Vec<u8>>,
+) -> crate::Result<()> {
+    let mut request = Vec::new();
+    let mut buffer = [0u8; 1024];
+
+    let path = loop {
+        let nread = stream.read(&mut buffer).await?;
+
+        if nread == 0 {
+            return Err(Error::Io(std::io::ErrorKind::UnexpectedEof.into()));
+        }
+        request.extend_from_slice(&buffer[..nread]);
+
+        let mut headers = [httparse::EMPTY_HEADER; 64];
+        let mut parsed = httparse::Request::new(&mut headers);
+
+        match parsed.parse(&request) {
+            Ok(httparse::Status::Complete(_)) =>
+                break parsed.path.ok_or(Error::InvalidData)?.to_string(),
+            Ok(httparse::Status::Partial) if request.len() < MAX_REQUEST_SIZE => {}
+            _ => return Err(Error::InvalidData),
+        }
+    };
+
+    // the query, e.g., `?netid=2`, is ignored
+    match path.split('?').next() {
+        Some(BUNDLE_PATH) => {
+            stream
+                .write_all(
+                    format!(
+                        "HTTP/1.1 200 OK\r\n\
+                         Content-Type: application/octet-stream\r\n\
+                         Content-Length: {}\r\n\
+                         Connection: close\r\n\r\n",
+                        bundle.len()
+                    )
+                    .as_bytes(),
+                )
+                .await?;
+            stream.write_all(&bundle).await?;
+        }
+        _ => {
+            stream
+                .write_all(
+                    b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                )
+                .await?;
+        }
+    }
+
+    stream.shutdown().await?;
+
+    Ok(())
+}
+
+/// Create new reseed bundle with `signer` on the blocking thread pool.
+///
+/// Reading `netDb` and signing the bundle would otherwise stall the tasks serving requests.
+async fn create_bundle(signer: Arc<BundleSigner>) -> crate::Result<Vec<u8>> {
+    tokio::task::spawn_blocking(move || signer.create_bundle())
+        .await
+        .map_err(|error| Error::Custom(error.to_string()))?
+}
+
+/// Serve reseed bundles created by `signer` to clients connecting to `listener`.
+///
+/// A new bundle is created every `refresh_interval`.
+async fn serve(
+    listener: TcpListener,
+    signer: BundleSigner,
+    tls: Option<TlsAcceptor>,
+    refresh_interval: Duration,
+) -> crate::Result<()> {
+    let signer = Arc::new(signer);
+    let bundle = Arc::new(RwLock::new(Arc::new(
+        create_bundle(Arc::clone(&signer)).await?,
+    )));
+
+    tokio::spawn({
+        let bundle = Arc::clone(&bundle);
+
+        async move {
+            loop {
+                tokio::time::sleep(refresh_interval).await;
+
+                match create_bundle(Arc::clone(&signer)).await {
+                    Ok(new_bundle) => *bundle.write() = Arc::new(new_bundle),
+                    Err(error) => tracing::warn!(
+                        target: LOG_TARGET,
+                        ?error,
+                        "failed to create reseed bundle",
+                    ),
+                }
+            }
+        }
+    });
+
+    tracing::info!(
+        target: LOG_TARGET,
+        address = ?listener.local_addr()?,
+        tls = ?tls.is_some(),
+        "reseed server started",
+    );
+
+    loop {
+        let (stream, address) = match listener.accept().await {
+            Ok(connection) => connection,
+            Err(error) => {
+                tracing::debug!(
+                    target: LOG_TARGET,
+                    ?error,
+                    "failed to accept connection",
+                );
+
+                // back off so running out of file descriptors doesn't turn into a busy loop
+                tokio::time::sleep(ACCEPT_BACKOFF).await;
+                continue;
+            }
+        };
+        let bundle = Arc::clone(&bundle.read());
+        let tls = tls.clone();
+
+        tokio::spawn(async move {
+            let result = tokio::time::timeout(REQUEST_TIMEOUT, async move {
+                match tls {
+                    None => handle_request(stream, bundle).await,
+                    Some(tls) => match tls.accept(stream).await {
+                        Ok(stream) => handle_request(stream, bundle).await,
+                        Err(error) => Err(Error::Custom(error.to_string())),
+                    },
+                }
+            })
+            .await;
+
+            match result {
+                Ok(Ok(())) => tracing::debug!(
+                    target: LOG_TARGET,
+                    ?address,
+                    "reseed request served",
+                ),
+                Ok(Err(error)) => tracing::debug!(
+                    target: LOG_TARGET,
+                    ?address,
+                    ?error,
+                    "failed to serve reseed request",
+                ),
+                Err(_) => tracing::debug!(
+                    target: LOG_TARGET,
+                    ?address,
+                    "reseed request timed out",
+                ),
+            }
+        });
+    }
+}
+
+/// Execute reseed `command`.
+pub fn run(command: ReseedCommand, base_path: Option<PathBuf>) -> crate::Result<()> {
+    match command {
+        ReseedCommand::Bundle { options, output } => {
+            let bundle = BundleSigner::new(options, base_path)?.create_bundle()?;
+
+            fs::write(&output, bundle)?;
+            println!("reseed bundle written to {}", output.display());
+
+            Ok(())
+        }
+        ReseedCommand::Serve {
+            options,
+            address,
+            tls_cert,
+            tls_key,
+            refresh_interval,
+        } => {
+            // the reseed server is long-running so initialize the default logger for it
+            let _ = crate::init_logger!(None);
+
+            let signer = BundleSigner::new(options, base_path)?;
+            let tls = match (tls_cert, tls_key) {
+                (Some(cert), Some(key)) => {
+                    let identity =
+                        native_tls::Identity::from_pkcs8(&fs::read(cert)?, &fs::read(key)?)
+                            .map_err(|error| Error::Custom(error.to_string()))?;
+
+                    Some(TlsAcceptor::from(
+                        native_tls::TlsAcceptor::new(identity)
+                            .map_err(|error| Error::Custom(error.to_string()))?,
+                    ))
+                }
+                _ => None,
+            };
+
+            tokio::runtime::Runtime::new()?.block_on(async move {
+                serve(
+                    TcpListener::bind(address).await?,
+                    signer,
+                    tls,
+                    Duration::from_secs(refresh_interval.max(1) * 60),
+                )
+                .await
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::SigningKey;
+    use rand::rngs::OsRng;
+
+    const SU3: &[u8] = include_bytes!("../../../emissary-util/assets/i2pseeds.su3");
+
+    /// Create `netDb` with the router infos of the bundled reseed file.
+    fn make_netdb() -> (tempfile::TempDir, usize) {
+        let dir = tempfile::tempdir().unwrap();
+        let router_infos = Su3::parse_reseed(SU3, true).unwrap();
+        let mut num_reachable = 0usize;
+
+        for (i, ReseedRouterInfo { name, router_info }) in router_infos.into_iter().enumerate() {
+            let path = dir.path().join(format!("r{}", i % 3));
+            fs::create_dir_all(&path).unwrap();
+
+            if RouterInfo::parse(&router_info).is_some_and(|info| info.is_reachable()) {
+                num_reachable += 1;
+            }
+            fs::write(path.join(name), router_info).unwrap();
+        }
+
+        // files which are not router infos are ignored
+        fs::write(dir.path().join("r0/routerInfo-invalid.dat"), vec![1u8; 128]).unwrap();
+        fs::write(dir.path().join("r1/profile.toml"), vec![1u8; 128]).unwrap();
+
+        (dir, num_reachable)
+    }
+
+    fn make_signer(netdb: &Path, count: usize) -> BundleSigner {
+        BundleSigner {
+            count,
+            netdb: netdb.to_path_buf(),
+            signer_id: String::from("test@mail.i2p"),
+            signer: Su3Signer::Ed25519ph(SigningKey::generate(&mut OsRng)),
+        }
+    }
+
+    #[test]
+    fn bundle_created_from_netdb() {
+        let (dir, num_reachable) = make_netdb();
+        assert!(num_reachable > 5);
+
+        let bundle = make_signer(dir.path(), 5).create_bundle().unwrap();
+        assert_eq!(Su3::parse_reseed(&bundle, false).unwrap().len(), 5);
+
+        let bundle = make_signer(dir.path(), 1000).create_bundle().unwrap();
+        assert_eq!(
+            Su3::parse_reseed(&bundle, false).unwrap().len(),
+            num_reachable
+        );
+
+        // empty netdb
+        let dir = tempfile::tempdir().unwrap();
+        assert!(make_signer(dir.path(), 5).create_bundle().is_err());
+    }
+
+    #[tokio::test]
+    async fn bundle_served_over_http() {
+        let (dir, _) = make_netdb();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        tokio::spawn(serve(
+            listener,
+            make_signer(dir.path(), 10),
+            None,
+            Duration::from_secs(3600),
+        ));
+
+        let response =
+            reqwest::get(format!("http://{address}/i2pseeds.su3?netid=2")).await.unwrap();
+        assert!(response.status().is_success());
+
+        let bundle = response.bytes().await.unwrap();
+        assert_eq!(Su3::parse_reseed(&bundle, false).unwrap().len(), 10);
+
+        let response = reqwest::get(format!("http://{address}/hosts.txt")).await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,62 @@
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Router info inspection.
+
+use crate::{cli::RouterInfoCommand, error::Error};
+
+use emissary_core::{crypto::base64_encode, primitives::RouterInfo};
+
+/// Execute router info `command`.
+pub fn run(command: RouterInfoCommand) -> crate::Result<()> {
+    match command {
+        RouterInfoCommand::Dump { file } => {
+            let router_info = RouterInfo::parse(std::fs::read(&file)?).ok_or(Error::InvalidData)?;
+
+            println!(
+                "router hash: {}",
+                base64_encode(router_info.identity.hash())
+            );
+            println!("published: {}", router_info.published.date());
+            println!("net id: {}", router_info.net_id);
+            println!("capabilities: {}", router_info.capabilities);
+            println!("floodfill: {}", router_info.is_floodfill());
+            println!("reachable: {}", router_info.is_reachable());
+
+            for (transport, address) in &router_info.addresses {
+                println!("address {transport:?}:");
+                println!("    cost: {}", address.cost);
+
+                if let Some(socket_address) = address.socket_address {
+                    println!("    socket address: {socket_address}");
+                }
+
+                for (key, value) in address.options.iter() {
+                    println!("    {key}={value}");
+                }
+            }
+
+            println!("options:");
+            for (key, value) in router_info.options.iter() {
+                println!("    {key}={value}");
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -22,6 +22,7 @@ use crate::{
     LOG_TARGET,
 };
 
+use emissary_util::news::parse_blocklist;
 use home::home_dir;
 use rand::{rngs::OsRng, thread_rng, Rng, RngCore};
 use serde::{Deserialize, Serialize};
@@ -59,6 +60,8 @@ pub struct Profile {
     num_lookup_successes: Option<usize>,
     num_rejected: Option<usize>,
     num_selected: Option<usize>,
+    num_store_failures: Option<usize>,
+    num_store_successes: Option<usize>,
     num_test_failures: Option<usize>,
     num_test_successes: Option<usize>,
     num_unaswered: Option<usize>,
@@ -80,6 +83,8 @@ impl From<emissary_core::Profile> for Profile {
             num_lookup_successes: Some(profile.num_lookup_successes),
             num_rejected: Some(profile.num_rejected),
             num_selected: Some(profile.num_selected),
+            num_store_failures: Some(profile.num_store_failures),
+            num_store_successes: Some(profile.num_store_successes),
             num_test_failures: Some(profile.num_test_failures),
             num_test_successes: Some(profile.num_test_successes),
             num_unaswered: Some(profile.num_unaswered),
@@ -124,43 +129,143 @@ pub struct SamConfig {
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ReseedConfig {
+    pub certificate_dir: Option<PathBuf>,
+    pub certificates: Option<Vec<PathBuf>>,
+    pub file: Option<PathBuf>,
     pub hosts: Option<Vec<String>>,
+    pub proxy: Option<String>,
     pub reseed_threshold: usize,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct HttpProxyConfig {
     pub port: u16,
     pub host: String,
     pub outproxy: Option<String>,
+    pub jump_services: Option<Vec<String>>,
+    #[serde(rename = "eepsite-headers")]
+    pub eepsite_headers: Option<HeaderPolicyConfig>,
+    #[serde(rename = "outproxy-headers")]
+    pub outproxy_headers: Option<HeaderPolicyConfig>,
+    pub isolation: Option<IsolationMode>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum IsolationMode {
+    /// All requests use the same destination.
+    #[default]
+    None,
+
+    /// Each host is visited with a separate destination.
+    Host,
+
+    /// Each first-party host and the resources it loads use a separate destination.
+    FirstParty,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HeaderPolicyConfig {
+    pub allow: Option<Vec<String>>,
+    pub deny: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct SocksProxyConfig {
     pub port: u16,
     pub host: String,
+    pub outproxy: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BanlistConfig {
+    pub blocklist: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NewsConfig {
+    pub certificate_dir: Option<PathBuf>,
+    pub certificates: Option<Vec<PathBuf>>,
+    pub refresh_interval: Option<u64>,
+    pub url: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct AddressBookConfig {
     pub default: Option<String>,
     pub subscriptions: Option<Vec<String>>,
+    pub refresh_interval: Option<u64>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ClientTunnelConfig {
     pub name: String,
     pub address: Option<String>,
     pub port: u16,
     pub destination: String,
     pub destination_port: Option<u16>,
+    pub inbound: Option<TunnelOptions>,
+    pub outbound: Option<TunnelOptions>,
+    pub keys: Option<String>,
+    pub shared: Option<bool>,
+    #[serde(rename = "type")]
+    pub kind: Option<ClientTunnelKind>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ClientTunnelKind {
+    /// Streams are relayed to the destination as-is.
+    #[default]
+    Client,
+
+    /// Streams are IRC connections whose messages are filtered.
+    Irc,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ServerTunnelKind {
+    /// Inbound streams are forwarded to the server as-is.
+    #[default]
+    Server,
+
+    /// Inbound streams are HTTP requests whose headers are rewritten.
+    Http,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TunnelOptions {
+    pub length: Option<usize>,
+    pub quantity: Option<usize>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ServerTunnelConfig {
     pub name: String,
     pub port: u16,
+    #[serde(default)]
     pub destination_path: String,
+    #[serde(rename = "type")]
+    pub kind: Option<ServerTunnelKind>,
+    pub address: Option<String>,
+    pub inbound: Option<TunnelOptions>,
+    pub outbound: Option<TunnelOptions>,
+    pub max_connections: Option<usize>,
+    pub max_connections_per_destination: Option<usize>,
+    pub allow_list: Option<Vec<String>>,
+    pub deny_list: Option<Vec<String>>,
+    pub keys: Option<String>,
+    pub inport: Option<u16>,
+}
+
+impl ServerTunnelConfig {
+    /// Get path of the private key of the tunnel's destination, relative to base path.
+    ///
+    /// Server tunnels which share a destination use `keys` instead of `destination_path`.
+    pub fn key_path(&self) -> &str {
+        self.keys.as_deref().unwrap_or(&self.destination_path)
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -207,6 +312,7 @@ struct EmissaryConfig {
     address_book: Option<AddressBookConfig>,
     #[serde(default)]
     allow_local: bool,
+    banlist: Option<BanlistConfig>,
     caps: Option<String>,
     exploratory: Option<ExploratoryConfig>,
     #[serde(default)]
@@ -221,6 +327,7 @@ struct EmissaryConfig {
     log: Option<String>,
     metrics: Option<MetricsConfig>,
     net_id: Option<u8>,
+    news: Option<NewsConfig>,
     ntcp2: Option<Ntcp2Config>,
     #[serde(rename = "port-forwarding")]
     port_forwarding: Option<PortForwardingConfig>,
@@ -244,12 +351,17 @@ impl Default for EmissaryConfig {
                     "http://udhdrtrcetjm5sxzskjyr5ztpeszydbh4dpl3pl4utgqqw2v4jna.b32.i2p/hosts.txt",
                 )),
                 subscriptions: None,
+                refresh_interval: None,
             }),
             caps: Some(String::from("XR")),
             http_proxy: Some(HttpProxyConfig {
                 host: "127.0.0.1".to_string(),
                 port: 4444u16,
                 outproxy: None,
+                jump_services: None,
+                eepsite_headers: None,
+                outproxy_headers: None,
+                isolation: None,
             }),
             socks_proxy: None,
             i2cp: Some(I2cpConfig {
@@ -276,8 +388,12 @@ impl Default for EmissaryConfig {
                 name: String::from("emissary"),
             }),
             reseed: Some(ReseedConfig {
+                certificate_dir: None,
+                certificates: None,
                 reseed_threshold: 25usize,
                 hosts: None,
+                file: None,
+                proxy: None,
             }),
             router_ui: Some(RouterUiConfig {
                 theme: Theme::Dark,
@@ -298,6 +414,8 @@ impl Default for EmissaryConfig {
             insecure_tunnels: false,
             log: None,
             net_id: None,
+            news: None,
+            banlist: None,
             ssu2: None,
             client_tunnels: None,
             server_tunnels: None,
@@ -316,6 +434,9 @@ pub struct Config {
     /// Base path.
     pub base_path: PathBuf,
 
+    /// Blocklist entries.
+    pub blocklist: Vec<emissary_core::BlocklistEntry>,
+
     /// Router capabilities.
     pub caps: Option<String>,
 
@@ -346,6 +467,9 @@ pub struct Config {
     /// Network ID.
     pub net_id: Option<u8>,
 
+    /// News feed config.
+    pub news: Option<NewsConfig>,
+
     /// NTCP2 config.
     pub ntcp2_config: Option<emissary_core::Ntcp2Config>,
 
@@ -389,10 +513,30 @@ pub struct Config {
     pub transit: Option<emissary_core::TransitConfig>,
 }
 
+/// Subset of [`Config`] which can be reloaded while the router is running.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ReloadableConfig {
+    /// Address book config.
+    pub address_book: Option<AddressBookConfig>,
+
+    /// Client tunnel configurations.
+    pub client_tunnels: Vec<ClientTunnelConfig>,
+
+    /// HTTP proxy config.
+    pub http_proxy: Option<HttpProxyConfig>,
+
+    /// Server tunnel configurations.
+    pub server_tunnels: Vec<ServerTunnelConfig>,
+
+    /// SOCKS proxy config.
+    pub socks_proxy: Option<SocksProxyConfig>,
+}
+
 impl From<Config> for emissary_core::Config {
     fn from(val: Config) -> Self {
         emissary_core::Config {
             allow_local: val.allow_local,
+            blocklist: val.blocklist,
             caps: val.caps,
             exploratory: val.exploratory,
             floodfill: val.floodfill,
@@ -415,6 +559,18 @@ impl From<Config> for emissary_core::Config {
 }
 
 impl Config {
+    /// Get mutable reference to reseed config, creating the default config if it doesn't exist.
+    fn reseed_mut(&mut self) -> &mut ReseedConfig {
+        self.reseed.get_or_insert(ReseedConfig {
+            certificate_dir: None,
+            certificates: None,
+            file: None,
+            hosts: None,
+            proxy: None,
+            reseed_threshold: 25usize,
+        })
+    }
+
     /// Attemp to parse configuration from `path` and merge config with `arguments`.
     ///
     /// If the configuratin file exists but it's invalid, exit early, unless `--overwrite-config`
@@ -543,6 +699,44 @@ impl Config {
         Ok(config)
     }
 
+    /// Re-read `router.toml` from `path` and return the parts of the configuration which can be
+    /// applied to a running router.
+    ///
+    /// Proxy options given as cli arguments are merged with the reloaded configuration, the same
+    /// way as in [`Config::parse()`].
+    pub fn reload(
+        path: &Path,
+        http_proxy: &HttpProxyOptions,
+        socks_proxy: &SocksProxyOptions,
+    ) -> crate::Result<ReloadableConfig> {
+        let config = Self::load_router_config(path.to_path_buf())?;
+        Self::validate_tunnels(&config)?;
+
+        let mut reloaded = ReloadableConfig {
+            address_book: config.address_book,
+            client_tunnels: config.client_tunnels.unwrap_or_default(),
+            http_proxy: config.http_proxy,
+            server_tunnels: config.server_tunnels.unwrap_or_default(),
+            socks_proxy: config.socks_proxy,
+        };
+
+        Self::merge_http_proxy(&mut reloaded.http_proxy, http_proxy);
+        Self::merge_socks_proxy(&mut reloaded.socks_proxy, socks_proxy);
+
+        Ok(reloaded)
+    }
+
+    /// Get the reloadable parts of [`Config`].
+    pub fn reloadable(&self) -> ReloadableConfig {
+        ReloadableConfig {
+            address_book: self.address_book.clone(),
+            client_tunnels: self.client_tunnels.clone(),
+            http_proxy: self.http_proxy.clone(),
+            server_tunnels: self.server_tunnels.clone(),
+            socks_proxy: self.socks_proxy.clone(),
+        }
+    }
+
     /// Create static key.
     fn create_static_key(base_path: PathBuf) -> crate::Result<[u8; 32]> {
         let key = x25519_dalek::StaticSecret::random();
@@ -730,6 +924,7 @@ impl Config {
             address_book: config.address_book,
             allow_local: config.allow_local,
             base_path,
+            blocklist: Vec::new(),
             caps: config.caps,
             client_tunnels: config.client_tunnels.unwrap_or(Vec::new()),
             exploratory: config.exploratory.map(|config| emissary_core::ExploratoryConfig {
@@ -750,6 +945,7 @@ impl Config {
                 .metrics
                 .map(|config| emissary_core::MetricsConfig { port: config.port }),
             net_id: config.net_id,
+            news: config.news,
             ntcp2_config: Some(emissary_core::Ntcp2Config {
                 port: config.ntcp2.as_ref().expect("ntcp").port,
                 host: None,
@@ -803,73 +999,15 @@ impl Config {
             }
         };
 
-        if let Some(tunnels) = &config.client_tunnels {
-            // ensure each client tunnel has a unique name
-            if tunnels.iter().map(|config| &config.name).collect::<HashSet<_>>().len()
-                != tunnels.len()
-            {
-                tracing::warn!(
-                    target: LOG_TARGET,
-                    "all client tunnels must have a unique name",
-                );
-                return Err(Error::InvalidData);
-            }
-
-            // ensure each client tunnel has a unique port
-            if tunnels.iter().map(|config| config.port).collect::<HashSet<_>>().len()
-                != tunnels.len()
-            {
-                tracing::warn!(
-                    target: LOG_TARGET,
-                    "all client tunnels must have a unique port",
-                );
-                return Err(Error::InvalidData);
-            }
-        }
-
-        if let Some(tunnels) = &config.server_tunnels {
-            // ensure each server tunnel has a unique name
-            if tunnels.iter().map(|config| &config.name).collect::<HashSet<_>>().len()
-                != tunnels.len()
-            {
-                tracing::warn!(
-                    target: LOG_TARGET,
-                    "all server tunnels must have a unique name",
-                );
-                return Err(Error::InvalidData);
-            }
-
-            // ensure each server tunnel has a unique port
-            if tunnels.iter().map(|config| config.port).collect::<HashSet<_>>().len()
-                != tunnels.len()
-            {
-                tracing::warn!(
-                    target: LOG_TARGET,
-                    "all server tunnels must have a unique port",
-                );
-                return Err(Error::InvalidData);
-            }
-
-            // ensure each server tunnel has a unique path
-            if tunnels
-                .iter()
-                .map(|config| config.destination_path.clone())
-                .collect::<HashSet<_>>()
-                .len()
-                != tunnels.len()
-            {
-                tracing::warn!(
-                    target: LOG_TARGET,
-                    "all server tunnels must have a destination path",
-                );
-                return Err(Error::InvalidData);
-            }
-        }
+        Self::validate_tunnels(&config)?;
+        Self::validate_news(&config)?;
+        let blocklist = Self::load_blocklist(&base_path, config.banlist.as_ref());
 
         Ok(Self {
             address_book: config.address_book,
             allow_local: config.allow_local,
             base_path,
+            blocklist,
             caps: config.caps,
             client_tunnels: config.client_tunnels.unwrap_or(Vec::new()),
             exploratory: config.exploratory.map(|config| emissary_core::ExploratoryConfig {
@@ -890,6 +1028,7 @@ impl Config {
                 .metrics
                 .map(|config| emissary_core::MetricsConfig { port: config.port }),
             net_id: config.net_id,
+            news: config.news,
             ntcp2_config: config.ntcp2.map(|config| emissary_core::Ntcp2Config {
                 port: config.port,
                 host: config.host,
@@ -925,6 +1064,153 @@ impl Config {
         })
     }
 
+    /// Check that `contents` is a valid `router.toml` without applying it.
+    #[cfg(feature = "web-ui")]
+    pub fn validate(contents: &str) -> crate::Result<()> {
+        let config = toml::from_str::<EmissaryConfig>(contents)
+            .map_err(|error| Error::Custom(format!("invalid router config: {error}")))?;
+
+        Self::validate_tunnels(&config)?;
+        Self::validate_news(&config)
+    }
+
+    /// Ensure the news feed of `config`, if enabled, has signers to verify it against.
+    ///
+    /// Certificates of the news signers are not bundled with emissary so without them, every
+    /// downloaded news feed would be rejected.
+    fn validate_news(config: &EmissaryConfig) -> crate::Result<()> {
+        let Some(news) = &config.news else {
+            return Ok(());
+        };
+
+        if news.certificates.as_ref().is_none_or(|certificates| certificates.is_empty())
+            && news.certificate_dir.is_none()
+        {
+            tracing::warn!(
+                target: LOG_TARGET,
+                "news feed enabled but neither `certificates` nor `certificate_dir` is set",
+            );
+            return Err(Error::InvalidData);
+        }
+
+        Ok(())
+    }
+
+    /// Ensure client and server tunnels of `config` have unique names, ports and paths.
+    fn validate_tunnels(config: &EmissaryConfig) -> crate::Result<()> {
+        if let Some(tunnels) = &config.client_tunnels {
+            // ensure each client tunnel has a unique name
+            if tunnels.iter().map(|config| &config.name).collect::<HashSet<_>>().len()
+                != tunnels.len()
+            {
+                tracing::warn!(
+                    target: LOG_TARGET,
+                    "all client tunnels must have a unique name",
+                );
+                return Err(Error::InvalidData);
+            }
+
+            // ensure each client tunnel has a unique port
+            if tunnels.iter().map(|config| config.port).collect::<HashSet<_>>().len()
+                != tunnels.len()
+            {
+                tracing::warn!(
+                    target: LOG_TARGET,
+                    "all client tunnels must have a unique port",
+                );
+                return Err(Error::InvalidData);
+            }
+        }
+
+        if let Some(tunnels) = &config.server_tunnels {
+            // ensure each server tunnel has a unique name
+            if tunnels.iter().map(|config| &config.name).collect::<HashSet<_>>().len()
+                != tunnels.len()
+            {
+                tracing::warn!(
+                    target: LOG_TARGET,
+                    "all server tunnels must have a unique name",
+                );
+                return Err(Error::InvalidData);
+            }
+
+            // ensure each server tunnel has a unique port
+            if tunnels.iter().map(|config| config.port).collect::<HashSet<_>>().len()
+                != tunnels.len()
+            {
+                tracing::warn!(
+                    target: LOG_TARGET,
+                    "all server tunnels must have a unique port",
+                );
+                return Err(Error::InvalidData);
+            }
+
+            // ensure each server tunnel which doesn't share its destination has a unique path
+            let unshared =
+                tunnels.iter().filter(|config| config.keys.is_none()).collect::<Vec<_>>();
+
+            if unshared.iter().any(|config| config.destination_path.is_empty())
+                || unshared
+                    .iter()
+                    .map(|config| config.destination_path.clone())
+                    .collect::<HashSet<_>>()
+                    .len()
+                    != unshared.len()
+            {
+                tracing::warn!(
+                    target: LOG_TARGET,
+                    "all server tunnels must have a destination path",
+                );
+                return Err(Error::InvalidData);
+            }
+
+            // ensure server tunnels sharing a destination have unique i2p ports
+            let shared = tunnels
+                .iter()
+                .filter_map(|config| config.keys.as_ref().map(|keys| (keys, config.inport)))
+                .collect::<Vec<_>>();
+
+            if shared.iter().collect::<HashSet<_>>().len() != shared.len() {
+                tracing::warn!(
+                    target: LOG_TARGET,
+                    "server tunnels sharing a destination must have unique `inport`s",
+                );
+                return Err(Error::InvalidData);
+            }
+
+            // shared key file cannot be used as the destination path of another tunnel
+            if shared
+                .iter()
+                .any(|(keys, _)| unshared.iter().any(|config| &&config.destination_path == keys))
+            {
+                tracing::warn!(
+                    target: LOG_TARGET,
+                    "shared destination cannot be used as a destination path",
+                );
+                return Err(Error::InvalidData);
+            }
+        }
+
+        // client and server tunnels cannot use the same destination
+        if let (Some(client_tunnels), Some(server_tunnels)) =
+            (&config.client_tunnels, &config.server_tunnels)
+        {
+            if client_tunnels
+                .iter()
+                .filter_map(|config| config.keys.as_deref())
+                .any(|keys| server_tunnels.iter().any(|config| config.key_path() == keys))
+            {
+                tracing::warn!(
+                    target: LOG_TARGET,
+                    "client tunnel cannot use the destination of a server tunnel",
+                );
+                return Err(Error::InvalidData);
+            }
+        }
+
+        Ok(())
+    }
+
     /// Attempt to load router infos.
     fn load_router_infos(path: &Path) -> Vec<Vec<u8>> {
         let Ok(router_dir) = fs::read_dir(path.join("netDb")) else {
@@ -964,6 +1250,44 @@ impl Config {
             .collect::<Vec<_>>()
     }
 
+    /// Attempt to load blocklist entries from the blocklist file specified in `config`.
+    ///
+    /// A relative path of the blocklist file is resolved against `path`.
+    fn load_blocklist(
+        path: &Path,
+        config: Option<&BanlistConfig>,
+    ) -> Vec<emissary_core::BlocklistEntry> {
+        let Some(blocklist) = config.and_then(|config| config.blocklist.as_ref()) else {
+            return Vec::new();
+        };
+        let blocklist = path.join(blocklist);
+
+        match fs::read_to_string(&blocklist) {
+            Ok(contents) => {
+                let entries = parse_blocklist(&contents);
+
+                tracing::info!(
+                    target: LOG_TARGET,
+                    ?blocklist,
+                    num_entries = ?entries.len(),
+                    "blocklist loaded",
+                );
+
+                entries
+            }
+            Err(error) => {
+                tracing::warn!(
+                    target: LOG_TARGET,
+                    ?blocklist,
+                    ?error,
+                    "failed to read blocklist",
+                );
+
+                Vec::new()
+            }
+        }
+    }
+
     /// Attempt to load router profiles.
     fn load_router_profiles(path: &Path) -> Vec<(String, emissary_core::Profile)> {
         let Ok(profile_dir) = fs::read_dir(path.join("peerProfiles")) else {
@@ -1024,6 +1348,8 @@ impl Config {
                                     num_lookup_successes: profile.num_lookup_successes.unwrap_or(0),
                                     num_rejected: profile.num_rejected.unwrap_or(0),
                                     num_selected: profile.num_selected.unwrap_or(0),
+                                    num_store_failures: profile.num_store_failures.unwrap_or(0),
+                                    num_store_successes: profile.num_store_successes.unwrap_or(0),
                                     num_test_failures: profile.num_test_failures.unwrap_or(0),
                                     num_test_successes: profile.num_test_successes.unwrap_or(0),
                                     num_unaswered: profile.num_unaswered.unwrap_or(0),
@@ -1037,6 +1363,92 @@ impl Config {
             .collect::<Vec<_>>()
     }
 
+    /// Merge HTTP proxy options given as cli arguments with HTTP proxy `config`.
+    fn merge_http_proxy(config: &mut Option<HttpProxyConfig>, options: &HttpProxyOptions) {
+        match (config.as_mut(), options) {
+            (
+                Some(config),
+                HttpProxyOptions {
+                    http_proxy_port,
+                    http_proxy_host,
+                    http_outproxy,
+                },
+            ) => {
+                if let Some(port) = http_proxy_port {
+                    config.port = *port;
+                }
+
+                if let Some(host) = &http_proxy_host {
+                    config.host = host.clone();
+                }
+
+                if let Some(outproxy) = http_outproxy {
+                    config.outproxy = Some(outproxy.clone());
+                }
+            }
+            (
+                None,
+                HttpProxyOptions {
+                    http_proxy_port: Some(port),
+                    http_proxy_host: Some(host),
+                    http_outproxy,
+                },
+            ) => {
+                *config = Some(HttpProxyConfig {
+                    port: *port,
+                    host: host.clone(),
+                    outproxy: http_outproxy.clone(),
+                    jump_services: None,
+                    eepsite_headers: None,
+                    outproxy_headers: None,
+                    isolation: None,
+                });
+            }
+            _ => {}
+        }
+    }
+
+    /// Merge SOCKS proxy options given as cli arguments with SOCKS proxy `config`.
+    fn merge_socks_proxy(config: &mut Option<SocksProxyConfig>, options: &SocksProxyOptions) {
+        match (config.as_mut(), options) {
+            (
+                Some(config),
+                SocksProxyOptions {
+                    socks_proxy_port,
+                    socks_proxy_host,
+                    socks_outproxy,
+                },
+            ) => {
+                if let Some(port) = socks_proxy_port {
+                    config.port = *port;
+                }
+
+                if let Some(host) = &socks_proxy_host {
+                    config.host = host.clone();
+                }
+
+                if let Some(outproxy) = socks_outproxy {
+                    config.outproxy = Some(outproxy.clone());
+                }
+            }
+            (
+                None,
+                SocksProxyOptions {
+                    socks_proxy_port: Some(port),
+                    socks_proxy_host: Some(host),
+                    socks_outproxy,
+                },
+            ) => {
+                *config = Some(SocksProxyConfig {
+                    port: *port,
+                    host: host.clone(),
+                    outproxy: socks_outproxy.clone(),
+                });
+            }
+            _ => {}
+        }
+    }
+
     /// Attempt to merge `arguments` with [`Config`].
     fn merge(mut self, arguments: &Arguments) -> Self {
         if let Some(true) = arguments.floodfill {
@@ -1084,7 +1496,11 @@ impl Config {
             match &mut self.reseed {
                 None => {
                     self.reseed = Some(ReseedConfig {
+                        certificate_dir: None,
+                        certificates: None,
+                        file: None,
                         hosts: Some(hosts.clone()),
+                        proxy: None,
                         reseed_threshold: 25usize,
                     });
                 }
@@ -1098,7 +1514,11 @@ impl Config {
             match &mut self.reseed {
                 None => {
                     self.reseed = Some(ReseedConfig {
+                        certificate_dir: None,
+                        certificates: None,
+                        file: None,
                         hosts: None,
+                        proxy: None,
                         reseed_threshold: threshold,
                     });
                 }
@@ -1108,79 +1528,29 @@ impl Config {
             }
         }
 
-        if let Some(true) = arguments.reseed.disable_reseed {
-            self.reseed = None;
+        if let Some(file) = &arguments.reseed.reseed_file {
+            self.reseed_mut().file = Some(file.clone());
         }
 
-        match (&mut self.http_proxy, &arguments.http_proxy) {
-            (
-                Some(config),
-                HttpProxyOptions {
-                    http_proxy_port,
-                    http_proxy_host,
-                    http_outproxy,
-                },
-            ) => {
-                if let Some(port) = http_proxy_port {
-                    config.port = *port;
-                }
-
-                if let Some(host) = &http_proxy_host {
-                    config.host = host.clone();
-                }
+        if let Some(proxy) = &arguments.reseed.reseed_proxy {
+            self.reseed_mut().proxy = Some(proxy.clone());
+        }
 
-                if let Some(outproxy) = http_outproxy {
-                    config.outproxy = Some(outproxy.clone());
-                }
-            }
-            (
-                None,
-                HttpProxyOptions {
-                    http_proxy_port: Some(port),
-                    http_proxy_host: Some(host),
-                    http_outproxy,
-                },
-            ) => {
-                self.http_proxy = Some(HttpProxyConfig {
-                    port: *port,
-                    host: host.clone(),
-                    outproxy: http_outproxy.clone(),
-                });
-            }
-            _ => {}
+        if let Some(dir) = &arguments.reseed.reseed_certificate_dir {
+            self.reseed_mut().certificate_dir = Some(dir.clone());
         }
 
-        match (&mut self.socks_proxy, &arguments.socks_proxy) {
-            (
-                Some(config),
-                SocksProxyOptions {
-                    socks_proxy_port,
-                    socks_proxy_host,
-                },
-            ) => {
-                if let Some(port) = socks_proxy_port {
-                    config.port = *port;
-                }
+        if let Some(certificates) = &arguments.reseed.reseed_certificates {
+            self.reseed_mut().certificates = Some(certificates.clone());
+        }
 
-                if let Some(host) = &socks_proxy_host {
-                    config.host = host.clone();
-                }
-            }
-            (
-                None,
-                SocksProxyOptions {
-                    socks_proxy_port: Some(port),
-                    socks_proxy_host: Some(host),
-                },
-            ) => {
-                self.socks_proxy = Some(SocksProxyConfig {
-                    port: *port,
-                    host: host.clone(),
-                });
-            }
-            _ => {}
+        if let Some(true) = arguments.reseed.disable_reseed {
+            self.reseed = None;
         }
 
+        Self::merge_http_proxy(&mut self.http_proxy, &arguments.http_proxy);
+        Self::merge_socks_proxy(&mut self.socks_proxy, &arguments.socks_proxy);
+
         self.exploratory = match &mut self.exploratory {
             None => Some(emissary_core::ExploratoryConfig {
                 inbound_len: arguments.tunnel.exploratory_inbound_len,
@@ -1267,6 +1637,7 @@ mod tests {
 
     fn make_arguments() -> Arguments {
         Arguments {
+            command: None,
             base_path: None,
             log: None,
             #[cfg(any(
@@ -1293,6 +1664,10 @@ mod tests {
             },
             reseed: ReseedOptions {
                 reseed_hosts: None,
+                reseed_file: None,
+                reseed_proxy: None,
+                reseed_certificates: None,
+                reseed_certificate_dir: None,
                 disable_reseed: None,
                 force_reseed: None,
                 reseed_threshold: None,
@@ -1310,6 +1685,7 @@ mod tests {
             socks_proxy: SocksProxyOptions {
                 socks_proxy_port: None,
                 socks_proxy_host: None,
+                socks_outproxy: None,
             },
             transit: TransitOptions {
                 max_transit_tunnels: None,
@@ -1432,6 +1808,38 @@ mod tests {
         assert_eq!(ntcp2_config.iv, ntcp2_iv);
     }
 
+    #[test]
+    fn blocklist_loaded() {
+        let dir = tempdir().unwrap();
+
+        // no blocklist by default
+        let config = Config::parse(Some(dir.path().to_owned()), &make_arguments()).unwrap();
+        assert!(config.blocklist.is_empty());
+
+        let mut file = fs::File::create(dir.path().to_owned().join("blocklist.txt")).unwrap();
+        file.write_all("# comment\n203.0.113.1\nspammers:198.51.100.0/24\n".as_bytes())
+            .unwrap();
+
+        let config = EmissaryConfig {
+            banlist: Some(BanlistConfig {
+                blocklist: Some(PathBuf::from("blocklist.txt")),
+            }),
+            ..Default::default()
+        };
+        let config = toml::to_string(&config).expect("to succeed");
+        let mut file = fs::File::create(dir.path().to_owned().join("router.toml")).unwrap();
+        file.write_all(config.as_bytes()).unwrap();
+
+        let config = Config::parse(Some(dir.path().to_owned()), &make_arguments()).unwrap();
+        assert_eq!(
+            config.blocklist,
+            vec![
+                emissary_core::BlocklistEntry::BlockAddress("203.0.113.1".parse().unwrap()),
+                emissary_core::BlocklistEntry::BlockRange("198.51.100.0/24".parse().unwrap()),
+            ]
+        );
+    }
+
     #[test]
     fn overwrite_config() {
         let dir = tempdir().unwrap();
@@ -1475,6 +1883,7 @@ mod tests {
                     port: 1337,
                     destination: "hello".to_string(),
                     destination_port: None,
+                    ..Default::default()
                 },
                 ClientTunnelConfig {
                     name: "tunnel".to_string(),
@@ -1482,6 +1891,7 @@ mod tests {
                     port: 1338,
                     destination: "hello".to_string(),
                     destination_port: None,
+                    ..Default::default()
                 },
             ]),
             ..Default::default()
@@ -1510,6 +1920,7 @@ mod tests {
                     port: 1337,
                     destination: "hello".to_string(),
                     destination_port: None,
+                    ..Default::default()
                 },
                 ClientTunnelConfig {
                     name: "tunnel2".to_string(),
@@ -1517,6 +1928,7 @@ mod tests {
                     port: 1337,
                     destination: "hello".to_string(),
                     destination_port: None,
+                    ..Default::default()
                 },
             ]),
             ..Default::default()
@@ -1531,4 +1943,184 @@ mod tests {
             _ => panic!("invalid result"),
         }
     }
+
+    #[test]
+    fn reload_config() {
+        let dir = tempdir().unwrap();
+        let config = Config::parse(Some(dir.path().to_owned()), &make_arguments()).unwrap();
+        let reloadable = config.reloadable();
+
+        // reloading an unmodified config results in the same configuration
+        let mut arguments = make_arguments();
+        assert_eq!(
+            Config::reload(dir.path(), &arguments.http_proxy, &arguments.socks_proxy).unwrap(),
+            reloadable
+        );
+
+        // add client tunnel and socks proxy
+        let config = EmissaryConfig {
+            client_tunnels: Some(vec![ClientTunnelConfig {
+                name: "tunnel".to_string(),
+                address: None,
+                port: 1337,
+                destination: "hello".to_string(),
+                destination_port: None,
+                ..Default::default()
+            }]),
+            socks_proxy: Some(SocksProxyConfig {
+                port: 4447,
+                host: "127.0.0.1".to_string(),
+                outproxy: None,
+            }),
+            ..Default::default()
+        };
+        let config = toml::to_string(&config).expect("to succeed");
+        let mut file = fs::File::create(dir.path().to_owned().join("router.toml")).unwrap();
+        file.write_all(config.as_bytes()).unwrap();
+
+        // cli options override the reloaded config
+        arguments.http_proxy.http_proxy_port = Some(8888);
+
+        let reloaded =
+            Config::reload(dir.path(), &arguments.http_proxy, &arguments.socks_proxy).unwrap();
+
+        assert_eq!(reloaded.client_tunnels.len(), 1);
+        assert_eq!(reloaded.client_tunnels[0].name, "tunnel");
+        assert_eq!(reloaded.socks_proxy.unwrap().port, 4447);
+        assert_eq!(reloaded.http_proxy.unwrap().port, 8888);
+        assert_eq!(reloaded.address_book, reloadable.address_book);
+    }
+
+    #[test]
+    fn reload_invalid_config() {
+        let dir = tempdir().unwrap();
+        let arguments = make_arguments();
+        Config::parse(Some(dir.path().to_owned()), &arguments).unwrap();
+
+        let config = EmissaryConfig {
+            server_tunnels: Some(vec![
+                ServerTunnelConfig {
+                    name: "tunnel".to_string(),
+                    port: 1337,
+                    destination_path: "tunnel1.b64".to_string(),
+                    ..Default::default()
+                },
+                ServerTunnelConfig {
+                    name: "tunnel".to_string(),
+                    port: 1338,
+                    destination_path: "tunnel2.b64".to_string(),
+                    ..Default::default()
+                },
+            ]),
+            ..Default::default()
+        };
+        let config = toml::to_string(&config).expect("to succeed");
+        let mut file = fs::File::create(dir.path().to_owned().join("router.toml")).unwrap();
+        file.write_all(config.as_bytes()).unwrap();
+
+        match Config::reload(dir.path(), &arguments.http_proxy, &arguments.socks_proxy) {
+            Err(Error::InvalidData) => {}
+            _ => panic!("invalid result"),
+        }
+    }
+
+    #[test]
+    fn shared_server_tunnel_keys() {
+        let shared = |name: &str, port: u16, inport: Option<u16>| ServerTunnelConfig {
+            name: name.to_string(),
+            port,
+            keys: Some("shared.b64".to_string()),
+            inport,
+            ..Default::default()
+        };
+
+        let config = EmissaryConfig {
+            server_tunnels: Some(vec![
+                shared("http", 8080, Some(80)),
+                shared("ssh", 22, Some(22)),
+                shared("default", 9000, None),
+            ]),
+            ..Default::default()
+        };
+        assert!(Config::validate_tunnels(&config).is_ok());
+
+        // two tunnels listening to the same port of a shared destination
+        let config = EmissaryConfig {
+            server_tunnels: Some(vec![
+                shared("http", 8080, Some(80)),
+                shared("http2", 8081, Some(80)),
+            ]),
+            ..Default::default()
+        };
+        assert!(Config::validate_tunnels(&config).is_err());
+
+        // shared destination conflicts with a tunnel that doesn't share its destination
+        let config = EmissaryConfig {
+            server_tunnels: Some(vec![
+                shared("http", 8080, Some(80)),
+                ServerTunnelConfig {
+                    name: "tunnel".to_string(),
+                    port: 1337,
+                    destination_path: "shared.b64".to_string(),
+                    ..Default::default()
+                },
+            ]),
+            ..Default::default()
+        };
+        assert!(Config::validate_tunnels(&config).is_err());
+    }
+
+    #[test]
+    fn client_tunnel_uses_server_tunnel_keys() {
+        let config = EmissaryConfig {
+            client_tunnels: Some(vec![ClientTunnelConfig {
+                name: "irc".to_string(),
+                port: 6668,
+                destination: "irc.postman.i2p".to_string(),
+                keys: Some("irc.b64".to_string()),
+                ..Default::default()
+            }]),
+            server_tunnels: Some(vec![ServerTunnelConfig {
+                name: "tunnel".to_string(),
+                port: 1337,
+                destination_path: "tunnel.b64".to_string(),
+                ..Default::default()
+            }]),
+            ..Default::default()
+        };
+        assert!(Config::validate_tunnels(&config).is_ok());
+
+        let config = EmissaryConfig {
+            server_tunnels: Some(vec![ServerTunnelConfig {
+                name: "tunnel".to_string(),
+                port: 1337,
+                destination_path: "irc.b64".to_string(),
+                ..Default::default()
+            }]),
+            ..config
+        };
+        assert!(Config::validate_tunnels(&config).is_err());
+    }
+
+    #[test]
+    fn news_requires_certificates() {
+        let news =
+            |certificates: Option<Vec<PathBuf>>, certificate_dir: Option<PathBuf>| EmissaryConfig {
+                news: Some(NewsConfig {
+                    certificate_dir,
+                    certificates,
+                    refresh_interval: None,
+                    url: None,
+                }),
+                ..Default::default()
+            };
+
+        assert!(Config::validate_news(&EmissaryConfig::default()).is_ok());
+        assert!(Config::validate_news(&news(None, None)).is_err());
+        assert!(Config::validate_news(&news(Some(Vec::new()), None)).is_err());
+        assert!(Config::validate_news(&news(Some(vec![PathBuf::from("news.crt")]), None)).is_ok());
+        assert!(
+            Config::validate_news(&news(None, Some(PathBuf::from("certificates/news")))).is_ok()
+        );
+    }
 }
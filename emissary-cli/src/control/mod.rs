@@ -0,0 +1,466 @@
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Control socket for inspecting and controlling a running router.
+//!
+//! The control socket is a Unix domain socket located at `<base path>/control.sock` which accepts
+//! newline-delimited JSON requests and responds to each request with a single line of JSON.
+
+use crate::{
+    config::{ClientTunnelConfig, ServerTunnelConfig},
+    error::Error,
+    reload::ReloadHandle,
+    storage::RouterStorage,
+    tunnel::TunnelManagerHandle,
+};
+
+use emissary_core::router::handle::RouterHandle;
+use emissary_util::{
+    reseeder::{ReseedOptions, Reseeder},
+    su3::ReseedRouterInfo,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{UnixListener, UnixStream},
+};
+
+use std::{
+    os::unix::fs::{DirBuilderExt, PermissionsExt},
+    path::{Path, PathBuf},
+};
+
+/// Logging target for the file.
+const LOG_TARGET: &str = "emissary::control";
+
+/// Name of the control socket file.
+pub const CONTROL_SOCKET_NAME: &str = "control.sock";
+
+/// Request sent over the control socket.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "kebab-case")]
+pub enum ControlRequest {
+    /// List client tunnels, server tunnels and tunnel pools.
+    ListTunnels,
+
+    /// List connected routers.
+    ListRouters,
+
+    /// List transit tunnels.
+    ListTransitTunnels,
+
+    /// List active destinations.
+    ListDestinations,
+
+    /// Add client tunnel.
+    AddClientTunnel {
+        /// Tunnel configuration.
+        tunnel: ClientTunnelConfig,
+    },
+
+    /// Remove client tunnel.
+    RemoveClientTunnel {
+        /// Name of the tunnel.
+        name: String,
+    },
+
+    /// Add server tunnel.
+    AddServerTunnel {
+        /// Tunnel configuration.
+        tunnel: ServerTunnelConfig,
+    },
+
+    /// Remove server tunnel.
+    RemoveServerTunnel {
+        /// Name of the tunnel.
+        name: String,
+    },
+
+    /// Reload router config.
+    Reload,
+
+    /// Reseed the router.
+    Reseed,
+
+    /// Start graceful shutdown of the router.
+    Shutdown,
+}
+
+/// Response sent over the control socket.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "kebab-case")]
+pub enum ControlResponse {
+    /// Request succeeded.
+    Ok {
+        /// Result of the request, `null` if the request doesn't return anything.
+        result: Value,
+    },
+
+    /// Request failed.
+    Error {
+        /// Error message.
+        message: String,
+    },
+}
+
+impl From<crate::Result<Value>> for ControlResponse {
+    fn from(value: crate::Result<Value>) -> Self {
+        match value {
+            Ok(result) => Self::Ok { result },
+            Err(error) => Self::Error {
+                message: error.to_string(),
+            },
+        }
+    }
+}
+
+/// Control server.
+#[derive(Clone)]
+pub struct ControlServer {
+    /// Handle to client tunnel manager, if SAMv3 was enabled.
+    client_tunnels: Option<TunnelManagerHandle<ClientTunnelConfig>>,
+
+    /// Handle to config reloader, if SAMv3 was enabled.
+    reload: Option<ReloadHandle>,
+
+    /// Reseed options from router config.
+    reseed_options: ReseedOptions,
+
+    /// Handle to the running router.
+    router: RouterHandle,
+
+    /// Handle to server tunnel manager, if SAMv3 was enabled.
+    server_tunnels: Option<TunnelManagerHandle<ServerTunnelConfig>>,
+
+    /// Router storage.
+    storage: RouterStorage,
+}
+
+impl ControlServer {
+    /// Create new [`ControlServer`].
+    pub fn new(
+        router: RouterHandle,
+        storage: RouterStorage,
+        client_tunnels: Option<TunnelManagerHandle<ClientTunnelConfig>>,
+        server_tunnels: Option<TunnelManagerHandle<ServerTunnelConfig>>,
+        reload: Option<ReloadHandle>,
+        reseed_options: ReseedOptions,
+    ) -> Self {
+        Self {
+            client_tunnels,
+            reload,
+            reseed_options,
+            router,
+            server_tunnels,
+            storage,
+        }
+    }
+
+    /// Get handle to client tunnel manager or return an error if SAMv3 is disabled.
+    fn client_tunnels(&self) -> crate::Result<&TunnelManagerHandle<ClientTunnelConfig>> {
+        self.client_tunnels
+            .as_ref()
+            .ok_or(Error::Custom(String::from("sam is disabled")))
+    }
+
+    /// Get handle to server tunnel manager or return an error if SAMv3 is disabled.
+    fn server_tunnels(&self) -> crate::Result<&TunnelManagerHandle<ServerTunnelConfig>> {
+        self.server_tunnels
+            .as_ref()
+            .ok_or(Error::Custom(String::from("sam is disabled")))
+    }
+
+    /// Reseed the router and store the downloaded router infos to disk.
+    async fn reseed(&self) -> crate::Result<Value> {
+        let routers = Reseeder::reseed(self.reseed_options.clone())
+            .await
+            .map_err(|error| Error::Custom(error.to_string()))?;
+        let num_routers = routers.len();
+
+        let router_infos = routers
+            .into_iter()
+            .map(|ReseedRouterInfo { name, router_info }| {
+                match name.strip_prefix("routerInfo-") {
+                    Some(start) =>
+                        if let Err(error) =
+                            self.storage.store_router_info(start.to_string(), router_info.clone())
+                        {
+                            tracing::warn!(
+                                target: LOG_TARGET,
+                                ?error,
+                                "failed to store router info to disk",
+                            );
+                        },
+                    None => tracing::warn!(
+                        target: LOG_TARGET,
+                        ?name,
+                        "malformed router info name, cannot store on disk",
+                    ),
+                }
+
+                router_info
+            })
+            .collect();
+
+        self.router
+            .add_routers(router_infos)
+            .map_err(|error| Error::Custom(error.to_string()))?;
+
+        Ok(json!({ "num_routers": num_routers }))
+    }
+
+    /// Handle `request`.
+    async fn on_request(&self, request: ControlRequest) -> crate::Result<Value> {
+        match request {
+            ControlRequest::ListTunnels => {
+                let status = self.status().await?;
+                let client = match &self.client_tunnels {
+                    Some(handle) => handle.list().await?,
+                    None => Vec::new(),
+                };
+                let server = match &self.server_tunnels {
+                    Some(handle) => handle.list().await?,
+                    None => Vec::new(),
+                };
+                let pools = status
+                    .tunnel_pools
+                    .into_iter()
+                    .map(|pool| {
+                        json!({
+                            "name": pool.name,
+                            "num_inbound": pool.num_inbound,
+                            "num_outbound": pool.num_outbound,
+                            "num_tunnels_built": pool.num_tunnels_built,
+                            "num_tunnel_build_failures": pool.num_tunnel_build_failures,
+                        })
+                    })
+                    .collect::<Vec<_>>();
+
+                Ok(json!({ "client": client, "server": server, "pools": pools }))
+            }
+            ControlRequest::ListRouters => Ok(json!(self.status().await?.connected_routers)),
+            ControlRequest::ListTransitTunnels => Ok(Value::Array(
+                self.status()
+                    .await?
+                    .transit_tunnels
+                    .into_iter()
+                    .map(|tunnel| {
+                        json!({
+                            "tunnel_id": tunnel.tunnel_id.to_string(),
+                            "role": format!("{:?}", tunnel.role),
+                        })
+                    })
+                    .collect(),
+            )),
+            ControlRequest::ListDestinations => Ok(Value::Array(
+                self.status()
+                    .await?
+                    .destinations
+                    .into_iter()
+                    .map(|destination| {
+                        json!({
+                            "name": destination.name,
+                            "address": destination.address,
+                        })
+                    })
+                    .collect(),
+            )),
+            ControlRequest::AddClientTunnel { tunnel } =>
+                self.client_tunnels()?.add(tunnel).await.map(|_| Value::Null),
+            ControlRequest::RemoveClientTunnel { name } =>
+                self.client_tunnels()?.remove(name).await.map(|_| Value::Null),
+            ControlRequest::AddServerTunnel { tunnel } =>
+                self.server_tunnels()?.add(tunnel).await.map(|_| Value::Null),
+            ControlRequest::RemoveServerTunnel { name } =>
+                self.server_tunnels()?.remove(name).await.map(|_| Value::Null),
+            ControlRequest::Reload => match &self.reload {
+                Some(handle) => handle.reload().await.map(|_| Value::Null),
+                None => Err(Error::Custom(String::from("sam is disabled"))),
+            },
+            ControlRequest::Reseed => self.reseed().await,
+            ControlRequest::Shutdown => self
+                .router
+                .shutdown()
+                .map(|_| Value::Null)
+                .map_err(|error| Error::Custom(error.to_string())),
+        }
+    }
+
+    /// Get status of the router.
+    async fn status(&self) -> crate::Result<emissary_core::router::handle::RouterStatus> {
+        self.router.status().await.map_err(|error| Error::Custom(error.to_string()))
+    }
+
+    /// Serve requests of a single control socket client until the connection is closed.
+    async fn handle_connection(self, stream: UnixStream) -> crate::Result<()> {
+        let (reader, mut writer) = stream.into_split();
+        let mut lines = BufReader::new(reader).lines();
+
+        while let Some(line) = lines.next_line().await? {
+            let response: ControlResponse = match serde_json::from_str::<ControlRequest>(&line) {
+                Ok(request) => {
+                    tracing::debug!(
+                        target: LOG_TARGET,
+                        ?request,
+                        "handle control request",
+                    );
+
+                    self.on_request(request).await.into()
+                }
+                Err(error) => ControlResponse::Error {
+                    message: format!("invalid request: {error}"),
+                },
+            };
+
+            let mut response =
+                serde_json::to_vec(&response).map_err(|error| Error::Custom(error.to_string()))?;
+            response.push(b'\n');
+
+            writer.write_all(&response).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Bind the control socket to `path`.
+    ///
+    /// The socket is bound inside a temporary directory accessible only to the owner of the
+    /// router and moved to `path` after its permissions have been restricted, so other users
+    /// never get a chance to connect to it.
+    fn bind(path: &Path) -> crate::Result<UnixListener> {
+        let directory = path.with_file_name(format!(".control-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+
+        std::fs::DirBuilder::new().mode(0o700).create(&directory)?;
+
+        let socket = directory.join(CONTROL_SOCKET_NAME);
+        let result = UnixListener::bind(&socket).map_err(Error::from).and_then(|listener| {
+            std::fs::set_permissions(&socket, std::fs::Permissions::from_mode(0o600))?;
+            std::fs::rename(&socket, path)?;
+
+            Ok(listener)
+        });
+        let _ = std::fs::remove_dir_all(&directory);
+
+        result
+    }
+
+    /// Run the event loop of [`ControlServer`].
+    ///
+    /// Any stale socket file at `path` is removed before binding the listener and the socket is
+    /// made accessible only to the owner of the router.
+    pub async fn run(self, path: PathBuf) -> crate::Result<()> {
+        if path.exists() {
+            tokio::fs::remove_file(&path).await?;
+        }
+
+        let listener = Self::bind(&path)?;
+
+        tracing::info!(
+            target: LOG_TARGET,
+            ?path,
+            "control socket started",
+        );
+
+        loop {
+            let (stream, _) = listener.accept().await?;
+            let server = self.clone();
+
+            tokio::spawn(async move {
+                if let Err(error) = server.handle_connection(stream).await {
+                    tracing::debug!(
+                        target: LOG_TARGET,
+                        ?error,
+                        "control connection exited with error",
+                    );
+                }
+            });
+        }
+    }
+}
+
+/// Send `request` to the control socket at `path` and wait for the response.
+pub async fn send_request(path: PathBuf, request: ControlRequest) -> crate::Result<Value> {
+    let stream = UnixStream::connect(&path).await.map_err(|error| {
+        Error::Custom(format!(
+            "failed to connect to control socket {path:?}: {error}"
+        ))
+    })?;
+    let (reader, mut writer) = stream.into_split();
+
+    let mut request =
+        serde_json::to_vec(&request).map_err(|error| Error::Custom(error.to_string()))?;
+    request.push(b'\n');
+    writer.write_all(&request).await?;
+
+    let line = BufReader::new(reader)
+        .lines()
+        .next_line()
+        .await?
+        .ok_or(Error::Custom(String::from("control socket closed")))?;
+
+    match serde_json::from_str::<ControlResponse>(&line)
+        .map_err(|error| Error::Custom(format!("invalid response: {error}")))?
+    {
+        ControlResponse::Ok { result } => Ok(result),
+        ControlResponse::Error { message } => Err(Error::Custom(message)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn request_serialization() {
+        let request = serde_json::from_str::<ControlRequest>(
+            r#"{"command":"add-client-tunnel","tunnel":{"name":"irc","port":6668,"destination":"irc.postman.i2p"}}"#,
+        )
+        .unwrap();
+
+        match request {
+            ControlRequest::AddClientTunnel { tunnel } => {
+                assert_eq!(tunnel.name, "irc");
+                assert_eq!(tunnel.port, 6668);
+                assert_eq!(tunnel.destination, "irc.postman.i2p");
+                assert!(tunnel.address.is_none());
+            }
+            _ => panic!("invalid request"),
+        }
+
+        assert!(std::matches!(
+            serde_json::from_str::<ControlRequest>(r#"{"command":"list-routers"}"#),
+            Ok(ControlRequest::ListRouters)
+        ));
+        assert!(serde_json::from_str::<ControlRequest>(r#"{"command":"invalid"}"#).is_err());
+    }
+
+    #[test]
+    fn response_serialization() {
+        let response: ControlResponse = Err(Error::Custom(String::from("failure"))).into();
+        let serialized = serde_json::to_string(&response).unwrap();
+
+        assert!(serialized.contains(r#""status":"error""#));
+
+        match serde_json::from_str::<ControlResponse>(&serialized).unwrap() {
+            ControlResponse::Error { message } => assert!(message.contains("failure")),
+            _ => panic!("invalid response"),
+        }
+    }
+}
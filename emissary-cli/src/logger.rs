@@ -16,10 +16,23 @@
 // FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
 // DEALINGS IN THE SOFTWARE.
 
+use parking_lot::Mutex;
 use tracing::Level;
 use tracing_subscriber::filter::{LevelFilter, Targets};
 
-use std::{collections::HashMap, str::FromStr, sync::LazyLock};
+use std::{
+    collections::{HashMap, VecDeque},
+    io,
+    str::FromStr,
+    sync::LazyLock,
+};
+
+/// How many of the most recent log lines are kept in memory for the router console.
+const LOG_BUFFER_SIZE: usize = 1000usize;
+
+/// Most recent log lines.
+static LOG_BUFFER: LazyLock<Mutex<VecDeque<String>>> =
+    LazyLock::new(|| Mutex::new(VecDeque::with_capacity(LOG_BUFFER_SIZE)));
 
 /// Logging presets.
 static PRESETS: LazyLock<HashMap<&'static str, &'static str>> = LazyLock::new(|| {
@@ -70,6 +83,36 @@ pub(super) fn parse_log_targets(log: Option<String>) -> Targets {
     targets
 }
 
+/// Writer which stores formatted log lines in memory so they can be shown in the router console.
+pub(super) struct LogBufferWriter;
+
+impl io::Write for LogBufferWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut lines = LOG_BUFFER.lock();
+
+        for line in String::from_utf8_lossy(buf).lines() {
+            if lines.len() == LOG_BUFFER_SIZE {
+                lines.pop_front();
+            }
+            lines.push_back(line.to_string());
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Get at most `limit` of the most recent log lines, oldest first.
+#[cfg(feature = "web-ui")]
+pub fn recent_logs(limit: usize) -> Vec<String> {
+    let lines = LOG_BUFFER.lock();
+
+    lines.iter().skip(lines.len().saturating_sub(limit)).cloned().collect()
+}
+
 #[macro_export]
 macro_rules! init_logger {
     ($log:expr) => {{
@@ -84,6 +127,13 @@ macro_rules! init_logger {
                 tracing_subscriber::fmt::layer()
                     .with_timer(ChronoLocal::new(String::from("%H:%M:%S%.3f"))),
             )
+            .with(cfg!(feature = "web-ui").then(|| {
+                // keep recent log lines in memory for the log viewer of the router console
+                tracing_subscriber::fmt::layer()
+                    .with_ansi(false)
+                    .with_timer(ChronoLocal::new(String::from("%H:%M:%S%.3f")))
+                    .with_writer(|| crate::logger::LogBufferWriter)
+            }))
             .with(filter)
             .try_init();
 
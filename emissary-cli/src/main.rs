@@ -22,30 +22,49 @@
 use crate::{
     address_book::AddressBookManager,
     cli::Arguments,
-    config::{Config, ReseedConfig, RouterUiConfig},
+    config::{Config, HttpProxyConfig, ReseedConfig, RouterUiConfig},
     error::Error,
+    news::NewsFetcher,
     port_mapper::PortMapper,
-    proxy::{http::HttpProxy, socks::SocksProxy},
+    reload::ConfigReloader,
     storage::RouterStorage,
     tunnel::{client::ClientTunnelManager, server::ServerTunnelManager},
 };
 
+#[cfg(unix)]
+use crate::control::{ControlServer, CONTROL_SOCKET_NAME};
+
 use anyhow::anyhow;
 use clap::Parser;
 use emissary_core::{events::EventSubscriber, router::Router};
-use emissary_util::{reseeder::Reseeder, runtime::tokio::Runtime, su3::ReseedRouterInfo};
-use futures::{channel::oneshot, StreamExt};
+use emissary_util::{
+    certificates,
+    reseeder::{ReseedOptions, Reseeder},
+    runtime::tokio::Runtime,
+    su3::ReseedRouterInfo,
+};
+use futures::StreamExt;
 use tokio::sync::mpsc::{channel, Receiver};
 
-use std::{fs::File, io::Write, mem, sync::Arc};
+use std::{
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 mod address_book;
 mod cli;
+mod command;
 mod config;
+#[cfg(unix)]
+mod control;
 mod error;
 mod logger;
+mod news;
 mod port_mapper;
 mod proxy;
+mod reload;
 mod storage;
 mod tunnel;
 mod ui;
@@ -76,12 +95,69 @@ struct RouterContext {
     /// Router UI config, if enabled.
     #[allow(unused)]
     router_ui_config: Option<RouterUiConfig>,
+
+    /// Handles given to the REST API of the web UI.
+    #[cfg(feature = "web-ui")]
+    console: ui::web::ConsoleHandles,
 }
 
-/// Setup router and related subsystems.
-async fn setup_router() -> anyhow::Result<RouterContext> {
-    let arguments = Arguments::parse();
+/// Trust su3 files signed by the owners of `certificates` and of the certificates found in
+/// `certificate_dir`, using `add` to add each certificate to the trusted certificates of `kind`.
+///
+/// Relative paths are resolved against `base_path`.
+fn add_trusted_certificates(
+    base_path: &Path,
+    certificates: Option<&[PathBuf]>,
+    certificate_dir: Option<&Path>,
+    kind: &str,
+    add: fn(&str) -> anyhow::Result<String>,
+) {
+    let from_dir = certificate_dir.into_iter().flat_map(|dir| {
+        std::fs::read_dir(base_path.join(dir))
+            .inspect_err(|error| {
+                tracing::warn!(
+                    target: LOG_TARGET,
+                    ?dir,
+                    %kind,
+                    ?error,
+                    "failed to read certificate directory",
+                )
+            })
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                let extension = path.extension()?.to_str()?;
+
+                (path.is_file() && (extension == "crt" || extension == "pem")).then_some(path)
+            })
+    });
+
+    for path in certificates.into_iter().flatten().cloned().chain(from_dir) {
+        match std::fs::read_to_string(base_path.join(&path))
+            .map_err(anyhow::Error::from)
+            .and_then(|certificate| add(&certificate))
+        {
+            Ok(signer_id) => tracing::info!(
+                target: LOG_TARGET,
+                %signer_id,
+                ?path,
+                %kind,
+                "added trusted certificate",
+            ),
+            Err(error) => tracing::warn!(
+                target: LOG_TARGET,
+                ?path,
+                %kind,
+                ?error,
+                "failed to add trusted certificate",
+            ),
+        }
+    }
+}
 
+/// Setup router and related subsystems.
+async fn setup_router(arguments: Arguments) -> anyhow::Result<RouterContext> {
     // initialize logger with any logging directive given as a cli argument
     let handle = init_logger!(arguments.log.clone());
 
@@ -96,10 +172,37 @@ async fn setup_router() -> anyhow::Result<RouterContext> {
         error
     })?;
     let storage = RouterStorage::new(config.base_path.clone());
+    let reseed_options = ReseedOptions {
+        file: config.reseed.as_ref().and_then(|config| config.file.clone()),
+        force_ipv4: !arguments.reseed.disable_force_ipv4.unwrap_or(false),
+        hosts: config.reseed.as_ref().and_then(|config| config.hosts.clone()),
+        proxy: config.reseed.as_ref().and_then(|config| config.proxy.clone()),
+    };
 
     // reinitialize the logger with any directives given in the configuration file
     init_logger!(config.log.clone(), handle);
 
+    // trust reseed bundles and news feeds signed by any of the additional signers
+    if let Some(reseed) = &config.reseed {
+        add_trusted_certificates(
+            &config.base_path,
+            reseed.certificates.as_deref(),
+            reseed.certificate_dir.as_deref(),
+            "reseed",
+            certificates::add_trusted_certificate,
+        );
+    }
+
+    if let Some(news) = &config.news {
+        add_trusted_certificates(
+            &config.base_path,
+            news.certificates.as_deref(),
+            news.certificate_dir.as_deref(),
+            "news",
+            certificates::add_trusted_news_certificate,
+        );
+    }
+
     // is the # of known routers less than reseed threshold or is reseed forced
     let should_reseed = config.reseed.as_ref().is_some_and(
         |ReseedConfig {
@@ -112,16 +215,13 @@ async fn setup_router() -> anyhow::Result<RouterContext> {
             target: LOG_TARGET,
             num_routers = ?config.routers.len(),
             forced_reseed = ?arguments.reseed.force_reseed.unwrap_or(false),
-            force_ipv4 = ?(!arguments.reseed.disable_force_ipv4.unwrap_or(false)),
+            force_ipv4 = ?reseed_options.force_ipv4,
+            file = ?reseed_options.file,
+            proxy = ?reseed_options.proxy,
             "reseed router"
         );
 
-        match Reseeder::reseed(
-            config.reseed.as_ref().and_then(|config| config.hosts.clone()),
-            !arguments.reseed.disable_force_ipv4.unwrap_or(false),
-        )
-        .await
-        {
+        match Reseeder::reseed(reseed_options.clone()).await {
             Ok(routers) => {
                 tracing::info!(
                     target: LOG_TARGET,
@@ -169,13 +269,16 @@ async fn setup_router() -> anyhow::Result<RouterContext> {
     }
 
     let path = config.base_path.clone();
-    let http = config.http_proxy.take();
-    let socks = config.socks_proxy.take();
+    let reloadable = config.reloadable();
     let port_forwarding = config.port_forwarding.take();
-    let client_tunnels = mem::take(&mut config.client_tunnels);
-    let server_tunnels = mem::take(&mut config.server_tunnels);
+    let news_config = config.news.take();
+    let http_proxy_config = config.http_proxy.clone();
     let router_ui_config = config.router_ui.clone();
 
+    #[cfg(unix)]
+    let control_storage = storage.clone();
+    #[cfg(feature = "web-ui")]
+    let mut console_address_book = None;
     let (router, events, local_router_info, address_book_manager) =
         match config.address_book.take() {
             None => Router::<Runtime>::new(config.into(), None, Some(Arc::new(storage)))
@@ -188,9 +291,14 @@ async fn setup_router() -> anyhow::Result<RouterContext> {
                     AddressBookManager::new(config.base_path.clone(), address_book_config);
                 let address_book_handle = address_book_manager.handle();
 
+                #[cfg(feature = "web-ui")]
+                {
+                    console_address_book = Some(address_book_handle.clone());
+                }
+
                 Router::<Runtime>::new(
                     config.into(),
-                    Some(address_book_handle),
+                    Some(Arc::new(address_book_handle)),
                     Some(Arc::new(storage)),
                 )
                 .await
@@ -204,88 +312,98 @@ async fn setup_router() -> anyhow::Result<RouterContext> {
     // save newest router info to disk
     File::create(path.join("router.info"))?.write_all(&local_router_info)?;
 
+    // start news fetcher if it was enabled
+    //
+    // the news feed is fetched over i2p so the http proxy must be enabled
+    match (news_config, http_proxy_config) {
+        (None, _) => {}
+        (Some(_), None) => tracing::warn!(
+            target: LOG_TARGET,
+            "http proxy disabled, cannot fetch news feed",
+        ),
+        (Some(news_config), Some(HttpProxyConfig { host, port, .. })) => {
+            tokio::spawn(
+                NewsFetcher::new(path.clone(), news_config, router.handle()).run(host, port),
+            );
+        }
+    }
+
+    // handles to tunnel managers and config reloader, given to the control socket
+    #[cfg_attr(not(unix), allow(unused))]
+    let mut handles = (None, None, None);
+
     // if sam was enabled, start all enabled proxies, client tunnels and the address book
+    //
+    // proxies and the address book are owned by `ConfigReloader` which restarts them if their
+    // configuration changes while the router is running
     if let Some(address) = router.protocol_address_info().sam_tcp {
-        // start http proxy if it was enabled
-        if let Some(config) = http {
-            // start event loop of address book manager if address book was enabled
-            //
-            // address book depends on the http proxy as it downloads hosts.txt from inside i2p
-            //
-            // if address book is enabled, create oneshot channel pair, pass the receiver to address
-            // book and sender to http proxy and once the http proxy is ready (its tunnel pool has
-            // been built), it'll signal the address book that it can start download hosts file(s)
-            //
-            // additionally, acquire handle to address book which is passed to http proxy so it can
-            // resolve .i2p hosts to .b32.i2p hosts
-            let (http_proxy_ready_tx, address_book_handle) = match address_book_manager {
-                None => (None, None),
-                Some(address_book_manager) => {
-                    let (tx, rx) = oneshot::channel();
-                    let handle = address_book_manager.handle();
-
-                    tokio::spawn(address_book_manager.run(config.port, config.host.clone(), rx));
-
-                    (Some(tx), Some(handle))
-                }
-            };
-
-            // start event loop of http proxy
-            tokio::spawn(async move {
-                match HttpProxy::new(
-                    config,
-                    address.port(),
-                    http_proxy_ready_tx,
-                    address_book_handle,
-                )
-                .await
-                {
-                    Ok(proxy) =>
-                        if let Err(error) = proxy.run().await {
-                            tracing::debug!(
-                                target: LOG_TARGET,
-                                ?error,
-                                "http proxy exited",
-                            );
-                        },
-                    Err(error) => tracing::warn!(
-                        target: LOG_TARGET,
-                        ?error,
-                        "failed to start http proxy",
-                    ),
-                }
-            });
-        }
+        let client_tunnel_manager = ClientTunnelManager::new(
+            reloadable.client_tunnels.clone(),
+            address.port(),
+            path.clone(),
+        );
+        let server_tunnel_manager = ServerTunnelManager::new(
+            reloadable.server_tunnels.clone(),
+            address.port(),
+            path.clone(),
+        )
+        .await;
+        let mut config_reloader = ConfigReloader::new(
+            path.clone(),
+            reloadable,
+            address_book_manager,
+            address.port(),
+            router.protocol_address_info().sam_udp.map(|address| address.port()),
+            client_tunnel_manager.handle(),
+            server_tunnel_manager.handle(),
+            arguments.http_proxy.clone(),
+            arguments.socks_proxy.clone(),
+        );
+        config_reloader.start().await;
 
-        // start socks proxy if it was enabled
-        if let Some(config) = socks {
-            // start event loop of socks proxy
-            tokio::spawn(async move {
-                match SocksProxy::new(config, address.port()).await {
-                    Ok(proxy) =>
-                        if let Err(error) = proxy.run().await {
-                            tracing::debug!(
-                                target: LOG_TARGET,
-                                ?error,
-                                "socks proxy exited",
-                            );
-                        },
-                    Err(error) => tracing::warn!(
-                        target: LOG_TARGET,
-                        ?error,
-                        "failed to start socks proxy",
-                    ),
-                }
-            });
-        }
+        handles = (
+            Some(client_tunnel_manager.handle()),
+            Some(server_tunnel_manager.handle()),
+            Some(config_reloader.handle()),
+        );
+        tokio::spawn(client_tunnel_manager.run());
+        tokio::spawn(server_tunnel_manager.run());
+        tokio::spawn(config_reloader.run());
+    }
 
-        // start client and server tunnels
-        tokio::spawn(ClientTunnelManager::new(client_tunnels, address.port()).run());
-        tokio::spawn(
-            ServerTunnelManager::new(server_tunnels, address.port(), path.clone())
-                .await
-                .run(),
+    #[cfg(feature = "web-ui")]
+    let console = ui::web::ConsoleHandles {
+        address_book: console_address_book,
+        base_path: path.clone(),
+        client_tunnels: handles.0.clone(),
+        reload: handles.2.clone(),
+        router: Some(router.handle()),
+        server_tunnels: handles.1.clone(),
+    };
+
+    // start control socket
+    #[cfg(unix)]
+    {
+        let (client_tunnels, server_tunnels, reload) = handles;
+        let server = ControlServer::new(
+            router.handle(),
+            control_storage,
+            client_tunnels,
+            server_tunnels,
+            reload,
+            reseed_options,
         );
+        let socket_path = path.join(CONTROL_SOCKET_NAME);
+
+        tokio::spawn(async move {
+            if let Err(error) = server.run(socket_path).await {
+                tracing::warn!(
+                    target: LOG_TARGET,
+                    ?error,
+                    "control socket exited",
+                );
+            }
+        });
     }
 
     // create port mapper from config and transport protocol info
@@ -302,6 +420,8 @@ async fn setup_router() -> anyhow::Result<RouterContext> {
         events,
         port_mapper,
         router_ui_config,
+        #[cfg(feature = "web-ui")]
+        console,
     })
 }
 
@@ -344,13 +464,20 @@ async fn router_event_loop(
 
 #[cfg(not(any(feature = "native-ui", feature = "web-ui")))]
 fn main() -> anyhow::Result<()> {
+    let mut arguments = Arguments::parse();
+
+    // execute offline subcommand, if one was given, without starting the router
+    if let Some(command) = arguments.command.take() {
+        return command::run(command, arguments.base_path.take()).map_err(From::from);
+    }
+
     let runtime = tokio::runtime::Runtime::new()?;
     let (_tx, shutdown_rx) = channel(1);
     let RouterContext {
         port_mapper,
         router,
         ..
-    } = runtime.block_on(setup_router())?;
+    } = runtime.block_on(setup_router(arguments))?;
 
     runtime.block_on(router_event_loop(router, port_mapper, shutdown_rx));
 
@@ -359,15 +486,22 @@ fn main() -> anyhow::Result<()> {
 
 #[cfg(feature = "web-ui")]
 fn main() -> anyhow::Result<()> {
+    let mut arguments = Arguments::parse();
+
+    // execute offline subcommand, if one was given, without starting the router
+    if let Some(command) = arguments.command.take() {
+        return command::run(command, arguments.base_path.take()).map_err(From::from);
+    }
+
     let runtime = tokio::runtime::Runtime::new()?;
     let (shutdown_tx, shutdown_rx) = channel(1);
     let RouterContext {
+        console,
         events,
         port_mapper,
         router,
         router_ui_config,
-        ..
-    } = runtime.block_on(setup_router())?;
+    } = runtime.block_on(setup_router(arguments))?;
 
     match router_ui_config {
         None => {
@@ -379,7 +513,9 @@ fn main() -> anyhow::Result<()> {
             ..
         }) => {
             runtime.spawn(async move {
-                ui::web::RouterUi::new(events, port, refresh_interval, shutdown_tx).run().await;
+                ui::web::RouterUi::new(events, port, refresh_interval, shutdown_tx, console)
+                    .run()
+                    .await;
             });
             runtime.block_on(router_event_loop(router, port_mapper, shutdown_rx));
         }
@@ -390,6 +526,13 @@ fn main() -> anyhow::Result<()> {
 
 #[cfg(feature = "native-ui")]
 fn main() -> anyhow::Result<()> {
+    let mut arguments = Arguments::parse();
+
+    // execute offline subcommand, if one was given, without starting the router
+    if let Some(command) = arguments.command.take() {
+        return command::run(command, arguments.base_path.take()).map_err(From::from);
+    }
+
     let runtime = tokio::runtime::Runtime::new()?;
     let (shutdown_tx, shutdown_rx) = channel(1);
     let RouterContext {
@@ -397,7 +540,7 @@ fn main() -> anyhow::Result<()> {
         port_mapper,
         events,
         router_ui_config,
-    } = runtime.block_on(setup_router())?;
+    } = runtime.block_on(setup_router(arguments))?;
 
     match router_ui_config {
         None => {
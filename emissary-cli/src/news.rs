@@ -0,0 +1,305 @@
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! News feed fetcher.
+//!
+//! Periodically downloads the signed news feed over I2P through the HTTP proxy, applies the
+//! blocklist embedded in the feed to the banlist of the router and publishes the news entries to
+//! router UIs.
+
+use crate::config::NewsConfig;
+
+use emissary_core::router::handle::RouterHandle;
+use emissary_util::{news::NewsFeed, su3::Su3};
+use reqwest::{Client, Proxy, StatusCode};
+
+use std::{path::PathBuf, time::Duration};
+
+/// Logging target for the file.
+const LOG_TARGET: &str = "emissary::news-fetcher";
+
+/// Default news feed URL.
+const NEWS_URL: &str =
+    "http://tc73n4kivdroccekirco7rhgxdg5f3cjvbaapabupeyzrqwv5guq.b32.i2p/news.su3";
+
+/// Default refresh interval, in hours.
+const REFRESH_INTERVAL: u64 = 12u64;
+
+/// How long is waited after the router has started before the news feed is fetched.
+///
+/// Gives the router time to build tunnels for the HTTP proxy.
+const INITIAL_DELAY: Duration = Duration::from_secs(3 * 60);
+
+/// How long is waited before retrying if the news feed couldn't be fetched.
+const RETRY_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+/// Name of the file the latest news feed is stored in.
+const NEWS_FILE: &str = "news.su3";
+
+/// Result of applying a news feed.
+#[derive(Debug, PartialEq, Eq)]
+enum ApplyResult {
+    /// News feed was applied.
+    Applied,
+
+    /// News feed is not newer than the latest applied news feed.
+    Outdated,
+
+    /// News feed is not a valid news feed.
+    Invalid,
+}
+
+/// News feed fetcher.
+pub struct NewsFetcher {
+    /// Version of the latest applied news feed.
+    ///
+    /// Older news feeds are rejected so that a replayed news feed can't undo blocks added by a
+    /// newer one.
+    latest_version: Option<u64>,
+
+    /// Path to the file the latest news feed is stored in.
+    path: PathBuf,
+
+    /// How often the news feed is fetched.
+    refresh_interval: Duration,
+
+    /// Handle to the router.
+    router_handle: RouterHandle,
+
+    /// URL of the news feed.
+    url: String,
+}
+
+impl NewsFetcher {
+    /// Create new [`NewsFetcher`].
+    pub fn new(base_path: PathBuf, config: NewsConfig, router_handle: RouterHandle) -> Self {
+        Self {
+            latest_version: None,
+            path: base_path.join(NEWS_FILE),
+            refresh_interval: Duration::from_secs(
+                config.refresh_interval.unwrap_or(REFRESH_INTERVAL) * 60 * 60,
+            ),
+            router_handle,
+            url: config.url.unwrap_or(NEWS_URL.to_string()),
+        }
+    }
+
+    /// Verify and parse `news`, apply its blocklist and publish its news entries.
+    ///
+    /// `news` is applied only if it's newer than the latest applied news feed.
+    fn apply(&mut self, news: &[u8]) -> ApplyResult {
+        let Some((version, NewsFeed { blocklist, items })) = Su3::parse_news(news) else {
+            return ApplyResult::Invalid;
+        };
+
+        if self.latest_version.is_some_and(|latest| latest >= version) {
+            tracing::debug!(
+                target: LOG_TARGET,
+                ?version,
+                latest_version = ?self.latest_version,
+                "news feed is not newer than the applied news feed",
+            );
+            return ApplyResult::Outdated;
+        }
+        self.latest_version = Some(version);
+
+        tracing::info!(
+            target: LOG_TARGET,
+            ?version,
+            num_items = ?items.len(),
+            num_blocklist_entries = ?blocklist.len(),
+            "news feed updated",
+        );
+
+        if !blocklist.is_empty() {
+            if let Err(error) = self.router_handle.apply_blocklist(blocklist) {
+                tracing::warn!(
+                    target: LOG_TARGET,
+                    ?error,
+                    "failed to apply blocklist",
+                );
+            }
+        }
+
+        if let Err(error) = self.router_handle.publish_news(items) {
+            tracing::warn!(
+                target: LOG_TARGET,
+                ?error,
+                "failed to publish news",
+            );
+        }
+
+        ApplyResult::Applied
+    }
+
+    /// Start event loop of [`NewsFetcher`].
+    ///
+    /// The news feed stored during a previous run is applied immediately, after which the news
+    /// feed is fetched periodically using the HTTP proxy listening on `http_host:http_port`.
+    pub async fn run(mut self, http_host: String, http_port: u16) {
+        tracing::info!(
+            target: LOG_TARGET,
+            url = %self.url,
+            refresh_interval = ?self.refresh_interval,
+            "start news fetcher",
+        );
+
+        if let Ok(news) = tokio::fs::read(&self.path).await {
+            if self.apply(&news) == ApplyResult::Invalid {
+                tracing::debug!(
+                    target: LOG_TARGET,
+                    path = ?self.path,
+                    "stored news feed is not valid",
+                );
+            }
+        }
+
+        let client = Client::builder()
+            .proxy(Proxy::http(format!("http://{http_host}:{http_port}")).expect("to succeed"))
+            .http1_title_case_headers()
+            .build()
+            .expect("to succeed");
+
+        tokio::time::sleep(INITIAL_DELAY).await;
+
+        loop {
+            let interval = match fetch(&client, &self.url).await {
+                Some(news) => {
+                    match self.apply(&news) {
+                        ApplyResult::Applied =>
+                            if let Err(error) = tokio::fs::write(&self.path, &news).await {
+                                tracing::warn!(
+                                    target: LOG_TARGET,
+                                    ?error,
+                                    "failed to store news feed",
+                                );
+                            },
+                        ApplyResult::Outdated => {}
+                        ApplyResult::Invalid => tracing::warn!(
+                            target: LOG_TARGET,
+                            url = %self.url,
+                            "failed to verify news feed",
+                        ),
+                    }
+
+                    self.refresh_interval
+                }
+                None => RETRY_INTERVAL,
+            };
+
+            tokio::time::sleep(interval).await;
+        }
+    }
+}
+
+/// Attempt to download news feed from `url`.
+async fn fetch(client: &Client, url: &str) -> Option<Vec<u8>> {
+    let response = client
+        .get(url)
+        .send()
+        .await
+        .inspect_err(|error| {
+            tracing::debug!(
+                target: LOG_TARGET,
+                %url,
+                ?error,
+                "failed to fetch news feed",
+            )
+        })
+        .ok()?;
+
+    if response.status() != StatusCode::OK {
+        tracing::debug!(
+            target: LOG_TARGET,
+            %url,
+            status = ?response.status(),
+            "failed to fetch news feed",
+        );
+        return None;
+    }
+
+    response.bytes().await.ok().map(|bytes| bytes.to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    /// Start HTTP proxy which responds to the first request with `status` and `body` and returns
+    /// the requested URL.
+    async fn proxy(
+        status: &'static str,
+        body: &'static [u8],
+    ) -> (u16, tokio::task::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let handle = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buffer = vec![0u8; 4096];
+            let nread = stream.read(&mut buffer).await.unwrap();
+            let request = String::from_utf8_lossy(&buffer[..nread]).to_string();
+
+            stream
+                .write_all(
+                    format!(
+                        "HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                        body.len()
+                    )
+                    .as_bytes(),
+                )
+                .await
+                .unwrap();
+            stream.write_all(body).await.unwrap();
+
+            request.split_whitespace().nth(1).unwrap().to_string()
+        });
+
+        (port, handle)
+    }
+
+    fn client(port: u16) -> Client {
+        Client::builder()
+            .proxy(Proxy::http(format!("http://127.0.0.1:{port}")).unwrap())
+            .build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn fetch_through_proxy() {
+        let (port, handle) = proxy("200 OK", b"I2Psu3").await;
+
+        assert_eq!(
+            fetch(&client(port), NEWS_URL).await.as_deref(),
+            Some(b"I2Psu3".as_slice())
+        );
+        assert_eq!(handle.await.unwrap(), NEWS_URL);
+    }
+
+    #[tokio::test]
+    async fn fetch_not_found() {
+        let (port, handle) = proxy("404 Not Found", b"").await;
+
+        assert!(fetch(&client(port), NEWS_URL).await.is_none());
+        handle.await.unwrap();
+    }
+}
@@ -47,6 +47,12 @@ pub enum HttpError {
 
     /// Received partial request.
     PartialRequest,
+
+    /// Address helper is invalid or has expired.
+    InvalidAddressHelper,
+
+    /// Failed to save host to address book.
+    AddressBookWrite,
 }
 
 impl From<std::io::Error> for HttpError {
@@ -78,6 +84,8 @@ impl std::fmt::Display for HttpError {
                 "Cannot connect to clearnet address, outproxy not enabled"
             ),
             HttpError::PartialRequest => write!(f, "Partial request"),
+            HttpError::InvalidAddressHelper => write!(f, "Invalid or expired address helper"),
+            HttpError::AddressBookWrite => write!(f, "Failed to save host to address book"),
         }
     }
 }
@@ -0,0 +1,580 @@
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! HTTP/1.1 message framing.
+//!
+//! Requests and responses are relayed message by message so that both the client connection and
+//! the stream to the remote destination can be reused for subsequent requests. Message bodies are
+//! relayed as they're read instead of being buffered.
+
+use crate::proxy::http::HttpError;
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Maximum size of the head of a request or a response.
+const MAX_HEAD_SIZE: usize = 16 * 1024;
+
+/// Maximum length of a chunk size line or a trailer line.
+const MAX_LINE_LEN: usize = 4096;
+
+/// Read buffer size.
+const READ_BUFFER_SIZE: usize = 8192;
+
+/// Body of an HTTP message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Body {
+    /// Message has no body.
+    None,
+
+    /// Body with a known length.
+    Length(u64),
+
+    /// Body with chunked transfer encoding.
+    Chunked,
+
+    /// Body which is terminated by closing the connection.
+    UntilClose,
+}
+
+/// Parsed head of a response.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ResponseHead {
+    /// Body of the response.
+    pub body: Body,
+
+    /// Can the connection be reused after the response.
+    pub keep_alive: bool,
+
+    /// Status code.
+    pub status: u16,
+}
+
+/// Does `headers` contain `Connection` header with `token`.
+pub fn has_connection_token(headers: &[httparse::Header<'_>], token: &str) -> bool {
+    headers
+        .iter()
+        .filter(|header| header.name.eq_ignore_ascii_case("connection"))
+        .filter_map(|header| std::str::from_utf8(header.value).ok())
+        .flat_map(|value| value.split(','))
+        .any(|value| value.trim().eq_ignore_ascii_case(token))
+}
+
+/// Is chunked the final transfer coding of `headers`.
+///
+/// Returns `None` if `Transfer-Encoding` doesn't exist.
+///
+/// Messages with more than one `Transfer-Encoding`, or with both `Transfer-Encoding` and
+/// `Content-Length`, are rejected as the proxy and the remote server could otherwise disagree on
+/// where the message ends.
+fn is_chunked(headers: &[httparse::Header<'_>]) -> Result<Option<bool>, HttpError> {
+    let mut values = headers
+        .iter()
+        .filter(|header| header.name.eq_ignore_ascii_case("transfer-encoding"));
+
+    let Some(header) = values.next() else {
+        return Ok(None);
+    };
+
+    if values.next().is_some()
+        || headers.iter().any(|header| header.name.eq_ignore_ascii_case("content-length"))
+    {
+        return Err(HttpError::Malformed);
+    }
+
+    let value = std::str::from_utf8(header.value).map_err(|_| HttpError::Malformed)?;
+
+    Ok(Some(value.rsplit(',').next().is_some_and(|coding| {
+        coding.trim().eq_ignore_ascii_case("chunked")
+    })))
+}
+
+/// Parse `Content-Length` of `headers`, if it exists.
+///
+/// Messages with more than one `Content-Length` are rejected, even if the values are equal, as
+/// the proxy and the remote server could otherwise disagree on where the message ends.
+fn content_length(headers: &[httparse::Header<'_>]) -> Result<Option<u64>, HttpError> {
+    let mut values = headers
+        .iter()
+        .filter(|header| header.name.eq_ignore_ascii_case("content-length"));
+
+    let Some(header) = values.next() else {
+        return Ok(None);
+    };
+
+    if values.next().is_some() {
+        return Err(HttpError::Malformed);
+    }
+
+    let value = std::str::from_utf8(header.value).map_err(|_| HttpError::Malformed)?.trim();
+
+    match !value.is_empty() && value.bytes().all(|byte| byte.is_ascii_digit()) {
+        true => value.parse::<u64>().map(Some).map_err(|_| HttpError::Malformed),
+        false => Err(HttpError::Malformed),
+    }
+}
+
+/// Get body of a request from `headers`.
+pub fn request_body(headers: &[httparse::Header<'_>]) -> Result<Body, HttpError> {
+    match (is_chunked(headers)?, content_length(headers)?) {
+        (Some(true), _) => Ok(Body::Chunked),
+        (Some(false), _) => Err(HttpError::Malformed),
+        (None, Some(0) | None) => Ok(Body::None),
+        (None, Some(length)) => Ok(Body::Length(length)),
+    }
+}
+
+/// Parse head of a response to a request made with `method`.
+pub fn parse_response_head(head: &[u8], method: &str) -> Result<ResponseHead, HttpError> {
+    let mut headers = [httparse::EMPTY_HEADER; 64];
+    let mut response = httparse::Response::new(&mut headers);
+
+    let httparse::Status::Complete(_) = response.parse(head)? else {
+        return Err(HttpError::PartialRequest);
+    };
+    let status = response.code.ok_or(HttpError::Malformed)?;
+    let keep_alive = match response.version {
+        Some(1) => !has_connection_token(response.headers, "close"),
+        _ => has_connection_token(response.headers, "keep-alive"),
+    };
+
+    let body = if method == "HEAD" || (100..200).contains(&status) || [204, 304].contains(&status) {
+        Body::None
+    } else {
+        match (
+            is_chunked(response.headers)?,
+            content_length(response.headers)?,
+        ) {
+            (Some(true), _) => Body::Chunked,
+            (Some(false), _) | (None, None) => Body::UntilClose,
+            (None, Some(length)) => Body::Length(length),
+        }
+    };
+
+    Ok(ResponseHead {
+        keep_alive: keep_alive && body != Body::UntilClose,
+        body,
+        status,
+    })
+}
+
+/// Stream with a read buffer.
+///
+/// Bytes read from the stream but not yet consumed, e.g., a pipelined request, are kept in the
+/// buffer and returned by subsequent reads.
+pub struct BufferedStream<S> {
+    /// Bytes read from `stream` but not consumed.
+    buffer: Vec<u8>,
+
+    /// Underlying stream.
+    stream: S,
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> BufferedStream<S> {
+    /// Create new [`BufferedStream`].
+    pub fn new(stream: S) -> Self {
+        Self {
+            buffer: Vec::new(),
+            stream,
+        }
+    }
+
+    /// Get mutable reference to the underlying stream.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.stream
+    }
+
+    /// Consume [`BufferedStream`] and return the underlying stream and the unconsumed bytes.
+    pub fn into_parts(self) -> (S, Vec<u8>) {
+        (self.stream, self.buffer)
+    }
+
+    /// Read more bytes from the stream into the buffer.
+    ///
+    /// Returns the number of bytes read, zero if the stream was closed.
+    async fn fill(&mut self) -> std::io::Result<usize> {
+        let mut chunk = [0u8; READ_BUFFER_SIZE];
+        let nread = self.stream.read(&mut chunk).await?;
+        self.buffer.extend_from_slice(&chunk[..nread]);
+
+        Ok(nread)
+    }
+
+    /// Read head of a message.
+    ///
+    /// `parse` returns the size of the head if the buffer contains a complete head.
+    ///
+    /// If the stream is closed before any bytes were read, `HttpError::Io(BrokenPipe)` is returned.
+    pub async fn read_head(
+        &mut self,
+        parse: impl Fn(&[u8]) -> Result<Option<usize>, httparse::Error>,
+    ) -> Result<Vec<u8>, HttpError> {
+        loop {
+            if !self.buffer.is_empty() {
+                if let Some(size) = parse(&self.buffer)? {
+                    return Ok(self.buffer.drain(..size).collect());
+                }
+
+                if self.buffer.len() > MAX_HEAD_SIZE {
+                    return Err(HttpError::Malformed);
+                }
+            }
+
+            match self.fill().await? {
+                0 if self.buffer.is_empty() =>
+                    return Err(HttpError::Io(std::io::ErrorKind::BrokenPipe)),
+                0 => return Err(HttpError::Io(std::io::ErrorKind::UnexpectedEof)),
+                _ => {}
+            }
+        }
+    }
+
+    /// Read head of a request.
+    pub async fn read_request_head(&mut self) -> Result<Vec<u8>, HttpError> {
+        self.read_head(|buffer| {
+            let mut headers = [httparse::EMPTY_HEADER; 64];
+
+            httparse::Request::new(&mut headers).parse(buffer).map(|status| match status {
+                httparse::Status::Complete(size) => Some(size),
+                httparse::Status::Partial => None,
+            })
+        })
+        .await
+    }
+
+    /// Read head of a response.
+    pub async fn read_response_head(&mut self) -> Result<Vec<u8>, HttpError> {
+        self.read_head(|buffer| {
+            let mut headers = [httparse::EMPTY_HEADER; 64];
+
+            httparse::Response::new(&mut headers).parse(buffer).map(|status| match status {
+                httparse::Status::Complete(size) => Some(size),
+                httparse::Status::Partial => None,
+            })
+        })
+        .await
+    }
+
+    /// Read line, including the line terminator.
+    async fn read_line(&mut self) -> std::io::Result<Vec<u8>> {
+        loop {
+            if let Some(position) = self.buffer.iter().position(|byte| byte == &b'\n') {
+                return Ok(self.buffer.drain(..=position).collect());
+            }
+
+            if self.buffer.len() > MAX_LINE_LEN {
+                return Err(std::io::ErrorKind::InvalidData.into());
+            }
+
+            if self.fill().await? == 0 {
+                return Err(std::io::ErrorKind::UnexpectedEof.into());
+            }
+        }
+    }
+
+    /// Copy exactly `length` bytes to `writer`.
+    async fn copy_exact<W: AsyncWrite + Unpin>(
+        &mut self,
+        writer: &mut W,
+        mut length: u64,
+    ) -> std::io::Result<()> {
+        while length > 0 {
+            if self.buffer.is_empty() && self.fill().await? == 0 {
+                return Err(std::io::ErrorKind::UnexpectedEof.into());
+            }
+
+            let size = std::cmp::min(length, self.buffer.len() as u64) as usize;
+            writer.write_all(&self.buffer[..size]).await?;
+            self.buffer.drain(..size);
+            length -= size as u64;
+        }
+
+        Ok(())
+    }
+
+    /// Relay message body of kind `body` to `writer`.
+    ///
+    /// Chunked bodies are relayed as-is, including chunk sizes and trailers.
+    pub async fn relay_body<W: AsyncWrite + Unpin>(
+        &mut self,
+        writer: &mut W,
+        body: Body,
+    ) -> std::io::Result<()> {
+        match body {
+            Body::None => {}
+            Body::Length(length) => self.copy_exact(writer, length).await?,
+            Body::Chunked => loop {
+                let line = self.read_line().await?;
+                let size = std::str::from_utf8(&line)
+                    .ok()
+                    .and_then(|line| line.split(';').next())
+                    .and_then(|size| u64::from_str_radix(size.trim(), 16).ok())
+                    .ok_or(std::io::Error::from(std::io::ErrorKind::InvalidData))?;
+                writer.write_all(&line).await?;
+
+                if size == 0 {
+                    // relay trailers and the final empty line
+                    loop {
+                        let line = self.read_line().await?;
+                        writer.write_all(&line).await?;
+
+                        if line == b"\r\n" || line == b"\n" {
+                            break;
+                        }
+                    }
+                    break;
+                }
+
+                // chunk data is followed by a line terminator
+                self.copy_exact(writer, size).await?;
+
+                let line = self.read_line().await?;
+                if line != b"\r\n" && line != b"\n" {
+                    return Err(std::io::ErrorKind::InvalidData.into());
+                }
+                writer.write_all(&line).await?;
+            },
+            Body::UntilClose => {
+                writer.write_all(&self.buffer).await?;
+                self.buffer.clear();
+                tokio::io::copy(&mut self.stream, writer).await?;
+            }
+        }
+
+        writer.flush().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_request_body(request: &str) -> Result<Body, HttpError> {
+        let mut headers = [httparse::EMPTY_HEADER; 64];
+        let mut req = httparse::Request::new(&mut headers);
+        req.parse(request.as_bytes()).unwrap();
+
+        request_body(req.headers)
+    }
+
+    #[test]
+    fn request_bodies() {
+        assert_eq!(
+            parse_request_body("GET / HTTP/1.1\r\nHost: host.i2p\r\n\r\n"),
+            Ok(Body::None)
+        );
+        assert_eq!(
+            parse_request_body("POST / HTTP/1.1\r\nContent-Length: 1337\r\n\r\n"),
+            Ok(Body::Length(1337))
+        );
+        assert_eq!(
+            parse_request_body("POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n"),
+            Ok(Body::Chunked)
+        );
+        assert_eq!(
+            parse_request_body("POST / HTTP/1.1\r\nTransfer-Encoding: gzip, chunked\r\n\r\n"),
+            Ok(Body::Chunked)
+        );
+        assert_eq!(
+            parse_request_body(
+                "POST / HTTP/1.1\r\nContent-Length: 10\r\nTransfer-Encoding: gzip, chunked\r\n\r\n"
+            ),
+            Err(HttpError::Malformed)
+        );
+        assert_eq!(
+            parse_request_body(
+                "POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\nTransfer-Encoding: gzip\r\n\r\n"
+            ),
+            Err(HttpError::Malformed)
+        );
+        assert_eq!(
+            parse_request_body("POST / HTTP/1.1\r\nTransfer-Encoding: gzip\r\n\r\n"),
+            Err(HttpError::Malformed)
+        );
+        assert_eq!(
+            parse_request_body("POST / HTTP/1.1\r\nContent-Length: -1\r\n\r\n"),
+            Err(HttpError::Malformed)
+        );
+        assert_eq!(
+            parse_request_body("POST / HTTP/1.1\r\nContent-Length: +5\r\n\r\n"),
+            Err(HttpError::Malformed)
+        );
+        assert_eq!(
+            parse_request_body("POST / HTTP/1.1\r\nContent-Length: 5, 5\r\n\r\n"),
+            Err(HttpError::Malformed)
+        );
+        assert_eq!(
+            parse_request_body(
+                "POST / HTTP/1.1\r\nContent-Length: 5\r\nContent-Length: 10\r\n\r\n"
+            ),
+            Err(HttpError::Malformed)
+        );
+        assert_eq!(
+            parse_request_body("POST / HTTP/1.1\r\nContent-Length: 5\r\nContent-Length: 5\r\n\r\n"),
+            Err(HttpError::Malformed)
+        );
+    }
+
+    #[test]
+    fn response_heads() {
+        assert_eq!(
+            parse_response_head(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\n", "GET"),
+            Ok(ResponseHead {
+                body: Body::Length(5),
+                keep_alive: true,
+                status: 200,
+            })
+        );
+        assert_eq!(
+            parse_response_head(
+                b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\nConnection: close\r\n\r\n",
+                "GET"
+            ),
+            Ok(ResponseHead {
+                body: Body::Length(5),
+                keep_alive: false,
+                status: 200,
+            })
+        );
+        assert_eq!(
+            parse_response_head(b"HTTP/1.1 200 OK\r\n\r\n", "GET"),
+            Ok(ResponseHead {
+                body: Body::UntilClose,
+                keep_alive: false,
+                status: 200,
+            })
+        );
+        assert_eq!(
+            parse_response_head(b"HTTP/1.0 200 OK\r\nContent-Length: 5\r\n\r\n", "GET"),
+            Ok(ResponseHead {
+                body: Body::Length(5),
+                keep_alive: false,
+                status: 200,
+            })
+        );
+        assert_eq!(
+            parse_response_head(
+                b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n",
+                "POST"
+            ),
+            Ok(ResponseHead {
+                body: Body::Chunked,
+                keep_alive: true,
+                status: 200,
+            })
+        );
+        assert_eq!(
+            parse_response_head(
+                b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\nContent-Length: 5\r\n\r\n",
+                "GET"
+            ),
+            Err(HttpError::Malformed)
+        );
+        assert_eq!(
+            parse_response_head(
+                b"HTTP/1.1 304 Not Modified\r\nContent-Length: 5\r\n\r\n",
+                "GET"
+            ),
+            Ok(ResponseHead {
+                body: Body::None,
+                keep_alive: true,
+                status: 304,
+            })
+        );
+        assert_eq!(
+            parse_response_head(
+                b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\nContent-Length: 6\r\n\r\n",
+                "GET"
+            ),
+            Err(HttpError::Malformed)
+        );
+    }
+
+    #[tokio::test]
+    async fn chunked_body_relayed() {
+        let (mut writer, reader) = tokio::io::duplex(1024);
+        let mut stream = BufferedStream::new(reader);
+        let mut output = Vec::new();
+
+        writer
+            .write_all(
+                b"5;name=value\r\nhello\r\n6\r\n world\r\n0\r\nTrailer: 1\r\n\r\nGET / HTTP/1.1",
+            )
+            .await
+            .unwrap();
+
+        stream.relay_body(&mut output, Body::Chunked).await.unwrap();
+
+        assert_eq!(
+            output,
+            b"5;name=value\r\nhello\r\n6\r\n world\r\n0\r\nTrailer: 1\r\n\r\n"
+        );
+        assert_eq!(stream.into_parts().1, b"GET / HTTP/1.1");
+    }
+
+    #[tokio::test]
+    async fn invalid_chunk() {
+        let (mut writer, reader) = tokio::io::duplex(1024);
+        let mut stream = BufferedStream::new(reader);
+
+        writer.write_all(b"5\r\nhello world\r\n0\r\n\r\n").await.unwrap();
+
+        assert!(stream.relay_body(&mut Vec::new(), Body::Chunked).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn large_body_relayed() {
+        let (mut writer, reader) = tokio::io::duplex(64 * 1024);
+        let mut stream = BufferedStream::new(reader);
+        let body = vec![0xaa; 1024 * 1024];
+
+        let handle = tokio::spawn({
+            let body = body.clone();
+
+            async move {
+                writer
+                    .write_all(b"POST / HTTP/1.1\r\nContent-Length: 1048576\r\n\r\n")
+                    .await
+                    .unwrap();
+                writer.write_all(&body).await.unwrap();
+                writer.write_all(b"GET").await.unwrap();
+            }
+        });
+
+        let head = stream.read_request_head().await.unwrap();
+        assert!(head.ends_with(b"\r\n\r\n"));
+
+        let mut output = Vec::new();
+        stream.relay_body(&mut output, Body::Length(1024 * 1024)).await.unwrap();
+        handle.await.unwrap();
+
+        assert_eq!(output, body);
+        assert_eq!(stream.into_parts().1, b"GET");
+    }
+
+    #[tokio::test]
+    async fn closed_before_head() {
+        let (writer, reader) = tokio::io::duplex(1024);
+        let mut stream = BufferedStream::new(reader);
+        drop(writer);
+
+        assert_eq!(
+            stream.read_response_head().await,
+            Err(HttpError::Io(std::io::ErrorKind::BrokenPipe))
+        );
+    }
+}
@@ -17,58 +17,234 @@
 // DEALINGS IN THE SOFTWARE.
 
 use crate::{
-    config::HttpProxyConfig,
+    address_book::AddressBookHandle,
+    config::{HttpProxyConfig, IsolationMode},
     proxy::http::{
         error::HttpError,
-        request::Request,
+        message::{Body, BufferedStream},
+        request::{AddressHelper, HeaderFilter, Request},
         response::{send_response, Status},
     },
 };
 
-use emissary_core::runtime::AddressBook;
+use emissary_core::{
+    crypto::{base32_encode, base64_decode},
+    primitives::Destination,
+    runtime::AddressBook,
+};
 use futures::{channel::oneshot, future::Either};
+use schnellru::{ByLength, LruMap};
 use tokio::{
-    io::{AsyncReadExt, AsyncWriteExt},
+    io::AsyncWriteExt,
     net::{TcpListener, TcpStream},
     task::JoinSet,
 };
-use yosemite::{style, Session, SessionOptions, StreamOptions};
+use yosemite::{style, Session, SessionOptions, Stream, StreamOptions};
 
-use std::{sync::Arc, time::Duration};
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
 
-mod error;
+pub(crate) mod error;
+pub(crate) mod message;
 mod request;
 mod response;
 
 /// Logging target for the file.
 const LOG_TARGET: &str = "emissary::proxy::http";
 
+/// Jump services used if none were configured.
+const DEFAULT_JUMP_SERVICES: [&str; 2] = [
+    "http://stats.i2p/cgi-bin/jump.cgi?a=",
+    "http://reg.i2p/jump/",
+];
+
+/// Maximum number of address helpers waiting for confirmation.
+const MAX_PENDING_ADDRESS_HELPERS: u32 = 64u32;
+
+/// Maximum number of hosts visited with an address helper without saving them.
+const MAX_UNSAVED_HOSTS: u32 = 128u32;
+
+/// How long the proxy waits for the first request of a client connection.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long the proxy waits for the response head after the request has been sent.
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How long an idle client connection is kept open between requests.
+const KEEP_ALIVE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long an idle stream to a remote destination is kept for reuse.
+const IDLE_STREAM_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Maximum number of idle streams kept per remote destination.
+const MAX_IDLE_STREAMS: usize = 4usize;
+
+/// How long an idle isolated session is kept before it's destroyed.
+const SESSION_IDLE_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// Maximum number of isolated sessions.
+///
+/// If the limit is reached, the least recently used idle session is destroyed when a new session
+/// is needed. If none of the sessions are idle, requests needing a new session are rejected.
+const MAX_ISOLATED_SESSIONS: usize = 32usize;
+
+/// Address helper waiting for confirmation from user.
+struct PendingAddressHelper {
+    /// .b32.i2p address of the destination.
+    b32: String,
+
+    /// Base64-encoded destination.
+    destination: String,
+
+    /// .i2p host.
+    host: String,
+
+    /// URL of the request without the address helper.
+    url: String,
+}
+
 /// Request context.
-#[derive(Debug)]
 struct RequestContext {
     /// Client's TCP stream.
-    stream: TcpStream,
+    stream: BufferedStream<TcpStream>,
 
     /// Parsed request.
     request: Request,
 }
 
+/// Assembled request waiting to be sent to remote destination.
+struct OutboundRequest {
+    /// Body of the request, read from the client connection.
+    body: Body,
+
+    /// Does the client expect `100 Continue` before sending the body.
+    expect_continue: bool,
+
+    /// Serialized head of the request.
+    head: Vec<u8>,
+
+    /// Should the client connection be kept open after the response.
+    keep_alive: bool,
+
+    /// Isolation key of the request, `None` if the request uses the shared session.
+    isolation: Option<String>,
+
+    /// Method of the request.
+    method: String,
+
+    /// Is the request a tunnel.
+    tunnel: bool,
+}
+
+impl OutboundRequest {
+    /// Can the request be sent again if the stream fails before a response is received.
+    ///
+    /// Only `GET` requests are retried as the remote destination may have processed the request
+    /// before the stream failed.
+    fn is_retryable(&self) -> bool {
+        self.method == "GET"
+    }
+}
+
+/// Pool key of an idle stream, the isolation key and the .b32.i2p address of the destination.
+type StreamKey = (Option<String>, String);
+
+/// SAMv3 session used by requests of one isolation key.
+struct IsolatedSession {
+    /// Number of active exchanges using the session.
+    active: usize,
+
+    /// When was the session last used.
+    last_used: Instant,
+
+    /// Requests waiting for the session to be created.
+    pending: Vec<(BufferedStream<TcpStream>, String, OutboundRequest)>,
+
+    /// SAMv3 session, `None` if it's still being created.
+    session: Option<Session<style::Stream>>,
+}
+
+/// Result of a request-response exchange.
+enum Exchange {
+    /// Exchange has finished.
+    Finished {
+        /// Client's TCP stream, if it can be used for the next request.
+        client: Option<BufferedStream<TcpStream>>,
+
+        /// .b32.i2p address of the remote destination.
+        host: String,
+
+        /// Stream to remote destination, if it can be reused.
+        stream: Option<Stream>,
+    },
+
+    /// Reused stream was closed by the remote destination before a response was received.
+    ///
+    /// The request must be sent again over a new stream.
+    Retry {
+        /// Client's TCP stream.
+        client: BufferedStream<TcpStream>,
+
+        /// .b32.i2p address of the remote destination.
+        host: String,
+
+        /// Request.
+        request: OutboundRequest,
+    },
+}
+
 /// HTTP proxy.
 pub struct HttpProxy {
     /// Handle to [`AddressBook`], if it was enabled.
-    address_book_handle: Option<Arc<dyn AddressBook>>,
+    address_book_handle: Option<AddressBookHandle>,
+
+    /// Address helpers waiting for confirmation, indexed by nonce.
+    address_helpers: LruMap<String, PendingAddressHelper>,
+
+    /// Pending request-response exchanges and their isolation keys.
+    exchanges: JoinSet<(Option<String>, Exchange)>,
+
+    /// Header filter for outbound requests.
+    header_filter: HeaderFilter,
+
+    /// Jump services listed when host is not found from address book.
+    jump_services: Vec<String>,
 
     // TCP listener.
     listener: TcpListener,
 
+    /// Destination isolation mode.
+    isolation: IsolationMode,
+
+    /// Isolated sessions being created.
+    pending_sessions: JoinSet<(String, yosemite::Result<Session<style::Stream>>)>,
+
     /// Inbound requests.
     requests: JoinSet<Option<RequestContext>>,
 
+    /// SAMv3 TCP port.
+    samv3_tcp_port: u16,
+
     /// SAMv3 streaming session for the HTTP proxy.
+    ///
+    /// Used by all requests unless destination isolation is enabled.
     session: Session<style::Stream>,
 
+    /// Isolated SAMv3 sessions, indexed by isolation key.
+    sessions: HashMap<String, IsolatedSession>,
+
     /// HTTP outproxy, if enabled.
     outproxy: Option<String>,
+
+    /// Idle streams to remote destinations.
+    streams: HashMap<StreamKey, Vec<(Stream, Instant)>>,
+
+    /// Hosts visited with an address helper without saving them to address book.
+    ///
+    /// Maps .i2p hosts to .b32.i2p addresses.
+    unsaved_hosts: LruMap<String, String>,
 }
 
 impl HttpProxy {
@@ -80,13 +256,14 @@ impl HttpProxy {
         config: HttpProxyConfig,
         samv3_tcp_port: u16,
         http_proxy_ready_tx: Option<oneshot::Sender<()>>,
-        address_book_handle: Option<Arc<dyn AddressBook>>,
+        address_book_handle: Option<AddressBookHandle>,
     ) -> crate::Result<Self> {
         tracing::info!(
             target: LOG_TARGET,
             host = %config.host,
             port = %config.port,
             outproxy = ?config.outproxy,
+            isolation = ?config.isolation,
             "starting http proxy",
         );
 
@@ -158,10 +335,22 @@ impl HttpProxy {
 
         Ok(Self {
             address_book_handle,
+            address_helpers: LruMap::new(ByLength::new(MAX_PENDING_ADDRESS_HELPERS)),
+            exchanges: JoinSet::new(),
+            header_filter: HeaderFilter::new(config.eepsite_headers, config.outproxy_headers),
+            jump_services: config.jump_services.unwrap_or_else(|| {
+                DEFAULT_JUMP_SERVICES.iter().map(|service| service.to_string()).collect()
+            }),
+            isolation: config.isolation.unwrap_or_default(),
             listener,
             outproxy,
+            pending_sessions: JoinSet::new(),
             requests: JoinSet::new(),
+            samv3_tcp_port,
             session,
+            sessions: HashMap::new(),
+            streams: HashMap::new(),
+            unsaved_hosts: LruMap::new(ByLength::new(MAX_UNSAVED_HOSTS)),
         })
     }
 
@@ -170,55 +359,441 @@ impl HttpProxy {
     /// Parses and validates the received request and returns [`RequestContext`] which contains the
     /// validated request and the TCP stream of the client which is used to send the response or an
     /// error.
-    async fn read_request(mut stream: TcpStream) -> Result<RequestContext, (TcpStream, HttpError)> {
-        let mut buffer = vec![0u8; 8192];
-        let mut nread = 0usize;
+    ///
+    /// Only the head of the request is read, the body is relayed to remote destination after a
+    /// stream has been opened.
+    async fn read_request(
+        mut stream: BufferedStream<TcpStream>,
+    ) -> Result<RequestContext, (TcpStream, HttpError)> {
+        let head = match stream.read_request_head().await {
+            Ok(head) => head,
+            Err(error) => return Err((stream.into_parts().0, error)),
+        };
 
-        // read from `stream` until complete request has been received
-        loop {
-            nread += match stream.read(&mut buffer[nread..]).await {
-                Err(error) => return Err((stream, HttpError::Io(error.kind()))),
-                Ok(0) => return Err((stream, HttpError::Io(std::io::ErrorKind::BrokenPipe))),
-                Ok(nread) => nread,
-            };
+        match Request::parse(head) {
+            Err(error) => Err((stream.into_parts().0, error)),
+            Ok(request) => Ok(RequestContext { stream, request }),
+        }
+    }
 
-            let mut headers = [httparse::EMPTY_HEADER; 64];
-            match httparse::Request::new(&mut headers).parse(&buffer[..nread]) {
-                Err(_) => return Err((stream, HttpError::Malformed)),
-                Ok(request) if request.is_complete() => break,
-                Ok(_) => {}
+    /// Start reading the next request from `stream`.
+    ///
+    /// If the client doesn't send a request within `timeout`, the connection is closed.
+    fn read_next_request(&mut self, stream: BufferedStream<TcpStream>, timeout: Duration) {
+        self.requests.spawn(async move {
+            match tokio::time::timeout(timeout, Self::read_request(stream)).await {
+                Err(_) => None,
+                Ok(Ok(request)) => Some(request),
+                Ok(Err((_, HttpError::Io(std::io::ErrorKind::BrokenPipe)))) => None,
+                Ok(Err((stream, error))) => {
+                    tracing::debug!(
+                        target: LOG_TARGET,
+                        ?error,
+                        "failed to handle inbound http request",
+                    );
+                    send_response(stream, Status::BadRequest(error)).await;
+                    None
+                }
+            }
+        });
+    }
+
+    /// Get an idle stream for `key`, if one exists.
+    fn idle_stream(&mut self, key: &StreamKey) -> Option<Stream> {
+        let streams = self.streams.get_mut(key)?;
+
+        let stream = loop {
+            match streams.pop() {
+                None => break None,
+                Some((stream, since)) if since.elapsed() < IDLE_STREAM_TIMEOUT =>
+                    break Some(stream),
+                Some(_) => {}
             }
+        };
+
+        if streams.is_empty() {
+            self.streams.remove(key);
         }
 
-        match Request::parse(buffer[..nread].to_vec()) {
-            Err(error) => Err((stream, error)),
-            Ok(request) => Ok(RequestContext { stream, request }),
+        stream
+    }
+
+    /// Store idle `stream` so it can be reused by a later request with the same `key`.
+    fn store_idle_stream(&mut self, key: StreamKey, stream: Stream) {
+        let streams = self.streams.entry(key).or_default();
+        streams.push((stream, Instant::now()));
+
+        if streams.len() > MAX_IDLE_STREAMS {
+            streams.remove(0);
         }
     }
 
-    /// Handle `request`.
-    ///
-    /// Assembles the validated request into an actual HTTP request and resolves a .i2p host into a
-    /// .b32.i2p host if a .i2p host was used and if address book was enabled.
+    /// Remove expired idle streams and isolated sessions.
+    fn prune_idle_streams(&mut self) {
+        self.streams.retain(|_, streams| {
+            streams.retain(|(_, since)| since.elapsed() < IDLE_STREAM_TIMEOUT);
+            !streams.is_empty()
+        });
+
+        let expired = self
+            .sessions
+            .iter()
+            .filter(|(_, session)| {
+                session.session.is_some()
+                    && session.active == 0
+                    && session.last_used.elapsed() >= SESSION_IDLE_TIMEOUT
+            })
+            .map(|(isolation, _)| isolation.clone())
+            .collect::<Vec<_>>();
+
+        for isolation in expired {
+            self.remove_session(&isolation);
+        }
+    }
+
+    /// Start creating an isolated session for `isolation`.
     ///
-    /// If the outbound request was for an outproxy, ensures that an outproxy has been configured.
+    /// If the maximum number of sessions has been reached, the least recently used idle session is
+    /// destroyed. If there are no idle sessions, the session is not created and `false` is
+    /// returned.
+    fn create_session(&mut self, isolation: String) -> bool {
+        if self.sessions.len() >= MAX_ISOLATED_SESSIONS {
+            let lru = self
+                .sessions
+                .iter()
+                .filter(|(_, session)| session.session.is_some() && session.active == 0)
+                .min_by_key(|(_, session)| session.last_used)
+                .map(|(isolation, _)| isolation.clone());
+
+            match lru {
+                Some(lru) => self.remove_session(&lru),
+                None => {
+                    tracing::warn!(
+                        target: LOG_TARGET,
+                        %isolation,
+                        "maximum number of isolated sessions in use",
+                    );
+                    return false;
+                }
+            }
+        }
+
+        tracing::debug!(
+            target: LOG_TARGET,
+            %isolation,
+            "creating isolated session",
+        );
+
+        let options = SessionOptions {
+            publish: false,
+            samv3_tcp_port: self.samv3_tcp_port,
+            nickname: format!("http-proxy-{:08x}", rand::random::<u32>()),
+            ..Default::default()
+        };
+
+        self.sessions.insert(
+            isolation.clone(),
+            IsolatedSession {
+                active: 0usize,
+                last_used: Instant::now(),
+                pending: Vec::new(),
+                session: None,
+            },
+        );
+        self.pending_sessions
+            .spawn(async move { (isolation, Session::<style::Stream>::new(options).await) });
+
+        true
+    }
+
+    /// Destroy isolated session of `isolation` and its idle streams.
+    fn remove_session(&mut self, isolation: &str) {
+        tracing::debug!(
+            target: LOG_TARGET,
+            %isolation,
+            "destroying isolated session",
+        );
+
+        self.sessions.remove(isolation);
+        self.streams.retain(|(key, _), _| key.as_deref() != Some(isolation));
+    }
+
+    /// Mark an exchange of isolated session `isolation` as finished.
+    fn release_session(&mut self, isolation: &str) {
+        if let Some(session) = self.sessions.get_mut(isolation) {
+            session.active = session.active.saturating_sub(1);
+            session.last_used = Instant::now();
+        }
+    }
+
+    /// Send `request` to `host` and relay the response to `client`.
     ///
-    /// After the final request has been assembled and the host has been resolved, opens a stream to
-    /// the remote destination and if a connection is successfully established, sends the request
-    /// and reads the response which is relayed to client.
-    async fn on_request(&mut self, request: RequestContext) -> Result<(), (TcpStream, HttpError)> {
-        let RequestContext {
-            mut stream,
-            request,
-        } = request;
+    /// If `reused` is true and the stream is found to be closed before any of the response has
+    /// been received, [`Exchange::Retry`] is returned so the request can be sent over a new
+    /// stream. Only retryable requests without a body are sent over reused streams.
+    async fn exchange(
+        mut client: BufferedStream<TcpStream>,
+        stream: Stream,
+        host: String,
+        request: OutboundRequest,
+        reused: bool,
+    ) -> Exchange {
+        let mut server = BufferedStream::new(stream);
+
+        if let Err(error) = server.get_mut().write_all(&request.head).await {
+            if reused {
+                return Exchange::Retry {
+                    client,
+                    host,
+                    request,
+                };
+            }
+
+            tracing::debug!(
+                target: LOG_TARGET,
+                %host,
+                ?error,
+                "failed to send request",
+            );
+            return Exchange::Finished {
+                client: None,
+                host,
+                stream: None,
+            };
+        }
+
+        if request.expect_continue && request.body != Body::None {
+            if let Err(error) = client.get_mut().write_all(b"HTTP/1.1 100 Continue\r\n\r\n").await {
+                tracing::debug!(
+                    target: LOG_TARGET,
+                    ?error,
+                    "failed to send `100 Continue` to client",
+                );
+                return Exchange::Finished {
+                    client: None,
+                    host,
+                    stream: None,
+                };
+            }
+        }
+
+        if let Err(error) = client.relay_body(server.get_mut(), request.body).await {
+            tracing::debug!(
+                target: LOG_TARGET,
+                %host,
+                ?error,
+                "failed to relay request body",
+            );
+            return Exchange::Finished {
+                client: None,
+                host,
+                stream: None,
+            };
+        }
+
+        // read response head, relaying interim responses to client
+        let response = loop {
+            let head = match tokio::time::timeout(RESPONSE_TIMEOUT, server.read_response_head())
+                .await
+                .unwrap_or(Err(HttpError::Io(std::io::ErrorKind::TimedOut)))
+            {
+                Ok(head) => head,
+                Err(HttpError::Io(std::io::ErrorKind::BrokenPipe)) if reused =>
+                    return Exchange::Retry {
+                        client,
+                        host,
+                        request,
+                    },
+                Err(HttpError::Malformed) => {
+                    // not a valid http/1.x response, relay it as-is until the stream is closed
+                    let _ = server.relay_body(client.get_mut(), Body::UntilClose).await;
+
+                    return Exchange::Finished {
+                        client: None,
+                        host,
+                        stream: None,
+                    };
+                }
+                Err(error) => {
+                    tracing::debug!(
+                        target: LOG_TARGET,
+                        %host,
+                        ?error,
+                        "failed to read response",
+                    );
+                    send_response(client.into_parts().0, Status::GatewayTimeout(host.clone()))
+                        .await;
+
+                    return Exchange::Finished {
+                        client: None,
+                        host,
+                        stream: None,
+                    };
+                }
+            };
+
+            let Ok(response) = message::parse_response_head(&head, &request.method) else {
+                tracing::debug!(
+                    target: LOG_TARGET,
+                    %host,
+                    "received invalid response head",
+                );
+                send_response(client.into_parts().0, Status::BadGateway(host.clone())).await;
+
+                return Exchange::Finished {
+                    client: None,
+                    host,
+                    stream: None,
+                };
+            };
+
+            if client.get_mut().write_all(&head).await.is_err() {
+                return Exchange::Finished {
+                    client: None,
+                    host,
+                    stream: None,
+                };
+            }
+
+            match response.status {
+                101 => {
+                    let (mut client, buffer) = client.into_parts();
+                    let (mut stream, response) = server.into_parts();
+
+                    if stream.write_all(&buffer).await.is_ok()
+                        && client.write_all(&response).await.is_ok()
+                    {
+                        let _ = tokio::io::copy_bidirectional(&mut stream, &mut client).await;
+                    }
+
+                    return Exchange::Finished {
+                        client: None,
+                        host,
+                        stream: None,
+                    };
+                }
+                100..=199 => {}
+                _ => break response,
+            }
+        };
 
-        let (host, request) =
-            match request.assemble(&self.address_book_handle, &self.outproxy).await {
-                Ok((host, request)) => (host, request),
-                Err(error) => return Err((stream, error)),
+        if let Err(error) = server.relay_body(client.get_mut(), response.body).await {
+            tracing::debug!(
+                target: LOG_TARGET,
+                %host,
+                ?error,
+                "failed to relay response body",
+            );
+            return Exchange::Finished {
+                client: None,
+                host,
+                stream: None,
             };
+        }
+
+        // the stream cannot be reused if remote sent more than the response
+        let (stream, buffer) = server.into_parts();
+
+        Exchange::Finished {
+            client: (request.keep_alive && response.keep_alive).then_some(client),
+            host,
+            stream: (response.keep_alive && buffer.is_empty()).then_some(stream),
+        }
+    }
+
+    /// Send tunnel `request` to `host` and relay data between `client` and `stream` until either
+    /// of them is closed.
+    async fn tunnel(
+        client: BufferedStream<TcpStream>,
+        mut stream: Stream,
+        host: String,
+        request: OutboundRequest,
+    ) -> Exchange {
+        let (mut client, buffer) = client.into_parts();
+
+        if stream.write_all(&request.head).await.is_ok() && stream.write_all(&buffer).await.is_ok()
+        {
+            let _ = tokio::io::copy_bidirectional(&mut stream, &mut client).await;
+        }
+
+        Exchange::Finished {
+            client: None,
+            host,
+            stream: None,
+        }
+    }
+
+    /// Send `request` to `host`.
+    ///
+    /// If the request is isolated, it's sent using the session of its isolation key which is
+    /// created if it doesn't exist. The request is sent once the session has been created.
+    ///
+    /// Retryable requests without a body are sent over an idle stream to `host`, if one exists.
+    /// Otherwise a new stream is opened.
+    fn send_request(
+        &mut self,
+        client: BufferedStream<TcpStream>,
+        host: String,
+        request: OutboundRequest,
+    ) {
+        if let Some(isolation) = &request.isolation {
+            if !self.sessions.contains_key(isolation) && !self.create_session(isolation.clone()) {
+                self.exchanges.spawn(async move {
+                    send_response(client.into_parts().0, Status::ServiceUnavailable).await;
+
+                    (
+                        None,
+                        Exchange::Finished {
+                            client: None,
+                            host,
+                            stream: None,
+                        },
+                    )
+                });
+                return;
+            }
+
+            if let Some(session) = self.sessions.get_mut(isolation) {
+                session.last_used = Instant::now();
+
+                if session.session.is_none() {
+                    session.pending.push((client, host, request));
+                    return;
+                }
+
+                session.active += 1;
+            }
+        }
+        let isolation = request.isolation.clone();
+
+        if !request.tunnel && request.body == Body::None && request.is_retryable() {
+            if let Some(stream) = self.idle_stream(&(isolation.clone(), host.clone())) {
+                tracing::trace!(
+                    target: LOG_TARGET,
+                    %host,
+                    ?isolation,
+                    "reusing stream",
+                );
+
+                self.exchanges.spawn(async move {
+                    (
+                        isolation,
+                        Self::exchange(client, stream, host, request, true).await,
+                    )
+                });
+                return;
+            }
+        }
 
-        let future = self.session.connect_detached_with_options(
+        let session = match isolation
+            .as_ref()
+            .and_then(|isolation| self.sessions.get_mut(isolation))
+            .and_then(|session| session.session.as_mut())
+        {
+            Some(session) => session,
+            None => &mut self.session,
+        };
+        let future = session.connect_detached_with_options(
             &host,
             StreamOptions {
                 dst_port: 80,
@@ -226,53 +801,218 @@ impl HttpProxy {
             },
         );
 
-        tokio::spawn(async move {
-            match future.await {
+        self.exchanges.spawn(async move {
+            let exchange = match future.await {
                 Err(error) => {
                     tracing::debug!(
                         target: LOG_TARGET,
                         ?error,
                         "failed to connect to destination",
                     );
-                    send_response(stream, Status::GatewayTimeout(host)).await;
-                    Err(error)
+                    send_response(client.into_parts().0, Status::GatewayTimeout(host.clone()))
+                        .await;
+
+                    Exchange::Finished {
+                        client: None,
+                        host,
+                        stream: None,
+                    }
                 }
-                Ok(mut i2p_stream) => {
-                    // write request and read from the stream until it is closed
-                    i2p_stream.write_all(&request).await?;
+                Ok(stream) if request.tunnel => Self::tunnel(client, stream, host, request).await,
+                Ok(stream) => Self::exchange(client, stream, host, request, false).await,
+            };
+
+            (isolation, exchange)
+        });
+    }
+
+    /// Handle address helper of `request`.
+    ///
+    /// Address helpers are only handled for .i2p hosts.
+    ///
+    /// If the address helper is offered for a host that doesn't exist in address book or which
+    /// exists with a different destination, a confirmation page is returned. If the user has
+    /// confirmed the address helper by submitting the page with a `POST` request, the host is saved
+    /// to address book if requested and the client is redirected to the original URL.
+    ///
+    /// Returns `None` if the request should be handled normally.
+    async fn on_address_helper(
+        &mut self,
+        request: &Request,
+        address_helper: AddressHelper,
+    ) -> Result<Option<Status>, HttpError> {
+        let Some(host) = request.i2p_host().map(ToOwned::to_owned) else {
+            return Ok(None);
+        };
+
+        match address_helper {
+            AddressHelper::Offer { destination } => {
+                let b32 = base64_decode(&destination)
+                    .and_then(Destination::parse)
+                    .map(|destination| base32_encode(destination.id().to_vec()))
+                    .ok_or(HttpError::InvalidAddressHelper)?;
+
+                let existing = match &self.address_book_handle {
+                    None => None,
+                    Some(handle) => match handle.resolve_b32(host.clone()) {
+                        Either::Left(host) => Some(host),
+                        Either::Right(future) => future.await,
+                    },
+                };
 
-                    tokio::io::copy_bidirectional(&mut i2p_stream, &mut stream)
-                        .await
-                        .map_err(From::from)
+                if existing.as_ref() == Some(&b32) {
+                    return Ok(None);
                 }
+
+                let nonce = format!("{:032x}", rand::random::<u128>());
+                let url = format!("http://{host}{}", request.path());
+
+                tracing::debug!(
+                    target: LOG_TARGET,
+                    %host,
+                    %b32,
+                    ?existing,
+                    "address helper received",
+                );
+
+                self.address_helpers.insert(
+                    nonce.clone(),
+                    PendingAddressHelper {
+                        b32: b32.clone(),
+                        destination,
+                        host: host.clone(),
+                        url: url.clone(),
+                    },
+                );
+
+                Ok(Some(Status::AddressHelper {
+                    host,
+                    b32,
+                    existing,
+                    nonce,
+                    can_save: self.address_book_handle.is_some(),
+                    url,
+                }))
             }
-        });
+            AddressHelper::Confirm { nonce, save } => {
+                // confirmations are submitted from the confirmation page as `POST` requests
+                if request.method() != "POST" {
+                    return Err(HttpError::InvalidAddressHelper);
+                }
+
+                let pending = self
+                    .address_helpers
+                    .remove(&nonce)
+                    .filter(|pending| pending.host == host)
+                    .ok_or(HttpError::InvalidAddressHelper)?;
+
+                match (save, &self.address_book_handle) {
+                    (true, Some(handle)) =>
+                        handle.add(&pending.host, &pending.destination).await.map_err(|error| {
+                            tracing::warn!(
+                                target: LOG_TARGET,
+                                %host,
+                                ?error,
+                                "failed to save host to address book",
+                            );
+                            HttpError::AddressBookWrite
+                        })?,
+                    _ => {
+                        self.unsaved_hosts.insert(pending.host, pending.b32);
+                    }
+                }
+
+                Ok(Some(Status::Redirect(pending.url)))
+            }
+        }
+    }
+
+    /// Handle `request`.
+    ///
+    /// Assembles the validated request into an actual HTTP request and resolves a .i2p host into a
+    /// .b32.i2p host if a .i2p host was used and if address book was enabled.
+    ///
+    /// If the outbound request was for an outproxy, ensures that an outproxy has been configured.
+    ///
+    /// After the final request has been assembled and the host has been resolved, sends the request
+    /// to the remote destination and relays the response to client.
+    async fn on_request(&mut self, request: RequestContext) -> Result<(), (TcpStream, HttpError)> {
+        let RequestContext {
+            stream,
+            mut request,
+        } = request;
+
+        // isolation key must be selected before the host is resolved
+        let isolation = match self.isolation {
+            IsolationMode::None => None,
+            IsolationMode::Host => Some(request.host().to_owned()),
+            IsolationMode::FirstParty => Some(request.first_party().to_owned()),
+        };
+
+        if let Some(address_helper) = request.address_helper().cloned() {
+            match self.on_address_helper(&request, address_helper).await {
+                Ok(None) => {}
+                Ok(Some(status)) => {
+                    tokio::spawn(send_response(stream.into_parts().0, status));
+                    return Ok(());
+                }
+                Err(error) => return Err((stream.into_parts().0, error)),
+            }
+        }
+
+        // host was visited with an address helper but it wasn't saved to address book
+        if let Some(host) =
+            request.i2p_host().and_then(|host| self.unsaved_hosts.peek(host).cloned())
+        {
+            request.resolve(format!("{host}.b32.i2p"));
+        }
+
+        let hostname = request.host().to_owned();
+        let mut outbound = OutboundRequest {
+            body: request.body(),
+            expect_continue: request.expect_continue(),
+            head: Vec::new(),
+            isolation,
+            keep_alive: request.keep_alive(),
+            method: request.method().to_owned(),
+            tunnel: request.is_tunnel(),
+        };
+        let (host, request) = match request
+            .assemble(
+                &self.address_book_handle,
+                &self.outproxy,
+                &self.header_filter,
+            )
+            .await
+        {
+            Ok((host, request)) => (host, request),
+            Err(HttpError::HostNotFound) => {
+                tokio::spawn(send_response(
+                    stream.into_parts().0,
+                    Status::HostNotFound {
+                        host: hostname,
+                        jump_services: self.jump_services.clone(),
+                    },
+                ));
+                return Ok(());
+            }
+            Err(error) => return Err((stream.into_parts().0, error)),
+        };
+
+        outbound.head = request;
+        self.send_request(stream, host, outbound);
 
         Ok(())
     }
 
     /// Run event loop of [`HttpProxy`].
     pub async fn run(mut self) -> anyhow::Result<()> {
+        let mut prune_timer = tokio::time::interval(IDLE_STREAM_TIMEOUT);
+
         loop {
             tokio::select! {
                 connection = self.listener.accept() => match connection {
-                    Ok((stream, _)) => {
-                        self.requests.spawn(async move {
-                            match tokio::time::timeout(Duration::from_secs(10), Self::read_request(stream)).await {
-                                Err(_) => None,
-                                Ok(Ok(request)) => Some(request),
-                                Ok(Err((stream, error))) => {
-                                    tracing::debug!(
-                                        target: LOG_TARGET,
-                                        ?error,
-                                        "failed to handle inbound http request",
-                                    );
-                                    send_response(stream, Status::BadRequest(error)).await;
-                                    None
-                                }
-                            }
-                        });
-                    }
+                    Ok((stream, _)) => self.read_next_request(BufferedStream::new(stream), REQUEST_TIMEOUT),
                     Err(error) => {
                         tracing::debug!(
                             target: LOG_TARGET,
@@ -296,8 +1036,89 @@ impl HttpProxy {
                         ?error,
                         "failed to poll http request",
                     ),
-                }
-            }
+                },
+                exchange = self.exchanges.join_next(), if !self.exchanges.is_empty() => match exchange {
+                    None => {}
+                    Some(Ok((isolation, Exchange::Finished { client, host, stream }))) => {
+                        if let Some(isolation) = &isolation {
+                            self.release_session(isolation);
+                        }
+
+                        if let Some(stream) = stream {
+                            self.store_idle_stream((isolation, host), stream);
+                        }
+
+                        if let Some(client) = client {
+                            self.read_next_request(client, KEEP_ALIVE_TIMEOUT);
+                        }
+                    }
+                    Some(Ok((isolation, Exchange::Retry { client, host, request }))) => {
+                        tracing::debug!(
+                            target: LOG_TARGET,
+                            %host,
+                            "reused stream was closed, retrying request",
+                        );
+
+                        if let Some(isolation) = &isolation {
+                            self.release_session(isolation);
+                        }
+
+                        // other idle streams to the host are likely to have been closed as well
+                        self.streams.remove(&(isolation, host.clone()));
+                        self.send_request(client, host, request);
+                    }
+                    Some(Err(error)) => tracing::debug!(
+                        target: LOG_TARGET,
+                        ?error,
+                        "failed to poll http exchange",
+                    ),
+                },
+                session = self.pending_sessions.join_next(), if !self.pending_sessions.is_empty() => match session {
+                    None => {}
+                    Some(Ok((isolation, Ok(session)))) => {
+                        tracing::debug!(
+                            target: LOG_TARGET,
+                            %isolation,
+                            "isolated session created",
+                        );
+
+                        let pending = match self.sessions.get_mut(&isolation) {
+                            None => Vec::new(),
+                            Some(isolated) => {
+                                isolated.session = Some(session);
+                                std::mem::take(&mut isolated.pending)
+                            }
+                        };
+
+                        for (client, host, request) in pending {
+                            self.send_request(client, host, request);
+                        }
+                    }
+                    Some(Ok((isolation, Err(error)))) => {
+                        tracing::warn!(
+                            target: LOG_TARGET,
+                            %isolation,
+                            ?error,
+                            "failed to create isolated session",
+                        );
+
+                        if let Some(isolated) = self.sessions.remove(&isolation) {
+                            for (client, host, _) in isolated.pending {
+                                tokio::spawn(send_response(
+                                    client.into_parts().0,
+                                    Status::GatewayTimeout(host),
+                                ));
+                            }
+                        }
+                    }
+                    Some(Err(error)) => tracing::debug!(
+                        target: LOG_TARGET,
+                        ?error,
+                        "failed to poll isolated session",
+                    ),
+                },
+                _ = prune_timer.tick() => self.prune_idle_streams(),
+            }
         }
     }
 }
@@ -307,16 +1128,37 @@ mod tests {
     use super::*;
     use crate::{address_book::AddressBookManager, config::AddressBookConfig};
     use reqwest::{
-        header::{HeaderMap, HeaderValue, CONNECTION},
+        header::{HeaderMap, HeaderValue, CONNECTION, REFERER},
         Client, Proxy, StatusCode,
     };
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
     use tempfile::tempdir;
-    use tokio::io::{AsyncBufReadExt, BufReader};
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
+
+    /// Destination of `zzz.i2p`.
+    const DESTINATION: &str = "GKapJ8koUcBj~jmQzHsTYxDg2tpfWj0xjQTzd8BhfC9c3OS5fwPBNajgF-eOD6eCjFTqTlorlh7Hnd8kXj1qblUGXT-tDoR9\
+        ~YV8dmXl51cJn9MVTRrEqRWSJVXbUUz9t5Po6Xa247Vr0sJn27R4KoKP8QVj1GuH6dB3b6wTPbOamC3dkO18vkQkfZWUdRMD\
+        Xk0d8AdjB0E0864nOT~J9Fpnd2pQE5uoFT6P0DqtQR2jsFvf9ME61aqLvKPPWpkgdn4z6Zkm-NJOcDz2Nv8Si7hli94E9Sgh\
+        MYRsdjU-knObKvxiagn84FIwcOpepxuG~kFXdD5NfsH0v6Uri3usE3XWD7Pw6P8qVYF39jUIq4OiNMwPnNYzy2N4mDMQdsdH\
+        O3LUVh~DEppOy9AAmEoHDjjJxt2BFBbGxfdpZCpENkwvmZeYUyNCCzASqTOOlNzdpne8cuesn3NDXIpNnqEE6Oe5Qm5YOJyk\
+        rX~Vx~cFFT3QzDGkIjjxlFBsjUJyYkFjBQAEAAcAAA==";
 
     /// Fake SAMv3 server.
+    ///
+    /// Streams opened with `STREAM CONNECT` are served by a keep-alive HTTP/1.1 server which
+    /// responds to each request with its method, path and body.
     struct SamServer {
         /// TCP listener for the server.
         listener: TcpListener,
+
+        /// Number of sessions created with `SESSION CREATE`.
+        sessions: Arc<AtomicUsize>,
+
+        /// Number of streams opened with `STREAM CONNECT`.
+        streams: Arc<AtomicUsize>,
     }
 
     impl SamServer {
@@ -324,16 +1166,70 @@ mod tests {
         async fn new() -> Self {
             let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
 
-            Self { listener }
+            Self {
+                listener,
+                sessions: Default::default(),
+                streams: Default::default(),
+            }
+        }
+
+        /// Serve HTTP requests received over `stream`.
+        async fn serve_http(stream: BufReader<TcpStream>) {
+            let mut stream = BufferedStream::new(stream);
+
+            while let Ok(head) = stream.read_request_head().await {
+                let mut headers = [httparse::EMPTY_HEADER; 64];
+                let mut request = httparse::Request::new(&mut headers);
+                request.parse(&head).unwrap();
+
+                let body = message::request_body(request.headers).unwrap();
+                let mut response =
+                    format!("{} {}\n", request.method.unwrap(), request.path.unwrap()).into_bytes();
+
+                if stream.relay_body(&mut response, body).await.is_err() {
+                    break;
+                }
+
+                let head = match request.path {
+                    Some("/invalid-response") => format!(
+                        "HTTP/1.1 200 OK\r\nContent-Length: {0}\r\nContent-Length: {0}\r\n\r\n",
+                        response.len()
+                    ),
+                    _ => format!(
+                        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n",
+                        response.len()
+                    ),
+                };
+
+                if stream.get_mut().write_all(head.as_bytes()).await.is_err()
+                    || stream.get_mut().write_all(&response).await.is_err()
+                {
+                    break;
+                }
+            }
         }
 
         /// Run the event loop of [`SamServer`].
         async fn run(self) {
             while let Ok((stream, _)) = self.listener.accept().await {
+                let sessions = Arc::clone(&self.sessions);
+                let streams = Arc::clone(&self.streams);
+
                 tokio::spawn(async move {
                     let mut lines = BufReader::new(stream).lines();
 
                     while let Ok(Some(command)) = lines.next_line().await {
+                        if command.starts_with("STREAM CONNECT") {
+                            streams.fetch_add(1, Ordering::Relaxed);
+                            lines
+                                .get_mut()
+                                .write_all("STREAM STATUS RESULT=OK\n".as_bytes())
+                                .await
+                                .unwrap();
+
+                            return Self::serve_http(lines.into_inner()).await;
+                        }
+
                         if command.starts_with("HELLO VERSION") {
                             lines
                                 .get_mut()
@@ -344,6 +1240,7 @@ mod tests {
                         }
 
                         if command.starts_with("SESSION CREATE") {
+                            sessions.fetch_add(1, Ordering::Relaxed);
                             lines
                                 .get_mut()
                                 .write_all(
@@ -407,6 +1304,10 @@ mod tests {
                 port: 0,
                 host: "127.0.0.1".to_string(),
                 outproxy: None,
+                jump_services: None,
+                eepsite_headers: None,
+                outproxy_headers: None,
+                isolation: None,
             },
             sam_port,
             None,
@@ -441,6 +1342,10 @@ mod tests {
                 port: 0,
                 host: "127.0.0.1".to_string(),
                 outproxy: None,
+                jump_services: None,
+                eepsite_headers: None,
+                outproxy_headers: None,
+                isolation: None,
             },
             sam_port,
             None,
@@ -499,6 +1404,7 @@ mod tests {
                 AddressBookConfig {
                     default: None,
                     subscriptions: None,
+                    refresh_interval: None,
                 },
             )
             .handle()
@@ -509,6 +1415,10 @@ mod tests {
                 port: 0,
                 host: "127.0.0.1".to_string(),
                 outproxy: None,
+                jump_services: None,
+                eepsite_headers: None,
+                outproxy_headers: None,
+                isolation: None,
             },
             sam_port,
             None,
@@ -557,6 +1467,10 @@ mod tests {
                 port: 0,
                 host: "127.0.0.1".to_string(),
                 outproxy: None,
+                jump_services: None,
+                eepsite_headers: None,
+                outproxy_headers: None,
+                isolation: None,
             },
             sam_port,
             None,
@@ -609,6 +1523,10 @@ mod tests {
                 port: 0,
                 host: "127.0.0.1".to_string(),
                 outproxy: Some("outproxy.i2p".to_string()),
+                jump_services: None,
+                eepsite_headers: None,
+                outproxy_headers: None,
+                isolation: None,
             },
             sam_port,
             None,
@@ -667,6 +1585,7 @@ mod tests {
                 AddressBookConfig {
                     default: None,
                     subscriptions: None,
+                    refresh_interval: None,
                 },
             )
             .handle()
@@ -677,6 +1596,10 @@ mod tests {
                 port: 0,
                 host: "127.0.0.1".to_string(),
                 outproxy: Some("outproxy.i2p".to_string()),
+                jump_services: None,
+                eepsite_headers: None,
+                outproxy_headers: None,
+                isolation: None,
             },
             sam_port,
             None,
@@ -763,6 +1686,7 @@ mod tests {
                 AddressBookConfig {
                     default: None,
                     subscriptions: None,
+                    refresh_interval: None,
                 },
             )
             .handle()
@@ -775,6 +1699,10 @@ mod tests {
                     port: 0,
                     host: "127.0.0.1".to_string(),
                     outproxy: Some("zzz.i2p".to_string()),
+                    jump_services: None,
+                    eepsite_headers: None,
+                    outproxy_headers: None,
+                    isolation: None,
                 },
                 sam_port,
                 None,
@@ -796,6 +1724,10 @@ mod tests {
                     port: 0,
                     host: "127.0.0.1".to_string(),
                     outproxy: Some("www.zzz.i2p".to_string()),
+                    jump_services: None,
+                    eepsite_headers: None,
+                    outproxy_headers: None,
+                    isolation: None,
                 },
                 sam_port,
                 None,
@@ -817,6 +1749,10 @@ mod tests {
                     port: 0,
                     host: "127.0.0.1".to_string(),
                     outproxy: Some("http://zzz.i2p".to_string()),
+                    jump_services: None,
+                    eepsite_headers: None,
+                    outproxy_headers: None,
+                    isolation: None,
                 },
                 sam_port,
                 None,
@@ -838,6 +1774,10 @@ mod tests {
                     port: 0,
                     host: "127.0.0.1".to_string(),
                     outproxy: Some("http://www.zzz.i2p".to_string()),
+                    jump_services: None,
+                    eepsite_headers: None,
+                    outproxy_headers: None,
+                    isolation: None,
                 },
                 sam_port,
                 None,
@@ -862,6 +1802,10 @@ mod tests {
                         "http://www.lhbd7ojcaiofbfku7ixh47qj537g572zmhdc4oilvugzxdpdghua.b32.i2p"
                             .to_string(),
                     ),
+                    jump_services: None,
+                    eepsite_headers: None,
+                    outproxy_headers: None,
+                    isolation: None,
                 },
                 sam_port,
                 None,
@@ -886,6 +1830,10 @@ mod tests {
                         "http://lhbd7ojcaiofbfku7ixh47qj537g572zmhdc4oilvugzxdpdghua.b32.i2p"
                             .to_string(),
                     ),
+                    jump_services: None,
+                    eepsite_headers: None,
+                    outproxy_headers: None,
+                    isolation: None,
                 },
                 sam_port,
                 None,
@@ -910,6 +1858,10 @@ mod tests {
                         "www.lhbd7ojcaiofbfku7ixh47qj537g572zmhdc4oilvugzxdpdghua.b32.i2p"
                             .to_string(),
                     ),
+                    jump_services: None,
+                    eepsite_headers: None,
+                    outproxy_headers: None,
+                    isolation: None,
                 },
                 sam_port,
                 None,
@@ -933,6 +1885,10 @@ mod tests {
                     outproxy: Some(
                         "lhbd7ojcaiofbfku7ixh47qj537g572zmhdc4oilvugzxdpdghua.b32.i2p".to_string(),
                     ),
+                    jump_services: None,
+                    eepsite_headers: None,
+                    outproxy_headers: None,
+                    isolation: None,
                 },
                 sam_port,
                 None,
@@ -947,4 +1903,583 @@ mod tests {
             );
         }
     }
+
+    #[tokio::test]
+    async fn address_helper_saved_to_address_book() {
+        let sam_port = {
+            let sam = SamServer::new().await;
+            let port = sam.listener.local_addr().unwrap().port();
+            tokio::spawn(sam.run());
+
+            port
+        };
+
+        // create empty address book
+        let dir = tempdir().unwrap().keep();
+        tokio::fs::create_dir_all(&dir.join("addressbook")).await.unwrap();
+        tokio::fs::File::create(dir.join("addressbook/addresses")).await.unwrap();
+
+        let address_book = AddressBookManager::new(
+            dir.clone(),
+            AddressBookConfig {
+                default: None,
+                subscriptions: None,
+                refresh_interval: None,
+            },
+        )
+        .handle();
+
+        let proxy = HttpProxy::new(
+            HttpProxyConfig {
+                port: 0,
+                host: "127.0.0.1".to_string(),
+                outproxy: None,
+                jump_services: None,
+                eepsite_headers: None,
+                outproxy_headers: None,
+                isolation: None,
+            },
+            sam_port,
+            None,
+            Some(address_book),
+        )
+        .await
+        .unwrap();
+        let port = proxy.listener.local_addr().unwrap().port();
+        tokio::spawn(proxy.run());
+
+        let client = Client::builder()
+            .proxy(Proxy::http(format!("http://127.0.0.1:{port}")).expect("to succeed"))
+            .redirect(reqwest::redirect::Policy::none())
+            .http1_title_case_headers()
+            .build()
+            .expect("to succeed");
+
+        let response = client
+            .get(format!(
+                "http://zzz.i2p/forums?i2paddresshelper={DESTINATION}"
+            ))
+            .header(CONNECTION, HeaderValue::from_static("close"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers().get("x-frame-options").unwrap(), "DENY");
+        assert_eq!(
+            response.headers().get("content-security-policy").unwrap(),
+            "frame-ancestors 'none'"
+        );
+
+        // extract confirmation link from the page
+        let page = response.text().await.unwrap();
+        assert!(page.contains("lhbd7ojcaiofbfku7ixh47qj537g572zmhdc4oilvugzxdpdghua.b32.i2p"));
+        assert!(!page.contains("Warning"));
+
+        let link = page
+            .split("action=\"")
+            .skip(1)
+            .map(|link| link.split('"').next().unwrap().replace("&amp;", "&"))
+            .find(|link| link.contains("i2paddresshelperaction=save"))
+            .unwrap();
+        assert!(link.starts_with("http://zzz.i2p/forums?i2paddresshelpernonce="));
+
+        // confirmation must be submitted with `POST`
+        let response = client
+            .get(&link)
+            .header(CONNECTION, HeaderValue::from_static("close"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = client
+            .post(&link)
+            .header(CONNECTION, HeaderValue::from_static("close"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        assert_eq!(
+            response.headers().get("location").unwrap().to_str().unwrap(),
+            "http://zzz.i2p/forums"
+        );
+        assert!(tokio::fs::read_to_string(dir.join("addressbook/local"))
+            .await
+            .unwrap()
+            .starts_with("zzz.i2p="));
+
+        // nonce can only be used once
+        let response = client
+            .post(&link)
+            .header(CONNECTION, HeaderValue::from_static("close"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn jump_services_listed() {
+        let sam_port = {
+            let sam = SamServer::new().await;
+            let port = sam.listener.local_addr().unwrap().port();
+            tokio::spawn(sam.run());
+
+            port
+        };
+
+        // create empty address book
+        let address_book = {
+            let dir = tempdir().unwrap().keep();
+            tokio::fs::create_dir_all(&dir.join("addressbook")).await.unwrap();
+            tokio::fs::File::create(dir.join("addressbook/addresses")).await.unwrap();
+
+            AddressBookManager::new(
+                dir.clone(),
+                AddressBookConfig {
+                    default: None,
+                    subscriptions: None,
+                    refresh_interval: None,
+                },
+            )
+            .handle()
+        };
+
+        let proxy = HttpProxy::new(
+            HttpProxyConfig {
+                port: 0,
+                host: "127.0.0.1".to_string(),
+                outproxy: None,
+                jump_services: Some(vec!["http://jump.i2p/jump/".to_string()]),
+                eepsite_headers: None,
+                outproxy_headers: None,
+                isolation: None,
+            },
+            sam_port,
+            None,
+            Some(address_book),
+        )
+        .await
+        .unwrap();
+        let port = proxy.listener.local_addr().unwrap().port();
+        tokio::spawn(proxy.run());
+
+        let client = Client::builder()
+            .proxy(Proxy::http(format!("http://127.0.0.1:{port}")).expect("to succeed"))
+            .http1_title_case_headers()
+            .build()
+            .expect("to succeed");
+
+        let response = client
+            .get("http://zzz.i2p")
+            .header(CONNECTION, HeaderValue::from_static("close"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::from_u16(400).unwrap());
+
+        let page = response.text().await.unwrap();
+        assert!(page.contains("Host not found in address book"));
+        assert!(page.contains("href=\"http://jump.i2p/jump/zzz.i2p\""));
+        assert!(!page.contains("stats.i2p"));
+    }
+
+    #[tokio::test]
+    async fn stream_reused_for_keep_alive_requests() {
+        let (sam_port, streams) = {
+            let sam = SamServer::new().await;
+            let port = sam.listener.local_addr().unwrap().port();
+            let streams = Arc::clone(&sam.streams);
+            tokio::spawn(sam.run());
+
+            (port, streams)
+        };
+
+        let proxy = HttpProxy::new(
+            HttpProxyConfig {
+                port: 0,
+                host: "127.0.0.1".to_string(),
+                outproxy: None,
+                jump_services: None,
+                eepsite_headers: None,
+                outproxy_headers: None,
+                isolation: None,
+            },
+            sam_port,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+        let port = proxy.listener.local_addr().unwrap().port();
+        tokio::spawn(proxy.run());
+
+        let client = Client::builder()
+            .proxy(Proxy::http(format!("http://127.0.0.1:{port}")).expect("to succeed"))
+            .http1_title_case_headers()
+            .build()
+            .expect("to succeed");
+
+        for i in 0..5 {
+            let response = client
+                .get(format!(
+                    "http://lhbd7ojcaiofbfku7ixh47qj537g572zmhdc4oilvugzxdpdghua.b32.i2p/page{i}"
+                ))
+                .send()
+                .await
+                .unwrap();
+
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.text().await.unwrap(), format!("GET /page{i}\n"));
+        }
+
+        // large request body is streamed over a new stream
+        let response = client
+            .post("http://lhbd7ojcaiofbfku7ixh47qj537g572zmhdc4oilvugzxdpdghua.b32.i2p/upload")
+            .body(vec![0xaa; 2 * 1024 * 1024])
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.bytes().await.unwrap().len(),
+            "POST /upload\n".len() + 2 * 1024 * 1024
+        );
+        assert_eq!(streams.load(Ordering::Relaxed), 2);
+    }
+
+    #[tokio::test]
+    async fn unsafe_requests_not_sent_over_reused_streams() {
+        let (sam_port, streams) = {
+            let sam = SamServer::new().await;
+            let port = sam.listener.local_addr().unwrap().port();
+            let streams = Arc::clone(&sam.streams);
+            tokio::spawn(sam.run());
+
+            (port, streams)
+        };
+
+        let proxy = HttpProxy::new(
+            HttpProxyConfig {
+                port: 0,
+                host: "127.0.0.1".to_string(),
+                outproxy: None,
+                jump_services: None,
+                eepsite_headers: None,
+                outproxy_headers: None,
+                isolation: None,
+            },
+            sam_port,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+        let port = proxy.listener.local_addr().unwrap().port();
+        tokio::spawn(proxy.run());
+
+        let client = Client::builder()
+            .proxy(Proxy::http(format!("http://127.0.0.1:{port}")).expect("to succeed"))
+            .http1_title_case_headers()
+            .build()
+            .expect("to succeed");
+        let url = "http://lhbd7ojcaiofbfku7ixh47qj537g572zmhdc4oilvugzxdpdghua.b32.i2p/";
+
+        let response = client.get(url).send().await.unwrap();
+        assert_eq!(response.text().await.unwrap(), "GET /\n");
+        assert_eq!(streams.load(Ordering::Relaxed), 1);
+
+        // post without a body must not be sent over the idle stream as it cannot be retried
+        let response = client.post(url).send().await.unwrap();
+        assert_eq!(response.text().await.unwrap(), "POST /\n");
+        assert_eq!(streams.load(Ordering::Relaxed), 2);
+
+        let response = client.get(url).send().await.unwrap();
+        assert_eq!(response.text().await.unwrap(), "GET /\n");
+        assert_eq!(streams.load(Ordering::Relaxed), 2);
+    }
+
+    #[tokio::test]
+    async fn invalid_response_head() {
+        let sam_port = {
+            let sam = SamServer::new().await;
+            let port = sam.listener.local_addr().unwrap().port();
+            tokio::spawn(sam.run());
+
+            port
+        };
+
+        let proxy = HttpProxy::new(
+            HttpProxyConfig {
+                port: 0,
+                host: "127.0.0.1".to_string(),
+                outproxy: None,
+                jump_services: None,
+                eepsite_headers: None,
+                outproxy_headers: None,
+                isolation: None,
+            },
+            sam_port,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+        let port = proxy.listener.local_addr().unwrap().port();
+        tokio::spawn(proxy.run());
+
+        let client = Client::builder()
+            .proxy(Proxy::http(format!("http://127.0.0.1:{port}")).expect("to succeed"))
+            .http1_title_case_headers()
+            .build()
+            .expect("to succeed");
+
+        let response = client
+            .get("http://lhbd7ojcaiofbfku7ixh47qj537g572zmhdc4oilvugzxdpdghua.b32.i2p/invalid-response")
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+    }
+
+    #[tokio::test]
+    async fn chunked_request_pipelined() {
+        let sam_port = {
+            let sam = SamServer::new().await;
+            let port = sam.listener.local_addr().unwrap().port();
+            tokio::spawn(sam.run());
+
+            port
+        };
+
+        let proxy = HttpProxy::new(
+            HttpProxyConfig {
+                port: 0,
+                host: "127.0.0.1".to_string(),
+                outproxy: None,
+                jump_services: None,
+                eepsite_headers: None,
+                outproxy_headers: None,
+                isolation: None,
+            },
+            sam_port,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+        let address = proxy.listener.local_addr().unwrap();
+        tokio::spawn(proxy.run());
+
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        stream
+            .write_all(
+                "POST http://lhbd7ojcaiofbfku7ixh47qj537g572zmhdc4oilvugzxdpdghua.b32.i2p/upload HTTP/1.1\r\n\
+                Host: lhbd7ojcaiofbfku7ixh47qj537g572zmhdc4oilvugzxdpdghua.b32.i2p\r\n\
+                Transfer-Encoding: chunked\r\n\r\n\
+                5\r\nhello\r\n7\r\n, world\r\n0\r\n\r\n\
+                GET http://lhbd7ojcaiofbfku7ixh47qj537g572zmhdc4oilvugzxdpdghua.b32.i2p/ HTTP/1.1\r\n\
+                Host: lhbd7ojcaiofbfku7ixh47qj537g572zmhdc4oilvugzxdpdghua.b32.i2p\r\n\
+                Connection: close\r\n\r\n"
+                    .as_bytes(),
+            )
+            .await
+            .unwrap();
+
+        let mut stream = BufferedStream::new(stream);
+
+        for expected in [
+            "POST /upload\n5\r\nhello\r\n7\r\n, world\r\n0\r\n\r\n",
+            "GET /\n",
+        ] {
+            let head = stream.read_response_head().await.unwrap();
+            let response = message::parse_response_head(&head, "GET").unwrap();
+            let mut body = Vec::new();
+
+            assert_eq!(response.status, 200);
+            stream.relay_body(&mut body, response.body).await.unwrap();
+            assert_eq!(std::str::from_utf8(&body).unwrap(), expected);
+        }
+
+        // connection is closed after the second request
+        let (mut stream, _) = stream.into_parts();
+        assert_eq!(stream.read(&mut [0u8; 64]).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn destination_isolated_per_host() {
+        let (sam_port, sessions, streams) = {
+            let sam = SamServer::new().await;
+            let port = sam.listener.local_addr().unwrap().port();
+            let sessions = Arc::clone(&sam.sessions);
+            let streams = Arc::clone(&sam.streams);
+            tokio::spawn(sam.run());
+
+            (port, sessions, streams)
+        };
+
+        let proxy = HttpProxy::new(
+            HttpProxyConfig {
+                port: 0,
+                host: "127.0.0.1".to_string(),
+                outproxy: None,
+                jump_services: None,
+                eepsite_headers: None,
+                outproxy_headers: None,
+                isolation: Some(IsolationMode::Host),
+            },
+            sam_port,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+        let port = proxy.listener.local_addr().unwrap().port();
+        tokio::spawn(proxy.run());
+
+        let client = Client::builder()
+            .proxy(Proxy::http(format!("http://127.0.0.1:{port}")).expect("to succeed"))
+            .http1_title_case_headers()
+            .build()
+            .expect("to succeed");
+
+        for host in [
+            "lhbd7ojcaiofbfku7ixh47qj537g572zmhdc4oilvugzxdpdghua",
+            "lhbd7ojcaiofbfku7ixh47qj537g572zmhdc4oilvugzxdpdghua",
+            "shx5vqsw7usdaunyzr2qmes2fq37oumybpudrd4jjj4e4vk4uusa",
+        ] {
+            let response = client.get(format!("http://{host}.b32.i2p/")).send().await.unwrap();
+
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.text().await.unwrap(), "GET /\n");
+        }
+
+        // shared session of the proxy and one session for each host
+        assert_eq!(sessions.load(Ordering::Relaxed), 3);
+
+        // stream to the first host was reused
+        assert_eq!(streams.load(Ordering::Relaxed), 2);
+    }
+
+    #[tokio::test]
+    async fn destination_isolated_per_first_party() {
+        let (sam_port, sessions) = {
+            let sam = SamServer::new().await;
+            let port = sam.listener.local_addr().unwrap().port();
+            let sessions = Arc::clone(&sam.sessions);
+            tokio::spawn(sam.run());
+
+            (port, sessions)
+        };
+
+        let proxy = HttpProxy::new(
+            HttpProxyConfig {
+                port: 0,
+                host: "127.0.0.1".to_string(),
+                outproxy: None,
+                jump_services: None,
+                eepsite_headers: None,
+                outproxy_headers: None,
+                isolation: Some(IsolationMode::FirstParty),
+            },
+            sam_port,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+        let port = proxy.listener.local_addr().unwrap().port();
+        tokio::spawn(proxy.run());
+
+        let client = Client::builder()
+            .proxy(Proxy::http(format!("http://127.0.0.1:{port}")).expect("to succeed"))
+            .http1_title_case_headers()
+            .build()
+            .expect("to succeed");
+
+        let site = "http://lhbd7ojcaiofbfku7ixh47qj537g572zmhdc4oilvugzxdpdghua.b32.i2p/";
+        let resource =
+            "http://shx5vqsw7usdaunyzr2qmes2fq37oumybpudrd4jjj4e4vk4uusa.b32.i2p/image.png";
+
+        // site and the resource it loads use the same session
+        for (url, referer) in [(site, None), (resource, Some(site))] {
+            let mut request = client.get(url);
+
+            if let Some(referer) = referer {
+                request = request.header(REFERER, referer);
+            }
+
+            assert_eq!(request.send().await.unwrap().status(), StatusCode::OK);
+        }
+        assert_eq!(sessions.load(Ordering::Relaxed), 2);
+
+        // resource visited directly uses a session of its own
+        assert_eq!(
+            client.get(resource).send().await.unwrap().status(),
+            StatusCode::OK
+        );
+        assert_eq!(sessions.load(Ordering::Relaxed), 3);
+
+        // link followed from the site uses a session of its own
+        let response = client
+            .get("http://ukeu3k5oycgaauneqgtnvselmt4yemvoilkln7jpvamvfx7dnkdq.b32.i2p/")
+            .header(REFERER, site)
+            .header("Sec-Fetch-Dest", "document")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(sessions.load(Ordering::Relaxed), 4);
+    }
+
+    #[tokio::test]
+    async fn isolated_sessions_capped() {
+        let sam_port = {
+            let sam = SamServer::new().await;
+            let port = sam.listener.local_addr().unwrap().port();
+            tokio::spawn(sam.run());
+
+            port
+        };
+
+        let mut proxy = HttpProxy::new(
+            HttpProxyConfig {
+                port: 0,
+                host: "127.0.0.1".to_string(),
+                outproxy: None,
+                jump_services: None,
+                eepsite_headers: None,
+                outproxy_headers: None,
+                isolation: Some(IsolationMode::FirstParty),
+            },
+            sam_port,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+
+        // all sessions are still being created and none of them can be evicted
+        for i in 0..MAX_ISOLATED_SESSIONS {
+            assert!(proxy.create_session(format!("host{i}.i2p")));
+        }
+        assert!(!proxy.create_session("host.i2p".to_string()));
+        assert_eq!(proxy.sessions.len(), MAX_ISOLATED_SESSIONS);
+
+        // idle session is evicted
+        let session = Session::<style::Stream>::new(SessionOptions {
+            publish: false,
+            samv3_tcp_port: sam_port,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        proxy.sessions.get_mut("host0.i2p").unwrap().session = Some(session);
+
+        assert!(proxy.create_session("host.i2p".to_string()));
+        assert!(!proxy.sessions.contains_key("host0.i2p"));
+        assert_eq!(proxy.sessions.len(), MAX_ISOLATED_SESSIONS);
+    }
 }
@@ -16,27 +16,211 @@
 // FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
 // DEALINGS IN THE SOFTWARE.
 
-use crate::proxy::http::{HttpError, LOG_TARGET};
+use crate::{
+    address_book::AddressBookHandle,
+    config::HeaderPolicyConfig,
+    proxy::http::{
+        message::{self, Body},
+        HttpError, LOG_TARGET,
+    },
+};
 
 use emissary_core::runtime::AddressBook;
 use futures::future::Either;
 
-use std::sync::{Arc, LazyLock};
-
-/// Illegal HTTP headers that get removed from the inbound HTTP request.
-static ILLEGAL: LazyLock<Vec<&'static str>> = LazyLock::new(|| {
-    Vec::from_iter([
-        "accept",
-        "referer",
-        "x-requested-with",
-        "via",
-        "from",
-        "forwarded",
-        "dnt",
-        "x-forwarded",
-        "proxy-",
-    ])
-});
+/// Query parameter of an address helper.
+const ADDRESS_HELPER: &str = "i2paddresshelper";
+
+/// Query parameter of the nonce of an address helper confirmation.
+pub const ADDRESS_HELPER_NONCE: &str = "i2paddresshelpernonce";
+
+/// Query parameter of the action of an address helper confirmation.
+pub const ADDRESS_HELPER_ACTION: &str = "i2paddresshelperaction";
+
+/// Address helper confirmation action which saves the host to address book.
+pub const ADDRESS_HELPER_SAVE: &str = "save";
+
+/// Headers removed from requests to eepsites, unless explicitly allowed.
+const EEPSITE_DENY: [&str; 8] = [
+    "accept*",
+    "dnt",
+    "forwarded",
+    "from",
+    "via",
+    "x-forwarded*",
+    "x-real-ip",
+    "x-requested-with",
+];
+
+/// Headers removed from requests to outproxy, unless explicitly allowed.
+const OUTPROXY_DENY: [&str; 10] = [
+    "accept*",
+    "cookie",
+    "cookie2",
+    "dnt",
+    "forwarded",
+    "from",
+    "via",
+    "x-forwarded*",
+    "x-real-ip",
+    "x-requested-with",
+];
+
+/// Headers forwarded by default even though they match a default deny rule.
+const DEFAULT_ALLOW: [&str; 2] = ["accept", "accept-encoding"];
+
+/// Headers which are only forwarded by default if they point to the host of the request.
+const SAME_ORIGIN: [&str; 2] = ["origin", "referer"];
+
+/// Headers which are required for message framing and are always forwarded.
+const FRAMING: [&str; 3] = ["content-length", "transfer-encoding", "upgrade"];
+
+/// Headers which are managed by the proxy and are never forwarded as-is.
+const MANAGED: [&str; 6] = [
+    "connection",
+    "expect",
+    "host",
+    "keep-alive",
+    "proxy-*",
+    "user-agent",
+];
+
+/// Does header `name` match `pattern`.
+///
+/// Patterns are case-insensitive header names and a trailing `*` matches any suffix.
+fn header_matches(pattern: &str, name: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) =>
+            name.len() >= prefix.len() && name[..prefix.len()].eq_ignore_ascii_case(prefix),
+        None => name.eq_ignore_ascii_case(pattern),
+    }
+}
+
+/// What to do with a header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeaderRule {
+    /// Forward the header as-is.
+    Allow,
+
+    /// Remove the header.
+    Deny,
+
+    /// Forward the header only if it points to the host of the request.
+    SameOrigin,
+}
+
+/// Header policy for one kind of remote host.
+#[derive(Debug, Clone)]
+pub struct HeaderPolicy {
+    /// Header patterns allowed by the user.
+    allow: Vec<String>,
+
+    /// Headers removed unless explicitly allowed.
+    default_deny: &'static [&'static str],
+
+    /// Header patterns denied by the user.
+    deny: Vec<String>,
+}
+
+impl HeaderPolicy {
+    /// Create new [`HeaderPolicy`] from `config`.
+    ///
+    /// Invalid patterns are ignored.
+    fn new(config: Option<HeaderPolicyConfig>, default_deny: &'static [&'static str]) -> Self {
+        let HeaderPolicyConfig { allow, deny } = config.unwrap_or_default();
+        let validate = |patterns: Option<Vec<String>>| {
+            patterns
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|pattern| {
+                    let name = pattern.strip_suffix('*').unwrap_or(pattern);
+                    let valid = name.bytes().all(|byte| {
+                        byte.is_ascii_alphanumeric() || b"!#$%&'+-.^_`|~".contains(&byte)
+                    });
+
+                    if !valid || (name.is_empty() && !pattern.ends_with('*')) {
+                        tracing::warn!(
+                            target: LOG_TARGET,
+                            %pattern,
+                            "ignoring invalid header pattern",
+                        );
+                        return false;
+                    }
+
+                    true
+                })
+                .map(|pattern| pattern.to_ascii_lowercase())
+                .collect::<Vec<_>>()
+        };
+
+        Self {
+            allow: validate(allow),
+            default_deny,
+            deny: validate(deny),
+        }
+    }
+
+    /// Get rule for header `name`.
+    ///
+    /// Headers required for message framing are always forwarded. Explicitly denied headers are
+    /// removed and explicitly allowed headers are forwarded, with deny rules taking precedence.
+    /// Other headers are handled according to the default policy where identifying headers are
+    /// removed and `Referer` and `Origin` are only forwarded within the same site.
+    ///
+    /// Headers managed by the proxy, such as `Host` and `User-Agent`, are not subject to policy.
+    pub fn rule(&self, name: &str) -> HeaderRule {
+        if FRAMING.iter().any(|framing| name.eq_ignore_ascii_case(framing)) {
+            return HeaderRule::Allow;
+        }
+
+        if self.deny.iter().any(|pattern| header_matches(pattern, name)) {
+            return HeaderRule::Deny;
+        }
+
+        if self.allow.iter().any(|pattern| header_matches(pattern, name))
+            || DEFAULT_ALLOW.iter().any(|pattern| header_matches(pattern, name))
+        {
+            return HeaderRule::Allow;
+        }
+
+        if self.default_deny.iter().any(|pattern| header_matches(pattern, name)) {
+            return HeaderRule::Deny;
+        }
+
+        match SAME_ORIGIN.iter().any(|same_origin| name.eq_ignore_ascii_case(same_origin)) {
+            true => HeaderRule::SameOrigin,
+            false => HeaderRule::Allow,
+        }
+    }
+}
+
+/// Header filter of the HTTP proxy.
+///
+/// Requests to eepsites and requests to outproxy have separate policies.
+#[derive(Debug, Clone)]
+pub struct HeaderFilter {
+    /// Policy for requests to eepsites.
+    eepsite: HeaderPolicy,
+
+    /// Policy for requests to outproxy.
+    outproxy: HeaderPolicy,
+}
+
+impl HeaderFilter {
+    /// Create new [`HeaderFilter`].
+    pub fn new(eepsite: Option<HeaderPolicyConfig>, outproxy: Option<HeaderPolicyConfig>) -> Self {
+        Self {
+            eepsite: HeaderPolicy::new(eepsite, &EEPSITE_DENY),
+            outproxy: HeaderPolicy::new(outproxy, &OUTPROXY_DENY),
+        }
+    }
+}
+
+impl Default for HeaderFilter {
+    fn default() -> Self {
+        Self::new(None, None)
+    }
+}
 
 /// Host kind.
 #[derive(Debug, PartialEq, Eq)]
@@ -64,20 +248,105 @@ pub enum HostKind {
     },
 }
 
+/// Address helper query parameters of the request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AddressHelper {
+    /// Address helper of a link, `?i2paddresshelper=<base64 destination>`.
+    Offer {
+        /// Base64-encoded destination.
+        destination: String,
+    },
+
+    /// Response to an address helper confirmation page.
+    Confirm {
+        /// Nonce of the confirmation page.
+        nonce: String,
+
+        /// Should the host be saved to address book.
+        save: bool,
+    },
+}
+
+/// Remove address helper query parameters from `path`.
+///
+/// Returns the path without the address helper parameters and the parsed address helper, if any.
+fn extract_address_helper(path: &str) -> (String, Option<AddressHelper>) {
+    let Some((path, query)) = path.split_once('?') else {
+        return (path.to_string(), None);
+    };
+
+    let mut destination = None;
+    let mut nonce = None;
+    let mut action = None;
+    let mut params = Vec::new();
+
+    for param in query.split('&') {
+        let (key, value) = param.split_once('=').unwrap_or((param, ""));
+        let value = || {
+            url::form_urlencoded::parse(format!("value={value}").as_bytes())
+                .next()
+                .map(|(_, value)| value.into_owned())
+                .unwrap_or_default()
+        };
+
+        match key.to_ascii_lowercase().as_str() {
+            ADDRESS_HELPER => destination = Some(value()),
+            ADDRESS_HELPER_NONCE => nonce = Some(value()),
+            ADDRESS_HELPER_ACTION => action = Some(value()),
+            _ => params.push(param),
+        }
+    }
+
+    let address_helper = match (destination, nonce) {
+        (_, Some(nonce)) => Some(AddressHelper::Confirm {
+            nonce,
+            save: action.as_deref() == Some(ADDRESS_HELPER_SAVE),
+        }),
+        (Some(destination), None) => Some(AddressHelper::Offer { destination }),
+        (None, None) => None,
+    };
+
+    match params.is_empty() {
+        true => (path.to_string(), address_helper),
+        false => (format!("{path}?{}", params.join("&")), address_helper),
+    }
+}
+
 /// Parsed request.
 #[derive(Debug)]
 pub struct Request {
+    /// Address helper, if any.
+    address_helper: Option<AddressHelper>,
+
+    /// Body of the request.
+    body: Body,
+
+    /// Does the client expect `100 Continue` before sending the body.
+    expect_continue: bool,
+
     /// Host kind.
     host: HostKind,
 
+    /// Should the client connection be kept open after the response.
+    keep_alive: bool,
+
     /// Method.
     method: String,
 
+    /// Is the request a top-level navigation, i.e., it has `Sec-Fetch-Dest: document`.
+    navigation: bool,
+
     /// Path.
     path: String,
 
+    /// Host of `Referer`, if any.
+    referer_host: Option<String>,
+
     /// Request.
     request: Vec<u8>,
+
+    /// Does the client request a protocol upgrade, e.g., to WebSocket.
+    upgrade: bool,
 }
 
 impl Request {
@@ -120,6 +389,12 @@ impl Request {
             Err(_) => req.path.ok_or(HttpError::InvalidPath)?.to_string(),
         };
 
+        // address helper parameters are handled by the proxy and not sent to remote host
+        let (path, address_helper) = match method.as_str() {
+            "CONNECT" => (path, None),
+            _ => extract_address_helper(&path),
+        };
+
         let host = match req.headers.iter().find(|header| header.name.to_lowercase() == "host") {
             None => {
                 tracing::warn!(
@@ -142,34 +417,139 @@ impl Request {
             }
         };
 
+        let body = message::request_body(req.headers)?;
+        let expect_continue = req.headers.iter().any(|header| {
+            header.name.eq_ignore_ascii_case("expect")
+                && header.value.eq_ignore_ascii_case(b"100-continue")
+        });
+        let upgrade = message::has_connection_token(req.headers, "upgrade");
+        let referer_host = req
+            .headers
+            .iter()
+            .find(|header| header.name.eq_ignore_ascii_case("referer"))
+            .and_then(|header| std::str::from_utf8(header.value).ok())
+            .and_then(|value| url::Url::parse(value).ok())
+            .and_then(|url| {
+                url.host_str().map(|host| host.strip_prefix("www.").unwrap_or(host).to_owned())
+            });
+        let navigation = req.headers.iter().any(|header| {
+            header.name.eq_ignore_ascii_case("sec-fetch-dest")
+                && header.value.eq_ignore_ascii_case(b"document")
+        });
+        let keep_alive = match req.version {
+            Some(1) => !message::has_connection_token(req.headers, "close"),
+            _ => message::has_connection_token(req.headers, "keep-alive"),
+        };
+
         Ok(Self {
+            address_helper,
+            body,
+            expect_continue,
             host,
+            keep_alive,
             method,
+            navigation,
             path,
+            referer_host,
             request,
+            upgrade,
         })
     }
 
+    /// Get address helper of the request, if any.
+    pub fn address_helper(&self) -> Option<&AddressHelper> {
+        self.address_helper.as_ref()
+    }
+
+    /// Get body of the request.
+    pub fn body(&self) -> Body {
+        self.body
+    }
+
+    /// Does the client expect `100 Continue` before sending the body.
+    pub fn expect_continue(&self) -> bool {
+        self.expect_continue
+    }
+
+    /// Should the client connection be kept open after the response.
+    pub fn keep_alive(&self) -> bool {
+        self.keep_alive
+    }
+
+    /// Is the request a tunnel, i.e., `CONNECT` or a protocol upgrade.
+    ///
+    /// Tunnels take over both the client connection and the stream to remote host.
+    pub fn is_tunnel(&self) -> bool {
+        self.method == "CONNECT" || self.upgrade
+    }
+
+    /// Get method of the request.
+    pub fn method(&self) -> &str {
+        &self.method
+    }
+
+    /// Get host of the request.
+    pub fn host(&self) -> &str {
+        match &self.host {
+            HostKind::I2p { host } | HostKind::B32 { host } | HostKind::Clearnet { host } => host,
+        }
+    }
+
+    /// Get the first-party host of the request.
+    ///
+    /// The first party of a subresource request is the host of `Referer`. Top-level navigations,
+    /// i.e., requests without `Referer` or with `Sec-Fetch-Dest: document`, are their own first
+    /// party so following a link doesn't load the linked site in the session of the linking site.
+    pub fn first_party(&self) -> &str {
+        match (&self.referer_host, self.navigation) {
+            (Some(referer_host), false) => referer_host,
+            _ => self.host(),
+        }
+    }
+
+    /// Get host of the request if it's a .i2p host.
+    pub fn i2p_host(&self) -> Option<&str> {
+        match &self.host {
+            HostKind::I2p { host } => Some(host),
+            _ => None,
+        }
+    }
+
+    /// Get path of the request.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Resolve the .i2p host of the request into .b32.i2p `host`.
+    ///
+    /// Used for hosts which were visited with an address helper but weren't saved to address
+    /// book.
+    pub fn resolve(&mut self, host: String) {
+        self.host = HostKind::B32 { host };
+    }
+
     /// Attempt to assemble [`Request`] into a serialized request that can be sent to remote host.
     ///
-    /// Takes two parameters: `address_book` and `outproxy`. `address_book` is used to resolve .i2p
-    /// host into a .b32.i2p host, if host is [`HostKind::I2p`]. If `address_book` doesn't exist or
-    /// the .i2p host was not found in the address book, an error is returned to indicate that the
-    /// request could not be assmebled. `outproxy` is the .b32.i2p host of the outproxy, if
-    /// configured, and it must exist if host is [`HostKind::Clearnet`].
+    /// Takes three parameters: `address_book`, `outproxy` and `filter`. `address_book` is used to
+    /// resolve .i2p host into a .b32.i2p host, if host is [`HostKind::I2p`]. If `address_book`
+    /// doesn't exist or the .i2p host was not found in the address book, an error is returned to
+    /// indicate that the request could not be assmebled. `outproxy` is the .b32.i2p host of the
+    /// outproxy, if configured, and it must exist if host is [`HostKind::Clearnet`].
     ///
-    /// The function constructs a new HTTP request, setting the correct user agent and stripping any
-    /// "illegal" headers before returning it to the caller, allowing them to send it to remote
-    /// host.
+    /// The function constructs a new HTTP request, setting the correct user agent and filtering the
+    /// headers with the policy of `filter` that matches the remote host before returning it to the
+    /// caller, allowing them to send it to remote host.
     ///
     /// Returns a `(host, request)` tuple where the `host` is the .b32.i2p address of the remote
     /// host SAM should connect to (either an eepsite or an outproxy) and where `request` is a
     /// serialized HTTP request.
     pub async fn assemble(
         self,
-        address_book: &Option<Arc<dyn AddressBook>>,
+        address_book: &Option<AddressBookHandle>,
         outproxy: &Option<String>,
+        filter: &HeaderFilter,
     ) -> Result<(String, Vec<u8>), HttpError> {
+        let original_host = self.host().to_owned();
         let user_agent = match &self.host {
             HostKind::Clearnet { .. } =>
                 "User-Agent: Mozilla/5.0 (Windows NT 10.0; rv:109.0) Gecko/20100101 Firefox/115.0\r\n",
@@ -232,73 +612,74 @@ impl Request {
         sanitized.extend_from_slice("HTTP/1.1\r\n".as_bytes());
         sanitized.extend_from_slice(user_agent.as_bytes());
 
-        for header in req.headers.iter_mut() {
-            if header.name.to_lowercase().starts_with("accept") {
-                if header.name.to_lowercase() == "accept-encoding" {
-                    sanitized.extend_from_slice("Accept-Encoding: ".as_bytes());
-                    sanitized.extend_from_slice(header.value);
-                    sanitized.extend_from_slice("\r\n".as_bytes());
-                }
-                continue;
-            }
+        let policy = match keep_original_host {
+            true => &filter.outproxy,
+            false => &filter.eepsite,
+        };
 
+        for header in req.headers.iter() {
             // modify host if not explicitly forbidden
             //
             // the host must be modified for .i2p requests as otherwise the request would leak
             // information about local addressbook
             //
             // the host must be kept unmodified for clearnet requests going through an outproxy
-            if header.name.to_lowercase() == "host" && !keep_original_host {
+            if header.name.eq_ignore_ascii_case("host") {
                 sanitized.extend_from_slice("Host: ".as_bytes());
-                sanitized.extend_from_slice(host.as_bytes());
-                sanitized.extend_from_slice("\r\n".as_bytes());
-                continue;
-            }
-
-            if header.name.to_lowercase() == "connection" {
-                match std::str::from_utf8(header.value) {
-                    Ok(value) if value.to_lowercase() == "upgrade" => {
-                        sanitized.extend_from_slice("Connection: upgrade\r\n".as_bytes());
-                    }
-                    _ => sanitized.extend_from_slice("Connection: close\r\n".as_bytes()),
+                match keep_original_host {
+                    true => sanitized.extend_from_slice(header.value),
+                    false => sanitized.extend_from_slice(host.as_bytes()),
                 }
-
+                sanitized.extend_from_slice("\r\n".as_bytes());
                 continue;
             }
 
-            if header.name.to_lowercase() == "referer" {
-                let Ok(value) = std::str::from_utf8(header.value) else {
-                    continue;
-                };
-
-                if value.contains(&host) {
-                    sanitized.extend_from_slice("Referer: ".as_bytes());
-                    sanitized.extend_from_slice(header.value);
-                    sanitized.extend_from_slice("\r\n".as_bytes());
-                } else {
-                    tracing::warn!(
-                        target: LOG_TARGET,
-                        ?value,
-                        "skipping invalid `Referer`",
-                    )
-                }
-
+            // `User-Agent` has already been added, connection management is hop-by-hop and
+            // `Connection` is added after the headers and `100 Continue` is sent to the client by
+            // the proxy so `Expect` is not forwarded
+            if MANAGED.iter().any(|managed| header_matches(managed, header.name)) {
                 continue;
             }
 
-            // ignore User-Agent as it has already been added
-            if header.name.to_lowercase() == "user-agent" {
+            // `Transfer-Encoding` overrides `Content-Length` which must not be forwarded
+            if header.name.eq_ignore_ascii_case("content-length") && self.body == Body::Chunked {
                 continue;
             }
 
-            if ILLEGAL.iter().any(|illegal| header.name.to_lowercase().starts_with(illegal)) {
-                tracing::warn!(
-                    target: LOG_TARGET,
-                    name = ?header.name,
-                    value = ?(std::str::from_utf8(header.value)),
-                    "skipping illegal header",
-                );
-                continue;
+            match policy.rule(header.name) {
+                HeaderRule::Allow => {}
+                HeaderRule::Deny => {
+                    tracing::debug!(
+                        target: LOG_TARGET,
+                        name = ?header.name,
+                        value = ?(std::str::from_utf8(header.value)),
+                        "skipping header",
+                    );
+                    continue;
+                }
+                HeaderRule::SameOrigin => {
+                    let origin = std::str::from_utf8(header.value)
+                        .ok()
+                        .and_then(|value| url::Url::parse(value).ok())
+                        .and_then(|url| url.host_str().map(ToOwned::to_owned));
+                    let same_origin = origin.is_some_and(|origin| {
+                        let origin = origin.strip_prefix("www.").unwrap_or(&origin);
+                        let original_host =
+                            original_host.split(':').next().unwrap_or(&original_host);
+
+                        origin == original_host || origin == host
+                    });
+
+                    if !same_origin {
+                        tracing::debug!(
+                            target: LOG_TARGET,
+                            name = ?header.name,
+                            value = ?(std::str::from_utf8(header.value)),
+                            "skipping cross-origin header",
+                        );
+                        continue;
+                    }
+                }
             }
 
             sanitized.extend_from_slice(format!("{}: ", header.name).as_bytes());
@@ -306,6 +687,12 @@ impl Request {
             sanitized.extend_from_slice("\r\n".as_bytes());
         }
 
+        match (self.method.as_str(), self.upgrade) {
+            ("CONNECT", _) => {}
+            (_, true) => sanitized.extend_from_slice("Connection: upgrade\r\n".as_bytes()),
+            (_, false) => sanitized.extend_from_slice("Connection: keep-alive\r\n".as_bytes()),
+        }
+
         sanitized.extend_from_slice("\r\n".as_bytes());
         sanitized.extend_from_slice(&self.request[body_start..]);
 
@@ -320,7 +707,7 @@ mod tests {
     use std::path::PathBuf;
     use tempfile::tempdir;
 
-    async fn make_address_book() -> (Arc<dyn AddressBook>, PathBuf) {
+    async fn make_address_book() -> (AddressBookHandle, PathBuf) {
         let hosts = "tracker2.postman.i2p=lnQ6yoBTxQuQU8EQ1FlF395ITIQF-HGJxUeFvzETLFnoczNjQvKDbtSB7aHhn853zjVXrJBgwlB9sO\
                 57KakBDaJ50lUZgVPhjlI19TgJ-CxyHhHSCeKx5JzURdEW-ucdONMynr-b2zwhsx8VQCJwCEkARvt21YkOyQDaB9IdV8aTAmP~PUJQxRwcea\
                 TMn96FcVenwdXqleE16fI8CVFOV18jbJKrhTOYpTtcZKV4l1wNYBDwKgwPx5c0kcrRzFyw5~bjuAKO~GJ5dR7BQsL7AwBoQUS4k1lwoYrG1k\
@@ -358,6 +745,7 @@ mod tests {
             AddressBookConfig {
                 default: Some(String::from("url")),
                 subscriptions: None,
+                refresh_interval: None,
             },
         );
 
@@ -376,6 +764,7 @@ mod tests {
             request,
             method,
             path,
+            ..
         } = Request::parse(request).unwrap();
 
         assert_eq!(
@@ -415,7 +804,8 @@ mod tests {
         assert_eq!(request.path, "/".to_string());
 
         // assemble request and verify `Host` is valid
-        let (host, request) = request.assemble(&None, &None).await.unwrap();
+        let (host, request) =
+            request.assemble(&None, &None, &HeaderFilter::default()).await.unwrap();
 
         let mut headers = [httparse::EMPTY_HEADER; 64];
         let mut req = httparse::Request::new(&mut headers);
@@ -448,7 +838,8 @@ mod tests {
         assert_eq!(request.method, "GET".to_string());
         assert_eq!(request.path, "/".to_string());
 
-        let (host, request) = request.assemble(&None, &None).await.unwrap();
+        let (host, request) =
+            request.assemble(&None, &None, &HeaderFilter::default()).await.unwrap();
         let mut headers = [httparse::EMPTY_HEADER; 64];
         let mut req = httparse::Request::new(&mut headers);
         let _body_start = req.parse(&request).unwrap().unwrap();
@@ -480,7 +871,8 @@ mod tests {
         assert_eq!(request.method, "GET".to_string());
         assert_eq!(request.path, "/topics/new-topic?query=1".to_string());
 
-        let (host, request) = request.assemble(&None, &None).await.unwrap();
+        let (host, request) =
+            request.assemble(&None, &None, &HeaderFilter::default()).await.unwrap();
         let mut headers = [httparse::EMPTY_HEADER; 64];
         let mut req = httparse::Request::new(&mut headers);
         let _body_start = req.parse(&request).unwrap().unwrap();
@@ -517,7 +909,8 @@ mod tests {
         assert_eq!(request.method, "POST".to_string());
         assert_eq!(request.path, "/upload".to_string());
 
-        let (host, request) = request.assemble(&None, &None).await.unwrap();
+        let (host, request) =
+            request.assemble(&None, &None, &HeaderFilter::default()).await.unwrap();
         let mut headers = [httparse::EMPTY_HEADER; 64];
         let mut req = httparse::Request::new(&mut headers);
         let _body_start = req.parse(&request).unwrap().unwrap();
@@ -563,7 +956,7 @@ mod tests {
         assert_eq!(request.method, "GET".to_string());
         assert_eq!(request.path, "/".to_string());
         assert_eq!(
-            request.assemble(&None, &None).await.unwrap_err(),
+            request.assemble(&None, &None, &HeaderFilter::default()).await.unwrap_err(),
             HttpError::AddressBookNotEnabled
         );
     }
@@ -583,7 +976,10 @@ mod tests {
         assert_eq!(request.method, "GET".to_string());
         assert_eq!(request.path, "/".to_string());
         assert_eq!(
-            request.assemble(&Some(address_book), &None).await.unwrap_err(),
+            request
+                .assemble(&Some(address_book), &None, &HeaderFilter::default())
+                .await
+                .unwrap_err(),
             HttpError::HostNotFound
         );
     }
@@ -603,7 +999,10 @@ mod tests {
         assert_eq!(request.method, "GET".to_string());
         assert_eq!(request.path, "/".to_string());
 
-        let (host, request) = request.assemble(&Some(address_book), &None).await.unwrap();
+        let (host, request) = request
+            .assemble(&Some(address_book), &None, &HeaderFilter::default())
+            .await
+            .unwrap();
         assert_eq!(
             host.as_str(),
             "lhbd7ojcaiofbfku7ixh47qj537g572zmhdc4oilvugzxdpdghua.b32.i2p"
@@ -638,7 +1037,10 @@ mod tests {
         assert_eq!(request.method, "GET".to_string());
         assert_eq!(request.path, "/topics/new-topic?query=1".to_string());
 
-        let (host, request) = request.assemble(&Some(address_book), &None).await.unwrap();
+        let (host, request) = request
+            .assemble(&Some(address_book), &None, &HeaderFilter::default())
+            .await
+            .unwrap();
         assert_eq!(
             host.as_str(),
             "lhbd7ojcaiofbfku7ixh47qj537g572zmhdc4oilvugzxdpdghua.b32.i2p"
@@ -674,8 +1076,10 @@ mod tests {
             assert_eq!(request.method, "GET".to_string());
             assert_eq!(request.path, "/".to_string());
 
-            let (host, request) =
-                request.assemble(&Some(address_book.clone()), &None).await.unwrap();
+            let (host, request) = request
+                .assemble(&Some(address_book.clone()), &None, &HeaderFilter::default())
+                .await
+                .unwrap();
             assert_eq!(
                 host.as_str(),
                 "lhbd7ojcaiofbfku7ixh47qj537g572zmhdc4oilvugzxdpdghua.b32.i2p"
@@ -710,7 +1114,10 @@ mod tests {
             assert_eq!(request.method, "GET".to_string());
             assert_eq!(request.path, "/".to_string());
 
-            let (host, request) = request.assemble(&Some(address_book), &None).await.unwrap();
+            let (host, request) = request
+                .assemble(&Some(address_book), &None, &HeaderFilter::default())
+                .await
+                .unwrap();
             assert_eq!(
                 host.as_str(),
                 "lhbd7ojcaiofbfku7ixh47qj537g572zmhdc4oilvugzxdpdghua.b32.i2p"
@@ -744,7 +1151,7 @@ mod tests {
         assert_eq!(request.path, "/".to_string());
 
         assert_eq!(
-            request.assemble(&None, &None).await.unwrap_err(),
+            request.assemble(&None, &None, &HeaderFilter::default()).await.unwrap_err(),
             HttpError::OutproxyNotEnabled
         );
     }
@@ -767,6 +1174,7 @@ mod tests {
             .assemble(
                 &None,
                 &Some("lhbd7ojcaiofbfku7ixh47qj537g572zmhdc4oilvugzxdpdghua.b32.i2p".to_string()),
+                &HeaderFilter::default(),
             )
             .await
             .unwrap();
@@ -812,6 +1220,7 @@ mod tests {
             .assemble(
                 &None,
                 &Some("lhbd7ojcaiofbfku7ixh47qj537g572zmhdc4oilvugzxdpdghua.b32.i2p".to_string()),
+                &HeaderFilter::default(),
             )
             .await
             .unwrap();
@@ -836,4 +1245,398 @@ mod tests {
             "Mozilla/5.0 (Windows NT 10.0; rv:109.0) Gecko/20100101 Firefox/115.0".as_bytes(),
         );
     }
+
+    #[test]
+    fn address_helper_extracted() {
+        assert_eq!(extract_address_helper("/"), ("/".to_string(), None));
+        assert_eq!(
+            extract_address_helper("/index.html?page=1"),
+            ("/index.html?page=1".to_string(), None)
+        );
+        assert_eq!(
+            extract_address_helper("/?i2paddresshelper=AAAA%3D%3D"),
+            (
+                "/".to_string(),
+                Some(AddressHelper::Offer {
+                    destination: "AAAA==".to_string()
+                })
+            )
+        );
+        assert_eq!(
+            extract_address_helper("/index.html?page=1&i2paddresshelper=AAAA&sort=asc"),
+            (
+                "/index.html?page=1&sort=asc".to_string(),
+                Some(AddressHelper::Offer {
+                    destination: "AAAA".to_string()
+                })
+            )
+        );
+        assert_eq!(
+            extract_address_helper(
+                "/?page=1&i2paddresshelpernonce=1337&i2paddresshelperaction=save"
+            ),
+            (
+                "/?page=1".to_string(),
+                Some(AddressHelper::Confirm {
+                    nonce: "1337".to_string(),
+                    save: true
+                })
+            )
+        );
+        assert_eq!(
+            extract_address_helper("/?i2paddresshelpernonce=1337&i2paddresshelperaction=continue"),
+            (
+                "/".to_string(),
+                Some(AddressHelper::Confirm {
+                    nonce: "1337".to_string(),
+                    save: false
+                })
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn address_helper_not_sent_to_remote_host() {
+        let request = "GET http://zzz.i2p/forums?i2paddresshelper=AAAA HTTP/1.1\r\n\
+                            Host: zzz.i2p\r\n\r\n"
+            .as_bytes()
+            .to_vec();
+        let mut request = Request::parse(request).unwrap();
+
+        assert_eq!(request.i2p_host(), Some("zzz.i2p"));
+        assert_eq!(request.path(), "/forums");
+        assert_eq!(
+            request.address_helper(),
+            Some(&AddressHelper::Offer {
+                destination: "AAAA".to_string()
+            })
+        );
+
+        // resolve host as if it was visited without saving it to address book
+        request.resolve("lhbd7ojcaiofbfku7ixh47qj537g572zmhdc4oilvugzxdpdghua.b32.i2p".to_string());
+
+        let (host, request) =
+            request.assemble(&None, &None, &HeaderFilter::default()).await.unwrap();
+        let mut headers = [httparse::EMPTY_HEADER; 64];
+        let mut req = httparse::Request::new(&mut headers);
+        let _body_start = req.parse(&request).unwrap().unwrap();
+
+        assert_eq!(req.path, Some("/forums"));
+        assert_eq!(
+            host.as_str(),
+            "lhbd7ojcaiofbfku7ixh47qj537g572zmhdc4oilvugzxdpdghua.b32.i2p",
+        );
+    }
+
+    #[test]
+    fn first_party_host() {
+        let request = Request::parse(
+            "GET /image.png HTTP/1.1\r\nHost: cdn.i2p\r\nReferer: http://www.zzz.i2p/forums\r\n\r\n"
+                .as_bytes()
+                .to_vec(),
+        )
+        .unwrap();
+        assert_eq!(request.first_party(), "zzz.i2p");
+
+        let request =
+            Request::parse("GET / HTTP/1.1\r\nHost: www.zzz.i2p\r\n\r\n".as_bytes().to_vec())
+                .unwrap();
+        assert_eq!(request.first_party(), "zzz.i2p");
+
+        // link followed from another site
+        let request = Request::parse(
+            "GET / HTTP/1.1\r\nHost: stats.i2p\r\nReferer: http://zzz.i2p/forums\r\n\
+            Sec-Fetch-Dest: document\r\n\r\n"
+                .as_bytes()
+                .to_vec(),
+        )
+        .unwrap();
+        assert_eq!(request.first_party(), "stats.i2p");
+
+        let request = Request::parse(
+            "GET /frame HTTP/1.1\r\nHost: stats.i2p\r\nReferer: http://zzz.i2p/forums\r\n\
+            Sec-Fetch-Dest: iframe\r\n\r\n"
+                .as_bytes()
+                .to_vec(),
+        )
+        .unwrap();
+        assert_eq!(request.first_party(), "zzz.i2p");
+    }
+
+    #[test]
+    fn keep_alive_parsed() {
+        let parse = |request: &str| Request::parse(request.as_bytes().to_vec()).unwrap();
+
+        let request = parse("GET / HTTP/1.1\r\nHost: host.b32.i2p\r\n\r\n");
+        assert!(request.keep_alive());
+        assert!(!request.is_tunnel());
+        assert_eq!(request.body(), Body::None);
+
+        let request = parse("GET / HTTP/1.1\r\nHost: host.b32.i2p\r\nConnection: close\r\n\r\n");
+        assert!(!request.keep_alive());
+
+        let request = parse("GET / HTTP/1.0\r\nHost: host.b32.i2p\r\n\r\n");
+        assert!(!request.keep_alive());
+
+        let request =
+            parse("GET / HTTP/1.0\r\nHost: host.b32.i2p\r\nConnection: keep-alive\r\n\r\n");
+        assert!(request.keep_alive());
+
+        let request = parse(
+            "GET /ws HTTP/1.1\r\nHost: host.b32.i2p\r\nConnection: keep-alive, Upgrade\r\n\
+            Upgrade: websocket\r\n\r\n",
+        );
+        assert!(request.is_tunnel());
+    }
+
+    #[tokio::test]
+    async fn hop_by_hop_headers_replaced() {
+        let request = "POST /upload HTTP/1.1\r\n\
+                        Host: lhbd7ojcaiofbfku7ixh47qj537g572zmhdc4oilvugzxdpdghua.b32.i2p\r\n\
+                        Connection: close\r\n\
+                        Keep-Alive: timeout=5\r\n\
+                        Expect: 100-continue\r\n\
+                        Transfer-Encoding: chunked\r\n\r\n"
+            .as_bytes()
+            .to_vec();
+        let request = Request::parse(request).unwrap();
+
+        assert_eq!(request.body(), Body::Chunked);
+        assert!(request.expect_continue());
+        assert!(!request.keep_alive());
+
+        let (_, request) = request.assemble(&None, &None, &HeaderFilter::default()).await.unwrap();
+        let mut headers = [httparse::EMPTY_HEADER; 64];
+        let mut req = httparse::Request::new(&mut headers);
+        let _body_start = req.parse(&request).unwrap().unwrap();
+
+        let names = req.headers.iter().map(|header| header.name).collect::<Vec<_>>();
+        assert!(!names.contains(&"Keep-Alive"));
+        assert!(!names.contains(&"Expect"));
+        assert_eq!(
+            req.headers.iter().find(|header| header.name == "Connection").unwrap().value,
+            "keep-alive".as_bytes(),
+        );
+        assert_eq!(
+            req.headers
+                .iter()
+                .find(|header| header.name == "Transfer-Encoding")
+                .unwrap()
+                .value,
+            "chunked".as_bytes(),
+        );
+    }
+
+    #[test]
+    fn conflicting_body_headers_rejected() {
+        let request = "POST /upload HTTP/1.1\r\n\
+                        Host: lhbd7ojcaiofbfku7ixh47qj537g572zmhdc4oilvugzxdpdghua.b32.i2p\r\n\
+                        Content-Length: 1337\r\n\
+                        Transfer-Encoding: chunked\r\n\r\n";
+        assert!(Request::parse(request.as_bytes().to_vec()).is_err());
+
+        let request = "POST /upload HTTP/1.1\r\n\
+                        Host: lhbd7ojcaiofbfku7ixh47qj537g572zmhdc4oilvugzxdpdghua.b32.i2p\r\n\
+                        Transfer-Encoding: gzip\r\n\
+                        Transfer-Encoding: chunked\r\n\r\n";
+        assert!(Request::parse(request.as_bytes().to_vec()).is_err());
+    }
+
+    /// Assemble `request` and return the names and values of the forwarded headers.
+    async fn assembled_headers(
+        request: &str,
+        outproxy: Option<&str>,
+        filter: &HeaderFilter,
+    ) -> Vec<(String, String)> {
+        let request = Request::parse(request.as_bytes().to_vec()).unwrap();
+        let (_, request) =
+            request.assemble(&None, &outproxy.map(ToOwned::to_owned), filter).await.unwrap();
+
+        let mut headers = [httparse::EMPTY_HEADER; 64];
+        let mut req = httparse::Request::new(&mut headers);
+        req.parse(&request).unwrap().unwrap();
+
+        req.headers
+            .iter()
+            .map(|header| {
+                (
+                    header.name.to_ascii_lowercase(),
+                    std::str::from_utf8(header.value).unwrap().to_owned(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn header_policy_rules() {
+        let filter = HeaderFilter::new(
+            Some(HeaderPolicyConfig {
+                allow: Some(vec![
+                    "Accept-Language".to_string(),
+                    "x-forwarded-for".to_string(),
+                ]),
+                deny: Some(vec![
+                    "x-tracking-*".to_string(),
+                    "x-forwarded-for".to_string(),
+                    "invalid header".to_string(),
+                ]),
+            }),
+            Some(HeaderPolicyConfig {
+                allow: Some(vec!["cookie".to_string()]),
+                deny: Some(vec!["*".to_string()]),
+            }),
+        );
+
+        // explicitly allowed
+        assert_eq!(filter.eepsite.rule("accept-language"), HeaderRule::Allow);
+
+        // deny takes precedence over allow
+        assert_eq!(filter.eepsite.rule("X-Forwarded-For"), HeaderRule::Deny);
+
+        // wildcard
+        assert_eq!(filter.eepsite.rule("X-Tracking-Id"), HeaderRule::Deny);
+
+        // default policy
+        assert_eq!(filter.eepsite.rule("Accept"), HeaderRule::Allow);
+        assert_eq!(filter.eepsite.rule("Accept-Charset"), HeaderRule::Deny);
+        assert_eq!(filter.eepsite.rule("Accept-Encoding"), HeaderRule::Allow);
+        assert_eq!(filter.eepsite.rule("Via"), HeaderRule::Deny);
+        assert_eq!(filter.eepsite.rule("Cookie"), HeaderRule::Allow);
+        assert_eq!(filter.eepsite.rule("Referer"), HeaderRule::SameOrigin);
+        assert_eq!(filter.eepsite.rule("Origin"), HeaderRule::SameOrigin);
+        assert_eq!(filter.eepsite.rule("Content-Type"), HeaderRule::Allow);
+
+        // invalid pattern is ignored
+        assert_eq!(filter.eepsite.deny.len(), 2);
+
+        // everything is denied except headers required for framing
+        assert_eq!(filter.outproxy.rule("Cookie"), HeaderRule::Deny);
+        assert_eq!(filter.outproxy.rule("Content-Type"), HeaderRule::Deny);
+        assert_eq!(filter.outproxy.rule("Content-Length"), HeaderRule::Allow);
+        assert_eq!(filter.outproxy.rule("Transfer-Encoding"), HeaderRule::Allow);
+
+        // cookies are not sent to outproxy by default
+        assert_eq!(
+            HeaderFilter::default().outproxy.rule("Cookie"),
+            HeaderRule::Deny
+        );
+        assert_eq!(
+            HeaderFilter::default().outproxy.rule("Cookie2"),
+            HeaderRule::Deny
+        );
+    }
+
+    #[tokio::test]
+    async fn eepsite_headers_filtered() {
+        let request = "GET /forums HTTP/1.1\r\n\
+            Host: zzz.b32.i2p\r\n\
+            Accept: text/html\r\n\
+            Accept-Language: en-US\r\n\
+            Accept-Encoding: gzip\r\n\
+            X-Forwarded-For: 10.0.0.1\r\n\
+            Via: 1.1 proxy\r\n\
+            Forwarded: for=10.0.0.1\r\n\
+            DNT: 1\r\n\
+            Cookie: session=1337\r\n\
+            Proxy-Authorization: Basic dXNlcjpwYXNz\r\n\
+            Referer: http://other.i2p/\r\n\
+            Origin: http://other.i2p\r\n\
+            X-Custom: value\r\n\r\n";
+
+        let headers = assembled_headers(request, None, &HeaderFilter::default()).await;
+        let names = headers.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>();
+
+        assert_eq!(
+            names,
+            vec![
+                "user-agent",
+                "host",
+                "accept",
+                "accept-encoding",
+                "cookie",
+                "x-custom",
+                "connection"
+            ]
+        );
+
+        // same-origin referer is forwarded, both with .b32.i2p and with the original host
+        for referer in ["http://zzz.b32.i2p/forums", "http://www.zzz.b32.i2p/"] {
+            let request =
+                format!("GET / HTTP/1.1\r\nHost: zzz.b32.i2p\r\nReferer: {referer}\r\n\r\n");
+            let headers = assembled_headers(&request, None, &HeaderFilter::default()).await;
+
+            assert!(headers.contains(&("referer".to_string(), referer.to_string())));
+        }
+
+        // same-origin `Origin` is forwarded
+        let origin = "POST /login HTTP/1.1\r\nHost: zzz.b32.i2p\r\nOrigin: http://zzz.b32.i2p\r\n\
+            Content-Length: 0\r\n\r\n";
+        let headers = assembled_headers(origin, None, &HeaderFilter::default()).await;
+        assert!(headers.contains(&("origin".to_string(), "http://zzz.b32.i2p".to_string())));
+
+        // user policy
+        let filter = HeaderFilter::new(
+            Some(HeaderPolicyConfig {
+                allow: Some(vec!["accept-language".to_string(), "referer".to_string()]),
+                deny: Some(vec!["cookie".to_string(), "x-*".to_string()]),
+            }),
+            None,
+        );
+        let headers = assembled_headers(request, None, &filter).await;
+        let names = headers.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>();
+
+        assert_eq!(
+            names,
+            vec![
+                "user-agent",
+                "host",
+                "accept",
+                "accept-language",
+                "accept-encoding",
+                "referer",
+                "connection"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn outproxy_headers_filtered() {
+        let request = "GET http://example.com/index.html HTTP/1.1\r\n\
+            Host: example.com\r\n\
+            Accept-Language: en-US\r\n\
+            Cookie: session=1337\r\n\
+            X-Forwarded-For: 10.0.0.1\r\n\
+            Referer: http://example.com/\r\n\
+            X-Custom: value\r\n\r\n";
+        let outproxy = Some("lhbd7ojcaiofbfku7ixh47qj537g572zmhdc4oilvugzxdpdghua.b32.i2p");
+
+        let headers = assembled_headers(request, outproxy, &HeaderFilter::default()).await;
+        let names = headers.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>();
+
+        // cookies are not sent to outproxy but same-origin referer is
+        assert_eq!(
+            names,
+            vec!["user-agent", "host", "referer", "x-custom", "connection"]
+        );
+        assert!(headers.contains(&("host".to_string(), "example.com".to_string())));
+
+        // eepsite policy doesn't apply to outproxy requests
+        let filter = HeaderFilter::new(
+            Some(HeaderPolicyConfig {
+                allow: None,
+                deny: Some(vec!["x-custom".to_string()]),
+            }),
+            Some(HeaderPolicyConfig {
+                allow: Some(vec!["cookie".to_string()]),
+                deny: Some(vec!["referer".to_string()]),
+            }),
+        );
+        let headers = assembled_headers(request, outproxy, &filter).await;
+        let names = headers.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>();
+
+        assert_eq!(
+            names,
+            vec!["user-agent", "host", "cookie", "x-custom", "connection"]
+        );
+    }
 }
@@ -16,47 +16,212 @@
 // FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
 // DEALINGS IN THE SOFTWARE.
 
-use crate::proxy::http::HttpError;
+use crate::proxy::http::{
+    request::{ADDRESS_HELPER_ACTION, ADDRESS_HELPER_NONCE, ADDRESS_HELPER_SAVE},
+    HttpError,
+};
 
 use tokio::{io::AsyncWriteExt, net::TcpStream};
 
-/// Error status.
+/// Response status.
 pub enum Status {
+    /// Address helper confirmation page.
+    AddressHelper {
+        /// .i2p host.
+        host: String,
+
+        /// .b32.i2p address of the address helper.
+        b32: String,
+
+        /// .b32.i2p address of the host in address book, if it exists.
+        existing: Option<String>,
+
+        /// Nonce of the confirmation.
+        nonce: String,
+
+        /// Can the host be saved to address book.
+        can_save: bool,
+
+        /// URL of the request without the address helper.
+        url: String,
+    },
+
     /// HTTP 400 Bad Request.
     BadRequest(HttpError),
 
+    /// HTTP 502 Bad Gateway.
+    BadGateway(String),
+
     /// HTTP 500 Gateway Timeout.
     GatewayTimeout(String),
+
+    /// Host was not found in address book.
+    HostNotFound {
+        /// .i2p host.
+        host: String,
+
+        /// Jump services which can be used to look up the host.
+        jump_services: Vec<String>,
+    },
+
+    /// Redirect to URL after a confirmation was submitted.
+    Redirect(String),
+
+    /// HTTP 503 Service Unavailable.
+    ServiceUnavailable,
 }
 
 impl std::fmt::Display for Status {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
+            Self::AddressHelper { .. } => write!(f, "200 OK"),
             Status::BadRequest(_) => write!(f, "400 Bad Request"),
+            Self::BadGateway(_) => write!(f, "502 Bad Gateway"),
             Self::GatewayTimeout(_) => write!(f, "504 Gateway Timeout"),
+            Self::HostNotFound { .. } => write!(f, "400 Bad Request"),
+            Self::Redirect(_) => write!(f, "303 See Other"),
+            Self::ServiceUnavailable => write!(f, "503 Service Unavailable"),
         }
     }
 }
 
-// Send HTTP error response to client.
+/// Escape `value` so it can be embedded into HTML.
+fn escape(value: &str) -> String {
+    value.chars().fold(String::with_capacity(value.len()), |mut out, c| {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+        out
+    })
+}
+
+/// Create form which confirms the address helper identified by `nonce` with `action`.
+///
+/// The confirmation is submitted as a `POST` request to `url`, with `nonce` and `action` in the
+/// query, so it can't be triggered by merely loading the URL.
+fn confirmation_form(url: &str, nonce: &str, action: &str, label: &str) -> String {
+    let separator = if url.contains('?') { '&' } else { '?' };
+
+    format!(
+        "<form method=\"post\" action=\"{}\"><button type=\"submit\">{}</button></form>",
+        escape(&format!(
+            "{url}{separator}{ADDRESS_HELPER_NONCE}={nonce}&{ADDRESS_HELPER_ACTION}={action}"
+        )),
+        escape(label),
+    )
+}
+
+// Send HTTP response to client.
 pub async fn send_response(mut stream: TcpStream, status: Status) {
-    let http_error = status.to_string();
-    let status_line = format!("HTTP/1.1 {http_error}");
-    let headers = "Connection: close\r\nContent-Type: text/html; charset=UTF-8";
-    let error = match status {
-        Status::BadRequest(error) => error.to_string(),
-        Status::GatewayTimeout(host) => format!("Failed to establish connection to {host}"),
+    let http_status = status.to_string();
+    let status_line = format!("HTTP/1.1 {http_status}");
+    // pages generated by the proxy must not be framed by eepsites, as that would allow tricking
+    // the user into confirming an address helper
+    let mut headers = String::from(
+        "Connection: close\r\n\
+        Content-Type: text/html; charset=UTF-8\r\n\
+        X-Frame-Options: DENY\r\n\
+        Content-Security-Policy: frame-ancestors 'none'",
+    );
+    let (title, content) = match status {
+        Status::AddressHelper {
+            host,
+            b32,
+            existing,
+            nonce,
+            can_save,
+            url,
+        } => {
+            let mut content = format!(
+                "<p>The link you followed provided an address helper for <b>{}</b>: \
+                 <code>{b32}.b32.i2p</code>.</p>",
+                escape(&host),
+            );
+
+            if let Some(existing) = existing {
+                content.push_str(&format!(
+                    "<p><b>Warning:</b> the address helper conflicts with the destination of \
+                     {} in your address book, <code>{existing}.b32.i2p</code>. The link may be \
+                     an attempt to impersonate the site.</p>",
+                    escape(&host),
+                ));
+            }
+
+            if can_save {
+                content.push_str(&confirmation_form(
+                    &url,
+                    &nonce,
+                    ADDRESS_HELPER_SAVE,
+                    &format!("Save {host} to address book and continue"),
+                ));
+            }
+
+            content.push_str(&confirmation_form(
+                &url,
+                &nonce,
+                "continue",
+                "Continue without saving",
+            ));
+
+            (String::from("Address helper"), content)
+        }
+        Status::BadRequest(error) => (http_status.clone(), format!("<p>{error}</p>")),
+        Status::BadGateway(host) => (
+            http_status.clone(),
+            format!("<p>Received an invalid response from {}</p>", escape(&host)),
+        ),
+        Status::GatewayTimeout(host) => (
+            http_status.clone(),
+            format!("<p>Failed to establish connection to {}</p>", escape(&host)),
+        ),
+        Status::HostNotFound {
+            host,
+            jump_services,
+        } => {
+            let mut content = format!("<p>{}</p>", HttpError::HostNotFound);
+
+            if !jump_services.is_empty() {
+                content.push_str(&format!(
+                    "<p>You can try to find {} using one of the following jump services:</p><ul>",
+                    escape(&host),
+                ));
+
+                for service in jump_services {
+                    content.push_str(&format!(
+                        "<li><a href=\"{0}\">{0}</a></li>",
+                        escape(&format!("{service}{host}")),
+                    ));
+                }
+
+                content.push_str("</ul>");
+            }
+
+            (http_status.clone(), content)
+        }
+        Status::Redirect(url) => {
+            headers.push_str(&format!("\r\nLocation: {url}"));
+            (http_status.clone(), String::new())
+        }
+        Status::ServiceUnavailable => (
+            http_status.clone(),
+            String::from("<p>Too many isolated destinations in use, try again later</p>"),
+        ),
     };
     let body = format!(
         r#"
             <!DOCTYPE html>
             <html>
             <head>
-                <title>{http_error}</title>
+                <title>{title}</title>
             </head>
             <body>
-                <h1>{http_error}</h1>
-                <p>{error}</p>
+                <h1>{title}</h1>
+                {content}
             </body>
             </html>
         "#
@@ -16,39 +16,226 @@
 // FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
 // DEALINGS IN THE SOFTWARE.
 
+//! SOCKS proxy.
+//!
+//! Supports SOCKSv4a `CONNECT` and SOCKSv5 `TCP CONNECT` and `UDP ASSOCIATE`. Streams are opened
+//! over a SAMv3 streaming session and UDP datagrams are sent and received over a SAMv3 repliable
+//! datagram session.
+//!
+//! If the client authenticates with a username and password, each username is given its own
+//! isolated SAMv3 session and destination. The password is not verified. SOCKSv4a user ID is
+//! treated as a username. Isolated sessions are destroyed after they've been unused for
+//! [`SESSION_IDLE_TIMEOUT`].
+//!
+//! Streams to clearnet hosts and IP addresses are tunneled through the SOCKS outproxy, if one
+//! has been configured.
+
 use crate::config::SocksProxyConfig;
 
+use emissary_core::{
+    crypto::{base32_encode, base64_decode},
+    primitives::Destination,
+};
 use tokio::{
-    io::{AsyncReadExt, AsyncWriteExt},
-    net::{TcpListener, TcpStream},
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::{TcpListener, TcpStream, UdpSocket},
     task::JoinSet,
 };
-use yosemite::{style, Session, SessionOptions, StreamOptions};
+use yosemite::{style, RouterApi, Session, SessionOptions, StreamOptions};
+
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 /// Logging target for the file.
 const LOG_TARGET: &str = "emissary::proxy::socks";
 
+/// SOCKSv4 version.
+const SOCKSV4_VERSION: u8 = 0x04;
+
+/// SOCKSv4 CONNECT.
+const SOCKSV4_CONNECT: u8 = 0x01;
+
+/// SOCKSv4 reply: request granted.
+const SOCKSV4_GRANTED: u8 = 0x5a;
+
+/// SOCKSv4 reply: request rejected or failed.
+const SOCKSV4_REJECTED: u8 = 0x5b;
+
+/// SOCKSv5 version.
+const SOCKSV5_VERSION: u8 = 0x05;
+
 /// SOCKSv5 TCP CONNECT
 const SOCKSV5_TCP: u8 = 0x01;
 
+/// SOCKSv5 UDP ASSOCIATE.
+const SOCKSV5_UDP: u8 = 0x03;
+
+/// SOCKSv5 IPv4 address.
+const SOCKSV5_IPV4: u8 = 0x01;
+
 /// SOCKSv5 Domain for TCP CONNECT.
 const SOCKSV5_DOMAIN: u8 = 0x03;
 
+/// SOCKSv5 IPv6 address.
+const SOCKSV5_IPV6: u8 = 0x04;
+
+/// SOCKSv5 "no authentication required" method.
+const SOCKSV5_NO_AUTH: u8 = 0x00;
+
+/// SOCKSv5 username/password authentication method.
+const SOCKSV5_USERNAME_PASSWORD: u8 = 0x02;
+
+/// SOCKSv5 "no acceptable methods" response.
+const SOCKSV5_NO_ACCEPTABLE_METHODS: u8 = 0xff;
+
+/// Version of the username/password subnegotiation (RFC 1929).
+const USERNAME_PASSWORD_VERSION: u8 = 0x01;
+
+/// SOCKSv5 reply: succeeded.
+const SOCKSV5_SUCCEEDED: u8 = 0x00;
+
+/// SOCKSv5 reply: general failure.
+const SOCKSV5_GENERAL_FAILURE: u8 = 0x01;
+
+/// SOCKSv5 reply: connection not allowed by ruleset.
+const SOCKSV5_NOT_ALLOWED: u8 = 0x02;
+
+/// Maximum number of isolated sessions, one for each username.
+const MAX_ISOLATED_SESSIONS: usize = 32usize;
+
+/// How long an idle isolated session is kept before it's destroyed.
+const SESSION_IDLE_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// How often idle isolated sessions are pruned.
+const SESSION_PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How long a name lookup of a UDP association may take.
+const NAME_LOOKUP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Maximum size of a UDP datagram.
+const MAX_DATAGRAM_SIZE: usize = 0xffff;
+
+/// SOCKS protocol version of a client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SocksVersion {
+    /// SOCKSv4(a).
+    V4,
+
+    /// SOCKSv5.
+    V5,
+}
+
+/// SOCKSv5 command.
+enum SocksCommand {
+    /// `TCP CONNECT` to `host:port`.
+    Connect {
+        /// Host.
+        host: String,
+
+        /// Port.
+        port: u16,
+    },
+
+    /// `UDP ASSOCIATE`.
+    UdpAssociate,
+}
+
+/// Parsed SOCKSv5 request.
+struct SocksRequest {
+    /// Command.
+    command: SocksCommand,
+
+    /// TCP stream of the client.
+    stream: TcpStream,
+
+    /// Username, if the client authenticated with username/password.
+    username: Option<String>,
+
+    /// SOCKS version of the client.
+    version: SocksVersion,
+}
+
+/// `TCP CONNECT` which hasn't been replied to yet.
+///
+/// The client is sent a reply once the stream to the remote destination has been opened.
+struct ConnectRequest {
+    /// Host.
+    host: String,
+
+    /// Port.
+    port: u16,
+
+    /// TCP stream of the client.
+    stream: TcpStream,
+
+    /// SOCKS version of the client.
+    version: SocksVersion,
+}
+
+/// Isolated SAMv3 streaming session of a username.
+struct IsolatedSession {
+    /// Reference held by each stream opened over the session, used to tell if the session is idle.
+    active: Arc<()>,
+
+    /// When was the session last used to open a stream.
+    last_used: Instant,
+
+    /// SAMv3 streaming session.
+    session: Session<style::Stream>,
+}
+
 /// SOCKSv5 proxy.
 pub struct SocksProxy {
-    /// Pending `TCP CONNECT` futures.
-    futures: JoinSet<anyhow::Result<(TcpStream, String, u16)>>,
+    /// Pending SOCKSv5 requests.
+    futures: JoinSet<anyhow::Result<SocksRequest>>,
+
+    /// Host the TCP listener was bound to, used for the UDP relays.
+    host: String,
 
     /// TCP listener for the server.
     listener: TcpListener,
 
+    /// SOCKS outproxy, if enabled.
+    outproxy: Option<String>,
+
+    /// Pending `TCP CONNECT`s for isolated sessions that are still being created.
+    pending: HashMap<String, Vec<ConnectRequest>>,
+
+    /// SAMv3 TCP port of the router.
+    samv3_tcp_port: u16,
+
+    /// SAMv3 UDP port of the router, if datagrams are enabled.
+    samv3_udp_port: Option<u16>,
+
     /// SAMv3 streaming session for the SOCKS proxy.
     session: Session<style::Stream>,
+
+    /// Pending isolated sessions.
+    session_futures: JoinSet<(String, yosemite::Result<Session<style::Stream>>)>,
+
+    /// Isolated SAMv3 streaming sessions, indexed by username.
+    sessions: HashMap<String, IsolatedSession>,
 }
 
 impl SocksProxy {
     /// Create new [`SocksProxy`].
-    pub async fn new(config: SocksProxyConfig, samv3_tcp_port: u16) -> crate::Result<Self> {
+    pub async fn new(
+        config: SocksProxyConfig,
+        samv3_tcp_port: u16,
+        samv3_udp_port: Option<u16>,
+    ) -> crate::Result<Self> {
+        tracing::info!(
+            target: LOG_TARGET,
+            host = %config.host,
+            port = %config.port,
+            outproxy = ?config.outproxy,
+            "starting socks proxy",
+        );
+
         let session = Session::<style::Stream>::new(SessionOptions {
             publish: false,
             samv3_tcp_port,
@@ -58,189 +245,1195 @@ impl SocksProxy {
         .await?;
         let listener = TcpListener::bind(format!("{}:{}", config.host, config.port)).await?;
 
+        // outproxy must be given as a .i2p or .b32.i2p host which is resolved by the router
+        let outproxy = match config.outproxy {
+            None => None,
+            Some(outproxy) => {
+                let outproxy = outproxy.to_ascii_lowercase();
+                let outproxy = outproxy.strip_prefix("socks://").unwrap_or(&outproxy);
+
+                match outproxy.ends_with(".i2p") {
+                    true => Some(outproxy.to_owned()),
+                    false => {
+                        tracing::warn!(
+                            target: LOG_TARGET,
+                            %outproxy,
+                            "outproxy must be .b32.i2p or .i2p hostname",
+                        );
+                        None
+                    }
+                }
+            }
+        };
+
         Ok(Self {
             futures: JoinSet::new(),
+            host: config.host,
             listener,
+            outproxy,
+            pending: HashMap::new(),
+            samv3_tcp_port,
+            samv3_udp_port,
             session,
+            session_futures: JoinSet::new(),
+            sessions: HashMap::new(),
         })
     }
 
-    /// Attempt to parse `TCP CONNECT` SOCKSv5 command from `stream` and return the parsed host.
-    async fn parse_tcp_connect(mut stream: TcpStream) -> anyhow::Result<(TcpStream, String, u16)> {
-        let mut buf = [0u8; 262];
+    /// Read host and port from `stream`.
+    ///
+    /// IPv4 and IPv6 addresses are read but only domains can be used as I2P destinations. Domains
+    /// are lowercased.
+    async fn read_address(stream: &mut TcpStream, atyp: u8) -> anyhow::Result<(String, u16)> {
+        let mut buf = [0u8; 256];
+
+        let host = match atyp {
+            SOCKSV5_IPV4 => {
+                stream.read_exact(&mut buf[..4]).await?;
+                IpAddr::from(<[u8; 4]>::try_from(&buf[..4])?).to_string()
+            }
+            SOCKSV5_IPV6 => {
+                stream.read_exact(&mut buf[..16]).await?;
+                IpAddr::from(<[u8; 16]>::try_from(&buf[..16])?).to_string()
+            }
+            SOCKSV5_DOMAIN => {
+                stream.read_exact(&mut buf[..1]).await?;
+                let len = buf[0] as usize;
+                stream.read_exact(&mut buf[..len]).await?;
+
+                String::from_utf8_lossy(&buf[..len]).to_ascii_lowercase()
+            }
+            _ => anyhow::bail!("Unknown address type"),
+        };
+
+        // Read port
         stream.read_exact(&mut buf[..2]).await?;
+        let port = u16::from_be_bytes([buf[0], buf[1]]);
+
+        Ok((host, port))
+    }
+
+    /// Read username/password subnegotiation from `stream` and return the username.
+    async fn read_username_password(stream: &mut TcpStream) -> anyhow::Result<String> {
+        let mut buf = [0u8; 256];
+        stream.read_exact(&mut buf[..2]).await?;
+
+        if buf[0] != USERNAME_PASSWORD_VERSION {
+            anyhow::bail!("Invalid username/password version");
+        }
+
+        let ulen = buf[1] as usize;
+        stream.read_exact(&mut buf[..ulen]).await?;
+        let username = String::from_utf8_lossy(&buf[..ulen]).to_string();
+
+        // password is ignored as it's only used for stream isolation
+        stream.read_exact(&mut buf[..1]).await?;
+        let plen = buf[0] as usize;
+        stream.read_exact(&mut buf[..plen]).await?;
+
+        // version 1, success
+        stream.write_all(&[USERNAME_PASSWORD_VERSION, 0x00]).await?;
+
+        Ok(username)
+    }
+
+    /// Read null-terminated string from `stream`.
+    async fn read_null_terminated(stream: &mut TcpStream) -> anyhow::Result<String> {
+        let mut buf = Vec::new();
+
+        loop {
+            match stream.read_u8().await? {
+                0x00 => return Ok(String::from_utf8_lossy(&buf).to_string()),
+                byte => buf.push(byte),
+            }
+
+            if buf.len() > 255 {
+                anyhow::bail!("String too long");
+            }
+        }
+    }
+
+    /// Attempt to parse SOCKS request from `stream`.
+    ///
+    /// If `outproxy_enabled` is false, requests to clearnet hosts and IP addresses are rejected.
+    async fn parse_request(
+        mut stream: TcpStream,
+        outproxy_enabled: bool,
+    ) -> anyhow::Result<SocksRequest> {
+        match stream.read_u8().await? {
+            SOCKSV4_VERSION => Self::parse_socksv4_request(stream, outproxy_enabled).await,
+            SOCKSV5_VERSION => Self::parse_socksv5_request(stream, outproxy_enabled).await,
+            _ => anyhow::bail!("Unsupported SOCKS version"),
+        }
+    }
 
-        if buf[0] != 0x05 {
-            anyhow::bail!("Not SOCKSv5");
+    /// Attempt to parse SOCKSv4(a) request from `stream`.
+    ///
+    /// Only `CONNECT` is supported and successful request is acknowledged once the stream to the
+    /// remote destination has been opened.
+    async fn parse_socksv4_request(
+        mut stream: TcpStream,
+        outproxy_enabled: bool,
+    ) -> anyhow::Result<SocksRequest> {
+        // cmd, port, ip
+        let mut buf = [0u8; 7];
+        stream.read_exact(&mut buf).await?;
+
+        let port = u16::from_be_bytes([buf[1], buf[2]]);
+        let username = Self::read_null_terminated(&mut stream).await?;
+
+        // socksv4a: ip of 0.0.0.x, where x is non-zero, is followed by a hostname
+        let host = match buf[3..7] {
+            [0, 0, 0, x] if x != 0 =>
+                Self::read_null_terminated(&mut stream).await?.to_ascii_lowercase(),
+            _ => IpAddr::from([buf[3], buf[4], buf[5], buf[6]]).to_string(),
+        };
+
+        let reject = if buf[0] != SOCKSV4_CONNECT {
+            Some("Only CONNECT supported")
+        } else if host.is_empty() {
+            Some("Empty host")
+        } else if !host.ends_with(".i2p") && !outproxy_enabled {
+            Some("Cannot connect to clearnet address, outproxy not enabled")
+        } else {
+            None
+        };
+
+        if let Some(reason) = reject {
+            Self::connect_reply(&mut stream, SocksVersion::V4, false).await?;
+            anyhow::bail!(reason);
         }
 
-        let nmethods = buf[1] as usize;
+        Ok(SocksRequest {
+            command: SocksCommand::Connect { host, port },
+            stream,
+            username: (!username.is_empty()).then_some(username),
+            version: SocksVersion::V4,
+        })
+    }
+
+    /// Attempt to parse SOCKSv5 request from `stream`.
+    ///
+    /// Successful `TCP CONNECT` is acknowledged once the stream to the remote destination has been
+    /// opened and `UDP ASSOCIATE` is acknowledged once the UDP relay has been started.
+    async fn parse_socksv5_request(
+        mut stream: TcpStream,
+        outproxy_enabled: bool,
+    ) -> anyhow::Result<SocksRequest> {
+        let mut buf = [0u8; 262];
+        stream.read_exact(&mut buf[..1]).await?;
+
+        let nmethods = buf[0] as usize;
         stream.read_exact(&mut buf[..nmethods]).await?;
 
-        // version 5, no-auth (0x00)
-        stream.write_all(&[0x05, 0x00]).await?;
+        // prefer username/password authentication as it selects the isolated session
+        let username = if buf[..nmethods].contains(&SOCKSV5_USERNAME_PASSWORD) {
+            stream.write_all(&[SOCKSV5_VERSION, SOCKSV5_USERNAME_PASSWORD]).await?;
+            Some(Self::read_username_password(&mut stream).await?)
+        } else if buf[..nmethods].contains(&SOCKSV5_NO_AUTH) {
+            stream.write_all(&[SOCKSV5_VERSION, SOCKSV5_NO_AUTH]).await?;
+            None
+        } else {
+            stream.write_all(&[SOCKSV5_VERSION, SOCKSV5_NO_ACCEPTABLE_METHODS]).await?;
+            anyhow::bail!("No acceptable authentication method");
+        };
 
         // version, cmd, rsv, atyp
         stream.read_exact(&mut buf[..4]).await?;
         let cmd = buf[1];
         let atyp = buf[3];
 
-        if cmd != SOCKSV5_TCP {
-            anyhow::bail!("Only TCP CONNECT supported");
+        match cmd {
+            SOCKSV5_TCP => {
+                let (host, port) = Self::read_address(&mut stream, atyp).await?;
+
+                // ip addresses and clearnet hosts can only be reached through an outproxy
+                if !host.ends_with(".i2p") && !outproxy_enabled {
+                    stream.write_all(&Self::reply(SOCKSV5_NOT_ALLOWED, None)).await?;
+                    anyhow::bail!("Cannot connect to clearnet address, outproxy not enabled");
+                }
+
+                Ok(SocksRequest {
+                    command: SocksCommand::Connect { host, port },
+                    stream,
+                    username,
+                    version: SocksVersion::V5,
+                })
+            }
+            SOCKSV5_UDP => {
+                // address is the address the client expects to send datagrams from
+                // but it's not used since datagrams are only accepted from the client's ip
+                let _ = Self::read_address(&mut stream, atyp).await?;
+
+                Ok(SocksRequest {
+                    command: SocksCommand::UdpAssociate,
+                    stream,
+                    username,
+                    version: SocksVersion::V5,
+                })
+            }
+            _ => anyhow::bail!("Only TCP CONNECT and UDP ASSOCIATE supported"),
         }
+    }
 
-        // only domain is supported for i2p
-        if atyp != SOCKSV5_DOMAIN {
-            anyhow::bail!("Only Domain supported");
+    /// Send reply to the `TCP CONNECT` of a `version` client.
+    async fn connect_reply(
+        stream: &mut TcpStream,
+        version: SocksVersion,
+        succeeded: bool,
+    ) -> std::io::Result<()> {
+        match (version, succeeded) {
+            // null byte, status, port, ip
+            (SocksVersion::V4, true) =>
+                stream.write_all(&[0x00, SOCKSV4_GRANTED, 0, 0, 0, 0, 0, 0]).await,
+            (SocksVersion::V4, false) =>
+                stream.write_all(&[0x00, SOCKSV4_REJECTED, 0, 0, 0, 0, 0, 0]).await,
+            (SocksVersion::V5, true) =>
+                stream.write_all(&Self::reply(SOCKSV5_SUCCEEDED, None)).await,
+            (SocksVersion::V5, false) =>
+                stream.write_all(&Self::reply(SOCKSV5_GENERAL_FAILURE, None)).await,
         }
+    }
 
-        stream.read_exact(&mut buf[..1]).await?;
-        let len = buf[0] as usize;
-        stream.read_exact(&mut buf[..len]).await?;
-        let target = String::from_utf8_lossy(&buf[..len]).to_string();
+    /// Reject `TCP CONNECT` of the client.
+    fn reject(request: ConnectRequest) {
+        let ConnectRequest {
+            mut stream,
+            version,
+            ..
+        } = request;
 
-        // Read port
-        stream.read_exact(&mut buf[..2]).await?;
-        let port = u16::from_be_bytes([buf[0], buf[1]]);
+        tokio::spawn(async move { Self::connect_reply(&mut stream, version, false).await });
+    }
+
+    /// Open stream to `host:port` of `request` using `session` and relay data between it and the
+    /// client.
+    ///
+    /// If `host` is not an I2P host, the stream is opened to `outproxy` instead and the
+    /// connection to `host:port` is requested from the outproxy. The client is sent a reply once
+    /// the stream has been opened or if opening it failed.
+    ///
+    /// `active` is held until the stream is closed.
+    fn connect(
+        session: &mut Session<style::Stream>,
+        request: ConnectRequest,
+        outproxy: Option<&str>,
+        active: Option<Arc<()>>,
+    ) {
+        let ConnectRequest {
+            host,
+            port,
+            mut stream,
+            version,
+        } = request;
+
+        let (destination, dst_port, outproxy_target) = match (host.ends_with(".i2p"), outproxy) {
+            (true, _) => (host, port, None),
+            (false, Some(outproxy)) => (outproxy.to_owned(), 0u16, Some((host, port))),
+            (false, None) => {
+                tracing::debug!(
+                    target: LOG_TARGET,
+                    %host,
+                    "cannot connect to clearnet address, outproxy not enabled",
+                );
+                return Self::reject(ConnectRequest {
+                    host,
+                    port,
+                    stream,
+                    version,
+                });
+            }
+        };
+
+        tracing::trace!(
+            target: LOG_TARGET,
+            %destination,
+            %dst_port,
+            outproxy_target = ?outproxy_target,
+            "connect to remote destination"
+        );
 
-        let reply = [
-            0x05, 0x00, 0x00, 0x01, // version, success, reserved, IPv4
-            0, 0, 0, 0, // BIND addr (0.0.0.0)
-            0, 0, // BIND port (0)
-        ];
-        stream.write_all(&reply).await?;
+        let future = session.connect_detached_with_options(
+            &destination,
+            StreamOptions {
+                dst_port,
+                ..Default::default()
+            },
+        );
+
+        tokio::spawn(async move {
+            let _active = active;
+
+            let mut i2p_stream = match future.await {
+                Ok(i2p_stream) => i2p_stream,
+                Err(error) => {
+                    tracing::debug!(
+                        target: LOG_TARGET,
+                        ?error,
+                        "failed to connect to destination",
+                    );
+                    Self::connect_reply(&mut stream, version, false).await?;
+                    return Err(anyhow::Error::from(error));
+                }
+            };
+
+            if let Some((host, port)) = outproxy_target {
+                if let Err(error) = outproxy_handshake(&mut i2p_stream, &host, port).await {
+                    tracing::debug!(
+                        target: LOG_TARGET,
+                        %host,
+                        %port,
+                        ?error,
+                        "outproxy failed to connect to host",
+                    );
+                    Self::connect_reply(&mut stream, version, false).await?;
+                    return Err(error);
+                }
+            }
+
+            Self::connect_reply(&mut stream, version, true).await?;
+
+            tokio::io::copy_bidirectional(&mut i2p_stream, &mut stream)
+                .await
+                .map_err(From::from)
+        });
+    }
+
+    /// Handle `TCP CONNECT` of `request`.
+    ///
+    /// If the client authenticated, the stream is opened over the isolated session of the
+    /// username and if the session doesn't exist yet, it's created. If the maximum number of
+    /// isolated sessions has been reached, the least recently used idle session is destroyed and if
+    /// there are no idle sessions, the request is rejected.
+    fn on_connect(&mut self, request: ConnectRequest, username: Option<String>) {
+        let outproxy = self.outproxy.as_deref();
+
+        let Some(username) = username else {
+            return Self::connect(&mut self.session, request, outproxy, None);
+        };
+
+        if let Some(session) = self.sessions.get_mut(&username) {
+            session.last_used = Instant::now();
+            let active = Some(Arc::clone(&session.active));
+
+            return Self::connect(&mut session.session, request, outproxy, active);
+        }
+
+        if let Some(pending) = self.pending.get_mut(&username) {
+            return pending.push(request);
+        }
+
+        if self.sessions.len() + self.pending.len() >= MAX_ISOLATED_SESSIONS {
+            let lru = self
+                .sessions
+                .iter()
+                .filter(|(_, session)| Arc::strong_count(&session.active) == 1)
+                .min_by_key(|(_, session)| session.last_used)
+                .map(|(username, _)| username.clone());
+
+            let Some(lru) = lru else {
+                tracing::warn!(
+                    target: LOG_TARGET,
+                    "maximum number of isolated sessions reached, rejecting connection",
+                );
+                return Self::reject(request);
+            };
+
+            tracing::debug!(
+                target: LOG_TARGET,
+                username = %lru,
+                "destroying least recently used isolated session",
+            );
+            self.sessions.remove(&lru);
+        }
+
+        tracing::debug!(
+            target: LOG_TARGET,
+            %username,
+            "create isolated session",
+        );
+
+        let samv3_tcp_port = self.samv3_tcp_port;
+        self.pending.insert(username.clone(), vec![request]);
+        self.session_futures.spawn(async move {
+            let session = Session::<style::Stream>::new(SessionOptions {
+                publish: false,
+                samv3_tcp_port,
+                ..Default::default()
+            })
+            .await;
+
+            (username, session)
+        });
+    }
+
+    /// Destroy isolated sessions which have no active streams and haven't been used for
+    /// [`SESSION_IDLE_TIMEOUT`].
+    fn prune_idle_sessions(&mut self) {
+        self.sessions.retain(|username, session| {
+            let idle = Arc::strong_count(&session.active) == 1
+                && session.last_used.elapsed() >= SESSION_IDLE_TIMEOUT;
+
+            if idle {
+                tracing::debug!(
+                    target: LOG_TARGET,
+                    %username,
+                    "destroying idle isolated session",
+                );
+            }
+
+            !idle
+        });
+    }
+
+    /// Handle `UDP ASSOCIATE`.
+    ///
+    /// Bind a UDP socket for the client and create a repliable datagram session for the
+    /// association. The association is active until the client closes `stream`.
+    async fn udp_associate(
+        mut stream: TcpStream,
+        host: String,
+        samv3_tcp_port: u16,
+        samv3_udp_port: Option<u16>,
+    ) -> anyhow::Result<()> {
+        let Some(samv3_udp_port) = samv3_udp_port else {
+            stream.write_all(&Self::reply(SOCKSV5_GENERAL_FAILURE, None)).await?;
+            anyhow::bail!("SAMv3 datagrams not enabled");
+        };
+
+        let socket = UdpSocket::bind(format!("{host}:0")).await?;
+        let mut session = match Session::<style::Repliable>::new(SessionOptions {
+            publish: false,
+            samv3_tcp_port,
+            samv3_udp_port,
+            ..Default::default()
+        })
+        .await
+        {
+            Ok(session) => session,
+            Err(error) => {
+                stream.write_all(&Self::reply(SOCKSV5_GENERAL_FAILURE, None)).await?;
+                return Err(error.into());
+            }
+        };
+
+        stream
+            .write_all(&Self::reply(SOCKSV5_SUCCEEDED, Some(socket.local_addr()?)))
+            .await?;
+
+        let client_ip = stream.peer_addr()?.ip();
+        let router_api = RouterApi::new(samv3_tcp_port);
+        let mut client_address: Option<SocketAddr> = None;
+        let mut destinations = HashMap::<String, String>::new();
+        let mut udp_buf = vec![0u8; MAX_DATAGRAM_SIZE];
+        let mut i2p_buf = vec![0u8; MAX_DATAGRAM_SIZE];
+        let mut tcp_buf = [0u8; 64];
+
+        loop {
+            tokio::select! {
+                result = stream.read(&mut tcp_buf) => match result {
+                    Ok(0) | Err(_) => return Ok(()),
+                    Ok(_) => {}
+                },
+                result = socket.recv_from(&mut udp_buf) => {
+                    let (nread, address) = result?;
+
+                    // only accept datagrams from the host that requested the association
+                    if address.ip() != client_ip {
+                        continue;
+                    }
+                    client_address = Some(address);
+
+                    let Some((host, _port, payload)) = parse_udp_header(&udp_buf[..nread]) else {
+                        tracing::debug!(
+                            target: LOG_TARGET,
+                            "invalid or fragmented udp datagram",
+                        );
+                        continue;
+                    };
+
+                    let destination = match destinations.get(&host) {
+                        Some(destination) => destination.clone(),
+                        None if host.ends_with(".i2p") => match tokio::time::timeout(
+                            NAME_LOOKUP_TIMEOUT,
+                            router_api.lookup_name(&host),
+                        )
+                        .await
+                        {
+                            Ok(Ok(destination)) => {
+                                destinations.insert(host, destination.clone());
+                                destination
+                            }
+                            Ok(Err(error)) => {
+                                tracing::debug!(
+                                    target: LOG_TARGET,
+                                    %host,
+                                    ?error,
+                                    "failed to resolve host",
+                                );
+                                continue;
+                            }
+                            Err(_) => {
+                                tracing::debug!(
+                                    target: LOG_TARGET,
+                                    %host,
+                                    "host lookup timed out",
+                                );
+                                continue;
+                            }
+                        },
+                        None => host,
+                    };
+
+                    if let Err(error) = session.send_to(payload, &destination).await {
+                        tracing::debug!(
+                            target: LOG_TARGET,
+                            ?error,
+                            "failed to send datagram",
+                        );
+                    }
+                }
+                result = session.recv_from(&mut i2p_buf) => {
+                    let (nread, destination) = result?;
+
+                    let Some(client_address) = client_address else {
+                        continue;
+                    };
 
-        Ok((stream, target, port))
+                    // datagrams are delivered to the client from the `.b32.i2p` address of the
+                    // sender since a full destination doesn't fit into a socks address
+                    let Some(host) = base64_decode(&destination)
+                        .and_then(|destination| Destination::parse(&destination))
+                        .map(|destination| {
+                            format!("{}.b32.i2p", base32_encode(destination.id().to_vec()))
+                        })
+                    else {
+                        continue;
+                    };
+
+                    let mut datagram = udp_header(&host, 0);
+                    datagram.extend_from_slice(&i2p_buf[..nread]);
+                    destinations.insert(host, destination);
+
+                    socket.send_to(&datagram, client_address).await?;
+                }
+            }
+        }
+    }
+
+    /// Create SOCKSv5 reply with status `reply` and bound address `address`.
+    fn reply(reply: u8, address: Option<SocketAddr>) -> Vec<u8> {
+        let mut out = vec![SOCKSV5_VERSION, reply, 0x00];
+
+        match address {
+            Some(SocketAddr::V6(address)) => {
+                out.push(SOCKSV5_IPV6);
+                out.extend_from_slice(&address.ip().octets());
+                out.extend_from_slice(&address.port().to_be_bytes());
+            }
+            Some(SocketAddr::V4(address)) => {
+                out.push(SOCKSV5_IPV4);
+                out.extend_from_slice(&address.ip().octets());
+                out.extend_from_slice(&address.port().to_be_bytes());
+            }
+            None => {
+                out.push(SOCKSV5_IPV4);
+                out.extend_from_slice(&[0u8; 6]);
+            }
+        }
+
+        out
     }
 
     /// Run event loop of [`SocksProxy`].
     pub async fn run(mut self) -> anyhow::Result<()> {
+        let mut prune_timer = tokio::time::interval(SESSION_PRUNE_INTERVAL);
+
         loop {
             tokio::select! {
+                _ = prune_timer.tick() => self.prune_idle_sessions(),
                 result = self.listener.accept() => {
                     let (stream, _) = result?;
-                    self.futures.spawn(Self::parse_tcp_connect(stream));
+                    self.futures.spawn(Self::parse_request(stream, self.outproxy.is_some()));
                 }
                 result = self.futures.join_next(), if !self.futures.is_empty() => match result {
                     None => {}
                     Some(Err(error)) => tracing::warn!(
                         target: LOG_TARGET,
                         %error,
-                        "failed to read TCP CONNECT from client",
+                        "failed to read request from client",
                     ),
                     Some(Ok(Err(error))) => tracing::warn!(
                         target: LOG_TARGET,
                         %error,
-                        "failed to parse TCP CONNECT",
+                        "failed to parse request",
                     ),
-                    Some(Ok(Ok((mut stream, host, port)))) => {
-                        tracing::trace!(
-                            target: LOG_TARGET,
-                            %host,
-                            %port,
-                            "connect to remote destination"
-                        );
+                    Some(Ok(Ok(SocksRequest { command, stream, username, version }))) => match command {
+                        SocksCommand::Connect { host, port } => self.on_connect(
+                            ConnectRequest {
+                                host,
+                                port,
+                                stream,
+                                version,
+                            },
+                            username,
+                        ),
+                        SocksCommand::UdpAssociate => {
+                            let host = self.host.clone();
+                            let samv3_tcp_port = self.samv3_tcp_port;
+                            let samv3_udp_port = self.samv3_udp_port;
+
+                            tokio::spawn(async move {
+                                if let Err(error) =
+                                    Self::udp_associate(stream, host, samv3_tcp_port, samv3_udp_port)
+                                        .await
+                                {
+                                    tracing::debug!(
+                                        target: LOG_TARGET,
+                                        %error,
+                                        "udp association exited",
+                                    );
+                                }
+                            });
+                        }
+                    }
+                },
+                result = self.session_futures.join_next(), if !self.session_futures.is_empty() =>
+                    match result {
+                        None | Some(Err(_)) => {}
+                        Some(Ok((username, Ok(mut session)))) => {
+                            let active = Arc::new(());
+
+                            for request in self.pending.remove(&username).unwrap_or_default() {
+                                Self::connect(
+                                    &mut session,
+                                    request,
+                                    self.outproxy.as_deref(),
+                                    Some(Arc::clone(&active)),
+                                );
+                            }
+
+                            self.sessions.insert(
+                                username,
+                                IsolatedSession {
+                                    active,
+                                    last_used: Instant::now(),
+                                    session,
+                                },
+                            );
+                        }
+                        Some(Ok((username, Err(error)))) => {
+                            tracing::warn!(
+                                target: LOG_TARGET,
+                                %username,
+                                ?error,
+                                "failed to create isolated session",
+                            );
+
+                            for request in self.pending.remove(&username).unwrap_or_default() {
+                                Self::reject(request);
+                            }
+                        }
+                    },
+            }
+        }
+    }
+}
+
+/// Request connection to `host:port` from a SOCKSv5 outproxy over `stream`.
+async fn outproxy_handshake<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+    host: &str,
+    port: u16,
+) -> anyhow::Result<()> {
+    stream.write_all(&[SOCKSV5_VERSION, 0x01, SOCKSV5_NO_AUTH]).await?;
+
+    let mut buf = [0u8; 256];
+    stream.read_exact(&mut buf[..2]).await?;
+
+    if buf[..2] != [SOCKSV5_VERSION, SOCKSV5_NO_AUTH] {
+        anyhow::bail!("Outproxy requires authentication");
+    }
+
+    let mut request = vec![SOCKSV5_VERSION, SOCKSV5_TCP, 0x00];
+    match host.parse::<IpAddr>() {
+        Ok(IpAddr::V4(address)) => {
+            request.push(SOCKSV5_IPV4);
+            request.extend_from_slice(&address.octets());
+        }
+        Ok(IpAddr::V6(address)) => {
+            request.push(SOCKSV5_IPV6);
+            request.extend_from_slice(&address.octets());
+        }
+        Err(_) => {
+            if host.len() > 255 {
+                anyhow::bail!("Host too long");
+            }
+
+            request.push(SOCKSV5_DOMAIN);
+            request.push(host.len() as u8);
+            request.extend_from_slice(host.as_bytes());
+        }
+    }
+    request.extend_from_slice(&port.to_be_bytes());
+    stream.write_all(&request).await?;
+
+    // version, reply, rsv, atyp
+    stream.read_exact(&mut buf[..4]).await?;
+
+    if buf[1] != SOCKSV5_SUCCEEDED {
+        anyhow::bail!("Outproxy rejected request: {}", buf[1]);
+    }
+
+    // read bound address and port which are not used
+    let len = match buf[3] {
+        SOCKSV5_IPV4 => 4 + 2,
+        SOCKSV5_IPV6 => 16 + 2,
+        SOCKSV5_DOMAIN => {
+            stream.read_exact(&mut buf[..1]).await?;
+            buf[0] as usize + 2
+        }
+        _ => anyhow::bail!("Unknown address type"),
+    };
+    stream.read_exact(&mut buf[..len]).await?;
+
+    Ok(())
+}
+
+/// Parse SOCKSv5 UDP request header from `datagram`.
+///
+/// Returns the lowercased destination host, port and the payload. Fragmented datagrams and IP
+/// addresses are not supported.
+fn parse_udp_header(datagram: &[u8]) -> Option<(String, u16, &[u8])> {
+    // rsv, frag, atyp
+    let [0x00, 0x00, 0x00, SOCKSV5_DOMAIN, len, rest @ ..] = datagram else {
+        return None;
+    };
+    let len = *len as usize;
+
+    if rest.len() < len + 2 {
+        return None;
+    }
+
+    let host = std::str::from_utf8(&rest[..len]).ok()?.to_ascii_lowercase();
+    let port = u16::from_be_bytes([rest[len], rest[len + 1]]);
+
+    Some((host, port, &rest[len + 2..]))
+}
+
+/// Create SOCKSv5 UDP request header for `host:port`.
+fn udp_header(host: &str, port: u16) -> Vec<u8> {
+    let mut out = vec![0x00, 0x00, 0x00, SOCKSV5_DOMAIN, host.len() as u8];
+    out.extend_from_slice(host.as_bytes());
+    out.extend_from_slice(&port.to_be_bytes());
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fast_socks5::{
+        client::{Config, Socks5Stream},
+        socks4::client::Socks4Stream,
+    };
+    use tokio::io::{AsyncBufReadExt, BufReader};
+
+    use std::time::Duration;
+
+    /// Fake SAMv3 server.
+    struct SamServer {
+        /// TCP listener for the server.
+        listener: TcpListener,
+
+        /// Whether isolated sessions are rejected.
+        reject_isolated: bool,
+    }
+
+    impl SamServer {
+        /// Create new [`SamServer`].
+        async fn new() -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+
+            Self {
+                listener,
+                reject_isolated: false,
+            }
+        }
+
+        /// Run the event loop of [`SamServer`].
+        ///
+        /// Opened streams respond to an outproxy handshake and are kept open until the proxy
+        /// closes them.
+        async fn run(self) {
+            while let Ok((stream, _)) = self.listener.accept().await {
+                let reject_isolated = self.reject_isolated;
+
+                tokio::spawn(async move {
+                    let mut lines = BufReader::new(stream).lines();
+
+                    while let Ok(Some(command)) = lines.next_line().await {
+                        if command.starts_with("SESSION CREATE")
+                            && reject_isolated
+                            && !command.contains("socks-proxy")
+                        {
+                            lines
+                                .get_mut()
+                                .write_all("SESSION STATUS RESULT=I2P_ERROR\n".as_bytes())
+                                .await
+                                .unwrap();
+                            continue;
+                        }
+
+                        if command.starts_with("STREAM CONNECT") {
+                            let mut stream = lines.into_inner();
+                            stream.write_all("STREAM STATUS RESULT=OK\n".as_bytes()).await.unwrap();
+
+                            let mut buffer = [0u8; 512];
+                            while let Ok(nread @ 1..) = stream.read(&mut buffer).await {
+                                // outproxy greeting and request
+                                match (nread, buffer[0]) {
+                                    (3, SOCKSV5_VERSION) => {
+                                        stream.write_all(&[0x05, 0x00]).await.unwrap();
+                                    }
+                                    (_, SOCKSV5_VERSION) => {
+                                        stream
+                                            .write_all(&SocksProxy::reply(SOCKSV5_SUCCEEDED, None))
+                                            .await
+                                            .unwrap();
+                                    }
+                                    _ => {}
+                                }
+                            }
+                            return;
+                        }
+
+                        if command.starts_with("HELLO VERSION") {
+                            lines
+                                .get_mut()
+                                .write_all("HELLO REPLY RESULT=OK VERSION=3.2\n".as_bytes())
+                                .await
+                                .unwrap();
+                            continue;
+                        }
+
+                        if command.starts_with("SESSION CREATE") {
+                            lines
+                                .get_mut()
+                                .write_all(
+                                    "SESSION STATUS RESULT=OK DESTINATION=Fam-qmfYnngAnwkq3qwhkkoUeWNP\
+                                        ckuYbZhK4xWwTzHa3BN9DY4dozKDPywI22LWfT1ALnVDonnRhCux0Iv3wc74-s2CTJOGLp\
+                                        YvPGviS99dFSqRwgxi1dESbt5Liw4FIDZQMcDjcNziHspnTFfE4B3sZUtoNM0GYkrgksS3\
+                                        BgVo3SvNn57~FkHDJvNxcaEL0uq9OGPfxNXNtyIeBxaUSJjYNbgcHG9Q2kzb~Z39FzylbE\
+                                        iS979HJnc~w9Wo4DO8VCHGM1j6-CeRlf3hZpMaqQQJU0Q~k035~voydSIzDLJzMPvVmKAV\
+                                        4q-0A5ikidKKv1N3kREQF5xDuDT1z3BMVHMIsyUECi8HOm3Ixa7XdcqpvHRl~W4RksOEdM\
+                                        ChLrUZbqVr-8uW0lMRhRszAuU2PnF16bw9XEZoVAsNNHgvFQvnOwfLnPpSxtZaGNHGO8w\
+                                        QaYmT3cImMUUhBbc9dcTYAHy8geZ1KzW4j7lpH4SsNaJPszCevkIVdvlqEAXZqh1YBQAE\
+                                        AAcAADwJfIcEBwdeM2rjFM~cPo4btsSszyKlGZeUPzoTfHZv~4eR5efcr5YlogkmARNw57\
+                                        h4sjmYvTESdTE7353u2uI=\n".as_bytes(),
+                                )
+                                .await
+                                .unwrap();
+                            continue;
+                        }
+
+                        println!("unhandled command: {command}");
+                    }
+                });
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn invalid_request() {
+        let sam_port = {
+            let sam = SamServer::new().await;
+            let port = sam.listener.local_addr().unwrap().port();
+            tokio::spawn(sam.run());
+
+            port
+        };
+
+        let proxy = SocksProxy::new(
+            SocksProxyConfig {
+                port: 0,
+                host: "127.0.0.1".to_string(),
+                outproxy: None,
+            },
+            sam_port,
+            None,
+        )
+        .await
+        .unwrap();
+        let address = proxy.listener.local_addr().unwrap();
+        tokio::spawn(proxy.run());
+
+        // send invalid http request to the proxy
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        stream.write_all("hello, world!\n".as_bytes()).await.unwrap();
+
+        let mut buffer = vec![0u8; 512];
+        assert!(stream.read(&mut buffer).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn socksv4_client() {
+        let sam_port = {
+            let sam = SamServer::new().await;
+            let port = sam.listener.local_addr().unwrap().port();
+            tokio::spawn(sam.run());
+
+            port
+        };
+
+        let proxy = SocksProxy::new(
+            SocksProxyConfig {
+                port: 0,
+                host: "127.0.0.1".to_string(),
+                outproxy: None,
+            },
+            sam_port,
+            None,
+        )
+        .await
+        .unwrap();
+        let address = proxy.listener.local_addr().unwrap();
+        tokio::spawn(proxy.run());
+
+        assert!(
+            Socks4Stream::connect(address, "http://host.i2p".to_string(), 80, false)
+                .await
+                .is_err()
+        )
+    }
+
+    #[tokio::test]
+    async fn socksv5_username_password_auth() {
+        let sam_port = {
+            let sam = SamServer::new().await;
+            let port = sam.listener.local_addr().unwrap().port();
+            tokio::spawn(sam.run());
+
+            port
+        };
+
+        let proxy = SocksProxy::new(
+            SocksProxyConfig {
+                port: 0,
+                host: "127.0.0.1".to_string(),
+                outproxy: None,
+            },
+            sam_port,
+            None,
+        )
+        .await
+        .unwrap();
+        let address = proxy.listener.local_addr().unwrap();
+        tokio::spawn(proxy.run());
+
+        assert!(Socks5Stream::connect_with_password(
+            address,
+            "http://host.i2p".to_string(),
+            80,
+            "username".to_string(),
+            "password".to_string(),
+            Config::default()
+        )
+        .await
+        .is_ok())
+    }
+
+    #[tokio::test]
+    async fn socksv5_ipv4_ignored() {
+        let sam_port = {
+            let sam = SamServer::new().await;
+            let port = sam.listener.local_addr().unwrap().port();
+            tokio::spawn(sam.run());
+
+            port
+        };
+
+        let proxy = SocksProxy::new(
+            SocksProxyConfig {
+                port: 0,
+                host: "127.0.0.1".to_string(),
+                outproxy: None,
+            },
+            sam_port,
+            None,
+        )
+        .await
+        .unwrap();
+        let address = proxy.listener.local_addr().unwrap();
+        tokio::spawn(proxy.run());
+
+        assert!(
+            Socks5Stream::connect(address, "127.0.0.1".to_string(), 80, Config::default())
+                .await
+                .is_err()
+        )
+    }
+
+    #[tokio::test]
+    async fn socksv5_ipv6_ignored() {
+        let sam_port = {
+            let sam = SamServer::new().await;
+            let port = sam.listener.local_addr().unwrap().port();
+            tokio::spawn(sam.run());
+
+            port
+        };
+
+        let proxy = SocksProxy::new(
+            SocksProxyConfig {
+                port: 0,
+                host: "127.0.0.1".to_string(),
+                outproxy: None,
+            },
+            sam_port,
+            None,
+        )
+        .await
+        .unwrap();
+        let address = proxy.listener.local_addr().unwrap();
+        tokio::spawn(proxy.run());
+
+        assert!(
+            Socks5Stream::connect(address, "::".to_string(), 80, Config::default())
+                .await
+                .is_err()
+        )
+    }
+
+    #[tokio::test]
+    async fn socksv5_no_acceptable_methods() {
+        let sam_port = {
+            let sam = SamServer::new().await;
+            let port = sam.listener.local_addr().unwrap().port();
+            tokio::spawn(sam.run());
+
+            port
+        };
+
+        let proxy = SocksProxy::new(
+            SocksProxyConfig {
+                port: 0,
+                host: "127.0.0.1".to_string(),
+                outproxy: None,
+            },
+            sam_port,
+            None,
+        )
+        .await
+        .unwrap();
+        let address = proxy.listener.local_addr().unwrap();
+        tokio::spawn(proxy.run());
+
+        // offer only gssapi
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        stream.write_all(&[0x05, 0x01, 0x01]).await.unwrap();
+
+        let mut buffer = [0u8; 2];
+        stream.read_exact(&mut buffer).await.unwrap();
+        assert_eq!(buffer, [0x05, 0xff]);
+    }
+
+    #[tokio::test]
+    async fn socksv5_udp_associate_without_datagrams() {
+        let sam_port = {
+            let sam = SamServer::new().await;
+            let port = sam.listener.local_addr().unwrap().port();
+            tokio::spawn(sam.run());
 
-                        let future = self.session.connect_detached_with_options(
-                            &host,
-                            StreamOptions {
-                                dst_port: port,
-                                ..Default::default()
-                            },
-                        );
+            port
+        };
 
-                        tokio::spawn(async move {
-                            match future.await {
-                                Err(error) => {
-                                    tracing::debug!(
-                                        target: LOG_TARGET,
-                                        ?error,
-                                        "failed to connect to destination",
-                                    );
-                                    Err(error)
-                                }
-                                Ok(mut i2p_stream) => {
-                                    tokio::io::copy_bidirectional(&mut i2p_stream, &mut stream)
-                                        .await
-                                        .map_err(From::from)
-                                }
-                            }
-                        });
-                    }
-                }
-            }
-        }
+        let proxy = SocksProxy::new(
+            SocksProxyConfig {
+                port: 0,
+                host: "127.0.0.1".to_string(),
+                outproxy: None,
+            },
+            sam_port,
+            None,
+        )
+        .await
+        .unwrap();
+        let address = proxy.listener.local_addr().unwrap();
+        tokio::spawn(proxy.run());
+
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        stream.write_all(&[0x05, 0x01, 0x00]).await.unwrap();
+
+        let mut buffer = [0u8; 10];
+        stream.read_exact(&mut buffer[..2]).await.unwrap();
+        assert_eq!(buffer[..2], [0x05, 0x00]);
+
+        // udp associate from 0.0.0.0:0
+        stream.write_all(&[0x05, 0x03, 0x00, 0x01, 0, 0, 0, 0, 0, 0]).await.unwrap();
+        stream.read_exact(&mut buffer).await.unwrap();
+        assert_eq!(buffer[..2], [0x05, SOCKSV5_GENERAL_FAILURE]);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use fast_socks5::{
-        client::{Config, Socks5Stream},
-        socks4::client::Socks4Stream,
-    };
-    use tokio::io::{AsyncBufReadExt, BufReader};
+    #[test]
+    fn udp_header_round_trip() {
+        let mut datagram = udp_header("host.i2p", 8888);
+        datagram.extend_from_slice(b"hello, world");
 
-    /// Fake SAMv3 server.
-    struct SamServer {
-        /// TCP listener for the server.
-        listener: TcpListener,
+        let (host, port, payload) = parse_udp_header(&datagram).unwrap();
+        assert_eq!(host, "host.i2p");
+        assert_eq!(port, 8888);
+        assert_eq!(payload, b"hello, world");
     }
 
-    impl SamServer {
-        /// Create new [`SamServer`].
-        async fn new() -> Self {
-            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    #[test]
+    fn fragmented_udp_datagram_rejected() {
+        let mut datagram = udp_header("host.i2p", 8888);
+        datagram[2] = 0x01;
 
-            Self { listener }
-        }
+        assert!(parse_udp_header(&datagram).is_none());
+        assert!(parse_udp_header(&[0x00, 0x00, 0x00, SOCKSV5_DOMAIN, 0x10, 0x00]).is_none());
+        assert!(parse_udp_header(&[0x00, 0x00, 0x00, SOCKSV5_IPV4, 0, 0, 0, 0, 0, 0]).is_none());
+    }
 
-        /// Run the event loop of [`SamServer`].
-        async fn run(self) {
-            while let Ok((stream, _)) = self.listener.accept().await {
-                tokio::spawn(async move {
-                    let mut lines = BufReader::new(stream).lines();
+    #[tokio::test]
+    async fn socksv4a_connect() {
+        let sam_port = {
+            let sam = SamServer::new().await;
+            let port = sam.listener.local_addr().unwrap().port();
+            tokio::spawn(sam.run());
 
-                    while let Ok(Some(command)) = lines.next_line().await {
-                        if command.starts_with("HELLO VERSION") {
-                            lines
-                                .get_mut()
-                                .write_all("HELLO REPLY RESULT=OK VERSION=3.2\n".as_bytes())
-                                .await
-                                .unwrap();
-                            continue;
-                        }
+            port
+        };
 
-                        if command.starts_with("SESSION CREATE") {
-                            lines
-                                .get_mut()
-                                .write_all(
-                                    "SESSION STATUS RESULT=OK DESTINATION=Fam-qmfYnngAnwkq3qwhkkoUeWNP\
-                                        ckuYbZhK4xWwTzHa3BN9DY4dozKDPywI22LWfT1ALnVDonnRhCux0Iv3wc74-s2CTJOGLp\
-                                        YvPGviS99dFSqRwgxi1dESbt5Liw4FIDZQMcDjcNziHspnTFfE4B3sZUtoNM0GYkrgksS3\
-                                        BgVo3SvNn57~FkHDJvNxcaEL0uq9OGPfxNXNtyIeBxaUSJjYNbgcHG9Q2kzb~Z39FzylbE\
-                                        iS979HJnc~w9Wo4DO8VCHGM1j6-CeRlf3hZpMaqQQJU0Q~k035~voydSIzDLJzMPvVmKAV\
-                                        4q-0A5ikidKKv1N3kREQF5xDuDT1z3BMVHMIsyUECi8HOm3Ixa7XdcqpvHRl~W4RksOEdM\
-                                        ChLrUZbqVr-8uW0lMRhRszAuU2PnF16bw9XEZoVAsNNHgvFQvnOwfLnPpSxtZaGNHGO8w\
-                                        QaYmT3cImMUUhBbc9dcTYAHy8geZ1KzW4j7lpH4SsNaJPszCevkIVdvlqEAXZqh1YBQAE\
-                                        AAcAADwJfIcEBwdeM2rjFM~cPo4btsSszyKlGZeUPzoTfHZv~4eR5efcr5YlogkmARNw57\
-                                        h4sjmYvTESdTE7353u2uI=\n".as_bytes(),
-                                )
-                                .await
-                                .unwrap();
-                            continue;
-                        }
+        let proxy = SocksProxy::new(
+            SocksProxyConfig {
+                port: 0,
+                host: "127.0.0.1".to_string(),
+                outproxy: None,
+            },
+            sam_port,
+            None,
+        )
+        .await
+        .unwrap();
+        let address = proxy.listener.local_addr().unwrap();
+        tokio::spawn(proxy.run());
 
-                        println!("unhandled command: {command}");
-                    }
-                });
-            }
-        }
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        stream
+            .write_all(&[0x04, 0x01, 0x00, 0x50, 0x00, 0x00, 0x00, 0x01])
+            .await
+            .unwrap();
+        stream.write_all(b"user\0host.i2p\0").await.unwrap();
+
+        let mut buffer = [0u8; 8];
+        stream.read_exact(&mut buffer).await.unwrap();
+        assert_eq!(buffer[..2], [0x00, SOCKSV4_GRANTED]);
     }
 
     #[tokio::test]
-    async fn invalid_request() {
+    async fn socksv4_ip_without_outproxy() {
         let sam_port = {
             let sam = SamServer::new().await;
             let port = sam.listener.local_addr().unwrap().port();
@@ -253,24 +1446,26 @@ mod tests {
             SocksProxyConfig {
                 port: 0,
                 host: "127.0.0.1".to_string(),
+                outproxy: None,
             },
             sam_port,
+            None,
         )
         .await
         .unwrap();
         let address = proxy.listener.local_addr().unwrap();
         tokio::spawn(proxy.run());
 
-        // send invalid http request to the proxy
         let mut stream = TcpStream::connect(address).await.unwrap();
-        stream.write_all("hello, world!\n".as_bytes()).await.unwrap();
+        stream.write_all(&[0x04, 0x01, 0x00, 0x50, 127, 0, 0, 1, 0x00]).await.unwrap();
 
-        let mut buffer = vec![0u8; 512];
-        assert!(stream.read(&mut buffer).await.is_err());
+        let mut buffer = [0u8; 8];
+        stream.read_exact(&mut buffer).await.unwrap();
+        assert_eq!(buffer[..2], [0x00, SOCKSV4_REJECTED]);
     }
 
     #[tokio::test]
-    async fn socksv4_client() {
+    async fn socksv5_clearnet_host_without_outproxy() {
         let sam_port = {
             let sam = SamServer::new().await;
             let port = sam.listener.local_addr().unwrap().port();
@@ -283,8 +1478,10 @@ mod tests {
             SocksProxyConfig {
                 port: 0,
                 host: "127.0.0.1".to_string(),
+                outproxy: None,
             },
             sam_port,
+            None,
         )
         .await
         .unwrap();
@@ -292,14 +1489,14 @@ mod tests {
         tokio::spawn(proxy.run());
 
         assert!(
-            Socks4Stream::connect(address, "http://host.i2p".to_string(), 80, false)
+            Socks5Stream::connect(address, "example.com".to_string(), 80, Config::default())
                 .await
                 .is_err()
         )
     }
 
     #[tokio::test]
-    async fn socksv5_auth_ignored() {
+    async fn socksv5_uppercase_i2p_host_without_outproxy() {
         let sam_port = {
             let sam = SamServer::new().await;
             let port = sam.listener.local_addr().unwrap().port();
@@ -312,28 +1509,25 @@ mod tests {
             SocksProxyConfig {
                 port: 0,
                 host: "127.0.0.1".to_string(),
+                outproxy: None,
             },
             sam_port,
+            None,
         )
         .await
         .unwrap();
         let address = proxy.listener.local_addr().unwrap();
         tokio::spawn(proxy.run());
 
-        assert!(Socks5Stream::connect_with_password(
-            address,
-            "http://host.i2p".to_string(),
-            80,
-            "username".to_string(),
-            "password".to_string(),
-            Config::default()
+        assert!(
+            Socks5Stream::connect(address, "Site.I2P".to_string(), 80, Config::default())
+                .await
+                .is_ok()
         )
-        .await
-        .is_ok())
     }
 
     #[tokio::test]
-    async fn socksv5_ipv4_ignored() {
+    async fn socksv5_ipv4_with_outproxy() {
         let sam_port = {
             let sam = SamServer::new().await;
             let port = sam.listener.local_addr().unwrap().port();
@@ -346,23 +1540,51 @@ mod tests {
             SocksProxyConfig {
                 port: 0,
                 host: "127.0.0.1".to_string(),
+                outproxy: Some("outproxy.i2p".to_string()),
             },
             sam_port,
+            None,
         )
         .await
         .unwrap();
+        assert_eq!(proxy.outproxy.as_deref(), Some("outproxy.i2p"));
+
         let address = proxy.listener.local_addr().unwrap();
         tokio::spawn(proxy.run());
 
         assert!(
             Socks5Stream::connect(address, "127.0.0.1".to_string(), 80, Config::default())
                 .await
-                .is_err()
+                .is_ok()
         )
     }
 
     #[tokio::test]
-    async fn socksv5_ipv6_ignored() {
+    async fn invalid_outproxy_disabled() {
+        let sam_port = {
+            let sam = SamServer::new().await;
+            let port = sam.listener.local_addr().unwrap().port();
+            tokio::spawn(sam.run());
+
+            port
+        };
+
+        let proxy = SocksProxy::new(
+            SocksProxyConfig {
+                port: 0,
+                host: "127.0.0.1".to_string(),
+                outproxy: Some("outproxy.com".to_string()),
+            },
+            sam_port,
+            None,
+        )
+        .await
+        .unwrap();
+        assert!(proxy.outproxy.is_none());
+    }
+
+    #[tokio::test]
+    async fn socksv5_reply_sent_after_stream_is_opened() {
         let sam_port = {
             let sam = SamServer::new().await;
             let port = sam.listener.local_addr().unwrap().port();
@@ -375,18 +1597,231 @@ mod tests {
             SocksProxyConfig {
                 port: 0,
                 host: "127.0.0.1".to_string(),
+                outproxy: None,
             },
             sam_port,
+            None,
         )
         .await
         .unwrap();
         let address = proxy.listener.local_addr().unwrap();
         tokio::spawn(proxy.run());
 
-        assert!(
-            Socks5Stream::connect(address, "::".to_string(), 80, Config::default())
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        stream.write_all(&[0x05, 0x01, SOCKSV5_NO_AUTH]).await.unwrap();
+
+        let mut buffer = [0u8; 10];
+        stream.read_exact(&mut buffer[..2]).await.unwrap();
+        assert_eq!(buffer[..2], [0x05, SOCKSV5_NO_AUTH]);
+
+        stream.write_all(&[0x05, SOCKSV5_TCP, 0x00, SOCKSV5_DOMAIN, 8]).await.unwrap();
+        stream.write_all(b"host.i2p").await.unwrap();
+        stream.write_all(&80u16.to_be_bytes()).await.unwrap();
+
+        stream.read_exact(&mut buffer).await.unwrap();
+        assert_eq!(buffer[..2], [0x05, SOCKSV5_SUCCEEDED]);
+    }
+
+    #[tokio::test]
+    async fn failed_isolated_session_rejects_queued_clients() {
+        let sam_port = {
+            let mut sam = SamServer::new().await;
+            sam.reject_isolated = true;
+            let port = sam.listener.local_addr().unwrap().port();
+            tokio::spawn(sam.run());
+
+            port
+        };
+
+        let proxy = SocksProxy::new(
+            SocksProxyConfig {
+                port: 0,
+                host: "127.0.0.1".to_string(),
+                outproxy: None,
+            },
+            sam_port,
+            None,
+        )
+        .await
+        .unwrap();
+        let address = proxy.listener.local_addr().unwrap();
+        tokio::spawn(proxy.run());
+
+        let futures = (0..3).map(|_| async move {
+            let mut stream = TcpStream::connect(address).await.unwrap();
+            stream
+                .write_all(&[0x04, 0x01, 0x00, 0x50, 0x00, 0x00, 0x00, 0x01])
                 .await
-                .is_err()
+                .unwrap();
+            stream.write_all(b"user\0host.i2p\0").await.unwrap();
+
+            let mut buffer = [0u8; 8];
+            stream.read_exact(&mut buffer).await.unwrap();
+            buffer
+        });
+
+        for buffer in futures::future::join_all(futures).await {
+            assert_eq!(buffer[..2], [0x00, SOCKSV4_REJECTED]);
+        }
+    }
+
+    #[tokio::test]
+    async fn idle_isolated_session_evicted() {
+        let sam_port = {
+            let sam = SamServer::new().await;
+            let port = sam.listener.local_addr().unwrap().port();
+            tokio::spawn(sam.run());
+
+            port
+        };
+
+        let proxy = SocksProxy::new(
+            SocksProxyConfig {
+                port: 0,
+                host: "127.0.0.1".to_string(),
+                outproxy: None,
+            },
+            sam_port,
+            None,
+        )
+        .await
+        .unwrap();
+        let address = proxy.listener.local_addr().unwrap();
+        tokio::spawn(proxy.run());
+
+        // create the maximum number of isolated sessions and close their streams
+        for i in 0..MAX_ISOLATED_SESSIONS {
+            let stream = Socks5Stream::connect_with_password(
+                address,
+                "host.i2p".to_string(),
+                80,
+                format!("user{i}"),
+                "password".to_string(),
+                Config::default(),
+            )
+            .await
+            .unwrap();
+            drop(stream);
+        }
+        tokio::time::sleep(Duration::from_millis(500)).await;
+
+        // the least recently used idle session is destroyed for the new username
+        assert!(Socks5Stream::connect_with_password(
+            address,
+            "host.i2p".to_string(),
+            80,
+            "new-user".to_string(),
+            "password".to_string(),
+            Config::default()
+        )
+        .await
+        .is_ok());
+    }
+
+    #[tokio::test]
+    async fn idle_isolated_sessions_pruned() {
+        let sam_port = {
+            let sam = SamServer::new().await;
+            let port = sam.listener.local_addr().unwrap().port();
+            tokio::spawn(sam.run());
+
+            port
+        };
+
+        let mut proxy = SocksProxy::new(
+            SocksProxyConfig {
+                port: 0,
+                host: "127.0.0.1".to_string(),
+                outproxy: None,
+            },
+            sam_port,
+            None,
         )
+        .await
+        .unwrap();
+
+        // reference held as if a stream was open over the session
+        let mut streams = Vec::new();
+
+        for (username, active) in [("idle", false), ("active", true), ("recent", false)] {
+            let session = Session::<style::Stream>::new(SessionOptions {
+                publish: false,
+                samv3_tcp_port: sam_port,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+            let last_used = match username {
+                "recent" => Instant::now(),
+                _ => Instant::now().checked_sub(SESSION_IDLE_TIMEOUT).unwrap(),
+            };
+
+            proxy.sessions.insert(
+                username.to_string(),
+                IsolatedSession {
+                    active: Arc::new(()),
+                    last_used,
+                    session,
+                },
+            );
+
+            if active {
+                streams.push(Arc::clone(&proxy.sessions[username].active));
+            }
+        }
+
+        proxy.prune_idle_sessions();
+
+        let mut remaining = proxy.sessions.keys().cloned().collect::<Vec<_>>();
+        remaining.sort();
+        assert_eq!(remaining, vec!["active".to_string(), "recent".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn outproxy_handshake_domain() {
+        let (mut client, mut server) = tokio::io::duplex(1024);
+
+        let handle = tokio::spawn(async move {
+            let mut buffer = [0u8; 3];
+            server.read_exact(&mut buffer).await.unwrap();
+            assert_eq!(buffer, [0x05, 0x01, 0x00]);
+            server.write_all(&[0x05, 0x00]).await.unwrap();
+
+            let mut buffer = [0u8; 5 + 11 + 2];
+            server.read_exact(&mut buffer).await.unwrap();
+            assert_eq!(buffer[..5], [0x05, 0x01, 0x00, SOCKSV5_DOMAIN, 11]);
+            assert_eq!(&buffer[5..16], b"example.com");
+            assert_eq!(buffer[16..], [0x01, 0xbb]);
+
+            server
+                .write_all(&[0x05, 0x00, 0x00, 0x01, 1, 2, 3, 4, 0x00, 0x50])
+                .await
+                .unwrap();
+        });
+
+        outproxy_handshake(&mut client, "example.com", 443).await.unwrap();
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn outproxy_handshake_rejected() {
+        let (mut client, mut server) = tokio::io::duplex(1024);
+
+        tokio::spawn(async move {
+            let mut buffer = [0u8; 3];
+            server.read_exact(&mut buffer).await.unwrap();
+            server.write_all(&[0x05, 0x00]).await.unwrap();
+
+            let mut buffer = [0u8; 4 + 16 + 2];
+            server.read_exact(&mut buffer).await.unwrap();
+            assert_eq!(buffer[3], SOCKSV5_IPV6);
+
+            server
+                .write_all(&[0x05, SOCKSV5_NOT_ALLOWED, 0x00, 0x01, 0, 0, 0, 0, 0, 0])
+                .await
+                .unwrap();
+        });
+
+        assert!(outproxy_handshake(&mut client, "::1", 80).await.is_err());
     }
 }
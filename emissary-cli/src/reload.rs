@@ -0,0 +1,552 @@
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Hot-reload of `router.toml`.
+//!
+//! When the router receives `SIGHUP` or a reload request over the control socket, `router.toml` is
+//! read again and compared against the configuration that is currently applied. Only the client
+//! tunnels, server tunnels, proxies and address book subscriptions that changed are restarted.
+
+use crate::{
+    address_book::{AddressBookHandle, AddressBookManager},
+    cli::{HttpProxyOptions, SocksProxyOptions},
+    config::{ClientTunnelConfig, Config, ReloadableConfig, ServerTunnelConfig},
+    error::Error,
+    proxy::{http::HttpProxy, socks::SocksProxy},
+    tunnel::TunnelManagerHandle,
+};
+
+use futures::channel::oneshot;
+use tokio::{sync::mpsc, task::JoinHandle};
+
+use std::path::PathBuf;
+
+/// Logging target for the file.
+const LOG_TARGET: &str = "emissary::reload";
+
+/// Handle which can be used to request [`ConfigReloader`] to reload `router.toml`.
+#[derive(Clone)]
+pub struct ReloadHandle {
+    /// TX channel for sending reload requests to [`ConfigReloader`].
+    tx: mpsc::Sender<oneshot::Sender<crate::Result<()>>>,
+}
+
+impl ReloadHandle {
+    /// Reload `router.toml` and wait until the changes have been applied.
+    pub async fn reload(&self) -> crate::Result<()> {
+        let (tx, rx) = oneshot::channel();
+
+        self.tx
+            .send(tx)
+            .await
+            .map_err(|_| Error::Custom(String::from("config reloader has exited")))?;
+
+        rx.await
+            .map_err(|_| Error::Custom(String::from("config reloader has exited")))?
+    }
+}
+
+/// Compare `old` and `new` tunnel configurations.
+///
+/// Returns names of the tunnels that must be stopped and configurations of the tunnels that must
+/// be started. A tunnel whose configuration changed is both stopped and started.
+fn diff_tunnels<T: Clone + PartialEq>(
+    old: &[T],
+    new: &[T],
+    name: impl Fn(&T) -> &str,
+) -> (Vec<String>, Vec<T>) {
+    let removed = old
+        .iter()
+        .filter(|tunnel| !new.contains(tunnel))
+        .map(|tunnel| name(tunnel).to_string())
+        .collect();
+    let added = new.iter().filter(|tunnel| !old.contains(tunnel)).cloned().collect();
+
+    (removed, added)
+}
+
+/// Config reloader.
+///
+/// Owns the HTTP proxy, SOCKS proxy and address book tasks and applies changes made to
+/// `router.toml` to them and to the client and server tunnel managers.
+pub struct ConfigReloader {
+    /// Address book manager, if the address book was enabled when the router was started.
+    ///
+    /// Used as a template for restarting the address book when its subscriptions change.
+    address_book: Option<AddressBookManager>,
+
+    /// Address book task and the TX channel for signaling it to shut down.
+    address_book_task: Option<(oneshot::Sender<()>, JoinHandle<()>)>,
+
+    /// Handle to address book, given to the HTTP proxy.
+    address_book_handle: Option<AddressBookHandle>,
+
+    /// Base path of the router.
+    base_path: PathBuf,
+
+    /// Handle to client tunnel manager.
+    client_tunnels: TunnelManagerHandle<ClientTunnelConfig>,
+
+    /// Currently applied configuration.
+    config: ReloadableConfig,
+
+    /// HTTP proxy options given as cli arguments.
+    http_options: HttpProxyOptions,
+
+    /// HTTP proxy task.
+    http_proxy_task: Option<JoinHandle<()>>,
+
+    /// RX channel for receiving reload requests.
+    reload_rx: mpsc::Receiver<oneshot::Sender<crate::Result<()>>>,
+
+    /// TX channel given to [`ReloadHandle`]s.
+    reload_tx: mpsc::Sender<oneshot::Sender<crate::Result<()>>>,
+
+    /// SAMv3 TCP port of the router.
+    sam_tcp_port: u16,
+
+    /// SAMv3 UDP port of the router, if datagrams are enabled.
+    sam_udp_port: Option<u16>,
+
+    /// Handle to server tunnel manager.
+    server_tunnels: TunnelManagerHandle<ServerTunnelConfig>,
+
+    /// SOCKS proxy options given as cli arguments.
+    socks_options: SocksProxyOptions,
+
+    /// SOCKS proxy task.
+    socks_proxy_task: Option<JoinHandle<()>>,
+}
+
+impl ConfigReloader {
+    /// Create new [`ConfigReloader`].
+    pub fn new(
+        base_path: PathBuf,
+        config: ReloadableConfig,
+        address_book: Option<AddressBookManager>,
+        sam_tcp_port: u16,
+        sam_udp_port: Option<u16>,
+        client_tunnels: TunnelManagerHandle<ClientTunnelConfig>,
+        server_tunnels: TunnelManagerHandle<ServerTunnelConfig>,
+        http_options: HttpProxyOptions,
+        socks_options: SocksProxyOptions,
+    ) -> Self {
+        let (reload_tx, reload_rx) = mpsc::channel(4);
+
+        Self {
+            address_book_handle: address_book.as_ref().map(|manager| manager.handle()),
+            address_book,
+            address_book_task: None,
+            base_path,
+            client_tunnels,
+            config,
+            http_options,
+            http_proxy_task: None,
+            reload_rx,
+            reload_tx,
+            sam_tcp_port,
+            sam_udp_port,
+            server_tunnels,
+            socks_options,
+            socks_proxy_task: None,
+        }
+    }
+
+    /// Get handle to [`ConfigReloader`].
+    pub fn handle(&self) -> ReloadHandle {
+        ReloadHandle {
+            tx: self.reload_tx.clone(),
+        }
+    }
+
+    /// Start the HTTP proxy, SOCKS proxy and address book, if they're enabled.
+    pub async fn start(&mut self) {
+        self.start_http_proxy().await;
+        self.start_socks_proxy().await;
+    }
+
+    /// Stop the address book, if it's running, and start it again with the current configuration.
+    ///
+    /// Address book downloads `hosts.txt` through the HTTP proxy so it's only started if the HTTP
+    /// proxy is enabled. Before the download starts, the address book waits on `ready_rx`.
+    ///
+    /// The running address book is signaled to shut down and awaited so that a write to disk in
+    /// progress isn't interrupted.
+    async fn start_address_book(&mut self, ready_rx: oneshot::Receiver<()>) {
+        if let Some((shutdown_tx, handle)) = self.address_book_task.take() {
+            let _ = shutdown_tx.send(());
+            let _ = handle.await;
+        }
+
+        let (Some(manager), Some(_), Some(http)) = (
+            &self.address_book,
+            &self.config.address_book,
+            &self.config.http_proxy,
+        ) else {
+            return;
+        };
+
+        let (shutdown_tx, shutdown_rx) = oneshot::channel();
+
+        self.address_book_task = Some((
+            shutdown_tx,
+            tokio::spawn(manager.clone().run(http.port, http.host.clone(), ready_rx, shutdown_rx)),
+        ));
+    }
+
+    /// Stop the HTTP proxy, if it's running, and start it again with the current configuration.
+    ///
+    /// As the address book depends on the HTTP proxy, it's restarted as well.
+    ///
+    /// The running proxy is awaited after it's aborted so that its port has been released before
+    /// the new proxy binds to it.
+    async fn start_http_proxy(&mut self) {
+        if let Some(handle) = self.http_proxy_task.take() {
+            handle.abort();
+            let _ = handle.await;
+        }
+
+        // if address book is enabled, create oneshot channel pair, pass the receiver to address
+        // book and sender to http proxy and once the http proxy is ready (its tunnel pool has
+        // been built), it'll signal the address book that it can start download hosts file(s)
+        let (http_proxy_ready_tx, http_proxy_ready_rx) = oneshot::channel();
+        self.start_address_book(http_proxy_ready_rx).await;
+
+        let Some(config) = self.config.http_proxy.clone() else {
+            return;
+        };
+        let http_proxy_ready_tx = self.address_book_task.is_some().then_some(http_proxy_ready_tx);
+        let address_book_handle = self.address_book_handle.clone();
+        let sam_tcp_port = self.sam_tcp_port;
+
+        self.http_proxy_task = Some(tokio::spawn(async move {
+            match HttpProxy::new(
+                config,
+                sam_tcp_port,
+                http_proxy_ready_tx,
+                address_book_handle,
+            )
+            .await
+            {
+                Ok(proxy) =>
+                    if let Err(error) = proxy.run().await {
+                        tracing::debug!(
+                            target: LOG_TARGET,
+                            ?error,
+                            "http proxy exited",
+                        );
+                    },
+                Err(error) => tracing::warn!(
+                    target: LOG_TARGET,
+                    ?error,
+                    "failed to start http proxy",
+                ),
+            }
+        }));
+    }
+
+    /// Stop the SOCKS proxy, if it's running, and start it again with the current configuration.
+    ///
+    /// The running proxy is awaited after it's aborted so that its port has been released before
+    /// the new proxy binds to it.
+    async fn start_socks_proxy(&mut self) {
+        if let Some(handle) = self.socks_proxy_task.take() {
+            handle.abort();
+            let _ = handle.await;
+        }
+
+        let Some(config) = self.config.socks_proxy.clone() else {
+            return;
+        };
+        let sam_tcp_port = self.sam_tcp_port;
+        let sam_udp_port = self.sam_udp_port;
+
+        self.socks_proxy_task = Some(tokio::spawn(async move {
+            match SocksProxy::new(config, sam_tcp_port, sam_udp_port).await {
+                Ok(proxy) =>
+                    if let Err(error) = proxy.run().await {
+                        tracing::debug!(
+                            target: LOG_TARGET,
+                            ?error,
+                            "socks proxy exited",
+                        );
+                    },
+                Err(error) => tracing::warn!(
+                    target: LOG_TARGET,
+                    ?error,
+                    "failed to start socks proxy",
+                ),
+            }
+        }));
+    }
+
+    /// Re-read `router.toml` and apply the changes.
+    ///
+    /// If the new configuration is invalid, the running configuration is left untouched.
+    async fn reload(&mut self) -> crate::Result<()> {
+        let config = Config::reload(&self.base_path, &self.http_options, &self.socks_options)?;
+
+        if config == self.config {
+            tracing::info!(
+                target: LOG_TARGET,
+                "router config reloaded, no changes",
+            );
+            return Ok(());
+        }
+
+        // client tunnels
+        let (removed, added) = diff_tunnels(
+            &self.config.client_tunnels,
+            &config.client_tunnels,
+            |tunnel| &tunnel.name,
+        );
+
+        for name in removed {
+            tracing::info!(
+                target: LOG_TARGET,
+                %name,
+                "stop client tunnel",
+            );
+
+            if let Err(error) = self.client_tunnels.remove(name.clone()).await {
+                tracing::warn!(
+                    target: LOG_TARGET,
+                    %name,
+                    ?error,
+                    "failed to stop client tunnel",
+                );
+            }
+        }
+
+        for tunnel in added {
+            let name = tunnel.name.clone();
+
+            if let Err(error) = self.client_tunnels.add(tunnel).await {
+                tracing::warn!(
+                    target: LOG_TARGET,
+                    %name,
+                    ?error,
+                    "failed to start client tunnel",
+                );
+            }
+        }
+
+        // server tunnels
+        let (removed, added) = diff_tunnels(
+            &self.config.server_tunnels,
+            &config.server_tunnels,
+            |tunnel| &tunnel.name,
+        );
+
+        for name in removed {
+            tracing::info!(
+                target: LOG_TARGET,
+                %name,
+                "stop server tunnel",
+            );
+
+            if let Err(error) = self.server_tunnels.remove(name.clone()).await {
+                tracing::warn!(
+                    target: LOG_TARGET,
+                    %name,
+                    ?error,
+                    "failed to stop server tunnel",
+                );
+            }
+        }
+
+        for tunnel in added {
+            let name = tunnel.name.clone();
+
+            if let Err(error) = self.server_tunnels.add(tunnel).await {
+                tracing::warn!(
+                    target: LOG_TARGET,
+                    %name,
+                    ?error,
+                    "failed to start server tunnel",
+                );
+            }
+        }
+
+        // address book
+        //
+        // the address book handle is given to the router when it's started so if address book
+        // wasn't enabled then, the router must be restarted for it to take effect
+        let address_book_changed = config.address_book != self.config.address_book;
+
+        match (&mut self.address_book, &config.address_book) {
+            (None, Some(_)) => tracing::warn!(
+                target: LOG_TARGET,
+                "address book was disabled at startup, restart the router to enable it",
+            ),
+            (Some(manager), Some(address_book)) if address_book_changed => {
+                *manager = manager.with_config(address_book.clone());
+            }
+            _ => {}
+        }
+
+        // http proxy and address book
+        let http_proxy_changed = config.http_proxy != self.config.http_proxy;
+        let socks_proxy_changed = config.socks_proxy != self.config.socks_proxy;
+
+        self.config = config;
+
+        if http_proxy_changed {
+            tracing::info!(
+                target: LOG_TARGET,
+                config = ?self.config.http_proxy,
+                "restart http proxy",
+            );
+
+            self.start_http_proxy().await;
+        } else if address_book_changed {
+            tracing::info!(
+                target: LOG_TARGET,
+                config = ?self.config.address_book,
+                "restart address book",
+            );
+
+            // http proxy is already running so the address book can start immediately
+            let (tx, rx) = oneshot::channel();
+            let _ = tx.send(());
+
+            self.start_address_book(rx).await;
+        }
+
+        // socks proxy
+        if socks_proxy_changed {
+            tracing::info!(
+                target: LOG_TARGET,
+                config = ?self.config.socks_proxy,
+                "restart socks proxy",
+            );
+
+            self.start_socks_proxy().await;
+        }
+
+        tracing::info!(
+            target: LOG_TARGET,
+            "router config reloaded",
+        );
+
+        Ok(())
+    }
+
+    /// Run the event loop of [`ConfigReloader`].
+    ///
+    /// On Unix, `router.toml` is reloaded when the process receives `SIGHUP`.
+    pub async fn run(mut self) {
+        #[cfg(unix)]
+        let mut hangup = match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+        {
+            Ok(signal) => Some(signal),
+            Err(error) => {
+                tracing::warn!(
+                    target: LOG_TARGET,
+                    ?error,
+                    "failed to install sighup handler",
+                );
+                None
+            }
+        };
+
+        loop {
+            #[cfg(unix)]
+            let sighup = async {
+                match hangup.as_mut() {
+                    Some(signal) => {
+                        signal.recv().await;
+                    }
+                    None => std::future::pending().await,
+                }
+            };
+            #[cfg(not(unix))]
+            let sighup = std::future::pending::<()>();
+
+            tokio::select! {
+                _ = sighup => {
+                    tracing::info!(
+                        target: LOG_TARGET,
+                        "sighup received, reloading router config",
+                    );
+
+                    if let Err(error) = self.reload().await {
+                        tracing::warn!(
+                            target: LOG_TARGET,
+                            ?error,
+                            "failed to reload router config",
+                        );
+                    }
+                }
+                tx = self.reload_rx.recv() => match tx {
+                    Some(tx) => {
+                        let _ = tx.send(self.reload().await);
+                    }
+                    None => return,
+                },
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client_tunnel(name: &str, port: u16) -> ClientTunnelConfig {
+        ClientTunnelConfig {
+            name: name.to_string(),
+            address: None,
+            port,
+            destination: String::from("host.i2p"),
+            destination_port: None,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn diff_unchanged_tunnels() {
+        let tunnels = vec![client_tunnel("irc", 6668), client_tunnel("git", 2222)];
+        let (removed, added) = diff_tunnels(&tunnels, &tunnels, |tunnel| &tunnel.name);
+
+        assert!(removed.is_empty());
+        assert!(added.is_empty());
+    }
+
+    #[test]
+    fn diff_added_removed_and_changed_tunnels() {
+        let old = vec![
+            client_tunnel("irc", 6668),
+            client_tunnel("git", 2222),
+            client_tunnel("mail", 7659),
+        ];
+        let new = vec![
+            client_tunnel("irc", 6668),
+            client_tunnel("git", 2223),
+            client_tunnel("news", 8080),
+        ];
+        let (mut removed, added) = diff_tunnels(&old, &new, |tunnel| &tunnel.name);
+        removed.sort();
+
+        assert_eq!(removed, vec![String::from("git"), String::from("mail")]);
+        assert_eq!(
+            added,
+            vec![client_tunnel("git", 2223), client_tunnel("news", 8080)]
+        );
+    }
+}
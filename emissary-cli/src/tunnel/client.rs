@@ -16,12 +16,22 @@
 // FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
 // DEALINGS IN THE SOFTWARE.
 
-use crate::config::ClientTunnelConfig;
+use crate::{
+    config::{ClientTunnelConfig, ClientTunnelKind},
+    error::Error,
+    tunnel::{
+        irc, load_or_create_destination, session_options, TunnelCommand, TunnelManagerHandle,
+    },
+};
 
-use tokio::{net::TcpListener, task::JoinSet};
-use yosemite::{style, Session, SessionOptions, StreamOptions};
+use tokio::{
+    net::TcpListener,
+    sync::{mpsc, oneshot},
+    task::{AbortHandle, JoinSet},
+};
+use yosemite::{style, DestinationKind, RouterApi, Session, SessionOptions, StreamOptions};
 
-use std::{future::Future, sync::Arc, time::Duration};
+use std::{collections::HashMap, future::Future, path::PathBuf, sync::Arc, time::Duration};
 
 /// Logging target for the file.
 const LOG_TARGET: &str = "emissary::client-tunnel";
@@ -29,28 +39,116 @@ const LOG_TARGET: &str = "emissary::client-tunnel";
 /// Retry timeout.
 const RETRY_TIMEOUT: Duration = Duration::from_secs(15);
 
+/// Nickname of the session shared by client tunnels.
+const SHARED_SESSION_NICKNAME: &str = "i2p-tunnel";
+
+/// Prefix of the nicknames of client tunnel sessions.
+///
+/// Keeps the nicknames distinct from the nicknames of server tunnel sessions.
+const SESSION_NICKNAME_PREFIX: &str = "client-";
+
+/// Destination of a client tunnel.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum SessionKey {
+    /// Transient destination shared by all client tunnels which don't specify otherwise.
+    Shared,
+
+    /// Persistent destination loaded from a key file, shared by all client tunnels which use the
+    /// same key file.
+    Keys(String),
+
+    /// Transient destination dedicated to one client tunnel.
+    Dedicated(String),
+}
+
+impl SessionKey {
+    /// Get the key of the session `config` uses.
+    fn new(config: &ClientTunnelConfig) -> Self {
+        match (&config.keys, config.shared) {
+            (Some(keys), _) => Self::Keys(keys.clone()),
+            (None, Some(false)) => Self::Dedicated(config.name.clone()),
+            (None, _) => Self::Shared,
+        }
+    }
+
+    /// Get the SAMv3 nickname of the session, created for `config`.
+    fn nickname(&self, config: &ClientTunnelConfig) -> String {
+        match self {
+            Self::Shared => SHARED_SESSION_NICKNAME.to_string(),
+            Self::Keys(_) | Self::Dedicated(_) =>
+                format!("{SESSION_NICKNAME_PREFIX}{}", config.name),
+        }
+    }
+}
+
+/// Client tunnel waiting for its SAMv3 session to be created.
+struct PendingTunnel {
+    /// Tunnel configuration.
+    config: Arc<ClientTunnelConfig>,
+
+    /// Oneshot sender for the result, if the tunnel was added over a [`TunnelManagerHandle`].
+    tx: Option<oneshot::Sender<crate::Result<()>>>,
+}
+
 /// Client tunnel manager.
 pub struct ClientTunnelManager {
+    /// Base path of the router.
+    base_path: PathBuf,
+
+    /// RX channel for receiving commands from [`TunnelManagerHandle`]s.
+    command_rx: mpsc::Receiver<TunnelCommand<ClientTunnelConfig>>,
+
     /// Tunnel futures.
     futures: JoinSet<Arc<ClientTunnelConfig>>,
 
+    /// Handle given to the control interface.
+    handle: TunnelManagerHandle<ClientTunnelConfig>,
+
+    /// Client tunnel configurations from router config.
+    pending: Vec<Arc<ClientTunnelConfig>>,
+
+    /// Client tunnels waiting for their session to be created.
+    pending_sessions: HashMap<SessionKey, Vec<PendingTunnel>>,
+
     /// SAMv3 server port of the router.
     sam_tcp_port: u16,
 
-    /// Client tunnel configurations.
-    tunnels: Vec<Arc<ClientTunnelConfig>>,
+    /// Pending SAMv3 sessions.
+    session_futures: JoinSet<(SessionKey, crate::Result<Session<style::Stream>>)>,
+
+    /// Active SAMv3 sessions.
+    ///
+    /// A session is created when the first client tunnel using it is started.
+    sessions: HashMap<SessionKey, Session<style::Stream>>,
+
+    /// Active client tunnels, indexed by name.
+    tunnels: HashMap<String, (Arc<ClientTunnelConfig>, AbortHandle)>,
 }
 
 impl ClientTunnelManager {
     /// Create new [`ClientTunnelManager`].
-    pub fn new(tunnels: Vec<ClientTunnelConfig>, sam_tcp_port: u16) -> Self {
+    pub fn new(tunnels: Vec<ClientTunnelConfig>, sam_tcp_port: u16, base_path: PathBuf) -> Self {
+        let (handle, command_rx) = TunnelManagerHandle::new();
+
         Self {
+            base_path,
+            command_rx,
             futures: JoinSet::new(),
+            handle,
+            pending: tunnels.into_iter().map(Arc::from).collect(),
+            pending_sessions: HashMap::new(),
             sam_tcp_port,
-            tunnels: tunnels.into_iter().map(Arc::from).collect(),
+            session_futures: JoinSet::new(),
+            sessions: HashMap::new(),
+            tunnels: HashMap::new(),
         }
     }
 
+    /// Get handle to [`ClientTunnelManager`].
+    pub fn handle(&self) -> TunnelManagerHandle<ClientTunnelConfig> {
+        self.handle.clone()
+    }
+
     /// Run the event loop of a client tunnel.
     async fn tunnel_event_loop(
         future: impl Future<Output = yosemite::Result<yosemite::Stream>>,
@@ -66,112 +164,405 @@ impl ClientTunnelManager {
         let (mut tcp_stream, _) = listener.accept().await?;
         let mut i2p_stream = future.await?;
 
-        tokio::io::copy_bidirectional(&mut i2p_stream, &mut tcp_stream).await?;
+        match tunnel.kind.unwrap_or_default() {
+            ClientTunnelKind::Client => {
+                tokio::io::copy_bidirectional(&mut i2p_stream, &mut tcp_stream).await?;
+            }
+            ClientTunnelKind::Irc => irc::relay(tcp_stream, i2p_stream).await?,
+        }
 
         Ok(())
     }
 
-    /// Run the event loop of [`ClientTunnelManger`].
+    /// Create the SAMv3 session identified by `key` for `tunnel`.
     ///
-    /// If there are no client tunnels congigured, [`ClientTunnelManager`] exits immediately.
-    pub async fn run(mut self) {
-        if self.tunnels.is_empty() {
+    /// If the session is shared, tunnel options of the session are taken from the client tunnel
+    /// which created it.
+    async fn create_session(
+        key: &SessionKey,
+        tunnel: &ClientTunnelConfig,
+        sam_tcp_port: u16,
+        base_path: PathBuf,
+    ) -> crate::Result<Session<style::Stream>> {
+        let destination = match key {
+            SessionKey::Shared | SessionKey::Dedicated(_) => DestinationKind::Transient,
+            SessionKey::Keys(keys) => {
+                let mut router_api = RouterApi::new(sam_tcp_port);
+                let private_key = load_or_create_destination(&mut router_api, base_path.join(keys))
+                    .await
+                    .ok_or_else(|| {
+                        Error::Custom(format!("failed to load or create destination `{keys}`"))
+                    })?;
+
+                DestinationKind::Persistent { private_key }
+            }
+        };
+
+        Session::<style::Stream>::new(session_options(
+            SessionOptions {
+                publish: false,
+                samv3_tcp_port: sam_tcp_port,
+                nickname: key.nickname(tunnel),
+                destination,
+                num_inbound: 4,
+                num_outbound: 4,
+                ..Default::default()
+            },
+            &tunnel.inbound.clone().unwrap_or_default(),
+            &tunnel.outbound.clone().unwrap_or_default(),
+        ))
+        .await
+        .map_err(From::from)
+    }
+
+    /// Start `tunnel` and send the result to `tx`, if specified.
+    ///
+    /// If the SAMv3 session used by `tunnel` doesn't exist yet, it's created in the background and
+    /// the tunnel is started once the session is ready.
+    fn start_tunnel(
+        &mut self,
+        tunnel: Arc<ClientTunnelConfig>,
+        tx: Option<oneshot::Sender<crate::Result<()>>>,
+    ) {
+        let key = SessionKey::new(&tunnel);
+
+        if self.sessions.contains_key(&key) {
+            self.spawn_tunnel(tunnel);
+
+            if let Some(tx) = tx {
+                let _ = tx.send(Ok(()));
+            }
             return;
         }
 
-        tracing::info!(
-            target: LOG_TARGET,
-            num_tunnels = ?self.tunnels.len(),
-            "starting client tunnel manager",
-        );
+        let pending = PendingTunnel {
+            config: Arc::clone(&tunnel),
+            tx,
+        };
 
-        let mut session = match Session::<style::Stream>::new(SessionOptions {
-            publish: false,
-            samv3_tcp_port: self.sam_tcp_port,
-            nickname: "i2p-tunnel".to_string(),
-            num_inbound: 4,
-            num_outbound: 4,
-            ..Default::default()
-        })
-        .await
-        {
-            Ok(session) => session,
+        if let Some(tunnels) = self.pending_sessions.get_mut(&key) {
+            return tunnels.push(pending);
+        }
+
+        let sam_tcp_port = self.sam_tcp_port;
+        let base_path = self.base_path.clone();
+
+        self.pending_sessions.insert(key.clone(), vec![pending]);
+        self.session_futures.spawn(async move {
+            let result = Self::create_session(&key, &tunnel, sam_tcp_port, base_path).await;
+
+            (key, result)
+        });
+    }
+
+    /// Handle the result of creating the session identified by `key`.
+    ///
+    /// If the session was created, the client tunnels waiting for it are started.
+    fn on_session_created(
+        &mut self,
+        key: SessionKey,
+        result: crate::Result<Session<style::Stream>>,
+    ) {
+        let tunnels = self.pending_sessions.remove(&key).unwrap_or_default();
+
+        match result {
+            Ok(session) => {
+                tracing::debug!(
+                    target: LOG_TARGET,
+                    ?key,
+                    tunnels = ?tunnels.iter().map(|tunnel| &tunnel.config.name).collect::<Vec<_>>(),
+                    "session created for client tunnels",
+                );
+
+                // all tunnels of a dedicated session may have been removed while it was created
+                if tunnels.is_empty() && key != SessionKey::Shared {
+                    return;
+                }
+                self.sessions.insert(key, session);
+
+                for PendingTunnel { config, tx } in tunnels {
+                    self.spawn_tunnel(config);
+
+                    if let Some(tx) = tx {
+                        let _ = tx.send(Ok(()));
+                    }
+                }
+            }
             Err(error) => {
                 tracing::error!(
                     target: LOG_TARGET,
+                    ?key,
                     ?error,
-                    "failed to start client tunnel manager",
+                    "failed to start client tunnels",
                 );
-                return;
-            }
-        };
 
-        for tunnel in self.tunnels.iter().cloned() {
-            let future = session.connect_detached_with_options(
-                &tunnel.destination,
-                StreamOptions {
-                    dst_port: tunnel.destination_port.unwrap_or(0),
-                    ..Default::default()
-                },
-            );
-
-            self.futures.spawn(async move {
-                match Self::tunnel_event_loop(future, &tunnel).await {
-                    Ok(()) => tunnel,
-                    Err(error) => {
-                        tracing::debug!(
-                            target: LOG_TARGET,
-                            name = %tunnel.name,
-                            ?error,
-                            "client tunnel exited with error",
-                        );
-
-                        tokio::time::sleep(RETRY_TIMEOUT).await;
-                        tunnel
+                for PendingTunnel { config, tx } in tunnels {
+                    if let Some(tx) = tx {
+                        let _ = tx.send(Err(Error::Custom(format!(
+                            "failed to create session for `{}`: {error}",
+                            config.name
+                        ))));
                     }
                 }
-            });
+            }
         }
+    }
 
-        while let Some(result) = self.futures.join_next().await {
-            match result {
+    /// Start the event loop of `tunnel`.
+    ///
+    /// The SAMv3 session of the tunnel must have been created before calling this function.
+    fn spawn_tunnel(&mut self, tunnel: Arc<ClientTunnelConfig>) {
+        let session = self.sessions.get_mut(&SessionKey::new(&tunnel)).expect("session to exist");
+        let future = session.connect_detached_with_options(
+            &tunnel.destination,
+            StreamOptions {
+                dst_port: tunnel.destination_port.unwrap_or(0),
+                ..Default::default()
+            },
+        );
+        let name = tunnel.name.clone();
+        let config = Arc::clone(&tunnel);
+
+        let handle = self.futures.spawn(async move {
+            match Self::tunnel_event_loop(future, &tunnel).await {
+                Ok(()) => tunnel,
                 Err(error) => {
-                    tracing::warn!(
+                    tracing::debug!(
                         target: LOG_TARGET,
+                        name = %tunnel.name,
                         ?error,
-                        "client tunnel panicked, unable to restart",
+                        "client tunnel exited with error",
                     );
-                    debug_assert!(false);
+
+                    tokio::time::sleep(RETRY_TIMEOUT).await;
+                    tunnel
                 }
-                Ok(tunnel) => {
-                    tracing::error!(target: LOG_TARGET, "tunnel returned, restart event loop");
-
-                    let future = session.connect_detached_with_options(
-                        &tunnel.destination,
-                        StreamOptions {
-                            dst_port: tunnel.destination_port.unwrap_or(0),
-                            ..Default::default()
-                        },
-                    );
+            }
+        });
+
+        self.tunnels.insert(name, (config, handle));
+    }
+
+    /// Handle `command` received from a [`TunnelManagerHandle`].
+    fn on_command(&mut self, command: TunnelCommand<ClientTunnelConfig>) {
+        match command {
+            TunnelCommand::Add { config, tx } => {
+                if self.tunnels.contains_key(&config.name) || self.is_pending(&config.name) {
+                    let _ = tx.send(Err(Error::Custom(format!(
+                        "client tunnel `{}` already exists",
+                        config.name
+                    ))));
+                    return;
+                }
+
+                tracing::info!(
+                    target: LOG_TARGET,
+                    name = %config.name,
+                    "add client tunnel",
+                );
+
+                self.start_tunnel(Arc::new(config), Some(tx));
+            }
+            TunnelCommand::Remove { name, tx } if self.is_pending(&name) => {
+                tracing::info!(
+                    target: LOG_TARGET,
+                    %name,
+                    "remove client tunnel",
+                );
+
+                for tunnels in self.pending_sessions.values_mut() {
+                    tunnels.retain_mut(|tunnel| {
+                        if tunnel.config.name != name {
+                            return true;
+                        }
 
-                    self.futures.spawn(async move {
-                        match Self::tunnel_event_loop(future, &tunnel).await {
-                            Ok(()) => tunnel,
-                            Err(error) => {
-                                tracing::debug!(
-                                    target: LOG_TARGET,
-                                    name = %tunnel.name,
-                                    ?error,
-                                    "client tunnel exited with error",
-                                );
-
-                                tokio::time::sleep(RETRY_TIMEOUT).await;
-                                tunnel
-                            }
+                        if let Some(tx) = tunnel.tx.take() {
+                            let _ = tx.send(Err(Error::Custom(format!(
+                                "client tunnel `{name}` was removed"
+                            ))));
                         }
+                        false
                     });
                 }
+
+                let _ = tx.send(Ok(()));
+            }
+            TunnelCommand::Remove { name, tx } => match self.tunnels.remove(&name) {
+                Some((config, handle)) => {
+                    tracing::info!(
+                        target: LOG_TARGET,
+                        %name,
+                        "remove client tunnel",
+                    );
+
+                    handle.abort();
+
+                    // destroy the session if it's not shared with other client tunnels
+                    let key = SessionKey::new(&config);
+
+                    if key != SessionKey::Shared
+                        && !self.tunnels.values().any(|(config, _)| SessionKey::new(config) == key)
+                    {
+                        self.sessions.remove(&key);
+                    }
+
+                    let _ = tx.send(Ok(()));
+                }
+                None => {
+                    let _ = tx.send(Err(Error::Custom(format!(
+                        "client tunnel `{name}` doesn't exist"
+                    ))));
+                }
+            },
+            TunnelCommand::List { tx } => {
+                let _ = tx.send(
+                    self.tunnels
+                        .values()
+                        .map(|(config, _)| config)
+                        .chain(
+                            self.pending_sessions.values().flatten().map(|tunnel| &tunnel.config),
+                        )
+                        .map(|config| config.as_ref().clone())
+                        .collect(),
+                );
             }
         }
     }
+
+    /// Is the client tunnel `name` waiting for its session to be created.
+    fn is_pending(&self, name: &str) -> bool {
+        self.pending_sessions
+            .values()
+            .flatten()
+            .any(|tunnel| tunnel.config.name == name)
+    }
+
+    /// Run the event loop of [`ClientTunnelManger`].
+    ///
+    /// Client tunnels from router config are started first, after which the manager keeps
+    /// restarting them and serving commands received from the control interface.
+    pub async fn run(mut self) {
+        if !self.pending.is_empty() {
+            tracing::info!(
+                target: LOG_TARGET,
+                num_tunnels = ?self.pending.len(),
+                "starting client tunnel manager",
+            );
+
+            for tunnel in std::mem::take(&mut self.pending) {
+                self.start_tunnel(tunnel, None);
+            }
+        }
+
+        loop {
+            tokio::select! {
+                result = self.futures.join_next(), if !self.futures.is_empty() => match result {
+                    Some(Err(error)) if error.is_cancelled() => {}
+                    Some(Err(error)) => {
+                        tracing::warn!(
+                            target: LOG_TARGET,
+                            ?error,
+                            "client tunnel panicked, unable to restart",
+                        );
+                        debug_assert!(false);
+                    }
+                    Some(Ok(tunnel)) => {
+                        // tunnel may have been removed or replaced while it was exiting
+                        let is_active = self
+                            .tunnels
+                            .get(&tunnel.name)
+                            .is_some_and(|(config, _)| Arc::ptr_eq(config, &tunnel));
+
+                        if is_active {
+                            tracing::debug!(
+                                target: LOG_TARGET,
+                                name = %tunnel.name,
+                                "tunnel returned, restart event loop",
+                            );
+                            self.spawn_tunnel(tunnel);
+                        }
+                    }
+                    None => {}
+                },
+                result = self.session_futures.join_next(), if !self.session_futures.is_empty() =>
+                    match result {
+                        Some(Ok((key, result))) => self.on_session_created(key, result),
+                        Some(Err(error)) => tracing::warn!(
+                            target: LOG_TARGET,
+                            ?error,
+                            "failed to create session",
+                        ),
+                        None => {}
+                    },
+                command = self.command_rx.recv() => match command {
+                    Some(command) => self.on_command(command),
+                    None => return,
+                },
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn session_key() {
+        let config = ClientTunnelConfig {
+            name: "irc".to_string(),
+            port: 6668,
+            destination: "irc.postman.i2p".to_string(),
+            ..Default::default()
+        };
+
+        assert_eq!(SessionKey::new(&config), SessionKey::Shared);
+        assert_eq!(
+            SessionKey::new(&ClientTunnelConfig {
+                shared: Some(true),
+                ..config.clone()
+            }),
+            SessionKey::Shared
+        );
+        assert_eq!(
+            SessionKey::new(&ClientTunnelConfig {
+                shared: Some(false),
+                ..config.clone()
+            }),
+            SessionKey::Dedicated("irc".to_string())
+        );
+
+        // key file takes precedence over `shared`
+        assert_eq!(
+            SessionKey::new(&ClientTunnelConfig {
+                keys: Some("irc.b64".to_string()),
+                shared: Some(false),
+                ..config
+            }),
+            SessionKey::Keys("irc.b64".to_string())
+        );
+    }
+
+    #[test]
+    fn session_nickname() {
+        let config = ClientTunnelConfig {
+            name: "irc".to_string(),
+            port: 6668,
+            destination: "irc.postman.i2p".to_string(),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            SessionKey::Shared.nickname(&config),
+            SHARED_SESSION_NICKNAME
+        );
+        assert_eq!(
+            SessionKey::Dedicated("irc".to_string()).nickname(&config),
+            "client-irc"
+        );
+        assert_eq!(
+            SessionKey::Keys("irc.b64".to_string()).nickname(&config),
+            "client-irc"
+        );
+    }
 }
@@ -0,0 +1,349 @@
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! HTTP server tunnel.
+//!
+//! Inbound streams are accepted from the session and the request headers are rewritten before
+//! the request is relayed to the local server:
+//!  * `X-I2P-DestB64`, `X-I2P-DestB32` and `X-I2P-DestHash` are set to the destination of the
+//!    client and all `X-I2P-*` headers sent by the client, including ones spelled with underscores,
+//!    are removed
+//!  * `Host` is rewritten to the address of the local server
+//!  * `Connection` is set to `close` so every request is made over a new stream and has its headers
+//!    rewritten
+//!
+//! Only the first request of a stream, including its body, is relayed to the local server. Any
+//! bytes the client sends after it, such as a pipelined request, are discarded so that they cannot
+//! reach the server without having their headers rewritten.
+
+use crate::{
+    error::Error,
+    proxy::http::{
+        error::HttpError,
+        message::{request_body, Body, BufferedStream},
+    },
+};
+
+use emissary_core::{
+    crypto::{base32_encode, base64_decode, base64_encode},
+    primitives::Destination,
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, AsyncWriteExt},
+    net::TcpStream,
+};
+
+/// Logging target for the file.
+const LOG_TARGET: &str = "emissary::server-tunnel::http";
+
+/// Headers removed from the request before it's relayed to the local server.
+///
+/// In addition to these, all headers starting with [`I2P_HEADER_PREFIX`] are removed.
+const FILTERED_HEADERS: [&str; 2] = ["connection", "host"];
+
+/// Prefix of the headers set by the server tunnel.
+const I2P_HEADER_PREFIX: &str = "x-i2p-";
+
+/// Destination headers injected into the request.
+#[derive(Debug)]
+pub struct DestinationHeaders {
+    /// Base32 address of the destination.
+    b32: String,
+
+    /// Base64 of the destination.
+    b64: String,
+
+    /// Base64 of the destination hash.
+    hash: String,
+}
+
+impl DestinationHeaders {
+    /// Create new [`DestinationHeaders`] from base64-encoded `destination`.
+    ///
+    /// Returns `None` if `destination` is not a valid destination.
+    pub fn new(destination: &str) -> Option<Self> {
+        let id = Destination::parse(base64_decode(destination)?)?.id().to_vec();
+
+        Some(Self {
+            b32: format!("{}.b32.i2p", base32_encode(&id)),
+            b64: destination.to_owned(),
+            hash: base64_encode(&id),
+        })
+    }
+
+    /// Get `.b32.i2p` address of the destination.
+    pub fn b32(&self) -> &str {
+        &self.b32
+    }
+}
+
+/// Should `name` be removed from the request.
+///
+/// Names are compared case-insensitively and with underscores treated as dashes, since some servers
+/// and frameworks normalize `X_I2P_DestB64` into `X-I2P-DestB64`.
+fn is_filtered(name: &str) -> bool {
+    let name = name.to_ascii_lowercase().replace('_', "-");
+
+    name.starts_with(I2P_HEADER_PREFIX) || FILTERED_HEADERS.contains(&name.as_str())
+}
+
+/// Rewrite the headers of `request` which must contain a complete request head.
+///
+/// Returns the rewritten request head and the body of the request.
+pub fn rewrite_request(
+    request: &[u8],
+    destination: &DestinationHeaders,
+    host: &str,
+) -> crate::Result<(Vec<u8>, Body)> {
+    let mut headers = [httparse::EMPTY_HEADER; 64];
+    let mut req = httparse::Request::new(&mut headers);
+
+    let httparse::Status::Complete(_) = req.parse(request).map_err(|_| Error::InvalidData)? else {
+        return Err(Error::InvalidData);
+    };
+
+    let (Some(method), Some(path), Some(version)) = (req.method, req.path, req.version) else {
+        return Err(Error::InvalidData);
+    };
+
+    let mut out = format!("{method} {path} HTTP/1.{version}\r\n").into_bytes();
+
+    let body = request_body(req.headers).map_err(|_| Error::InvalidData)?;
+
+    for header in req.headers.iter() {
+        if is_filtered(header.name) {
+            continue;
+        }
+
+        out.extend_from_slice(header.name.as_bytes());
+        out.extend_from_slice(b": ");
+        out.extend_from_slice(header.value);
+        out.extend_from_slice(b"\r\n");
+    }
+
+    out.extend_from_slice(
+        format!(
+            "Host: {host}\r\n\
+            Connection: close\r\n\
+            X-I2P-DestB64: {}\r\n\
+            X-I2P-DestB32: {}\r\n\
+            X-I2P-DestHash: {}\r\n\r\n",
+            destination.b64, destination.b32, destination.hash,
+        )
+        .as_bytes(),
+    );
+
+    Ok((out, body))
+}
+
+/// Handle inbound `stream` from `destination` by rewriting its request and relaying it to the
+/// server at `host`.
+///
+/// After the request and its body have been relayed, only the response is relayed back to
+/// `stream`.
+pub async fn handle_stream<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: S,
+    destination: DestinationHeaders,
+    host: String,
+) -> crate::Result<()> {
+    let mut stream = BufferedStream::new(stream);
+
+    let head = match stream.read_request_head().await {
+        Ok(head) => head,
+        Err(HttpError::Io(kind)) => return Err(Error::Io(kind.into())),
+        Err(_) => {
+            tracing::debug!(
+                target: LOG_TARGET,
+                destination = %destination.b32,
+                "malformed request",
+            );
+
+            stream
+                .get_mut()
+                .write_all(b"HTTP/1.1 400 Bad Request\r\nConnection: close\r\n\r\n")
+                .await?;
+            return Err(Error::InvalidData);
+        }
+    };
+
+    let (request, body) = rewrite_request(&head, &destination, &host)?;
+
+    tracing::trace!(
+        target: LOG_TARGET,
+        destination = %destination.b32,
+        ?body,
+        "relay request to server",
+    );
+
+    let mut server = TcpStream::connect(&host).await?;
+    server.write_all(&request).await?;
+    stream.relay_body(&mut server, body).await?;
+
+    // the request has been relayed in full, discard anything else the client sends
+    let (mut stream, _) = stream.into_parts();
+    tokio::io::copy(&mut server, &mut stream).await?;
+    stream.shutdown().await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use emissary_core::crypto::base32_decode;
+    use tokio::io::AsyncReadExt;
+
+    const DESTINATION: &str = "a11l91etedRW5Kl2GhdDI9qiRBbDRAQY6TWJb8KlSc0P9WUrEviABAAltqDU1DFJrRhMAZg5i6rWGszkJrF-pWLQK9JOH33l4~mQjB8Hkt83l9qnNJPUlGlh9yIfBY40CQ0Ermy8gzjHLayUpypDJFv2V6rHLwxAQeaXJu8YXbyvCucEu9i6HVO49akXW9YSxcZEqxK04wZnjBqhHGlVbehleMqTx9nkd0pUpBZz~vIaG9matUSHinopEo6Wegml9FEz~FEaQpPknKuMAGGSNFVJb0NtaOQSAocAOg1nLKh80v232Y8sJOHG63asSJoBa6bGwjIHftsqD~lEmVV4NkgNPybmvsD1SCbMQ2ExaCXFPVQV-yJhIAPN9MRVT9cSBT2GCq-vpMwdJ5Nf0iPR3M-Ak961JUwWXPYTL79toXCgxDX2~nZ5QFRV490YNnfB7LQu10G89wG8lzS9GWf2i-nk~~ez0Lq0dH7qQokFXdUkPc7bvSrxqkytrbd-h8O8AAAA";
+
+    #[test]
+    fn destination_headers() {
+        let headers = DestinationHeaders::new(DESTINATION).unwrap();
+
+        assert_eq!(headers.b64, DESTINATION);
+        assert!(headers.b32.ends_with(".b32.i2p"));
+        assert_eq!(headers.b32.len(), 52 + 8);
+        assert_eq!(
+            base64_decode(&headers.hash),
+            base32_decode(headers.b32.strip_suffix(".b32.i2p").unwrap()),
+        );
+    }
+
+    #[test]
+    fn invalid_destination() {
+        assert!(DestinationHeaders::new("hello, world").is_none());
+        assert!(DestinationHeaders::new(&base64_encode(vec![0u8; 16])).is_none());
+    }
+
+    #[test]
+    fn headers_injected() {
+        let headers = DestinationHeaders::new(DESTINATION).unwrap();
+        let request = "GET /index.html HTTP/1.1\r\n\
+            Host: host.i2p\r\n\
+            User-Agent: test\r\n\
+            Connection: keep-alive\r\n\r\n";
+
+        let (rewritten, body) =
+            rewrite_request(request.as_bytes(), &headers, "127.0.0.1:8080").unwrap();
+        let rewritten = std::str::from_utf8(&rewritten).unwrap();
+
+        assert_eq!(body, Body::None);
+        assert!(rewritten.starts_with("GET /index.html HTTP/1.1\r\n"));
+        assert!(rewritten.contains("User-Agent: test\r\n"));
+        assert!(rewritten.contains("Host: 127.0.0.1:8080\r\n"));
+        assert!(rewritten.contains("Connection: close\r\n"));
+        assert!(!rewritten.contains("host.i2p"));
+        assert!(!rewritten.contains("keep-alive"));
+        assert!(rewritten.contains(&format!("X-I2P-DestB64: {DESTINATION}\r\n")));
+        assert!(rewritten.contains(&format!("X-I2P-DestB32: {}\r\n", headers.b32)));
+        assert!(rewritten.contains(&format!("X-I2P-DestHash: {}\r\n", headers.hash)));
+        assert!(rewritten.ends_with("\r\n\r\n"));
+    }
+
+    #[test]
+    fn spoofed_headers_removed() {
+        let headers = DestinationHeaders::new(DESTINATION).unwrap();
+        let request = "POST /login HTTP/1.1\r\n\
+            Host: host.i2p\r\n\
+            x-i2p-destb64: spoofed\r\n\
+            X-I2P-DESTB32: spoofed.b32.i2p\r\n\
+            X-I2P-DestHash: spoofed\r\n\
+            X_I2P_DestB64: spoofed\r\n\
+            x_i2p-destb32: spoofed.b32.i2p\r\n\
+            X-I2P-Other: spoofed\r\n\
+            Content-Length: 5\r\n\r\n\
+            hello";
+
+        let (rewritten, body) =
+            rewrite_request(request.as_bytes(), &headers, "127.0.0.1:8080").unwrap();
+        let rewritten = std::str::from_utf8(&rewritten).unwrap();
+
+        assert_eq!(body, Body::Length(5));
+        assert!(!rewritten.contains("spoofed"));
+        assert!(rewritten.contains("Content-Length: 5\r\n"));
+        assert_eq!(rewritten.matches("X-I2P-DestB64").count(), 1);
+        assert_eq!(rewritten.matches("X-I2P-DestB32").count(), 1);
+        assert_eq!(rewritten.matches("X-I2P-DestHash").count(), 1);
+    }
+
+    #[test]
+    fn partial_request_rejected() {
+        let headers = DestinationHeaders::new(DESTINATION).unwrap();
+
+        assert!(rewrite_request(b"GET / HTTP/1.1\r\nHost: ", &headers, "127.0.0.1:80").is_err());
+        assert!(rewrite_request(b"hello, world\r\n\r\n", &headers, "127.0.0.1:80").is_err());
+    }
+
+    #[tokio::test]
+    async fn pipelined_request_not_relayed() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let host = listener.local_addr().unwrap().to_string();
+
+        // local server which reads the request and responds once its body has been received
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut received = Vec::new();
+            let mut buffer = [0u8; 1024];
+
+            while !received.ends_with(b"hello") {
+                let nread = stream.read(&mut buffer).await.unwrap();
+                assert_ne!(nread, 0);
+                received.extend_from_slice(&buffer[..nread]);
+            }
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok")
+                .await
+                .unwrap();
+            stream.shutdown().await.unwrap();
+
+            // read until the tunnel closes the connection
+            loop {
+                match stream.read(&mut buffer).await.unwrap() {
+                    0 => break received,
+                    nread => received.extend_from_slice(&buffer[..nread]),
+                }
+            }
+        });
+
+        let (mut client, stream) = tokio::io::duplex(4096);
+        let handle = tokio::spawn(handle_stream(
+            stream,
+            DestinationHeaders::new(DESTINATION).unwrap(),
+            host,
+        ));
+
+        client
+            .write_all(
+                b"POST /login HTTP/1.1\r\nHost: host.i2p\r\nContent-Length: 5\r\n\r\nhello\
+                GET /admin HTTP/1.1\r\nHost: host.i2p\r\nX-I2P-DestB32: spoofed.b32.i2p\r\n\r\n",
+            )
+            .await
+            .unwrap();
+
+        let mut response = Vec::new();
+        client.read_to_end(&mut response).await.unwrap();
+        assert!(response.ends_with(b"ok"));
+
+        handle.await.unwrap().unwrap();
+        let received = String::from_utf8(server.await.unwrap()).unwrap();
+
+        assert!(received.starts_with("POST /login HTTP/1.1\r\n"));
+        assert!(received.ends_with("\r\n\r\nhello"));
+        assert!(!received.contains("/admin"));
+        assert!(!received.contains("spoofed"));
+    }
+}
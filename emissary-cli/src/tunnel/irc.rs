@@ -0,0 +1,550 @@
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! IRC client tunnel.
+//!
+//! Messages are relayed line by line and filtered in both directions so the IRC client cannot
+//! leak identifying information about the user:
+//!  * `USER` is rewritten to hide the hostname and real name of the user
+//!  * `QUIT` and `PART` messages are replaced as they may contain the client's version
+//!  * CTCP requests and replies other than `ACTION`, including `VERSION` and `DCC`, are dropped
+//!  * commands not known to be safe are dropped
+
+use crate::error::Error;
+
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+
+/// Logging target for the file.
+const LOG_TARGET: &str = "emissary::client-tunnel::irc";
+
+/// Maximum length of an IRC line, including message tags.
+const MAX_LINE_LEN: usize = 8191 + 512;
+
+/// Commands the client is allowed to send to the server.
+///
+/// `USER`, `QUIT`, `PART`, `PRIVMSG` and `NOTICE` are handled separately.
+const OUTBOUND_COMMANDS: &[&str] = &[
+    "ADMIN",
+    "AUTHENTICATE",
+    "AWAY",
+    "CAP",
+    "CNOTICE",
+    "CPRIVMSG",
+    "INFO",
+    "INVITE",
+    "ISON",
+    "JOIN",
+    "KICK",
+    "KILL",
+    "KNOCK",
+    "LINKS",
+    "LIST",
+    "LUSERS",
+    "MAP",
+    "MODE",
+    "MOTD",
+    "NAMES",
+    "NICK",
+    "OPER",
+    "PASS",
+    "PING",
+    "PONG",
+    "SILENCE",
+    "STATS",
+    "TIME",
+    "TOPIC",
+    "TRACE",
+    "USERHOST",
+    "USERS",
+    "VERSION",
+    "WALLOPS",
+    "WATCH",
+    "WHO",
+    "WHOIS",
+    "WHOWAS",
+];
+
+/// Commands the server is allowed to send to the client, in addition to numeric replies.
+///
+/// `PRIVMSG` and `NOTICE` are handled separately.
+const INBOUND_COMMANDS: &[&str] = &[
+    "ACCOUNT",
+    "AUTHENTICATE",
+    "AWAY",
+    "BATCH",
+    "CAP",
+    "CHGHOST",
+    "ERROR",
+    "INVITE",
+    "JOIN",
+    "KICK",
+    "KILL",
+    "MODE",
+    "NICK",
+    "PART",
+    "PING",
+    "PONG",
+    "QUIT",
+    "SETNAME",
+    "SILENCE",
+    "TOPIC",
+    "WALLOPS",
+];
+
+/// Parsed IRC message.
+///
+/// Only the command is required to be valid UTF-8, other parts of the message are kept as bytes
+/// as IRC doesn't mandate an encoding and legacy encodings such as Latin-1 are still in use.
+struct Message<'a> {
+    /// Command.
+    command: &'a str,
+
+    /// Parameters of the command, including the trailing parameter.
+    params: &'a [u8],
+
+    /// Prefix, if any.
+    prefix: Option<&'a [u8]>,
+
+    /// Message tags, if any.
+    tags: Option<&'a [u8]>,
+}
+
+/// Split `bytes` at the first space.
+fn split_once_space(bytes: &[u8]) -> Option<(&[u8], &[u8])> {
+    let index = bytes.iter().position(|byte| *byte == b' ')?;
+
+    Some((&bytes[..index], &bytes[index + 1..]))
+}
+
+/// Get the first space-separated parameter of `params`.
+fn first_param(params: &[u8]) -> Option<&[u8]> {
+    params.split(|byte| *byte == b' ').find(|param| !param.is_empty())
+}
+
+impl<'a> Message<'a> {
+    /// Attempt to parse `line` into [`Message`].
+    fn parse(line: &'a [u8]) -> Option<Self> {
+        let mut rest = line.trim_ascii_start();
+
+        let tags = match rest.strip_prefix(b"@") {
+            None => None,
+            Some(remaining) => {
+                let (tags, remaining) = split_once_space(remaining)?;
+                rest = remaining.trim_ascii_start();
+
+                Some(tags)
+            }
+        };
+
+        let prefix = match rest.strip_prefix(b":") {
+            None => None,
+            Some(remaining) => {
+                let (prefix, remaining) = split_once_space(remaining)?;
+                rest = remaining.trim_ascii_start();
+
+                Some(prefix)
+            }
+        };
+
+        let (command, params) = split_once_space(rest).unwrap_or((rest, &[]));
+        let command = std::str::from_utf8(command).ok()?;
+
+        (!command.is_empty()).then_some(Self {
+            command,
+            params: params.trim_ascii_start(),
+            prefix,
+            tags,
+        })
+    }
+
+    /// Get the commands of all CTCP messages embedded in the parameters.
+    ///
+    /// CTCP messages are delimited by `\x01` and may appear anywhere in the message, not just at
+    /// the start of the trailing parameter, and an unterminated CTCP message is also accepted.
+    fn ctcp_commands(&self) -> Vec<String> {
+        self.params
+            .split(|byte| *byte == b'\x01')
+            .skip(1)
+            .step_by(2)
+            .map(|ctcp| {
+                let command = ctcp.split(|byte| *byte == b' ').next().unwrap_or_default();
+
+                String::from_utf8_lossy(command).to_ascii_uppercase()
+            })
+            .collect()
+    }
+
+    /// Serialize [`Message`] into bytes.
+    fn serialize(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(MAX_LINE_LEN);
+
+        if let Some(tags) = self.tags {
+            out.push(b'@');
+            out.extend_from_slice(tags);
+            out.push(b' ');
+        }
+
+        if let Some(prefix) = self.prefix {
+            out.push(b':');
+            out.extend_from_slice(prefix);
+            out.push(b' ');
+        }
+
+        out.extend_from_slice(self.command.as_bytes());
+
+        if !self.params.is_empty() {
+            out.push(b' ');
+            out.extend_from_slice(self.params);
+        }
+
+        out
+    }
+}
+
+/// Filter `line` sent by the client.
+///
+/// Returns the line that should be relayed to the server or `None` if the line is dropped.
+pub fn filter_outbound(line: &[u8]) -> Option<Vec<u8>> {
+    let message = Message::parse(line)?;
+    let command = message.command.to_ascii_uppercase();
+
+    // prefix is never needed in messages sent by the client
+    let message = Message {
+        prefix: None,
+        ..message
+    };
+
+    match command.as_str() {
+        "USER" => {
+            let user = first_param(message.params)?;
+
+            Some([b"USER ", user, b" hostname localhost :realname"].concat())
+        }
+        "QUIT" => Some(b"QUIT :leaving".to_vec()),
+        "PART" => {
+            let channels = first_param(message.params)?;
+
+            Some([b"PART ", channels].concat())
+        }
+        "PRIVMSG" | "NOTICE" => match message
+            .ctcp_commands()
+            .into_iter()
+            .find(|ctcp| command != "PRIVMSG" || ctcp != "ACTION")
+        {
+            None => Some(message.serialize()),
+            Some(ctcp) => {
+                tracing::debug!(
+                    target: LOG_TARGET,
+                    %command,
+                    %ctcp,
+                    "dropping outbound ctcp message",
+                );
+                None
+            }
+        },
+        command if OUTBOUND_COMMANDS.contains(&command) => Some(message.serialize()),
+        command => {
+            tracing::debug!(
+                target: LOG_TARGET,
+                %command,
+                "dropping outbound message",
+            );
+            None
+        }
+    }
+}
+
+/// Filter `line` sent by the server.
+///
+/// Returns the line that should be relayed to the client or `None` if the line is dropped.
+pub fn filter_inbound(line: &[u8]) -> Option<Vec<u8>> {
+    let message = Message::parse(line)?;
+    let command = message.command.to_ascii_uppercase();
+
+    match command.as_str() {
+        command if command.len() == 3 && command.bytes().all(|byte| byte.is_ascii_digit()) =>
+            Some(line.to_vec()),
+        "PRIVMSG" | "NOTICE" =>
+            match message.ctcp_commands().into_iter().find(|ctcp| ctcp != "ACTION") {
+                None => Some(line.to_vec()),
+                Some(ctcp) => {
+                    tracing::debug!(
+                        target: LOG_TARGET,
+                        %command,
+                        %ctcp,
+                        "dropping inbound ctcp message",
+                    );
+                    None
+                }
+            },
+        command if INBOUND_COMMANDS.contains(&command) => Some(line.to_vec()),
+        command => {
+            tracing::debug!(
+                target: LOG_TARGET,
+                %command,
+                "dropping inbound message",
+            );
+            None
+        }
+    }
+}
+
+/// Read lines from `reader`, filter them with `filter` and write the remaining lines to `writer`.
+async fn relay_lines<R, W>(
+    reader: R,
+    mut writer: W,
+    filter: fn(&[u8]) -> Option<Vec<u8>>,
+) -> crate::Result<()>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut reader = BufReader::new(reader);
+    let mut line = Vec::new();
+
+    loop {
+        line.clear();
+
+        let nread = (&mut reader).take(MAX_LINE_LEN as u64).read_until(b'\n', &mut line).await?;
+
+        // propagate the half-close so the other direction can finish
+        if nread == 0 {
+            writer.shutdown().await?;
+            return Ok(());
+        }
+
+        if !line.ends_with(b"\n") && nread == MAX_LINE_LEN {
+            return Err(Error::InvalidData);
+        }
+
+        let line = line.strip_suffix(b"\n").unwrap_or(&line);
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some(line) = filter(line) {
+            writer.write_all(&line).await?;
+            writer.write_all(b"\r\n").await?;
+            writer.flush().await?;
+        }
+    }
+}
+
+/// Relay messages between the IRC `client` and `server`, filtering them in both directions.
+///
+/// When either side closes its write half, the close is propagated to the other side and messages
+/// in the other direction are relayed until it closes as well.
+pub async fn relay<C, S>(client: C, server: S) -> crate::Result<()>
+where
+    C: AsyncRead + AsyncWrite + Unpin,
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let (client_read, client_write) = tokio::io::split(client);
+    let (server_read, server_write) = tokio::io::split(server);
+
+    tokio::try_join!(
+        relay_lines(client_read, server_write, filter_outbound),
+        relay_lines(server_read, client_write, filter_inbound),
+    )
+    .map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outbound(line: &str) -> Option<String> {
+        filter_outbound(line.as_bytes()).map(|line| String::from_utf8(line).unwrap())
+    }
+
+    fn inbound(line: &str) -> Option<String> {
+        filter_inbound(line.as_bytes()).map(|line| String::from_utf8(line).unwrap())
+    }
+
+    #[test]
+    fn user_rewritten() {
+        assert_eq!(
+            outbound("USER alice alice-desktop 192.168.0.1 :Alice Smith").as_deref(),
+            Some("USER alice hostname localhost :realname")
+        );
+        assert_eq!(
+            outbound("user alice 0 * :Alice Smith").as_deref(),
+            Some("USER alice hostname localhost :realname")
+        );
+        assert!(outbound("USER").is_none());
+    }
+
+    #[test]
+    fn quit_and_part_rewritten() {
+        assert_eq!(
+            outbound("QUIT :Client 1.0 on Linux").as_deref(),
+            Some("QUIT :leaving")
+        );
+        assert_eq!(
+            outbound("PART #i2p,#emissary :Client 1.0 on Linux").as_deref(),
+            Some("PART #i2p,#emissary")
+        );
+    }
+
+    #[test]
+    fn outbound_ctcp_filtered() {
+        assert_eq!(
+            outbound("PRIVMSG #i2p :hello").as_deref(),
+            Some("PRIVMSG #i2p :hello")
+        );
+        assert_eq!(
+            outbound("PRIVMSG #i2p :\x01ACTION waves\x01").as_deref(),
+            Some("PRIVMSG #i2p :\x01ACTION waves\x01")
+        );
+        assert!(outbound("NOTICE bob :\x01VERSION Client 1.0 on Linux\x01").is_none());
+        assert!(outbound("PRIVMSG bob :\x01DCC SEND file 3232235521 1337 100\x01").is_none());
+        assert!(outbound("NOTICE bob :\x01ACTION waves\x01").is_none());
+    }
+
+    #[test]
+    fn embedded_ctcp_filtered() {
+        assert!(outbound("PRIVMSG bob :hi \x01DCC SEND file 3232235521 1337 100\x01").is_none());
+        assert!(outbound("PRIVMSG bob :\x01ACTION waves\x01\x01VERSION\x01").is_none());
+        assert!(outbound("PRIVMSG bob :hi \x01DCC CHAT chat 3232235521 1337").is_none());
+        assert_eq!(
+            outbound("PRIVMSG #i2p :hi \x01ACTION waves\x01").as_deref(),
+            Some("PRIVMSG #i2p :hi \x01ACTION waves\x01")
+        );
+        assert!(inbound(":bob PRIVMSG alice :hello \x01VERSION\x01").is_none());
+    }
+
+    #[test]
+    fn non_utf8_bytes_preserved() {
+        assert_eq!(
+            filter_outbound(b"PRIVMSG #i2p :caf\xe9"),
+            Some(b"PRIVMSG #i2p :caf\xe9".to_vec())
+        );
+        assert_eq!(
+            filter_inbound(b":bob PRIVMSG alice :\xe0 bient\xf4t"),
+            Some(b":bob PRIVMSG alice :\xe0 bient\xf4t".to_vec())
+        );
+        assert_eq!(
+            filter_outbound(b"USER \xe9l\xe9onore 0 * :\xc9l\xe9onore"),
+            Some(b"USER \xe9l\xe9onore hostname localhost :realname".to_vec())
+        );
+    }
+
+    #[test]
+    fn outbound_prefix_removed() {
+        assert_eq!(
+            outbound(":alice!alice@192.168.0.1 JOIN #i2p").as_deref(),
+            Some("JOIN #i2p")
+        );
+        assert_eq!(
+            outbound("@label=1 :alice PRIVMSG #i2p :hi").as_deref(),
+            Some("@label=1 PRIVMSG #i2p :hi")
+        );
+    }
+
+    #[test]
+    fn unknown_commands_dropped() {
+        assert!(outbound("SQUIT irc.postman.i2p :bye").is_none());
+        assert!(outbound("").is_none());
+        assert!(inbound(":server FOO bar").is_none());
+    }
+
+    #[test]
+    fn inbound_messages_filtered() {
+        assert_eq!(
+            inbound(":irc.postman.i2p 001 alice :Welcome").as_deref(),
+            Some(":irc.postman.i2p 001 alice :Welcome")
+        );
+        assert_eq!(
+            inbound("PING :irc.postman.i2p").as_deref(),
+            Some("PING :irc.postman.i2p")
+        );
+        assert_eq!(
+            inbound(":bob!bob@127.0.0.1 PRIVMSG #i2p :\x01ACTION waves\x01").as_deref(),
+            Some(":bob!bob@127.0.0.1 PRIVMSG #i2p :\x01ACTION waves\x01")
+        );
+        assert!(inbound(":bob!bob@127.0.0.1 PRIVMSG alice :\x01VERSION\x01").is_none());
+        assert!(inbound(":bob!bob@127.0.0.1 PRIVMSG alice :\x01DCC CHAT chat 1 2\x01").is_none());
+    }
+
+    #[tokio::test]
+    async fn relay_filters_messages() {
+        let (client, mut client_remote) = tokio::io::duplex(1024);
+        let (server, mut server_remote) = tokio::io::duplex(1024);
+
+        tokio::spawn(relay(client, server));
+
+        client_remote
+            .write_all(b"USER alice 0 * :Alice\r\nPRIVMSG bob :\x01VERSION\x01\r\nNICK alice\r\n")
+            .await
+            .unwrap();
+
+        let mut reader = BufReader::new(&mut server_remote);
+        let mut line = String::new();
+
+        reader.read_line(&mut line).await.unwrap();
+        assert_eq!(line, "USER alice hostname localhost :realname\r\n");
+
+        line.clear();
+        reader.read_line(&mut line).await.unwrap();
+        assert_eq!(line, "NICK alice\r\n");
+
+        server_remote
+            .write_all(b":bob PRIVMSG alice :\x01VERSION\x01\n:bob PRIVMSG alice :hi\n")
+            .await
+            .unwrap();
+
+        let mut reader = BufReader::new(&mut client_remote);
+        let mut line = String::new();
+
+        reader.read_line(&mut line).await.unwrap();
+        assert_eq!(line, ":bob PRIVMSG alice :hi\r\n");
+    }
+
+    #[tokio::test]
+    async fn relay_drains_after_half_close() {
+        let (client, mut client_remote) = tokio::io::duplex(1024);
+        let (server, mut server_remote) = tokio::io::duplex(1024);
+
+        let handle = tokio::spawn(relay(client, server));
+
+        // client sends `QUIT` and closes its write half
+        client_remote.write_all(b"QUIT :bye\r\n").await.unwrap();
+        client_remote.shutdown().await.unwrap();
+
+        let mut line = String::new();
+        let mut reader = BufReader::new(&mut server_remote);
+        reader.read_line(&mut line).await.unwrap();
+        assert_eq!(line, "QUIT :leaving\r\n");
+
+        // the close is propagated to the server
+        line.clear();
+        assert_eq!(reader.read_line(&mut line).await.unwrap(), 0);
+
+        // server's reply is still relayed to the client
+        server_remote.write_all(b"ERROR :Closing link\r\n").await.unwrap();
+        server_remote.shutdown().await.unwrap();
+
+        let mut received = String::new();
+        client_remote.read_to_string(&mut received).await.unwrap();
+        assert_eq!(received, "ERROR :Closing link\r\n");
+
+        handle.await.unwrap().unwrap();
+    }
+}
@@ -16,5 +16,181 @@
 // FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
 // DEALINGS IN THE SOFTWARE.
 
+use crate::{config::TunnelOptions, error::Error};
+
+use tokio::sync::{mpsc, oneshot};
+use yosemite::{RouterApi, SessionOptions};
+
+use std::{path::PathBuf, time::Duration};
+
 pub mod client;
+pub mod http;
+pub mod irc;
 pub mod server;
+
+/// Logging target for the file.
+const LOG_TARGET: &str = "emissary::tunnel";
+
+/// Size of the command channel of a tunnel manager.
+const COMMAND_CHANNEL_SIZE: usize = 16usize;
+
+/// Number of destination generation retries.
+const DESTINATION_CREATION_RETRY_COUNT: usize = 3usize;
+
+/// Destination generation failure backoff.
+const DESTINATION_CREATION_BACKOFF: Duration = Duration::from_secs(10);
+
+/// Commands sent to a tunnel manager via [`TunnelManagerHandle`].
+pub enum TunnelCommand<T> {
+    /// Add new tunnel.
+    Add {
+        /// Tunnel configuration.
+        config: T,
+
+        /// Oneshot sender used to send the result to caller.
+        tx: oneshot::Sender<crate::Result<()>>,
+    },
+
+    /// Remove tunnel by name.
+    Remove {
+        /// Name of the tunnel.
+        name: String,
+
+        /// Oneshot sender used to send the result to caller.
+        tx: oneshot::Sender<crate::Result<()>>,
+    },
+
+    /// List active tunnels.
+    List {
+        /// Oneshot sender used to send the result to caller.
+        tx: oneshot::Sender<Vec<T>>,
+    },
+}
+
+/// Handle to a tunnel manager.
+///
+/// Allows adding, removing and listing the tunnels of a running tunnel manager.
+pub struct TunnelManagerHandle<T> {
+    /// TX channel for sending commands to the tunnel manager.
+    tx: mpsc::Sender<TunnelCommand<T>>,
+}
+
+impl<T> Clone for TunnelManagerHandle<T> {
+    fn clone(&self) -> Self {
+        Self {
+            tx: self.tx.clone(),
+        }
+    }
+}
+
+impl<T> TunnelManagerHandle<T> {
+    /// Create new [`TunnelManagerHandle`] and return the RX channel for the tunnel manager.
+    fn new() -> (Self, mpsc::Receiver<TunnelCommand<T>>) {
+        let (tx, rx) = mpsc::channel(COMMAND_CHANNEL_SIZE);
+
+        (Self { tx }, rx)
+    }
+
+    /// Send `command` to the tunnel manager and wait for the response.
+    async fn send<U>(
+        &self,
+        command: impl FnOnce(oneshot::Sender<U>) -> TunnelCommand<T>,
+    ) -> crate::Result<U> {
+        let (tx, rx) = oneshot::channel();
+
+        self.tx
+            .send(command(tx))
+            .await
+            .map_err(|_| Error::Custom(String::from("tunnel manager has exited")))?;
+
+        rx.await.map_err(|_| Error::Custom(String::from("tunnel manager has exited")))
+    }
+
+    /// Add new tunnel.
+    pub async fn add(&self, config: T) -> crate::Result<()> {
+        self.send(|tx| TunnelCommand::Add { config, tx }).await?
+    }
+
+    /// Remove tunnel by `name`.
+    pub async fn remove(&self, name: String) -> crate::Result<()> {
+        self.send(|tx| TunnelCommand::Remove { name, tx }).await?
+    }
+
+    /// List active tunnels.
+    pub async fn list(&self) -> crate::Result<Vec<T>> {
+        self.send(|tx| TunnelCommand::List { tx }).await
+    }
+}
+
+/// Apply `inbound` and `outbound` tunnel options to session `options`.
+///
+/// Unspecified tunnel options keep the values of `options`.
+pub fn session_options(
+    options: SessionOptions,
+    inbound: &TunnelOptions,
+    outbound: &TunnelOptions,
+) -> SessionOptions {
+    SessionOptions {
+        inbound_len: inbound.length.unwrap_or(options.inbound_len),
+        num_inbound: inbound.quantity.unwrap_or(options.num_inbound),
+        outbound_len: outbound.length.unwrap_or(options.outbound_len),
+        num_outbound: outbound.quantity.unwrap_or(options.num_outbound),
+        ..options
+    }
+}
+
+/// Attempt to load destination from `path` and if it does't exist, call router over SAMv3 to
+/// create new persistent destination.
+///
+/// Destination generation is attempted three times before bailing out.
+pub async fn load_or_create_destination(
+    router_api: &mut RouterApi,
+    path: PathBuf,
+) -> Option<String> {
+    if let Some(destination) = tokio::fs::read(&path).await.ok().and_then(|contents| {
+        std::str::from_utf8(&contents).ok().map(|destination| destination.to_string())
+    }) {
+        return Some(destination);
+    };
+
+    tracing::debug!(
+        target: LOG_TARGET,
+        ?path,
+        "destination not found from disk, create new destination",
+    );
+
+    for _ in 0..DESTINATION_CREATION_RETRY_COUNT {
+        match router_api.generate_destination().await {
+            Ok((_, private_key)) => {
+                if let Err(error) = tokio::fs::write(&path, private_key.as_bytes()).await {
+                    tracing::warn!(
+                        target: LOG_TARGET,
+                        ?path,
+                        ?error,
+                        "failed to write destination to disk",
+                    );
+                }
+
+                return Some(private_key);
+            }
+            Err(error) => {
+                tracing::debug!(
+                    target: LOG_TARGET,
+                    ?path,
+                    ?error,
+                    "failed to generate destination",
+                );
+                tokio::time::sleep(DESTINATION_CREATION_BACKOFF).await;
+            }
+        }
+    }
+
+    tracing::warn!(
+        target: LOG_TARGET,
+        ?path,
+        retry_count = ?DESTINATION_CREATION_RETRY_COUNT,
+        "failed to generate destination after multiple retries",
+    );
+
+    None
+}
@@ -16,43 +16,252 @@
 // FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
 // DEALINGS IN THE SOFTWARE.
 
-use crate::config::ServerTunnelConfig;
+use crate::{
+    config::{ServerTunnelConfig, ServerTunnelKind, TunnelOptions},
+    error::Error,
+    tunnel::{
+        http::{self, DestinationHeaders},
+        load_or_create_destination, session_options, TunnelCommand, TunnelManagerHandle,
+    },
+};
 
-use yosemite::{style, DestinationKind, RouterApi, Session, SessionOptions};
+use tokio::{
+    net::TcpStream,
+    sync::mpsc,
+    task::{AbortHandle, JoinSet},
+};
+use yosemite::{style, DestinationKind, RouterApi, Session, SessionOptions, Stream};
 
-use std::{path::PathBuf, sync::Arc, time::Duration};
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
 /// Logging target for the file.
 const LOG_TARGET: &str = "emissary::server-tunnel";
 
-/// Number of destination generation retries.
-const DESTINATION_CREATION_RETRY_COUNT: usize = 3usize;
-
-/// Destination generation failure backoff.
-const DESTINATION_CREATION_BACKOFF: Duration = Duration::from_secs(10);
-
 /// Backoff for `STREAM FORWARD` failure.
 const STREAM_FORWARD_BACKOFF: Duration = Duration::from_secs(10);
 
-/// Server tunnel configuration
-pub struct TunnelConfig {
-    /// Base64 destination.
-    destination: String,
+/// Backoff for `STREAM ACCEPT` failure.
+const STREAM_ACCEPT_BACKOFF: Duration = Duration::from_secs(10);
+
+/// Default address of the server.
+const DEFAULT_ADDRESS: &str = "127.0.0.1";
+
+/// Prefix of the SAMv3 nicknames of server tunnel sessions.
+///
+/// Keeps the nicknames distinct from the nicknames of client tunnel sessions.
+const SESSION_NICKNAME_PREFIX: &str = "server-";
+
+/// Normalize `.b32.i2p` address for access list comparison.
+fn normalize_b32(address: &str) -> String {
+    let address = address.trim().to_ascii_lowercase();
+
+    address.strip_suffix(".b32.i2p").map(ToOwned::to_owned).unwrap_or(address)
+}
+
+/// Active connections of a server tunnel.
+#[derive(Default)]
+struct Connections {
+    /// Active connections, indexed by `.b32.i2p` address of the remote destination.
+    destinations: HashMap<String, usize>,
+
+    /// Total number of active connections.
+    total: usize,
+}
+
+/// Connection of a server tunnel.
+///
+/// Releases the connection slot when dropped.
+struct ConnectionGuard {
+    /// Normalized `.b32.i2p` address of the remote destination.
+    address: String,
+
+    /// Server tunnel.
+    tunnel: Arc<ServerTunnel>,
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        let mut connections = self.tunnel.connections.lock().expect("to succeed");
+
+        connections.total = connections.total.saturating_sub(1);
+
+        if let Some(count) = connections.destinations.get_mut(&self.address) {
+            *count -= 1;
+
+            if *count == 0 {
+                connections.destinations.remove(&self.address);
+            }
+        }
+    }
+}
+
+/// Server tunnel.
+struct ServerTunnel {
+    /// Allowed destinations, if the tunnel only accepts connections from some destinations.
+    allow_list: Option<HashSet<String>>,
+
+    /// Active connections.
+    connections: Mutex<Connections>,
+
+    /// Denied destinations.
+    deny_list: HashSet<String>,
+
+    /// Address of the server, including port.
+    host: String,
+
+    /// I2P port of the tunnel, if specified.
+    inport: Option<u16>,
+
+    /// Tunnel kind.
+    kind: ServerTunnelKind,
+
+    /// Maximum number of concurrent connections.
+    max_connections: Option<usize>,
+
+    /// Maximum number of concurrent connections from one destination.
+    max_connections_per_destination: Option<usize>,
 
     /// Name of the tunnel.
     name: String,
+}
+
+impl ServerTunnel {
+    /// Create new [`ServerTunnel`] from `config`.
+    fn new(config: &ServerTunnelConfig) -> Self {
+        Self {
+            allow_list: config
+                .allow_list
+                .as_ref()
+                .map(|list| list.iter().map(|address| normalize_b32(address)).collect()),
+            connections: Mutex::new(Connections::default()),
+            deny_list: config
+                .deny_list
+                .iter()
+                .flatten()
+                .map(|address| normalize_b32(address))
+                .collect(),
+            host: format!(
+                "{}:{}",
+                config.address.as_deref().unwrap_or(DEFAULT_ADDRESS),
+                config.port
+            ),
+            inport: config.inport,
+            kind: config.kind.unwrap_or_default(),
+            max_connections: config.max_connections,
+            max_connections_per_destination: config.max_connections_per_destination,
+            name: config.name.clone(),
+        }
+    }
+
+    /// Can inbound streams of the tunnel be forwarded with `STREAM FORWARD`.
+    ///
+    /// Streams can be forwarded if the server is on the local host and the remote destination
+    /// doesn't need to be inspected.
+    fn is_forwardable(&self) -> bool {
+        self.kind == ServerTunnelKind::Server
+            && self.host.starts_with(&format!("{DEFAULT_ADDRESS}:"))
+            && self.allow_list.is_none()
+            && self.deny_list.is_empty()
+            && self.max_connections.is_none()
+            && self.max_connections_per_destination.is_none()
+            && self.inport.is_none()
+    }
+
+    /// Is `address` allowed to connect to the tunnel.
+    fn is_allowed(&self, address: &str) -> bool {
+        !self.deny_list.contains(address)
+            && self.allow_list.as_ref().is_none_or(|allow_list| allow_list.contains(address))
+    }
+
+    /// Attempt to acquire a connection slot for `address`.
+    ///
+    /// Returns `None` if either connection limit has been reached.
+    fn acquire(self: &Arc<Self>, address: String) -> Option<ConnectionGuard> {
+        let mut connections = self.connections.lock().expect("to succeed");
+
+        if self.max_connections.is_some_and(|max| connections.total >= max) {
+            return None;
+        }
+
+        let count = connections.destinations.get(&address).copied().unwrap_or(0);
+
+        if self.max_connections_per_destination.is_some_and(|max| count >= max) {
+            return None;
+        }
+
+        connections.total += 1;
+        connections.destinations.insert(address.clone(), count + 1);
+
+        Some(ConnectionGuard {
+            address,
+            tunnel: Arc::clone(self),
+        })
+    }
+}
+
+/// Configuration of a server tunnel session.
+///
+/// One session is created for each destination and all server tunnels sharing the destination
+/// accept their streams from the same session.
+pub struct SessionConfig {
+    /// Base64 destination.
+    destination: String,
+
+    /// Inbound tunnel options.
+    inbound: TunnelOptions,
+
+    /// Nickname of the session.
+    nickname: String,
 
-    /// Server port.
-    port: u16,
+    /// Outbound tunnel options.
+    outbound: TunnelOptions,
 
     /// SAMv3 TCP port.
     sam_tcp_port: u16,
+
+    /// Server tunnels of the session.
+    tunnels: Vec<Arc<ServerTunnel>>,
+}
+
+impl SessionConfig {
+    /// Select server tunnel for a stream sent to `port`.
+    ///
+    /// Tunnel with a matching `inport` is preferred over a tunnel without `inport`.
+    fn select_tunnel(&self, port: Option<u16>) -> Option<&Arc<ServerTunnel>> {
+        self.tunnels
+            .iter()
+            .find(|tunnel| tunnel.inport.is_some() && tunnel.inport == port)
+            .or_else(|| self.tunnels.iter().find(|tunnel| tunnel.inport.is_none()))
+    }
 }
 
 /// Server tunnel manager.
 pub struct ServerTunnelManager {
-    /// Server tunnels.
-    tunnels: Vec<Arc<TunnelConfig>>,
+    /// Base path of the router.
+    base_path: PathBuf,
+
+    /// RX channel for receiving commands from [`TunnelManagerHandle`]s.
+    command_rx: mpsc::Receiver<TunnelCommand<ServerTunnelConfig>>,
+
+    /// Server tunnel futures.
+    futures: JoinSet<()>,
+
+    /// Handle given to the control interface.
+    handle: TunnelManagerHandle<ServerTunnelConfig>,
+
+    /// Sessions from router config, started when the event loop is started.
+    pending: Vec<(Vec<ServerTunnelConfig>, Arc<SessionConfig>)>,
+
+    /// SAMv3 TCP port.
+    sam_tcp_port: u16,
+
+    /// Active sessions and their server tunnels, indexed by key path.
+    sessions: HashMap<String, (Vec<ServerTunnelConfig>, AbortHandle)>,
 }
 
 impl ServerTunnelManager {
@@ -62,126 +271,113 @@ impl ServerTunnelManager {
         sam_tcp_port: u16,
         base_path: PathBuf,
     ) -> Self {
-        let mut tunnels = Vec::<Arc<TunnelConfig>>::new();
+        let mut pending = Vec::<(Vec<ServerTunnelConfig>, Arc<SessionConfig>)>::new();
         let mut router_api = RouterApi::new(sam_tcp_port);
 
-        for ServerTunnelConfig {
-            name,
-            port,
-            destination_path,
-            ..
-        } in configs
-        {
-            match Self::load_or_create_destination(
-                &mut router_api,
-                base_path.join(&destination_path),
-            )
-            .await
+        // group server tunnels by their destination
+        let mut groups = Vec::<Vec<ServerTunnelConfig>>::new();
+
+        for config in configs {
+            match groups.iter_mut().find(|group| group[0].key_path() == config.key_path()) {
+                Some(group) => group.push(config),
+                None => groups.push(vec![config]),
+            }
+        }
+
+        for configs in groups {
+            if let Some(session) =
+                Self::create_session(&mut router_api, &base_path, sam_tcp_port, &configs).await
             {
-                None => {
-                    tracing::warn!(
-                        target: LOG_TARGET,
-                        %name,
-                        %destination_path,
-                        "failed to load or create destination for server tunnel",
-                    );
-                    continue;
-                }
-                Some(destination) => {
-                    tunnels.push(Arc::from(TunnelConfig {
-                        destination,
-                        name,
-                        port,
-                        sam_tcp_port,
-                    }));
-                }
+                pending.push((configs, session));
             }
         }
 
-        Self { tunnels }
+        let (handle, command_rx) = TunnelManagerHandle::new();
+
+        Self {
+            base_path,
+            command_rx,
+            futures: JoinSet::new(),
+            handle,
+            pending,
+            sam_tcp_port,
+            sessions: HashMap::new(),
+        }
     }
 
-    /// Attempt to load destination from `path` and if it does't exist, call router over SAMv3 to
-    /// create new persistent destination.
+    /// Get handle to [`ServerTunnelManager`].
+    pub fn handle(&self) -> TunnelManagerHandle<ServerTunnelConfig> {
+        self.handle.clone()
+    }
+
+    /// Create [`SessionConfig`] for server tunnels `configs` which share a destination by loading
+    /// or creating the destination.
     ///
-    /// Destination generation is attempted three times before bailing out.
-    async fn load_or_create_destination(
+    /// Tunnel options are taken from the first tunnel that specifies them.
+    async fn create_session(
         router_api: &mut RouterApi,
-        path: PathBuf,
-    ) -> Option<String> {
-        if let Some(destination) = tokio::fs::read(&path).await.ok().and_then(|contents| {
-            std::str::from_utf8(&contents).ok().map(|destination| destination.to_string())
-        }) {
-            return Some(destination);
-        };
-
-        tracing::debug!(
-            target: LOG_TARGET,
-            ?path,
-            "destination not found from disk, create new destination",
-        );
-
-        for _ in 0..DESTINATION_CREATION_RETRY_COUNT {
-            match router_api.generate_destination().await {
-                Ok((_, private_key)) => {
-                    if let Err(error) = tokio::fs::write(&path, private_key.as_bytes()).await {
-                        tracing::warn!(
-                            target: LOG_TARGET,
-                            ?path,
-                            ?error,
-                            "failed to write destination to disk",
-                        );
-                    }
+        base_path: &Path,
+        sam_tcp_port: u16,
+        configs: &[ServerTunnelConfig],
+    ) -> Option<Arc<SessionConfig>> {
+        let config = configs.first()?;
 
-                    return Some(private_key);
-                }
-                Err(error) => {
-                    tracing::debug!(
-                        target: LOG_TARGET,
-                        ?path,
-                        ?error,
-                        "failed to generate destination",
-                    );
-                    tokio::time::sleep(DESTINATION_CREATION_BACKOFF).await;
-                }
+        match load_or_create_destination(router_api, base_path.join(config.key_path())).await {
+            None => {
+                tracing::warn!(
+                    target: LOG_TARGET,
+                    name = %config.name,
+                    key_path = %config.key_path(),
+                    "failed to load or create destination for server tunnel",
+                );
+                None
             }
+            Some(destination) => Some(Arc::new(SessionConfig {
+                destination,
+                inbound: configs
+                    .iter()
+                    .find_map(|config| config.inbound.clone())
+                    .unwrap_or_default(),
+                nickname: config.name.clone(),
+                outbound: configs
+                    .iter()
+                    .find_map(|config| config.outbound.clone())
+                    .unwrap_or_default(),
+                sam_tcp_port,
+                tunnels: configs.iter().map(|config| Arc::new(ServerTunnel::new(config))).collect(),
+            })),
         }
-
-        tracing::warn!(
-            target: LOG_TARGET,
-            ?path,
-            retry_count = ?DESTINATION_CREATION_RETRY_COUNT,
-            "failed to generate destination after multiple retries",
-        );
-
-        None
     }
 
-    /// Run the event loop of server tunnel.
-    async fn server_event_loop(config: Arc<TunnelConfig>) {
+    /// Run the event loop of server tunnel session.
+    async fn server_event_loop(config: Arc<SessionConfig>) {
         tracing::info!(
             target: LOG_TARGET,
-            name = %config.name,
-            port = %config.port,
+            name = %config.nickname,
+            tunnels = ?config.tunnels.iter().map(|tunnel| &tunnel.name).collect::<Vec<_>>(),
             "starting server tunnel",
         );
 
-        let mut session = match Session::<style::Stream>::new(SessionOptions {
-            samv3_tcp_port: config.sam_tcp_port,
-            nickname: config.name.clone(),
-            silent_forward: true,
-            destination: DestinationKind::Persistent {
-                private_key: config.destination.clone(),
+        let mut session = match Session::<style::Stream>::new(session_options(
+            SessionOptions {
+                samv3_tcp_port: config.sam_tcp_port,
+                nickname: format!("{SESSION_NICKNAME_PREFIX}{}", config.nickname),
+                silent_forward: true,
+                destination: DestinationKind::Persistent {
+                    private_key: config.destination.clone(),
+                },
+                ..Default::default()
             },
-            ..Default::default()
-        })
+            &config.inbound,
+            &config.outbound,
+        ))
         .await
         {
             Ok(session) => session,
             Err(error) => {
                 tracing::error!(
                     target: LOG_TARGET,
-                    name = %config.name,
+                    name = %config.nickname,
                     ?error,
                     "failed to start client samv3 session for server tunnel",
                 );
@@ -189,15 +385,27 @@ impl ServerTunnelManager {
             }
         };
 
+        let port = match config.tunnels.as_slice() {
+            [tunnel] if tunnel.is_forwardable() => config.tunnels[0]
+                .host
+                .strip_prefix(&format!("{DEFAULT_ADDRESS}:"))
+                .and_then(|port| port.parse::<u16>().ok()),
+            _ => None,
+        };
+
+        let Some(port) = port else {
+            return Self::accept_event_loop(config, session).await;
+        };
+
         // send `STREAM FORWARD` command to session and if it fails, sleep and try again later
         loop {
-            let Err(error) = session.forward(config.port).await else {
+            let Err(error) = session.forward(port).await else {
                 break;
             };
 
             tracing::warn!(
                 target: LOG_TARGET,
-                name = %config.name,
+                name = %config.nickname,
                 ?error,
                 "failed to forward stream",
             );
@@ -210,14 +418,360 @@ impl ServerTunnelManager {
         }
     }
 
-    /// Run the event loop of [`ServerTunnelManager`].
-    pub async fn run(self) {
-        if self.tunnels.is_empty() {
+    /// Run the event loop of a server tunnel session which accepts its inbound streams.
+    ///
+    /// Inbound streams are accepted from `session` instead of being forwarded if the remote
+    /// destination or the port of the stream must be known, e.g., for access lists, connection
+    /// limits, rewriting HTTP headers or selecting the server tunnel of a shared destination.
+    async fn accept_event_loop(config: Arc<SessionConfig>, mut session: Session<style::Stream>) {
+        loop {
+            match session.accept().await {
+                Ok(stream) => Self::on_inbound_stream(&config, stream),
+                Err(error) => {
+                    tracing::warn!(
+                        target: LOG_TARGET,
+                        name = %config.nickname,
+                        ?error,
+                        "failed to accept stream",
+                    );
+
+                    tokio::time::sleep(STREAM_ACCEPT_BACKOFF).await;
+                }
+            }
+        }
+    }
+
+    /// Handle inbound `stream`.
+    ///
+    /// Select server tunnel for the stream, verify that the remote destination is allowed to
+    /// connect and relay the stream to the server of the tunnel in the background.
+    fn on_inbound_stream(config: &SessionConfig, stream: Stream) {
+        // the accept response contains the destination, optionally followed by the ports
+        let (destination, port) = {
+            let mut parts = stream.remote_destination().split_whitespace();
+            let destination = parts.next().and_then(DestinationHeaders::new);
+            let port = parts
+                .find_map(|part| part.strip_prefix("TO_PORT="))
+                .and_then(|port| port.parse::<u16>().ok());
+
+            (destination, port)
+        };
+
+        let Some(destination) = destination else {
+            tracing::debug!(
+                target: LOG_TARGET,
+                name = %config.nickname,
+                "invalid remote destination for inbound stream",
+            );
+            return;
+        };
+
+        let Some(tunnel) = config.select_tunnel(port) else {
+            tracing::debug!(
+                target: LOG_TARGET,
+                name = %config.nickname,
+                ?port,
+                "no server tunnel for port",
+            );
             return;
+        };
+
+        let address = normalize_b32(destination.b32());
+
+        if !tunnel.is_allowed(&address) {
+            tracing::debug!(
+                target: LOG_TARGET,
+                name = %tunnel.name,
+                destination = %destination.b32(),
+                "destination not allowed to connect",
+            );
+            return;
+        }
+
+        let Some(guard) = tunnel.acquire(address) else {
+            tracing::debug!(
+                target: LOG_TARGET,
+                name = %tunnel.name,
+                destination = %destination.b32(),
+                "connection limit reached",
+            );
+            return;
+        };
+
+        let host = tunnel.host.clone();
+        let kind = tunnel.kind;
+
+        tokio::spawn(async move {
+            let result = match kind {
+                ServerTunnelKind::Http => http::handle_stream(stream, destination, host).await,
+                ServerTunnelKind::Server => Self::relay_stream(stream, host).await,
+            };
+
+            if let Err(error) = result {
+                tracing::debug!(
+                    target: LOG_TARGET,
+                    name = %guard.tunnel.name,
+                    ?error,
+                    "failed to handle inbound stream",
+                );
+            }
+        });
+    }
+
+    /// Relay `stream` to the server at `host`.
+    async fn relay_stream(mut stream: Stream, host: String) -> crate::Result<()> {
+        let mut server = TcpStream::connect(&host).await?;
+        tokio::io::copy_bidirectional(&mut stream, &mut server).await?;
+
+        Ok(())
+    }
+
+    /// Start the event loop of session `session` for server tunnels `configs`.
+    fn spawn_session(&mut self, configs: Vec<ServerTunnelConfig>, session: Arc<SessionConfig>) {
+        let key_path = configs[0].key_path().to_owned();
+        let handle = self.futures.spawn(Self::server_event_loop(session));
+
+        self.sessions.insert(key_path, (configs, handle));
+    }
+
+    /// Restart the session for server tunnels `configs` which share the destination at
+    /// `key_path`.
+    ///
+    /// If `configs` is empty, the session is stopped.
+    async fn restart_session(
+        &mut self,
+        key_path: &str,
+        configs: Vec<ServerTunnelConfig>,
+    ) -> crate::Result<()> {
+        if let Some((_, handle)) = self.sessions.remove(key_path) {
+            handle.abort();
+        }
+
+        if configs.is_empty() {
+            return Ok(());
+        }
+
+        let mut router_api = RouterApi::new(self.sam_tcp_port);
+
+        match Self::create_session(
+            &mut router_api,
+            &self.base_path,
+            self.sam_tcp_port,
+            &configs,
+        )
+        .await
+        {
+            Some(session) => {
+                self.spawn_session(configs, session);
+                Ok(())
+            }
+            None => Err(Error::Custom(format!(
+                "failed to load or create destination for `{key_path}`"
+            ))),
         }
+    }
+
+    /// Handle `command` received from a [`TunnelManagerHandle`].
+    async fn on_command(&mut self, command: TunnelCommand<ServerTunnelConfig>) {
+        match command {
+            TunnelCommand::Add { config, tx } => {
+                if self
+                    .sessions
+                    .values()
+                    .any(|(configs, _)| configs.iter().any(|tunnel| tunnel.name == config.name))
+                {
+                    let _ = tx.send(Err(Error::Custom(format!(
+                        "server tunnel `{}` already exists",
+                        config.name
+                    ))));
+                    return;
+                }
+
+                let key_path = config.key_path().to_owned();
+                let mut configs = match self.sessions.get(&key_path) {
+                    None => Vec::new(),
+                    Some((configs, _)) => {
+                        // destination can only be shared by tunnels that use `keys`
+                        // and each of them must be listening to a different port
+                        if config.keys.is_none()
+                            || configs.iter().any(|tunnel| {
+                                tunnel.keys.is_none() || tunnel.inport == config.inport
+                            })
+                        {
+                            let _ = tx.send(Err(Error::Custom(format!(
+                                "destination of `{}` is already in use",
+                                config.name
+                            ))));
+                            return;
+                        }
+
+                        configs.clone()
+                    }
+                };
+                configs.push(config);
+
+                let _ = tx.send(self.restart_session(&key_path, configs).await);
+            }
+            TunnelCommand::Remove { name, tx } => {
+                let Some(key_path) = self.sessions.iter().find_map(|(key_path, (configs, _))| {
+                    configs.iter().any(|tunnel| tunnel.name == name).then(|| key_path.clone())
+                }) else {
+                    let _ = tx.send(Err(Error::Custom(format!(
+                        "server tunnel `{name}` doesn't exist"
+                    ))));
+                    return;
+                };
+
+                tracing::info!(
+                    target: LOG_TARGET,
+                    %name,
+                    "remove server tunnel",
+                );
+
+                let configs = self
+                    .sessions
+                    .get(&key_path)
+                    .map(|(configs, _)| {
+                        configs.iter().filter(|tunnel| tunnel.name != name).cloned().collect()
+                    })
+                    .unwrap_or_default();
 
-        for tunnel in self.tunnels {
-            tokio::spawn(Self::server_event_loop(Arc::clone(&tunnel)));
+                let _ = tx.send(self.restart_session(&key_path, configs).await);
+            }
+            TunnelCommand::List { tx } => {
+                let _ = tx.send(
+                    self.sessions.values().flat_map(|(configs, _)| configs.clone()).collect(),
+                );
+            }
+        }
+    }
+
+    /// Run the event loop of [`ServerTunnelManager`].
+    ///
+    /// Server tunnels from router config are started first, after which the manager serves
+    /// commands received from the control interface.
+    pub async fn run(mut self) {
+        for (configs, session) in std::mem::take(&mut self.pending) {
+            self.spawn_session(configs, session);
         }
+
+        loop {
+            tokio::select! {
+                _ = self.futures.join_next(), if !self.futures.is_empty() => {}
+                command = self.command_rx.recv() => match command {
+                    Some(command) => self.on_command(command).await,
+                    None => return,
+                },
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_tunnel(config: ServerTunnelConfig) -> Arc<ServerTunnel> {
+        Arc::new(ServerTunnel::new(&ServerTunnelConfig {
+            port: 8080,
+            destination_path: "tunnel.b64".to_string(),
+            ..config
+        }))
+    }
+
+    #[test]
+    fn plain_tunnel_is_forwarded() {
+        assert!(make_tunnel(ServerTunnelConfig::default()).is_forwardable());
+        assert!(!make_tunnel(ServerTunnelConfig {
+            kind: Some(ServerTunnelKind::Http),
+            ..Default::default()
+        })
+        .is_forwardable());
+        assert!(!make_tunnel(ServerTunnelConfig {
+            address: Some("192.168.0.1".to_string()),
+            ..Default::default()
+        })
+        .is_forwardable());
+        assert!(!make_tunnel(ServerTunnelConfig {
+            max_connections: Some(10),
+            ..Default::default()
+        })
+        .is_forwardable());
+    }
+
+    #[test]
+    fn access_lists() {
+        let allowed = "ukeu3k5oycgaauneqgtnvselmt4yemvoilkln7jpvamvfx7dnkdq";
+        let denied = "udhdrtrcetjm5sxzskjyr5ztpeszydbh4dpl3pl4utgqqw2v4jna";
+        let other = "shx5vqsw7usdaunyzr2qmes2fq37oumybpudrd4jjj4e4vk4uusa";
+
+        let tunnel = make_tunnel(ServerTunnelConfig {
+            deny_list: Some(vec![format!("{denied}.b32.i2p")]),
+            ..Default::default()
+        });
+        assert!(tunnel.is_allowed(allowed));
+        assert!(tunnel.is_allowed(other));
+        assert!(!tunnel.is_allowed(denied));
+
+        let tunnel = make_tunnel(ServerTunnelConfig {
+            allow_list: Some(vec![format!("{}.B32.I2P", allowed.to_uppercase())]),
+            ..Default::default()
+        });
+        assert!(tunnel.is_allowed(allowed));
+        assert!(!tunnel.is_allowed(other));
+        assert!(!tunnel.is_allowed(denied));
+    }
+
+    #[test]
+    fn connection_limits() {
+        let tunnel = make_tunnel(ServerTunnelConfig {
+            max_connections: Some(3),
+            max_connections_per_destination: Some(2),
+            ..Default::default()
+        });
+
+        let guard1 = tunnel.acquire("dest1".to_string()).unwrap();
+        let _guard2 = tunnel.acquire("dest1".to_string()).unwrap();
+        assert!(tunnel.acquire("dest1".to_string()).is_none());
+
+        let _guard3 = tunnel.acquire("dest2".to_string()).unwrap();
+        assert!(tunnel.acquire("dest3".to_string()).is_none());
+
+        // connection slot is released when the connection is closed
+        drop(guard1);
+        assert!(tunnel.acquire("dest1".to_string()).is_some());
+        assert!(tunnel.connections.lock().unwrap().destinations.get("dest1") == Some(&1));
+    }
+
+    #[test]
+    fn tunnel_selected_by_port() {
+        let config = SessionConfig {
+            destination: String::new(),
+            inbound: TunnelOptions::default(),
+            nickname: "tunnel".to_string(),
+            outbound: TunnelOptions::default(),
+            sam_tcp_port: 7656,
+            tunnels: vec![
+                make_tunnel(ServerTunnelConfig {
+                    name: "default".to_string(),
+                    ..Default::default()
+                }),
+                make_tunnel(ServerTunnelConfig {
+                    name: "ssh".to_string(),
+                    inport: Some(22),
+                    ..Default::default()
+                }),
+            ],
+        };
+
+        assert_eq!(config.select_tunnel(Some(22)).unwrap().name, "ssh");
+        assert_eq!(config.select_tunnel(Some(80)).unwrap().name, "default");
+        assert_eq!(config.select_tunnel(None).unwrap().name, "default");
+
+        let config = SessionConfig {
+            tunnels: vec![config.tunnels[1].clone()],
+            ..config
+        };
+        assert!(config.select_tunnel(Some(80)).is_none());
     }
 }
@@ -175,6 +175,7 @@ impl RouterUi {
                                 self.status = Status::ShuttingDown(Instant::now());
                             },
                         Event::ShutDown => {}
+                        _ => {}
                     }
                 }
 
@@ -16,7 +16,22 @@
 // FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
 // DEALINGS IN THE SOFTWARE.
 
+//! Web UI of the router.
+//!
+//! Besides the router console served at `/`, the web UI exposes a JSON REST API under `/api`
+//! which can be used to inspect and control the router from scripts.
+//!
+//! Websites open in the user's browser can send requests to the web UI as well, so requests whose
+//! `Host` or `Origin` is not the web UI itself are rejected and request bodies must be sent as
+//! `application/json`, which browsers don't allow other websites to do without a CORS preflight.
+
 use crate::{
+    address_book::{AddressBookHandle, Book},
+    config::{ClientTunnelConfig, Config, ServerTunnelConfig},
+    error::Error,
+    logger::recent_logs,
+    reload::ReloadHandle,
+    tunnel::TunnelManagerHandle,
     ui::{calculate_bandwidth, Status},
     LOG_TARGET,
 };
@@ -24,14 +39,24 @@ use crate::{
 use axum::{
     extract::{
         ws::{Message, Utf8Bytes, WebSocket, WebSocketUpgrade},
-        State,
+        Path, RawQuery, Request, State,
     },
-    response::IntoResponse,
-    routing::get,
+    http::{header, Method, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{delete, get, post, put},
     Router,
 };
-use emissary_core::events::{Event, EventSubscriber};
+use emissary_core::{
+    events::{Event, EventSubscriber},
+    router::handle::{
+        BannedPeerInfo, DestinationInfo, NetDbRouterInfo, NewsItem, RouterHandle, SybilInfo,
+        TransitTunnelInfo, TunnelPoolInfo,
+    },
+};
 use futures::StreamExt;
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, Value};
 use tokio::{
     net::TcpListener,
     sync::mpsc::Sender,
@@ -39,7 +64,8 @@ use tokio::{
 };
 
 use std::{
-    sync::{Arc, Mutex},
+    path::PathBuf,
+    sync::{Arc, Mutex, MutexGuard},
     time::{Duration, Instant},
 };
 
@@ -50,14 +76,50 @@ const ROUTER_CONSOLE: &str =
 /// Default listening port for web UI.
 const LISTEN_PORT: u16 = 7657;
 
+/// How many log lines are returned by `/api/logs` by default.
+const DEFAULT_LOG_LINES: usize = 200usize;
+
+/// Handles used by the REST API to control the router.
+#[derive(Clone)]
+pub struct ConsoleHandles {
+    /// Handle to address book, if the address book has been enabled.
+    pub address_book: Option<AddressBookHandle>,
+
+    /// Base path of the router.
+    pub base_path: PathBuf,
+
+    /// Handle to client tunnel manager, if SAM has been enabled.
+    pub client_tunnels: Option<TunnelManagerHandle<ClientTunnelConfig>>,
+
+    /// Handle to config reloader, if SAM has been enabled.
+    pub reload: Option<ReloadHandle>,
+
+    /// Handle to the router, used to query the network database and connected routers.
+    ///
+    /// `None` if the router isn't running.
+    pub router: Option<RouterHandle>,
+
+    /// Handle to server tunnel manager, if SAM has been enabled.
+    pub server_tunnels: Option<TunnelManagerHandle<ServerTunnelConfig>>,
+}
+
 /// Router state.
 struct InnerRouterState {
     /// Cumulative bandwidth of all transports.
     bandwidth: usize,
 
+    /// Banned routers and addresses.
+    banlist: Vec<BannedPeerInfo>,
+
     /// Active client destinations.
     client_destinations: Vec<String>,
 
+    /// Active client and server destinations.
+    destinations: Vec<DestinationInfo>,
+
+    /// News items.
+    news: Vec<NewsItem>,
+
     /// Total number of routers.
     num_routers: usize,
 
@@ -82,6 +144,12 @@ struct InnerRouterState {
     /// Cumulative bandwidth of all transit tunnels.
     transit_bandwidth: usize,
 
+    /// Active transit tunnels.
+    transit_tunnels: Vec<TransitTunnelInfo>,
+
+    /// Active tunnel pools.
+    tunnel_pools: Vec<TunnelPoolInfo>,
+
     /// Web UI update interval.
     update_interval: Duration,
 
@@ -89,13 +157,101 @@ struct InnerRouterState {
     uptime: Instant,
 }
 
+impl InnerRouterState {
+    /// Update router state from `event`.
+    fn on_event(&mut self, event: Event) {
+        match event {
+            Event::RouterStatus {
+                client_destinations,
+                server_destinations,
+                transit,
+                transport,
+                tunnel,
+            } => {
+                self.transit_bandwidth = transit.bandwidth;
+                self.num_transit_tunnels = transit.num_tunnels;
+                self.bandwidth = transport.bandwidth;
+                self.num_routers = transport.num_connected_routers;
+                self.server_destinations.extend(server_destinations);
+                self.client_destinations.extend(client_destinations);
+                self.num_tunnels_built = tunnel.num_tunnels_built;
+                self.num_tunnel_build_failures = tunnel.num_tunnel_build_failures;
+            }
+            Event::Destinations { destinations } => self.destinations = destinations,
+            Event::TransitTunnels { tunnels } => self.transit_tunnels = tunnels,
+            Event::TunnelPools { pools } => self.tunnel_pools = pools,
+            Event::Banlist { peers } => self.banlist = peers,
+            Event::News { items } => self.news = items,
+            Event::ShuttingDown =>
+                if let Status::Active = self.status {
+                    self.status = Status::ShuttingDown(Instant::now());
+                },
+            Event::ShutDown => {}
+        }
+    }
+}
+
 /// Router state.
 #[derive(Clone)]
 struct RouterState {
+    /// Handles used by the REST API.
+    handles: ConsoleHandles,
+
     /// Router state.
     state: Arc<Mutex<InnerRouterState>>,
 }
 
+impl RouterState {
+    /// Create new [`RouterState`].
+    fn new(handles: ConsoleHandles, shutdown_tx: Sender<()>, update_interval: Duration) -> Self {
+        Self {
+            handles,
+            state: Arc::new(Mutex::new(InnerRouterState {
+                bandwidth: 0usize,
+                banlist: Vec::new(),
+                client_destinations: Vec::new(),
+                destinations: Vec::new(),
+                news: Vec::new(),
+                num_routers: 0usize,
+                num_transit_tunnels: 0usize,
+                num_tunnel_build_failures: 0usize,
+                num_tunnels_built: 0usize,
+                server_destinations: Vec::new(),
+                shutdown_tx,
+                status: Status::Active,
+                transit_bandwidth: 0usize,
+                transit_tunnels: Vec::new(),
+                tunnel_pools: Vec::new(),
+                update_interval,
+                uptime: Instant::now(),
+            })),
+        }
+    }
+
+    /// Get access to router state.
+    fn inner(&self) -> Result<MutexGuard<'_, InnerRouterState>, ApiError> {
+        self.state
+            .lock()
+            .map_err(|_| ApiError(StatusCode::INTERNAL_SERVER_ERROR, "poisoned lock".into()))
+    }
+
+    /// Get handle to the router.
+    fn router(&self) -> Result<&RouterHandle, ApiError> {
+        self.handles.router.as_ref().ok_or(ApiError(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "router is not running".into(),
+        ))
+    }
+
+    /// Get routers stored in the network database.
+    async fn netdb_routers(&self) -> Result<Vec<NetDbRouterInfo>, ApiError> {
+        self.router()?
+            .netdb_routers()
+            .await
+            .map_err(|error| ApiError(StatusCode::SERVICE_UNAVAILABLE, error.to_string()))
+    }
+}
+
 /// Router UI.
 pub struct RouterUi {
     /// Subscriber to events emitted by `emissary-core`.
@@ -121,6 +277,7 @@ impl RouterUi {
         port: Option<u16>,
         refresh_interval: usize,
         shutdown_tx: Sender<()>,
+        handles: ConsoleHandles,
     ) -> Self {
         let update_interval = if refresh_interval == 0 {
             Duration::from_secs(10)
@@ -132,22 +289,7 @@ impl RouterUi {
             events,
             port: port.unwrap_or(LISTEN_PORT),
             _shutdown_tx: shutdown_tx.clone(),
-            state: RouterState {
-                state: Arc::new(Mutex::new(InnerRouterState {
-                    bandwidth: 0usize,
-                    client_destinations: Vec::new(),
-                    num_routers: 0usize,
-                    num_transit_tunnels: 0usize,
-                    num_tunnel_build_failures: 0usize,
-                    num_tunnels_built: 0usize,
-                    server_destinations: Vec::new(),
-                    shutdown_tx,
-                    status: Status::Active,
-                    transit_bandwidth: 0usize,
-                    update_interval,
-                    uptime: Instant::now(),
-                })),
-            },
+            state: RouterState::new(handles, shutdown_tx, update_interval),
             update_interval: interval(update_interval),
         }
     }
@@ -167,10 +309,7 @@ impl RouterUi {
             }
         };
 
-        let app = Router::new()
-            .route("/", get(index))
-            .route("/ws", get(ws_handler))
-            .with_state(self.state.clone());
+        let app = app(self.state.clone(), self.port);
 
         tokio::spawn(async move {
             if let Err(error) = axum::serve(listener, app).await {
@@ -190,31 +329,7 @@ impl RouterUi {
                             return;
                         };
 
-                        match event {
-                            Event::RouterStatus {
-                                client_destinations,
-                                server_destinations,
-                                transit,
-                                transport,
-                                tunnel,
-                            } => {
-                                inner.transit_bandwidth = transit.bandwidth;
-                                inner.num_transit_tunnels = transit.num_tunnels;
-                                inner.bandwidth = transport.bandwidth;
-                                inner.num_routers = transport.num_connected_routers;
-                                inner.server_destinations.extend(server_destinations);
-                                inner.client_destinations.extend(client_destinations);
-                                inner.num_tunnels_built = tunnel.num_tunnels_built;
-                                inner.num_tunnel_build_failures = tunnel.num_tunnel_build_failures;
-                            }
-                            Event::ShuttingDown => match inner.status {
-                                Status::Active => {
-                                    inner.status = Status::ShuttingDown(Instant::now());
-                                }
-                                _ => {}
-                            },
-                            Event::ShutDown => {}
-                        }
+                        inner.on_event(event);
                     }
                 }
             }
@@ -222,6 +337,90 @@ impl RouterUi {
     }
 }
 
+/// Create the HTTP service of the web UI listening on `port`.
+fn app(state: RouterState, port: u16) -> Router {
+    Router::new()
+        .route("/", get(index))
+        .route("/ws", get(ws_handler))
+        .route("/api/status", get(api_status))
+        .route("/api/shutdown", post(api_shutdown))
+        .route("/api/tunnels", get(api_tunnels))
+        .route("/api/tunnels/client", post(api_add_client_tunnel))
+        .route(
+            "/api/tunnels/client/{name}",
+            delete(api_remove_client_tunnel),
+        )
+        .route("/api/tunnels/server", post(api_add_server_tunnel))
+        .route(
+            "/api/tunnels/server/{name}",
+            delete(api_remove_server_tunnel),
+        )
+        .route("/api/destinations", get(api_destinations))
+        .route("/api/routers", get(api_routers))
+        .route("/api/transit", get(api_transit_tunnels))
+        .route("/api/netdb", get(api_netdb))
+        .route("/api/netdb/{router_id}", get(api_netdb_router))
+        .route("/api/addressbook", get(api_address_book).post(api_add_host))
+        .route("/api/addressbook/export", get(api_export_address_book))
+        .route(
+            "/api/addressbook/{host}",
+            put(api_rename_host).delete(api_remove_host),
+        )
+        .route("/api/banlist", get(api_banlist))
+        .route("/api/sybil", get(api_sybil))
+        .route("/api/news", get(api_news))
+        .route("/api/logs", get(api_logs))
+        .route("/api/config", get(api_config).put(api_update_config))
+        .layer(middleware::from_fn(move |request, next| {
+            check_request(port, request, next)
+        }))
+        .with_state(state)
+}
+
+/// Reject `request` if it may have been sent by another website.
+///
+/// `Host` must be the address of the web UI to prevent DNS rebinding, `Origin`, if present, must
+/// be the origin of the web UI and requests which modify router state must have a JSON body.
+async fn check_request(port: u16, request: Request, next: Next) -> Response {
+    let headers = request.headers();
+    let host = headers.get(header::HOST).and_then(|host| host.to_str().ok());
+    let is_local = |host: &str| {
+        host.rsplit_once(':').is_some_and(|(host, host_port)| {
+            ["127.0.0.1", "localhost"].contains(&host) && host_port.parse() == Ok(port)
+        })
+    };
+
+    if !host.is_some_and(is_local) {
+        return ApiError(StatusCode::FORBIDDEN, "invalid host".into()).into_response();
+    }
+
+    if let Some(origin) = headers.get(header::ORIGIN) {
+        let origin = origin.to_str().ok().and_then(|origin| origin.strip_prefix("http://"));
+
+        if !origin.is_some_and(is_local) {
+            return ApiError(StatusCode::FORBIDDEN, "invalid origin".into()).into_response();
+        }
+    }
+
+    if [Method::POST, Method::PUT].contains(request.method()) {
+        let is_json = headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|content_type| content_type.to_str().ok())
+            .and_then(|content_type| content_type.split(';').next())
+            .is_some_and(|mime| mime.trim().eq_ignore_ascii_case("application/json"));
+
+        if !is_json {
+            return ApiError(
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                "content type must be application/json".into(),
+            )
+            .into_response();
+        }
+    }
+
+    next.run(request).await
+}
+
 async fn index() -> impl IntoResponse {
     axum::response::Html(ROUTER_CONSOLE)
 }
@@ -346,3 +545,891 @@ async fn handle_socket(mut socket: WebSocket, state: RouterState) {
         }
     }
 }
+
+/// Error returned by the REST API.
+#[derive(Debug)]
+struct ApiError(StatusCode, String);
+
+impl ApiError {
+    /// Create new [`ApiError`] for a resource that doesn't exist.
+    fn not_found(resource: &str) -> Self {
+        Self(StatusCode::NOT_FOUND, format!("{resource} not found"))
+    }
+
+    /// Create new [`ApiError`] for a subsystem that hasn't been enabled.
+    fn disabled(subsystem: &str) -> Self {
+        Self(
+            StatusCode::SERVICE_UNAVAILABLE,
+            format!("{subsystem} is disabled"),
+        )
+    }
+}
+
+impl From<Error> for ApiError {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::Io(error) => Self(StatusCode::INTERNAL_SERVER_ERROR, error.to_string()),
+            Error::Custom(message) => Self(StatusCode::BAD_REQUEST, message),
+            error => Self(StatusCode::BAD_REQUEST, error.to_string()),
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, json_response(json!({ "error": self.1 }))).into_response()
+    }
+}
+
+/// Result type of the REST API.
+type ApiResult = Result<Response, ApiError>;
+
+/// Create JSON response from `value`.
+fn json_response(value: Value) -> Response {
+    (
+        [(header::CONTENT_TYPE, "application/json")],
+        value.to_string(),
+    )
+        .into_response()
+}
+
+/// Parse JSON request body.
+fn parse_body<T: DeserializeOwned>(body: &str) -> Result<T, ApiError> {
+    serde_json::from_str(body)
+        .map_err(|error| ApiError(StatusCode::BAD_REQUEST, format!("invalid request: {error}")))
+}
+
+/// Get value of `key` from query string of the request, if it exists.
+fn query_param(query: &Option<String>, key: &str) -> Option<String> {
+    url::form_urlencoded::parse(query.as_deref()?.as_bytes())
+        .find(|(name, _)| name == key)
+        .map(|(_, value)| value.into_owned())
+}
+
+/// Serialize tunnel pool information.
+fn tunnel_pool_json(pool: &TunnelPoolInfo) -> Value {
+    json!({
+        "name": pool.name,
+        "num_inbound": pool.num_inbound,
+        "num_outbound": pool.num_outbound,
+        "num_tunnels_built": pool.num_tunnels_built,
+        "num_tunnel_build_failures": pool.num_tunnel_build_failures,
+    })
+}
+
+/// Serialize summary of a router stored in the network database.
+fn netdb_router_json(router: &NetDbRouterInfo) -> Value {
+    json!({
+        "router_id": router.router_id,
+        "capabilities": router.capabilities,
+        "floodfill": router.floodfill,
+        "reachable": router.reachable,
+        "version": router.version,
+        "published": router.published,
+        "addresses": router.addresses,
+    })
+}
+
+/// Serialize entry of the banlist.
+fn banned_peer_json(peer: &BannedPeerInfo) -> Value {
+    json!({
+        "peer": peer.peer,
+        "reason": peer.reason,
+        "expires": peer.expires,
+    })
+}
+
+/// Serialize findings of the Sybil detection.
+fn sybil_json(report: &SybilInfo) -> Value {
+    json!({
+        "num_ipv4_routers": report.num_ipv4_routers,
+        "num_ipv6_routers": report.num_ipv6_routers,
+        "clusters": report.clusters.iter().map(|cluster| json!({
+            "address": cluster.address,
+            "routers": cluster.routers,
+        })).collect::<Vec<_>>(),
+        "families": report.families.iter().map(|family| json!({
+            "family": family.family,
+            "routers": family.routers,
+        })).collect::<Vec<_>>(),
+        "close_floodfills": report.close_floodfills.iter().map(|floodfill| json!({
+            "router_id": floodfill.router_id,
+            "shared_bits": floodfill.shared_bits,
+        })).collect::<Vec<_>>(),
+    })
+}
+
+/// Serialize news item.
+fn news_item_json(item: &NewsItem) -> Value {
+    json!({
+        "id": item.id,
+        "title": item.title,
+        "updated": item.updated,
+        "link": item.link,
+        "summary": item.summary,
+    })
+}
+
+/// `GET /api/status`
+async fn api_status(State(state): State<RouterState>) -> ApiResult {
+    let num_netdb_routers = match state.handles.router {
+        None => 0usize,
+        Some(_) => state.netdb_routers().await?.len(),
+    };
+    let inner = state.inner()?;
+
+    Ok(json_response(json!({
+        "status": inner.status.to_string(),
+        "uptime": inner.uptime.elapsed().as_secs(),
+        "bandwidth": inner.bandwidth,
+        "transit_bandwidth": inner.transit_bandwidth,
+        "num_routers": inner.num_routers,
+        "num_netdb_routers": num_netdb_routers,
+        "num_transit_tunnels": inner.num_transit_tunnels,
+        "num_tunnels_built": inner.num_tunnels_built,
+        "num_tunnel_build_failures": inner.num_tunnel_build_failures,
+    })))
+}
+
+/// `POST /api/shutdown`
+///
+/// Start graceful shutdown of the router.
+async fn api_shutdown(State(state): State<RouterState>) -> ApiResult {
+    let _ = state.inner()?.shutdown_tx.try_send(());
+
+    Ok(json_response(Value::Null))
+}
+
+/// `GET /api/tunnels`
+async fn api_tunnels(State(state): State<RouterState>) -> ApiResult {
+    let pools = state.inner()?.tunnel_pools.iter().map(tunnel_pool_json).collect::<Vec<_>>();
+    let client = match &state.handles.client_tunnels {
+        Some(handle) => handle.list().await?,
+        None => Vec::new(),
+    };
+    let server = match &state.handles.server_tunnels {
+        Some(handle) => handle.list().await?,
+        None => Vec::new(),
+    };
+
+    Ok(json_response(json!({
+        "client": client,
+        "server": server,
+        "pools": pools,
+    })))
+}
+
+/// `POST /api/tunnels/client`
+async fn api_add_client_tunnel(State(state): State<RouterState>, body: String) -> ApiResult {
+    let handle = state.handles.client_tunnels.as_ref().ok_or(ApiError::disabled("sam"))?;
+    handle.add(parse_body(&body)?).await?;
+
+    Ok(json_response(Value::Null))
+}
+
+/// `DELETE /api/tunnels/client/{name}`
+async fn api_remove_client_tunnel(
+    State(state): State<RouterState>,
+    Path(name): Path<String>,
+) -> ApiResult {
+    let handle = state.handles.client_tunnels.as_ref().ok_or(ApiError::disabled("sam"))?;
+    handle.remove(name).await?;
+
+    Ok(json_response(Value::Null))
+}
+
+/// `POST /api/tunnels/server`
+async fn api_add_server_tunnel(State(state): State<RouterState>, body: String) -> ApiResult {
+    let handle = state.handles.server_tunnels.as_ref().ok_or(ApiError::disabled("sam"))?;
+    handle.add(parse_body(&body)?).await?;
+
+    Ok(json_response(Value::Null))
+}
+
+/// `DELETE /api/tunnels/server/{name}`
+async fn api_remove_server_tunnel(
+    State(state): State<RouterState>,
+    Path(name): Path<String>,
+) -> ApiResult {
+    let handle = state.handles.server_tunnels.as_ref().ok_or(ApiError::disabled("sam"))?;
+    handle.remove(name).await?;
+
+    Ok(json_response(Value::Null))
+}
+
+/// `GET /api/destinations`
+async fn api_destinations(State(state): State<RouterState>) -> ApiResult {
+    let destinations = state
+        .inner()?
+        .destinations
+        .iter()
+        .map(|destination| {
+            json!({
+                "name": destination.name,
+                "address": destination.address,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    Ok(json_response(Value::Array(destinations)))
+}
+
+/// `GET /api/routers`
+async fn api_routers(State(state): State<RouterState>) -> ApiResult {
+    let status = state
+        .router()?
+        .status()
+        .await
+        .map_err(|error| ApiError(StatusCode::SERVICE_UNAVAILABLE, error.to_string()))?;
+
+    Ok(json_response(json!(status.connected_routers)))
+}
+
+/// `GET /api/transit`
+async fn api_transit_tunnels(State(state): State<RouterState>) -> ApiResult {
+    let tunnels = state
+        .inner()?
+        .transit_tunnels
+        .iter()
+        .map(|tunnel| {
+            json!({
+                "tunnel_id": tunnel.tunnel_id.to_string(),
+                "role": format!("{:?}", tunnel.role),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    Ok(json_response(Value::Array(tunnels)))
+}
+
+/// `GET /api/netdb`
+///
+/// Routers can be filtered with `?floodfill=true|false` and by router hash prefix with `?id=`.
+async fn api_netdb(State(state): State<RouterState>, RawQuery(query): RawQuery) -> ApiResult {
+    let routers = state.netdb_routers().await?;
+
+    netdb_json(&routers, &query).map(json_response)
+}
+
+/// Serialize routers of the network database matching the filters in `query`.
+fn netdb_json(routers: &[NetDbRouterInfo], query: &Option<String>) -> Result<Value, ApiError> {
+    let floodfill = match query_param(query, "floodfill").as_deref() {
+        None => None,
+        Some("true") => Some(true),
+        Some("false") => Some(false),
+        Some(value) =>
+            return Err(ApiError(
+                StatusCode::BAD_REQUEST,
+                format!("invalid floodfill filter: {value}"),
+            )),
+    };
+    let prefix = query_param(query, "id").unwrap_or_default();
+
+    Ok(Value::Array(
+        routers
+            .iter()
+            .filter(|router| floodfill.is_none_or(|floodfill| router.floodfill == floodfill))
+            .filter(|router| router.router_id.starts_with(&prefix))
+            .map(netdb_router_json)
+            .collect(),
+    ))
+}
+
+/// `GET /api/netdb/{router_id}`
+async fn api_netdb_router(
+    State(state): State<RouterState>,
+    Path(router_id): Path<String>,
+) -> ApiResult {
+    state
+        .netdb_routers()
+        .await?
+        .iter()
+        .find(|router| router.router_id == router_id)
+        .map(|router| json_response(netdb_router_json(router)))
+        .ok_or(ApiError::not_found("router"))
+}
+
+/// Host added to an address book via the REST API.
+#[derive(Deserialize)]
+struct AddHost {
+    /// Hostname, e.g., `example.i2p`.
+    host: String,
+
+    /// Base64-encoded destination of the host.
+    destination: String,
+
+    /// Address book, defaults to the local address book.
+    book: Option<Book>,
+}
+
+/// Host renamed via the REST API.
+#[derive(Deserialize)]
+struct RenameHost {
+    /// New hostname.
+    host: String,
+
+    /// Address book, defaults to the local address book.
+    book: Option<Book>,
+}
+
+/// Get address book from the `?book=` query parameter, defaulting to the local address book.
+fn query_book(query: &Option<String>) -> Result<Book, ApiError> {
+    query_param(query, "book").map_or(Ok(Book::Local), |book| book.parse().map_err(From::from))
+}
+
+/// `GET /api/addressbook`
+async fn api_address_book(State(state): State<RouterState>) -> ApiResult {
+    let handle = state.handles.address_book.as_ref().ok_or(ApiError::disabled("address book"))?;
+    let hosts = |book| {
+        handle
+            .hosts(book)
+            .into_iter()
+            .map(|(host, destination)| json!({ "host": host, "destination": destination }))
+            .collect::<Vec<_>>()
+    };
+
+    Ok(json_response(json!({
+        "local": hosts(Book::Local),
+        "private": hosts(Book::Private),
+        "router": hosts(Book::Router),
+        "conflicts": handle.conflicts(),
+    })))
+}
+
+/// `GET /api/addressbook/export`
+///
+/// Local address book in `hosts.txt` format.
+async fn api_export_address_book(State(state): State<RouterState>) -> ApiResult {
+    let handle = state.handles.address_book.as_ref().ok_or(ApiError::disabled("address book"))?;
+
+    Ok(([(header::CONTENT_TYPE, "text/plain")], handle.export()).into_response())
+}
+
+/// `POST /api/addressbook`
+async fn api_add_host(State(state): State<RouterState>, body: String) -> ApiResult {
+    let handle = state.handles.address_book.as_ref().ok_or(ApiError::disabled("address book"))?;
+    let AddHost {
+        host,
+        destination,
+        book,
+    } = parse_body(&body)?;
+
+    handle
+        .add_to(book.unwrap_or(Book::Local), &host, &destination)
+        .await
+        .map_err(|error| match error {
+            Error::InvalidData => ApiError(StatusCode::BAD_REQUEST, "invalid destination".into()),
+            error => error.into(),
+        })?;
+
+    Ok(json_response(Value::Null))
+}
+
+/// `PUT /api/addressbook/{host}`
+///
+/// Rename a host of the local or private address book.
+async fn api_rename_host(
+    State(state): State<RouterState>,
+    Path(host): Path<String>,
+    body: String,
+) -> ApiResult {
+    let handle = state.handles.address_book.as_ref().ok_or(ApiError::disabled("address book"))?;
+    let RenameHost {
+        host: new_host,
+        book,
+    } = parse_body(&body)?;
+
+    match handle.rename(book.unwrap_or(Book::Local), &host, &new_host).await? {
+        true => Ok(json_response(Value::Null)),
+        false => Err(ApiError::not_found("host")),
+    }
+}
+
+/// `DELETE /api/addressbook/{host}`
+///
+/// Only hosts of the local and private address books can be removed. The address book can be
+/// specified with `?book=`.
+async fn api_remove_host(
+    State(state): State<RouterState>,
+    Path(host): Path<String>,
+    RawQuery(query): RawQuery,
+) -> ApiResult {
+    let handle = state.handles.address_book.as_ref().ok_or(ApiError::disabled("address book"))?;
+
+    match handle.remove(query_book(&query)?, &host).await? {
+        true => Ok(json_response(Value::Null)),
+        false => Err(ApiError::not_found("host")),
+    }
+}
+
+/// `GET /api/banlist`
+async fn api_banlist(State(state): State<RouterState>) -> ApiResult {
+    Ok(json_response(json!(state
+        .inner()?
+        .banlist
+        .iter()
+        .map(banned_peer_json)
+        .collect::<Vec<_>>())))
+}
+
+/// `GET /api/sybil`
+async fn api_sybil(State(state): State<RouterState>) -> ApiResult {
+    let report = state
+        .router()?
+        .sybil()
+        .await
+        .map_err(|error| ApiError(StatusCode::SERVICE_UNAVAILABLE, error.to_string()))?;
+
+    Ok(json_response(sybil_json(&report)))
+}
+
+/// `GET /api/news`
+async fn api_news(State(state): State<RouterState>) -> ApiResult {
+    Ok(json_response(json!(state
+        .inner()?
+        .news
+        .iter()
+        .map(news_item_json)
+        .collect::<Vec<_>>())))
+}
+
+/// `GET /api/logs`
+///
+/// The number of returned lines can be specified with `?lines=`.
+async fn api_logs(RawQuery(query): RawQuery) -> ApiResult {
+    let lines = match query_param(&query, "lines") {
+        None => DEFAULT_LOG_LINES,
+        Some(lines) => lines.parse::<usize>().map_err(|_| {
+            ApiError(
+                StatusCode::BAD_REQUEST,
+                format!("invalid line count: {lines}"),
+            )
+        })?,
+    };
+
+    Ok(json_response(json!(recent_logs(lines))))
+}
+
+/// Router configuration sent and received via the REST API.
+#[derive(Deserialize)]
+struct RouterConfig {
+    /// Contents of `router.toml`.
+    config: String,
+}
+
+/// `GET /api/config`
+async fn api_config(State(state): State<RouterState>) -> ApiResult {
+    let config = tokio::fs::read_to_string(state.handles.base_path.join("router.toml"))
+        .await
+        .map_err(|error| ApiError(StatusCode::INTERNAL_SERVER_ERROR, error.to_string()))?;
+
+    Ok(json_response(json!({ "config": config })))
+}
+
+/// `PUT /api/config`
+///
+/// The new configuration is validated before it's saved and if SAM has been enabled, the
+/// reloadable parts of the configuration are applied immediately. Other changes are applied when
+/// the router is restarted.
+async fn api_update_config(State(state): State<RouterState>, body: String) -> ApiResult {
+    let RouterConfig { config } = parse_body(&body)?;
+    Config::validate(&config)?;
+
+    // write the configuration into a temporary file first so a partial write cannot corrupt
+    // `router.toml`
+    let path = state.handles.base_path.join("router.toml");
+    let tmp_path = state.handles.base_path.join("router.toml.tmp");
+
+    tokio::fs::write(&tmp_path, config).await.map_err(Error::from)?;
+    tokio::fs::rename(&tmp_path, &path).await.map_err(Error::from)?;
+
+    tracing::info!(
+        target: LOG_TARGET,
+        "router config updated via web ui",
+    );
+
+    let reloaded = match &state.handles.reload {
+        Some(handle) => {
+            handle.reload().await?;
+            true
+        }
+        None => false,
+    };
+
+    Ok(json_response(json!({ "reloaded": reloaded })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{address_book::AddressBookManager, config::AddressBookConfig};
+    use emissary_core::router::handle::{CloseFloodfillInfo, FamilyInfo, IpClusterInfo};
+    use reqwest::Client;
+    use tempfile::tempdir;
+
+    const DESTINATION: &str = "GKapJ8koUcBj~jmQzHsTYxDg2tpfWj0xjQTzd8BhfC9c3OS5fwPBNajgF-eOD6eCjFTqTlorlh7Hnd8kXj1qblUGXT-tDoR9~YV8dmXl51cJn9MVTRrEqRWSJVXbUUz9t5Po6Xa247Vr0sJn27R4KoKP8QVj1GuH6dB3b6wTPbOamC3dkO18vkQkfZWUdRMDXk0d8AdjB0E0864nOT~J9Fpnd2pQE5uoFT6P0DqtQR2jsFvf9ME61aqLvKPPWpkgdn4z6Zkm-NJOcDz2Nv8Si7hli94E9SghMYRsdjU-knObKvxiagn84FIwcOpepxuG~kFXdD5NfsH0v6Uri3usE3XWD7Pw6P8qVYF39jUIq4OiNMwPnNYzy2N4mDMQdsdHO3LUVh~DEppOy9AAmEoHDjjJxt2BFBbGxfdpZCpENkwvmZeYUyNCCzASqTOOlNzdpne8cuesn3NDXIpNnqEE6Oe5Qm5YOJykrX~Vx~cFFT3QzDGkIjjxlFBsjUJyYkFjBQAEAAcAAA==";
+
+    /// Create [`RouterState`] with all optional handles disabled.
+    fn router_state(base_path: PathBuf, address_book: Option<AddressBookHandle>) -> RouterState {
+        let (shutdown_tx, _shutdown_rx) = tokio::sync::mpsc::channel(1);
+
+        RouterState::new(
+            ConsoleHandles {
+                address_book,
+                base_path,
+                client_tunnels: None,
+                reload: None,
+                router: None,
+                server_tunnels: None,
+            },
+            shutdown_tx,
+            Duration::from_secs(1),
+        )
+    }
+
+    /// Read JSON body of `response`.
+    async fn json(response: reqwest::Response) -> Value {
+        serde_json::from_str(&response.text().await.unwrap()).unwrap()
+    }
+
+    /// Serve the web UI for `state` and return its base URL.
+    async fn serve(state: RouterState) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        tokio::spawn(async move { axum::serve(listener, app(state, address.port())).await });
+
+        format!("http://{address}")
+    }
+
+    #[tokio::test]
+    async fn router_state_served_over_rest_api() {
+        let state = router_state(tempdir().unwrap().keep(), None);
+        {
+            let mut inner = state.state.lock().unwrap();
+
+            inner.on_event(Event::TunnelPools {
+                pools: vec![TunnelPoolInfo {
+                    name: String::from("exploratory"),
+                    num_inbound: 2,
+                    num_outbound: 3,
+                    num_tunnels_built: 10,
+                    num_tunnel_build_failures: 5,
+                }],
+            });
+            inner.on_event(Event::News {
+                items: vec![NewsItem {
+                    id: String::from("urn:uuid:1"),
+                    title: String::from("title"),
+                    link: Some(String::from("http://i2p-projekt.i2p/")),
+                    ..Default::default()
+                }],
+            });
+            inner.on_event(Event::Banlist {
+                peers: vec![
+                    BannedPeerInfo {
+                        peer: String::from("203.0.113.0/24"),
+                        reason: String::from("blocklist"),
+                        expires: None,
+                    },
+                    BannedPeerInfo {
+                        peer: String::from("198.51.100.1"),
+                        reason: String::from("invalid handshake"),
+                        expires: Some(900),
+                    },
+                ],
+            });
+        }
+        let url = serve(state).await;
+        let client = Client::new();
+
+        let status = json(client.get(format!("{url}/api/status")).send().await.unwrap()).await;
+        assert_eq!(status["status"], "Active");
+        assert_eq!(status["num_netdb_routers"], 0);
+
+        let tunnels = json(client.get(format!("{url}/api/tunnels")).send().await.unwrap()).await;
+        assert_eq!(tunnels["client"], json!([]));
+        assert_eq!(tunnels["pools"][0]["num_tunnels_built"], 10);
+        assert_eq!(tunnels["pools"][0]["num_tunnel_build_failures"], 5);
+
+        let news = json(client.get(format!("{url}/api/news")).send().await.unwrap()).await;
+        assert_eq!(news[0]["title"], "title");
+        assert_eq!(news[0]["link"], "http://i2p-projekt.i2p/");
+        assert_eq!(news[0]["summary"], Value::Null);
+
+        let banlist = json(client.get(format!("{url}/api/banlist")).send().await.unwrap()).await;
+        assert_eq!(banlist.as_array().unwrap().len(), 2);
+        assert_eq!(banlist[0]["expires"], Value::Null);
+        assert_eq!(banlist[1]["reason"], "invalid handshake");
+        assert_eq!(banlist[1]["expires"], 900);
+
+        // the network database is queried from the router on demand
+        for path in ["netdb", "netdb/router", "sybil", "routers"] {
+            let response = client.get(format!("{url}/api/{path}")).send().await.unwrap();
+            assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        }
+
+        // tunnels cannot be added without sam
+        let response = client
+            .post(format!("{url}/api/tunnels/client"))
+            .header(header::CONTENT_TYPE, "application/json")
+            .body("{}")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[test]
+    fn netdb_and_sybil_serialized() {
+        let routers = vec![
+            NetDbRouterInfo {
+                router_id: String::from("floodfill"),
+                capabilities: String::from("XfR"),
+                floodfill: true,
+                ..Default::default()
+            },
+            NetDbRouterInfo {
+                router_id: String::from("router"),
+                capabilities: String::from("LR"),
+                ..Default::default()
+            },
+        ];
+
+        let floodfills = netdb_json(&routers, &Some(String::from("floodfill=true"))).unwrap();
+        assert_eq!(floodfills.as_array().unwrap().len(), 1);
+        assert_eq!(floodfills[0]["capabilities"], "XfR");
+
+        let router = netdb_json(&routers, &Some(String::from("id=rou"))).unwrap();
+        assert_eq!(router.as_array().unwrap().len(), 1);
+        assert_eq!(router[0]["floodfill"], false);
+
+        assert_eq!(
+            netdb_json(&routers, &None).unwrap().as_array().unwrap().len(),
+            2
+        );
+        assert_eq!(
+            netdb_json(&routers, &Some(String::from("floodfill=maybe"))).unwrap_err().0,
+            StatusCode::BAD_REQUEST
+        );
+
+        let sybil = sybil_json(&SybilInfo {
+            num_ipv4_routers: 10,
+            num_ipv6_routers: 2,
+            clusters: vec![IpClusterInfo {
+                address: String::from("198.51.100.0/24"),
+                routers: vec![String::from("router"); 6],
+            }],
+            families: vec![FamilyInfo {
+                family: String::from("family"),
+                routers: vec![String::from("router"); 2],
+            }],
+            close_floodfills: vec![CloseFloodfillInfo {
+                router_id: String::from("floodfill"),
+                shared_bits: 20,
+            }],
+        });
+        assert_eq!(sybil["num_ipv4_routers"], 10);
+        assert_eq!(sybil["clusters"][0]["address"], "198.51.100.0/24");
+        assert_eq!(sybil["clusters"][0]["routers"].as_array().unwrap().len(), 6);
+        assert_eq!(sybil["families"][0]["family"], "family");
+        assert_eq!(sybil["families"][0]["routers"].as_array().unwrap().len(), 2);
+        assert_eq!(sybil["close_floodfills"][0]["shared_bits"], 20);
+    }
+
+    #[tokio::test]
+    async fn address_book_managed_over_rest_api() {
+        let dir = tempdir().unwrap().keep();
+        let handle = AddressBookManager::new(
+            dir.clone(),
+            AddressBookConfig {
+                default: None,
+                subscriptions: None,
+                refresh_interval: None,
+            },
+        )
+        .handle();
+        let url = serve(router_state(dir, Some(handle))).await;
+        let client = Client::new();
+
+        let response = client
+            .post(format!("{url}/api/addressbook"))
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(json!({ "host": "host.i2p", "destination": DESTINATION }).to_string())
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = client
+            .post(format!("{url}/api/addressbook"))
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(json!({ "host": "host2.i2p", "destination": "invalid" }).to_string())
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let hosts = json(client.get(format!("{url}/api/addressbook")).send().await.unwrap()).await;
+        assert_eq!(
+            hosts["local"],
+            json!([{ "host": "host.i2p", "destination": DESTINATION }])
+        );
+        assert_eq!(hosts["router"], json!([]));
+        assert_eq!(hosts["conflicts"], json!([]));
+
+        // private hosts are not exported
+        let response = client
+            .post(format!("{url}/api/addressbook"))
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(
+                json!({ "host": "secret.i2p", "destination": DESTINATION, "book": "private" })
+                    .to_string(),
+            )
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = client
+            .put(format!("{url}/api/addressbook/host.i2p"))
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(json!({ "host": "renamed.i2p" }).to_string())
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let export = client
+            .get(format!("{url}/api/addressbook/export"))
+            .send()
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        assert_eq!(export, format!("renamed.i2p={DESTINATION}\n"));
+
+        let response = client
+            .delete(format!("{url}/api/addressbook/secret.i2p?book=private"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = client
+            .delete(format!("{url}/api/addressbook/renamed.i2p"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = client
+            .delete(format!("{url}/api/addressbook/renamed.i2p"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let response = client
+            .delete(format!("{url}/api/addressbook/renamed.i2p?book=router"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn config_validated_before_saving() {
+        let dir = tempdir().unwrap().keep();
+        tokio::fs::write(dir.join("router.toml"), "floodfill = false\n").await.unwrap();
+
+        let url = serve(router_state(dir.clone(), None)).await;
+        let client = Client::new();
+
+        let config = json(client.get(format!("{url}/api/config")).send().await.unwrap()).await;
+        assert_eq!(config["config"], "floodfill = false\n");
+
+        let response = client
+            .put(format!("{url}/api/config"))
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(json!({ "config": "floodfill = \"yes\"\n" }).to_string())
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            tokio::fs::read_to_string(dir.join("router.toml")).await.unwrap(),
+            "floodfill = false\n"
+        );
+
+        let response = json(
+            client
+                .put(format!("{url}/api/config"))
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(json!({ "config": "floodfill = true\n" }).to_string())
+                .send()
+                .await
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(response["reloaded"], false);
+        assert_eq!(
+            tokio::fs::read_to_string(dir.join("router.toml")).await.unwrap(),
+            "floodfill = true\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn cross_site_requests_rejected() {
+        let (shutdown_tx, mut shutdown_rx) = tokio::sync::mpsc::channel(1);
+        let state = RouterState::new(
+            ConsoleHandles {
+                address_book: None,
+                base_path: tempdir().unwrap().keep(),
+                client_tunnels: None,
+                reload: None,
+                router: None,
+                server_tunnels: None,
+            },
+            shutdown_tx,
+            Duration::from_secs(1),
+        );
+        let url = serve(state).await;
+        let port = url.rsplit_once(':').unwrap().1;
+        let client = Client::new();
+
+        // simple request sent by another website
+        let response = client
+            .post(format!("{url}/api/shutdown"))
+            .header(header::CONTENT_TYPE, "text/plain")
+            .header(header::ORIGIN, "http://example.com")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        // dns rebinding
+        let response = client
+            .get(format!("{url}/api/config"))
+            .header(header::HOST, format!("example.com:{port}"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let response = client.post(format!("{url}/api/shutdown")).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        assert!(shutdown_rx.try_recv().is_err());
+
+        let response = client
+            .post(format!("{url}/api/shutdown"))
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::ORIGIN, format!("http://localhost:{port}"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(shutdown_rx.try_recv().is_ok());
+    }
+}
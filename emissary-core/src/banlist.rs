@@ -0,0 +1,611 @@
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Router banlist.
+//!
+//! Routers and IP addresses added to the banlist are neither dialed nor accepted, their router
+//! infos are not stored in the network database and they're not selected as tunnel hops.
+//!
+//! Routers and addresses are banned either permanently, e.g., through a blocklist, or temporarily
+//! after they've committed a protocol violation, such as sending a router info with an invalid
+//! signature or failing an inbound handshake.
+
+use crate::{
+    crypto::base64_encode,
+    error::Error,
+    primitives::{RouterId, RouterInfo},
+    router::handle::BannedPeerInfo,
+    runtime::{Instant, Runtime},
+};
+
+use hashbrown::{HashMap, HashSet};
+
+#[cfg(feature = "std")]
+use parking_lot::RwLock;
+#[cfg(feature = "no_std")]
+use spin::rwlock::RwLock;
+
+use alloc::{
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use core::{
+    fmt,
+    hash::Hash,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    str::FromStr,
+    time::Duration,
+};
+
+/// Logging target for the file.
+const LOG_TARGET: &str = "emissary::banlist";
+
+/// How long a router is banned for after it has committed a protocol violation.
+const PROTOCOL_VIOLATION_BAN_DURATION: Duration = Duration::from_secs(60 * 60);
+
+/// How long a router or an address is banned for after it has been found to be part of a
+/// different network.
+const NETWORK_MISMATCH_BAN_DURATION: Duration = Duration::from_secs(60 * 60);
+
+/// Maximum number of temporary bans kept for routers and for addresses.
+///
+/// If the limit is reached, the temporary ban closest to expiring is replaced.
+const MAX_TEMPORARY_BANS: usize = 10_000usize;
+
+/// Range of IP addresses, e.g., `203.0.113.0/24`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IpRange {
+    /// Network address of the range.
+    address: IpAddr,
+
+    /// Length of the network prefix.
+    prefix_len: u8,
+}
+
+impl IpRange {
+    /// Create new [`IpRange`] from `address` and `prefix_len`.
+    ///
+    /// Host bits of `address` are cleared. Returns `None` if `prefix_len` is too large for the
+    /// address family of `address`.
+    pub fn new(address: IpAddr, prefix_len: u8) -> Option<Self> {
+        let max_prefix_len = match address {
+            IpAddr::V4(_) => 32u8,
+            IpAddr::V6(_) => 128u8,
+        };
+
+        (prefix_len <= max_prefix_len).then(|| Self {
+            address: Self::mask(address, prefix_len),
+            prefix_len,
+        })
+    }
+
+    /// Clear all but the first `prefix_len` bits of `address`.
+    fn mask(address: IpAddr, prefix_len: u8) -> IpAddr {
+        match address {
+            IpAddr::V4(address) => {
+                let mask = u32::MAX.checked_shl(32 - prefix_len as u32).unwrap_or(0);
+
+                IpAddr::V4(Ipv4Addr::from(u32::from(address) & mask))
+            }
+            IpAddr::V6(address) => {
+                let mask = u128::MAX.checked_shl(128 - prefix_len as u32).unwrap_or(0);
+
+                IpAddr::V6(Ipv6Addr::from(u128::from(address) & mask))
+            }
+        }
+    }
+
+    /// Check if `address` belongs to the range.
+    pub fn contains(&self, address: &IpAddr) -> bool {
+        core::mem::discriminant(&self.address) == core::mem::discriminant(address)
+            && Self::mask(*address, self.prefix_len) == self.address
+    }
+}
+
+impl FromStr for IpRange {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (address, prefix_len) = value.split_once('/').ok_or(Error::InvalidData)?;
+        let address = address.parse::<IpAddr>().map_err(|_| Error::InvalidData)?;
+        let prefix_len = prefix_len.parse::<u8>().map_err(|_| Error::InvalidData)?;
+
+        Self::new(address, prefix_len).ok_or(Error::InvalidData)
+    }
+}
+
+impl fmt::Display for IpRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.address, self.prefix_len)
+    }
+}
+
+/// Blocklist entry.
+///
+/// Blocklist entries are received, e.g., from the news feed or read from a blocklist file and
+/// are applied to the banlist of the router with [`RouterHandle::apply_blocklist()`].
+///
+/// [`RouterHandle::apply_blocklist()`]: crate::router::handle::RouterHandle::apply_blocklist
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BlocklistEntry {
+    /// Block router.
+    BlockRouter(RouterId),
+
+    /// Block IP address.
+    BlockAddress(IpAddr),
+
+    /// Block range of IP addresses.
+    BlockRange(IpRange),
+
+    /// Unblock router.
+    UnblockRouter(RouterId),
+
+    /// Unblock IP address.
+    UnblockAddress(IpAddr),
+
+    /// Unblock range of IP addresses.
+    UnblockRange(IpRange),
+}
+
+/// Reason why a router or an address was banned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum BanReason {
+    /// Router or address was listed in a blocklist.
+    Blocklist,
+
+    /// Router sent a database store with an invalid signature.
+    InvalidSignature,
+
+    /// Router sent a database store whose key didn't match the stored router info.
+    InvalidStore,
+
+    /// Router is part of a different network.
+    NetworkMismatch,
+}
+
+impl BanReason {
+    /// How long the ban lasts for.
+    ///
+    /// `None` if the ban is permanent.
+    fn duration(&self) -> Option<Duration> {
+        match self {
+            Self::Blocklist => None,
+            Self::InvalidSignature | Self::InvalidStore => Some(PROTOCOL_VIOLATION_BAN_DURATION),
+            Self::NetworkMismatch => Some(NETWORK_MISMATCH_BAN_DURATION),
+        }
+    }
+}
+
+impl fmt::Display for BanReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Blocklist => write!(f, "blocklist"),
+            Self::InvalidSignature => write!(f, "invalid signature"),
+            Self::InvalidStore => write!(f, "invalid database store"),
+            Self::NetworkMismatch => write!(f, "network mismatch"),
+        }
+    }
+}
+
+/// Ban of a router or an address.
+struct Ban<R: Runtime> {
+    /// When the ban was issued and how long it lasts for.
+    ///
+    /// `None` if the ban is permanent.
+    expires: Option<(R::Instant, Duration)>,
+
+    /// Reason for the ban.
+    reason: BanReason,
+}
+
+impl<R: Runtime> Ban<R> {
+    /// Create new [`Ban`] for `reason`.
+    fn new(reason: BanReason) -> Self {
+        Self {
+            expires: reason.duration().map(|duration| (R::now(), duration)),
+            reason,
+        }
+    }
+
+    /// Get the remaining duration of the ban.
+    ///
+    /// `None` if the ban is permanent.
+    fn remaining(&self) -> Option<Duration> {
+        self.expires
+            .map(|(started, duration)| duration.saturating_sub(started.elapsed()))
+    }
+
+    /// Check if the ban is still active.
+    fn is_active(&self) -> bool {
+        self.remaining().is_none_or(|remaining| !remaining.is_zero())
+    }
+
+    /// Check if the ban is permanent.
+    fn is_permanent(&self) -> bool {
+        self.expires.is_none()
+    }
+}
+
+/// Router banlist.
+pub(crate) struct Banlist<R: Runtime> {
+    /// Banned IP addresses.
+    addresses: Arc<RwLock<HashMap<IpAddr, Ban<R>>>>,
+
+    /// Blocked IP address ranges.
+    ranges: Arc<RwLock<HashSet<IpRange>>>,
+
+    /// Banned routers.
+    routers: Arc<RwLock<HashMap<RouterId, Ban<R>>>>,
+}
+
+impl<R: Runtime> Clone for Banlist<R> {
+    fn clone(&self) -> Self {
+        Self {
+            addresses: Arc::clone(&self.addresses),
+            ranges: Arc::clone(&self.ranges),
+            routers: Arc::clone(&self.routers),
+        }
+    }
+}
+
+impl<R: Runtime> Default for Banlist<R> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<R: Runtime> Banlist<R> {
+    /// Create new [`Banlist`].
+    pub(crate) fn new() -> Self {
+        Self {
+            addresses: Default::default(),
+            ranges: Default::default(),
+            routers: Default::default(),
+        }
+    }
+
+    /// Apply blocklist `entries` to the banlist.
+    pub(crate) fn apply(&self, entries: Vec<BlocklistEntry>) {
+        let num_entries = entries.len();
+        let mut addresses = self.addresses.write();
+        let mut ranges = self.ranges.write();
+        let mut routers = self.routers.write();
+
+        for entry in entries {
+            match entry {
+                BlocklistEntry::BlockRouter(router_id) => {
+                    routers.insert(router_id, Ban::new(BanReason::Blocklist));
+                }
+                BlocklistEntry::BlockAddress(address) => {
+                    addresses.insert(address, Ban::new(BanReason::Blocklist));
+                }
+                BlocklistEntry::BlockRange(range) => {
+                    ranges.insert(range);
+                }
+                BlocklistEntry::UnblockRouter(router_id) => {
+                    routers.remove(&router_id);
+                }
+                BlocklistEntry::UnblockAddress(address) => {
+                    addresses.remove(&address);
+                }
+                BlocklistEntry::UnblockRange(range) => {
+                    ranges.remove(&range);
+                }
+            }
+        }
+
+        tracing::info!(
+            target: LOG_TARGET,
+            ?num_entries,
+            num_banned_routers = ?routers.len(),
+            num_blocked_addresses = ?addresses.len(),
+            num_blocked_ranges = ?ranges.len(),
+            "blocklist applied",
+        );
+    }
+
+    /// Insert ban for `key` into `bans`.
+    ///
+    /// Expired bans are pruned and temporary bans don't override a permanent ban. If there are
+    /// too many temporary bans, the one closest to expiring is removed.
+    ///
+    /// Returns `false` if `key` is already banned permanently.
+    fn insert<K: Eq + Hash + Clone>(
+        bans: &mut HashMap<K, Ban<R>>,
+        key: K,
+        reason: BanReason,
+    ) -> bool {
+        bans.retain(|_, ban| ban.is_active());
+
+        if bans.get(&key).is_some_and(|ban| ban.is_permanent()) {
+            return false;
+        }
+
+        if !bans.contains_key(&key)
+            && bans.values().filter(|ban| !ban.is_permanent()).count() >= MAX_TEMPORARY_BANS
+        {
+            if let Some(key) = bans
+                .iter()
+                .filter_map(|(key, ban)| ban.remaining().map(|remaining| (key, remaining)))
+                .min_by_key(|(_, remaining)| *remaining)
+                .map(|(key, _)| key.clone())
+            {
+                bans.remove(&key);
+            }
+        }
+
+        bans.insert(key, Ban::new(reason));
+        true
+    }
+
+    /// Ban `router_id` for `reason`.
+    ///
+    /// Temporary bans don't override a permanent ban of the router.
+    pub(crate) fn ban_router(&self, router_id: RouterId, reason: BanReason) {
+        if Self::insert(&mut self.routers.write(), router_id.clone(), reason) {
+            tracing::debug!(
+                target: LOG_TARGET,
+                %router_id,
+                %reason,
+                "ban router",
+            );
+        }
+    }
+
+    /// Ban `address` for `reason`.
+    ///
+    /// Temporary bans don't override a permanent ban of the address.
+    pub(crate) fn ban_address(&self, address: IpAddr, reason: BanReason) {
+        if Self::insert(&mut self.addresses.write(), address, reason) {
+            tracing::debug!(
+                target: LOG_TARGET,
+                ?address,
+                %reason,
+                "ban address",
+            );
+        }
+    }
+
+    /// Check if `router_id` is banned.
+    pub(crate) fn is_banned(&self, router_id: &RouterId) -> bool {
+        self.routers.read().get(router_id).is_some_and(|ban| ban.is_active())
+    }
+
+    /// Check if `address` is blocked.
+    pub(crate) fn is_blocked(&self, address: &IpAddr) -> bool {
+        if self.addresses.read().get(address).is_some_and(|ban| ban.is_active()) {
+            return true;
+        }
+
+        self.ranges.read().iter().any(|range| range.contains(address))
+    }
+
+    /// Check if the router of `router_info` is banned or if it has published a blocked address.
+    pub(crate) fn is_router_info_banned(&self, router_info: &RouterInfo) -> bool {
+        if self.is_banned(&router_info.identity.id()) {
+            return true;
+        }
+
+        router_info.addresses.values().any(|address| {
+            address
+                .socket_address
+                .is_some_and(|socket_address| self.is_blocked(&socket_address.ip()))
+        })
+    }
+
+    /// Get all active bans.
+    pub(crate) fn bans(&self) -> Vec<BannedPeerInfo> {
+        let info = |peer: String, ban: &Ban<R>| BannedPeerInfo {
+            peer,
+            reason: ban.reason.to_string(),
+            expires: ban.remaining().map(|remaining| remaining.as_secs()),
+        };
+
+        let routers = self.routers.read();
+        let addresses = self.addresses.read();
+        let ranges = self.ranges.read();
+
+        routers
+            .iter()
+            .filter(|(_, ban)| ban.is_active())
+            .map(|(router_id, ban)| info(base64_encode(router_id.to_vec()), ban))
+            .chain(
+                addresses
+                    .iter()
+                    .filter(|(_, ban)| ban.is_active())
+                    .map(|(address, ban)| info(address.to_string(), ban)),
+            )
+            .chain(ranges.iter().map(|range| BannedPeerInfo {
+                peer: range.to_string(),
+                reason: BanReason::Blocklist.to_string(),
+                expires: None,
+            }))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{primitives::RouterInfoBuilder, runtime::mock::MockRuntime, Ntcp2Config};
+
+    #[test]
+    fn block_and_unblock() {
+        let banlist = Banlist::<MockRuntime>::new();
+        let router_id = RouterId::random();
+        let address = "203.0.113.1".parse::<IpAddr>().unwrap();
+
+        assert!(!banlist.is_banned(&router_id));
+        assert!(!banlist.is_blocked(&address));
+
+        banlist.apply(vec![
+            BlocklistEntry::BlockRouter(router_id.clone()),
+            BlocklistEntry::BlockAddress(address),
+        ]);
+        assert!(banlist.is_banned(&router_id));
+        assert!(banlist.is_blocked(&address));
+
+        // ban is visible through all clones of the banlist
+        let other = banlist.clone();
+        other.apply(vec![BlocklistEntry::UnblockRouter(router_id.clone())]);
+        assert!(!banlist.is_banned(&router_id));
+        assert!(banlist.is_blocked(&address));
+
+        other.apply(vec![BlocklistEntry::UnblockAddress(address)]);
+        assert!(!banlist.is_blocked(&address));
+    }
+
+    #[test]
+    fn router_info_with_blocked_address() {
+        let banlist = Banlist::<MockRuntime>::new();
+        let (router_info, _, _) = RouterInfoBuilder::default()
+            .with_ntcp2(Ntcp2Config {
+                port: 8888,
+                host: Some("203.0.113.1".parse().unwrap()),
+                publish: true,
+                key: [0xbb; 32],
+                iv: [0xaa; 16],
+            })
+            .build();
+
+        assert!(!banlist.is_router_info_banned(&router_info));
+
+        banlist.apply(vec![BlocklistEntry::BlockAddress(
+            "203.0.113.2".parse().unwrap(),
+        )]);
+        assert!(!banlist.is_router_info_banned(&router_info));
+
+        banlist.apply(vec![BlocklistEntry::BlockAddress(
+            "203.0.113.1".parse().unwrap(),
+        )]);
+        assert!(banlist.is_router_info_banned(&router_info));
+
+        banlist.apply(vec![
+            BlocklistEntry::UnblockAddress("203.0.113.1".parse().unwrap()),
+            BlocklistEntry::BlockRouter(router_info.identity.id()),
+        ]);
+        assert!(banlist.is_router_info_banned(&router_info));
+
+        banlist.apply(vec![
+            BlocklistEntry::UnblockRouter(router_info.identity.id()),
+            BlocklistEntry::BlockRange("203.0.113.0/24".parse().unwrap()),
+        ]);
+        assert!(banlist.is_router_info_banned(&router_info));
+    }
+
+    #[test]
+    fn ip_ranges() {
+        let range = "203.0.113.77/24".parse::<IpRange>().unwrap();
+
+        assert_eq!(range.to_string(), "203.0.113.0/24");
+        assert!(range.contains(&"203.0.113.1".parse().unwrap()));
+        assert!(range.contains(&"203.0.113.255".parse().unwrap()));
+        assert!(!range.contains(&"203.0.114.1".parse().unwrap()));
+        assert!(!range.contains(&"::1".parse().unwrap()));
+
+        let range = "2001:db8::/32".parse::<IpRange>().unwrap();
+
+        assert!(range.contains(&"2001:db8:1234::1".parse().unwrap()));
+        assert!(!range.contains(&"2001:db9::1".parse().unwrap()));
+        assert!(!range.contains(&"203.0.113.1".parse().unwrap()));
+
+        // all addresses of the family
+        let range = "0.0.0.0/0".parse::<IpRange>().unwrap();
+        assert!(range.contains(&"198.51.100.1".parse().unwrap()));
+
+        assert!("203.0.113.0/33".parse::<IpRange>().is_err());
+        assert!("203.0.113.0".parse::<IpRange>().is_err());
+        assert!("example.com/24".parse::<IpRange>().is_err());
+    }
+
+    #[test]
+    fn temporary_ban_expires() {
+        let banlist = Banlist::<MockRuntime>::new();
+        let router_id = RouterId::random();
+        let address = "203.0.113.1".parse::<IpAddr>().unwrap();
+
+        banlist.ban_router(router_id.clone(), BanReason::InvalidSignature);
+        banlist.ban_address(address, BanReason::NetworkMismatch);
+        assert!(banlist.is_banned(&router_id));
+        assert!(banlist.is_blocked(&address));
+        assert_eq!(banlist.bans().len(), 2);
+        assert!(banlist.bans().iter().all(|info| info.expires.is_some()));
+
+        // shorten the address ban and wait until it expires
+        banlist.addresses.write().get_mut(&address).unwrap().expires =
+            Some((MockRuntime::now(), Duration::from_millis(50)));
+        std::thread::sleep(Duration::from_millis(100));
+
+        assert!(banlist.is_banned(&router_id));
+        assert!(!banlist.is_blocked(&address));
+        assert_eq!(banlist.bans().len(), 1);
+
+        // expired bans are pruned when new bans are added
+        banlist.ban_address("203.0.113.2".parse().unwrap(), BanReason::NetworkMismatch);
+        assert!(!banlist.addresses.read().contains_key(&address));
+    }
+
+    #[test]
+    fn temporary_ban_does_not_override_permanent_ban() {
+        let banlist = Banlist::<MockRuntime>::new();
+        let router_id = RouterId::random();
+
+        banlist.apply(vec![BlocklistEntry::BlockRouter(router_id.clone())]);
+        banlist.ban_router(router_id.clone(), BanReason::InvalidStore);
+        assert!(banlist.is_banned(&router_id));
+
+        match banlist.bans().as_slice() {
+            [info] => {
+                assert_eq!(info.reason, "blocklist");
+                assert_eq!(info.expires, None);
+            }
+            bans => panic!("invalid bans: {bans:?}"),
+        }
+    }
+
+    #[test]
+    fn temporary_bans_capped() {
+        let banlist = Banlist::<MockRuntime>::new();
+        let permanent = RouterId::random();
+        let expiring = RouterId::random();
+
+        banlist.apply(vec![BlocklistEntry::BlockRouter(permanent.clone())]);
+        banlist.ban_router(expiring.clone(), BanReason::InvalidStore);
+        banlist.routers.write().get_mut(&expiring).unwrap().expires =
+            Some((MockRuntime::now(), Duration::from_secs(60)));
+
+        {
+            let mut routers = banlist.routers.write();
+
+            for _ in 1..MAX_TEMPORARY_BANS {
+                routers.insert(RouterId::random(), Ban::new(BanReason::NetworkMismatch));
+            }
+        }
+        assert_eq!(banlist.routers.read().len(), MAX_TEMPORARY_BANS + 1);
+
+        // the ban closest to expiring is replaced by the new ban
+        let router_id = RouterId::random();
+        banlist.ban_router(router_id.clone(), BanReason::NetworkMismatch);
+
+        assert_eq!(banlist.routers.read().len(), MAX_TEMPORARY_BANS + 1);
+        assert!(banlist.is_banned(&router_id));
+        assert!(banlist.is_banned(&permanent));
+        assert!(!banlist.is_banned(&expiring));
+    }
+}
@@ -18,7 +18,7 @@
 
 use core::net::Ipv4Addr;
 
-use crate::{primitives::Str, profile::Profile, tunnel::TunnelPoolConfig};
+use crate::{banlist::BlocklistEntry, primitives::Str, profile::Profile, tunnel::TunnelPoolConfig};
 
 use alloc::{string::String, vec::Vec};
 
@@ -138,6 +138,9 @@ pub struct Config {
     /// Allow local addresses.
     pub allow_local: bool,
 
+    /// Blocklist entries applied to the router banlist on startup.
+    pub blocklist: Vec<BlocklistEntry>,
+
     /// Router capabilities.
     pub caps: Option<String>,
 
@@ -77,6 +77,13 @@ const STORAGE_VERIFICATION_TIMEOUT: Duration = Duration::from_secs(2);
 /// Once deemed as failed, local lease set is republished to `NetDb`.
 const STORAGE_VERIFICATION_TOTAL_TIMEOUT: Duration = Duration::from_secs(15);
 
+/// How many floodfills must have been queried for the lease set before a timed out storage
+/// verification is recorded as a failure in the profile of the floodfill it was stored to.
+///
+/// A failed lookup may be caused by the queried floodfill rather than the floodfill the lease set
+/// was stored to.
+const MIN_STORAGE_VERIFIERS: usize = 2usize;
+
 enum RetryKind<R: Runtime> {
     /// Get floodfills closet to [`Destination`].
     GetClosestFloodfills,
@@ -216,6 +223,11 @@ pub struct LeaseSetManager<R: Runtime> {
     /// [`LeaseSet2`] publish state.
     state: PublishState<R>,
 
+    /// Router ID of the floodfill the lease set was most recently stored to.
+    ///
+    /// Used to record the result of the storage verification in the floodfill's profile.
+    stored_floodfill: Option<RouterId>,
+
     /// Router IDs of the floodfills that unsuccessfully used to store the lease set.
     storage_floodfills: HashSet<RouterId>,
 
@@ -275,6 +287,7 @@ impl<R: Runtime> LeaseSetManager<R> {
             queried_floodfills: HashSet::new(),
             router_info_queries: R::join_set(),
             state,
+            stored_floodfill: None,
             storage_floodfills: HashSet::new(),
             tunnel_message_sender,
             tunnels: HashMap::from_iter(tunnels.into_iter().map(|lease| (lease.tunnel_id, lease))),
@@ -331,9 +344,14 @@ impl<R: Runtime> LeaseSetManager<R> {
                 tracing::debug!(
                     target: LOG_TARGET,
                     local = %self.destination_id,
+                    floodfill = ?self.stored_floodfill,
                     "lease set storage verified",
                 );
 
+                if let Some(floodfill) = self.stored_floodfill.take() {
+                    self.profile_storage.database_store_success(&floodfill);
+                }
+
                 self.state = PublishState::Inactive;
                 self.storage_floodfills.clear();
                 self.queried_floodfills.clear();
@@ -804,7 +822,8 @@ impl<R: Runtime> Future for LeaseSetManager<R> {
                         }
                         Ok(()) => {
                             self.storage_floodfills.insert(floodfill.clone());
-                            self.queried_floodfills.insert(floodfill);
+                            self.queried_floodfills.insert(floodfill.clone());
+                            self.stored_floodfill = Some(floodfill);
 
                             self.state = PublishState::AwaitingFlooding {
                                 timer: R::timer(STORAGE_VERIFICATION_START_TIMEOUT),
@@ -832,6 +851,26 @@ impl<R: Runtime> Future for LeaseSetManager<R> {
                             "lease set verification timed out, republishing lease set",
                         );
 
+                        if let Some(floodfill) = self.stored_floodfill.take() {
+                            let num_verifiers = self
+                                .queried_floodfills
+                                .iter()
+                                .filter(|router_id| *router_id != &floodfill)
+                                .count();
+
+                            if num_verifiers >= MIN_STORAGE_VERIFIERS {
+                                self.profile_storage.database_store_failure(&floodfill);
+                            } else {
+                                tracing::debug!(
+                                    target: LOG_TARGET,
+                                    local = %self.destination_id,
+                                    %floodfill,
+                                    ?num_verifiers,
+                                    "too few verifiers queried, not recording store failure",
+                                );
+                            }
+                        }
+
                         self.queried_floodfills.clear();
                         self.state = PublishState::PublishLeaseSet;
                         continue;
@@ -994,6 +1033,7 @@ mod tests {
         },
         netdb::NetDbAction,
         primitives::{LeaseSet2, RouterInfo, RouterInfoBuilder},
+        profile::Bucket,
         runtime::mock::MockRuntime,
         tunnel::{
             DeliveryInstructions as GarlicDeliveryInstructions, GarlicHandler, TunnelMessage,
@@ -1016,6 +1056,9 @@ mod tests {
         let destination_id = lease_set.header.destination.id();
         let key = Bytes::from(destination_id.to_vec());
         let serialized = lease_set.serialize(&signing_key);
+        let profile_storage = ProfileStorage::<MockRuntime>::from_random(
+            (0..3).map(|_| RouterInfoBuilder::default().as_floodfill().build().0).collect(),
+        );
         let mut manager = LeaseSetManager::<MockRuntime>::new(
             tunnels,
             destination_id,
@@ -1023,18 +1066,15 @@ mod tests {
             3usize,
             netdb_handle,
             noise_ctx,
-            ProfileStorage::new(&[], &[]),
+            profile_storage.clone(),
             false,
             Bytes::from(serialized),
         );
 
-        let mut floodfills = (0..3)
-            .map(|_| {
-                (
-                    RouterId::random(),
-                    StaticPrivateKey::random(MockRuntime::rng()),
-                )
-            })
+        let mut floodfills = profile_storage
+            .get_router_ids(Bucket::Any, |_, _, _| true)
+            .into_iter()
+            .map(|router_id| (router_id, StaticPrivateKey::random(MockRuntime::rng())))
             .collect::<HashMap<_, _>>();
 
         assert!(std::matches!(
@@ -1062,7 +1102,7 @@ mod tests {
             }
         }
 
-        loop {
+        let stored_floodfill = loop {
             tokio::select! {
                 _ = &mut manager => {}
                 event = tm_rx.recv() => match event.unwrap() {
@@ -1103,13 +1143,13 @@ mod tests {
                             _ => panic!("invalid message type"),
                         }
 
-                        break
+                        break router_id;
                     }
                     _ => panic!("unexpected tunnel message"),
                 },
                 _ = tokio::time::sleep(Duration::from_secs(5)) => panic!("timeout"),
             }
-        }
+        };
 
         loop {
             tokio::select! {
@@ -1175,6 +1215,12 @@ mod tests {
         }
 
         assert!(std::matches!(manager.state, PublishState::Inactive));
+
+        // verified store is recorded in the profile of the floodfill
+        let reader = profile_storage.reader();
+        let profile = reader.profile(&stored_floodfill).unwrap();
+        assert_eq!(profile.num_store_successes, 1);
+        assert_eq!(profile.num_store_failures, 0);
     }
 
     #[tokio::test]
@@ -1373,6 +1419,9 @@ mod tests {
         let destination_id = lease_set.header.destination.id();
         let key = Bytes::from(destination_id.to_vec());
         let serialized = lease_set.serialize(&signing_key);
+        let profile_storage = ProfileStorage::<MockRuntime>::from_random(
+            (0..3).map(|_| RouterInfoBuilder::default().as_floodfill().build().0).collect(),
+        );
         let mut manager = LeaseSetManager::<MockRuntime>::new(
             tunnels,
             destination_id,
@@ -1380,18 +1429,15 @@ mod tests {
             3usize,
             netdb_handle,
             noise_ctx,
-            ProfileStorage::new(&[], &[]),
+            profile_storage.clone(),
             false,
             Bytes::from(serialized),
         );
 
-        let floodfills = (0..3)
-            .map(|_| {
-                (
-                    RouterId::random(),
-                    StaticPrivateKey::random(MockRuntime::rng()),
-                )
-            })
+        let floodfills = profile_storage
+            .get_router_ids(Bucket::Any, |_, _, _| true)
+            .into_iter()
+            .map(|router_id| (router_id, StaticPrivateKey::random(MockRuntime::rng())))
             .collect::<HashMap<_, _>>();
         let mut lookup_floodfills = floodfills.clone();
 
@@ -1548,6 +1594,183 @@ mod tests {
         }
 
         assert!(std::matches!(manager.state, PublishState::Inactive));
+
+        // the failed store is recorded in the profile of the first floodfill and the verified
+        // store in the profile of the floodfill used for the second store
+        let reader = profile_storage.reader();
+        let profile = reader.profile(&selected_floodfill).unwrap();
+        assert_eq!(profile.num_store_failures, 1);
+        assert_eq!(profile.num_store_successes, 0);
+        assert_eq!(
+            floodfills
+                .keys()
+                .map(|router_id| reader.profile(router_id).unwrap().num_store_successes)
+                .sum::<usize>(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn store_failure_not_recorded_with_one_verifier() {
+        let (tp_handle, tm_rx, _tp_tx, _srx) = TunnelPoolHandle::create();
+        let sender = tp_handle.sender();
+        let (netdb_handle, netdb_rx) = NetDbHandle::create();
+        let noise_ctx = NoiseContext::new(
+            StaticPrivateKey::random(MockRuntime::rng()),
+            Bytes::from(RouterId::random().to_vec()),
+        );
+        let (lease_set, signing_key) = LeaseSet2::random();
+        let tunnels = lease_set.leases.clone();
+        let destination_id = lease_set.header.destination.id();
+        let serialized = lease_set.serialize(&signing_key);
+        let profile_storage = ProfileStorage::<MockRuntime>::from_random(
+            (0..2).map(|_| RouterInfoBuilder::default().as_floodfill().build().0).collect(),
+        );
+        let mut manager = LeaseSetManager::<MockRuntime>::new(
+            tunnels,
+            destination_id,
+            sender,
+            3usize,
+            netdb_handle,
+            noise_ctx,
+            profile_storage.clone(),
+            false,
+            Bytes::from(serialized),
+        );
+
+        let floodfills = profile_storage
+            .get_router_ids(Bucket::Any, |_, _, _| true)
+            .into_iter()
+            .map(|router_id| (router_id, StaticPrivateKey::random(MockRuntime::rng())))
+            .collect::<HashMap<_, _>>();
+
+        assert!(std::matches!(
+            manager.state,
+            PublishState::GetClosestFloodfills { .. }
+        ));
+
+        // process the initial floodfill request
+        loop {
+            tokio::select! {
+                _ = &mut manager => {}
+                event = netdb_rx.recv() => match event.unwrap() {
+                    NetDbAction::GetClosestFloodfills { tx, .. } => {
+                        tx.send(
+                            floodfills
+                                .iter()
+                                .map(|(router_id, key)| (router_id.clone(), key.public()))
+                                .collect(),
+                        )
+                        .unwrap();
+                        break;
+                    }
+                    _ => panic!("invalid action received"),
+                },
+                _ = tokio::time::sleep(Duration::from_secs(40)) => panic!("timeout"),
+            }
+        }
+
+        // process initial database store message
+        let selected_floodfill = loop {
+            tokio::select! {
+                _ = &mut manager => {}
+                event = tm_rx.recv() => match event.unwrap() {
+                    TunnelMessage::RouterDeliveryViaRoute {
+                        outbound_tunnel: None,
+                        router_id,
+                        message,
+                    } => {
+                        let message = Message::parse_standard(&message).unwrap();
+                        assert_eq!(message.message_type, MessageType::Garlic);
+
+                        let key = floodfills.get(&router_id).unwrap().clone();
+
+                        let mut garlic = GarlicHandler::<MockRuntime>::new(
+                            NoiseContext::new(key, Bytes::from(router_id.to_vec())),
+                            MockRuntime::register_metrics(vec![], None),
+                        );
+                        let GarlicDeliveryInstructions::Local { message } = garlic
+                            .handle_message(message)
+                            .unwrap()
+                            .filter(|message| {
+                                std::matches!(message, GarlicDeliveryInstructions::Local { .. })
+                            })
+                            .collect::<VecDeque<_>>()
+                            .pop_front()
+                            .expect("to exist")
+                        else {
+                            panic!("invalid type");
+                        };
+
+                        match message.message_type {
+                            MessageType::DatabaseStore => {
+                                let DatabaseStore { .. } =
+                                    DatabaseStore::<MockRuntime>::parse(&message.payload).unwrap();
+
+                                break router_id;
+                            }
+                            _ => panic!("invalid message type"),
+                        }
+                    }
+                    _ => panic!("unexpected tunnel message"),
+                },
+                _ = tokio::time::sleep(Duration::from_secs(5)) => panic!("timeout"),
+            }
+        };
+
+        // the only other floodfill is queried once and its lookup is ignored which causes the
+        // verification to time out and the lease set to be republished
+        let republished_floodfill = loop {
+            tokio::select! {
+                _ = &mut manager => {}
+                event = tm_rx.recv() => match event.unwrap() {
+                    TunnelMessage::RouterDeliveryViaRoute {
+                        outbound_tunnel: None,
+                        router_id,
+                        message,
+                    } => {
+                        let message = Message::parse_standard(&message).unwrap();
+                        assert_eq!(message.message_type, MessageType::Garlic);
+
+                        let static_key = floodfills.get(&router_id).unwrap();
+
+                        let mut garlic = GarlicHandler::<MockRuntime>::new(
+                            NoiseContext::new(static_key.clone(), Bytes::from(router_id.to_vec())),
+                            MockRuntime::register_metrics(vec![], None),
+                        );
+                        let GarlicDeliveryInstructions::Local { message } = garlic
+                            .handle_message(message)
+                            .unwrap()
+                            .filter(|message| {
+                                std::matches!(message, GarlicDeliveryInstructions::Local { .. })
+                            })
+                            .collect::<VecDeque<_>>()
+                            .pop_front()
+                            .expect("to exist")
+                        else {
+                            panic!("invalid type");
+                        };
+
+                        match message.message_type {
+                            MessageType::DatabaseLookup => {
+                                assert_ne!(selected_floodfill, router_id);
+                            }
+                            MessageType::DatabaseStore => break router_id,
+                            _ => panic!("invalid message type"),
+                        }
+                    }
+                    _ => panic!("invalid event"),
+                },
+                _ = tokio::time::sleep(Duration::from_secs(40)) => panic!("timeout"),
+            }
+        };
+        assert_ne!(selected_floodfill, republished_floodfill);
+
+        // only one floodfill verified the store so the failure is not recorded
+        let reader = profile_storage.reader();
+        let profile = reader.profile(&selected_floodfill).unwrap();
+        assert_eq!(profile.num_store_failures, 0);
+        assert_eq!(profile.num_store_successes, 0);
     }
 
     #[tokio::test]
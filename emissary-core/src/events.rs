@@ -16,9 +16,17 @@
 // FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
 // DEALINGS IN THE SOFTWARE.
 
-use crate::runtime::Runtime;
+use crate::{
+    i2np::HopRole,
+    primitives::TunnelId,
+    router::handle::{
+        BannedPeerInfo, DestinationInfo, NewsItem, RouterStatus, TransitTunnelInfo, TunnelPoolInfo,
+    },
+    runtime::Runtime,
+};
 
 use futures::FutureExt;
+use hashbrown::HashMap;
 use thingbuf::mpsc::{channel, Receiver, Sender};
 
 use alloc::{string::String, sync::Arc, vec::Vec};
@@ -34,6 +42,12 @@ use core::{
 /// Default update interval.
 const UPDATE_INTERVAL: Duration = Duration::from_secs(10);
 
+/// Channel size for subsystem events.
+///
+/// Each tunnel pool reports its status on every update so the channel must be large enough to
+/// accommodate several pools.
+const EVENT_CHANNEL_SIZE: usize = 256usize;
+
 /// Events emitted by [`EventSubscriber`].
 #[derive(Debug, Clone)]
 enum SubsystemEvent {
@@ -51,6 +65,48 @@ enum SubsystemEvent {
         /// Address of the destination.
         address: String,
     },
+
+    /// Destination has been stopped.
+    DestinationStopped {
+        /// Name of the destination.
+        name: String,
+    },
+
+    /// Active transit tunnels.
+    TransitTunnels {
+        /// Tunnel IDs and roles.
+        tunnels: Vec<(TunnelId, HopRole)>,
+    },
+
+    /// Tunnel pool status.
+    TunnelPoolStatus {
+        /// Name of the tunnel pool.
+        name: String,
+
+        /// Number of inbound tunnels.
+        num_inbound: usize,
+
+        /// Number of outbound tunnels.
+        num_outbound: usize,
+
+        /// Number of tunnels built since the previous update.
+        num_tunnels_built: usize,
+
+        /// Number of tunnel build failures since the previous update.
+        num_tunnel_build_failures: usize,
+    },
+
+    /// Banned routers and addresses.
+    Banlist {
+        /// Banned peers.
+        peers: Vec<BannedPeerInfo>,
+    },
+
+    /// Tunnel pool has been shut down.
+    TunnelPoolShutDown {
+        /// Name of the tunnel pool.
+        name: String,
+    },
 }
 
 impl Default for SubsystemEvent {
@@ -161,6 +217,47 @@ impl<R: Runtime> EventHandle<R> {
     pub(crate) fn client_destination_started(&self, name: String) {
         let _ = self.event_tx.try_send(SubsystemEvent::ClientDestinationStarted { name });
     }
+
+    /// Inform the event system that a destination has been stopped.
+    pub(crate) fn destination_stopped(&self, name: String) {
+        let _ = self.event_tx.try_send(SubsystemEvent::DestinationStopped { name });
+    }
+
+    /// Update the set of active transit tunnels.
+    pub(crate) fn transit_tunnels(&self, tunnels: Vec<(TunnelId, HopRole)>) {
+        let _ = self.event_tx.try_send(SubsystemEvent::TransitTunnels { tunnels });
+    }
+
+    /// Update status of a tunnel pool.
+    ///
+    /// `num_tunnels_built` and `num_tunnel_build_failures` are the build results since the
+    /// previous update and are accumulated by [`EventManager`].
+    pub(crate) fn tunnel_pool_status(
+        &self,
+        name: String,
+        num_inbound: usize,
+        num_outbound: usize,
+        num_tunnels_built: usize,
+        num_tunnel_build_failures: usize,
+    ) {
+        let _ = self.event_tx.try_send(SubsystemEvent::TunnelPoolStatus {
+            name,
+            num_inbound,
+            num_outbound,
+            num_tunnels_built,
+            num_tunnel_build_failures,
+        });
+    }
+
+    /// Update the set of banned routers and addresses.
+    pub(crate) fn banlist(&self, peers: Vec<BannedPeerInfo>) {
+        let _ = self.event_tx.try_send(SubsystemEvent::Banlist { peers });
+    }
+
+    /// Inform the event system that a tunnel pool has been shut down.
+    pub(crate) fn tunnel_pool_shut_down(&self, name: String) {
+        let _ = self.event_tx.try_send(SubsystemEvent::TunnelPoolShutDown { name });
+    }
 }
 
 impl<R: Runtime> Future for EventHandle<R> {
@@ -250,6 +347,36 @@ pub enum Event {
         tunnel: TunnelStatus,
     },
 
+    /// Active client and server destinations.
+    Destinations {
+        /// Destinations.
+        destinations: Vec<DestinationInfo>,
+    },
+
+    /// Active transit tunnels.
+    TransitTunnels {
+        /// Transit tunnels.
+        tunnels: Vec<TransitTunnelInfo>,
+    },
+
+    /// Active tunnel pools.
+    TunnelPools {
+        /// Tunnel pools and their build statistics.
+        pools: Vec<TunnelPoolInfo>,
+    },
+
+    /// Banned routers and addresses.
+    Banlist {
+        /// Banned peers.
+        peers: Vec<BannedPeerInfo>,
+    },
+
+    /// News items, e.g., entries of the router news feed.
+    News {
+        /// News items.
+        items: Vec<NewsItem>,
+    },
+
     /// Router is shutting down.
     ShuttingDown,
 
@@ -272,12 +399,21 @@ enum State {
 
 /// Event manager.
 pub(crate) struct EventManager<R: Runtime> {
+    /// Banned routers and addresses.
+    banlist: Vec<BannedPeerInfo>,
+
+    /// Active destinations and their addresses, if they're server destinations.
+    destinations: HashMap<String, Option<String>>,
+
     /// RX channel for receiving events from other subsystems.
     event_rx: Receiver<SubsystemEvent>,
 
     /// Event handle.
     handle: EventHandle<R>,
 
+    /// News items.
+    news: Vec<NewsItem>,
+
     /// Pending client destinatin updates.
     pending_client_updates: Vec<String>,
 
@@ -292,6 +428,12 @@ pub(crate) struct EventManager<R: Runtime> {
 
     /// Update timer.
     timer: R::Timer,
+
+    /// Active transit tunnels.
+    transit_tunnels: Vec<(TunnelId, HopRole)>,
+
+    /// Active tunnel pools.
+    tunnel_pools: HashMap<String, TunnelPoolInfo>,
 }
 
 impl<R: Runtime> EventManager<R> {
@@ -299,7 +441,7 @@ impl<R: Runtime> EventManager<R> {
     pub(crate) fn new(
         update_interval: Option<Duration>,
     ) -> (Self, EventSubscriber, EventHandle<R>) {
-        let (event_tx, event_rx) = channel(64);
+        let (event_tx, event_rx) = channel(EVENT_CHANNEL_SIZE);
         let (status_tx, status_rx) = channel(64);
         let update_interval = update_interval.unwrap_or(UPDATE_INTERVAL);
         let handle = EventHandle {
@@ -316,6 +458,8 @@ impl<R: Runtime> EventManager<R> {
 
         (
             Self {
+                banlist: Vec::new(),
+                destinations: HashMap::new(),
                 event_rx,
                 state: State::Active,
                 handle: EventHandle {
@@ -329,16 +473,64 @@ impl<R: Runtime> EventManager<R> {
                     update_interval,
                     timer: None,
                 },
+                news: Vec::new(),
                 pending_client_updates: Vec::new(),
                 pending_server_updates: Vec::new(),
                 status_tx,
                 timer: R::timer(update_interval),
+                transit_tunnels: Vec::new(),
+                tunnel_pools: HashMap::new(),
             },
             EventSubscriber { status_rx },
             handle,
         )
     }
 
+    /// Get active transit tunnels.
+    fn transit_tunnels(&self) -> Vec<TransitTunnelInfo> {
+        self.transit_tunnels
+            .iter()
+            .map(|(tunnel_id, role)| TransitTunnelInfo {
+                tunnel_id: *tunnel_id,
+                role: *role,
+            })
+            .collect()
+    }
+
+    /// Get active destinations.
+    fn destinations(&self) -> Vec<DestinationInfo> {
+        self.destinations
+            .iter()
+            .map(|(name, address)| DestinationInfo {
+                name: name.clone(),
+                address: address.clone(),
+            })
+            .collect()
+    }
+
+    /// Get snapshot of router's status.
+    ///
+    /// `connected_routers` are the base64-encoded router hashes of currently connected routers.
+    pub(crate) fn router_status(&self, connected_routers: Vec<String>) -> RouterStatus {
+        RouterStatus {
+            connected_routers,
+            transit_tunnels: self.transit_tunnels(),
+            tunnel_pools: self.tunnel_pools.values().cloned().collect(),
+            destinations: self.destinations(),
+        }
+    }
+
+    /// Store news `items` and report them to [`EventSubscriber`].
+    ///
+    /// The items are reported again on every status update so that UIs started later also
+    /// receive them.
+    pub(crate) fn news(&mut self, items: Vec<NewsItem>) {
+        let _ = self.status_tx.try_send(Event::News {
+            items: items.clone(),
+        });
+        self.news = items;
+    }
+
     /// Send shutdown signal to [`EventSubscriber`].
     pub(crate) fn shutdown(&mut self) {
         match self.state {
@@ -366,11 +558,43 @@ impl<R: Runtime> Future for EventManager<R> {
                 Poll::Pending => break,
                 Poll::Ready(None) => return Poll::Ready(()),
                 Poll::Ready(Some(SubsystemEvent::ClientDestinationStarted { name })) => {
+                    self.destinations.insert(name.clone(), None);
                     self.pending_client_updates.push(name);
                 }
                 Poll::Ready(Some(SubsystemEvent::ServerDestinationStarted { name, address })) => {
+                    self.destinations.insert(name.clone(), Some(address.clone()));
                     self.pending_server_updates.push((name, address));
                 }
+                Poll::Ready(Some(SubsystemEvent::DestinationStopped { name })) => {
+                    self.destinations.remove(&name);
+                }
+                Poll::Ready(Some(SubsystemEvent::TransitTunnels { tunnels })) => {
+                    self.transit_tunnels = tunnels;
+                }
+                Poll::Ready(Some(SubsystemEvent::TunnelPoolStatus {
+                    name,
+                    num_inbound,
+                    num_outbound,
+                    num_tunnels_built,
+                    num_tunnel_build_failures,
+                })) => {
+                    let pool =
+                        self.tunnel_pools.entry(name.clone()).or_insert_with(|| TunnelPoolInfo {
+                            name,
+                            ..Default::default()
+                        });
+
+                    pool.num_inbound = num_inbound;
+                    pool.num_outbound = num_outbound;
+                    pool.num_tunnels_built += num_tunnels_built;
+                    pool.num_tunnel_build_failures += num_tunnel_build_failures;
+                }
+                Poll::Ready(Some(SubsystemEvent::Banlist { peers })) => {
+                    self.banlist = peers;
+                }
+                Poll::Ready(Some(SubsystemEvent::TunnelPoolShutDown { name })) => {
+                    self.tunnel_pools.remove(&name);
+                }
             }
         }
 
@@ -400,6 +624,24 @@ impl<R: Runtime> Future for EventManager<R> {
                 server_destinations,
                 client_destinations,
             });
+            let _ = self.status_tx.try_send(Event::Destinations {
+                destinations: self.destinations(),
+            });
+            let _ = self.status_tx.try_send(Event::TransitTunnels {
+                tunnels: self.transit_tunnels(),
+            });
+            let _ = self.status_tx.try_send(Event::TunnelPools {
+                pools: self.tunnel_pools.values().cloned().collect(),
+            });
+            let _ = self.status_tx.try_send(Event::Banlist {
+                peers: self.banlist.clone(),
+            });
+
+            if !self.news.is_empty() {
+                let _ = self.status_tx.try_send(Event::News {
+                    items: self.news.clone(),
+                });
+            }
 
             self.timer = R::timer(self.handle.update_interval);
             let _ = self.timer.poll_unpin(cx);
@@ -440,4 +682,148 @@ mod tests {
             assert!(tokio::time::timeout(Duration::from_secs(5), &mut new_handle).await.is_ok());
         }
     }
+
+    #[tokio::test]
+    async fn router_status_tracks_subsystem_events() {
+        let (mut manager, _subscriber, handle) =
+            EventManager::<MockRuntime>::new(Some(Duration::from_secs(1)));
+
+        handle.client_destination_started("client".to_string());
+        handle.server_destination_started("server".to_string(), "address".to_string());
+        handle.transit_tunnels(vec![(TunnelId::random(), HopRole::Participant)]);
+        handle.tunnel_pool_status("client".to_string(), 2, 3, 0, 0);
+        handle.tunnel_pool_status("server".to_string(), 1, 1, 0, 0);
+
+        assert!(futures::poll!(&mut manager).is_pending());
+
+        let status = manager.router_status(vec!["router".to_string()]);
+        assert_eq!(status.connected_routers, vec!["router".to_string()]);
+        assert_eq!(status.transit_tunnels.len(), 1);
+        assert_eq!(status.tunnel_pools.len(), 2);
+        assert_eq!(status.destinations.len(), 2);
+        assert!(status
+            .destinations
+            .iter()
+            .any(|info| info.name == "server" && info.address.as_deref() == Some("address")));
+
+        handle.destination_stopped("server".to_string());
+        handle.tunnel_pool_shut_down("server".to_string());
+
+        assert!(futures::poll!(&mut manager).is_pending());
+
+        let status = manager.router_status(Vec::new());
+        assert_eq!(status.tunnel_pools.len(), 1);
+        assert_eq!(status.tunnel_pools[0].num_inbound, 2);
+        assert_eq!(status.tunnel_pools[0].num_outbound, 3);
+        assert_eq!(status.destinations.len(), 1);
+        assert_eq!(status.destinations[0].name, "client");
+    }
+
+    #[tokio::test]
+    async fn router_details_emitted_on_update() {
+        let (mut manager, mut subscriber, handle) =
+            EventManager::<MockRuntime>::new(Some(Duration::from_secs(1)));
+
+        handle.server_destination_started("server".to_string(), "address".to_string());
+        handle.transit_tunnels(vec![(TunnelId::random(), HopRole::Participant)]);
+        handle.tunnel_pool_status("client".to_string(), 2, 2, 4, 1);
+        handle.tunnel_pool_status("client".to_string(), 3, 2, 2, 3);
+        handle.banlist(vec![BannedPeerInfo {
+            peer: "203.0.113.1".to_string(),
+            reason: "invalid handshake".to_string(),
+            expires: Some(60),
+        }]);
+
+        // wait until the update timer fires
+        tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                let _ = futures::poll!(&mut manager);
+
+                if subscriber.status_rx.len() >= 5 {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        })
+        .await
+        .expect("no timeout");
+
+        assert!(std::matches!(
+            subscriber.router_status(),
+            Some(Event::RouterStatus { .. })
+        ));
+
+        match subscriber.router_status() {
+            Some(Event::Destinations { destinations }) => {
+                assert_eq!(destinations.len(), 1);
+                assert_eq!(destinations[0].address.as_deref(), Some("address"));
+            }
+            event => panic!("invalid event: {event:?}"),
+        }
+
+        match subscriber.router_status() {
+            Some(Event::TransitTunnels { tunnels }) => {
+                assert_eq!(tunnels.len(), 1);
+                assert_eq!(tunnels[0].role, HopRole::Participant);
+            }
+            event => panic!("invalid event: {event:?}"),
+        }
+
+        // build statistics are accumulated over updates
+        match subscriber.router_status() {
+            Some(Event::TunnelPools { pools }) => {
+                assert_eq!(pools.len(), 1);
+                assert_eq!(pools[0].name, "client");
+                assert_eq!(pools[0].num_inbound, 3);
+                assert_eq!(pools[0].num_tunnels_built, 6);
+                assert_eq!(pools[0].num_tunnel_build_failures, 4);
+            }
+            event => panic!("invalid event: {event:?}"),
+        }
+
+        match subscriber.router_status() {
+            Some(Event::Banlist { peers }) => {
+                assert_eq!(peers.len(), 1);
+                assert_eq!(peers[0].expires, Some(60));
+            }
+            event => panic!("invalid event: {event:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn news_emitted_on_update() {
+        let (mut manager, mut subscriber, _handle) =
+            EventManager::<MockRuntime>::new(Some(Duration::from_secs(1)));
+
+        manager.news(vec![NewsItem {
+            id: "id".to_string(),
+            title: "title".to_string(),
+            ..Default::default()
+        }]);
+
+        match subscriber.router_status() {
+            Some(Event::News { items }) => {
+                assert_eq!(items.len(), 1);
+                assert_eq!(items[0].title, "title");
+            }
+            event => panic!("invalid event: {event:?}"),
+        }
+
+        // news items are reported again on the next update
+        tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                let _ = futures::poll!(&mut manager);
+
+                if subscriber.status_rx.len() >= 6 {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        })
+        .await
+        .expect("no timeout");
+
+        assert!(std::iter::from_fn(|| subscriber.router_status())
+            .any(|event| std::matches!(event, Event::News { items } if items[0].id == "id")));
+    }
 }
@@ -0,0 +1,75 @@
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::i2cp::message::{MessageType, I2CP_HEADER_SIZE};
+
+use bytes::{BufMut, BytesMut};
+
+/// Message status kind.
+///
+/// Only the statuses emissary sends to clients are listed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageStatusKind {
+    /// Message is available for reception.
+    ///
+    /// Only used if the client has disabled `i2cp.fastReceive`.
+    Available,
+
+    /// Message has been accepted by the router.
+    Accepted,
+}
+
+impl MessageStatusKind {
+    /// Serialize [`MessageStatusKind`].
+    fn as_u8(&self) -> u8 {
+        match self {
+            Self::Available => 0,
+            Self::Accepted => 1,
+        }
+    }
+}
+
+/// `MessageStatus` message.
+///
+/// https://geti2p.net/spec/i2cp#messagestatusmessage
+pub struct MessageStatus(());
+
+impl MessageStatus {
+    /// Create new `MessageStatus` message.
+    pub fn new(
+        session_id: u16,
+        message_id: u32,
+        kind: MessageStatusKind,
+        size: u32,
+        nonce: u32,
+    ) -> BytesMut {
+        // session id + message id + status + size + nonce
+        let payload_len = 2 + 4 + 1 + 4 + 4;
+        let mut out = BytesMut::with_capacity(I2CP_HEADER_SIZE + payload_len);
+
+        out.put_u32(payload_len as u32);
+        out.put_u8(MessageType::MessageStatus.as_u8());
+        out.put_u16(session_id);
+        out.put_u32(message_id);
+        out.put_u8(kind.as_u8());
+        out.put_u32(size);
+        out.put_u32(nonce);
+
+        out
+    }
+}
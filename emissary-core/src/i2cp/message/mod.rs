@@ -34,6 +34,7 @@ use core::{fmt, time::Duration};
 pub use bandwidth::BandwidthLimits;
 pub use host_reply::{HostReply, HostReplyKind};
 pub use lease_set::RequestVariableLeaseSet;
+pub use message_status::{MessageStatus, MessageStatusKind};
 pub use payload::MessagePayload;
 pub use session_status::{SessionStatus, SessionStatusKind};
 pub use set_date::SetDate;
@@ -41,6 +42,7 @@ pub use set_date::SetDate;
 mod bandwidth;
 mod host_reply;
 mod lease_set;
+mod message_status;
 mod payload;
 mod session_status;
 mod set_date;
@@ -354,10 +356,22 @@ pub enum Message {
     MessageStatus,
 
     /// Start reception of a message.
-    ReceiveMessageBegin,
+    ReceiveMessageBegin {
+        /// Session ID.
+        session_id: SessionId,
+
+        /// ID of the message the client wants to receive.
+        message_id: u32,
+    },
 
     /// Inform router that a message was delivered successfully.
-    ReceiveMessageEnd,
+    ReceiveMessageEnd {
+        /// Session ID.
+        session_id: SessionId,
+
+        /// ID of the message that was received.
+        message_id: u32,
+    },
 
     /// Reconfigure session.
     ReconfigureSession,
@@ -372,7 +386,22 @@ pub enum Message {
     RequestVariableLeaseSet,
 
     /// Send message to remote router.
-    SendMessage,
+    SendMessage {
+        /// Session ID.
+        session_id: SessionId,
+
+        /// Destination.
+        destination: Destination,
+
+        /// I2CP protocol parameters.
+        parameters: I2cpParameters,
+
+        /// Serialized I2CP payload.
+        payload: Vec<u8>,
+
+        /// Nonce.
+        nonce: u32,
+    },
 
     /// Send message to remote router with expiration and options.
     SendMessageExpires {
@@ -620,6 +649,64 @@ impl Message {
         })
     }
 
+    /// Attempt to parse [`Message::SendMessage`] from `input`.
+    ///
+    /// https://geti2p.net/spec/i2cp#sendmessagemessage
+    fn parse_send_message(input: impl AsRef<[u8]>) -> Option<Self> {
+        let (rest, session_id) = be_u16::<_, ()>(input.as_ref()).ok()?;
+        let (rest, destination) = Destination::parse_frame(rest).ok()?;
+        let (rest, payload_len) = be_u32::<_, ()>(rest).ok()?;
+        let (rest, payload) = take::<_, _, ()>(payload_len)(rest).ok()?;
+        let (_rest, nonce) = be_u32::<_, ()>(rest).ok()?;
+
+        let Some(parameters) = I2cpParameters::new(payload) else {
+            tracing::warn!(
+                target: LOG_TARGET,
+                ?session_id,
+                "invalid i2cp payload",
+            );
+            return None;
+        };
+
+        Some(Message::SendMessage {
+            session_id: SessionId::from(session_id),
+            destination,
+            parameters,
+            payload: payload.to_vec(),
+            nonce,
+        })
+    }
+
+    /// Attempt to parse [`Message::ReceiveMessageBegin`] from `input`.
+    ///
+    /// https://geti2p.net/spec/i2cp#receivemessagebeginmessage
+    fn parse_receive_message_begin(input: impl AsRef<[u8]>) -> Option<Self> {
+        let (rest, session_id) = be_u16::<_, ()>(input.as_ref()).ok()?;
+        let (rest, message_id) = be_u32::<_, ()>(rest).ok()?;
+
+        debug_assert!(rest.is_empty());
+
+        Some(Message::ReceiveMessageBegin {
+            session_id: SessionId::from(session_id),
+            message_id,
+        })
+    }
+
+    /// Attempt to parse [`Message::ReceiveMessageEnd`] from `input`.
+    ///
+    /// https://geti2p.net/spec/i2cp#receivemessageendmessage
+    fn parse_receive_message_end(input: impl AsRef<[u8]>) -> Option<Self> {
+        let (rest, session_id) = be_u16::<_, ()>(input.as_ref()).ok()?;
+        let (rest, message_id) = be_u32::<_, ()>(rest).ok()?;
+
+        debug_assert!(rest.is_empty());
+
+        Some(Message::ReceiveMessageEnd {
+            session_id: SessionId::from(session_id),
+            message_id,
+        })
+    }
+
     /// Attempt to parse [`Message::SendMessageExpires`] from `input`.
     ///
     /// https://geti2p.net/spec/i2cp#sendmessageexpiresmessage
@@ -668,7 +755,10 @@ impl Message {
             MessageType::CreateSession => Self::parse_create_session(input),
             MessageType::HostLookup => Self::parse_host_lookup(input),
             MessageType::CreateLeaseSet2 => Self::parse_create_leaseset2(input),
+            MessageType::SendMessage => Self::parse_send_message(input),
             MessageType::SendMessageExpires => Self::parse_send_message_expires(input),
+            MessageType::ReceiveMessageBegin => Self::parse_receive_message_begin(input),
+            MessageType::ReceiveMessageEnd => Self::parse_receive_message_end(input),
             msg_type => {
                 tracing::warn!(
                     target: LOG_TARGET,
@@ -685,6 +775,86 @@ impl Message {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{
+        i2cp::payload::I2cpPayloadBuilder, protocol::Protocol, runtime::mock::MockRuntime,
+    };
+    use bytes::{BufMut, BytesMut};
+
+    #[test]
+    fn parse_send_message() {
+        let (destination, _) = Destination::random();
+        let payload = I2cpPayloadBuilder::<MockRuntime>::new(b"hello, world")
+            .with_source_port(13)
+            .with_destination_port(37)
+            .with_protocol(Protocol::Anonymous)
+            .build()
+            .unwrap();
+
+        let mut out = BytesMut::new();
+        out.put_u16(1337);
+        out.put_slice(destination.serialized());
+        out.put_u32(payload.len() as u32);
+        out.put_slice(&payload);
+        out.put_u32(0xdeadbeef);
+
+        match Message::parse(MessageType::SendMessage, &out) {
+            Some(Message::SendMessage {
+                session_id: SessionId::Session(1337),
+                destination: parsed,
+                parameters,
+                payload: parsed_payload,
+                nonce: 0xdeadbeef,
+            }) => {
+                assert_eq!(parsed.id(), destination.id());
+                assert_eq!(parsed_payload, payload);
+                assert_eq!(
+                    parameters,
+                    I2cpParameters {
+                        dst_port: 37,
+                        protocol: Protocol::Anonymous,
+                        src_port: 13,
+                    }
+                );
+            }
+            _ => panic!("invalid message"),
+        }
+    }
+
+    #[test]
+    fn parse_send_message_truncated() {
+        let (destination, _) = Destination::random();
+
+        let mut out = BytesMut::new();
+        out.put_u16(1337);
+        out.put_slice(destination.serialized());
+        out.put_u32(128);
+        out.put_slice(&[0u8; 64]);
+
+        assert!(Message::parse(MessageType::SendMessage, &out).is_none());
+    }
+
+    #[test]
+    fn parse_receive_message_begin_and_end() {
+        let mut out = BytesMut::new();
+        out.put_u16(1337);
+        out.put_u32(0xcafe);
+
+        assert!(matches!(
+            Message::parse(MessageType::ReceiveMessageBegin, &out),
+            Some(Message::ReceiveMessageBegin {
+                session_id: SessionId::Session(1337),
+                message_id: 0xcafe,
+            })
+        ));
+        assert!(matches!(
+            Message::parse(MessageType::ReceiveMessageEnd, &out),
+            Some(Message::ReceiveMessageEnd {
+                session_id: SessionId::Session(1337),
+                message_id: 0xcafe,
+            })
+        ));
+        assert!(Message::parse(MessageType::ReceiveMessageEnd, &out[..4]).is_none());
+    }
 
     #[test]
     fn parse_create_leaseset2() {
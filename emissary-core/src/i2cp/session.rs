@@ -21,20 +21,21 @@ use crate::{
     destination::{DeliveryStyle, Destination, DestinationEvent, LeaseSetStatus},
     i2cp::{
         message::{
-            BandwidthLimits, HostReply, HostReplyKind, Message, MessagePayload, RequestKind,
-            RequestVariableLeaseSet, SessionId, SessionStatus, SessionStatusKind, SetDate,
+            BandwidthLimits, HostReply, HostReplyKind, Message, MessagePayload, MessageStatus,
+            MessageStatusKind, RequestKind, RequestVariableLeaseSet, SessionId, SessionStatus,
+            SessionStatusKind, SetDate,
         },
         payload::I2cpParameters,
         pending::I2cpSessionContext,
         socket::I2cpSocket,
     },
     netdb::NetDbHandle,
-    primitives::{Date, DestinationId, Mapping, Str},
-    runtime::{AddressBook, JoinSet, Runtime},
+    primitives::{Date, Destination as Dest, DestinationId, Mapping, Str},
+    runtime::{AddressBook, Instant, JoinSet, Runtime},
 };
 
 use bytes::{Bytes, BytesMut};
-use futures::StreamExt;
+use futures::{FutureExt, StreamExt};
 use hashbrown::HashMap;
 
 use alloc::{collections::VecDeque, string::ToString, sync::Arc, vec::Vec};
@@ -43,11 +44,23 @@ use core::{
     pin::Pin,
     str::FromStr,
     task::{Context, Poll},
+    time::Duration,
 };
 
 /// Logging target for the file.
 const LOG_TARGET: &str = "emissary::i2cp::session";
 
+/// Maximum number of messages waiting to be received by the client.
+///
+/// If the limit is reached, the oldest message is dropped.
+const MAX_PENDING_RECEIVES: usize = 256usize;
+
+/// How long a message is kept waiting for `ReceiveMessageEnd` before it's dropped.
+const PENDING_RECEIVE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How often are expired pending receives pruned.
+const PENDING_RECEIVE_PRUNE_INTERVAL: Duration = Duration::from_secs(10);
+
 /// Context for a pending outbound message.
 ///
 /// Message is marked as outbound because a lease set query for the remote destination is pending.
@@ -72,6 +85,11 @@ pub struct I2cpSession<R: Runtime> {
     /// Destination.
     destination: Destination<R>,
 
+    /// Whether messages are sent to client without waiting for `ReceiveMessageBegin`.
+    ///
+    /// Set to `false` by older clients which specify `i2cp.fastReceive=false`.
+    fast_receive: bool,
+
     /// Pending host lookups.
     host_lookups: R::JoinSet<(SessionId, u32, Option<Bytes>)>,
 
@@ -88,6 +106,17 @@ pub struct I2cpSession<R: Runtime> {
     /// Pending lease set lookups.
     pending_lookups: HashMap<DestinationId, (SessionId, u32)>,
 
+    /// Messages waiting to be received by the client, their message IDs and when they were made
+    /// available, in the order they were made available.
+    ///
+    /// Only used if fast receive mode is disabled.
+    pending_receives: VecDeque<(u32, Vec<u8>, R::Instant)>,
+
+    /// Timer for pruning expired pending receives.
+    ///
+    /// `None` if there are no pending receives.
+    pending_receive_timer: Option<R::Timer>,
+
     /// Session ID.
     session_id: u16,
 
@@ -141,30 +170,193 @@ impl<R: Runtime> I2cpSession<R> {
         );
         destination.publish_lease_set(leaseset);
 
+        let fast_receive = options
+            .get(&Str::from("i2cp.fastReceive"))
+            .map(|value| value.parse::<bool>().unwrap_or(true))
+            .unwrap_or(true);
+
         Self {
             address_book,
             destination,
+            fast_receive,
             host_lookups: R::join_set(),
             next_message_id: 0u32,
             options,
             pending_connections: HashMap::new(),
             pending_lookups: HashMap::new(),
+            pending_receives: VecDeque::new(),
+            pending_receive_timer: None,
             session_id,
             socket,
         }
     }
 
+    /// Allocate next message ID.
+    fn next_message_id(&mut self) -> u32 {
+        let message_id = self.next_message_id;
+        self.next_message_id = self.next_message_id.wrapping_add(1);
+
+        message_id
+    }
+
+    /// Acknowledge message sent by the client with `SendMessage` or `SendMessageExpires`.
+    ///
+    /// Non-zero `nonce` means the client expects the message to be acknowledged.
+    fn accept_message(&mut self, nonce: u32, payload_len: usize) {
+        if nonce == 0 {
+            return;
+        }
+
+        let message_id = self.next_message_id();
+        self.socket.send_message(MessageStatus::new(
+            self.session_id,
+            message_id,
+            MessageStatusKind::Accepted,
+            payload_len as u32,
+            nonce,
+        ));
+    }
+
     /// Send `MessagePayload` message to client.
+    ///
+    /// If fast receive mode is disabled, the message is stored until the client requests it with
+    /// `ReceiveMessageBegin` and the client is only notified that a message is available.
     fn send_payload_message(&mut self, payload: Vec<u8>) {
-        let message_id = {
-            let message_id = self.next_message_id;
-            self.next_message_id = self.next_message_id.wrapping_add(1);
+        let message_id = self.next_message_id();
 
-            message_id
-        };
+        if self.fast_receive {
+            self.socket
+                .send_message(MessagePayload::new(self.session_id, message_id, payload));
+            return;
+        }
+
+        self.socket.send_message(MessageStatus::new(
+            self.session_id,
+            message_id,
+            MessageStatusKind::Available,
+            payload.len() as u32,
+            0u32,
+        ));
 
-        self.socket
-            .send_message(MessagePayload::new(self.session_id, message_id, payload));
+        self.prune_pending_receives();
+
+        if self.pending_receives.len() >= MAX_PENDING_RECEIVES {
+            if let Some((oldest, _, _)) = self.pending_receives.pop_front() {
+                tracing::debug!(
+                    target: LOG_TARGET,
+                    session_id = ?self.session_id,
+                    message_id = ?oldest,
+                    "too many pending receives, dropping oldest message",
+                );
+            }
+        }
+
+        self.pending_receives.push_back((message_id, payload, R::now()));
+
+        if self.pending_receive_timer.is_none() {
+            self.pending_receive_timer = Some(R::timer(PENDING_RECEIVE_PRUNE_INTERVAL));
+        }
+    }
+
+    /// Drop messages the client hasn't acknowledged within [`PENDING_RECEIVE_TIMEOUT`].
+    ///
+    /// Pending receives are ordered by the time they were made available so only the oldest
+    /// messages need to be checked.
+    fn prune_pending_receives(&mut self) {
+        let num_pending = self.pending_receives.len();
+
+        while self
+            .pending_receives
+            .front()
+            .is_some_and(|(_, _, received)| received.elapsed() >= PENDING_RECEIVE_TIMEOUT)
+        {
+            self.pending_receives.pop_front();
+        }
+
+        if self.pending_receives.len() != num_pending {
+            tracing::debug!(
+                target: LOG_TARGET,
+                session_id = ?self.session_id,
+                num_dropped = ?(num_pending - self.pending_receives.len()),
+                "dropped expired pending receives",
+            );
+        }
+    }
+
+    /// Send `payload` to `destination`.
+    ///
+    /// If lease set for `destination` is not available, the message is queued until the lease set
+    /// lookup has finished.
+    fn on_send_message(
+        &mut self,
+        session_id: SessionId,
+        destination: Dest,
+        parameters: I2cpParameters,
+        payload: Vec<u8>,
+    ) {
+        let destination_id = destination.id();
+
+        match self.destination.query_lease_set(&destination_id) {
+            LeaseSetStatus::Found => {
+                tracing::trace!(
+                    target: LOG_TARGET,
+                    ?session_id,
+                    %destination_id,
+                    protocol = ?parameters.protocol,
+                    "send message",
+                );
+
+                if let Err(error) = self.destination.send_message(
+                    DeliveryStyle::Unspecified {
+                        destination_id: destination.id(),
+                    },
+                    payload,
+                ) {
+                    tracing::error!(
+                        target: LOG_TARGET,
+                        session_id = ?self.session_id,
+                        ?error,
+                        "failed to encrypt message",
+                    );
+                }
+            }
+            LeaseSetStatus::NotFound => {
+                tracing::debug!(
+                    target: LOG_TARGET,
+                    %destination_id,
+                    "cannot send message, lease set doesn't exist",
+                );
+
+                // `Destination` has started a lease set query and will notify
+                // `I2cpConnection` once the query has completed
+                //
+                // pending messages will be sent if the lease set is found
+                self.pending_connections.insert(
+                    destination_id,
+                    VecDeque::from_iter([PendingMessage {
+                        parameters,
+                        payload,
+                        session_id,
+                    }]),
+                );
+            }
+            LeaseSetStatus::Pending => match self.pending_connections.get_mut(&destination_id) {
+                Some(messages) => messages.push_back(PendingMessage {
+                    parameters,
+                    payload,
+                    session_id,
+                }),
+                None => {
+                    // TODO: fix this, could be pending lookup as well
+                    tracing::warn!(
+                        target: LOG_TARGET,
+                        %destination_id,
+                        "pending connection doesn't exist",
+                    );
+                    // debug_assert!(false);
+                }
+            },
+        }
     }
 
     /// Handle I2CP message received from the client.
@@ -315,92 +507,74 @@ impl<R: Runtime> I2cpSession<R> {
 
                 self.destination.publish_lease_set(leaseset);
             }
+            Message::SendMessage {
+                session_id,
+                destination,
+                parameters,
+                payload,
+                nonce,
+            } => {
+                self.accept_message(nonce, payload.len());
+                self.on_send_message(session_id, destination, parameters, payload);
+            }
             Message::SendMessageExpires {
                 session_id,
                 destination,
-                parameters:
-                    I2cpParameters {
-                        dst_port,
-                        protocol,
-                        src_port,
-                    },
+                parameters,
                 payload,
+                nonce,
                 ..
             } => {
-                let destination_id = destination.id();
-
-                match self.destination.query_lease_set(&destination_id) {
-                    LeaseSetStatus::Found => {
-                        tracing::trace!(
-                            target: LOG_TARGET,
-                            ?session_id,
-                            %destination_id,
-                            ?protocol,
-                            "send message with expiration",
-                        );
-
-                        if let Err(error) = self.destination.send_message(
-                            DeliveryStyle::Unspecified {
-                                destination_id: destination.id(),
-                            },
-                            payload,
-                        ) {
-                            tracing::error!(
-                                target: LOG_TARGET,
-                                session_id = ?self.session_id,
-                                ?error,
-                                "failed to encrypt message",
-                            );
-                        }
-                    }
-                    LeaseSetStatus::NotFound => {
-                        tracing::debug!(
-                            target: LOG_TARGET,
-                            %destination_id,
-                            "cannot send message, lease set doesn't exist",
-                        );
+                self.accept_message(nonce, payload.len());
+                self.on_send_message(session_id, destination, parameters, payload);
+            }
+            Message::ReceiveMessageBegin {
+                session_id,
+                message_id,
+            } => match self.pending_receives.iter().find(|(id, _, _)| *id == message_id) {
+                Some((_, payload, _)) => {
+                    tracing::trace!(
+                        target: LOG_TARGET,
+                        ?session_id,
+                        ?message_id,
+                        "send message to i2cp client",
+                    );
 
-                        // `Destination` has started a lease set query and will notify
-                        // `I2cpConnection` once the query has completed
-                        //
-                        // pending messages will be sent if the lease set is found
-                        self.pending_connections.insert(
-                            destination_id,
-                            VecDeque::from_iter([PendingMessage {
-                                parameters: I2cpParameters {
-                                    dst_port,
-                                    protocol,
-                                    src_port,
-                                },
-                                payload,
-                                session_id,
-                            }]),
-                        );
-                    }
-                    LeaseSetStatus::Pending => {
-                        match self.pending_connections.get_mut(&destination_id) {
-                            Some(messages) => messages.push_back(PendingMessage {
-                                parameters: I2cpParameters {
-                                    dst_port,
-                                    protocol,
-                                    src_port,
-                                },
-                                payload,
-                                session_id,
-                            }),
-                            None => {
-                                // TODO: fix this, could be pending lookup as well
-                                tracing::warn!(
-                                    target: LOG_TARGET,
-                                    %destination_id,
-                                    "pending connection doesn't exist",
-                                );
-                                // debug_assert!(false);
-                            }
-                        }
-                    }
+                    self.socket.send_message(MessagePayload::new(
+                        self.session_id,
+                        message_id,
+                        payload.clone(),
+                    ));
                 }
-            }
+                None => tracing::warn!(
+                    target: LOG_TARGET,
+                    ?session_id,
+                    ?message_id,
+                    "`ReceiveMessageBegin` for a message that doesn't exist",
+                ),
+            },
+            Message::ReceiveMessageEnd {
+                session_id,
+                message_id,
+            } => match self
+                .pending_receives
+                .iter()
+                .position(|(id, _, _)| *id == message_id)
+                .and_then(|index| self.pending_receives.remove(index))
+            {
+                Some(_) => tracing::trace!(
+                    target: LOG_TARGET,
+                    ?session_id,
+                    ?message_id,
+                    "message received by i2cp client",
+                ),
+                None => tracing::debug!(
+                    target: LOG_TARGET,
+                    ?session_id,
+                    ?message_id,
+                    "`ReceiveMessageEnd` for a message that doesn't exist",
+                ),
+            },
             _ => {}
         }
     }
@@ -563,6 +737,160 @@ impl<R: Runtime> Future for I2cpSession<R> {
             }
         }
 
+        if self
+            .pending_receive_timer
+            .as_mut()
+            .is_some_and(|timer| timer.poll_unpin(cx).is_ready())
+        {
+            self.prune_pending_receives();
+
+            // the timer is only kept armed while there are messages waiting to be received
+            self.pending_receive_timer = (!self.pending_receives.is_empty())
+                .then(|| R::timer(PENDING_RECEIVE_PRUNE_INTERVAL));
+        }
+
+        // register the timer into the executor
+        if let Some(timer) = self.pending_receive_timer.as_mut() {
+            let _ = timer.poll_unpin(cx);
+        }
+
         Poll::Pending
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        crypto::StaticPrivateKey,
+        i2cp::message::MessageType,
+        primitives::DestinationId,
+        profile::ProfileStorage,
+        runtime::mock::{MockRuntime, MockTcpStream},
+        tunnel::TunnelPoolHandle,
+    };
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+    };
+
+    /// Create [`I2cpSession`] with fast receive disabled and the client's end of the socket.
+    ///
+    /// The returned channels must be kept alive for the session to stay active.
+    async fn make_session() -> (I2cpSession<MockRuntime>, TcpStream, Box<dyn std::any::Any>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).await.unwrap();
+        let (stream, _) = listener.accept().await.unwrap();
+
+        let (netdb_handle, netdb_rx) = NetDbHandle::create();
+        let (tp_handle, tm_rx, tp_tx, shutdown_rx) = TunnelPoolHandle::create();
+        let destination = Destination::<MockRuntime>::new(
+            DestinationId::random(),
+            StaticPrivateKey::random(MockRuntime::rng()),
+            Bytes::new(),
+            netdb_handle,
+            tp_handle,
+            Vec::new(),
+            Vec::new(),
+            false,
+            ProfileStorage::new(&[], &[]),
+        );
+
+        let session = I2cpSession {
+            address_book: None,
+            destination,
+            fast_receive: false,
+            host_lookups: MockRuntime::join_set(),
+            next_message_id: 0u32,
+            options: Mapping::default(),
+            pending_connections: HashMap::new(),
+            pending_lookups: HashMap::new(),
+            pending_receives: VecDeque::new(),
+            pending_receive_timer: None,
+            session_id: 1337,
+            socket: I2cpSocket::new(MockTcpStream::new(stream)),
+        };
+
+        (
+            session,
+            client,
+            Box::new((netdb_rx, tm_rx, tp_tx, shutdown_rx)),
+        )
+    }
+
+    /// Read one I2CP frame from `client`, returning its type and payload.
+    async fn read_frame(client: &mut TcpStream) -> (u8, Vec<u8>) {
+        let mut header = [0u8; 5];
+        client.read_exact(&mut header).await.unwrap();
+
+        let size = u32::from_be_bytes(TryInto::<[u8; 4]>::try_into(&header[..4]).unwrap());
+        let mut payload = vec![0u8; size as usize];
+        client.read_exact(&mut payload).await.unwrap();
+
+        (header[4], payload)
+    }
+
+    /// Write `message_type` frame for `message_id` to `client`.
+    async fn write_frame(client: &mut TcpStream, message_type: u8, message_id: u32) {
+        let mut frame = BytesMut::new();
+        frame.extend_from_slice(&6u32.to_be_bytes());
+        frame.extend_from_slice(&[message_type]);
+        frame.extend_from_slice(&1337u16.to_be_bytes());
+        frame.extend_from_slice(&message_id.to_be_bytes());
+
+        client.write_all(&frame).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn receive_message_begin_and_end() {
+        let (mut session, mut client, _channels) = make_session().await;
+
+        // client is notified that the message is available and the message is kept
+        session.send_payload_message(vec![1, 2, 3, 4]);
+        assert!(tokio::time::timeout(Duration::from_millis(200), &mut session).await.is_err());
+        assert_eq!(session.pending_receives.len(), 1);
+
+        let (message_type, _) = read_frame(&mut client).await;
+        assert_eq!(message_type, MessageType::MessageStatus.as_u8());
+
+        // client requests the message and receives the payload
+        write_frame(&mut client, MessageType::ReceiveMessageBegin.as_u8(), 0u32).await;
+        assert!(tokio::time::timeout(Duration::from_millis(200), &mut session).await.is_err());
+
+        let (message_type, payload) = read_frame(&mut client).await;
+        assert_eq!(message_type, MessageType::MessagePayload.as_u8());
+        assert!(payload.ends_with(&[1, 2, 3, 4]));
+        assert_eq!(session.pending_receives.len(), 1);
+
+        // client acknowledges the message and it's removed
+        write_frame(&mut client, MessageType::ReceiveMessageEnd.as_u8(), 0u32).await;
+        assert!(tokio::time::timeout(Duration::from_millis(200), &mut session).await.is_err());
+        assert!(session.pending_receives.is_empty());
+    }
+
+    #[tokio::test]
+    async fn unacknowledged_messages_are_bounded_and_expire() {
+        let (mut session, _client, _channels) = make_session().await;
+
+        (0..MAX_PENDING_RECEIVES + 1).for_each(|i| {
+            session.send_payload_message(vec![i as u8]);
+        });
+        assert_eq!(session.pending_receives.len(), MAX_PENDING_RECEIVES);
+        assert!(session.pending_receive_timer.is_some());
+
+        // the oldest message was dropped
+        assert_eq!(session.pending_receives.front().unwrap().0, 1u32);
+        assert_eq!(
+            session.pending_receives.back().unwrap().0,
+            MAX_PENDING_RECEIVES as u32
+        );
+
+        // expire the oldest message and verify it's pruned
+        let (_, _, received) = session.pending_receives.front_mut().unwrap();
+        *received = received.subtract(PENDING_RECEIVE_TIMEOUT);
+
+        session.prune_pending_receives();
+        assert_eq!(session.pending_receives.len(), MAX_PENDING_RECEIVES - 1);
+        assert_eq!(session.pending_receives.front().unwrap().0, 2u32);
+    }
+}
@@ -263,13 +263,19 @@ impl<R: Runtime> DatabaseStore<R> {
                     Err::Error(make_error(input, ErrorKind::Fail))
                 })?;
 
-                let router_info = RouterInfo::parse(&data).ok_or_else(|| {
+                let (_, router_info) = RouterInfo::parse_frame(&data).map_err(|error| {
                     tracing::debug!(
                         target: LOG_TARGET,
+                        ?error,
                         "failed to parse gzipped router info",
                     );
 
-                    Err::Error(make_error(input, ErrorKind::Fail))
+                    // propagate signature verification failures to the caller
+                    match error {
+                        Err::Error(error) if error.code == ErrorKind::Verify =>
+                            Err::Error(make_error(input, ErrorKind::Verify)),
+                        _ => Err::Error(make_error(input, ErrorKind::Fail)),
+                    }
                 })?;
 
                 Ok((
@@ -311,6 +317,15 @@ impl<R: Runtime> DatabaseStore<R> {
         Self::parse_frame(input).ok().map(|(_, message)| message)
     }
 
+    /// Check if `input` is a [`DatabaseStore`] whose router info or lease set has an invalid
+    /// signature.
+    pub fn has_invalid_signature(input: &[u8]) -> bool {
+        core::matches!(
+            Self::parse_frame(input),
+            Err(Err::Error(error)) if error.code == ErrorKind::Verify
+        )
+    }
+
     /// Extract raw payload from [`DatabaseStore`] message.
     fn extraw_raw_data_payload(input: &[u8], router_info: bool) -> Bytes {
         let (rest, _) = take::<_, _, ()>(DATABASE_KEY_SIZE)(input).expect("to succeed");
@@ -605,6 +620,53 @@ mod tests {
         }
     }
 
+    #[test]
+    fn invalid_lease_set_signature_detected() {
+        let (leaseset, signing_key) = LeaseSet2::random();
+        let key = {
+            let mut key = vec![0u8; 32];
+            rand::thread_rng().fill_bytes(&mut key);
+
+            Bytes::from(key)
+        };
+
+        let mut lease_set = leaseset.serialize(&signing_key);
+        let serialized = DatabaseStoreBuilder::new(
+            key.clone(),
+            DatabaseStoreKind::LeaseSet2 {
+                lease_set: Bytes::from(lease_set.clone()),
+            },
+        )
+        .build();
+
+        assert!(DatabaseStore::<MockRuntime>::parse(&serialized).is_some());
+        assert!(!DatabaseStore::<MockRuntime>::has_invalid_signature(
+            &serialized
+        ));
+
+        // corrupt the signature
+        let len = lease_set.len();
+        lease_set[len - 1] ^= 0xff;
+
+        let serialized = DatabaseStoreBuilder::new(
+            key.clone(),
+            DatabaseStoreKind::LeaseSet2 {
+                lease_set: Bytes::from(lease_set.clone()),
+            },
+        )
+        .build();
+
+        assert!(DatabaseStore::<MockRuntime>::parse(&serialized).is_none());
+        assert!(DatabaseStore::<MockRuntime>::has_invalid_signature(
+            &serialized
+        ));
+
+        // truncated store is malformed but its signature hasn't been verified
+        assert!(!DatabaseStore::<MockRuntime>::has_invalid_signature(
+            &serialized[..serialized.len() / 2]
+        ));
+    }
+
     #[test]
     fn serialize_and_parse_store_with_reply() {
         let (leaseset, signing_key) = LeaseSet2::random();
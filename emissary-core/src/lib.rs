@@ -33,6 +33,7 @@ extern crate alloc;
 
 pub type Result<T> = core::result::Result<T, Error>;
 
+pub use banlist::{BlocklistEntry, IpRange};
 pub use config::{
     Config, ExploratoryConfig, I2cpConfig, MetricsConfig, Ntcp2Config, SamConfig, Ssu2Config,
     TransitConfig,
@@ -40,6 +41,7 @@ pub use config::{
 pub use error::Error;
 pub use profile::Profile;
 
+mod banlist;
 mod bloom;
 mod config;
 mod destination;
@@ -50,6 +52,7 @@ mod profile;
 mod sam;
 mod shutdown;
 mod subsystem;
+mod sybil;
 mod transport;
 mod tunnel;
 mod util;
@@ -22,6 +22,7 @@ use crate::{
     primitives::RouterId,
     router::context::RouterContext,
     runtime::Runtime,
+    sybil::CLOSE_KEY_SHARED_BITS,
 };
 
 use hashbrown::HashSet;
@@ -45,6 +46,9 @@ const LOOKUP_FAILED_SCORE: isize = 1isize;
 
 /// Kademlia DHT implementation.
 pub struct Dht<R: Runtime> {
+    /// Is this a [`Dht`] for floodfills.
+    floodfill: bool,
+
     /// Kademlia routing table.
     routing_table: RoutingTable,
 
@@ -80,6 +84,7 @@ impl<R: Runtime> Dht<R> {
 
             scores.sort_by(|(_, a), (_, b)| b.cmp(a));
             scores.into_iter().for_each(|(router_id, _)| {
+                Self::check_close_key(&router_ctx, &router_id);
                 routing_table.add_router(router_id);
             });
 
@@ -95,11 +100,31 @@ impl<R: Runtime> Dht<R> {
         };
 
         Self {
+            floodfill,
             routing_table,
             router_ctx,
         }
     }
 
+    /// Flag floodfill `router_id` to the Sybil detector if its router hash shares suspiciously
+    /// many leading bits with the router hash of the local router.
+    fn check_close_key(router_ctx: &RouterContext<R>, router_id: &RouterId) {
+        let distance =
+            Key::from(router_ctx.router_id().clone()).distance(&Key::from(router_id.clone()));
+
+        // distance is zero only for the local router
+        let Some(shared_bits) = distance.ilog2().map(|log2| 255 - log2) else {
+            return;
+        };
+
+        if shared_bits >= CLOSE_KEY_SHARED_BITS {
+            router_ctx
+                .profile_storage()
+                .sybil()
+                .flag_close_key(router_id.clone(), shared_bits);
+        }
+    }
+
     /// Get UTC date from the unix timestamp.
     fn utc_date(unix_timestamp: u64) -> String {
         const DAYS_PER_YEAR: u64 = 365;
@@ -177,6 +202,10 @@ impl<R: Runtime> Dht<R> {
 
     /// Insert new router into [`Dht`].
     pub(super) fn add_router(&mut self, router_id: RouterId) {
+        if self.floodfill {
+            Self::check_close_key(&self.router_ctx, &router_id);
+        }
+
         self.routing_table.add_router(router_id);
     }
 
@@ -292,7 +321,7 @@ impl<R: Runtime> Dht<R> {
 mod tests {
     use super::*;
     use crate::{
-        crypto::{base32_decode, base64_decode},
+        crypto::{base32_decode, base64_decode, base64_encode},
         events::EventManager,
         primitives::RouterInfoBuilder,
         profile::ProfileStorage,
@@ -357,6 +386,59 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn close_floodfill_flagged() {
+        let (router_info, static_key, signing_key) = RouterInfoBuilder::default().build();
+        let (_event_mgr, _event_subscriber, event_handle) = EventManager::new(None);
+        let router_ctx = RouterContext::new(
+            MockRuntime::register_metrics(vec![], None),
+            ProfileStorage::new(&[], &[]),
+            router_info.identity.id(),
+            Bytes::from(router_info.serialize(&signing_key)),
+            static_key,
+            signing_key,
+            2u8,
+            event_handle.clone(),
+        );
+
+        // create router ids which differ from the local router id at the 21st and the first bit
+        let flip_bit = |bit: usize| {
+            let mut router_id = router_info.identity.id().to_vec();
+            router_id[bit / 8] ^= 0x80 >> (bit % 8);
+
+            RouterId::from(router_id)
+        };
+        let close = flip_bit(20);
+        let far = flip_bit(0);
+
+        // routers of the non-floodfill dht are not flagged
+        let mut dht = Dht::<MockRuntime>::new(
+            router_info.identity.id(),
+            HashSet::new(),
+            router_ctx.clone(),
+            false,
+        );
+        dht.add_router(close.clone());
+        assert!(router_ctx.profile_storage().sybil().report().close_floodfills.is_empty());
+
+        let mut dht = Dht::<MockRuntime>::new(
+            router_info.identity.id(),
+            HashSet::new(),
+            router_ctx.clone(),
+            true,
+        );
+        dht.add_router(close.clone());
+        dht.add_router(far);
+
+        let report = router_ctx.profile_storage().sybil().report();
+        assert_eq!(report.close_floodfills.len(), 1);
+        assert_eq!(
+            report.close_floodfills[0].router_id,
+            base64_encode(close.to_vec())
+        );
+        assert_eq!(report.close_floodfills[0].shared_bits, 20);
+    }
+
     #[tokio::test]
     async fn utc_date() {
         type D = Dht<MockRuntime>;
@@ -17,6 +17,7 @@
 // DEALINGS IN THE SOFTWARE.
 
 use crate::{
+    banlist::BanReason,
     crypto::{base32_encode, base64_encode, StaticPublicKey},
     error::{Error, QueryError},
     i2np::{
@@ -96,6 +97,27 @@ const EXPLORATION_INTERVAL_LOW_ROUTER_COUNT: usize = 55usize;
 /// How often should router exploration be performed if the known peer count is high
 const EXPLORATION_INTERVAL_HIGH_ROUTER_COUNT: usize = 170usize;
 
+/// How long to wait after storing the local router info before its storage is verified.
+///
+/// The delay gives the floodfills time to flood the router info to other floodfills.
+const ROUTER_INFO_VERIFICATION_DELAY: Duration = Duration::from_secs(20);
+
+/// Timeout for a router info store verification lookup.
+///
+/// The lookup is sent through an exploratory tunnel and may take considerably longer than a
+/// direct lookup.
+const ROUTER_INFO_VERIFICATION_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// How many floodfills are queried to verify a local router info store before the floodfills the
+/// router info was stored to are considered to have failed.
+///
+/// A failed lookup may be caused by the queried floodfill rather than the floodfills the router
+/// info was stored to.
+const MAX_ROUTER_INFO_VERIFICATION_LOOKUPS: usize = 2usize;
+
+/// How many times the local router info is stored before giving up until the next republish.
+const MAX_ROUTER_INFO_STORE_ATTEMPTS: usize = 3usize;
+
 /// Message kind.
 #[derive(Clone)]
 enum MessageKind {
@@ -149,6 +171,35 @@ impl fmt::Debug for RouterState {
     }
 }
 
+/// Verification of a local [`RouterInfo`] store.
+struct RouterInfoVerification<R: Runtime> {
+    /// Floodfills which have previously failed to store the router info.
+    failed: HashSet<RouterId>,
+
+    /// Floodfills the router info was stored to.
+    floodfills: HashSet<RouterId>,
+
+    /// How many times the router info has been stored.
+    num_attempts: usize,
+
+    /// When the stored router info was published, in milliseconds since UNIX epoch.
+    published: u64,
+
+    /// Router ID of the local router.
+    router_id: RouterId,
+
+    /// Serialized router info, used to retry the store if the verification fails.
+    router_info: Bytes,
+
+    /// Timer which expires when the verification lookup should be sent.
+    ///
+    /// `None` if the lookup has been sent and its reply is being awaited.
+    timer: Option<R::Timer>,
+
+    /// Floodfills which have been queried to verify the store.
+    verifiers: HashSet<RouterId>,
+}
+
 /// Network database (NetDB).
 pub struct NetDb<R: Runtime> {
     /// Active queries.
@@ -206,6 +257,15 @@ pub struct NetDb<R: Runtime> {
     /// This contains entries only if `floodfill` is true.
     router_infos: HashMap<Bytes, (Bytes, Duration)>,
 
+    /// Pending verification of the local router info store.
+    router_info_verification: Option<RouterInfoVerification<R>>,
+
+    /// Timers of router info store verification lookups.
+    ///
+    /// Each timer carries the nonce of its lookup so that the timer of a previous lookup doesn't
+    /// expire the lookup which replaced it.
+    verification_timers: R::JoinSet<(Bytes, u32)>,
+
     /// Connected routers.
     routers: HashMap<RouterId, RouterState>,
 
@@ -293,6 +353,8 @@ impl<R: Runtime> NetDb<R> {
                 query_timers: R::join_set(),
                 router_ctx: router_ctx.clone(),
                 router_dht,
+                router_info_verification: None,
+                verification_timers: R::join_set(),
                 router_infos: HashMap::new(),
                 routers: HashMap::new(),
                 routing_table,
@@ -462,9 +524,24 @@ impl<R: Runtime> NetDb<R> {
         reply: StoreReplyType,
         message: &[u8],
         router_info: RouterInfo,
+        sender: Option<RouterId>,
     ) {
         let router_id = router_info.identity.id();
 
+        if key != router_id.to_vec() {
+            tracing::warn!(
+                target: LOG_TARGET,
+                %router_id,
+                ?sender,
+                "store key doesn't match router info, ignoring router info store",
+            );
+
+            if let Some(sender) = sender {
+                self.router_ctx.banlist().ban_router(sender, BanReason::InvalidStore);
+            }
+            return;
+        }
+
         if router_info.net_id() != self.router_ctx.net_id() {
             tracing::warn!(
                 target: LOG_TARGET,
@@ -472,6 +549,17 @@ impl<R: Runtime> NetDb<R> {
                 remote_net_id = ?router_info.net_id(),
                 "invalid network id, ignoring router info store",
             );
+            self.router_ctx.banlist().ban_router(router_id, BanReason::NetworkMismatch);
+
+            return;
+        }
+
+        if self.router_ctx.banlist().is_router_info_banned(&router_info) {
+            tracing::debug!(
+                target: LOG_TARGET,
+                %router_id,
+                "router is banned, ignoring router info store",
+            );
             return;
         }
 
@@ -1041,15 +1129,27 @@ impl<R: Runtime> NetDb<R> {
         } = DatabaseStore::<R>::parse(&message.payload).ok_or_else(|| {
             tracing::debug!(
                 target: LOG_TARGET,
+                ?sender,
                 "malformed database store received",
             );
+
+            // router info or lease set has been verified by the sender before it was sent
+            // directly to us, ban the sender if the signature of the payload is invalid
+            if let Some(sender) = &sender {
+                if DatabaseStore::<R>::has_invalid_signature(&message.payload) {
+                    self.router_ctx
+                        .banlist()
+                        .ban_router(sender.clone(), BanReason::InvalidSignature);
+                }
+            }
+
             Error::InvalidData
         })?;
 
         match self.active.remove(&key) {
             None => match payload {
                 DatabaseStorePayload::RouterInfo { router_info } => {
-                    self.on_router_info_store(key, reply, &message.payload, router_info);
+                    self.on_router_info_store(key, reply, &message.payload, router_info, sender);
                 }
                 DatabaseStorePayload::LeaseSet2 { lease_set } if self.floodfill => {
                     self.on_lease_set_store(key, reply, &message.payload, lease_set);
@@ -1096,6 +1196,15 @@ impl<R: Runtime> NetDb<R> {
                         return Ok(());
                     }
 
+                    if self.router_ctx.banlist().is_router_info_banned(&router_info) {
+                        tracing::debug!(
+                            target: LOG_TARGET,
+                            %router_id,
+                            "router is banned, ignoring router info query reply",
+                        );
+                        return Ok(());
+                    }
+
                     if router_info.is_floodfill() {
                         self.floodfill_dht.add_router(router_id.clone());
                     }
@@ -1158,6 +1267,25 @@ impl<R: Runtime> NetDb<R> {
                         query.complete(Err(QueryError::Malformed));
                     }
                 }
+                (
+                    DatabaseStorePayload::RouterInfo { router_info },
+                    QueryKind::StoreVerification { floodfill, .. },
+                ) => {
+                    let verified =
+                        self.router_info_verification.as_ref().is_some_and(|verification| {
+                            *router_info.published.date() >= verification.published
+                        });
+
+                    tracing::trace!(
+                        target: LOG_TARGET,
+                        %floodfill,
+                        ?verified,
+                        "router info store verification reply received",
+                    );
+
+                    self.floodfill_dht.register_lookup_success(&floodfill);
+                    self.on_router_info_verification(verified);
+                }
                 (payload, query) => tracing::warn!(
                     target: LOG_TARGET,
                     %payload,
@@ -1341,6 +1469,14 @@ impl<R: Runtime> NetDb<R> {
                 key = ?base64_encode(key),
                 "router info lookup failed",
             ),
+            Some(QueryKind::StoreVerification { floodfill, .. }) => {
+                tracing::debug!(
+                    target: LOG_TARGET,
+                    %floodfill,
+                    "local router info not found from floodfill",
+                );
+                self.on_router_info_verification(false);
+            }
         }
 
         Ok(())
@@ -1585,9 +1721,11 @@ impl<R: Runtime> NetDb<R> {
         key: Bytes,
         tx: oneshot::Sender<Vec<(RouterId, StaticPublicKey)>>,
     ) {
+        // floodfills which exceed the limit of their ip cluster are not selected
+        let excluded = self.router_ctx.profile_storage().sybil().excluded();
         let floodfills = self
             .floodfill_dht
-            .closest(&key, 10usize)
+            .closest_with_ignore(&key, 10usize, &excluded)
             .collect::<Vec<_>>()
             .into_iter()
             .filter_map(|router_id| {
@@ -1612,10 +1750,28 @@ impl<R: Runtime> NetDb<R> {
     }
 
     /// Publish `router_info` under `router_id` in `NetDb`.
+    ///
+    /// Any pending verification of a previously published router info is cancelled.
     fn publish_router_info(&mut self, router_id: RouterId, router_info: Bytes) {
+        self.router_info_verification = None;
+        self.store_router_info(router_id, router_info, HashSet::new(), 1usize);
+    }
+
+    /// Store `router_info` to the floodfills closest to `router_id`, ignoring `failed` floodfills,
+    /// and schedule the verification of the store.
+    fn store_router_info(
+        &mut self,
+        router_id: RouterId,
+        router_info: Bytes,
+        failed: HashSet<RouterId>,
+        num_attempts: usize,
+    ) {
         let key = Bytes::from(router_id.to_vec());
 
-        let floodfills = self.floodfill_dht.closest(&key, 3usize).collect::<Vec<_>>();
+        let floodfills = self
+            .floodfill_dht
+            .closest_with_ignore(&key, 3usize, &failed)
+            .collect::<Vec<_>>();
         if floodfills.is_empty() {
             tracing::warn!(
                 target: LOG_TARGET,
@@ -1625,11 +1781,24 @@ impl<R: Runtime> NetDb<R> {
             return;
         }
 
+        // router info is created by us and is expected to be valid
+        let Some(published) =
+            RouterInfo::parse(&router_info).map(|router_info| *router_info.published.date())
+        else {
+            tracing::error!(
+                target: LOG_TARGET,
+                %router_id,
+                "cannot publish router info, invalid router info",
+            );
+            debug_assert!(false);
+            return;
+        };
+
         // gzip-compress the serialized router info, as required by the spec
         //
         // call is expected to succeed as the router info is created by us
         let serialized_router_info =
-            Bytes::from(R::gzip_compress(router_info).expect("to succeed"));
+            Bytes::from(R::gzip_compress(router_info.clone()).expect("to succeed"));
 
         let reply_token = R::rng().next_u32();
         let message = DatabaseStoreBuilder::new(
@@ -1656,10 +1825,169 @@ impl<R: Runtime> NetDb<R> {
             %router_id,
             ?floodfills,
             %reply_token,
+            ?num_attempts,
             "publish router info",
         );
 
         self.send_message(&floodfills, MessageKind::NonExpiring { message });
+        self.router_info_verification = Some(RouterInfoVerification {
+            failed,
+            floodfills: floodfills.into_iter().collect(),
+            num_attempts,
+            published,
+            router_id,
+            router_info,
+            timer: Some(R::timer(ROUTER_INFO_VERIFICATION_DELAY)),
+            verifiers: HashSet::new(),
+        });
+    }
+
+    /// Verify the local router info store by looking up the router info from a floodfill it
+    /// wasn't stored to and which hasn't been queried yet.
+    fn verify_router_info_store(&mut self) {
+        let Some(verification) = self.router_info_verification.as_mut() else {
+            return;
+        };
+        let key = Bytes::from(verification.router_id.to_vec());
+        verification.timer = None;
+
+        let ignore = verification
+            .floodfills
+            .union(&verification.verifiers)
+            .cloned()
+            .collect::<HashSet<_>>();
+        let Some(floodfill) = self.floodfill_dht.closest_with_ignore(&key, 1usize, &ignore).next()
+        else {
+            tracing::debug!(
+                target: LOG_TARGET,
+                "no floodfills for router info store verification",
+            );
+            self.router_info_verification = None;
+            return;
+        };
+
+        if self.active.contains_key(&key) {
+            tracing::debug!(
+                target: LOG_TARGET,
+                "router info query already in progress, postponing store verification",
+            );
+            verification.timer = Some(R::timer(ROUTER_INFO_VERIFICATION_DELAY));
+            return;
+        }
+
+        tracing::debug!(
+            target: LOG_TARGET,
+            %floodfill,
+            "verify router info store",
+        );
+
+        match self.message_builder.create_router_info_query(key.clone()) {
+            Ok((message, outbound_tunnel)) => match self
+                .exploratory_pool_handle
+                .send_message(message)
+                .router_delivery(floodfill.clone())
+                .via_outbound_tunnel(outbound_tunnel)
+                .try_send()
+            {
+                Ok(()) => {
+                    let nonce = R::rng().next_u32();
+
+                    verification.verifiers.insert(floodfill.clone());
+                    self.active.insert(
+                        key.clone(),
+                        QueryKind::StoreVerification { floodfill, nonce },
+                    );
+                    self.verification_timers.push(async move {
+                        R::delay(ROUTER_INFO_VERIFICATION_TIMEOUT).await;
+                        (key, nonce)
+                    });
+                }
+                Err(error) => {
+                    tracing::debug!(
+                        target: LOG_TARGET,
+                        ?error,
+                        "failed to send router info store verification, trying again later",
+                    );
+                    verification.timer = Some(R::timer(ROUTER_INFO_VERIFICATION_DELAY));
+                }
+            },
+            Err(error) => {
+                tracing::debug!(
+                    target: LOG_TARGET,
+                    ?error,
+                    "failed to create router info store verification, trying again later",
+                );
+                verification.timer = Some(R::timer(ROUTER_INFO_VERIFICATION_DELAY));
+            }
+        }
+    }
+
+    /// Handle the result of the local router info store verification.
+    ///
+    /// If the lookup failed, the store is verified with another floodfill, up to
+    /// [`MAX_ROUTER_INFO_VERIFICATION_LOOKUPS`] floodfills in total.
+    ///
+    /// The result is recorded in the profiles of the floodfills the router info was stored to and
+    /// if the verification failed, the router info is stored to other floodfills.
+    fn on_router_info_verification(&mut self, verified: bool) {
+        if !verified
+            && self.router_info_verification.as_ref().is_some_and(|verification| {
+                verification.verifiers.len() < MAX_ROUTER_INFO_VERIFICATION_LOOKUPS
+            })
+        {
+            tracing::debug!(
+                target: LOG_TARGET,
+                "router info store verification failed, verifying with another floodfill",
+            );
+            return self.verify_router_info_store();
+        }
+
+        let Some(RouterInfoVerification {
+            mut failed,
+            floodfills,
+            num_attempts,
+            router_id,
+            router_info,
+            ..
+        }) = self.router_info_verification.take()
+        else {
+            return;
+        };
+
+        if verified {
+            tracing::debug!(
+                target: LOG_TARGET,
+                ?floodfills,
+                "router info store verified",
+            );
+
+            floodfills.iter().for_each(|floodfill| {
+                self.router_ctx.profile_storage().database_store_success(floodfill);
+            });
+            return;
+        }
+
+        floodfills.iter().for_each(|floodfill| {
+            self.router_ctx.profile_storage().database_store_failure(floodfill);
+        });
+
+        if num_attempts >= MAX_ROUTER_INFO_STORE_ATTEMPTS {
+            tracing::warn!(
+                target: LOG_TARGET,
+                ?num_attempts,
+                "failed to verify router info store",
+            );
+            return;
+        }
+
+        tracing::debug!(
+            target: LOG_TARGET,
+            ?floodfills,
+            "router info store verification failed, storing to other floodfills",
+        );
+
+        failed.extend(floodfills);
+        self.store_router_info(router_id, router_info, failed, num_attempts + 1);
     }
 
     /// Perform general maintenance of [`NetDb`].
@@ -1882,6 +2210,16 @@ impl<R: Runtime> NetDb<R> {
                     key
                 });
             }
+            QueryKind::StoreVerification { floodfill, .. } => {
+                tracing::debug!(
+                    target: LOG_TARGET,
+                    %floodfill,
+                    "router info store verification timed out",
+                );
+
+                self.floodfill_dht.register_lookup_timeout(&floodfill);
+                self.on_router_info_verification(false);
+            }
             kind => tracing::debug!(
                 target: LOG_TARGET,
                 ?kind,
@@ -2012,10 +2350,53 @@ impl<R: Runtime> Future for NetDb<R> {
             match self.query_timers.poll_next_unpin(cx) {
                 Poll::Pending => break,
                 Poll::Ready(None) => return Poll::Ready(()),
-                Poll::Ready(Some(key)) =>
-                    if let Some(query) = self.active.remove(&key) {
+                Poll::Ready(Some(key)) => {
+                    // store verification lookups are timed out by `verification_timers`
+                    if self.active.get(&key).is_some_and(|query| {
+                        !core::matches!(query, QueryKind::StoreVerification { .. })
+                    }) {
+                        let query = self.active.remove(&key).expect("to exist");
                         self.handle_timeout(key, query);
-                    },
+                    }
+                }
+            }
+        }
+
+        loop {
+            match self.verification_timers.poll_next_unpin(cx) {
+                Poll::Pending => break,
+                Poll::Ready(None) => return Poll::Ready(()),
+                Poll::Ready(Some((key, nonce))) => {
+                    // timers of previous lookups are ignored
+                    if self.active.get(&key).is_some_and(|query| {
+                        core::matches!(
+                            query,
+                            QueryKind::StoreVerification { nonce: current, .. } if *current == nonce
+                        )
+                    }) {
+                        let query = self.active.remove(&key).expect("to exist");
+                        self.handle_timeout(key, query);
+                    }
+                }
+            }
+        }
+
+        let verification_ready = self
+            .router_info_verification
+            .as_mut()
+            .and_then(|verification| verification.timer.as_mut())
+            .is_some_and(|timer| timer.poll_unpin(cx).is_ready());
+
+        if verification_ready {
+            self.verify_router_info_store();
+
+            // register the timer into the executor if verification was postponed
+            if let Some(timer) = self
+                .router_info_verification
+                .as_mut()
+                .and_then(|verification| verification.timer.as_mut())
+            {
+                let _ = timer.poll_unpin(cx);
             }
         }
 
@@ -2059,6 +2440,7 @@ impl<R: Runtime> Future for NetDb<R> {
 mod tests {
     use super::*;
     use crate::{
+        banlist::BlocklistEntry,
         crypto::{SigningPrivateKey, StaticPrivateKey},
         events::EventManager,
         i2np::database::lookup::DatabaseLookupBuilder,
@@ -2071,6 +2453,7 @@ mod tests {
         subsystem::{InnerSubsystemEvent, SubsystemCommand},
         transport::ProtocolCommand,
         tunnel::{RoutingKindRecycle, TunnelMessage},
+        Ntcp2Config,
     };
     use std::collections::VecDeque;
     use thingbuf::mpsc::{channel, with_recycle};
@@ -2918,6 +3301,181 @@ mod tests {
         assert!(netdb.routers.is_empty());
     }
 
+    #[tokio::test]
+    async fn invalid_router_info_store_bans_sender() {
+        let (service, _rx, _tx, storage) = TransportService::new();
+        let (tp_handle, _tm_rx, _tp_tx, _srx) = TunnelPoolHandle::create();
+
+        let (router_info, static_key, signing_key) = RouterInfoBuilder::default().build();
+        let (_msg_tx, msg_rx) = channel(64);
+        let (_event_mgr, _event_subscriber, event_handle) = EventManager::new(None);
+        let (tm_mgr_tx, _tm_mgr_rx) = with_recycle(64, RoutingKindRecycle::default());
+        let (transit_tx, _transit_rx) = channel(64);
+        let rtbl = RoutingTable::new(router_info.identity.id(), tm_mgr_tx, transit_tx);
+
+        let (mut netdb, _handle) = NetDb::<MockRuntime>::new(
+            RouterContext::new(
+                MockRuntime::register_metrics(vec![], None),
+                storage,
+                router_info.identity.id(),
+                Bytes::from(router_info.serialize(&signing_key)),
+                static_key,
+                signing_key,
+                2u8,
+                event_handle.clone(),
+            ),
+            true,
+            service,
+            tp_handle,
+            rtbl,
+            msg_rx,
+        );
+
+        let (remote, _, remote_signing_key) = RouterInfoBuilder::default().build();
+        let remote_id = remote.identity.id();
+        let serialized = remote.serialize(&remote_signing_key);
+        let store = |key: Bytes, router_info: &[u8]| Message {
+            payload: DatabaseStoreBuilder::new(
+                key,
+                DatabaseStoreKind::RouterInfo {
+                    router_info: Bytes::from(MockRuntime::gzip_compress(router_info).unwrap()),
+                },
+            )
+            .build()
+            .to_vec(),
+            message_type: MessageType::DatabaseStore,
+            ..Default::default()
+        };
+
+        // store key doesn't match the router info
+        let sender1 = RouterId::random();
+        assert!(netdb
+            .on_message(
+                store(Bytes::from(RouterId::random().to_vec()), &serialized),
+                Some(sender1.clone())
+            )
+            .is_ok());
+        assert!(netdb.router_infos.is_empty());
+        assert!(netdb.router_ctx.banlist().is_banned(&sender1));
+
+        // router info has an invalid signature
+        let sender2 = RouterId::random();
+        let mut invalid = serialized.clone();
+        let len = invalid.len();
+        invalid[len - 1] ^= 0xff;
+
+        assert!(netdb
+            .on_message(
+                store(Bytes::from(remote_id.to_vec()), &invalid),
+                Some(sender2.clone())
+            )
+            .is_err());
+        assert!(netdb.router_infos.is_empty());
+        assert!(netdb.router_ctx.banlist().is_banned(&sender2));
+
+        // router info of a banned router is not stored
+        netdb
+            .router_ctx
+            .banlist()
+            .ban_router(remote_id.clone(), BanReason::InvalidSignature);
+        assert!(netdb
+            .on_message(store(Bytes::from(remote_id.to_vec()), &serialized), None)
+            .is_ok());
+        assert!(netdb.router_infos.is_empty());
+
+        // router info is stored after the ban has been lifted
+        netdb
+            .router_ctx
+            .banlist()
+            .apply(vec![BlocklistEntry::UnblockRouter(remote_id.clone())]);
+        assert!(netdb
+            .on_message(store(Bytes::from(remote_id.to_vec()), &serialized), None)
+            .is_ok());
+        assert_eq!(netdb.router_infos.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn excess_clustered_floodfills_not_selected() {
+        let (service, _rx, _tx, storage) = TransportService::new();
+        let (tp_handle, _tm_rx, _tp_tx, _srx) = TunnelPoolHandle::create();
+
+        let (router_info, static_key, signing_key) = RouterInfoBuilder::default().build();
+        let (_msg_tx, msg_rx) = channel(64);
+        let (_event_mgr, _event_subscriber, event_handle) = EventManager::new(None);
+        let (tm_mgr_tx, _tm_mgr_rx) = with_recycle(64, RoutingKindRecycle::default());
+        let (transit_tx, _transit_rx) = channel(64);
+        let rtbl = RoutingTable::new(router_info.identity.id(), tm_mgr_tx, transit_tx);
+
+        let (mut netdb, _handle) = NetDb::<MockRuntime>::new(
+            RouterContext::new(
+                MockRuntime::register_metrics(vec![], None),
+                storage.clone(),
+                router_info.identity.id(),
+                Bytes::from(router_info.serialize(&signing_key)),
+                static_key,
+                signing_key,
+                2u8,
+                event_handle.clone(),
+            ),
+            true,
+            service,
+            tp_handle,
+            rtbl,
+            msg_rx,
+        );
+
+        // add four floodfills sharing an address and two floodfills with random addresses
+        let floodfill = |host: Option<&str>| {
+            let mut builder = RouterInfoBuilder::default().as_floodfill();
+
+            if let Some(host) = host {
+                builder = builder.with_ntcp2(Ntcp2Config {
+                    port: 8888,
+                    host: Some(host.parse().unwrap()),
+                    publish: true,
+                    key: [0xbb; 32],
+                    iv: [0xaa; 16],
+                });
+            }
+
+            builder.build().0
+        };
+        let floodfills = [
+            floodfill(Some("198.51.5.1")),
+            floodfill(Some("198.51.5.1")),
+            floodfill(Some("198.51.5.1")),
+            floodfill(Some("198.51.5.1")),
+            floodfill(None),
+            floodfill(None),
+        ];
+
+        for router_info in floodfills.iter().cloned() {
+            let router_id = router_info.identity.id();
+
+            storage.add_router(router_info);
+            storage.dial_succeeded(&router_id);
+            netdb.floodfill_dht.add_router(router_id);
+        }
+
+        let (tx, rx) = oneshot::channel();
+        netdb.get_closest_floodfills(Bytes::from(vec![0xaa; 32]), tx);
+
+        let selected = rx
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|(router_id, _)| router_id)
+            .collect::<HashSet<_>>();
+        // only the floodfill exceeding the limit of the cluster is not selected
+        assert_eq!(selected.len(), 5);
+        assert!(!selected.contains(&floodfills[3].identity.id()));
+
+        // clustered floodfills are reported by the sybil detector
+        let report = netdb.router_ctx.profile_storage().sybil().report();
+        assert_eq!(report.clusters.len(), 1);
+        assert_eq!(report.clusters[0].address, "198.51.5.1");
+    }
+
     #[tokio::test]
     async fn lease_set_query() {
         let (service, _rx, _tx, storage) = TransportService::new();
@@ -4501,6 +5059,163 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn local_router_info_store_verified() {
+        let (service, _rx, _tx, storage) = TransportService::new();
+        let (tp_handle, tm_rx, _tp_tx, _srx) = TunnelPoolHandle::create();
+
+        // add few floodfills to router storage
+        let floodfills = (0..5)
+            .map(|_| {
+                let info = RouterInfoBuilder::default().as_floodfill().build().0;
+                let id = info.identity.id();
+                storage.add_router(info);
+
+                id
+            })
+            .collect::<HashSet<_>>();
+
+        let (router_info, static_key, signing_key) = RouterInfoBuilder::default().build();
+        let router_id = router_info.identity.id();
+        let (_msg_tx, msg_rx) = channel(64);
+        let serialized = Bytes::from(router_info.serialize(&signing_key));
+        let (_event_mgr, _event_subscriber, event_handle) = EventManager::new(None);
+        let (tm_mgr_tx, _tm_mgr_rx) = with_recycle(64, RoutingKindRecycle::default());
+        let (transit_tx, _transit_rx) = channel(64);
+        let rtbl = RoutingTable::new(router_id.clone(), tm_mgr_tx, transit_tx);
+
+        let (mut netdb, _handle) = NetDb::<MockRuntime>::new(
+            RouterContext::new(
+                MockRuntime::register_metrics(vec![], None),
+                storage.clone(),
+                router_id.clone(),
+                serialized.clone(),
+                static_key,
+                signing_key,
+                2u8,
+                event_handle.clone(),
+            ),
+            false,
+            service,
+            tp_handle,
+            rtbl,
+            msg_rx,
+        );
+
+        netdb
+            .message_builder
+            .inbound_tunnels
+            .add_tunnel(LeaseSet2::random().0.leases[0].clone());
+        netdb.message_builder.outbound_tunnels.add_tunnel(TunnelId::random());
+
+        // publish local router info and verify it's stored to three floodfills
+        netdb.publish_router_info(router_id.clone(), serialized.clone());
+        let stored = netdb.router_info_verification.as_ref().unwrap().floodfills.clone();
+        assert_eq!(stored.len(), 3);
+
+        // verify the store and ensure the lookup is sent to a floodfill not stored to
+        let key = Bytes::from(router_id.to_vec());
+        netdb.verify_router_info_store();
+
+        let verifier = || match tm_rx.try_recv().unwrap() {
+            TunnelMessage::RouterDeliveryViaRoute {
+                router_id, message, ..
+            } => {
+                assert!(floodfills.contains(&router_id));
+                assert!(!stored.contains(&router_id));
+                assert_eq!(
+                    Message::parse_standard(&message).unwrap().message_type,
+                    MessageType::DatabaseLookup
+                );
+
+                router_id
+            }
+            _ => panic!("invalid message received"),
+        };
+        let first_verifier = verifier();
+        let nonce = |netdb: &NetDb<MockRuntime>| match netdb.active.get(&key) {
+            Some(QueryKind::StoreVerification { nonce, .. }) => *nonce,
+            query => panic!("invalid query: {query:?}"),
+        };
+        let first_nonce = nonce(&netdb);
+
+        // time out the verification and verify the store is verified with another floodfill
+        // before the failure is recorded for the floodfills
+        let kind = netdb.active.remove(&key).unwrap();
+        netdb.handle_timeout(key.clone(), kind);
+
+        assert_ne!(verifier(), first_verifier);
+        let second_nonce = nonce(&netdb);
+
+        // timer of the first lookup doesn't expire the second lookup
+        {
+            let key = key.clone();
+            netdb.verification_timers.push(async move { (key, first_nonce) });
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(futures::poll!(&mut netdb).is_pending());
+        assert_eq!(nonce(&netdb), second_nonce);
+        assert_eq!(
+            netdb.router_info_verification.as_ref().unwrap().num_attempts,
+            1
+        );
+        {
+            let reader = storage.reader();
+            assert!(stored.iter().all(|floodfill| {
+                let profile = reader.profile(floodfill).unwrap();
+                profile.num_store_failures == 0
+            }));
+        }
+
+        let kind = netdb.active.remove(&key).unwrap();
+        netdb.handle_timeout(key.clone(), kind);
+        {
+            let reader = storage.reader();
+            assert!(stored.iter().all(|floodfill| {
+                let profile = reader.profile(floodfill).unwrap();
+                profile.num_store_failures == 1 && profile.num_store_successes == 0
+            }));
+        }
+
+        // verify the router info is stored to the remaining floodfills
+        let verification = netdb.router_info_verification.as_ref().unwrap();
+        assert_eq!(verification.num_attempts, 2);
+        assert_eq!(verification.floodfills.len(), 2);
+        assert!(verification.floodfills.iter().all(|floodfill| !stored.contains(floodfill)));
+        let restored = verification.floodfills.clone();
+
+        // verify the store again and reply with the local router info
+        netdb.verify_router_info_store();
+        assert!(tm_rx.try_recv().is_ok());
+
+        let message = DatabaseStoreBuilder::new(
+            key.clone(),
+            DatabaseStoreKind::RouterInfo {
+                router_info: Bytes::from(MockRuntime::gzip_compress(serialized).unwrap()),
+            },
+        )
+        .build();
+
+        assert!(netdb
+            .on_message(
+                Message {
+                    payload: message.to_vec(),
+                    message_type: MessageType::DatabaseStore,
+                    ..Default::default()
+                },
+                None
+            )
+            .is_ok());
+        assert!(netdb.router_info_verification.is_none());
+        assert!(!netdb.active.contains_key(&key));
+
+        let reader = storage.reader();
+        assert!(restored.iter().all(|floodfill| {
+            let profile = reader.profile(floodfill).unwrap();
+            profile.num_store_failures == 0 && profile.num_store_successes == 1
+        }));
+    }
+
     #[tokio::test]
     async fn router_exploration_works() {
         let (service, _rx, _tx, storage) = TransportService::new();
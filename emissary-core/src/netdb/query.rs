@@ -245,6 +245,15 @@ pub enum QueryKind<R: Runtime> {
 
     /// Router info lookup.
     Router,
+
+    /// Verification of a local router info store.
+    StoreVerification {
+        /// Floodfill the router info was looked up from.
+        floodfill: RouterId,
+
+        /// Nonce of the lookup, used to ignore the timers of previous lookups.
+        nonce: u32,
+    },
 }
 
 impl<R: Runtime> fmt::Debug for QueryKind<R> {
@@ -255,6 +264,11 @@ impl<R: Runtime> fmt::Debug for QueryKind<R> {
                 f.debug_struct("QueryKind::RouterInfo").finish_non_exhaustive(),
             Self::Exploration => f.debug_struct("QueryKind::Exploration").finish(),
             Self::Router => f.debug_struct("QueryKind::Router").finish(),
+            Self::StoreVerification { floodfill, nonce } => f
+                .debug_struct("QueryKind::StoreVerification")
+                .field("floodfill", &floodfill)
+                .field("nonce", &nonce)
+                .finish(),
         }
     }
 }
@@ -370,7 +370,7 @@ impl LeaseSet2 {
                         "invalid signature for lease set",
                     );
 
-                    Err::Error(make_error(input, ErrorKind::Fail))
+                    Err::Error(make_error(input, ErrorKind::Verify))
                 })?;
             }
             Some(verifying_key) => {
@@ -381,7 +381,7 @@ impl LeaseSet2 {
                         "invalid signature for lease set with offline key",
                     );
 
-                    Err::Error(make_error(input, ErrorKind::Fail))
+                    Err::Error(make_error(input, ErrorKind::Verify))
                 })?;
             }
         }
@@ -133,7 +133,7 @@ impl RouterInfo {
         }
     }
 
-    fn parse_frame(input: &[u8]) -> IResult<&[u8], RouterInfo> {
+    pub(crate) fn parse_frame(input: &[u8]) -> IResult<&[u8], RouterInfo> {
         let (rest, identity) = RouterIdentity::parse_frame(input)?;
         let (rest, published) = Date::parse_frame(rest)?;
         let (rest, num_addresses) = be_u8(rest)?;
@@ -221,7 +221,7 @@ impl RouterInfo {
                     ?error,
                     "invalid signature for router info",
                 );
-                Err::Error(make_error(input, ErrorKind::Fail))
+                Err::Error(make_error(input, ErrorKind::Verify))
             })?;
 
         Ok((
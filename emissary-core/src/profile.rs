@@ -17,9 +17,11 @@
 // DEALINGS IN THE SOFTWARE.
 
 use crate::{
+    banlist::Banlist,
     crypto::{base64_decode, base64_encode},
     primitives::{RouterId, RouterInfo},
     runtime::Runtime,
+    sybil::SybilDetector,
 };
 
 use bytes::Bytes;
@@ -44,7 +46,7 @@ const LAST_DECLINE_THRESHOLD: Duration = Duration::from_secs(180);
 /// How long the router is considered unreachable after last dial failure.
 const UNREACHABILITY_THRESHOLD: Duration = Duration::from_secs(180);
 
-/// How often [`ProfileManager`] sorts profiles.
+/// How often [`ProfileManager`] sorts profiles and prunes expired routers of the Sybil detector.
 const PROFILE_STORAGE_MAINTENANCE_INTERVAL: Duration = Duration::from_secs(60);
 
 /// How many routers does the high capacity bucket hold.
@@ -108,6 +110,12 @@ pub struct Profile {
     /// Number of times the router has been selecte for a tunnel.
     pub num_selected: usize,
 
+    /// How many [`DatabaseStore`]s sent to the floodfill could not be verified.
+    pub num_store_failures: usize,
+
+    /// How many [`DatabaseStore`]s sent to the floodfill have been verified.
+    pub num_store_successes: usize,
+
     /// Number of test failures for tunnels where the router was a selected hop.
     pub num_test_failures: usize,
 
@@ -133,6 +141,8 @@ impl Profile {
             num_lookup_successes: 0usize,
             num_rejected: 0usize,
             num_selected: 0usize,
+            num_store_failures: 0usize,
+            num_store_successes: 0usize,
             num_test_failures: 0usize,
             num_test_successes: 0usize,
             num_unaswered: 0usize,
@@ -200,6 +210,8 @@ impl Profile {
         self.num_lookup_failures as isize
             + (self.num_lookup_no_responses as isize * -5isize)
             + (self.num_lookup_successes as isize * 10isize)
+            + (self.num_store_failures as isize * -5isize)
+            + (self.num_store_successes as isize * 10isize)
     }
 }
 
@@ -227,6 +239,9 @@ impl Reader<'_> {
 /// Profile storage.
 #[derive(Clone)]
 pub struct ProfileStorage<R: Runtime> {
+    /// Router banlist.
+    banlist: Banlist<R>,
+
     /// Discovered routers.
     discovered_routers: Arc<RwLock<HashMap<RouterId, Vec<u8>>>>,
 
@@ -245,6 +260,9 @@ pub struct ProfileStorage<R: Runtime> {
     /// Standard routers.
     standard: Arc<RwLock<HashSet<RouterId>>>,
 
+    /// Sybil detector.
+    sybil: SybilDetector<R>,
+
     /// Marker for `Runtime`.
     _runtime: PhantomData<R>,
 }
@@ -402,12 +420,23 @@ impl<R: Runtime> ProfileStorage<R> {
             }
         };
 
+        // only the addresses of routers which the local router has connected to are tracked
+        let sybil = SybilDetector::<R>::new();
+        routers
+            .iter()
+            .filter(|(router_id, _)| {
+                profiles.get(*router_id).is_some_and(|profile| profile.num_connection > 0)
+            })
+            .for_each(|(_, router_info)| sybil.add_router(router_info));
+
         let storage = Self {
+            banlist: Banlist::new(),
             discovered_routers: Default::default(),
             fast: Arc::new(RwLock::new(fast)),
             profiles: Arc::new(RwLock::new(profiles)),
             routers: Arc::new(RwLock::new(routers)),
             standard: Arc::new(RwLock::new(standard)),
+            sybil,
             untracked: Arc::new(RwLock::new(untracked)),
             _runtime: Default::default(),
         };
@@ -480,6 +509,15 @@ impl<R: Runtime> ProfileStorage<R> {
         let routers = self.routers.read();
         let profiles = self.profiles.read();
 
+        // banned routers and routers which exceed the limit of their ip cluster are never
+        // returned, regardless of `filter`
+        let excluded = self.sybil.excluded();
+        let filter = |router_id: &RouterId, router_info: &RouterInfo, profile: &Profile| {
+            !self.banlist.is_router_info_banned(router_info)
+                && !excluded.contains(router_id)
+                && filter(router_id, router_info, profile)
+        };
+
         match bucket {
             Bucket::Any => {
                 let fast = self.fast.read();
@@ -542,6 +580,25 @@ impl<R: Runtime> ProfileStorage<R> {
         }
     }
 
+    /// Get reference to the [`Banlist`] of the router.
+    pub(crate) fn banlist(&self) -> &Banlist<R> {
+        &self.banlist
+    }
+
+    /// Get reference to the [`SybilDetector`] of the router.
+    pub(crate) fn sybil(&self) -> &SybilDetector<R> {
+        &self.sybil
+    }
+
+    /// Map each stored [`RouterInfo`] with `f`.
+    pub fn map_routers<T>(&self, f: impl Fn(&RouterId, &RouterInfo) -> T) -> Vec<T> {
+        self.routers
+            .read()
+            .iter()
+            .map(|(router_id, router_info)| f(router_id, router_info))
+            .collect()
+    }
+
     /// Get [`Reader`].
     pub fn reader(&self) -> Reader<'_> {
         Reader {
@@ -644,22 +701,30 @@ impl<R: Runtime> ProfileStorage<R> {
     /// Record dial success for `router_id`.
     ///
     /// Profile might not exist if this is an inbound connection.
+    ///
+    /// The addresses of the router are tracked by [`SybilDetector`].
     pub fn dial_succeeded(&self, router_id: &RouterId) {
-        let mut inner = self.profiles.write();
+        {
+            let mut inner = self.profiles.write();
 
-        match inner.get_mut(router_id) {
-            Some(profile) => {
-                profile.num_connection += 1;
-                profile.last_activity = R::time_since_epoch();
-            }
-            None => {
-                let mut profile = Profile::new();
-                profile.num_connection += 1;
-                profile.last_activity = R::time_since_epoch();
+            match inner.get_mut(router_id) {
+                Some(profile) => {
+                    profile.num_connection += 1;
+                    profile.last_activity = R::time_since_epoch();
+                }
+                None => {
+                    let mut profile = Profile::new();
+                    profile.num_connection += 1;
+                    profile.last_activity = R::time_since_epoch();
 
-                inner.insert(router_id.clone(), profile);
+                    inner.insert(router_id.clone(), profile);
+                }
             }
         }
+
+        if let Some(router_info) = self.routers.read().get(router_id) {
+            self.sybil.add_router(router_info);
+        }
     }
 
     /// Record dial failure for `router_id`.
@@ -712,6 +777,24 @@ impl<R: Runtime> ProfileStorage<R> {
         }
     }
 
+    /// Record verified store to a floodfill.
+    pub fn database_store_success(&self, router_id: &RouterId) {
+        let mut inner = self.profiles.write();
+
+        if let Some(profile) = inner.get_mut(router_id) {
+            profile.num_store_successes += 1;
+        }
+    }
+
+    /// Record store to a floodfill which couldn't be verified.
+    pub fn database_store_failure(&self, router_id: &RouterId) {
+        let mut inner = self.profiles.write();
+
+        if let Some(profile) = inner.get_mut(router_id) {
+            profile.num_store_failures += 1;
+        }
+    }
+
     /// Get backup of [`ProfileStorage`].
     pub fn backup(&self) -> Vec<(String, Option<Vec<u8>>, Profile)> {
         let profiles = self.profiles.read().clone();
@@ -758,11 +841,13 @@ impl<R: Runtime> ProfileStorage<R> {
             .unzip();
 
         Self {
+            banlist: Banlist::new(),
             discovered_routers: Default::default(),
             fast: Arc::new(RwLock::new(fast.into_iter().flatten().collect())),
             profiles: Arc::new(RwLock::new(profiles)),
             routers: Arc::new(RwLock::new(routers)),
             standard: Arc::new(RwLock::new(standard.into_iter().flatten().collect())),
+            sybil: SybilDetector::new(),
             untracked: Default::default(),
             _runtime: Default::default(),
         }
@@ -785,6 +870,7 @@ impl<R: Runtime> ProfileManager<R> {
     async fn run(self) {
         loop {
             R::delay(PROFILE_STORAGE_MAINTENANCE_INTERVAL).await;
+            self.profile_storage.sybil.prune();
 
             let profiles = self.profile_storage.profiles.read();
 
@@ -875,7 +961,10 @@ impl<R: Runtime> ProfileManager<R> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{crypto::base64_encode, primitives::RouterInfoBuilder, runtime::mock::MockRuntime};
+    use crate::{
+        banlist::BlocklistEntry, crypto::base64_encode, primitives::RouterInfoBuilder,
+        runtime::mock::MockRuntime, Ntcp2Config,
+    };
 
     #[tokio::test]
     async fn initialize_with_infos_without_profiles() {
@@ -929,6 +1018,8 @@ mod tests {
                         num_lookup_successes: i + 1,
                         num_rejected: i + 1,
                         num_selected: i + 1,
+                        num_store_failures: i + 1,
+                        num_store_successes: i + 1,
                         num_test_failures: i + 1,
                         num_test_successes: i + 1,
                         num_unaswered: i + 1,
@@ -982,6 +1073,8 @@ mod tests {
                         num_lookup_successes: i + 1,
                         num_rejected: i + 1,
                         num_selected: i + 1,
+                        num_store_failures: i + 1,
+                        num_store_successes: i + 1,
                         num_test_failures: i + 1,
                         num_test_successes: i + 1,
                         num_unaswered: i + 1,
@@ -1012,4 +1105,61 @@ mod tests {
             1usize
         );
     }
+
+    #[test]
+    fn clustered_routers_not_returned() {
+        let router = |host: &str| {
+            RouterInfoBuilder::default()
+                .with_ntcp2(Ntcp2Config {
+                    port: 8888,
+                    host: Some(host.parse().unwrap()),
+                    publish: true,
+                    key: [0xbb; 32],
+                    iv: [0xaa; 16],
+                })
+                .build()
+                .0
+        };
+        let routers = (0..4).map(|_| router("198.51.5.1")).collect::<Vec<_>>();
+        let profiles = ProfileStorage::<MockRuntime>::from_random(routers.clone());
+
+        // addresses of routers which haven't been connected to are not tracked
+        assert_eq!(
+            profiles.get_router_ids(Bucket::Any, |_, _, _| true).len(),
+            4
+        );
+
+        routers[..3]
+            .iter()
+            .for_each(|router| profiles.dial_succeeded(&router.identity.id()));
+        assert_eq!(
+            profiles.get_router_ids(Bucket::Any, |_, _, _| true).len(),
+            4
+        );
+
+        // fourth connected router on the same address exceeds the limit of the cluster
+        profiles.dial_succeeded(&routers[3].identity.id());
+
+        let router_ids = profiles.get_router_ids(Bucket::Any, |_, _, _| true);
+        assert_eq!(router_ids.len(), 3);
+        assert!(!router_ids.contains(&routers[3].identity.id()));
+    }
+
+    #[test]
+    fn banned_routers_not_returned() {
+        let infos = (0..3).map(|_| RouterInfoBuilder::default().build().0).collect::<Vec<_>>();
+        let banned = infos[0].identity.id();
+        let profiles = ProfileStorage::<MockRuntime>::from_random(infos);
+
+        assert_eq!(
+            profiles.get_router_ids(Bucket::Any, |_, _, _| true).len(),
+            3
+        );
+
+        profiles.banlist().apply(vec![BlocklistEntry::BlockRouter(banned.clone())]);
+
+        let router_ids = profiles.get_router_ids(Bucket::Any, |_, _, _| true);
+        assert_eq!(router_ids.len(), 2);
+        assert!(!router_ids.contains(&banned));
+    }
 }
@@ -17,6 +17,7 @@
 // DEALINGS IN THE SOFTWARE.
 
 use crate::{
+    banlist::Banlist,
     crypto::{SigningPrivateKey, StaticPrivateKey},
     events::EventHandle,
     primitives::RouterId,
@@ -148,6 +149,11 @@ impl<R: Runtime> RouterContext<R> {
         &self.inner.signing_key
     }
 
+    /// Get reference to [`Banlist`].
+    pub(crate) fn banlist(&self) -> &Banlist<R> {
+        self.profile_storage.banlist()
+    }
+
     /// Get reference to [`EventHandle`].
     pub(crate) fn event_handle(&self) -> &EventHandle<R> {
         &self.event_handle
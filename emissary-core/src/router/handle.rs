@@ -0,0 +1,381 @@
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Handle for inspecting and controlling a running [`Router`].
+//!
+//! [`Router`]: crate::router::Router
+
+use crate::{
+    banlist::BlocklistEntry,
+    crypto::base64_encode,
+    error::{ChannelError, Error},
+    i2np::HopRole,
+    primitives::{RouterId, RouterInfo, Str, TransportKind, TunnelId},
+};
+
+use futures_channel::oneshot;
+use thingbuf::mpsc;
+
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+/// Recycling strategy for [`RouterCommand`].
+#[derive(Default, Clone)]
+pub(crate) struct RouterCommandRecycle(());
+
+impl thingbuf::Recycle<RouterCommand> for RouterCommandRecycle {
+    fn new_element(&self) -> RouterCommand {
+        RouterCommand::Dummy
+    }
+
+    fn recycle(&self, element: &mut RouterCommand) {
+        *element = RouterCommand::Dummy;
+    }
+}
+
+/// Commands sent to [`Router`] via [`RouterHandle`].
+///
+/// [`Router`]: crate::router::Router
+pub(crate) enum RouterCommand {
+    /// Get status of the router.
+    GetStatus {
+        /// Oneshot sender used to send the result to caller.
+        tx: oneshot::Sender<RouterStatus>,
+    },
+
+    /// Get routers stored in the network database.
+    GetNetDb {
+        /// Oneshot sender used to send the result to caller.
+        tx: oneshot::Sender<Vec<NetDbRouterInfo>>,
+    },
+
+    /// Get findings of the Sybil detection.
+    GetSybil {
+        /// Oneshot sender used to send the result to caller.
+        tx: oneshot::Sender<SybilInfo>,
+    },
+
+    /// Add serialized router infos to the router, e.g., after a reseed.
+    AddRouters {
+        /// Serialized router infos.
+        router_infos: Vec<Vec<u8>>,
+    },
+
+    /// Apply blocklist entries to the banlist of the router.
+    ApplyBlocklist {
+        /// Blocklist entries.
+        entries: Vec<BlocklistEntry>,
+    },
+
+    /// Publish news items to the UIs.
+    PublishNews {
+        /// News items.
+        items: Vec<NewsItem>,
+    },
+
+    /// Start graceful shutdown.
+    Shutdown,
+
+    /// Dummy value.
+    Dummy,
+}
+
+/// Transit tunnel information.
+#[derive(Debug, Clone)]
+pub struct TransitTunnelInfo {
+    /// Tunnel ID.
+    pub tunnel_id: TunnelId,
+
+    /// Role of the router in the tunnel.
+    pub role: HopRole,
+}
+
+/// Tunnel pool information.
+#[derive(Debug, Clone, Default)]
+pub struct TunnelPoolInfo {
+    /// Name of the tunnel pool.
+    pub name: String,
+
+    /// Number of active inbound tunnels.
+    pub num_inbound: usize,
+
+    /// Number of active outbound tunnels.
+    pub num_outbound: usize,
+
+    /// Number of tunnels the pool has built.
+    pub num_tunnels_built: usize,
+
+    /// Number of tunnel build failures of the pool, either timeouts or rejections.
+    pub num_tunnel_build_failures: usize,
+}
+
+/// Summary of a router stored in the network database.
+#[derive(Debug, Clone, Default)]
+pub struct NetDbRouterInfo {
+    /// Base64-encoded router hash.
+    pub router_id: String,
+
+    /// Capabilities of the router, e.g., `XfR`.
+    pub capabilities: String,
+
+    /// Is the router a floodfill router.
+    pub floodfill: bool,
+
+    /// Is the router reachable.
+    pub reachable: bool,
+
+    /// Version of the router, if published.
+    pub version: Option<String>,
+
+    /// When the router info was published, in milliseconds since UNIX epoch.
+    pub published: u64,
+
+    /// Published transport addresses, e.g., `ntcp2 203.0.113.1:8888`.
+    pub addresses: Vec<String>,
+}
+
+impl NetDbRouterInfo {
+    /// Create new [`NetDbRouterInfo`] from `router_info`.
+    pub(crate) fn new(router_id: &RouterId, router_info: &RouterInfo) -> Self {
+        Self {
+            router_id: base64_encode(router_id.to_vec()),
+            capabilities: router_info.capabilities.to_string(),
+            floodfill: router_info.is_floodfill(),
+            reachable: router_info.is_reachable(),
+            version: router_info.options.get(&Str::from("router.version")).map(ToString::to_string),
+            published: *router_info.published.date(),
+            addresses: router_info
+                .addresses
+                .values()
+                .filter_map(|address| {
+                    let transport = match address.transport {
+                        TransportKind::Ntcp2 => "ntcp2",
+                        TransportKind::Ssu2 => "ssu2",
+                    };
+
+                    address
+                        .socket_address
+                        .map(|socket_address| format!("{transport} {socket_address}"))
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Router, IP address or IP address range in the banlist of the router.
+#[derive(Debug, Clone, Default)]
+pub struct BannedPeerInfo {
+    /// Banned peer: a base64-encoded router hash, an IP address or an IP address range.
+    pub peer: String,
+
+    /// Reason for the ban, e.g., `invalid signature`.
+    pub reason: String,
+
+    /// Seconds until the ban expires.
+    ///
+    /// `None` if the ban is permanent.
+    pub expires: Option<u64>,
+}
+
+/// Routers sharing an IP address or an IP prefix.
+#[derive(Debug, Clone, Default)]
+pub struct IpClusterInfo {
+    /// Shared IP address or IP prefix, e.g., `203.0.113.0/24`.
+    pub address: String,
+
+    /// Base64-encoded router hashes of the routers in the cluster.
+    pub routers: Vec<String>,
+}
+
+/// Routers which have declared the same router family.
+#[derive(Debug, Clone, Default)]
+pub struct FamilyInfo {
+    /// Name of the router family.
+    pub family: String,
+
+    /// Base64-encoded router hashes of the routers of the family.
+    pub routers: Vec<String>,
+}
+
+/// Floodfill whose router hash is close to the router hash of the local router.
+#[derive(Debug, Clone, Default)]
+pub struct CloseFloodfillInfo {
+    /// Base64-encoded router hash of the floodfill.
+    pub router_id: String,
+
+    /// Number of leading bits shared with the router hash of the local router.
+    pub shared_bits: u32,
+}
+
+/// Findings of the Sybil detection of the network database.
+#[derive(Debug, Clone, Default)]
+pub struct SybilInfo {
+    /// Number of routers which have published an IPv4 address.
+    pub num_ipv4_routers: usize,
+
+    /// Number of routers which have published an IPv6 address.
+    pub num_ipv6_routers: usize,
+
+    /// Clusters of routers sharing an IP address or an IP prefix.
+    pub clusters: Vec<IpClusterInfo>,
+
+    /// Router families of the tracked routers.
+    pub families: Vec<FamilyInfo>,
+
+    /// Floodfills close to the router hash of the local router.
+    pub close_floodfills: Vec<CloseFloodfillInfo>,
+}
+
+/// News item, e.g., an entry of the router news feed.
+#[derive(Debug, Clone, Default)]
+pub struct NewsItem {
+    /// ID of the item.
+    pub id: String,
+
+    /// Title of the item.
+    pub title: String,
+
+    /// When the item was last updated, e.g., `2025-01-01T12:00:00Z`.
+    pub updated: String,
+
+    /// Link to the full article, if any.
+    pub link: Option<String>,
+
+    /// Summary of the item, if any.
+    pub summary: Option<String>,
+}
+
+/// Destination information.
+#[derive(Debug, Clone)]
+pub struct DestinationInfo {
+    /// Name of the destination.
+    pub name: String,
+
+    /// Base32 address of the destination, if it's a server destination.
+    pub address: Option<String>,
+}
+
+/// Snapshot of router's status.
+///
+/// The snapshot is updated periodically by the subsystems of the router so it might be
+/// slightly out of date.
+#[derive(Debug, Clone, Default)]
+pub struct RouterStatus {
+    /// Base64-encoded router hashes of connected routers.
+    pub connected_routers: Vec<String>,
+
+    /// Active transit tunnels.
+    pub transit_tunnels: Vec<TransitTunnelInfo>,
+
+    /// Active tunnel pools.
+    pub tunnel_pools: Vec<TunnelPoolInfo>,
+
+    /// Active client and server destinations.
+    pub destinations: Vec<DestinationInfo>,
+}
+
+/// Handle to [`Router`].
+///
+/// [`Router`]: crate::router::Router
+#[derive(Clone)]
+pub struct RouterHandle {
+    /// TX channel for sending commands to [`Router`].
+    ///
+    /// [`Router`]: crate::router::Router
+    tx: mpsc::Sender<RouterCommand, RouterCommandRecycle>,
+}
+
+impl RouterHandle {
+    /// Create new [`RouterHandle`].
+    pub(super) fn new(tx: mpsc::Sender<RouterCommand, RouterCommandRecycle>) -> Self {
+        Self { tx }
+    }
+
+    /// Get status of the router.
+    pub async fn status(&self) -> crate::Result<RouterStatus> {
+        let (tx, rx) = oneshot::channel();
+
+        self.tx
+            .try_send(RouterCommand::GetStatus { tx })
+            .map_err(|error| Error::Channel(ChannelError::from(error)))?;
+
+        rx.await.map_err(|_| Error::Channel(ChannelError::Closed))
+    }
+
+    /// Get routers stored in the network database.
+    pub async fn netdb_routers(&self) -> crate::Result<Vec<NetDbRouterInfo>> {
+        let (tx, rx) = oneshot::channel();
+
+        self.tx
+            .try_send(RouterCommand::GetNetDb { tx })
+            .map_err(|error| Error::Channel(ChannelError::from(error)))?;
+
+        rx.await.map_err(|_| Error::Channel(ChannelError::Closed))
+    }
+
+    /// Get findings of the Sybil detection of the network database.
+    pub async fn sybil(&self) -> crate::Result<SybilInfo> {
+        let (tx, rx) = oneshot::channel();
+
+        self.tx
+            .try_send(RouterCommand::GetSybil { tx })
+            .map_err(|error| Error::Channel(ChannelError::from(error)))?;
+
+        rx.await.map_err(|_| Error::Channel(ChannelError::Closed))
+    }
+
+    /// Add serialized router infos to the router.
+    ///
+    /// Router infos that fail to parse are ignored.
+    pub fn add_routers(&self, router_infos: Vec<Vec<u8>>) -> crate::Result<()> {
+        self.tx
+            .try_send(RouterCommand::AddRouters { router_infos })
+            .map_err(|error| Error::Channel(ChannelError::from(error)))
+    }
+
+    /// Apply blocklist `entries` to the banlist of the router.
+    ///
+    /// Banned routers and routers with blocked addresses are neither dialed nor accepted.
+    pub fn apply_blocklist(&self, entries: Vec<BlocklistEntry>) -> crate::Result<()> {
+        self.tx
+            .try_send(RouterCommand::ApplyBlocklist { entries })
+            .map_err(|error| Error::Channel(ChannelError::from(error)))
+    }
+
+    /// Publish news `items` which are reported to [`EventSubscriber`] in [`Event::News`].
+    ///
+    /// Replaces previously published news items.
+    ///
+    /// [`EventSubscriber`]: crate::events::EventSubscriber
+    /// [`Event::News`]: crate::events::Event::News
+    pub fn publish_news(&self, items: Vec<NewsItem>) -> crate::Result<()> {
+        self.tx
+            .try_send(RouterCommand::PublishNews { items })
+            .map_err(|error| Error::Channel(ChannelError::from(error)))
+    }
+
+    /// Start graceful shutdown of the router.
+    pub fn shutdown(&self) -> crate::Result<()> {
+        self.tx
+            .try_send(RouterCommand::Shutdown)
+            .map_err(|error| Error::Channel(ChannelError::from(error)))
+    }
+}
@@ -17,15 +17,19 @@
 // DEALINGS IN THE SOFTWARE.
 
 use crate::{
+    banlist::Banlist,
     config::{Config, I2cpConfig, MetricsConfig, SamConfig},
-    crypto::{SigningPrivateKey, StaticPrivateKey},
+    crypto::{base64_encode, SigningPrivateKey, StaticPrivateKey},
     error::Error,
     events::{EventManager, EventSubscriber},
     i2cp::I2cpServer,
     netdb::NetDb,
     primitives::RouterInfo,
     profile::ProfileStorage,
-    router::context::RouterContext,
+    router::{
+        context::RouterContext,
+        handle::{NetDbRouterInfo, RouterCommand, RouterCommandRecycle, RouterHandle},
+    },
     runtime::{AddressBook, Runtime, Storage},
     sam::SamServer,
     shutdown::ShutdownContext,
@@ -37,6 +41,7 @@ use crate::{
 use bytes::Bytes;
 use futures::FutureExt;
 use rand_core::RngCore;
+use thingbuf::mpsc;
 
 use alloc::{string::ToString, sync::Arc, vec::Vec};
 use core::{
@@ -49,6 +54,7 @@ use core::{
 };
 
 pub mod context;
+pub mod handle;
 
 /// Logging target for the file.
 const LOG_TARGET: &str = "emissary::router";
@@ -60,6 +66,9 @@ const NET_ID: u8 = 2u8;
 /// immediately, cancelling graceful shutdown.
 const IMMEDIATE_SHUTDOWN_COUNT: usize = 2usize;
 
+/// Size of the channel used to send commands to [`Router`] via [`RouterHandle`].
+const COMMAND_CHANNEL_SIZE: usize = 64usize;
+
 /// Profile storage backup interval.
 ///
 /// How often is backup (stored to disk) taken of [`ProfileStorage`].
@@ -132,9 +141,21 @@ pub struct Router<R: Runtime> {
     /// Protocol address information.
     address_info: ProtocolAddressInfo,
 
+    /// Router banlist.
+    banlist: Banlist<R>,
+
+    /// RX channel for receiving commands from [`RouterHandle`]s.
+    command_rx: mpsc::Receiver<RouterCommand, RouterCommandRecycle>,
+
+    /// TX channel given to [`RouterHandle`]s.
+    command_tx: mpsc::Sender<RouterCommand, RouterCommandRecycle>,
+
     /// Event manager
     event_manager: EventManager<R>,
 
+    /// Profile storage.
+    profile_storage: ProfileStorage<R>,
+
     /// Shutdown context.
     shutdown_context: ShutdownContext<R>,
 
@@ -213,10 +234,15 @@ impl<R: Runtime> Router<R> {
             metrics,
             transit,
             refresh_interval,
+            blocklist,
             ..
         } = config;
 
         let profile_storage = ProfileStorage::<R>::new(&routers, &profiles);
+
+        if !blocklist.is_empty() {
+            profile_storage.banlist().apply(blocklist);
+        }
         let serialized_router_info = local_router_info.serialize(&local_signing_key);
         let local_router_id = local_router_info.identity.id();
         let mut address_info = ProtocolAddressInfo::default();
@@ -276,6 +302,7 @@ impl<R: Runtime> Router<R> {
             event_handle,
         );
         let sam_event_handle = router_ctx.event_handle().clone();
+        let banlist = router_ctx.banlist().clone();
 
         // create transport manager builder and initialize & start enabled transports
         //
@@ -387,6 +414,8 @@ impl<R: Runtime> Router<R> {
         //
         // all this task does is periodically backup router infos and profiles to disk
         if let Some(storage) = storage {
+            let profile_storage = profile_storage.clone();
+
             R::spawn(async move {
                 loop {
                     let _ = R::delay(PROFILE_STORAGE_BACKUP_INTERVAL).await;
@@ -416,10 +445,17 @@ impl<R: Runtime> Router<R> {
             transport_manager_builder.register_ssu2(context);
         }
 
+        let (command_tx, command_rx) =
+            mpsc::with_recycle(COMMAND_CHANNEL_SIZE, RouterCommandRecycle::default());
+
         Ok((
             Self {
                 address_info,
+                banlist,
+                command_rx,
+                command_tx,
                 event_manager,
+                profile_storage,
                 shutdown_context,
                 shutdown_count: 0usize,
                 transport_manager: transport_manager_builder.build(),
@@ -453,6 +489,57 @@ impl<R: Runtime> Router<R> {
         }
     }
 
+    /// Get [`RouterHandle`] which can be used to inspect and control the router while it's running.
+    pub fn handle(&self) -> RouterHandle {
+        RouterHandle::new(self.command_tx.clone())
+    }
+
+    /// Handle `command` received from a [`RouterHandle`].
+    fn on_command(&mut self, command: RouterCommand) {
+        match command {
+            RouterCommand::GetStatus { tx } => {
+                let connected_routers = self
+                    .transport_manager
+                    .connected_routers()
+                    .map(|router_id| base64_encode(router_id.to_vec()))
+                    .collect();
+
+                let _ = tx.send(self.event_manager.router_status(connected_routers));
+            }
+            RouterCommand::GetNetDb { tx } => {
+                let _ = tx.send(self.profile_storage.map_routers(NetDbRouterInfo::new));
+            }
+            RouterCommand::GetSybil { tx } => {
+                let _ = tx.send(self.profile_storage.sybil().report());
+            }
+            RouterCommand::AddRouters { router_infos } => {
+                let num_routers = router_infos.len();
+                let num_added = router_infos
+                    .into_iter()
+                    .filter_map(|serialized| {
+                        RouterInfo::parse(&serialized)
+                            .map(|router_info| (router_info, Bytes::from(serialized)))
+                    })
+                    .map(|(router_info, serialized)| {
+                        self.profile_storage.discover_router(router_info, serialized)
+                    })
+                    .filter(|added| *added)
+                    .count();
+
+                tracing::info!(
+                    target: LOG_TARGET,
+                    ?num_routers,
+                    ?num_added,
+                    "added routers to profile storage",
+                );
+            }
+            RouterCommand::ApplyBlocklist { entries } => self.banlist.apply(entries),
+            RouterCommand::PublishNews { items } => self.event_manager.news(items),
+            RouterCommand::Shutdown => self.shutdown(),
+            RouterCommand::Dummy => unreachable!(),
+        }
+    }
+
     /// Get reference to [`ProtocolAddressInfo`].
     pub fn protocol_address_info(&self) -> &ProtocolAddressInfo {
         &self.address_info
@@ -490,6 +577,11 @@ impl<R: Runtime> Future for Router<R> {
             return Poll::Ready(());
         }
 
+        // `Router` holds a sender itself so the channel never closes
+        while let Poll::Ready(Some(command)) = self.command_rx.poll_recv(cx) {
+            self.on_command(command);
+        }
+
         match self.transport_manager.poll_unpin(cx) {
             Poll::Pending => {}
             Poll::Ready(()) => return Poll::Ready(()),
@@ -270,6 +270,7 @@ impl<R: Runtime> Future for PendingSamConnection<R> {
                             "client connected"
                         );
 
+                        socket.set_version(version);
                         socket.send_message(
                             format!("HELLO REPLY RESULT=OK VERSION={version}\n")
                                 .as_bytes()
@@ -21,7 +21,7 @@ use crate::{
     error::StreamingError,
     primitives::DestinationId,
     runtime::{JoinSet, Runtime, TcpStream},
-    sam::socket::SamSocket,
+    sam::{parser::SamVersion, socket::SamSocket},
     util::AsyncWriteExt,
 };
 
@@ -118,6 +118,9 @@ pub enum SocketKind<R: Runtime> {
 
         /// Has the stream configured to be silent.
         silent: bool,
+
+        /// Negotiated SAMv3 version of the client.
+        version: Option<SamVersion>,
     },
 
     /// Forwarded connection open with `STREAM FORWARD`.
@@ -132,6 +135,9 @@ pub enum SocketKind<R: Runtime> {
 
         /// Has the stream configured to be silent.
         silent: bool,
+
+        /// Negotiated SAMv3 version of the client.
+        version: Option<SamVersion>,
     },
 }
 
@@ -209,7 +215,6 @@ enum ListenerState<R: Runtime> {
         silent: bool,
 
         /// Socket that was used to send the `STREAM FORWARD` command.
-        #[allow(unused)]
         socket: SamSocket<R>,
     },
 
@@ -284,6 +289,7 @@ impl<R: Runtime> StreamListener<R> {
                 Some(SocketKind::Accept {
                     pending_routing_path_handle,
                     silent,
+                    version: socket.version(),
                     socket: socket.into_inner(),
                 })
             }
@@ -291,7 +297,7 @@ impl<R: Runtime> StreamListener<R> {
                 port,
                 silent,
                 pending_routing_path_handle,
-                ..
+                socket,
             } => {
                 let port = *port;
                 let silent = *silent;
@@ -299,6 +305,7 @@ impl<R: Runtime> StreamListener<R> {
                 Some(SocketKind::Forwarded {
                     pending_routing_path_handle: pending_routing_path_handle.clone(),
                     silent,
+                    version: socket.version(),
                     future: Box::pin(async move {
                         R::TcpStream::connect(SocketAddr::new(
                             IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
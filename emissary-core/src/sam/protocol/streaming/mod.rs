@@ -24,6 +24,7 @@ use crate::{
     primitives::{Destination, DestinationId},
     runtime::{Instant, JoinSet, Runtime},
     sam::{
+        parser::SamVersion,
         protocol::streaming::{
             config::StreamConfig,
             listener::{SocketKind, StreamListener, StreamListenerEvent},
@@ -435,6 +436,8 @@ impl<R: Runtime> StreamManager<R> {
                 },
                 recv_stream_id,
                 destination_id.clone(),
+                destination.clone(),
+                (src_port, dst_port),
                 StreamKind::Outbound {
                     dst_port,
                     send_stream_id,
@@ -490,6 +493,8 @@ impl<R: Runtime> StreamManager<R> {
                 socket,
                 recv_stream_id,
                 destination.id(),
+                destination.clone(),
+                (src_port, dst_port),
                 StreamKind::Inbound {
                     payload: payload.to_vec(),
                 },
@@ -509,7 +514,8 @@ impl<R: Runtime> StreamManager<R> {
 
                 let (pending, packet) = PendingStream::new(
                     self.destination.clone(),
-                    destination_id.clone(),
+                    destination.clone(),
+                    (src_port, dst_port),
                     recv_stream_id,
                     payload.to_vec(),
                     &self.signing_key,
@@ -546,6 +552,8 @@ impl<R: Runtime> StreamManager<R> {
         socket: SocketKind<R>,
         recv_stream_id: u32,
         destination_id: DestinationId,
+        remote_destination: Destination,
+        (src_port, dst_port): (u16, u16),
         stream_kind: StreamKind,
     ) {
         // create context for the stream
@@ -564,11 +572,25 @@ impl<R: Runtime> StreamManager<R> {
             signing_key: self.signing_key.clone(),
         };
 
-        // if the socket wasn't configured to be silent, send the remote's destination
+        // if the socket wasn't configured to be silent, send the remote's full destination
         // to client before the socket is convered into a regural tcp stream
+        //
+        // starting from v3.2, the destination is followed by the ports of the stream
         let initial_message = match &socket {
-            SocketKind::Accept { silent, .. } | SocketKind::Forwarded { silent, .. } if !silent =>
-                Some(format!("{}\n", base64_encode(context.remote.to_vec())).into_bytes()),
+            SocketKind::Accept {
+                silent, version, ..
+            }
+            | SocketKind::Forwarded {
+                silent, version, ..
+            } if !silent => {
+                let destination = base64_encode(remote_destination.serialized());
+
+                Some(match version {
+                    None | Some(SamVersion::V31) => format!("{destination}\n").into_bytes(),
+                    Some(_) => format!("{destination} FROM_PORT={src_port} TO_PORT={dst_port}\n")
+                        .into_bytes(),
+                })
+            }
             SocketKind::Connect { silent, .. } if !silent =>
                 Some(b"STREAM STATUS RESULT=OK\n".to_vec()),
             _ => None,
@@ -722,6 +744,8 @@ impl<R: Runtime> StreamManager<R> {
                 destination_id,
                 send_stream_id,
                 packets,
+                ports,
+                remote_destination,
                 seq_nro,
                 ..
             } = self.pending_inbound.remove(&stream_id).expect("to exist");
@@ -731,6 +755,8 @@ impl<R: Runtime> StreamManager<R> {
                 socket,
                 stream_id,
                 destination_id,
+                remote_destination,
+                ports,
                 StreamKind::InboundPending {
                     send_stream_id,
                     seq_nro,
@@ -2063,7 +2089,7 @@ mod tests {
 
         let signing_key = SigningPrivateKey::from_bytes(&[1u8; 32]).unwrap();
         let destination = Destination::new::<MockRuntime>(signing_key.public());
-        let destination_id = base64_encode(destination.id().to_vec());
+        let remote_destination = base64_encode(destination.serialized());
         let packet = PacketBuilder::new(1337u32)
             .with_synchronize()
             .with_send_stream_id(0u32)
@@ -2112,10 +2138,86 @@ mod tests {
         reader.read_line(&mut response).await.unwrap();
         assert_eq!(response, "STREAM STATUS RESULT=OK\n");
 
-        // read remote's destination id
+        // read remote's destination
+        response.clear();
+        reader.read_line(&mut response).await.unwrap();
+        assert_eq!(response, format!("{remote_destination}\n"));
+
+        // read payload from syn packet
         response.clear();
         reader.read_line(&mut response).await.unwrap();
-        assert_eq!(response, format!("{destination_id}\n"));
+        assert_eq!(response, "hello, world\n");
+    }
+
+    #[tokio::test]
+    async fn ports_sent_to_v32_client() {
+        let socket_factory = SocketFactory::new().await;
+
+        let mut manager = {
+            let signing_key = SigningPrivateKey::from_bytes(&[0u8; 32]).unwrap();
+            let destination = Destination::new::<MockRuntime>(signing_key.public());
+            StreamManager::<MockRuntime>::new(destination, signing_key)
+        };
+
+        let signing_key = SigningPrivateKey::from_bytes(&[1u8; 32]).unwrap();
+        let destination = Destination::new::<MockRuntime>(signing_key.public());
+        let remote_destination = base64_encode(destination.serialized());
+        let packet = PacketBuilder::new(1337u32)
+            .with_synchronize()
+            .with_send_stream_id(0u32)
+            .with_replay_protection(&manager.destination.id())
+            .with_from_included(destination.clone())
+            .with_signature()
+            .with_payload(b"hello, world\n")
+            .build_and_sign(&signing_key)
+            .to_vec();
+
+        let outbound1 = TunnelId::random();
+        let inbound1 = Lease::random();
+        let mut path_manager1 =
+            RoutingPathManager::<MockRuntime>::new(manager.destination_id.clone(), vec![outbound1]);
+        let pending_handle = path_manager1.pending_handle();
+        path_manager1.register_leases(&destination.id(), Ok(vec![inbound1]));
+
+        tokio::spawn(async move { while path_manager1.next().await.is_some() {} });
+
+        // register listener for `manager1`
+        let (mut socket, client_socket) = socket_factory.socket().await;
+        socket.set_version(SamVersion::V32);
+        assert!(manager
+            .register_listener(ListenerKind::Ephemeral {
+                socket,
+                silent: false,
+                pending_routing_path_handle: pending_handle,
+            })
+            .is_ok());
+
+        // handle syn packet and spawn manager in the background
+        assert!(manager
+            .on_packet(I2cpPayload {
+                src_port: 1337u16,
+                dst_port: 8080u16,
+                protocol: Protocol::Streaming,
+                payload: packet
+            })
+            .is_ok());
+
+        tokio::spawn(async move { while manager.next().await.is_some() {} });
+
+        let mut reader = BufReader::new(client_socket);
+        let mut response = String::new();
+
+        // read stream status
+        reader.read_line(&mut response).await.unwrap();
+        assert_eq!(response, "STREAM STATUS RESULT=OK\n");
+
+        // read remote's destination
+        response.clear();
+        reader.read_line(&mut response).await.unwrap();
+        assert_eq!(
+            response,
+            format!("{remote_destination} FROM_PORT=1337 TO_PORT=8080\n")
+        );
 
         // read payload from syn packet
         response.clear();
@@ -2135,7 +2237,7 @@ mod tests {
 
         let signing_key = SigningPrivateKey::from_bytes(&[1u8; 32]).unwrap();
         let destination = Destination::new::<MockRuntime>(signing_key.public());
-        let destination_id = base64_encode(destination.id().to_vec());
+        let remote_destination = base64_encode(destination.serialized());
         let packet = PacketBuilder::new(1337u32)
             .with_synchronize()
             .with_send_stream_id(0u32)
@@ -2185,10 +2287,10 @@ mod tests {
         reader.read_line(&mut response).await.unwrap();
         assert_eq!(response, "STREAM STATUS RESULT=OK\n");
 
-        // read remote's destination id
+        // read remote's destination
         response.clear();
         reader.read_line(&mut response).await.unwrap();
-        assert_eq!(response, format!("{destination_id}\n"));
+        assert_eq!(response, format!("{remote_destination}\n"));
 
         // read payload from syn packet
         response.clear();
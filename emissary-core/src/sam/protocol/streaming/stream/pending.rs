@@ -76,6 +76,12 @@ pub struct PendingStream<R: Runtime> {
     /// Packets that have been received and ACKed while the stream was pending.
     pub packets: VecDeque<Vec<u8>>,
 
+    /// Destination of the remote peer.
+    pub remote_destination: Destination,
+
+    /// Source and destination ports of the `SYN` packet.
+    pub ports: (u16, u16),
+
     /// Receive stream ID.
     pub recv_stream_id: u32,
 
@@ -92,7 +98,8 @@ impl<R: Runtime> PendingStream<R> {
     /// `syn_payload` is the payload contained within the `SYN` message and may be empty.
     pub fn new(
         destination: Destination,
-        remote_destination_id: DestinationId,
+        remote_destination: Destination,
+        ports: (u16, u16),
         recv_stream_id: u32,
         syn_payload: Vec<u8>,
         signing_key: &SigningPrivateKey,
@@ -109,13 +116,15 @@ impl<R: Runtime> PendingStream<R> {
 
         (
             Self {
-                destination_id: remote_destination_id,
+                destination_id: remote_destination.id(),
                 established: R::now(),
                 packets: match syn_payload.is_empty() {
                     true => VecDeque::new(),
                     false => VecDeque::from_iter([syn_payload]),
                 },
+                ports,
                 recv_stream_id,
+                remote_destination,
                 send_stream_id,
                 seq_nro: 0u32,
             },
@@ -212,7 +221,8 @@ mod tests {
         let signing_key = SigningPrivateKey::from_bytes(&[0u8; 32]).unwrap();
         let (mut stream, _) = PendingStream::<NoopRuntime>::new(
             Destination::new::<NoopRuntime>(signing_key.public()),
-            DestinationId::random(),
+            Destination::new::<NoopRuntime>(SigningPrivateKey::random(NoopRuntime::rng()).public()),
+            (0u16, 0u16),
             1337u32,
             vec![],
             &SigningPrivateKey::random(NoopRuntime::rng()),
@@ -235,7 +245,8 @@ mod tests {
         let signing_key = SigningPrivateKey::from_bytes(&[0u8; 32]).unwrap();
         let (mut stream, _) = PendingStream::<NoopRuntime>::new(
             Destination::new::<NoopRuntime>(signing_key.public()),
-            DestinationId::random(),
+            Destination::new::<NoopRuntime>(SigningPrivateKey::random(NoopRuntime::rng()).public()),
+            (0u16, 0u16),
             1337u32,
             vec![],
             &SigningPrivateKey::random(NoopRuntime::rng()),
@@ -259,7 +270,8 @@ mod tests {
         let signing_key = SigningPrivateKey::from_bytes(&[0u8; 32]).unwrap();
         let (mut stream, _) = PendingStream::<NoopRuntime>::new(
             Destination::new::<NoopRuntime>(signing_key.public()),
-            DestinationId::random(),
+            Destination::new::<NoopRuntime>(SigningPrivateKey::random(NoopRuntime::rng()).public()),
+            (0u16, 0u16),
             1337u32,
             vec![],
             &SigningPrivateKey::random(NoopRuntime::rng()),
@@ -283,7 +295,8 @@ mod tests {
         let signing_key = SigningPrivateKey::from_bytes(&[0u8; 32]).unwrap();
         let (mut stream, _) = PendingStream::<NoopRuntime>::new(
             Destination::new::<NoopRuntime>(signing_key.public()),
-            DestinationId::random(),
+            Destination::new::<NoopRuntime>(SigningPrivateKey::random(NoopRuntime::rng()).public()),
+            (0u16, 0u16),
             1337u32,
             vec![],
             &SigningPrivateKey::random(NoopRuntime::rng()),
@@ -329,7 +342,8 @@ mod tests {
         let signing_key = SigningPrivateKey::from_bytes(&[0u8; 32]).unwrap();
         let (mut stream, _) = PendingStream::<NoopRuntime>::new(
             Destination::new::<NoopRuntime>(signing_key.public()),
-            DestinationId::random(),
+            Destination::new::<NoopRuntime>(SigningPrivateKey::random(NoopRuntime::rng()).public()),
+            (0u16, 0u16),
             1337u32,
             vec![],
             &SigningPrivateKey::random(NoopRuntime::rng()),
@@ -346,7 +360,8 @@ mod tests {
         let signing_key = SigningPrivateKey::from_bytes(&[0u8; 32]).unwrap();
         let (mut stream, _) = PendingStream::<NoopRuntime>::new(
             Destination::new::<NoopRuntime>(signing_key.public()),
-            DestinationId::random(),
+            Destination::new::<NoopRuntime>(SigningPrivateKey::random(NoopRuntime::rng()).public()),
+            (0u16, 0u16),
             1337u32,
             vec![],
             &SigningPrivateKey::random(NoopRuntime::rng()),
@@ -394,7 +409,8 @@ mod tests {
         let signing_key = SigningPrivateKey::from_bytes(&[0u8; 32]).unwrap();
         let (mut stream, _) = PendingStream::<NoopRuntime>::new(
             Destination::new::<NoopRuntime>(signing_key.public()),
-            DestinationId::random(),
+            Destination::new::<NoopRuntime>(SigningPrivateKey::random(NoopRuntime::rng()).public()),
+            (0u16, 0u16),
             1337u32,
             vec![1, 2, 3, 4],
             &SigningPrivateKey::random(NoopRuntime::rng()),
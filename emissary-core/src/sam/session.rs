@@ -301,7 +301,6 @@ pub struct SamSession<R: Runtime> {
     encryption_key: StaticPrivateKey,
 
     /// Event handle.
-    #[allow(unused)]
     event_handle: EventHandle<R>,
 
     /// Pending host lookups
@@ -1316,6 +1315,12 @@ impl<R: Runtime> SamSession<R> {
     }
 }
 
+impl<R: Runtime> Drop for SamSession<R> {
+    fn drop(&mut self) {
+        self.event_handle.destination_stopped(self.session_id.to_string());
+    }
+}
+
 impl<R: Runtime> Future for SamSession<R> {
     type Output = Arc<str>;
 
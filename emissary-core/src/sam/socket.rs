@@ -18,7 +18,7 @@
 
 use crate::{
     runtime::{AsyncRead, AsyncWrite, Runtime},
-    sam::parser::SamCommand,
+    sam::parser::{SamCommand, SamVersion},
     util::AsyncWriteExt,
 };
 
@@ -70,6 +70,9 @@ pub struct SamSocket<R: Runtime> {
     /// TCP stream.
     stream: R::TcpStream,
 
+    /// Negotiated SAMv3 version, if the client has been handshaked.
+    version: Option<SamVersion>,
+
     /// Write state.
     write_state: WriteState,
 }
@@ -82,10 +85,21 @@ impl<R: Runtime> SamSocket<R> {
             read_buffer: vec![0u8; 4096],
             read_offset: 0usize,
             stream,
+            version: None,
             write_state: WriteState::GetMessage,
         }
     }
 
+    /// Set the negotiated SAMv3 version of the client.
+    pub fn set_version(&mut self, version: SamVersion) {
+        self.version = Some(version);
+    }
+
+    /// Get the negotiated SAMv3 version of the client, if the client has been handshaked.
+    pub fn version(&self) -> Option<SamVersion> {
+        self.version
+    }
+
     /// Convert [`SamSocket`] into `TcpStream`.
     pub fn into_inner(self) -> R::TcpStream {
         self.stream
@@ -0,0 +1,746 @@
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Sybil detection.
+//!
+//! Tracks the IP addresses published by the routers the local router has connected to and flags
+//! clusters of routers sharing an IP address or an IP prefix (/24 for IPv4, /48 for IPv6). A large
+//! number of routers operated from the same address or subnet is a typical sign of a Sybil attack.
+//! Only a limited number of routers of each cluster, those which were tracked first, are selected
+//! as tunnel hops or as floodfills so that routers added later can't push out the routers already
+//! operating from the same subnet.
+//!
+//! Routers which have declared the same router family in their router info are operated by the same
+//! party so only a limited number of the routers of each family are selected as well.
+//!
+//! Floodfills whose router hash is suspiciously close to the router hash of the local router are
+//! flagged as well, as they may have been generated to take over the part of the keyspace the
+//! local router is responsible for.
+//!
+//! Routers and floodfills which haven't been seen for [`TRACKING_EXPIRATION`] are no longer
+//! tracked.
+
+use crate::{
+    banlist::IpRange,
+    crypto::base64_encode,
+    primitives::{RouterId, RouterInfo, Str},
+    router::handle::{CloseFloodfillInfo, FamilyInfo, IpClusterInfo, SybilInfo},
+    runtime::{Instant, Runtime},
+    util::is_global,
+};
+
+use hashbrown::{HashMap, HashSet};
+
+#[cfg(feature = "std")]
+use parking_lot::RwLock;
+#[cfg(feature = "no_std")]
+use spin::rwlock::RwLock;
+
+use alloc::{string::ToString, sync::Arc, vec::Vec};
+use core::{cmp::Reverse, net::IpAddr, time::Duration};
+
+/// Logging target for the file.
+const LOG_TARGET: &str = "emissary::sybil";
+
+/// Maximum number of routers allowed to share an IP address before they're considered a cluster.
+///
+/// This is also the number of routers of an address cluster that are still selected.
+const MAX_ROUTERS_PER_ADDRESS: usize = 3usize;
+
+/// Maximum number of routers allowed to share an IP prefix before they're considered a cluster.
+///
+/// This is also the number of routers of a prefix cluster that are still selected.
+const MAX_ROUTERS_PER_PREFIX: usize = 10usize;
+
+/// Maximum number of routers allowed to declare the same router family before they're considered
+/// a cluster.
+///
+/// This is also the number of routers of a family that are still selected.
+const MAX_ROUTERS_PER_FAMILY: usize = 10usize;
+
+/// How long a router or a floodfill is tracked after it was last seen.
+const TRACKING_EXPIRATION: Duration = Duration::from_secs(6 * 60 * 60);
+
+/// Length of the tracked IPv4 prefix.
+const IPV4_PREFIX_LEN: u8 = 24u8;
+
+/// Length of the tracked IPv6 prefix.
+const IPV6_PREFIX_LEN: u8 = 48u8;
+
+/// How many leading bits a floodfill's router hash must share with the router hash of the local
+/// router for the floodfill to be flagged.
+///
+/// With a few thousand floodfills in the network, the closest floodfill is expected to share
+/// around 12 bits with any given router hash.
+pub(crate) const CLOSE_KEY_SHARED_BITS: u32 = 18u32;
+
+/// Check if `address` should be tracked.
+///
+/// Local and private addresses are ignored as they don't identify the operator of the router and
+/// local test networks would otherwise be considered a cluster.
+fn is_tracked(address: &IpAddr) -> bool {
+    match address {
+        IpAddr::V4(address) => is_global(*address),
+        IpAddr::V6(address) => {
+            let segment = address.segments()[0];
+
+            !(address.is_loopback()
+                || address.is_unspecified()
+                || address.is_multicast()
+                || (segment & 0xfe00) == 0xfc00
+                || (segment & 0xffc0) == 0xfe80)
+        }
+    }
+}
+
+/// IP prefix of `address` tracked by [`SybilDetector`].
+fn prefix(address: &IpAddr) -> IpRange {
+    let prefix_len = match address {
+        IpAddr::V4(_) => IPV4_PREFIX_LEN,
+        IpAddr::V6(_) => IPV6_PREFIX_LEN,
+    };
+
+    IpRange::new(*address, prefix_len).expect("valid prefix length")
+}
+
+/// Router tracked by [`SybilDetector`].
+struct TrackedRouter<R: Runtime> {
+    /// Tracked IP addresses of the router.
+    addresses: Vec<IpAddr>,
+
+    /// Router family declared by the router, if any.
+    family: Option<Str>,
+
+    /// When the router was last seen.
+    last_seen: R::Instant,
+
+    /// Sequence number of the router, used to order the routers of a cluster.
+    sequence: u64,
+}
+
+/// Sybil detector state.
+struct SybilState<R: Runtime> {
+    /// Routers by IP address.
+    addresses: HashMap<IpAddr, HashSet<RouterId>>,
+
+    /// Floodfills whose router hashes are close to the router hash of the local router, the
+    /// number of leading bits they share with it and when they were last seen.
+    close_keys: HashMap<RouterId, (u32, R::Instant)>,
+
+    /// Cached set of routers which exceed the limit of their cluster.
+    ///
+    /// `None` if the tracked routers have changed since the set was last calculated.
+    excluded: Option<Arc<HashSet<RouterId>>>,
+
+    /// Routers by declared router family.
+    families: HashMap<Str, HashSet<RouterId>>,
+
+    /// Sequence number assigned to the next tracked router.
+    next_sequence: u64,
+
+    /// Routers by IP prefix.
+    prefixes: HashMap<IpRange, HashSet<RouterId>>,
+
+    /// Tracked routers.
+    routers: HashMap<RouterId, TrackedRouter<R>>,
+}
+
+impl<R: Runtime> Default for SybilState<R> {
+    fn default() -> Self {
+        Self {
+            addresses: HashMap::new(),
+            close_keys: HashMap::new(),
+            excluded: None,
+            families: HashMap::new(),
+            next_sequence: 0u64,
+            prefixes: HashMap::new(),
+            routers: HashMap::new(),
+        }
+    }
+}
+
+impl<R: Runtime> SybilState<R> {
+    /// Remove `router_id` from the address, prefix and family clusters.
+    ///
+    /// Returns the sequence number of the router if it was tracked.
+    fn remove_router(&mut self, router_id: &RouterId) -> Option<u64> {
+        let TrackedRouter {
+            addresses,
+            family,
+            sequence,
+            ..
+        } = self.routers.remove(router_id)?;
+
+        if let Some(family) = family {
+            if let Some(routers) = self.families.get_mut(&family) {
+                routers.remove(router_id);
+
+                if routers.is_empty() {
+                    self.families.remove(&family);
+                }
+            }
+        }
+
+        for address in addresses {
+            if let Some(routers) = self.addresses.get_mut(&address) {
+                routers.remove(router_id);
+
+                if routers.is_empty() {
+                    self.addresses.remove(&address);
+                }
+            }
+
+            let prefix = prefix(&address);
+
+            if let Some(routers) = self.prefixes.get_mut(&prefix) {
+                routers.remove(router_id);
+
+                if routers.is_empty() {
+                    self.prefixes.remove(&prefix);
+                }
+            }
+        }
+
+        Some(sequence)
+    }
+
+    /// Calculate the set of routers which exceed the limit of their address, prefix or family
+    /// cluster.
+    ///
+    /// The routers of a cluster which were tracked first are within the limit.
+    fn excluded(&self) -> HashSet<RouterId> {
+        let excess = |routers: &HashSet<RouterId>, limit: usize| {
+            let mut routers = routers
+                .iter()
+                .map(|router_id| {
+                    let sequence =
+                        self.routers.get(router_id).map_or(u64::MAX, |router| router.sequence);
+
+                    (sequence, router_id)
+                })
+                .collect::<Vec<_>>();
+            routers.sort_unstable_by_key(|(sequence, _)| *sequence);
+
+            routers
+                .into_iter()
+                .skip(limit)
+                .map(|(_, router_id)| router_id.clone())
+                .collect::<Vec<_>>()
+        };
+
+        self.addresses
+            .values()
+            .filter(|routers| routers.len() > MAX_ROUTERS_PER_ADDRESS)
+            .flat_map(|routers| excess(routers, MAX_ROUTERS_PER_ADDRESS))
+            .chain(
+                self.prefixes
+                    .values()
+                    .filter(|routers| routers.len() > MAX_ROUTERS_PER_PREFIX)
+                    .flat_map(|routers| excess(routers, MAX_ROUTERS_PER_PREFIX)),
+            )
+            .chain(
+                self.families
+                    .values()
+                    .filter(|routers| routers.len() > MAX_ROUTERS_PER_FAMILY)
+                    .flat_map(|routers| excess(routers, MAX_ROUTERS_PER_FAMILY)),
+            )
+            .collect()
+    }
+}
+
+/// Sybil detector.
+pub(crate) struct SybilDetector<R: Runtime> {
+    /// Detector state.
+    state: Arc<RwLock<SybilState<R>>>,
+}
+
+impl<R: Runtime> Clone for SybilDetector<R> {
+    fn clone(&self) -> Self {
+        Self {
+            state: Arc::clone(&self.state),
+        }
+    }
+}
+
+impl<R: Runtime> SybilDetector<R> {
+    /// Create new [`SybilDetector`].
+    pub(crate) fn new() -> Self {
+        Self {
+            state: Default::default(),
+        }
+    }
+
+    /// Track the IP addresses and the router family published in `router_info`.
+    ///
+    /// Must only be called for routers the local router has connected to, as the addresses of
+    /// other routers can't be trusted. If the router was already tracked, its previous addresses
+    /// and family are replaced but it keeps its position in its clusters.
+    pub(crate) fn add_router(&self, router_info: &RouterInfo) {
+        let router_id = router_info.identity.id();
+        let addresses = router_info
+            .addresses
+            .values()
+            .filter_map(|address| address.socket_address.map(|address| address.ip()))
+            .filter(is_tracked)
+            .collect::<HashSet<_>>();
+        let family = router_info.options.get(&Str::from("family")).cloned();
+
+        let mut state = self.state.write();
+
+        // the set of excluded routers is recalculated only if the addresses or the family have
+        // changed
+        if let Some(router) = state.routers.get_mut(&router_id) {
+            router.last_seen = R::now();
+
+            if router.family == family
+                && router.addresses.len() == addresses.len()
+                && router.addresses.iter().all(|address| addresses.contains(address))
+            {
+                return;
+            }
+        }
+        state.excluded = None;
+
+        let sequence = match state.remove_router(&router_id) {
+            Some(sequence) => sequence,
+            None => {
+                state.next_sequence += 1;
+                state.next_sequence
+            }
+        };
+
+        if addresses.is_empty() && family.is_none() {
+            return;
+        }
+
+        for address in &addresses {
+            let num_address = {
+                let routers = state.addresses.entry(*address).or_default();
+                routers.insert(router_id.clone());
+                routers.len()
+            };
+            let num_prefix = {
+                let routers = state.prefixes.entry(prefix(address)).or_default();
+                routers.insert(router_id.clone());
+                routers.len()
+            };
+
+            if num_address == MAX_ROUTERS_PER_ADDRESS + 1
+                || num_prefix == MAX_ROUTERS_PER_PREFIX + 1
+            {
+                tracing::debug!(
+                    target: LOG_TARGET,
+                    %router_id,
+                    ?address,
+                    ?num_address,
+                    ?num_prefix,
+                    "router cluster detected",
+                );
+            }
+        }
+
+        if let Some(family) = &family {
+            let num_family = {
+                let routers = state.families.entry(family.clone()).or_default();
+                routers.insert(router_id.clone());
+                routers.len()
+            };
+
+            if num_family == MAX_ROUTERS_PER_FAMILY + 1 {
+                tracing::debug!(
+                    target: LOG_TARGET,
+                    %router_id,
+                    %family,
+                    ?num_family,
+                    "router family cluster detected",
+                );
+            }
+        }
+
+        state.routers.insert(
+            router_id,
+            TrackedRouter {
+                addresses: addresses.into_iter().collect(),
+                family,
+                last_seen: R::now(),
+                sequence,
+            },
+        );
+    }
+
+    /// Flag floodfill `router_id` whose router hash shares `shared_bits` leading bits with the
+    /// router hash of the local router.
+    pub(crate) fn flag_close_key(&self, router_id: RouterId, shared_bits: u32) {
+        if self
+            .state
+            .write()
+            .close_keys
+            .insert(router_id.clone(), (shared_bits, R::now()))
+            .is_none()
+        {
+            tracing::warn!(
+                target: LOG_TARGET,
+                %router_id,
+                ?shared_bits,
+                "floodfill close to local router hash",
+            );
+        }
+    }
+
+    /// Stop tracking routers and floodfills which haven't been seen for [`TRACKING_EXPIRATION`].
+    pub(crate) fn prune(&self) {
+        let mut state = self.state.write();
+        let expired = state
+            .routers
+            .iter()
+            .filter_map(|(router_id, router)| {
+                (router.last_seen.elapsed() >= TRACKING_EXPIRATION).then_some(router_id.clone())
+            })
+            .collect::<Vec<_>>();
+
+        if !expired.is_empty() {
+            state.excluded = None;
+            expired.iter().for_each(|router_id| {
+                state.remove_router(router_id);
+            });
+        }
+
+        let num_close_keys = state.close_keys.len();
+        state
+            .close_keys
+            .retain(|_, (_, last_seen)| last_seen.elapsed() < TRACKING_EXPIRATION);
+
+        tracing::trace!(
+            target: LOG_TARGET,
+            num_routers = ?expired.len(),
+            num_close_keys = ?(num_close_keys - state.close_keys.len()),
+            "pruned expired routers",
+        );
+    }
+
+    /// Get the routers which exceed the limit of their address, prefix or family cluster.
+    ///
+    /// These routers must not be selected as tunnel hops or as floodfills.
+    pub(crate) fn excluded(&self) -> Arc<HashSet<RouterId>> {
+        if let Some(excluded) = &self.state.read().excluded {
+            return Arc::clone(excluded);
+        }
+
+        let mut state = self.state.write();
+        let excluded = Arc::new(state.excluded());
+        state.excluded = Some(Arc::clone(&excluded));
+
+        excluded
+    }
+
+    /// Get report of the Sybil detection.
+    pub(crate) fn report(&self) -> SybilInfo {
+        let state = self.state.read();
+        let encode = |routers: &HashSet<RouterId>| {
+            routers
+                .iter()
+                .map(|router_id| base64_encode(router_id.to_vec()))
+                .collect::<Vec<_>>()
+        };
+
+        let (num_ipv4_routers, num_ipv6_routers) =
+            state.routers.values().fold((0usize, 0usize), |(ipv4, ipv6), router| {
+                (
+                    ipv4 + router.addresses.iter().any(IpAddr::is_ipv4) as usize,
+                    ipv6 + router.addresses.iter().any(IpAddr::is_ipv6) as usize,
+                )
+            });
+
+        let mut clusters = state
+            .addresses
+            .iter()
+            .filter(|(_, routers)| routers.len() > MAX_ROUTERS_PER_ADDRESS)
+            .map(|(address, routers)| IpClusterInfo {
+                address: address.to_string(),
+                routers: encode(routers),
+            })
+            .chain(
+                state
+                    .prefixes
+                    .iter()
+                    .filter(|(_, routers)| routers.len() > MAX_ROUTERS_PER_PREFIX)
+                    .map(|(prefix, routers)| IpClusterInfo {
+                        address: prefix.to_string(),
+                        routers: encode(routers),
+                    }),
+            )
+            .collect::<Vec<_>>();
+        clusters.sort_by_key(|cluster| Reverse(cluster.routers.len()));
+
+        let mut families = state
+            .families
+            .iter()
+            .map(|(family, routers)| FamilyInfo {
+                family: family.to_string(),
+                routers: encode(routers),
+            })
+            .collect::<Vec<_>>();
+        families.sort_by_key(|family| Reverse(family.routers.len()));
+
+        let mut close_floodfills = state
+            .close_keys
+            .iter()
+            .map(|(router_id, (shared_bits, _))| CloseFloodfillInfo {
+                router_id: base64_encode(router_id.to_vec()),
+                shared_bits: *shared_bits,
+            })
+            .collect::<Vec<_>>();
+        close_floodfills.sort_by_key(|floodfill| Reverse(floodfill.shared_bits));
+
+        SybilInfo {
+            num_ipv4_routers,
+            num_ipv6_routers,
+            clusters,
+            families,
+            close_floodfills,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{primitives::RouterInfoBuilder, runtime::mock::MockRuntime};
+    use core::net::SocketAddr;
+
+    /// Create random router info which has published `address`.
+    fn router_info(address: &str) -> RouterInfo {
+        let mut router_info = RouterInfoBuilder::default().build().0;
+
+        router_info.addresses.values_mut().for_each(|router_address| {
+            router_address.socket_address = Some(SocketAddr::new(address.parse().unwrap(), 8888));
+        });
+
+        router_info
+    }
+
+    #[test]
+    fn address_cluster() {
+        let detector = SybilDetector::<MockRuntime>::new();
+        let routers = (0..=MAX_ROUTERS_PER_ADDRESS)
+            .map(|_| router_info("198.51.5.1"))
+            .collect::<Vec<_>>();
+
+        routers[..MAX_ROUTERS_PER_ADDRESS]
+            .iter()
+            .for_each(|router| detector.add_router(router));
+        assert!(detector.excluded().is_empty());
+
+        // only the router exceeding the limit is excluded
+        detector.add_router(&routers[MAX_ROUTERS_PER_ADDRESS]);
+        assert_eq!(
+            *detector.excluded(),
+            HashSet::from([routers[MAX_ROUTERS_PER_ADDRESS].identity.id()])
+        );
+
+        // tracking a router again doesn't change its position in the cluster
+        detector.add_router(&routers[0]);
+        assert_eq!(
+            *detector.excluded(),
+            HashSet::from([routers[MAX_ROUTERS_PER_ADDRESS].identity.id()])
+        );
+
+        let report = detector.report();
+        assert_eq!(report.num_ipv4_routers, MAX_ROUTERS_PER_ADDRESS + 1);
+        assert_eq!(report.clusters.len(), 1);
+        assert_eq!(report.clusters[0].address, "198.51.5.1");
+        assert_eq!(
+            report.clusters[0].routers.len(),
+            MAX_ROUTERS_PER_ADDRESS + 1
+        );
+
+        // router publishes a new address which dissolves the cluster
+        let mut router = routers[0].clone();
+        router.addresses.values_mut().for_each(|router_address| {
+            router_address.socket_address = Some("198.51.6.1:8888".parse().unwrap());
+        });
+        detector.add_router(&router);
+
+        assert!(detector.excluded().is_empty());
+        assert!(detector.report().clusters.is_empty());
+        assert_eq!(
+            detector.report().num_ipv4_routers,
+            MAX_ROUTERS_PER_ADDRESS + 1
+        );
+    }
+
+    #[test]
+    fn prefix_cluster() {
+        let detector = SybilDetector::<MockRuntime>::new();
+
+        (1..=MAX_ROUTERS_PER_PREFIX)
+            .for_each(|i| detector.add_router(&router_info(&format!("198.51.5.{i}"))));
+        (1..=MAX_ROUTERS_PER_PREFIX)
+            .for_each(|i| detector.add_router(&router_info(&format!("2001:470:1:{i}::1"))));
+        assert!(detector.excluded().is_empty());
+
+        let ipv4 = router_info("198.51.5.200");
+        let ipv6 = router_info("2001:470:1:ffff::1");
+        detector.add_router(&ipv4);
+        detector.add_router(&ipv6);
+
+        assert_eq!(
+            *detector.excluded(),
+            HashSet::from([ipv4.identity.id(), ipv6.identity.id()])
+        );
+
+        // routers in a different prefix are not part of the cluster
+        let other = router_info("198.51.6.1");
+        detector.add_router(&other);
+        assert!(!detector.excluded().contains(&other.identity.id()));
+
+        let report = detector.report();
+        assert_eq!(report.num_ipv4_routers, MAX_ROUTERS_PER_PREFIX + 2);
+        assert_eq!(report.num_ipv6_routers, MAX_ROUTERS_PER_PREFIX + 1);
+        assert_eq!(report.clusters.len(), 2);
+        assert!(report.clusters.iter().any(|cluster| cluster.address == "198.51.5.0/24"));
+        assert!(report.clusters.iter().any(|cluster| cluster.address == "2001:470:1::/48"));
+    }
+
+    #[test]
+    fn local_addresses_ignored() {
+        let detector = SybilDetector::<MockRuntime>::new();
+
+        [
+            "127.0.0.1",
+            "10.0.0.1",
+            "192.168.1.1",
+            "::1",
+            "fd00::1",
+            "fe80::1",
+        ]
+        .into_iter()
+        .for_each(|address| {
+            (0..MAX_ROUTERS_PER_PREFIX + 1)
+                .for_each(|_| detector.add_router(&router_info(address)));
+        });
+
+        assert!(detector.excluded().is_empty());
+
+        let report = detector.report();
+        assert_eq!(report.num_ipv4_routers, 0);
+        assert_eq!(report.num_ipv6_routers, 0);
+    }
+
+    #[test]
+    fn close_floodfills_reported() {
+        let detector = SybilDetector::<MockRuntime>::new();
+        let (first, second) = (RouterId::random(), RouterId::random());
+
+        detector.flag_close_key(first.clone(), 18);
+        detector.flag_close_key(second.clone(), 24);
+        detector.flag_close_key(second.clone(), 24);
+
+        let report = detector.report();
+        assert_eq!(report.close_floodfills.len(), 2);
+        assert_eq!(
+            report.close_floodfills[0].router_id,
+            base64_encode(second.to_vec())
+        );
+        assert_eq!(report.close_floodfills[0].shared_bits, 24);
+        assert_eq!(report.close_floodfills[1].shared_bits, 18);
+    }
+
+    #[test]
+    fn family_cluster() {
+        let detector = SybilDetector::<MockRuntime>::new();
+        let family = |router_info: &mut RouterInfo, family: &'static str| {
+            router_info.options.insert(Str::from("family"), Str::from(family));
+        };
+        let routers = (0..=MAX_ROUTERS_PER_FAMILY)
+            .map(|i| {
+                let mut router = router_info(&format!("198.51.{i}.1"));
+                family(&mut router, "family");
+                router
+            })
+            .collect::<Vec<_>>();
+
+        routers[..MAX_ROUTERS_PER_FAMILY]
+            .iter()
+            .for_each(|router| detector.add_router(router));
+        assert!(detector.excluded().is_empty());
+
+        // only the router exceeding the limit of the family is excluded
+        detector.add_router(&routers[MAX_ROUTERS_PER_FAMILY]);
+        assert_eq!(
+            *detector.excluded(),
+            HashSet::from([routers[MAX_ROUTERS_PER_FAMILY].identity.id()])
+        );
+
+        let report = detector.report();
+        assert!(report.clusters.is_empty());
+        assert_eq!(report.families.len(), 1);
+        assert_eq!(report.families[0].family, "family");
+        assert_eq!(report.families[0].routers.len(), MAX_ROUTERS_PER_FAMILY + 1);
+
+        // router leaves the family which dissolves the cluster
+        let mut router = routers[0].clone();
+        family(&mut router, "other");
+        detector.add_router(&router);
+
+        assert!(detector.excluded().is_empty());
+        assert_eq!(detector.report().families.len(), 2);
+    }
+
+    #[test]
+    fn expired_routers_pruned() {
+        let detector = SybilDetector::<MockRuntime>::new();
+        let routers = (0..=MAX_ROUTERS_PER_ADDRESS)
+            .map(|_| router_info("198.51.5.1"))
+            .collect::<Vec<_>>();
+        let (close, recent) = (RouterId::random(), RouterId::random());
+
+        routers.iter().for_each(|router| detector.add_router(router));
+        detector.flag_close_key(close.clone(), 20);
+        detector.flag_close_key(recent.clone(), 20);
+        assert_eq!(detector.excluded().len(), 1);
+
+        // the first router and the first floodfill haven't been seen recently
+        {
+            let mut state = detector.state.write();
+            let expired = MockRuntime::now().subtract(TRACKING_EXPIRATION);
+
+            state.routers.get_mut(&routers[0].identity.id()).unwrap().last_seen = expired;
+            state.close_keys.get_mut(&close).unwrap().1 = expired;
+        }
+        detector.prune();
+
+        assert!(detector.excluded().is_empty());
+
+        let report = detector.report();
+        assert_eq!(report.num_ipv4_routers, MAX_ROUTERS_PER_ADDRESS);
+        assert!(report.clusters.is_empty());
+        assert_eq!(report.close_floodfills.len(), 1);
+        assert_eq!(
+            report.close_floodfills[0].router_id,
+            base64_encode(recent.to_vec())
+        );
+
+        // router seen again is tracked as the last router of the cluster
+        detector.add_router(&routers[0]);
+        assert_eq!(
+            *detector.excluded(),
+            HashSet::from([routers[0].identity.id()])
+        );
+    }
+}
@@ -22,7 +22,7 @@ use crate::{
     netdb::NetDbHandle,
     primitives::{Date, RouterAddress, RouterId, RouterInfo, Str, TransportKind},
     router::context::RouterContext,
-    runtime::{Counter, Gauge, JoinSet, MetricType, MetricsHandle, Runtime},
+    runtime::{Counter, Gauge, Instant, JoinSet, MetricType, MetricsHandle, Runtime},
     subsystem::{
         InnerSubsystemEvent, SubsystemCommand, SubsystemEvent, SubsystemHandle, SubsystemKind,
     },
@@ -60,6 +60,12 @@ const LOG_TARGET: &str = "emissary::transport-manager";
 /// Local router info gets republished to `NetDb` every 15 minutes.
 const ROUTER_INFO_REPUBLISH_INTERVAL: Duration = Duration::from_secs(15 * 60);
 
+/// How often the banlist is published to the event system.
+///
+/// Publishing the banlist requires cloning all bans so it's done less frequently than other
+/// updates.
+const BANLIST_PUBLISH_INTERVAL: Duration = Duration::from_secs(60);
+
 /// Default channel size.
 const DEFAULT_CHANNEL_SIZE: usize = 1024;
 
@@ -519,6 +525,7 @@ impl<R: Runtime> TransportManagerBuilder<R> {
     /// Build into [`TransportManager`].
     pub fn build(self) -> TransportManager<R> {
         TransportManager {
+            banlist_published: None,
             cmd_rx: self.cmd_rx,
             event_handle: self.router_ctx.event_handle().clone(),
             external_address: None,
@@ -549,6 +556,9 @@ impl<R: Runtime> TransportManagerBuilder<R> {
 /// together with enabled, lower-level transports and polling for polling those
 /// transports so that they can make progress.
 pub struct TransportManager<R: Runtime> {
+    /// When the banlist was last published to the event system.
+    banlist_published: Option<R::Instant>,
+
     /// RX channel for receiving commands from other subsystems.
     cmd_rx: Receiver<ProtocolCommand>,
 
@@ -620,6 +630,11 @@ impl<R: Runtime> TransportManager<R> {
         self.shutting_down = true;
     }
 
+    /// Get iterator over the router IDs of connected routers.
+    pub fn connected_routers(&self) -> impl Iterator<Item = &RouterId> {
+        self.routers.iter()
+    }
+
     /// Add external address for the router.
     pub fn add_external_address(&mut self, address: Ipv4Addr) {
         tracing::info!(
@@ -720,6 +735,19 @@ impl<R: Runtime> TransportManager<R> {
         }
 
         match self.router_ctx.profile_storage().get(&router_id) {
+            Some(router_info) if self.router_ctx.banlist().is_router_info_banned(&router_info) => {
+                tracing::debug!(
+                    target: LOG_TARGET,
+                    %router_id,
+                    "cannot dial router, router is banned",
+                );
+
+                // report connection failure to subsystems
+                let mut handle = self.subsystem_handle.clone();
+                R::spawn(async move {
+                    handle.report_connection_failure(router_id).await;
+                });
+            }
             Some(router_info) => {
                 // even though `TransportService` prevents dialing the same router from the same
                 // subsystem twice, the notion of a "pending router", i.e., it being dialed, is not
@@ -838,6 +866,23 @@ impl<R: Runtime> Future for TransportManager<R> {
                         direction,
                         router_id,
                     })) => match direction {
+                        _ if self.router_ctx.banlist().is_banned(&router_id) => {
+                            tracing::debug!(
+                                target: LOG_TARGET,
+                                %router_id,
+                                ?direction,
+                                "router is banned, rejecting connection",
+                            );
+                            self.transports[index].reject(&router_id);
+
+                            // the router was banned while it was being dialed
+                            if self.pending_connections.remove(&router_id) {
+                                let mut handle = self.subsystem_handle.clone();
+                                R::spawn(async move {
+                                    handle.report_connection_failure(router_id).await;
+                                });
+                            }
+                        }
                         Direction::Inbound if self.pending_connections.contains(&router_id) => {
                             tracing::debug!(
                                 target: LOG_TARGET,
@@ -1007,6 +1052,15 @@ impl<R: Runtime> Future for TransportManager<R> {
 
         if self.event_handle.poll_unpin(cx).is_ready() {
             self.event_handle.num_connected_routers(self.routers.len());
+
+            if self
+                .banlist_published
+                .as_ref()
+                .is_none_or(|published| published.elapsed() >= BANLIST_PUBLISH_INTERVAL)
+            {
+                self.event_handle.banlist(self.router_ctx.banlist().bans());
+                self.banlist_published = Some(R::now());
+            }
         }
 
         Poll::Pending
@@ -58,7 +58,7 @@ impl<R: Runtime> Ntcp2Listener<R> {
 }
 
 impl<R: Runtime> Stream for Ntcp2Listener<R> {
-    type Item = R::TcpStream;
+    type Item = (R::TcpStream, SocketAddr);
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
         loop {
@@ -74,7 +74,7 @@ impl<R: Runtime> Stream for Ntcp2Listener<R> {
                         );
                         continue;
                     }
-                    _ => return Poll::Ready(Some(stream)),
+                    address => return Poll::Ready(Some((stream, address))),
                 },
             }
         }
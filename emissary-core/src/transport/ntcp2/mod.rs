@@ -17,6 +17,7 @@
 // DEALINGS IN THE SOFTWARE.
 
 use crate::{
+    banlist::BanReason,
     config::Ntcp2Config,
     error::{ConnectionError, Error},
     primitives::{RouterAddress, RouterId, RouterInfo},
@@ -294,14 +295,38 @@ impl<R: Runtime> Stream for Ntcp2Transport<R> {
             match self.listener.poll_next_unpin(cx) {
                 Poll::Pending => break,
                 Poll::Ready(None) => return Poll::Ready(None),
-                Poll::Ready(Some(stream)) => {
+                Poll::Ready(Some((stream, address))) => {
+                    if self.router_ctx.banlist().is_blocked(&address.ip()) {
+                        tracing::debug!(
+                            target: LOG_TARGET,
+                            ?address,
+                            "inbound tcp connection from a banned address, rejecting",
+                        );
+                        continue;
+                    }
+
                     tracing::trace!(
                         target: LOG_TARGET,
                         "inbound tcp connection, accept session",
                     );
 
                     let future = self.session_manager.accept_session(stream);
-                    self.pending_handshakes.push(future);
+                    let banlist = self.router_ctx.banlist().clone();
+
+                    self.pending_handshakes.push(async move {
+                        let result = future.await;
+
+                        // ban the address temporarily if the remote router completed the
+                        // handshake but belongs to a different network
+                        //
+                        // failures during earlier handshake messages are not authenticated and
+                        // don't result in a ban
+                        if let Err((_, Error::NetworkMismatch)) = &result {
+                            banlist.ban_address(address.ip(), BanReason::NetworkMismatch);
+                        }
+
+                        result
+                    });
                     self.router_ctx.metrics_handle().counter(NUM_INBOUND).increment(1);
                 }
             }
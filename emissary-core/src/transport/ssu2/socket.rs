@@ -307,6 +307,17 @@ impl<R: Runtime> Ssu2Socket<R> {
                 pkt_num,
                 src_id,
             }) => {
+                if self.router_ctx.banlist().is_blocked(&address.ip()) {
+                    tracing::debug!(
+                        target: LOG_TARGET,
+                        ?address,
+                        "token request from a banned address, ignoring",
+                    );
+                    return Ok(());
+                }
+
+                // the address is not banned since token request is not authenticated and its
+                // source address may have been spoofed
                 if net_id != self.router_ctx.net_id() {
                     tracing::warn!(
                         target: LOG_TARGET,
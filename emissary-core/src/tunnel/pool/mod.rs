@@ -54,7 +54,7 @@ use hashbrown::{HashMap, HashSet};
 use listener::ReceiveKind;
 use rand_core::RngCore;
 
-use alloc::vec::Vec;
+use alloc::{string::ToString, vec::Vec};
 use core::{
     future::Future,
     pin::{pin, Pin},
@@ -1429,6 +1429,7 @@ impl<R: Runtime, S: TunnelSelector + HopSelector> Future for TunnelPool<R, S> {
                 self.inbound_tunnels.values().for_each(|(tunnel_id, _)| {
                     self.routing_table.remove_tunnel(tunnel_id);
                 });
+                self.event_handle.tunnel_pool_shut_down(self.config.name.to_string());
 
                 if let Err(error) = self.context.register_tunnel_pool_shut_down() {
                     tracing::warn!(
@@ -1445,6 +1446,13 @@ impl<R: Runtime, S: TunnelSelector + HopSelector> Future for TunnelPool<R, S> {
         if self.event_handle.poll_unpin(cx).is_ready() {
             self.event_handle
                 .tunnel_status(self.num_tunnels_built, self.num_tunnel_build_failures);
+            self.event_handle.tunnel_pool_status(
+                self.config.name.to_string(),
+                self.inbound.len(),
+                self.outbound.len(),
+                self.num_tunnels_built,
+                self.num_tunnel_build_failures,
+            );
 
             // reset counters to zero as the cumulative success/failure tate is tracked by the event
             // system whereas each tunnel pool only  tracks the rate during each report period
@@ -48,6 +48,7 @@ use futures::{
     FutureExt, StreamExt,
 };
 use futures_channel::oneshot;
+use hashbrown::HashMap;
 use thingbuf::mpsc::Receiver;
 
 use alloc::{string::ToString, vec::Vec};
@@ -123,6 +124,9 @@ pub struct TransitTunnelManager<R: Runtime> {
     /// Routing table.
     routing_table: RoutingTable,
 
+    /// Roles of active transit tunnels, reported to the event system.
+    roles: HashMap<TunnelId, HopRole>,
+
     /// Shutdown handle.
     shutdown_handle: ShutdownHandle,
 
@@ -158,6 +162,7 @@ impl<R: Runtime> TransitTunnelManager<R> {
             config,
             event_handle: router_ctx.event_handle().clone(),
             message_rx,
+            roles: HashMap::new(),
             router_ctx,
             routing_table,
             shutdown_handle,
@@ -340,6 +345,7 @@ impl<R: Runtime> TransitTunnelManager<R> {
                 )?;
                 let (tx, rx) = oneshot::channel::<()>();
                 let event_handle = self.router_ctx.event_handle().clone();
+                self.roles.insert(tunnel_id, role);
 
                 match role {
                     HopRole::InboundGateway => self.tunnels.push(async move {
@@ -599,6 +605,7 @@ impl<R: Runtime> TransitTunnelManager<R> {
                 let tunnel_keys = session.finalize()?;
                 let (tx, rx) = oneshot::channel::<()>();
                 let event_handle = self.router_ctx.event_handle().clone();
+                self.roles.insert(tunnel_id, role);
 
                 match role {
                     HopRole::InboundGateway => {
@@ -862,6 +869,7 @@ impl<R: Runtime> Future for TransitTunnelManager<R> {
                 }
             };
 
+            self.roles.remove(&tunnel_id);
             self.routing_table.remove_tunnel(&tunnel_id);
             self.router_ctx.metrics_handle().gauge(NUM_TRANSIT_TUNNELS).decrement(1);
 
@@ -877,6 +885,9 @@ impl<R: Runtime> Future for TransitTunnelManager<R> {
 
         if self.event_handle.poll_unpin(cx).is_ready() {
             self.router_ctx.event_handle().num_transit_tunnels(self.tunnels.len());
+            self.router_ctx
+                .event_handle()
+                .transit_tunnels(self.roles.iter().map(|(id, role)| (*id, *role)).collect());
         }
 
         Poll::Pending
@@ -16,10 +16,18 @@
 // FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
 // DEALINGS IN THE SOFTWARE.
 
+use ed25519_dalek::VerifyingKey;
 use rsa::{BigUint, RsaPublicKey};
-use x509_parser::public_key::PublicKey;
+use x509_parser::{
+    der_parser::der::parse_der_integer,
+    oid_registry::{OID_EC_P256, OID_NIST_EC_P384, OID_NIST_EC_P521, OID_SIG_ED25519},
+    public_key::PublicKey,
+};
 
-use std::{collections::HashMap, sync::LazyLock};
+use std::{
+    collections::HashMap,
+    sync::{LazyLock, RwLock},
+};
 
 const ACETONE: &str = include_str!(concat!(
     env!("CARGO_MANIFEST_DIR"),
@@ -110,35 +118,212 @@ pub const CERTIFICATES: &[(&str, &str); 15] = &[
     ("unixeno@cubicchaos.net", CUBIC_CHAOS),
 ];
 
-/// Public keys of the reseed bundle signers.
-pub static PUBLIC_KEYS: LazyLock<HashMap<&'static str, RsaPublicKey>> = LazyLock::new(|| {
-    CERTIFICATES
-        .iter()
-        .filter_map(|(key, value)| {
-            let cert = pem::parse(value).ok()?.into_contents();
-            let (_, cert) = x509_parser::parse_x509_certificate(&cert).ok()?;
-
-            if !cert.tbs_certificate.validity.is_valid() {
-                tracing::warn!(
-                    target: "emissary-util::certificate",
-                    %key,
-                    not_before = ?cert.tbs_certificate.validity.not_before,
-                    not_after = ?cert.tbs_certificate.validity.not_after,
-                    "self-signed certificate is no longer valid",
-                );
+/// Public key of a reseed bundle signer.
+#[derive(Clone)]
+pub enum SignerKey {
+    /// DSA public key.
+    Dsa(dsa::VerifyingKey),
+
+    /// ECDSA P-256 public key.
+    EcdsaP256(p256::ecdsa::VerifyingKey),
+
+    /// ECDSA P-384 public key.
+    EcdsaP384(p384::ecdsa::VerifyingKey),
+
+    /// ECDSA P-521 public key.
+    EcdsaP521(p521::ecdsa::VerifyingKey),
+
+    /// Ed25519 public key.
+    Ed25519(VerifyingKey),
+
+    /// RSA public key.
+    Rsa(RsaPublicKey),
+}
+
+impl PartialEq for SignerKey {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Dsa(key), Self::Dsa(other)) => key == other,
+            (Self::EcdsaP256(key), Self::EcdsaP256(other)) => key == other,
+            (Self::EcdsaP384(key), Self::EcdsaP384(other)) => key == other,
+            (Self::EcdsaP521(key), Self::EcdsaP521(other)) =>
+                key.to_encoded_point(false) == other.to_encoded_point(false),
+            (Self::Ed25519(key), Self::Ed25519(other)) => key == other,
+            (Self::Rsa(key), Self::Rsa(other)) => key == other,
+            _ => false,
+        }
+    }
+}
+
+/// Attempt to parse DSA public key from `y` and domain `parameters` of the certificate.
+fn parse_dsa_key(y: &[u8], parameters: &[u8]) -> Option<dsa::VerifyingKey> {
+    let (rest, p) = parse_der_integer(parameters).ok()?;
+    let (rest, q) = parse_der_integer(rest).ok()?;
+    let (_, g) = parse_der_integer(rest).ok()?;
 
+    let components = dsa::Components::from_components(
+        dsa::BigUint::from_bytes_be(p.as_slice().ok()?),
+        dsa::BigUint::from_bytes_be(q.as_slice().ok()?),
+        dsa::BigUint::from_bytes_be(g.as_slice().ok()?),
+    )
+    .ok()?;
+
+    dsa::VerifyingKey::from_components(components, dsa::BigUint::from_bytes_be(y)).ok()
+}
+
+/// Attempt to parse a PEM-encoded X.509 `certificate` of a reseed bundle signer.
+///
+/// Returns the common name of the subject, which is the signer ID, and the public key.
+fn parse_certificate(certificate: &str) -> Option<(String, SignerKey)> {
+    let cert = pem::parse(certificate).ok()?.into_contents();
+    let (_, cert) = x509_parser::parse_x509_certificate(&cert).ok()?;
+    let signer_id = cert.subject().iter_common_name().next()?.as_str().ok()?.to_string();
+
+    if !cert.tbs_certificate.validity.is_valid() {
+        tracing::warn!(
+            target: "emissary-util::certificate",
+            %signer_id,
+            not_before = ?cert.tbs_certificate.validity.not_before,
+            not_after = ?cert.tbs_certificate.validity.not_after,
+            "self-signed certificate is no longer valid",
+        );
+
+        return None;
+    }
+
+    let algorithm = &cert.public_key().algorithm;
+    let key = match cert.public_key().parsed().ok()? {
+        PublicKey::RSA(public_key) => {
+            let modulus = BigUint::from_bytes_be(public_key.modulus);
+            let exponent = BigUint::from_bytes_be(public_key.exponent);
+
+            SignerKey::Rsa(RsaPublicKey::new(modulus, exponent).ok()?)
+        }
+        PublicKey::EC(point) => {
+            let curve = algorithm.parameters.as_ref()?.as_oid().ok()?;
+
+            if curve == OID_EC_P256 {
+                SignerKey::EcdsaP256(p256::ecdsa::VerifyingKey::from_sec1_bytes(point.data()).ok()?)
+            } else if curve == OID_NIST_EC_P384 {
+                SignerKey::EcdsaP384(p384::ecdsa::VerifyingKey::from_sec1_bytes(point.data()).ok()?)
+            } else if curve == OID_NIST_EC_P521 {
+                SignerKey::EcdsaP521(p521::ecdsa::VerifyingKey::from_sec1_bytes(point.data()).ok()?)
+            } else {
                 return None;
             }
+        }
+        PublicKey::DSA(y) => SignerKey::Dsa(parse_dsa_key(y, algorithm.parameters.as_ref()?.data)?),
+        PublicKey::Unknown(public_key) if algorithm.algorithm == OID_SIG_ED25519 =>
+            SignerKey::Ed25519(VerifyingKey::from_bytes(public_key.try_into().ok()?).ok()?),
+        _ => return None,
+    };
 
-            match cert.public_key().parsed().ok()? {
-                PublicKey::RSA(public_key) => {
-                    let modulus = BigUint::from_bytes_be(public_key.modulus);
-                    let exponent = BigUint::from_bytes_be(public_key.exponent);
+    Some((signer_id, key))
+}
 
-                    Some((*key, RsaPublicKey::new(modulus, exponent).ok()?))
-                }
-                _ => None,
-            }
-        })
-        .collect()
+/// Public keys of the reseed bundle signers.
+///
+/// Initialized with the certificates bundled with emissary and can be extended at runtime with
+/// [`add_trusted_certificate()`].
+static PUBLIC_KEYS: LazyLock<RwLock<HashMap<String, SignerKey>>> = LazyLock::new(|| {
+    RwLock::new(
+        CERTIFICATES
+            .iter()
+            .filter_map(|(signer_id, certificate)| {
+                parse_certificate(certificate).map(|(_, key)| (signer_id.to_string(), key))
+            })
+            .collect(),
+    )
 });
+
+/// Get public key of `signer_id`, if it's trusted.
+pub(crate) fn public_key(signer_id: &str) -> Option<SignerKey> {
+    PUBLIC_KEYS.read().expect("to succeed").get(signer_id).cloned()
+}
+
+/// Public keys of the news feed signers.
+///
+/// No news feed signers are bundled with emissary, the certificates must be added at runtime with
+/// [`add_trusted_news_certificate()`].
+static NEWS_PUBLIC_KEYS: LazyLock<RwLock<HashMap<String, SignerKey>>> =
+    LazyLock::new(Default::default);
+
+/// Get public key of news feed signer `signer_id`, if it's trusted.
+pub(crate) fn news_public_key(signer_id: &str) -> Option<SignerKey> {
+    NEWS_PUBLIC_KEYS.read().expect("to succeed").get(signer_id).cloned()
+}
+
+/// Parse `certificate` and insert its public key into `keys`.
+///
+/// Fails if `keys` already contains another key for the signer so a user-supplied certificate
+/// cannot replace the key of a trusted signer, e.g., one of the bundled reseed signers.
+fn add_certificate(
+    keys: &RwLock<HashMap<String, SignerKey>>,
+    certificate: &str,
+) -> anyhow::Result<String> {
+    let (signer_id, key) =
+        parse_certificate(certificate).ok_or_else(|| anyhow::anyhow!("invalid certificate"))?;
+    let mut keys = keys.write().expect("to succeed");
+
+    match keys.get(&signer_id) {
+        Some(trusted) if trusted != &key =>
+            anyhow::bail!("signer {signer_id} is already trusted with another key"),
+        Some(_) => {}
+        None => {
+            tracing::debug!(
+                target: "emissary-util::certificate",
+                %signer_id,
+                "add trusted certificate",
+            );
+
+            keys.insert(signer_id.clone(), key);
+        }
+    }
+
+    Ok(signer_id)
+}
+
+/// Trust reseed bundles signed by the owner of `certificate`.
+///
+/// `certificate` is a PEM-encoded X.509 certificate with a DSA, ECDSA (P-256, P-384 or P-521),
+/// Ed25519 or RSA public key. The common name of the certificate's subject is used as the signer
+/// ID.
+///
+/// Returns the signer ID on success.
+pub fn add_trusted_certificate(certificate: &str) -> anyhow::Result<String> {
+    add_certificate(&PUBLIC_KEYS, certificate)
+}
+
+/// Trust news feeds and blocklists signed by the owner of `certificate`.
+///
+/// See [`add_trusted_certificate()`] for the supported certificates.
+///
+/// Returns the signer ID on success.
+pub fn add_trusted_news_certificate(certificate: &str) -> anyhow::Result<String> {
+    add_certificate(&NEWS_PUBLIC_KEYS, certificate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ED25519: &str = include_str!("../assets/test/test-ed25519_at_mail.i2p.crt");
+    const RSA: &str = include_str!("../assets/test/test-rsa4096_at_mail.i2p.crt");
+
+    #[test]
+    fn trusted_signer_not_replaced() {
+        let (signer_id, key) = parse_certificate(ED25519).unwrap();
+        let (_, other_key) = parse_certificate(RSA).unwrap();
+
+        // same certificate can be added more than once
+        let keys = RwLock::new(HashMap::new());
+        assert_eq!(add_certificate(&keys, ED25519).unwrap(), signer_id);
+        assert_eq!(add_certificate(&keys, ED25519).unwrap(), signer_id);
+
+        // signer is already trusted with another key
+        let keys = RwLock::new(HashMap::from([(signer_id.clone(), other_key.clone())]));
+        assert!(add_certificate(&keys, ED25519).is_err());
+        assert!(keys.read().unwrap().get(&signer_id) == Some(&other_key));
+        assert!(keys.read().unwrap().get(&signer_id) != Some(&key));
+    }
+}
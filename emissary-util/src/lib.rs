@@ -18,8 +18,8 @@
 
 #![allow(clippy::manual_async_fn)]
 
-mod certificates;
-
+pub mod certificates;
+pub mod news;
 pub mod reseeder;
 pub mod runtime;
 pub mod su3;
@@ -0,0 +1,280 @@
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Router news feed.
+//!
+//! The news feed is an Atom feed distributed in a signed su3 file. In addition to news entries,
+//! the feed may contain an `<i2p:blocklist>` element listing router hashes and IP addresses
+//! which should be blocked or unblocked.
+//!
+//! https://geti2p.net/spec/updates#news-file-specification
+
+use emissary_core::{
+    crypto::base64_decode, primitives::RouterId, router::handle::NewsItem, BlocklistEntry, IpRange,
+};
+
+use std::net::IpAddr;
+
+/// Logging target for the file.
+const LOG_TARGET: &str = "emissary::news";
+
+/// Namespace of the Atom elements.
+const ATOM_NAMESPACE: &str = "http://www.w3.org/2005/Atom";
+
+/// Namespace of the I2P-specific elements.
+const I2P_NAMESPACE: &str = "http://geti2p.net/en/docs/spec/updates";
+
+/// Length of a SHA-256 router hash.
+const ROUTER_HASH_LEN: usize = 32usize;
+
+/// Parsed news feed.
+#[derive(Debug, Default)]
+pub struct NewsFeed {
+    /// Blocklist entries.
+    pub blocklist: Vec<BlocklistEntry>,
+
+    /// News entries.
+    pub items: Vec<NewsItem>,
+}
+
+/// Attempt to parse an address entry from `value`.
+///
+/// `value` is either an IP address or a range of IP addresses in CIDR notation.
+fn parse_address_entry(value: &str, block: bool) -> Option<BlocklistEntry> {
+    if let Ok(address) = value.parse::<IpAddr>() {
+        return Some(match block {
+            true => BlocklistEntry::BlockAddress(address),
+            false => BlocklistEntry::UnblockAddress(address),
+        });
+    }
+
+    value.parse::<IpRange>().ok().map(|range| match block {
+        true => BlocklistEntry::BlockRange(range),
+        false => BlocklistEntry::UnblockRange(range),
+    })
+}
+
+/// Attempt to parse blocklist entry from `value`.
+///
+/// `value` is either an IP address, a range of IP addresses in CIDR notation or a base64-encoded
+/// router hash.
+fn parse_blocklist_entry(value: &str, block: bool) -> Option<BlocklistEntry> {
+    let value = value.trim();
+
+    if let Some(entry) = parse_address_entry(value, block) {
+        return Some(entry);
+    }
+
+    match base64_decode(value) {
+        Some(hash) if hash.len() == ROUTER_HASH_LEN => Some(match block {
+            true => BlocklistEntry::BlockRouter(RouterId::from(hash)),
+            false => BlocklistEntry::UnblockRouter(RouterId::from(hash)),
+        }),
+        _ => {
+            tracing::debug!(
+                target: LOG_TARGET,
+                %value,
+                "unsupported blocklist entry, ignoring",
+            );
+            None
+        }
+    }
+}
+
+/// Attempt to parse news feed from `xml`.
+pub fn parse_feed(xml: &str) -> Option<NewsFeed> {
+    let document = roxmltree::Document::parse(xml)
+        .inspect_err(|error| {
+            tracing::warn!(
+                target: LOG_TARGET,
+                ?error,
+                "failed to parse news feed",
+            )
+        })
+        .ok()?;
+    let feed = document.root_element();
+
+    if !feed.has_tag_name((ATOM_NAMESPACE, "feed")) {
+        tracing::warn!(
+            target: LOG_TARGET,
+            tag = ?feed.tag_name(),
+            "news feed is not an atom feed",
+        );
+        return None;
+    }
+
+    let text = |node: roxmltree::Node<'_, '_>, name: &str| -> Option<String> {
+        node.children()
+            .find(|child| child.has_tag_name((ATOM_NAMESPACE, name)))
+            .and_then(|child| child.text())
+            .map(|text| text.trim().to_string())
+    };
+
+    let items = feed
+        .children()
+        .filter(|node| node.has_tag_name((ATOM_NAMESPACE, "entry")))
+        .filter_map(|entry| {
+            Some(NewsItem {
+                id: text(entry, "id")?,
+                title: text(entry, "title")?,
+                updated: text(entry, "updated").unwrap_or_default(),
+                link: entry
+                    .children()
+                    .find(|child| child.has_tag_name((ATOM_NAMESPACE, "link")))
+                    .and_then(|link| link.attribute("href"))
+                    .map(ToString::to_string),
+                summary: text(entry, "summary"),
+            })
+        })
+        .collect();
+
+    let blocklist = feed
+        .children()
+        .filter(|node| node.has_tag_name((I2P_NAMESPACE, "blocklist")))
+        .flat_map(|blocklist| blocklist.children())
+        .filter_map(|node| {
+            let block = match node.tag_name().name() {
+                "block" => true,
+                "unblock" => false,
+                _ => return None,
+            };
+
+            (node.tag_name().namespace() == Some(I2P_NAMESPACE))
+                .then(|| parse_blocklist_entry(node.text()?, block))
+                .flatten()
+        })
+        .collect();
+
+    Some(NewsFeed { blocklist, items })
+}
+
+/// Parse blocklist entries from `text`.
+///
+/// Each line contains an IP address, a range of IP addresses in CIDR notation or a base64-encoded
+/// router hash, optionally prefixed with a comment separated by a colon, e.g.,
+/// `spammer:203.0.113.0/24`. Empty lines and lines starting with `#` are ignored.
+pub fn parse_blocklist(text: &str) -> Vec<BlocklistEntry> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| match parse_address_entry(line, true) {
+            Some(entry) => Some(entry),
+            None => {
+                let (_, value) = line.split_once(':').unwrap_or(("", line));
+                parse_blocklist_entry(value, true)
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use emissary_core::crypto::base64_encode;
+
+    const FEED: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom" xmlns:i2p="http://geti2p.net/en/docs/spec/updates" xml:lang="en">
+  <id>urn:uuid:60a76c80-d399-11d9-b91C-543213999af6</id>
+  <title>I2P News</title>
+  <updated>2025-01-02T12:00:00Z</updated>
+  <i2p:release date="2025-01-01" minVersion="0.9.9" minJavaVersion="1.8">
+    <i2p:version>2.8.0</i2p:version>
+  </i2p:release>
+  <i2p:blocklist signer="test@mail.i2p" sig="abcd">
+    <i2p:block>203.0.113.1</i2p:block>
+    <i2p:block>2001:db8::1</i2p:block>
+    <i2p:block>ROUTER_HASH</i2p:block>
+    <i2p:block>invalid</i2p:block>
+    <i2p:unblock>198.51.100.1</i2p:unblock>
+  </i2p:blocklist>
+  <entry>
+    <id>urn:uuid:1</id>
+    <title>Release 2.8.0</title>
+    <updated>2025-01-01T12:00:00Z</updated>
+    <link href="http://i2p-projekt.i2p/en/blog/post/2025/01/01/2.8.0-Release"/>
+    <author><name>zzz</name></author>
+    <summary>2.8.0 released</summary>
+    <content type="xhtml"><div xmlns="http://www.w3.org/1999/xhtml"><p>Release</p></div></content>
+  </entry>
+  <entry>
+    <id>urn:uuid:2</id>
+    <title>Second entry</title>
+  </entry>
+  <entry>
+    <id>urn:uuid:3</id>
+  </entry>
+</feed>"#;
+
+    #[test]
+    fn parse_news_feed() {
+        let router_id = RouterId::from([0xaa; 32]);
+        let feed = FEED.replace("ROUTER_HASH", &base64_encode(router_id.to_vec()));
+        let NewsFeed { blocklist, items } = parse_feed(&feed).unwrap();
+
+        // entry without a title is ignored
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].id, "urn:uuid:1");
+        assert_eq!(items[0].title, "Release 2.8.0");
+        assert_eq!(items[0].updated, "2025-01-01T12:00:00Z");
+        assert_eq!(
+            items[0].link.as_deref(),
+            Some("http://i2p-projekt.i2p/en/blog/post/2025/01/01/2.8.0-Release")
+        );
+        assert_eq!(items[0].summary.as_deref(), Some("2.8.0 released"));
+        assert_eq!(items[1].title, "Second entry");
+        assert!(items[1].link.is_none());
+
+        assert_eq!(
+            blocklist,
+            vec![
+                BlocklistEntry::BlockAddress("203.0.113.1".parse().unwrap()),
+                BlocklistEntry::BlockAddress("2001:db8::1".parse().unwrap()),
+                BlocklistEntry::BlockRouter(router_id),
+                BlocklistEntry::UnblockAddress("198.51.100.1".parse().unwrap()),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_invalid_feed() {
+        assert!(parse_feed("not xml").is_none());
+        assert!(parse_feed("<rss></rss>").is_none());
+    }
+
+    #[test]
+    fn parse_blocklist_file() {
+        let router_id = RouterId::from([0xaa; 32]);
+        let text = format!(
+            "# comment\n\n203.0.113.1\nspammer:198.51.100.1\n2001:db8::1\n{}\nfoo:bar\n\
+             198.51.100.0/24\nrange:2001:db8::/32\n2001:db8::/129\n",
+            base64_encode(router_id.to_vec()),
+        );
+
+        assert_eq!(
+            parse_blocklist(&text),
+            vec![
+                BlocklistEntry::BlockAddress("203.0.113.1".parse().unwrap()),
+                BlocklistEntry::BlockAddress("198.51.100.1".parse().unwrap()),
+                BlocklistEntry::BlockAddress("2001:db8::1".parse().unwrap()),
+                BlocklistEntry::BlockRouter(router_id),
+                BlocklistEntry::BlockRange("198.51.100.0/24".parse().unwrap()),
+                BlocklistEntry::BlockRange("2001:db8::/32".parse().unwrap()),
+            ]
+        );
+    }
+}
@@ -18,18 +18,19 @@
 
 use crate::{
     certificates::{CREATIVECOWPAT_SSL, CUBICCHAOS_SSL},
-    su3::{ReseedRouterInfo, Su3},
+    su3::{self, ReseedRouterInfo, Su3, SU3_MAGIC},
 };
 
 use anyhow::anyhow;
-use rand::{thread_rng, Rng};
+use rand::{seq::SliceRandom, thread_rng};
 use reqwest::{
     header::{HeaderMap, HeaderValue, CONNECTION, USER_AGENT},
-    Certificate, ClientBuilder,
+    Certificate, ClientBuilder, Proxy,
 };
 
 use std::{
-    collections::{HashMap, HashSet},
+    collections::HashMap,
+    path::{Path, PathBuf},
     time::Duration,
 };
 
@@ -42,6 +43,9 @@ const NUM_RETRIES: usize = 5usize;
 /// How many routers should [`Reseeder`] find before terminating the process.
 const MIN_ROUTER_INFOS_TO_DOWNLOAD: usize = 100usize;
 
+/// Zip magic.
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+
 /// Reseed servers.
 const RESEED_SERVERS: &[&str] = &[
     "https://reseed.stormycloud.org/",
@@ -60,19 +64,93 @@ const RESEED_SERVERS: &[&str] = &[
     "https://cubicchaos.net:8443/",
 ];
 
+/// Reseed options.
+#[derive(Debug, Default, Clone)]
+pub struct ReseedOptions {
+    /// Local `.su3` or `.zip` file to reseed from.
+    ///
+    /// If specified, reseed servers are not contacted.
+    pub file: Option<PathBuf>,
+
+    /// Force reseeding over IPv4.
+    ///
+    /// Ignored if `proxy` is specified.
+    pub force_ipv4: bool,
+
+    /// Reseed servers.
+    ///
+    /// Either base URLs of reseed servers or full URLs of `.su3` files.
+    ///
+    /// If not specified, the default reseed servers are used.
+    pub hosts: Option<Vec<String>>,
+
+    /// HTTP or SOCKS5 proxy used to connect to reseed servers.
+    ///
+    /// For example, `http://127.0.0.1:4444` or `socks5h://127.0.0.1:9050`.
+    pub proxy: Option<String>,
+}
+
 /// HTTPS reseeder.
 pub struct Reseeder;
 
 impl Reseeder {
+    /// Create URL of the reseed file from `host`.
+    ///
+    /// If `host` already points to an `.su3` file, it's used as-is.
+    fn reseed_url(host: &str) -> String {
+        if host.ends_with(".su3") {
+            return host.to_string();
+        }
+
+        format!("{}/i2pseeds.su3", host.trim_end_matches('/'))
+    }
+
+    /// Parse local reseed file `bytes` either as a signed su3 file or as an unsigned zip file.
+    ///
+    /// Unsigned zip files are only accepted from local files, reseed servers must serve su3 files
+    /// with a valid signature.
+    fn parse_file(bytes: &[u8]) -> Option<Vec<ReseedRouterInfo>> {
+        if bytes.starts_with(SU3_MAGIC) {
+            return Su3::parse_reseed(bytes, true);
+        }
+
+        if bytes.starts_with(ZIP_MAGIC) {
+            return su3::parse_zip(bytes);
+        }
+
+        None
+    }
+
+    /// Attempt to reseed from a local `.su3` or `.zip` file.
+    fn reseed_from_file(path: &Path) -> anyhow::Result<Vec<ReseedRouterInfo>> {
+        let bytes = std::fs::read(path)
+            .map_err(|error| anyhow!("failed to read {}: {error}", path.display()))?;
+
+        match Self::parse_file(&bytes) {
+            Some(routers) if !routers.is_empty() => {
+                tracing::info!(
+                    target: LOG_TARGET,
+                    path = %path.display(),
+                    num_routers = ?routers.len(),
+                    "reseed from file succeeded",
+                );
+
+                Ok(routers)
+            }
+            _ => Err(anyhow!("{} is not a valid reseed file", path.display())),
+        }
+    }
+
     /// Attempt to reseed from `hosts` and parse response into a vector of serialized router infos.
     async fn reseed_inner(
         hosts: &[&str],
         force_ipv4: bool,
+        proxy: Option<&str>,
     ) -> anyhow::Result<Vec<ReseedRouterInfo>> {
-        let client = if force_ipv4 {
-            ClientBuilder::new().local_address("0.0.0.0:0".parse().ok())
-        } else {
-            ClientBuilder::new()
+        let client = match proxy {
+            Some(proxy) => ClientBuilder::new().proxy(Proxy::all(proxy)?),
+            None if force_ipv4 => ClientBuilder::new().local_address("0.0.0.0:0".parse().ok()),
+            None => ClientBuilder::new(),
         }
         .add_root_certificate(
             Certificate::from_pem_bundle(CREATIVECOWPAT_SSL.as_bytes())
@@ -94,42 +172,34 @@ impl Reseeder {
             (CONNECTION, HeaderValue::from_static("close")),
         ]);
 
-        // servers which have failed
-        let mut already_tried = HashSet::<usize>::new();
         let mut routers = HashMap::<String, ReseedRouterInfo>::new();
 
-        for _ in 0..NUM_RETRIES {
-            let server = loop {
-                let server = thread_rng().gen_range(0..hosts.len());
-
-                if already_tried.insert(server) {
-                    break server;
-                }
-            };
+        let servers = hosts
+            .choose_multiple(&mut thread_rng(), NUM_RETRIES)
+            .copied()
+            .collect::<Vec<_>>();
 
+        for server in servers {
             tracing::info!(
                 target: LOG_TARGET,
-                host = %hosts[server],
+                host = %server,
+                proxy = ?proxy,
                 "reseed from host"
             );
 
-            let response = match client
-                .get(format!("{}/i2pseeds.su3", hosts[server]))
-                .headers(headers.clone())
-                .send()
-                .await
-            {
-                Err(error) => {
-                    tracing::warn!(
-                        target: LOG_TARGET,
-                        server = ?hosts[server],
-                        ?error,
-                        "failed to reseed"
-                    );
-                    continue;
-                }
-                Ok(response) => response,
-            };
+            let response =
+                match client.get(Self::reseed_url(server)).headers(headers.clone()).send().await {
+                    Err(error) => {
+                        tracing::warn!(
+                            target: LOG_TARGET,
+                            server = ?server,
+                            ?error,
+                            "failed to reseed"
+                        );
+                        continue;
+                    }
+                    Ok(response) => response,
+                };
 
             if !response.status().is_success() {
                 tracing::warn!(
@@ -142,11 +212,18 @@ impl Reseeder {
 
             match response.bytes().await {
                 Ok(bytes) => match Su3::parse_reseed(&bytes, true) {
-                    None => continue,
+                    None => {
+                        tracing::warn!(
+                            target: LOG_TARGET,
+                            server = ?server,
+                            "reseed server didn't return a valid signed su3 file",
+                        );
+                        continue;
+                    }
                     Some(downloaded) => {
                         tracing::info!(
                             target: LOG_TARGET,
-                            server = ?hosts[server],
+                            server = ?server,
                             num_routers = ?downloaded.len(),
                             "reseed succeeded"
                         );
@@ -162,7 +239,7 @@ impl Reseeder {
                 Err(error) => {
                     tracing::warn!(
                         target: LOG_TARGET,
-                        server = ?hosts[server],
+                        server = ?server,
                         %error,
                         "failed to get response from reseeed server"
                     );
@@ -183,19 +260,279 @@ impl Reseeder {
         Err(anyhow!("failed to reseed"))
     }
 
-    /// Reseed from `hosts` or from `RESEED_SERVERS` if `hosts` are not specified.
-    pub async fn reseed(
-        hosts: Option<Vec<String>>,
-        force_ipv4: bool,
-    ) -> anyhow::Result<Vec<ReseedRouterInfo>> {
+    /// Reseed using `options`.
+    ///
+    /// If a reseed file is specified, routers are read from it. Otherwise they're downloaded from
+    /// the specified reseed servers or from `RESEED_SERVERS` if no servers were specified.
+    pub async fn reseed(options: ReseedOptions) -> anyhow::Result<Vec<ReseedRouterInfo>> {
+        let ReseedOptions {
+            file,
+            force_ipv4,
+            hosts,
+            proxy,
+        } = options;
+
+        if let Some(path) = file {
+            return Self::reseed_from_file(&path);
+        }
+
         match hosts {
-            None => Self::reseed_inner(RESEED_SERVERS, force_ipv4).await,
+            None => Self::reseed_inner(RESEED_SERVERS, force_ipv4, proxy.as_deref()).await,
             Some(hosts) =>
                 Self::reseed_inner(
                     &hosts.iter().map(AsRef::as_ref).collect::<Vec<_>>(),
                     force_ipv4,
+                    proxy.as_deref(),
                 )
                 .await,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+    };
+    use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+    const SU3: &[u8] = include_bytes!("../assets/i2pseeds.su3");
+
+    /// Create an unsigned zip file containing `routers`.
+    fn zip_file(routers: &[ReseedRouterInfo]) -> Vec<u8> {
+        let mut writer = ZipWriter::new(std::io::Cursor::new(Vec::new()));
+
+        for info in routers {
+            writer
+                .start_file(
+                    &info.name,
+                    SimpleFileOptions::default().compression_method(CompressionMethod::Stored),
+                )
+                .unwrap();
+            writer.write_all(&info.router_info).unwrap();
+        }
+
+        writer.finish().unwrap().into_inner()
+    }
+
+    /// Read HTTP request from `stream` and respond to it with `file`.
+    async fn serve_file(stream: &mut TcpStream, file: &[u8]) -> String {
+        let mut request = Vec::new();
+        let mut buffer = [0u8; 1024];
+
+        while !request.ends_with(b"\r\n\r\n") {
+            let nread = stream.read(&mut buffer).await.unwrap();
+            assert_ne!(nread, 0);
+            request.extend_from_slice(&buffer[..nread]);
+        }
+
+        stream
+            .write_all(
+                format!(
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    file.len()
+                )
+                .as_bytes(),
+            )
+            .await
+            .unwrap();
+        stream.write_all(file).await.unwrap();
+
+        String::from_utf8(request).unwrap().lines().next().unwrap().to_string()
+    }
+
+    #[test]
+    fn reseed_url() {
+        assert_eq!(
+            Reseeder::reseed_url("https://reseed.i2p/"),
+            "https://reseed.i2p/i2pseeds.su3"
+        );
+        assert_eq!(
+            Reseeder::reseed_url("https://reseed.i2p"),
+            "https://reseed.i2p/i2pseeds.su3"
+        );
+        assert_eq!(
+            Reseeder::reseed_url("https://reseed.i2p/custom.su3"),
+            "https://reseed.i2p/custom.su3"
+        );
+    }
+
+    #[tokio::test]
+    async fn reseed_from_su3_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("i2pseeds.su3");
+        std::fs::write(&path, SU3).unwrap();
+
+        let routers = Reseeder::reseed(ReseedOptions {
+            file: Some(path),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(routers.len(), Su3::parse_reseed(SU3, true).unwrap().len());
+    }
+
+    #[tokio::test]
+    async fn reseed_from_zip_file() {
+        let expected = Su3::parse_reseed(SU3, true).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("i2pseeds.zip");
+
+        std::fs::write(&path, zip_file(&expected)).unwrap();
+
+        let mut routers = Reseeder::reseed(ReseedOptions {
+            file: Some(path),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        routers.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut expected = expected;
+        expected.sort_by(|a, b| a.name.cmp(&b.name));
+
+        assert_eq!(routers.len(), expected.len());
+        assert!(routers
+            .iter()
+            .zip(expected.iter())
+            .all(|(a, b)| a.name == b.name && a.router_info == b.router_info));
+    }
+
+    #[tokio::test]
+    async fn reseed_from_invalid_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("i2pseeds.su3");
+
+        // file doesn't exist
+        assert!(Reseeder::reseed(ReseedOptions {
+            file: Some(path.clone()),
+            ..Default::default()
+        })
+        .await
+        .is_err());
+
+        // neither su3 nor zip
+        std::fs::write(&path, b"hello, world").unwrap();
+        assert!(Reseeder::reseed(ReseedOptions {
+            file: Some(path.clone()),
+            ..Default::default()
+        })
+        .await
+        .is_err());
+
+        // su3 with invalid signature
+        let mut bytes = SU3.to_vec();
+        let len = bytes.len();
+        bytes[len - 1] = bytes[len - 1].wrapping_add(1);
+        std::fs::write(&path, bytes).unwrap();
+
+        assert!(Reseeder::reseed(ReseedOptions {
+            file: Some(path),
+            ..Default::default()
+        })
+        .await
+        .is_err());
+    }
+
+    #[tokio::test]
+    async fn reseed_through_http_proxy() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            serve_file(&mut stream, SU3).await
+        });
+
+        let routers = Reseeder::reseed(ReseedOptions {
+            hosts: Some(vec!["http://reseed.invalid/".to_string()]),
+            proxy: Some(format!("http://{address}")),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(routers.len(), Su3::parse_reseed(SU3, true).unwrap().len());
+        assert_eq!(
+            server.await.unwrap(),
+            "GET http://reseed.invalid/i2pseeds.su3 HTTP/1.1"
+        );
+    }
+
+    #[tokio::test]
+    async fn unsigned_zip_from_server_rejected() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let zip = zip_file(&Su3::parse_reseed(SU3, true).unwrap());
+
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            serve_file(&mut stream, &zip).await
+        });
+
+        assert!(Reseeder::reseed(ReseedOptions {
+            hosts: Some(vec![format!("http://{address}/")]),
+            ..Default::default()
+        })
+        .await
+        .is_err());
+        assert_eq!(server.await.unwrap(), "GET /i2pseeds.su3 HTTP/1.1");
+    }
+
+    #[tokio::test]
+    async fn reseed_through_socks5_proxy() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+
+            // greeting, accept "no authentication"
+            let mut header = [0u8; 2];
+            stream.read_exact(&mut header).await.unwrap();
+            assert_eq!(header[0], 5);
+
+            let mut methods = vec![0u8; header[1] as usize];
+            stream.read_exact(&mut methods).await.unwrap();
+            assert!(methods.contains(&0));
+            stream.write_all(&[5, 0]).await.unwrap();
+
+            // connect request for a domain name, resolved by the proxy
+            let mut request = [0u8; 5];
+            stream.read_exact(&mut request).await.unwrap();
+            assert_eq!(request[..4], [5, 1, 0, 3]);
+
+            let mut host = vec![0u8; request[4] as usize + 2];
+            stream.read_exact(&mut host).await.unwrap();
+            let port = u16::from_be_bytes([host[host.len() - 2], host[host.len() - 1]]);
+            host.truncate(host.len() - 2);
+
+            stream.write_all(&[5, 0, 0, 1, 0, 0, 0, 0, 0, 0]).await.unwrap();
+
+            (
+                String::from_utf8(host).unwrap(),
+                port,
+                serve_file(&mut stream, SU3).await,
+            )
+        });
+
+        let routers = Reseeder::reseed(ReseedOptions {
+            hosts: Some(vec!["http://reseed.invalid:8080/custom.su3".to_string()]),
+            proxy: Some(format!("socks5h://{address}")),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(routers.len(), Su3::parse_reseed(SU3, true).unwrap().len());
+
+        let (host, port, request) = server.await.unwrap();
+        assert_eq!(host, "reseed.invalid");
+        assert_eq!(port, 8080);
+        assert_eq!(request, "GET /custom.su3 HTTP/1.1");
+    }
+}
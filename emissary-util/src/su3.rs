@@ -21,7 +21,14 @@
 // FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
 // DEALINGS IN THE SOFTWARE.
 
-use crate::certificates::PUBLIC_KEYS;
+use crate::{
+    certificates::{self, SignerKey},
+    news::{self, NewsFeed},
+};
+
+use dsa::signature::hazmat::PrehashVerifier;
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier};
+use flate2::read::GzDecoder;
 
 use nom::{
     bytes::complete::{tag, take},
@@ -30,21 +37,29 @@ use nom::{
     Err, IResult,
 };
 use rsa::{
-    sha2::{Digest, Sha512},
-    traits::SignatureScheme,
+    pkcs1::DecodeRsaPrivateKey,
+    pkcs8::DecodePrivateKey,
+    sha2::{Digest, Sha256, Sha384, Sha512},
+    traits::{PublicKeyParts, SignatureScheme},
+    Pkcs1v15Sign, RsaPrivateKey,
 };
+use sha1::Sha1;
 use tempfile::TempDir;
 
 use std::{
     fs::File,
-    io::{copy, Write},
+    io::{copy, Cursor, Read, Write},
+    time::{SystemTime, UNIX_EPOCH},
 };
 
 /// Logging target for the file.
 const LOG_TARGET: &str = "emissary::su3";
 
 /// SU3 magic.
-const SU3_MAGIC: &[u8] = b"I2Psu3";
+pub(crate) const SU3_MAGIC: &[u8] = b"I2Psu3";
+
+/// Minimum length of the version field.
+const MIN_VERSION_LEN: usize = 16usize;
 
 /// Router info.
 pub struct ReseedRouterInfo {
@@ -86,6 +101,194 @@ impl TryFrom<u16> for SignatureKind {
     }
 }
 
+impl From<&SignatureKind> for u16 {
+    fn from(value: &SignatureKind) -> Self {
+        match value {
+            SignatureKind::DsaSha1 => 0x0000,
+            SignatureKind::EcDsaSha256P256 => 0x0001,
+            SignatureKind::EcDsaSha384P384 => 0x0002,
+            SignatureKind::EcDsaSha512P521 => 0x0003,
+            SignatureKind::Rsa2048Sha256 => 0x0004,
+            SignatureKind::Rsa3072Sha384 => 0x0005,
+            SignatureKind::Rsa4096Sha512 => 0x0006,
+            SignatureKind::EdDsaSha512Ed25519ph => 0x0008,
+        }
+    }
+}
+
+/// Signing key of a reseed bundle signer.
+pub enum Su3Signer {
+    /// ECDSA P-256 signing key.
+    EcdsaP256(p256::ecdsa::SigningKey),
+
+    /// ECDSA P-384 signing key.
+    EcdsaP384(p384::ecdsa::SigningKey),
+
+    /// ECDSA P-521 signing key.
+    EcdsaP521(p521::ecdsa::SigningKey),
+
+    /// Ed25519 signing key, used to sign the SHA-512 hash of the message.
+    Ed25519ph(SigningKey),
+
+    /// RSA-2048, RSA-3072 or RSA-4096 signing key.
+    Rsa(RsaPrivateKey),
+}
+
+impl Su3Signer {
+    /// Attempt to parse signing key from a PEM-encoded private key.
+    ///
+    /// PKCS#8 is supported for all keys, SEC1 for ECDSA keys and PKCS#1 for RSA keys.
+    pub fn from_pem(pem: &str) -> anyhow::Result<Self> {
+        if let Ok(key) = SigningKey::from_pkcs8_pem(pem) {
+            return Ok(Self::Ed25519ph(key));
+        }
+
+        if let Ok(key) =
+            p256::SecretKey::from_pkcs8_pem(pem).or_else(|_| p256::SecretKey::from_sec1_pem(pem))
+        {
+            return Ok(Self::EcdsaP256(key.into()));
+        }
+
+        if let Ok(key) =
+            p384::SecretKey::from_pkcs8_pem(pem).or_else(|_| p384::SecretKey::from_sec1_pem(pem))
+        {
+            return Ok(Self::EcdsaP384(key.into()));
+        }
+
+        if let Ok(key) =
+            p521::SecretKey::from_pkcs8_pem(pem).or_else(|_| p521::SecretKey::from_sec1_pem(pem))
+        {
+            return Ok(Self::EcdsaP521(p521::ecdsa::SigningKey::from_bytes(
+                &key.to_bytes(),
+            )?));
+        }
+
+        let key = RsaPrivateKey::from_pkcs8_pem(pem)
+            .or_else(|_| RsaPrivateKey::from_pkcs1_pem(pem))
+            .map_err(|_| anyhow::anyhow!("unsupported private key"))?;
+
+        match key.size() {
+            256 | 384 | 512 => Ok(Self::Rsa(key)),
+            size => Err(anyhow::anyhow!(
+                "unsupported rsa key size: {} bits",
+                size * 8
+            )),
+        }
+    }
+
+    /// Get signature kind of the signer.
+    fn signature_kind(&self) -> SignatureKind {
+        match self {
+            Self::EcdsaP256(_) => SignatureKind::EcDsaSha256P256,
+            Self::EcdsaP384(_) => SignatureKind::EcDsaSha384P384,
+            Self::EcdsaP521(_) => SignatureKind::EcDsaSha512P521,
+            Self::Ed25519ph(_) => SignatureKind::EdDsaSha512Ed25519ph,
+            Self::Rsa(key) => match key.size() {
+                256 => SignatureKind::Rsa2048Sha256,
+                384 => SignatureKind::Rsa3072Sha384,
+                _ => SignatureKind::Rsa4096Sha512,
+            },
+        }
+    }
+
+    /// Get signature length of the signer.
+    fn signature_len(&self) -> usize {
+        match self {
+            Self::EcdsaP256(_) => 64usize,
+            Self::EcdsaP384(_) => 96usize,
+            Self::EcdsaP521(_) => 132usize,
+            Self::Ed25519ph(_) => 64usize,
+            Self::Rsa(key) => key.size(),
+        }
+    }
+
+    /// Sign `message`.
+    fn sign(&self, message: &[u8]) -> anyhow::Result<Vec<u8>> {
+        match self {
+            Self::EcdsaP256(key) => {
+                let signature: p256::ecdsa::Signature = key.sign(message);
+                Ok(signature.to_vec())
+            }
+            Self::EcdsaP384(key) => {
+                let signature: p384::ecdsa::Signature = key.sign(message);
+                Ok(signature.to_vec())
+            }
+            Self::EcdsaP521(key) => {
+                let signature: p521::ecdsa::Signature = key.sign(message);
+                Ok(signature.to_vec())
+            }
+            Self::Ed25519ph(key) => Ok(key.sign(&Sha512::digest(message)).to_vec()),
+            Self::Rsa(key) => {
+                let digest = match key.size() {
+                    256 => Sha256::digest(message).to_vec(),
+                    384 => Sha384::digest(message).to_vec(),
+                    _ => Sha512::digest(message).to_vec(),
+                };
+
+                Ok(key.sign(Pkcs1v15Sign::new_unprefixed(), &digest)?)
+            }
+        }
+    }
+}
+
+/// Verify that `signature` of `kind` over `message` was created by `key`.
+fn verify_signature(
+    kind: &SignatureKind,
+    key: &SignerKey,
+    message: &[u8],
+    signature: &[u8],
+) -> bool {
+    match (kind, key) {
+        (SignatureKind::DsaSha1, SignerKey::Dsa(key)) => {
+            if signature.len() != 40 {
+                return false;
+            }
+
+            let Ok(signature) = dsa::Signature::from_components(
+                dsa::BigUint::from_bytes_be(&signature[..20]),
+                dsa::BigUint::from_bytes_be(&signature[20..]),
+            ) else {
+                return false;
+            };
+
+            key.verify_prehash(&Sha1::digest(message), &signature).is_ok()
+        }
+        (SignatureKind::EcDsaSha256P256, SignerKey::EcdsaP256(key)) =>
+            p256::ecdsa::Signature::from_slice(signature)
+                .is_ok_and(|signature| key.verify(message, &signature).is_ok()),
+        (SignatureKind::EcDsaSha384P384, SignerKey::EcdsaP384(key)) =>
+            p384::ecdsa::Signature::from_slice(signature)
+                .is_ok_and(|signature| key.verify(message, &signature).is_ok()),
+        (SignatureKind::EcDsaSha512P521, SignerKey::EcdsaP521(key)) =>
+            p521::ecdsa::Signature::from_slice(signature)
+                .is_ok_and(|signature| key.verify(message, &signature).is_ok()),
+        // rsa signatures are over the raw digest, taken from ire
+        (SignatureKind::Rsa2048Sha256, SignerKey::Rsa(key)) if key.size() == 256 =>
+            Pkcs1v15Sign::new_unprefixed()
+                .verify(key, &Sha256::digest(message), signature)
+                .is_ok(),
+        (SignatureKind::Rsa3072Sha384, SignerKey::Rsa(key)) if key.size() == 384 =>
+            Pkcs1v15Sign::new_unprefixed()
+                .verify(key, &Sha384::digest(message), signature)
+                .is_ok(),
+        (SignatureKind::Rsa4096Sha512, SignerKey::Rsa(key)) if key.size() == 512 =>
+            Pkcs1v15Sign::new_unprefixed()
+                .verify(key, &Sha512::digest(message), signature)
+                .is_ok(),
+        (SignatureKind::EdDsaSha512Ed25519ph, SignerKey::Ed25519(key)) =>
+            Signature::from_slice(signature)
+                .is_ok_and(|signature| key.verify(&Sha512::digest(message), &signature).is_ok()),
+        (kind, _) => {
+            tracing::warn!(
+                target: LOG_TARGET,
+                ?kind,
+                "signature kind doesn't match the key of the signer",
+            );
+            false
+        }
+    }
+}
+
 /// File kind.
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum FileKind {
@@ -115,6 +318,20 @@ impl TryFrom<u8> for FileKind {
     }
 }
 
+impl From<&FileKind> for u8 {
+    fn from(value: &FileKind) -> Self {
+        match value {
+            FileKind::Zip => 0x00,
+            FileKind::Xml => 0x01,
+            FileKind::Html => 0x02,
+            FileKind::XmlGz => 0x03,
+            FileKind::TxtGz => 0x04,
+            FileKind::Dmg => 0x05,
+            FileKind::Exe => 0x06,
+        }
+    }
+}
+
 /// Content kind.
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ContentKind {
@@ -143,6 +360,27 @@ impl TryFrom<u8> for ContentKind {
     }
 }
 
+impl From<&ContentKind> for u8 {
+    fn from(value: &ContentKind) -> Self {
+        match value {
+            ContentKind::Unknown => 0x00,
+            ContentKind::RouterUpdate => 0x01,
+            ContentKind::PluginUpdate => 0x02,
+            ContentKind::ReseedData => 0x03,
+            ContentKind::NewsFeed => 0x04,
+            ContentKind::BlocklistFeed => 0x05,
+        }
+    }
+}
+
+/// Attempt to decompress gzipped `input`.
+fn gunzip(input: &[u8]) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    GzDecoder::new(input).read_to_end(&mut out).ok()?;
+
+    Some(out)
+}
+
 /// Software update.
 #[allow(unused)]
 pub struct Su3<'a> {
@@ -229,98 +467,296 @@ impl<'a> Su3<'a> {
         ))
     }
 
+    /// Verify the signature of the su3 file.
+    ///
+    /// `public_key` is used to look up the public key of the signer.
+    fn verify(&self, public_key: impl Fn(&str) -> Option<SignerKey>) -> bool {
+        let Ok(signer_id) = std::str::from_utf8(self.signer_id) else {
+            tracing::warn!(
+                target: LOG_TARGET,
+                "invalid signer id",
+            );
+            return false;
+        };
+
+        let Some(key) = public_key(signer_id) else {
+            tracing::warn!(
+                target: LOG_TARGET,
+                ?signer_id,
+                "public key for signer id not found",
+            );
+            return false;
+        };
+
+        if !verify_signature(&self.signature_kind, &key, self.message, self.signature) {
+            tracing::warn!(
+                target: LOG_TARGET,
+                %signer_id,
+                kind = ?self.signature_kind,
+                "invalid signature",
+            );
+            return false;
+        }
+
+        true
+    }
+
     /// Attempt to parse reseed data from `input`.
     pub fn parse_reseed(input: &'a [u8], verify: bool) -> Option<Vec<ReseedRouterInfo>> {
         let (_, su3) = Self::parse_inner(input).ok()?;
 
-        if verify {
-            let Ok(signer_id) = std::str::from_utf8(su3.signer_id) else {
-                tracing::warn!(
-                    target: LOG_TARGET,
-                    "invalid signer id",
-                );
-                return None;
-            };
+        if verify && !su3.verify(certificates::public_key) {
+            return None;
+        }
 
-            let SignatureKind::Rsa4096Sha512 = su3.signature_kind else {
+        match (su3.file_kind, su3.content_kind) {
+            (FileKind::Zip, ContentKind::ReseedData) => {}
+            (file_kind, content_kind) => {
                 tracing::warn!(
                     target: LOG_TARGET,
-                    %signer_id,
-                    kind = ?su3.signature_kind,
-                    "signature kind not supported",
+                    ?file_kind,
+                    ?content_kind,
+                    "failed to parse reseed data, invalid file/content kind",
                 );
                 return None;
-            };
+            }
+        }
 
-            let Some(key) = PUBLIC_KEYS.get(signer_id) else {
-                tracing::warn!(
-                    target: LOG_TARGET,
-                    ?signer_id,
-                    "public key for signer id not found",
-                );
-                return None;
-            };
+        parse_zip(su3.content)
+    }
 
-            // taken from ire
-            rsa::Pkcs1v15Sign::new_unprefixed()
-                .verify(key, &Sha512::digest(su3.message), su3.signature)
-                .ok()?;
+    /// Attempt to parse news feed from `input`.
+    ///
+    /// The news feed must be signed by a signer trusted with
+    /// [`certificates::add_trusted_news_certificate()`].
+    ///
+    /// Returns the version of the su3 file, the time the news feed was signed at in seconds since
+    /// UNIX epoch, and the news feed.
+    pub fn parse_news(input: &'a [u8]) -> Option<(u64, NewsFeed)> {
+        let (_, su3) = Self::parse_inner(input).ok()?;
+
+        if !su3.verify(certificates::news_public_key) {
+            return None;
         }
 
-        match (su3.file_kind, su3.content_kind) {
-            (FileKind::Zip, ContentKind::ReseedData) => {}
+        let Some(version) = std::str::from_utf8(su3.version)
+            .ok()
+            .and_then(|version| version.trim_end_matches('\0').parse::<u64>().ok())
+        else {
+            tracing::warn!(
+                target: LOG_TARGET,
+                version = ?su3.version,
+                "failed to parse news feed, invalid version",
+            );
+            return None;
+        };
+
+        let xml = match (su3.file_kind, su3.content_kind) {
+            (FileKind::Xml, ContentKind::NewsFeed) => su3.content.to_vec(),
+            (FileKind::XmlGz, ContentKind::NewsFeed) => gunzip(su3.content)?,
             (file_kind, content_kind) => {
                 tracing::warn!(
                     target: LOG_TARGET,
                     ?file_kind,
                     ?content_kind,
-                    "failed to parse reseed data, invalid file/content kind",
+                    "failed to parse news feed, invalid file/content kind",
                 );
                 return None;
             }
+        };
+
+        Some((version, news::parse_feed(std::str::from_utf8(&xml).ok()?)?))
+    }
+
+    /// Create signed reseed bundle from `router_infos`.
+    ///
+    /// `signer_id` must match the common name of the certificate corresponding to `signer`.
+    pub fn create_reseed(
+        signer_id: &str,
+        signer: &Su3Signer,
+        router_infos: &[ReseedRouterInfo],
+    ) -> anyhow::Result<Vec<u8>> {
+        Self::create(
+            signer_id,
+            signer,
+            FileKind::Zip,
+            ContentKind::ReseedData,
+            &create_zip(router_infos)?,
+        )
+    }
+
+    /// Create signed su3 file from `content`.
+    fn create(
+        signer_id: &str,
+        signer: &Su3Signer,
+        file_kind: FileKind,
+        content_kind: ContentKind,
+        content: &[u8],
+    ) -> anyhow::Result<Vec<u8>> {
+        let mut out = Self::create_unsigned(
+            signer_id,
+            &signer.signature_kind(),
+            signer.signature_len(),
+            file_kind,
+            content_kind,
+            content,
+        )?;
+
+        let signature = signer.sign(&out)?;
+        out.extend_from_slice(&signature);
+
+        Ok(out)
+    }
+
+    /// Create the signed part of an su3 file.
+    fn create_unsigned(
+        signer_id: &str,
+        signature_kind: &SignatureKind,
+        signature_len: usize,
+        file_kind: FileKind,
+        content_kind: ContentKind,
+        content: &[u8],
+    ) -> anyhow::Result<Vec<u8>> {
+        let mut version = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs()
+            .to_string()
+            .into_bytes();
+        version.resize(MIN_VERSION_LEN.max(version.len()), 0u8);
+
+        if signer_id.len() > u8::MAX as usize {
+            return Err(anyhow::anyhow!("signer id is too long"));
         }
 
-        let temp_dir = TempDir::new().ok()?;
-        let mut zip_file = File::create_new(temp_dir.path().join("routers.zip")).ok()?;
-        File::write_all(&mut zip_file, su3.content).ok()?;
-
-        let mut archive = zip::ZipArchive::new(zip_file).ok()?;
-        let router_infos = (0..archive.len())
-            .filter_map(|i| {
-                let mut file = archive.by_index(i).expect("to exist");
-                let outpath = file.enclosed_name()?;
-
-                if !file.is_file() {
-                    tracing::warn!(
-                        target: LOG_TARGET,
-                        "non-file encountered in router info, ignoring",
-                    );
-                    return None;
-                }
-
-                let mut router_info = Vec::new();
-                copy(&mut file, &mut router_info).ok()?;
-
-                Some(ReseedRouterInfo {
-                    name: outpath.display().to_string(),
-                    router_info,
-                })
-            })
-            .collect::<Vec<_>>();
+        let mut out = Vec::with_capacity(
+            40 + version.len() + signer_id.len() + content.len() + signature_len,
+        );
+
+        out.extend_from_slice(SU3_MAGIC);
+        out.push(0u8); // unused
+        out.push(0u8); // su3 file format version
+        out.extend_from_slice(&u16::from(signature_kind).to_be_bytes());
+        out.extend_from_slice(&(signature_len as u16).to_be_bytes());
+        out.push(0u8); // unused
+        out.push(version.len() as u8);
+        out.push(0u8); // unused
+        out.push(signer_id.len() as u8);
+        out.extend_from_slice(&(content.len() as u64).to_be_bytes());
+        out.push(0u8); // unused
+        out.push(u8::from(&file_kind));
+        out.push(0u8); // unused
+        out.push(u8::from(&content_kind));
+        out.extend_from_slice(&[0u8; 12]); // unused
+        out.extend_from_slice(&version);
+        out.extend_from_slice(signer_id.as_bytes());
+        out.extend_from_slice(content);
+
+        Ok(out)
+    }
+}
 
-        drop(archive);
-        temp_dir.close().ok()?;
+/// Create reseed data, a zip archive of router info files, from `router_infos`.
+pub fn create_zip(router_infos: &[ReseedRouterInfo]) -> anyhow::Result<Vec<u8>> {
+    let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    let options = zip::write::SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated);
 
-        Some(router_infos)
+    for ReseedRouterInfo { name, router_info } in router_infos {
+        writer.start_file(name.as_str(), options)?;
+        writer.write_all(router_info)?;
     }
+
+    Ok(writer.finish()?.into_inner())
+}
+
+/// Attempt to parse router infos from reseed data, a zip archive of router info files.
+pub fn parse_zip(input: &[u8]) -> Option<Vec<ReseedRouterInfo>> {
+    let temp_dir = TempDir::new().ok()?;
+    let mut zip_file = File::create_new(temp_dir.path().join("routers.zip")).ok()?;
+    File::write_all(&mut zip_file, input).ok()?;
+
+    let mut archive = zip::ZipArchive::new(zip_file).ok()?;
+    let router_infos = (0..archive.len())
+        .filter_map(|i| {
+            let mut file = archive.by_index(i).expect("to exist");
+            let outpath = file.enclosed_name()?;
+
+            if !file.is_file() {
+                tracing::warn!(
+                    target: LOG_TARGET,
+                    "non-file encountered in router info, ignoring",
+                );
+                return None;
+            }
+
+            let mut router_info = Vec::new();
+            copy(&mut file, &mut router_info).ok()?;
+
+            Some(ReseedRouterInfo {
+                name: outpath.display().to_string(),
+                router_info,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    drop(archive);
+    temp_dir.close().ok()?;
+
+    Some(router_infos)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use emissary_core::BlocklistEntry;
 
     const SU3: &'static [u8] = include_bytes!("../assets/i2pseeds.su3");
 
+    /// Certificate and private key of a test signer.
+    macro_rules! signer {
+        ($name:literal) => {
+            (
+                include_str!(concat!("../assets/test/test-", $name, "_at_mail.i2p.crt")),
+                include_str!(concat!("../assets/test/test-", $name, "_at_mail.i2p.key")),
+            )
+        };
+    }
+
+    fn router_infos() -> Vec<ReseedRouterInfo> {
+        (0..10)
+            .map(|i| ReseedRouterInfo {
+                name: format!("routerInfo-{i}.dat"),
+                router_info: vec![i as u8; 512],
+            })
+            .collect()
+    }
+
+    fn create_and_parse((cert, key): (&str, &str), kind: SignatureKind) {
+        let signer_id = certificates::add_trusted_certificate(cert).unwrap();
+        let signer = Su3Signer::from_pem(key).unwrap();
+        assert_eq!(signer.signature_kind(), kind);
+
+        let bundle = Su3::create_reseed(&signer_id, &signer, &router_infos()).unwrap();
+        assert_parses(bundle);
+    }
+
+    fn assert_parses(bundle: Vec<u8>) {
+        let mut parsed = Su3::parse_reseed(&bundle, true).unwrap();
+        parsed.sort_by(|a, b| a.name.cmp(&b.name));
+
+        assert_eq!(parsed.len(), 10);
+        assert!(parsed
+            .iter()
+            .zip(router_infos().iter())
+            .all(|(a, b)| a.name == b.name && a.router_info == b.router_info));
+
+        // tampered bundle is rejected
+        let mut tampered = bundle.clone();
+        tampered[60] = tampered[60].wrapping_add(1);
+        assert!(Su3::parse_reseed(&tampered, true).is_none());
+    }
+
     #[test]
     fn parse_su3() {
         assert!(Su3::parse_reseed(SU3, true).is_some());
@@ -345,4 +781,163 @@ mod tests {
 
         assert!(Su3::parse_reseed(&bytes, false).is_some());
     }
+
+    #[test]
+    fn create_and_parse_rsa2048() {
+        create_and_parse(signer!("rsa2048"), SignatureKind::Rsa2048Sha256);
+    }
+
+    #[test]
+    fn create_and_parse_rsa3072() {
+        create_and_parse(signer!("rsa3072"), SignatureKind::Rsa3072Sha384);
+    }
+
+    #[test]
+    fn create_and_parse_rsa4096() {
+        create_and_parse(signer!("rsa4096"), SignatureKind::Rsa4096Sha512);
+    }
+
+    #[test]
+    fn create_and_parse_ecdsa_p256() {
+        create_and_parse(signer!("p256"), SignatureKind::EcDsaSha256P256);
+    }
+
+    #[test]
+    fn create_and_parse_ecdsa_p384() {
+        create_and_parse(signer!("p384"), SignatureKind::EcDsaSha384P384);
+    }
+
+    #[test]
+    fn create_and_parse_ecdsa_p521() {
+        create_and_parse(signer!("p521"), SignatureKind::EcDsaSha512P521);
+    }
+
+    #[test]
+    fn create_and_parse_ed25519ph() {
+        create_and_parse(signer!("ed25519"), SignatureKind::EdDsaSha512Ed25519ph);
+    }
+
+    #[test]
+    fn parse_dsa_sha1() {
+        use dsa::pkcs8::DecodePrivateKey;
+
+        let (cert, key) = signer!("dsa");
+        let signer_id = certificates::add_trusted_certificate(cert).unwrap();
+        let key = dsa::SigningKey::from_pkcs8_pem(key).unwrap();
+
+        // dsa is only supported for verification
+        let mut bundle = Su3::create_unsigned(
+            &signer_id,
+            &SignatureKind::DsaSha1,
+            40,
+            FileKind::Zip,
+            ContentKind::ReseedData,
+            &create_zip(&router_infos()).unwrap(),
+        )
+        .unwrap();
+
+        let signature = key.sign_prehashed_rfc6979::<Sha1>(&Sha1::digest(&bundle)).unwrap();
+        let (r, s) = (signature.r().to_bytes_be(), signature.s().to_bytes_be());
+
+        bundle.extend(core::iter::repeat_n(0u8, 20 - r.len()).chain(r));
+        bundle.extend(core::iter::repeat_n(0u8, 20 - s.len()).chain(s));
+
+        assert_parses(bundle);
+    }
+
+    #[test]
+    fn signature_kind_must_match_key() {
+        let (cert, _) = signer!("p256");
+        let (_, key) = signer!("p384");
+        let signer_id = certificates::add_trusted_certificate(cert).unwrap();
+        let signer = Su3Signer::from_pem(key).unwrap();
+
+        let bundle = Su3::create_reseed(&signer_id, &signer, &router_infos()).unwrap();
+        assert!(Su3::parse_reseed(&bundle, true).is_none());
+    }
+
+    #[test]
+    fn create_with_untrusted_signer() {
+        let signer = Su3Signer::from_pem(signer!("ed25519").1).unwrap();
+        let bundle = Su3::create_reseed("untrusted@mail.i2p", &signer, &router_infos()).unwrap();
+
+        assert!(Su3::parse_reseed(&bundle, true).is_none());
+        assert_eq!(Su3::parse_reseed(&bundle, false).unwrap().len(), 10);
+    }
+
+    #[test]
+    fn invalid_signer() {
+        assert!(Su3Signer::from_pem(signer!("rsa4096").0).is_err());
+        assert!(Su3Signer::from_pem(signer!("dsa").1).is_err());
+        assert!(certificates::add_trusted_certificate(signer!("ed25519").1).is_err());
+    }
+
+    /// Gzip `input`.
+    fn gzip(input: &[u8]) -> Vec<u8> {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), Default::default());
+        encoder.write_all(input).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn create_and_parse_news() {
+        let (cert, key) = signer!("ed25519");
+        let signer_id = certificates::add_trusted_news_certificate(cert).unwrap();
+        let signer = Su3Signer::from_pem(key).unwrap();
+        let feed = r#"<feed xmlns="http://www.w3.org/2005/Atom"
+              xmlns:i2p="http://geti2p.net/en/docs/spec/updates">
+            <i2p:blocklist><i2p:block>203.0.113.1</i2p:block></i2p:blocklist>
+            <entry><id>urn:uuid:1</id><title>title</title></entry>
+        </feed>"#;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+
+        for (file_kind, content) in [
+            (FileKind::Xml, feed.as_bytes().to_vec()),
+            (FileKind::XmlGz, gzip(feed.as_bytes())),
+        ] {
+            let news = Su3::create(
+                &signer_id,
+                &signer,
+                file_kind,
+                ContentKind::NewsFeed,
+                &content,
+            )
+            .unwrap();
+            let (version, NewsFeed { blocklist, items }) = Su3::parse_news(&news).unwrap();
+
+            assert!(version.abs_diff(now) <= 5);
+
+            assert_eq!(items.len(), 1);
+            assert_eq!(items[0].title, "title");
+            assert_eq!(
+                blocklist,
+                vec![BlocklistEntry::BlockAddress("203.0.113.1".parse().unwrap())]
+            );
+
+            // news feed is not a reseed bundle
+            assert!(Su3::parse_reseed(&news, false).is_none());
+        }
+
+        // reseed bundle is not a news feed
+        let bundle = Su3::create_reseed(&signer_id, &signer, &router_infos()).unwrap();
+        assert!(Su3::parse_news(&bundle).is_none());
+    }
+
+    #[test]
+    fn news_signer_must_be_trusted() {
+        // signers trusted for reseed bundles are not trusted for news feeds
+        let (cert, key) = signer!("rsa3072");
+        let signer_id = certificates::add_trusted_certificate(cert).unwrap();
+        let signer = Su3Signer::from_pem(key).unwrap();
+        let news = Su3::create(
+            &signer_id,
+            &signer,
+            FileKind::Xml,
+            ContentKind::NewsFeed,
+            br#"<feed xmlns="http://www.w3.org/2005/Atom"></feed>"#,
+        )
+        .unwrap();
+
+        assert!(Su3::parse_news(&news).is_none());
+    }
 }